@@ -3,23 +3,31 @@
 //! 用法:
 //!   antigravity-server [OPTIONS]
 //!
-//! OPTIONS:
-//!   --port <PORT>           API 服务端口 (默认: 8765)
-//!   --static-dir <PATH>     前端静态文件目录 (默认: ./dist)
-//!   --data-dir <PATH>       数据目录 (默认: ~/.antigravity)
-//!   --host <HOST>           绑定地址 (默认: 0.0.0.0)
+//! 运行 `antigravity-server --help` 查看全部选项。未指定 --static-dir 时使用
+//! 编译内嵌的前端 (embed-frontend feature)，否则读取 ./dist。
 
 use axum::{
-    http::{header, Method, StatusCode},
-    response::IntoResponse,
+    extract::Request,
+    http::{header, HeaderValue, Method, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+    routing::get,
     Router,
 };
 
-use std::net::SocketAddr;
-use std::path::PathBuf;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 use tower_http::{
+    compression::{
+        predicate::{DefaultPredicate, NotForContentType, Predicate},
+        CompressionLayer,
+    },
     cors::{Any, CorsLayer},
     services::ServeDir,
     trace::TraceLayer,
@@ -28,112 +36,582 @@ use tracing::{info, error, warn, debug};
 use socket2::TcpKeepalive;
 
 // 导入库中的模块
-use antigravity_tools_lib::modules::logger;
-use antigravity_tools_lib::web_api::{create_api_router, WebApiState};
+use antigravity_tools_lib::modules::{self, logger};
+use antigravity_tools_lib::proxy::config::listeners_conflict;
+use antigravity_tools_lib::web_api::{self, create_api_router, health_check, WebApiState};
+
+/// 版本号 + git commit，用于 --version
+const VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), " (", env!("GIT_HASH"), ")");
+
+/// 退出码：启动失败 (数据迁移、PID 文件等)
+const EXIT_STARTUP: i32 = 1;
+/// 退出码：参数或配置错误
+const EXIT_CONFIG: i32 = 2;
+/// 退出码：监听端口绑定失败 (端口被占用、无权限等)
+const EXIT_BIND: i32 = 3;
+/// --port-auto 时在 --port 之后尝试的端口数
+const PORT_AUTO_ATTEMPTS: u16 = 10;
 
 /// 命令行参数
+///
+/// 每个选项都可通过对应的 `ANTIGRAVITY_*` 环境变量设置，也可写入 --config 指定的配置文件。
+/// 合并优先级见 [`ServerConfig::resolve`]
+#[derive(Parser, Debug)]
+#[command(
+    name = "antigravity-server",
+    version = VERSION,
+    about = "Antigravity Manager - Web Server Mode",
+    disable_help_flag = true,
+    after_help = "示例:\n  antigravity-server --port 8080 --static-dir ./web\n  antigravity-server -p 9000 -d /data/antigravity\n  antigravity-server --profile work\n  antigravity-server --base-path /antigravity"
+)]
 struct Args {
+    /// API 服务端口
+    #[arg(
+        short, long, env = "ANTIGRAVITY_PORT", hide_env_values = true,
+        default_value_t = 8765, value_parser = clap::value_parser!(u16).range(1..)
+    )]
     port: u16,
-    host: String,
-    static_dir: PathBuf,
+
+    /// 端口被占用时依次尝试后续 10 个端口
+    #[arg(long, env = "ANTIGRAVITY_PORT_AUTO", hide_env_values = true)]
+    port_auto: bool,
+
+    /// 绑定地址
+    #[arg(short = 'h', long, env = "ANTIGRAVITY_HOST", hide_env_values = true, default_value = "0.0.0.0")]
+    host: IpAddr,
+
+    /// 管理 API 单独监听的地址 (仅指定 --admin-port 时默认 127.0.0.1)
+    #[arg(long, env = "ANTIGRAVITY_ADMIN_HOST", hide_env_values = true, value_name = "HOST")]
+    admin_host: Option<IpAddr>,
+
+    /// 管理 API 单独监听的端口，指定后 --host/--port 只提供健康检查
+    #[arg(
+        long, env = "ANTIGRAVITY_ADMIN_PORT", hide_env_values = true,
+        value_name = "PORT", value_parser = clap::value_parser!(u16).range(1..)
+    )]
+    admin_port: Option<u16>,
+
+    /// 前端静态文件目录
+    #[arg(
+        short, long, env = "ANTIGRAVITY_STATIC_DIR", hide_env_values = true,
+        value_name = "PATH", value_parser = existing_dir
+    )]
+    static_dir: Option<PathBuf>,
+
+    /// 数据目录 (默认: ~/.antigravity_tools)
+    #[arg(short, long, env = "ANTIGRAVITY_DATA_DIR", hide_env_values = true, value_name = "PATH")]
     data_dir: Option<PathBuf>,
+
+    /// 使用 ~/.antigravity_tools/profiles/<NAME> 作为数据目录
+    #[arg(
+        long, env = "ANTIGRAVITY_PROFILE", hide_env_values = true,
+        value_name = "NAME", value_parser = parse_profile
+    )]
+    profile: Option<String>,
+
+    /// 关闭响应压缩 (gzip/brotli)
+    #[arg(long, env = "ANTIGRAVITY_NO_COMPRESSION", hide_env_values = true)]
+    no_compression: bool,
+
+    /// 子路径挂载前缀
+    #[arg(
+        long, env = "ANTIGRAVITY_BASE_PATH", hide_env_values = true,
+        value_name = "PATH", default_value = "/", value_parser = parse_base_path
+    )]
+    base_path: String,
+
+    /// 种子账号文件 (JSON 数组)，启动时导入其中的账号
+    #[arg(long, env = "ANTIGRAVITY_SEED_ACCOUNTS", hide_env_values = true, value_name = "PATH")]
+    seed_accounts_file: Option<PathBuf>,
+
+    /// PID 文件路径，启动时写入，正常退出时删除
+    #[arg(long, env = "ANTIGRAVITY_PID_FILE", hide_env_values = true, value_name = "PATH")]
+    pid_file: Option<PathBuf>,
+
+    /// 管理 API 白名单 (CIDR，逗号分隔)
+    #[arg(
+        long = "allow-ip", env = "ANTIGRAVITY_ALLOWED_IPS", hide_env_values = true,
+        value_name = "CIDR", value_delimiter = ',', value_parser = parse_cidr
+    )]
+    allowed_ips: Vec<String>,
+
+    /// 可信反向代理 (CIDR，逗号分隔)
+    #[arg(
+        long = "trust-proxy", env = "ANTIGRAVITY_TRUSTED_PROXIES", hide_env_values = true,
+        value_name = "CIDR", value_delimiter = ',', value_parser = parse_cidr
+    )]
+    trusted_proxies: Vec<String>,
+
+    /// 服务端配置文件 (TOML/YAML)
+    #[arg(long, env = "ANTIGRAVITY_CONFIG", hide_env_values = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// 打印合并后的配置并退出
+    #[arg(long)]
+    #[allow(dead_code)]
+    print_config: bool,
+
+    /// 显示帮助信息
+    #[arg(long, action = clap::ArgAction::Help)]
+    #[allow(dead_code)]
+    help: Option<bool>,
 }
 
-impl Args {
-    fn parse() -> Self {
-        let mut args = std::env::args().skip(1);
-        let mut port = 8765u16;
-        let mut host = "0.0.0.0".to_string();
-        let mut static_dir = PathBuf::from("./dist");
-        let mut data_dir: Option<PathBuf> = None;
+/// 校验 --static-dir 指向已存在的目录
+fn existing_dir(value: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(value);
+    if path.is_dir() {
+        Ok(path)
+    } else {
+        Err(format!("静态文件目录不存在: {}", path.display()))
+    }
+}
 
-        while let Some(arg) = args.next() {
-            match arg.as_str() {
-                "--port" | "-p" => {
-                    if let Some(val) = args.next() {
-                        port = val.parse().unwrap_or(8765);
-                    }
-                }
-                "--host" | "-h" => {
-                    if let Some(val) = args.next() {
-                        host = val;
-                    }
-                }
-                "--static-dir" | "-s" => {
-                    if let Some(val) = args.next() {
-                        static_dir = PathBuf::from(val);
-                    }
-                }
-                "--data-dir" | "-d" => {
-                    if let Some(val) = args.next() {
-                        data_dir = Some(PathBuf::from(val));
-                    }
-                }
-                "--help" => {
-                    print_help();
-                    std::process::exit(0);
-                }
-                _ => {}
+fn parse_cidr(value: &str) -> Result<String, String> {
+    value
+        .parse::<antigravity_tools_lib::proxy::ip_filter::IpNet>()
+        .map(|_| value.trim().to_string())
+}
+
+fn parse_profile(value: &str) -> Result<String, String> {
+    modules::account::validate_profile_name(value).map(|_| value.to_string())
+}
+
+fn parse_base_path(value: &str) -> Result<String, String> {
+    Ok(normalize_base_path(value))
+}
+
+/// 配置文件内容，所有字段均可省略
+#[derive(Debug, Default, Deserialize)]
+struct FileConfig {
+    port: Option<u16>,
+    port_auto: Option<bool>,
+    host: Option<IpAddr>,
+    admin_host: Option<IpAddr>,
+    admin_port: Option<u16>,
+    static_dir: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+    profile: Option<String>,
+    no_compression: Option<bool>,
+    base_path: Option<String>,
+    pid_file: Option<PathBuf>,
+    seed_accounts_file: Option<PathBuf>,
+    allowed_ips: Option<Vec<String>>,
+    trusted_proxies: Option<Vec<String>>,
+    /// 未识别的键只告警不报错，便于新旧版本共用同一份配置文件
+    #[serde(flatten)]
+    unknown: BTreeMap<String, serde::de::IgnoredAny>,
+}
+
+impl FileConfig {
+    /// 按扩展名解析 TOML (默认) 或 YAML 配置文件，并做与命令行相同的校验
+    fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("读取配置文件 {} 失败: {}", path.display(), e))?;
+
+        let mut config: FileConfig = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml") | Some("yml") => serde_yaml::from_str(&content)
+                .map_err(|e| format!("解析配置文件 {} 失败: {}", path.display(), e))?,
+            _ => toml::from_str(&content)
+                .map_err(|e| format!("解析配置文件 {} 失败: {}", path.display(), e))?,
+        };
+
+        if config.port == Some(0) {
+            return Err(format!("配置文件 {} 中的 port 必须在 1-65535 之间", path.display()));
+        }
+        if config.admin_port == Some(0) {
+            return Err(format!("配置文件 {} 中的 admin_port 必须在 1-65535 之间", path.display()));
+        }
+        if let Some(dir) = &config.static_dir {
+            existing_dir(&dir.to_string_lossy())?;
+        }
+        if let Some(name) = &config.profile {
+            parse_profile(name).map_err(|e| format!("配置文件 {} 中 {}", path.display(), e))?;
+        }
+        config.base_path = config.base_path.as_deref().map(normalize_base_path);
+        for cidr in config.allowed_ips.iter().chain(config.trusted_proxies.iter()).flatten() {
+            parse_cidr(cidr).map_err(|e| format!("配置文件 {} 中 {}", path.display(), e))?;
+        }
+
+        Ok(config)
+    }
+}
+
+/// 合并后的服务端配置
+#[derive(Debug, Serialize)]
+struct ServerConfig {
+    port: u16,
+    port_auto: bool,
+    host: IpAddr,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    admin_host: Option<IpAddr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    admin_port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    static_dir: Option<PathBuf>,
+    /// 仅记录显式指定的目录 (--data-dir / 配置文件)，ANTIGRAVITY_DATA_DIR 由解析顺序处理
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data_dir: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    profile: Option<String>,
+    no_compression: bool,
+    base_path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pid_file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed_accounts_file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    allowed_ips: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    trusted_proxies: Vec<String>,
+}
+
+/// 单个选项最终取值的来源
+enum ConfigLayer {
+    Env,
+    Cli,
+    FileOrDefault,
+}
+
+impl ServerConfig {
+    /// 合并各层配置，优先级: 环境变量 > 命令行 > 配置文件 > 默认值
+    ///
+    /// `cli_matches` 为完整命令行的解析结果；另外以空命令行重新解析一次，
+    /// 得到仅由环境变量决定的取值。返回值附带需要在日志初始化后输出的告警。
+    fn resolve(cli_matches: &ArgMatches) -> Result<(Self, Vec<String>), String> {
+        let env_matches = Args::command()
+            .try_get_matches_from(["antigravity-server"])
+            .map_err(|e| e.to_string())?;
+        let cli = Args::from_arg_matches(cli_matches).map_err(|e| e.to_string())?;
+        let file = match &cli.config {
+            Some(path) => FileConfig::load(path)?,
+            None => FileConfig::default(),
+        };
+        Self::merge(cli_matches, &env_matches, file)
+    }
+
+    fn merge(
+        cli_matches: &ArgMatches,
+        env_matches: &ArgMatches,
+        file: FileConfig,
+    ) -> Result<(Self, Vec<String>), String> {
+        let cli = Args::from_arg_matches(cli_matches).map_err(|e| e.to_string())?;
+        let env = Args::from_arg_matches(env_matches).map_err(|e| e.to_string())?;
+
+        let layer = |id: &str| {
+            if env_matches.value_source(id) == Some(ValueSource::EnvVariable) {
+                ConfigLayer::Env
+            } else if cli_matches.value_source(id) == Some(ValueSource::CommandLine) {
+                ConfigLayer::Cli
+            } else {
+                ConfigLayer::FileOrDefault
             }
+        };
+
+        macro_rules! merge {
+            ($field:ident) => {
+                match layer(stringify!($field)) {
+                    ConfigLayer::Env => env.$field,
+                    ConfigLayer::Cli => cli.$field,
+                    ConfigLayer::FileOrDefault => file.$field.map(Into::into).unwrap_or(cli.$field),
+                }
+            };
         }
 
-        Self {
-            port,
-            host,
-            static_dir,
-            data_dir,
+        let warnings = file
+            .unknown
+            .keys()
+            .map(|key| format!("配置文件中存在未知配置项 `{}`，已忽略", key))
+            .collect();
+
+        let config = Self {
+            port: merge!(port),
+            port_auto: merge!(port_auto),
+            host: merge!(host),
+            admin_host: merge!(admin_host),
+            admin_port: merge!(admin_port),
+            static_dir: merge!(static_dir),
+            // 数据目录不参与环境变量优先: --data-dir > 配置文件 > (profile > ANTIGRAVITY_DATA_DIR)
+            data_dir: match cli_matches.value_source("data_dir") {
+                Some(ValueSource::CommandLine) => cli.data_dir,
+                _ => file.data_dir,
+            },
+            profile: merge!(profile),
+            no_compression: merge!(no_compression),
+            base_path: merge!(base_path),
+            pid_file: merge!(pid_file),
+            seed_accounts_file: merge!(seed_accounts_file),
+            allowed_ips: merge!(allowed_ips),
+            trusted_proxies: merge!(trusted_proxies),
+        };
+        Ok((config, warnings))
+    }
+
+    /// 单独的管理 API 监听地址，未指定 --admin-host/--admin-port 时为空
+    fn admin_addr(&self) -> Option<SocketAddr> {
+        if self.admin_host.is_none() && self.admin_port.is_none() {
+            return None;
+        }
+        let host = self.admin_host.unwrap_or(IpAddr::V4(std::net::Ipv4Addr::LOCALHOST));
+        Some(SocketAddr::new(host, self.admin_port.unwrap_or(self.port)))
+    }
+
+    /// 公共监听与管理 API 监听地址，两者争用同一端口时报错
+    fn listen_addrs(&self) -> Result<(SocketAddr, Option<SocketAddr>), String> {
+        let public = SocketAddr::new(self.host, self.port);
+        match self.admin_addr() {
+            Some(admin) if listeners_conflict(admin, public) => Err(format!(
+                "管理 API 监听地址 {} 与 --host/--port 监听地址 {} 冲突，请为 --admin-port 指定其他端口",
+                admin, public
+            )),
+            admin => Ok((public, admin)),
+        }
+    }
+
+    /// 以 TOML 形式输出，格式与配置文件一致
+    fn to_toml(&self) -> String {
+        toml::to_string(self).unwrap_or_default()
+    }
+
+    /// 解析实际数据目录及其来源
+    fn resolved_data_dir(&self) -> Result<(PathBuf, modules::account::DataDirSource), String> {
+        let home = dirs::home_dir().ok_or("无法获取用户主目录")?;
+        let env_dir = std::env::var_os("ANTIGRAVITY_DATA_DIR")
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from);
+        Ok(modules::account::resolve_data_dir(
+            self.data_dir.as_deref(),
+            self.profile.as_deref(),
+            env_dir.as_deref(),
+            &home,
+        ))
+    }
+
+    /// --print-config 输出：以注释说明数据目录解析顺序与最终结果
+    fn print_config(&self) -> String {
+        let mut out = String::from(
+            "# 数据目录解析顺序: --data-dir > --profile > ANTIGRAVITY_DATA_DIR > ~/.antigravity_tools\n",
+        );
+        if let Ok((dir, source)) = self.resolved_data_dir() {
+            out.push_str(&format!("# 实际数据目录: {} (来源: {})\n", dir.display(), source.as_str()));
+        }
+        out.push_str(&self.to_toml());
+        out
+    }
+
+    /// 静态资源来源：显式指定 --static-dir 时读取磁盘，否则优先使用编译内嵌的前端
+    fn static_assets(&self) -> StaticAssets {
+        match &self.static_dir {
+            Some(dir) => StaticAssets::Disk(dir.clone()),
+            #[cfg(feature = "embed-frontend")]
+            None => StaticAssets::Embedded,
+            #[cfg(not(feature = "embed-frontend"))]
+            None => StaticAssets::Disk(PathBuf::from("./dist")),
+        }
+    }
+}
+
+/// 前端静态资源来源
+enum StaticAssets {
+    /// 从磁盘目录读取 (--static-dir)
+    Disk(PathBuf),
+    /// 使用编译进二进制的前端 dist
+    #[cfg(feature = "embed-frontend")]
+    Embedded,
+}
+
+impl std::fmt::Display for StaticAssets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StaticAssets::Disk(dir) => write!(f, "disk ({})", dir.display()),
+            #[cfg(feature = "embed-frontend")]
+            StaticAssets::Embedded => write!(f, "embedded ({} files)", embedded::FrontendAssets::iter().count()),
         }
     }
 }
 
-fn print_help() {
-    println!(
-        r#"Antigravity Manager - Web Server Mode
+/// 编译内嵌的前端资源
+#[cfg(feature = "embed-frontend")]
+mod embedded {
+    use axum::{
+        http::{header, StatusCode, Uri},
+        response::{Html, IntoResponse, Response},
+    };
 
-用法:
-  antigravity-server [OPTIONS]
+    #[derive(rust_embed::RustEmbed)]
+    #[folder = "../dist"]
+    pub struct FrontendAssets;
 
-OPTIONS:
-  -p, --port <PORT>         API 服务端口 (默认: 8765)
-  -h, --host <HOST>         绑定地址 (默认: 0.0.0.0)
-  -s, --static-dir <PATH>   前端静态文件目录 (默认: ./dist)
-  -d, --data-dir <PATH>     数据目录 (默认: ~/.antigravity)
-      --help                显示帮助信息
+    /// 按请求路径返回内嵌文件，未命中时回退到 index.html (SPA 路由)
+    pub async fn serve(uri: Uri, base_path: String) -> Response {
+        let path = uri.path().trim_start_matches('/');
+        if !path.is_empty() && path != "index.html" {
+            if let Some(response) = asset_response(path) {
+                return response;
+            }
+        }
+        index_response(&base_path).unwrap_or_else(|| StatusCode::NOT_FOUND.into_response())
+    }
 
-示例:
-  antigravity-server --port 8080 --static-dir ./web
-  antigravity-server -p 9000 -d /data/antigravity
-"#
-    );
+    fn index_response(base_path: &str) -> Option<Response> {
+        let file = FrontendAssets::get("index.html")?;
+        let html = String::from_utf8_lossy(&file.data);
+        Some(Html(super::rewrite_index_html(&html, base_path)).into_response())
+    }
+
+    fn asset_response(path: &str) -> Option<Response> {
+        let file = FrontendAssets::get(path)?;
+        Some(
+            (
+                [(header::CONTENT_TYPE, file.metadata.mimetype().to_string())],
+                file.data.into_owned(),
+            )
+                .into_response(),
+        )
+    }
 }
 
-#[tokio::main]
-async fn main() {
-    // 解析命令行参数
-    let args = Args::parse();
+/// 响应头中的服务端版本，前端与 `/api/health` 的版本比对，发现升级后提示刷新页面
+const APP_VERSION_HEADER: &str = "x-app-version";
 
-    // 设置数据目录环境变量 (如果指定)
-    if let Some(ref data_dir) = args.data_dir {
-        std::env::set_var("ANTIGRAVITY_DATA_DIR", data_dir);
+/// 文件名带内容哈希 (Vite 输出的 `assets/index-BdX3k2aQ.js`、`app.3f9a1c2e.css` 等)，内容不会变化
+fn is_hashed_asset(path: &str) -> bool {
+    if path.trim_start_matches('/').starts_with("assets/") {
+        return true;
     }
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let Some((stem, _)) = name.rsplit_once('.') else {
+        return false;
+    };
+    let Some((_, hash)) = stem.rsplit_once(['-', '.']) else {
+        return false;
+    };
+    hash.len() >= 8
+        && hash.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && hash.chars().any(|c| c.is_ascii_digit() || c.is_ascii_uppercase())
+}
 
-    // 初始化日志
-    logger::init_logger();
+/// 静态资源的缓存策略：带哈希的资源长期缓存，HTML (index.html 与 SPA fallback) 每次向服务端校验，
+/// 避免升级后浏览器继续使用旧版前端
+async fn static_cache_headers(req: Request, next: Next) -> Response {
+    let path = req.uri().path().to_string();
+    let mut res = next.run(req).await;
+    let cacheable = res.status().is_success() || res.status() == StatusCode::NOT_MODIFIED;
+    if cacheable && !path.starts_with("/api/") {
+        let is_html = res
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("text/html"));
+        let value = if is_html {
+            "no-cache"
+        } else if is_hashed_asset(&path) {
+            "public, max-age=31536000, immutable"
+        } else {
+            "public, max-age=3600"
+        };
+        res.headers_mut().insert(header::CACHE_CONTROL, HeaderValue::from_static(value));
+    }
+    res.headers_mut()
+        .insert(APP_VERSION_HEADER, HeaderValue::from_static(env!("CARGO_PKG_VERSION")));
+    res
+}
 
-    info!("Antigravity Manager Web Server starting...");
-    info!("  Port: {}", args.port);
-    info!("  Host: {}", args.host);
-    info!("  Static dir: {:?}", args.static_dir);
-    if let Some(ref data_dir) = args.data_dir {
-        info!("  Data dir: {:?}", data_dir);
+/// 规范化挂载前缀：确保以 `/` 开头、不以 `/` 结尾；根路径返回空字符串
+fn normalize_base_path(raw: &str) -> String {
+    let trimmed = raw.trim().trim_matches('/');
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", trimmed)
     }
+}
 
-    // 创建共享状态
-    let state = Arc::new(WebApiState::new());
+/// 在子路径部署时改写 index.html：注入 `<base href>` 与前端可读的前缀，
+/// 并把以 `/` 开头的资源引用 (Vite 默认输出) 加上前缀
+fn rewrite_index_html(html: &str, base_path: &str) -> String {
+    if base_path.is_empty() {
+        return html.to_string();
+    }
 
+    let html = html
+        .replace("src=\"/", &format!("src=\"{}/", base_path))
+        .replace("href=\"/", &format!("href=\"{}/", base_path));
+    let injected = format!(
+        "<head>\n  <base href=\"{0}/\" />\n  <script>window.__ANTIGRAVITY_BASE_PATH__ = \"{0}\";</script>",
+        base_path
+    );
+    html.replacen("<head>", &injected, 1)
+}
+
+/// 访问挂载前缀本身 (如 `/antigravity`) 时重定向到带尾斜杠的地址，保证相对路径正确解析
+async fn redirect_bare_base_path(base_path: String, req: Request, next: Next) -> Response {
+    if req.uri().path() == base_path {
+        let target = match req.uri().query() {
+            Some(q) => format!("{}/?{}", base_path, q),
+            None => format!("{}/", base_path),
+        };
+        return Redirect::permanent(&target).into_response();
+    }
+    next.run(req).await
+}
+
+/// 不带前缀的请求重定向到前缀下的同一路径 (308 保留请求方法)
+async fn redirect_into_base_path(base_path: String, uri: Uri) -> Response {
+    let target = match uri.path_and_query() {
+        Some(pq) => format!("{}{}", base_path, pq.as_str()),
+        None => format!("{}/", base_path),
+    };
+    Redirect::permanent(&target).into_response()
+}
+
+/// 磁盘静态文件服务，未命中的路径回退到 index.html (SPA 路由)
+fn disk_service(
+    static_dir: &Path,
+    compression: bool,
+    base_path: &str,
+) -> ServeDir<axum::routing::MethodRouter> {
+    // 创建 fallback 用于 SPA 路由
+    let index_path = static_dir.join("index.html");
+    let base_path = base_path.to_string();
+    let fallback = move || {
+        let index_path = index_path.clone();
+        let base_path = base_path.clone();
+        async move {
+            match tokio::fs::read_to_string(&index_path).await {
+                Ok(content) => {
+                    axum::response::Html(rewrite_index_html(&content, &base_path)).into_response()
+                }
+                Err(_) => StatusCode::NOT_FOUND.into_response(),
+            }
+        }
+    };
+
+    // 静态文件优先使用预压缩的 .br / .gz 旁路文件 (若存在)
+    let mut serve_dir = ServeDir::new(static_dir).append_index_html_on_directories(true);
+    if compression {
+        serve_dir = serve_dir.precompressed_br().precompressed_gzip();
+    }
+    serve_dir.fallback(axum::routing::get(fallback))
+}
+
+/// 组合 API 路由、静态文件与 SPA fallback
+fn build_app(
+    state: Arc<WebApiState>,
+    assets: &StaticAssets,
+    compression: bool,
+    base_path: &str,
+) -> Router {
     // 创建 API 路由
     let api_router = create_api_router(state.clone());
 
+    // Swagger UI (需启用 swagger-ui feature)，文档数据来自 /api/openapi.json
+    #[cfg(feature = "swagger-ui")]
+    let api_router = api_router.merge(
+        utoipa_swagger_ui::SwaggerUi::new("/api/docs")
+            .config(utoipa_swagger_ui::Config::from("/api/openapi.json")),
+    );
+
     // 创建 CORS 配置
     let cors = CorsLayer::new()
         .allow_origin(Any)
@@ -141,46 +619,63 @@ async fn main() {
             Method::GET,
             Method::POST,
             Method::PUT,
+            Method::PATCH,
             Method::DELETE,
             Method::OPTIONS,
         ])
-        .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION]);
+        .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION, header::IF_MATCH, header::IF_NONE_MATCH])
+        // 跨域前端需要读取配置的 ETag，保存时通过 If-Match 回传
+        .expose_headers([header::ETAG]);
 
-    // 创建 fallback 用于 SPA 路由
-    let static_dir_clone = args.static_dir.clone();
-    let index_path = args.static_dir.join("index.html");
-    let fallback = move || {
-        let index_path = index_path.clone();
-        async move {
-            match tokio::fs::read_to_string(&index_path).await {
-                Ok(content) => axum::response::Html(content).into_response(),
-                Err(_) => StatusCode::NOT_FOUND.into_response(),
-            }
+    // 组合路由
+    let app = Router::new().merge(api_router);
+    let app = match assets {
+        StaticAssets::Disk(static_dir) => {
+            app.fallback_service(disk_service(static_dir, compression, base_path))
+        }
+        #[cfg(feature = "embed-frontend")]
+        StaticAssets::Embedded => {
+            let base_path = base_path.to_string();
+            app.fallback(move |uri: Uri| embedded::serve(uri, base_path.clone()))
         }
     };
+    let app = app.layer(axum::middleware::from_fn(static_cache_headers));
 
-    // 组合路由
-    let app = Router::new()
-        .merge(api_router)
-        .fallback_service(
-            ServeDir::new(&static_dir_clone)
-                .append_index_html_on_directories(true)
-                .fallback(axum::routing::get(fallback)),
-        )
-        .layer(cors)
-        .layer(TraceLayer::new_for_http());
-
+    // 子路径部署：整体挂载到前缀下，根路径仅保留健康检查供负载均衡探测
+    let app = if base_path.is_empty() {
+        app
+    } else {
+        let bare = base_path.to_string();
+        let outer = base_path.to_string();
+        Router::new()
+            .nest(base_path, app)
+            .route("/api/health", get(health_check).with_state(state))
+            .fallback(move |uri: Uri| redirect_into_base_path(outer.clone(), uri))
+            .layer(axum::middleware::from_fn(move |req: Request, next: Next| {
+                redirect_bare_base_path(bare.clone(), req, next)
+            }))
+    };
 
-    // 启动服务器
-    let addr: SocketAddr = format!("{}:{}", args.host, args.port)
-        .parse()
-        .expect("Invalid address");
+    let app = if compression {
+        // SSE (/api/events 等) 必须排除，压缩层的缓冲会导致事件无法实时下发
+        let predicate = DefaultPredicate::new().and(NotForContentType::SSE);
+        app.layer(CompressionLayer::new().br(true).gzip(true).compress_when(predicate))
+    } else {
+        app
+    };
 
-    info!("Server listening on http://{}", addr);
-    info!("Open http://localhost:{} in your browser", args.port);
+    app.layer(cors).layer(TraceLayer::new_for_http())
+}
 
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+/// 管理 API 单独监听时，公共监听只保留健康检查
+fn build_public_app(state: Arc<WebApiState>) -> Router {
+    Router::new()
+        .route("/api/health", get(health_check).with_state(state))
+        .layer(TraceLayer::new_for_http())
+}
 
+/// 接受连接直到收到停止信号
+async fn serve(listener: tokio::net::TcpListener, app: Router, mut shutdown: tokio::sync::watch::Receiver<bool>) {
     // [FIX] 使用手动 hyper 连接处理，配置 TCP Keep-Alive 防止 Docker 环境下的 EPIPE 错误
     // 这与 server.rs 中的实现保持一致，确保长时间 SSE 流连接的稳定性
     use hyper::server::conn::http1;
@@ -188,8 +683,13 @@ async fn main() {
     use hyper_util::service::TowerToHyperService;
 
     loop {
-        match listener.accept().await {
-            Ok((stream, _)) => {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = shutdown.changed() => break,
+        };
+
+        match accepted {
+            Ok((stream, peer)) => {
                 // [FIX] 设置 TCP Keep-Alive 以防止 Docker/网络环境下的连接静默断开
                 // 这对于长时间运行的 SSE 流式连接尤为重要
                 if let Ok(sock_ref) = socket2::SockRef::try_from(&stream) {
@@ -203,7 +703,14 @@ async fn main() {
                 }
 
                 let io = TokioIo::new(stream);
-                let service = TowerToHyperService::new(app.clone());
+                // 写入对端地址，供 IP 白名单使用
+                let service = TowerToHyperService::new(tower::ServiceExt::map_request(
+                    app.clone(),
+                    move |mut req: hyper::Request<hyper::body::Incoming>| {
+                        req.extensions_mut().insert(axum::extract::ConnectInfo(peer));
+                        req
+                    },
+                ));
 
                 tokio::task::spawn(async move {
                     if let Err(err) = http1::Builder::new()
@@ -222,3 +729,824 @@ async fn main() {
         }
     }
 }
+
+/// 绑定监听地址；`auto_port` 时端口被占用则依次尝试后续端口 (跳过与 `avoid` 冲突的地址)
+///
+/// 失败时返回最后尝试的地址与错误。
+async fn bind_listener(
+    addr: SocketAddr,
+    auto_port: bool,
+    avoid: Option<SocketAddr>,
+) -> Result<tokio::net::TcpListener, (SocketAddr, std::io::Error)> {
+    let attempts = if auto_port { PORT_AUTO_ATTEMPTS } else { 0 };
+    let candidates = (0..=attempts)
+        .filter_map(|offset| addr.port().checked_add(offset))
+        .map(|port| SocketAddr::new(addr.ip(), port))
+        .filter(|candidate| *candidate == addr || !avoid.is_some_and(|avoid| listeners_conflict(avoid, *candidate)));
+
+    let mut last_error = None;
+    for candidate in candidates {
+        match tokio::net::TcpListener::bind(candidate).await {
+            Ok(listener) => {
+                if candidate != addr {
+                    warn!("================================================================");
+                    warn!("端口 {} 已被占用，已自动改用端口 {} (--port-auto)", addr.port(), candidate.port());
+                    warn!("================================================================");
+                }
+                return Ok(listener);
+            }
+            Err(e) if auto_port && e.kind() == std::io::ErrorKind::AddrInUse => {
+                debug!("端口 {} 已被占用，尝试下一个端口", candidate.port());
+                last_error = Some((candidate, e));
+            }
+            Err(e) => return Err((candidate, e)),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| (addr, std::io::ErrorKind::AddrInUse.into())))
+}
+
+/// 绑定失败时输出诊断信息：占用端口的进程 (尽力查找) 与解决建议
+fn report_bind_error(requested: SocketAddr, failed: SocketAddr, error: &std::io::Error, auto_port: bool) {
+    error!("监听 {} 失败: {}", failed, error);
+    match error.kind() {
+        std::io::ErrorKind::AddrInUse => {
+            match port_owner::find(requested.port()) {
+                Some(owner) => error!("端口 {} 正被 {} 占用", requested.port(), owner),
+                None => error!(
+                    "端口 {} 已被其他进程占用 (无法确定具体进程，可用 `lsof -i :{}` 或 `ss -ltnp` 查看)",
+                    requested.port(),
+                    requested.port()
+                ),
+            }
+            if auto_port {
+                error!("端口 {}-{} 均被占用，请使用 --port 指定其他端口", requested.port(), failed.port());
+            } else {
+                error!("请使用 --port 指定其他端口，或加上 --port-auto 自动选择后续空闲端口");
+            }
+        }
+        std::io::ErrorKind::PermissionDenied if failed.port() < 1024 => {
+            error!("监听 1024 以下的端口需要 root 权限或 CAP_NET_BIND_SERVICE，请使用 --port 指定其他端口");
+        }
+        std::io::ErrorKind::AddrNotAvailable => {
+            error!("本机没有地址 {}，请检查 --host", failed.ip());
+        }
+        _ => {}
+    }
+}
+
+/// 绑定监听地址，失败时输出诊断信息并以 `EXIT_BIND` 退出
+async fn bind_or_exit(addr: SocketAddr, auto_port: bool, avoid: Option<SocketAddr>) -> tokio::net::TcpListener {
+    match bind_listener(addr, auto_port, avoid).await {
+        Ok(listener) => listener,
+        Err((failed, e)) => {
+            report_bind_error(addr, failed, &e, auto_port);
+            std::process::exit(EXIT_BIND);
+        }
+    }
+}
+
+/// 查找监听指定 TCP 端口的进程 (尽力而为，仅用于错误提示)
+mod port_owner {
+    /// 返回 `进程名 (PID n)`，无法确定时为空
+    #[cfg(target_os = "linux")]
+    pub fn find(port: u16) -> Option<String> {
+        let inodes: Vec<u64> = ["/proc/net/tcp", "/proc/net/tcp6"]
+            .iter()
+            .filter_map(|path| std::fs::read_to_string(path).ok())
+            .flat_map(|content| listening_inodes(&content, port))
+            .collect();
+        if inodes.is_empty() {
+            return None;
+        }
+
+        // 其他用户的进程通常无权读取其 fd，此时找不到
+        for entry in std::fs::read_dir("/proc").ok()?.flatten() {
+            let Some(pid) = entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) else {
+                continue;
+            };
+            let Ok(fds) = std::fs::read_dir(entry.path().join("fd")) else {
+                continue;
+            };
+            let owns = fds.flatten().any(|fd| {
+                std::fs::read_link(fd.path()).is_ok_and(|target| {
+                    let target = target.to_string_lossy();
+                    inodes.iter().any(|inode| target == format!("socket:[{}]", inode))
+                })
+            });
+            if owns {
+                let name = std::fs::read_to_string(entry.path().join("comm")).unwrap_or_default();
+                return Some(format!("{} (PID {})", name.trim(), pid));
+            }
+        }
+        None
+    }
+
+    /// 解析 `/proc/net/tcp` 格式的内容，返回监听 `port` 的 socket inode
+    #[cfg(target_os = "linux")]
+    pub fn listening_inodes(content: &str, port: u16) -> Vec<u64> {
+        const TCP_LISTEN: &str = "0A";
+        content
+            .lines()
+            .skip(1)
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                let local_port = u16::from_str_radix(fields.get(1)?.rsplit(':').next()?, 16).ok()?;
+                if local_port != port || *fields.get(3)? != TCP_LISTEN {
+                    return None;
+                }
+                fields.get(9)?.parse().ok()
+            })
+            .collect()
+    }
+
+    /// 其他 Unix 系统通过 lsof 查找
+    #[cfg(all(unix, not(target_os = "linux")))]
+    pub fn find(port: u16) -> Option<String> {
+        let output = std::process::Command::new("lsof")
+            .args(["-nP", &format!("-iTCP:{}", port), "-sTCP:LISTEN", "-Fpc"])
+            .output()
+            .ok()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let pid = stdout.lines().find_map(|line| line.strip_prefix('p'))?;
+        let name = stdout.lines().find_map(|line| line.strip_prefix('c')).unwrap_or("?");
+        Some(format!("{} (PID {})", name, pid))
+    }
+
+    #[cfg(not(unix))]
+    pub fn find(_port: u16) -> Option<String> {
+        None
+    }
+}
+
+/// 守护进程辅助：PID 文件与 systemd `Type=notify` 通知
+mod daemon {
+    use std::path::{Path, PathBuf};
+    use tracing::{info, warn};
+
+    use antigravity_tools_lib::utils::tasks::TaskSupervisor;
+
+    /// PID 文件，drop 时删除
+    pub struct PidFile(PathBuf);
+
+    impl PidFile {
+        pub fn create(path: &Path) -> Result<Self, String> {
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("创建 PID 文件目录失败: {}", e))?;
+            }
+            std::fs::write(path, format!("{}\n", std::process::id()))
+                .map_err(|e| format!("写入 PID 文件 {} 失败: {}", path.display(), e))?;
+            Ok(Self(path.to_path_buf()))
+        }
+    }
+
+    impl Drop for PidFile {
+        fn drop(&mut self) {
+            if let Err(e) = std::fs::remove_file(&self.0) {
+                warn!("删除 PID 文件 {} 失败: {}", self.0.display(), e);
+            }
+        }
+    }
+
+    /// 通知 systemd 服务已就绪，并在配置了 WatchdogSec 时启动心跳
+    #[cfg(unix)]
+    pub fn notify_ready(tasks: &TaskSupervisor) {
+        use sd_notify::NotifyState;
+
+        // 不在 systemd 下运行时 NOTIFY_SOCKET 不存在，调用为空操作
+        if let Err(e) = sd_notify::notify(false, &[NotifyState::Ready]) {
+            warn!("sd_notify READY 失败: {}", e);
+        }
+
+        let mut usec = 0u64;
+        if sd_notify::watchdog_enabled(false, &mut usec) && usec > 0 {
+            // 按 systemd 建议以超时的一半为间隔发送心跳
+            let interval = std::time::Duration::from_micros(usec / 2);
+            info!("systemd watchdog enabled, ping every {:?}", interval);
+            // 心跳不访问数据目录，恢复备份时保持运行
+            tasks.register_persistent("systemd-watchdog", move |ctx| async move {
+                loop {
+                    let _ = sd_notify::notify(false, &[NotifyState::Watchdog]);
+                    if !ctx.sleep(interval).await {
+                        break;
+                    }
+                }
+            });
+        }
+    }
+
+    /// 通知 systemd 服务正在停止
+    #[cfg(unix)]
+    pub fn notify_stopping() {
+        let _ = sd_notify::notify(false, &[sd_notify::NotifyState::Stopping]);
+    }
+
+    #[cfg(not(unix))]
+    pub fn notify_ready(_tasks: &TaskSupervisor) {}
+
+    #[cfg(not(unix))]
+    pub fn notify_stopping() {}
+
+    /// 等待 Ctrl+C 或 SIGTERM
+    pub async fn shutdown_signal() {
+        let ctrl_c = async {
+            let _ = tokio::signal::ctrl_c().await;
+        };
+
+        #[cfg(unix)]
+        let terminate = async {
+            match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                Ok(mut sig) => {
+                    sig.recv().await;
+                }
+                Err(e) => {
+                    warn!("注册 SIGTERM 处理失败: {}", e);
+                    std::future::pending::<()>().await;
+                }
+            }
+        };
+
+        #[cfg(not(unix))]
+        let terminate = std::future::pending::<()>();
+
+        tokio::select! {
+            _ = ctrl_c => {},
+            _ = terminate => {},
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    // 解析命令行参数并与环境变量、配置文件合并
+    let cli_matches = Args::command().get_matches();
+    let print_config = cli_matches.get_flag("print_config");
+    let config_path = cli_matches.get_one::<PathBuf>("config").cloned();
+    let (config, warnings) = match ServerConfig::resolve(&cli_matches) {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(EXIT_CONFIG);
+        }
+    };
+    let (public_addr, admin_addr) = match config.listen_addrs() {
+        Ok(addrs) => addrs,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(EXIT_CONFIG);
+        }
+    };
+
+    if print_config {
+        for warning in &warnings {
+            eprintln!("warning: {}", warning);
+        }
+        print!("{}", config.print_config());
+        return;
+    }
+
+    // 选定数据目录，之后所有模块通过 get_data_dir 读取
+    if let Err(e) = modules::account::select_data_dir(config.data_dir.clone(), config.profile.clone()) {
+        eprintln!("error: {}", e);
+        std::process::exit(EXIT_CONFIG);
+    }
+
+    // 允许通过 ANTIGRAVITY_<PATH>__<FIELD> 环境变量覆盖应用配置
+    modules::config::enable_env_overrides();
+
+    // 初始化日志
+    logger::init_logger();
+
+    info!("Antigravity Manager Web Server starting...");
+    if let Some(ref path) = config_path {
+        info!("  Config file: {:?}", path);
+    }
+    for warning in &warnings {
+        warn!("{}", warning);
+    }
+    info!("  Port: {}", config.port);
+    info!("  Host: {}", config.host);
+    if let Some(addr) = admin_addr {
+        info!("  Admin: {}", addr);
+    }
+    let assets = config.static_assets();
+    info!("  Static assets: {}", assets);
+    info!("  Compression: {}", if config.no_compression { "off" } else { "gzip, br" });
+    if !config.base_path.is_empty() {
+        info!("  Base path: {}", config.base_path);
+    }
+    if let Ok((data_dir, source)) = modules::account::current_data_dir() {
+        info!("  Data dir: {:?} ({})", data_dir, source.as_str());
+    }
+    if !config.allowed_ips.is_empty() {
+        info!("  Allowed IPs: {}", config.allowed_ips.join(", "));
+    }
+    if let Some(ref path) = config.seed_accounts_file {
+        info!("  Seed accounts: {:?}", path);
+    }
+    let overridden = modules::config::overridden_paths();
+    if !overridden.is_empty() {
+        info!("  Config overrides: {}", overridden.join(", "));
+    }
+
+    // 先绑定端口再做迁移、写 PID 文件，端口被占用时不留下半初始化的状态；
+    // 管理 API 监听先绑定，--port-auto 选择公共端口时避开它
+    let admin_listener = match admin_addr {
+        Some(addr) => Some(bind_or_exit(addr, false, None).await),
+        None => None,
+    };
+    let public_listener = bind_or_exit(public_addr, config.port_auto, admin_addr).await;
+    let public_addr = public_listener.local_addr().unwrap_or(public_addr);
+
+    // 检查数据格式版本并执行迁移；数据由更新版本写入时拒绝启动
+    if let Err(e) = modules::schema::ensure_current() {
+        error!("{}", e);
+        std::process::exit(EXIT_STARTUP);
+    }
+
+    let _pid_file = match config.pid_file.as_deref().map(daemon::PidFile::create) {
+        Some(Ok(pid_file)) => Some(pid_file),
+        Some(Err(e)) => {
+            error!("{}", e);
+            std::process::exit(EXIT_STARTUP);
+        }
+        None => None,
+    };
+
+    // 创建共享状态
+    let event_capacity = modules::config::load_app_config()
+        .map(|c| c.sse_channel_capacity)
+        .unwrap_or_else(|_| antigravity_tools_lib::models::config::default_sse_channel_capacity());
+    let state = Arc::new(
+        WebApiState::new()
+            .with_base_path(config.base_path.clone())
+            .with_ip_access(config.allowed_ips.clone(), config.trusted_proxies.clone())
+            .with_listeners(admin_addr.into_iter().chain([public_addr]).collect(), admin_addr)
+            .with_event_capacity(event_capacity),
+    );
+
+    // 自动启动的反代与服务端监听同一端口时直接报错，避免启动到一半才绑定失败
+    if let Ok(app_config) = modules::config::load_app_config() {
+        if app_config.proxy.auto_start {
+            if let Err(e) = state.check_proxy_bind(&app_config.proxy) {
+                error!("{}", e);
+                std::process::exit(EXIT_CONFIG);
+            }
+        }
+    }
+
+    web_api::watch_auto_switch(&state);
+    web_api::spawn_db_sync(&state);
+    web_api::spawn_stats_tick(&state);
+    web_api::spawn_rotation(&state);
+    web_api::watch_config(state.clone());
+    web_api::watch_accounts(state.clone());
+
+    let app = build_app(state.clone(), &assets, !config.no_compression, &config.base_path);
+
+    // 启动服务器，管理 API 单独监听时公共监听只保留健康检查
+    let manage_port = admin_addr.unwrap_or(public_addr).port();
+    info!("Server listening on http://{}", public_addr);
+    if let Some(addr) = admin_addr {
+        info!("Management API listening on http://{}", addr);
+    }
+    info!("Open http://localhost:{}{}/ in your browser", manage_port, config.base_path);
+
+    // 导入种子账号，失败只记录不中止启动；在自动启动反代前完成，使账号池包含导入的账号
+    if let Some(ref path) = config.seed_accounts_file {
+        modules::seed::seed_from_file(path).await;
+    }
+
+    // 自动启动反代服务，完成后再通知 systemd 就绪
+    match web_api::auto_start_proxy(&state).await {
+        Ok(true) => info!("反代服务自动启动成功"),
+        Ok(false) => {}
+        Err(e) => error!("自动启动反代服务失败: {}", e),
+    }
+    daemon::notify_ready(&state.tasks);
+
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let mut servers = Vec::new();
+    match admin_listener {
+        Some(admin_listener) => {
+            servers.push(tokio::spawn(serve(admin_listener, app, shutdown_rx.clone())));
+            servers.push(tokio::spawn(serve(
+                public_listener,
+                build_public_app(state.clone()),
+                shutdown_rx,
+            )));
+        }
+        None => servers.push(tokio::spawn(serve(public_listener, app, shutdown_rx))),
+    }
+
+    daemon::shutdown_signal().await;
+    info!("Shutting down...");
+    daemon::notify_stopping();
+    let _ = shutdown_tx.send(true);
+    for server in servers {
+        let _ = server.await;
+    }
+    web_api::flush_proxy_usage(&state).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use tower::ServiceExt;
+
+    fn test_app(compression: bool) -> Router {
+        build_app(
+            Arc::new(WebApiState::new()),
+            &StaticAssets::Disk(PathBuf::from("./nonexistent-dist")),
+            compression,
+            "",
+        )
+    }
+
+    fn based_app() -> Router {
+        build_app(
+            Arc::new(WebApiState::new().with_base_path("/antigravity".to_string())),
+            &StaticAssets::Disk(PathBuf::from("./nonexistent-dist")),
+            false,
+            "/antigravity",
+        )
+    }
+
+    async fn send_get(app: Router, uri: &str) -> Response {
+        let req = axum::http::Request::builder().uri(uri).body(Body::empty()).unwrap();
+        app.oneshot(req).await.unwrap()
+    }
+
+    fn health_request() -> Request {
+        axum::http::Request::builder()
+            .uri("/api/health")
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn api_responses_are_gzip_encoded_when_accepted() {
+        let res = test_app(true).oneshot(health_request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get(header::CONTENT_ENCODING).unwrap(), "gzip");
+    }
+
+    #[tokio::test]
+    async fn no_compression_disables_encoding() {
+        let res = test_app(false).oneshot(health_request()).await.unwrap();
+        assert!(res.headers().get(header::CONTENT_ENCODING).is_none());
+    }
+
+    #[tokio::test]
+    async fn cors_preflight_allows_patch() {
+        let req = axum::http::Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/api/config")
+            .header(header::ORIGIN, "https://ui.example.com")
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "PATCH")
+            .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "content-type")
+            .body(Body::empty())
+            .unwrap();
+        let res = test_app(false).oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let methods = res.headers().get(header::ACCESS_CONTROL_ALLOW_METHODS).unwrap().to_str().unwrap();
+        assert!(methods.split(',').any(|m| m.trim() == "PATCH"), "{}", methods);
+    }
+
+    #[tokio::test]
+    async fn cors_allows_conditional_config_requests() {
+        let req = axum::http::Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/api/config")
+            .header(header::ORIGIN, "https://ui.example.com")
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "PUT")
+            .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "content-type,if-match")
+            .body(Body::empty())
+            .unwrap();
+        let res = test_app(false).oneshot(req).await.unwrap();
+        let headers = res.headers().get(header::ACCESS_CONTROL_ALLOW_HEADERS).unwrap().to_str().unwrap();
+        assert!(headers.split(',').any(|h| h.trim() == "if-match"), "{}", headers);
+
+        let req = axum::http::Request::builder()
+            .uri("/api/health")
+            .header(header::ORIGIN, "https://ui.example.com")
+            .body(Body::empty())
+            .unwrap();
+        let res = test_app(false).oneshot(req).await.unwrap();
+        assert_eq!(res.headers().get(header::ACCESS_CONTROL_EXPOSE_HEADERS).unwrap(), "etag");
+    }
+
+    #[tokio::test]
+    async fn allowlist_rejects_other_clients_but_keeps_health_open() {
+        let state = WebApiState::new().with_ip_access(vec!["10.8.0.0/16".to_string()], Vec::new());
+        let app = build_app(
+            Arc::new(state),
+            &StaticAssets::Disk(PathBuf::from("./nonexistent-dist")),
+            false,
+            "",
+        );
+        let request = |uri: &str, peer: &str| {
+            let mut req = axum::http::Request::builder().uri(uri).body(Body::empty()).unwrap();
+            let peer: SocketAddr = peer.parse().unwrap();
+            req.extensions_mut().insert(axum::extract::ConnectInfo(peer));
+            req
+        };
+
+        let res = app.clone().oneshot(request("/api/system/base-path", "10.8.1.2:5000")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = app.clone().oneshot(request("/api/system/base-path", "192.168.1.2:5000")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        let res = app.oneshot(request("/api/health", "192.168.1.2:5000")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn base_path_is_normalized() {
+        assert_eq!(normalize_base_path("/"), "");
+        assert_eq!(normalize_base_path("antigravity"), "/antigravity");
+        assert_eq!(normalize_base_path("/antigravity/"), "/antigravity");
+        assert_eq!(normalize_base_path(" /a/b/ "), "/a/b");
+    }
+
+    #[test]
+    fn index_html_is_rewritten_for_base_path() {
+        let html = r#"<head><script type="module" src="/assets/index.js"></script><link href="/icon.png"></head>"#;
+        assert_eq!(rewrite_index_html(html, ""), html);
+
+        let rewritten = rewrite_index_html(html, "/antigravity");
+        assert!(rewritten.contains(r#"<base href="/antigravity/" />"#));
+        assert!(rewritten.contains(r#"src="/antigravity/assets/index.js""#));
+        assert!(rewritten.contains(r#"href="/antigravity/icon.png""#));
+        assert!(rewritten.contains(r#"window.__ANTIGRAVITY_BASE_PATH__ = "/antigravity""#));
+    }
+
+    #[tokio::test]
+    async fn base_path_nests_api_and_keeps_root_health() {
+        assert_eq!(send_get(based_app(), "/antigravity/api/health").await.status(), StatusCode::OK);
+        assert_eq!(send_get(based_app(), "/api/health").await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn base_path_redirects_bare_and_prefixless_requests() {
+        let res = send_get(based_app(), "/antigravity").await;
+        assert_eq!(res.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(res.headers()[header::LOCATION], "/antigravity/");
+
+        let res = send_get(based_app(), "/accounts?tab=1").await;
+        assert_eq!(res.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(res.headers()[header::LOCATION], "/antigravity/accounts?tab=1");
+    }
+
+    #[test]
+    fn hashed_asset_names_are_detected() {
+        assert!(is_hashed_asset("/assets/index-BdX3k2aQ.js"));
+        assert!(is_hashed_asset("/static/app.3f9a1c2e.css"));
+        assert!(is_hashed_asset("/vendor-react-Ab12Cd34.js"));
+        assert!(!is_hashed_asset("/icon.png"));
+        assert!(!is_hashed_asset("/index.html"));
+        assert!(!is_hashed_asset("/my-components.js"));
+    }
+
+    #[tokio::test]
+    async fn hashed_assets_are_immutable_and_fallback_is_not_cached() {
+        let dir = std::env::temp_dir().join(format!("ag-static-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("assets")).unwrap();
+        std::fs::write(dir.join("index.html"), "<html><head></head></html>").unwrap();
+        std::fs::write(dir.join("assets/index-BdX3k2aQ.js"), "console.log(1)").unwrap();
+        let app = build_app(Arc::new(WebApiState::new()), &StaticAssets::Disk(dir.clone()), false, "");
+
+        let asset = send_get(app.clone(), "/assets/index-BdX3k2aQ.js").await;
+        assert_eq!(asset.status(), StatusCode::OK);
+        assert_eq!(asset.headers()[header::CACHE_CONTROL], "public, max-age=31536000, immutable");
+
+        for uri in ["/", "/index.html", "/accounts/settings"] {
+            let page = send_get(app.clone(), uri).await;
+            assert_eq!(page.status(), StatusCode::OK, "{}", uri);
+            assert_eq!(page.headers()[header::CACHE_CONTROL], "no-cache", "{}", uri);
+            assert_eq!(page.headers()[APP_VERSION_HEADER], env!("CARGO_PKG_VERSION"));
+        }
+
+        let health = send_get(app, "/api/health").await;
+        assert!(health.headers().get(header::CACHE_CONTROL).is_none());
+        assert_eq!(health.headers()[APP_VERSION_HEADER], env!("CARGO_PKG_VERSION"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// --help 快照：修改选项或默认值时需同步更新，保证文档与代码一致
+    const HELP_SNAPSHOT: &str = r#"
+Antigravity Manager - Web Server Mode
+
+Usage: antigravity-server [OPTIONS]
+
+Options:
+  -p, --port <PORT>                API 服务端口 [env: ANTIGRAVITY_PORT] [default: 8765]
+      --port-auto                  端口被占用时依次尝试后续 10 个端口 [env: ANTIGRAVITY_PORT_AUTO]
+  -h, --host <HOST>                绑定地址 [env: ANTIGRAVITY_HOST] [default: 0.0.0.0]
+      --admin-host <HOST>          管理 API 单独监听的地址 (仅指定 --admin-port 时默认 127.0.0.1) [env: ANTIGRAVITY_ADMIN_HOST]
+      --admin-port <PORT>          管理 API 单独监听的端口，指定后 --host/--port 只提供健康检查 [env: ANTIGRAVITY_ADMIN_PORT]
+  -s, --static-dir <PATH>          前端静态文件目录 [env: ANTIGRAVITY_STATIC_DIR]
+  -d, --data-dir <PATH>            数据目录 (默认: ~/.antigravity_tools) [env: ANTIGRAVITY_DATA_DIR]
+      --profile <NAME>             使用 ~/.antigravity_tools/profiles/<NAME> 作为数据目录 [env: ANTIGRAVITY_PROFILE]
+      --no-compression             关闭响应压缩 (gzip/brotli) [env: ANTIGRAVITY_NO_COMPRESSION]
+      --base-path <PATH>           子路径挂载前缀 [env: ANTIGRAVITY_BASE_PATH] [default: /]
+      --seed-accounts-file <PATH>  种子账号文件 (JSON 数组)，启动时导入其中的账号 [env: ANTIGRAVITY_SEED_ACCOUNTS]
+      --pid-file <PATH>            PID 文件路径，启动时写入，正常退出时删除 [env: ANTIGRAVITY_PID_FILE]
+      --allow-ip <CIDR>            管理 API 白名单 (CIDR，逗号分隔) [env: ANTIGRAVITY_ALLOWED_IPS]
+      --trust-proxy <CIDR>         可信反向代理 (CIDR，逗号分隔) [env: ANTIGRAVITY_TRUSTED_PROXIES]
+      --config <PATH>              服务端配置文件 (TOML/YAML) [env: ANTIGRAVITY_CONFIG]
+      --print-config               打印合并后的配置并退出
+      --help                       显示帮助信息
+  -V, --version                    Print version
+
+示例:
+  antigravity-server --port 8080 --static-dir ./web
+  antigravity-server -p 9000 -d /data/antigravity
+  antigravity-server --profile work
+  antigravity-server --base-path /antigravity
+"#;
+
+    fn normalize(text: &str) -> String {
+        text.lines().map(str::trim_end).collect::<Vec<_>>().join("\n").trim().to_string()
+    }
+
+    #[test]
+    fn help_output_matches_snapshot() {
+        let help = Args::command().render_help().to_string();
+        assert_eq!(normalize(&help), normalize(HELP_SNAPSHOT));
+    }
+
+    #[test]
+    fn cli_keeps_short_flags() {
+        let args = Args::try_parse_from(["antigravity-server", "-p", "9000", "-h", "127.0.0.1", "-d", "/tmp/ag"]).unwrap();
+        assert_eq!(args.port, 9000);
+        assert_eq!(args.host.to_string(), "127.0.0.1");
+        assert_eq!(args.data_dir, Some(PathBuf::from("/tmp/ag")));
+    }
+
+    #[test]
+    fn cli_rejects_invalid_values() {
+        for argv in [
+            vec!["antigravity-server", "--port", "abc"],
+            vec!["antigravity-server", "--port", "0"],
+            vec!["antigravity-server", "--host", "not-an-ip"],
+            vec!["antigravity-server", "--static-dir", "./definitely-missing-dir"],
+            vec!["antigravity-server", "--unknown-flag"],
+        ] {
+            assert!(Args::try_parse_from(&argv).is_err(), "{:?} should be rejected", argv);
+        }
+    }
+
+    fn matches(argv: &[&str]) -> ArgMatches {
+        Args::command().try_get_matches_from(argv).unwrap()
+    }
+
+    #[test]
+    fn file_config_parses_toml_and_yaml_with_unknown_keys() {
+        let dir = std::env::temp_dir().join(format!("ag-server-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let toml_path = dir.join("server.toml");
+        std::fs::write(&toml_path, "port = 9001\nbase_path = \"tools/\"\nfuture_option = 1\n").unwrap();
+        let file = FileConfig::load(&toml_path).unwrap();
+        assert_eq!(file.port, Some(9001));
+        assert_eq!(file.base_path.as_deref(), Some("/tools"));
+        assert!(file.unknown.contains_key("future_option"));
+
+        let yaml_path = dir.join("server.yaml");
+        std::fs::write(&yaml_path, "host: 127.0.0.1\nno_compression: true\n").unwrap();
+        let file = FileConfig::load(&yaml_path).unwrap();
+        assert_eq!(file.host, Some("127.0.0.1".parse().unwrap()));
+        assert_eq!(file.no_compression, Some(true));
+
+        std::fs::write(&toml_path, "port = 0\n").unwrap();
+        assert!(FileConfig::load(&toml_path).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn cli_overrides_file_and_file_overrides_defaults() {
+        let file = FileConfig {
+            port: Some(9001),
+            host: Some("127.0.0.1".parse().unwrap()),
+            unknown: [("tls_cert".to_string(), serde::de::IgnoredAny)].into_iter().collect(),
+            ..Default::default()
+        };
+        let (config, warnings) = ServerConfig::merge(
+            &matches(&["antigravity-server", "--port", "9100"]),
+            &matches(&["antigravity-server"]),
+            file,
+        )
+        .unwrap();
+
+        assert_eq!(config.port, 9100);
+        assert_eq!(config.host.to_string(), "127.0.0.1");
+        assert_eq!(config.base_path, "");
+        assert_eq!(warnings.len(), 1);
+        assert!(config.to_toml().contains("port = 9100"));
+    }
+
+    #[test]
+    fn admin_listener_is_separate_and_must_not_conflict() {
+        let resolve = |argv: &[&str]| {
+            ServerConfig::merge(&matches(argv), &matches(&["antigravity-server"]), FileConfig::default())
+                .unwrap()
+                .0
+        };
+
+        let config = resolve(&["antigravity-server"]);
+        assert_eq!(config.listen_addrs().unwrap().1, None);
+
+        // 只指定端口时管理 API 默认仅监听本机
+        let config = resolve(&["antigravity-server", "--admin-port", "8766"]);
+        let (public, admin) = config.listen_addrs().unwrap();
+        assert_eq!(public.to_string(), "0.0.0.0:8765");
+        assert_eq!(admin.unwrap().to_string(), "127.0.0.1:8766");
+
+        // 通配地址与本机地址共用端口会争用同一端口
+        let config = resolve(&["antigravity-server", "--admin-host", "127.0.0.1"]);
+        assert!(config.listen_addrs().unwrap_err().contains("冲突"));
+        let config = resolve(&["antigravity-server", "-h", "192.168.1.5", "--admin-host", "127.0.0.1"]);
+        assert!(config.listen_addrs().is_ok());
+    }
+
+    #[tokio::test]
+    async fn port_auto_skips_occupied_ports() {
+        let occupied = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = occupied.local_addr().unwrap();
+
+        let (failed, e) = bind_listener(addr, false, None).await.unwrap_err();
+        assert_eq!(failed, addr);
+        assert_eq!(e.kind(), std::io::ErrorKind::AddrInUse);
+
+        // 后续端口也可能恰好被占用，只要求换到了范围内的其他端口
+        let listener = bind_listener(addr, true, None).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(port > addr.port() && port <= addr.port() + PORT_AUTO_ATTEMPTS);
+
+        // 跳过管理 API 的地址
+        let next = SocketAddr::new(addr.ip(), addr.port() + 1);
+        drop(listener);
+        let listener = bind_listener(addr, true, Some(next)).await.unwrap();
+        assert_ne!(listener.local_addr().unwrap(), next);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn port_owner_reads_proc_net_tcp() {
+        let content = "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000:223D 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 41234 1 0000000000000000 100 0 0 10 0
+   1: 0100007F:223D 0100007F:9C40 01 00000000:00000000 00:00000000 00000000  1000        0 41299 1 0000000000000000 20 4 30 10 -1
+   2: 0100007F:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 50001 1 0000000000000000 100 0 0 10 0
+";
+        assert_eq!(port_owner::listening_inodes(content, 8765), vec![41234]);
+        assert_eq!(port_owner::listening_inodes(content, 8080), vec![50001]);
+        assert!(port_owner::listening_inodes(content, 9000).is_empty());
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let owner = port_owner::find(listener.local_addr().unwrap().port()).unwrap();
+        assert!(owner.ends_with(&format!("(PID {})", std::process::id())), "{}", owner);
+    }
+
+    #[tokio::test]
+    async fn public_app_only_serves_health() {
+        let app = build_public_app(Arc::new(WebApiState::new()));
+        assert_eq!(send_get(app.clone(), "/api/health").await.status(), StatusCode::OK);
+        assert_eq!(send_get(app, "/api/accounts").await.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn explicit_data_dir_beats_profile_and_env() {
+        let file = FileConfig { profile: Some("work".to_string()), ..Default::default() };
+        let (config, _) = ServerConfig::merge(
+            &matches(&["antigravity-server", "-d", "/data/ag"]),
+            &matches(&["antigravity-server"]),
+            file,
+        )
+        .unwrap();
+        assert_eq!(config.data_dir, Some(PathBuf::from("/data/ag")));
+        assert_eq!(config.profile.as_deref(), Some("work"));
+        let (dir, source) = config.resolved_data_dir().unwrap();
+        assert_eq!((dir, source), (PathBuf::from("/data/ag"), modules::account::DataDirSource::Explicit));
+
+        let (config, _) = ServerConfig::merge(
+            &matches(&["antigravity-server", "--profile", "work"]),
+            &matches(&["antigravity-server"]),
+            FileConfig::default(),
+        )
+        .unwrap();
+        let (dir, source) = config.resolved_data_dir().unwrap();
+        assert_eq!(source, modules::account::DataDirSource::Profile);
+        assert!(dir.ends_with("profiles/work"));
+        let printed = config.print_config();
+        assert!(printed.starts_with("# 数据目录解析顺序: --data-dir > --profile > ANTIGRAVITY_DATA_DIR"));
+        assert!(printed.contains("(来源: profile)"));
+        assert!(printed.contains("profile = \"work\""));
+
+        assert!(Args::try_parse_from(["antigravity-server", "--profile", "../x"]).is_err());
+    }
+}
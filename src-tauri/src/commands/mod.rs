@@ -103,6 +103,115 @@ pub async fn reorder_accounts(account_ids: Vec<String>) -> Result<(), String> {
     })
 }
 
+/// 移动单个账号到指定位置或指定账号之前
+#[tauri::command]
+pub async fn move_account(
+    account_id: String,
+    position: Option<usize>,
+    before: Option<String>,
+) -> Result<(), String> {
+    let target = match (position, before) {
+        (Some(position), None) => modules::account::MoveTarget::Position(position),
+        (None, Some(before)) => modules::account::MoveTarget::Before(before),
+        _ => return Err("position 与 before 须且只能指定一个".to_string()),
+    };
+    modules::account::move_account(&account_id, target)
+}
+
+/// 置顶/取消置顶账号
+#[tauri::command]
+pub async fn set_account_pinned(account_id: String, pinned: bool) -> Result<Account, String> {
+    modules::account::set_account_pinned(&account_id, pinned)
+}
+
+/// 设置账号的上游端点，为空时使用反代的默认端点
+#[tauri::command]
+pub async fn set_account_endpoint(
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    account_id: String,
+    endpoint: Option<String>,
+) -> Result<Account, String> {
+    let account = modules::account::set_account_endpoint(&account_id, endpoint.as_deref())?;
+    let _ = crate::commands::proxy::reload_proxy_accounts(proxy_state).await;
+    Ok(account)
+}
+
+/// 发现账号可用的项目并保存选中的 project_id
+#[tauri::command]
+pub async fn discover_account_project(
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    account_id: String,
+) -> Result<modules::project::ProjectDiscovery, String> {
+    let discovery = modules::project::discover_project(&account_id).await?;
+    let _ = crate::commands::proxy::reload_proxy_accounts(proxy_state).await;
+    Ok(discovery)
+}
+
+/// 手动设置账号的 project_id，为空时清除
+#[tauri::command]
+pub async fn set_account_project_id(
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    account_id: String,
+    project_id: Option<String>,
+) -> Result<Account, String> {
+    let account = modules::project::set_project_id(&account_id, project_id.as_deref())?;
+    let _ = crate::commands::proxy::reload_proxy_accounts(proxy_state).await;
+    Ok(account)
+}
+
+/// 设置或清除账号的地区限制标记
+#[tauri::command]
+pub async fn set_account_region_blocked(
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    account_id: String,
+    region_blocked: bool,
+) -> Result<Account, String> {
+    let account = modules::account::set_region_blocked(&account_id, region_blocked)?;
+    let _ = crate::commands::proxy::reload_proxy_accounts(proxy_state).await;
+    Ok(account)
+}
+
+/// 设置账号标签，用于反代分组调度
+#[tauri::command]
+pub async fn set_account_tags(
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    account_id: String,
+    tags: Vec<String>,
+) -> Result<Account, String> {
+    let account = modules::account::set_tags(&account_id, &tags)?;
+    let _ = crate::commands::proxy::reload_proxy_accounts(proxy_state).await;
+    Ok(account)
+}
+
+/// 解除账号的 forbidden 标记 (申诉恢复后)
+#[tauri::command]
+pub async fn clear_account_forbidden(
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    account_id: String,
+) -> Result<Account, String> {
+    let account = modules::account::clear_forbidden(&account_id)?;
+    let _ = crate::commands::proxy::reload_proxy_accounts(proxy_state).await;
+    Ok(account)
+}
+
+/// 合并同邮箱的重复账号，`dry_run` 时只返回计划
+#[tauri::command]
+pub async fn dedupe_accounts(dry_run: bool) -> Result<modules::account::DedupeReport, String> {
+    modules::account::dedupe_accounts(dry_run)
+}
+
+/// 修复损坏的账号文件，无法修复的移入 accounts/.corrupt/
+#[tauri::command]
+pub async fn repair_accounts(
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+) -> Result<modules::account_repair::RepairReport, String> {
+    let report = modules::account_repair::repair()?;
+    if !report.repaired.is_empty() || !report.quarantined.is_empty() {
+        let _ = crate::commands::proxy::reload_proxy_accounts(proxy_state).await;
+    }
+    Ok(report)
+}
+
 /// 切换账号
 #[tauri::command]
 pub async fn switch_account(app: tauri::AppHandle, account_id: String) -> Result<(), String> {
@@ -113,6 +222,22 @@ pub async fn switch_account(app: tauri::AppHandle, account_id: String) -> Result
     res
 }
 
+/// 立即将当前账号轮换到下一个候选账号
+#[tauri::command]
+pub async fn rotate_account_now(app: tauri::AppHandle) -> Result<crate::modules::rotation::RotationStatus, String> {
+    if modules::rotation::rotate_now().await?.is_none() {
+        return Err("没有可轮换的账号".to_string());
+    }
+    crate::modules::tray::update_tray_menus(&app);
+    modules::rotation::status()
+}
+
+/// 获取账号定时轮换状态
+#[tauri::command]
+pub async fn get_rotation_status() -> Result<crate::modules::rotation::RotationStatus, String> {
+    modules::rotation::status()
+}
+
 /// 获取当前账号
 #[tauri::command]
 pub async fn get_current_account() -> Result<Option<Account>, String> {
@@ -296,11 +421,24 @@ pub async fn save_config(
     proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
     config: AppConfig,
 ) -> Result<(), String> {
+    let issues = config.validate();
+    if !issues.is_empty() {
+        return Err(crate::proxy::ConfigIssue::summarize(&issues));
+    }
     modules::save_app_config(&config)?;
 
     // 通知托盘配置已更新
     let _ = app.emit("config://updated", ());
 
+    crate::proxy::pricing::update(&config.pricing);
+    crate::modules::i18n::update(&config);
+
+    if let Some(monitor) = proxy_state.monitor.read().await.as_ref() {
+        monitor
+            .set_limits(config.proxy.monitor_buffer_size, config.proxy.monitor_max_memory_mb)
+            .await;
+    }
+
     // 热更新正在运行的服务
     let instance_lock = proxy_state.instance.read().await;
     if let Some(instance) = instance_lock.as_ref() {
@@ -312,11 +450,20 @@ pub async fn save_config(
             .update_proxy(config.proxy.upstream_proxy.clone())
             .await;
         // 更新安全策略 (auth)
-        instance.axum_server.update_security(&config.proxy).await;
+        if instance.axum_server.update_security(&config.proxy).await {
+            let summary = instance.axum_server.security().await.summary();
+            let _ = app.emit("proxy://security-updated", summary);
+        }
         // 更新 z.ai 配置
         instance.axum_server.update_zai(&config.proxy).await;
         // 更新实验性配置
         instance.axum_server.update_experimental(&config.proxy).await;
+        // 更新提示词规则
+        instance.axum_server.update_prompt_rules(&config.proxy).await;
+        // 更新响应头透传与注入
+        instance.axum_server.update_response_headers(&config.proxy).await;
+        // 更新上游超时与连接池
+        instance.axum_server.update_upstream(&config.proxy);
         tracing::debug!("已同步热更新反代服务配置");
     }
 
@@ -471,52 +618,78 @@ pub async fn cancel_oauth_login() -> Result<(), String> {
 // --- 导入命令 ---
 
 #[tauri::command]
-pub async fn import_v1_accounts(app: tauri::AppHandle) -> Result<Vec<Account>, String> {
-    let accounts = modules::migration::import_from_v1().await?;
-
-    // 对导入的账号尝试刷新一波
-    for mut account in accounts.clone() {
-        let _ = internal_refresh_account_quota(&app, &mut account).await;
+pub async fn import_v1_accounts(
+    app: tauri::AppHandle,
+    on_duplicate: Option<modules::account::OnDuplicate>,
+    dry_run: Option<bool>,
+) -> Result<modules::migration::ImportReport, String> {
+    let options = import_options(on_duplicate, dry_run);
+    let report = modules::migration::import_from_v1(options).await?;
+
+    // 对导入的账号尝试刷新一波 (跳过的账号保持不变)
+    for outcome in &report.outcomes {
+        if outcome.action != modules::account::ImportAction::Skipped {
+            let mut account = outcome.account.clone();
+            let _ = internal_refresh_account_quota(&app, &mut account).await;
+        }
     }
 
-    Ok(accounts)
+    Ok(report)
 }
 
-#[tauri::command]
-pub async fn import_from_db(app: tauri::AppHandle) -> Result<Account, String> {
-    // 同步函数包装为 async
-    let mut account = modules::migration::import_from_db().await?;
+fn import_options(
+    on_duplicate: Option<modules::account::OnDuplicate>,
+    dry_run: Option<bool>,
+) -> modules::migration::ImportOptions {
+    modules::migration::ImportOptions {
+        on_duplicate: on_duplicate.unwrap_or_default(),
+        dry_run: dry_run.unwrap_or(false),
+    }
+}
+
+/// 单账号导入完成后设为当前账号并刷新额度
+async fn finish_single_import(
+    app: &tauri::AppHandle,
+    report: &mut modules::migration::ImportReport,
+) -> Result<(), String> {
+    let Some(outcome) = report.outcomes.first_mut() else {
+        return Ok(());
+    };
 
     // 既然是从数据库导入（即 IDE 当前账号），自动将其设为 Manager 的当前账号
-    let account_id = account.id.clone();
-    modules::account::set_current_account_id(&account_id)?;
+    modules::account::set_current_account_id(&outcome.account.id)?;
 
     // 自动触发刷新额度
-    let _ = internal_refresh_account_quota(&app, &mut account).await;
+    let _ = internal_refresh_account_quota(app, &mut outcome.account).await;
 
     // 刷新托盘图标展示
-    crate::modules::tray::update_tray_menus(&app);
+    crate::modules::tray::update_tray_menus(app);
+    Ok(())
+}
 
-    Ok(account)
+#[tauri::command]
+pub async fn import_from_db(
+    app: tauri::AppHandle,
+    on_duplicate: Option<modules::account::OnDuplicate>,
+    dry_run: Option<bool>,
+) -> Result<modules::migration::ImportReport, String> {
+    let mut report = modules::migration::import_from_db(import_options(on_duplicate, dry_run)).await?;
+    finish_single_import(&app, &mut report).await?;
+    Ok(report)
 }
 
 #[tauri::command]
 #[allow(dead_code)]
-pub async fn import_custom_db(app: tauri::AppHandle, path: String) -> Result<Account, String> {
+pub async fn import_custom_db(
+    app: tauri::AppHandle,
+    path: String,
+    on_duplicate: Option<modules::account::OnDuplicate>,
+    dry_run: Option<bool>,
+) -> Result<modules::migration::ImportReport, String> {
     // 调用重构后的自定义导入函数
-    let mut account = modules::migration::import_from_custom_db_path(path).await?;
-
-    // 自动设为当前账号
-    let account_id = account.id.clone();
-    modules::account::set_current_account_id(&account_id)?;
-
-    // 自动触发刷新额度
-    let _ = internal_refresh_account_quota(&app, &mut account).await;
-
-    // 刷新托盘图标展示
-    crate::modules::tray::update_tray_menus(&app);
-
-    Ok(account)
+    let mut report = modules::migration::import_from_custom_db_path(path, import_options(on_duplicate, dry_run)).await?;
+    finish_single_import(&app, &mut report).await?;
+    Ok(report)
 }
 
 #[tauri::command]
@@ -549,8 +722,8 @@ pub async fn sync_account_from_db(app: tauri::AppHandle) -> Result<Option<Accoun
     }
 
     // 4. 执行完整导入
-    let account = import_from_db(app).await?;
-    Ok(Some(account))
+    let report = import_from_db(app, None, None).await?;
+    Ok(Some(report.into_single()?.account))
 }
 
 /// 保存文本文件 (绕过前端 Scope 限制)
@@ -693,39 +866,24 @@ pub async fn toggle_proxy_status(
         if enable { "启用" } else { "禁用" }
     ));
 
-    // 1. 读取账号文件
-    let data_dir = modules::account::get_data_dir()?;
-    let account_path = data_dir.join("accounts").join(format!("{}.json", account_id));
-
-    if !account_path.exists() {
-        return Err(format!("账号文件不存在: {}", account_id));
-    }
-
-    let content = std::fs::read_to_string(&account_path)
-        .map_err(|e| format!("读取账号文件失败: {}", e))?;
-
-    let mut account_json: serde_json::Value = serde_json::from_str(&content)
-        .map_err(|e| format!("解析账号文件失败: {}", e))?;
+    // 1. 读取账号
+    let mut account = modules::account::load_account(&account_id)?;
 
     // 2. 更新 proxy_disabled 字段
     if enable {
         // 启用反代
-        account_json["proxy_disabled"] = serde_json::Value::Bool(false);
-        account_json["proxy_disabled_reason"] = serde_json::Value::Null;
-        account_json["proxy_disabled_at"] = serde_json::Value::Null;
+        account.proxy_disabled = false;
+        account.proxy_disabled_reason = None;
+        account.proxy_disabled_at = None;
     } else {
         // 禁用反代
-        let now = chrono::Utc::now().timestamp();
-        account_json["proxy_disabled"] = serde_json::Value::Bool(true);
-        account_json["proxy_disabled_at"] = serde_json::Value::Number(now.into());
-        account_json["proxy_disabled_reason"] = serde_json::Value::String(
-            reason.unwrap_or_else(|| "用户手动禁用".to_string())
-        );
+        account.proxy_disabled = true;
+        account.proxy_disabled_at = Some(chrono::Utc::now().timestamp());
+        account.proxy_disabled_reason = Some(reason.unwrap_or_else(|| "用户手动禁用".to_string()));
     }
 
     // 3. 保存到磁盘
-    std::fs::write(&account_path, serde_json::to_string_pretty(&account_json).unwrap())
-        .map_err(|e| format!("写入账号文件失败: {}", e))?;
+    modules::account::save_account(&account)?;
 
     modules::logger::log_info(&format!(
         "账号反代状态已更新: {} ({})",
@@ -3,8 +3,11 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use serde::{Serialize, Deserialize};
 use crate::proxy::{ProxyConfig, TokenManager};
+use crate::proxy::token_manager::AccountAvailability;
 use tokio::time::Duration;
-use crate::proxy::monitor::{ProxyMonitor, ProxyRequestLog, ProxyStats};
+use crate::proxy::monitor::{LogCursorPage, LogFilter, MonitorStatus, ProxyMonitor, ProxyRequestLog, ProxyStats};
+use crate::modules::stats_history::{CostGroupBy, CostReport, DailyStats, UsageHeatmap};
+use crate::utils::tasks::TaskSupervisor;
 
 
 /// 反代服务状态
@@ -13,13 +16,19 @@ pub struct ProxyStatus {
     pub running: bool,
     pub port: u16,
     pub base_url: String,
-    pub active_accounts: usize,
+    /// 反代监听地址，由 `allow_lan_access` 决定
+    pub bind_address: String,
+    /// 账号总数、可用数与不可用原因
+    #[serde(flatten)]
+    pub accounts: AccountAvailability,
 }
 
 /// 反代服务全局状态
 pub struct ProxyServiceState {
     pub instance: Arc<RwLock<Option<ProxyServiceInstance>>>,
     pub monitor: Arc<RwLock<Option<Arc<ProxyMonitor>>>>,
+    /// 反代相关的后台任务 (使用统计与当天汇总落盘)
+    pub tasks: TaskSupervisor,
 }
 
 /// 反代服务实例
@@ -35,6 +44,7 @@ impl ProxyServiceState {
         Self {
             instance: Arc::new(RwLock::new(None)),
             monitor: Arc::new(RwLock::new(None)),
+            tasks: TaskSupervisor::new(),
         }
     }
 }
@@ -53,15 +63,23 @@ pub async fn start_proxy_service(
         return Err("服务已在运行中".to_string());
     }
 
+    let issues = config.validate("");
+    if !issues.is_empty() {
+        return Err(crate::proxy::ConfigIssue::summarize(&issues));
+    }
+
     // Ensure monitor exists
     {
         let mut monitor_lock = state.monitor.write().await;
         if monitor_lock.is_none() {
-            *monitor_lock = Some(Arc::new(ProxyMonitor::new(1000, Some(app_handle.clone()))));
+            let monitor = Arc::new(ProxyMonitor::new(config.monitor_buffer_size, Some(app_handle.clone())));
+            monitor.register_daily_flush(&state.tasks);
+            *monitor_lock = Some(monitor);
         }
         // Sync enabled state from config
         if let Some(monitor) = monitor_lock.as_ref() {
-            monitor.set_enabled(config.enable_logging);
+            monitor.apply_saved(config.enable_logging);
+            monitor.set_limits(config.monitor_buffer_size, config.monitor_max_memory_mb).await;
         }
     }
     
@@ -76,17 +94,27 @@ pub async fn start_proxy_service(
     let token_manager = Arc::new(TokenManager::new(accounts_dir));
     // 同步 UI 传递的调度配置
     token_manager.update_sticky_config(config.scheduling.clone()).await;
+    token_manager.register_usage_flusher(&state.tasks);
     
     // 3. 加载账号
-    let active_accounts = token_manager.load_accounts().await
+    token_manager.load_accounts().await
         .map_err(|e| format!("加载账号失败: {}", e))?;
+    let accounts = token_manager.availability();
     
-    if active_accounts == 0 {
+    if accounts.total_accounts == 0 {
         let zai_enabled = config.zai.enabled
             && !matches!(config.zai.dispatch_mode, crate::proxy::ZaiDispatchMode::Off);
         if !zai_enabled {
             return Err("没有可用账号，请先添加账号".to_string());
         }
+    } else if accounts.usable_accounts == 0 {
+        tracing::warn!(
+            "共 {} 个账号但均不可用 (反代禁用 {}，冷却 {}，403 {})，服务仍将启动",
+            accounts.total_accounts,
+            accounts.excluded.proxy_disabled,
+            accounts.excluded.cooldown,
+            accounts.excluded.forbidden
+        );
     }
     
     // 启动 Axum 服务器
@@ -97,16 +125,37 @@ pub async fn start_proxy_service(
             token_manager.clone(),
             config.custom_mapping.clone(),
             config.request_timeout,
+            config.timeouts.clone(),
             config.upstream_proxy.clone(),
+            crate::proxy::upstream::client::UpstreamConnectionSettings::from_proxy_config(&config),
             crate::proxy::ProxySecurityConfig::from_proxy_config(&config),
             config.zai.clone(),
             monitor.clone(),
             config.experimental.clone(),
-
+            config.prompt_rules.clone(),
+            config.response_headers.clone(),
         ).await {
             Ok((server, handle)) => (server, handle),
             Err(e) => return Err(format!("启动 Axum 服务器失败: {}", e)),
         };
+
+    // 监听已绑定，预热在后台进行，进度通过 proxy://warmup 事件推送
+    if config.warmup {
+        use tauri::Emitter;
+        tokio::spawn(crate::modules::proxy_warmup::run(
+            token_manager.clone(),
+            axum_server.upstream(),
+            config.custom_mapping.clone(),
+            config.warmup_accounts,
+            config.warmup_probe,
+            move |result, done, total| {
+                let _ = app_handle.emit(
+                    "proxy://warmup",
+                    serde_json::json!({ "done": done, "total": total, "result": result }),
+                );
+            },
+        ));
+    }
     
     // 创建服务实例
     let instance = ProxyServiceInstance {
@@ -128,7 +177,8 @@ pub async fn start_proxy_service(
         running: true,
         port: config.port,
         base_url: format!("http://127.0.0.1:{}", config.port),
-        active_accounts,
+        bind_address: config.bind_socket_addr().to_string(),
+        accounts,
     })
 }
 
@@ -148,11 +198,54 @@ pub async fn stop_proxy_service(
         instance.axum_server.stop();
         // 等待服务器任务完成
         instance.server_handle.await.ok();
+        instance.token_manager.flush_usage();
+    }
+    if let Some(monitor) = state.monitor.read().await.as_ref() {
+        monitor.flush_daily_stats();
     }
     
     Ok(())
 }
 
+/// 获取运行中反代实际执行的安全配置 (密钥已脱敏)
+#[tauri::command]
+pub async fn get_proxy_security(
+    state: State<'_, ProxyServiceState>,
+) -> Result<crate::proxy::security::SecuritySummary, String> {
+    let instance_lock = state.instance.read().await;
+    let instance = instance_lock.as_ref().ok_or("反代服务未运行")?;
+    Ok(instance.axum_server.security().await.summary())
+}
+
+/// 获取反代鉴权失败统计与封禁列表
+#[tauri::command]
+pub async fn get_proxy_unauthorized(
+    state: State<'_, ProxyServiceState>,
+    limit: Option<usize>,
+) -> Result<crate::proxy::unauthorized::UnauthorizedReport, String> {
+    let instance_lock = state.instance.read().await;
+    let instance = instance_lock.as_ref().ok_or("反代服务未运行")?;
+    let now = chrono::Utc::now().timestamp_millis();
+    Ok(instance.axum_server.unauthorized().report(limit.unwrap_or(20).clamp(1, 1000), now))
+}
+
+/// 解除反代来源 IP 的封禁，未指定地址时解除全部
+#[tauri::command]
+pub async fn clear_proxy_bans(
+    state: State<'_, ProxyServiceState>,
+    ip: Option<String>,
+) -> Result<usize, String> {
+    let ip = ip
+        .as_deref()
+        .map(str::trim)
+        .filter(|ip| !ip.is_empty())
+        .map(|ip| ip.parse::<std::net::IpAddr>().map_err(|_| format!("无效的 IP 地址: {}", ip)))
+        .transpose()?;
+    let instance_lock = state.instance.read().await;
+    let instance = instance_lock.as_ref().ok_or("反代服务未运行")?;
+    Ok(instance.axum_server.unauthorized().clear_bans(ip, chrono::Utc::now().timestamp_millis()))
+}
+
 /// 获取反代服务状态
 #[tauri::command]
 pub async fn get_proxy_status(
@@ -165,13 +258,15 @@ pub async fn get_proxy_status(
             running: true,
             port: instance.config.port,
             base_url: format!("http://127.0.0.1:{}", instance.config.port),
-            active_accounts: instance.token_manager.len(),
+            bind_address: instance.config.bind_socket_addr().to_string(),
+            accounts: instance.token_manager.availability(),
         }),
         None => Ok(ProxyStatus {
             running: false,
             port: 0,
             base_url: String::new(),
-            active_accounts: 0,
+            bind_address: String::new(),
+            accounts: AccountAvailability::default(),
         }),
     }
 }
@@ -189,43 +284,160 @@ pub async fn get_proxy_stats(
     }
 }
 
+/// 获取按天汇总的请求统计
+#[tauri::command]
+pub async fn get_proxy_daily_stats(
+    state: State<'_, ProxyServiceState>,
+    days: Option<usize>,
+) -> Result<Vec<DailyStats>, String> {
+    let days = days.unwrap_or(30).clamp(1, 400);
+    let monitor = state.monitor.read().await.clone();
+    tokio::task::spawn_blocking(move || match monitor {
+        Some(monitor) => monitor.daily_stats(days),
+        None => crate::modules::stats_history::recent(days, None),
+    })
+    .await
+    .map_err(|e| format!("读取统计历史失败: {}", e))?
+}
+
+/// 获取按账号的小时请求热力图
+#[tauri::command]
+pub async fn get_usage_heatmap(
+    state: State<'_, ProxyServiceState>,
+    days: Option<usize>,
+) -> Result<UsageHeatmap, String> {
+    let days = days.unwrap_or(7).clamp(1, 14);
+    let monitor = state.monitor.read().await.clone();
+    tokio::task::spawn_blocking(move || match monitor {
+        Some(monitor) => monitor.usage_heatmap(days),
+        None => crate::modules::stats_history::heatmap(days, None),
+    })
+    .await
+    .map_err(|e| format!("读取统计历史失败: {}", e))?
+}
+
+/// 获取按账号、API 密钥或模型分组的估算费用
+#[tauri::command]
+pub async fn get_usage_cost(
+    state: State<'_, ProxyServiceState>,
+    group_by: Option<CostGroupBy>,
+    window: Option<String>,
+) -> Result<CostReport, String> {
+    let group_by = group_by.unwrap_or_default();
+    let days = crate::modules::stats_history::parse_window_days(window.as_deref().unwrap_or("30d"))?;
+    let monitor = state.monitor.read().await.clone();
+    tokio::task::spawn_blocking(move || match monitor {
+        Some(monitor) => monitor.cost_report(group_by, days),
+        None => crate::modules::stats_history::cost_report(group_by, days, None),
+    })
+    .await
+    .map_err(|e| format!("读取统计历史失败: {}", e))?
+}
+
 /// 获取反代请求日志
 #[tauri::command]
 pub async fn get_proxy_logs(
     state: State<'_, ProxyServiceState>,
     limit: Option<usize>,
+    client_ip: Option<String>,
+    blocked: Option<bool>,
 ) -> Result<Vec<ProxyRequestLog>, String> {
     let monitor_lock = state.monitor.read().await;
     if let Some(monitor) = monitor_lock.as_ref() {
-        Ok(monitor.get_logs(limit.unwrap_or(100)).await)
+        Ok(monitor.get_logs_filtered(limit.unwrap_or(100), client_ip.as_deref(), None, blocked).await)
     } else {
         Ok(Vec::new())
     }
 }
 
-/// 设置监控开启状态
+/// 增量获取序号大于 `after_seq` 的请求日志
+#[tauri::command]
+pub async fn get_proxy_logs_after(
+    state: State<'_, ProxyServiceState>,
+    after_seq: u64,
+    limit: Option<usize>,
+) -> Result<LogCursorPage, String> {
+    let limit = limit.unwrap_or(100).clamp(1, 1000);
+    let monitor_lock = state.monitor.read().await;
+    Ok(match monitor_lock.as_ref() {
+        Some(monitor) => monitor.logs_after(after_seq, limit).await,
+        None => LogCursorPage {
+            logs: Vec::new(),
+            latest_seq: after_seq,
+            has_more: false,
+            truncated: false,
+        },
+    })
+}
+
+/// 获取监控开关状态
+#[tauri::command]
+pub async fn get_proxy_monitor_status(
+    state: State<'_, ProxyServiceState>,
+) -> Result<MonitorStatus, String> {
+    let config = crate::modules::config::load_app_config()?;
+    let persisted = config.proxy.enable_logging;
+    match state.monitor.read().await.as_ref() {
+        Some(monitor) => Ok(monitor.status(persisted).await),
+        None => Ok(MonitorStatus {
+            enabled: persisted,
+            buffer_size: config.proxy.monitor_buffer_size,
+            entries: 0,
+            persisted,
+        }),
+    }
+}
+
+/// 设置监控开启状态，默认写入配置；`transient` 为 true 时只临时切换
 #[tauri::command]
 pub async fn set_proxy_monitor_enabled(
     state: State<'_, ProxyServiceState>,
     enabled: bool,
-) -> Result<(), String> {
-    let monitor_lock = state.monitor.read().await;
-    if let Some(monitor) = monitor_lock.as_ref() {
-        monitor.set_enabled(enabled);
+    transient: Option<bool>,
+) -> Result<MonitorStatus, String> {
+    {
+        let monitor_lock = state.monitor.read().await;
+        if transient.unwrap_or(false) {
+            let monitor = monitor_lock
+                .as_ref()
+                .ok_or("反代服务尚未启动，无法临时切换监控")?;
+            monitor.set_transient(enabled);
+        } else {
+            crate::proxy::monitor::save_enabled(enabled)?;
+            if let Some(monitor) = monitor_lock.as_ref() {
+                monitor.set_enabled(enabled);
+            }
+        }
     }
-    Ok(())
+    get_proxy_monitor_status(state).await
 }
 
 /// 清除反代请求日志
 #[tauri::command]
 pub async fn clear_proxy_logs(
     state: State<'_, ProxyServiceState>,
-) -> Result<(), String> {
+    before: Option<i64>,
+    status_class: Option<String>,
+    model: Option<String>,
+    history: Option<bool>,
+) -> Result<usize, String> {
+    let filter = LogFilter {
+        before,
+        status_class: status_class.as_deref().map(LogFilter::parse_status_class).transpose()?,
+        model: model.map(|m| m.trim().to_string()).filter(|m| !m.is_empty()),
+    };
+    let history = history.unwrap_or(false);
     let monitor_lock = state.monitor.read().await;
+    let mut removed = 0;
     if let Some(monitor) = monitor_lock.as_ref() {
-        monitor.clear().await;
+        removed = monitor.clear_matching(&filter).await;
+        if history {
+            monitor.clear_history();
+        }
+    } else if history {
+        crate::modules::stats_history::clear()?;
     }
-    Ok(())
+    Ok(removed)
 }
 
 /// 获取反代请求日志 (分页)
@@ -233,10 +445,15 @@ pub async fn clear_proxy_logs(
 pub async fn get_proxy_logs_paginated(
     limit: Option<usize>,
     offset: Option<usize>,
+    client_ip: Option<String>,
+    blocked: Option<bool>,
 ) -> Result<Vec<ProxyRequestLog>, String> {
-    crate::modules::proxy_db::get_logs_summary(
+    crate::modules::proxy_db::get_logs_filtered(
         limit.unwrap_or(20),
-        offset.unwrap_or(0)
+        offset.unwrap_or(0),
+        client_ip.as_deref(),
+        None,
+        blocked,
     )
 }
 
@@ -248,6 +465,19 @@ pub async fn get_proxy_log_detail(
     crate::modules::proxy_db::get_log_detail(&log_id)
 }
 
+/// 经当前反代链路重放日志记录的请求
+#[tauri::command]
+pub async fn replay_proxy_log(
+    state: State<'_, ProxyServiceState>,
+    log_id: String,
+    same_account: Option<bool>,
+) -> Result<crate::modules::replay::ReplayResult, String> {
+    let monitor = state.monitor.read().await.clone().ok_or("反代服务未运行")?;
+    let instance_lock = state.instance.read().await;
+    let instance = instance_lock.as_ref().ok_or("反代服务未运行")?;
+    crate::modules::replay::replay(&instance.axum_server, &monitor, &log_id, same_account.unwrap_or(false)).await
+}
+
 /// 生成 API Key
 #[tauri::command]
 pub fn generate_api_key() -> String {
@@ -30,6 +30,9 @@ fn greet(name: &str) -> String {
 pub fn run() {
     // 初始化日志
     logger::init_logger();
+
+    // 检查数据格式版本并执行迁移；失败时在 setup 中提示并退出
+    let schema_check = modules::schema::ensure_current();
     
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
@@ -49,7 +52,16 @@ pub fn run() {
                 });
         }))
         .manage(commands::proxy::ProxyServiceState::new())
-        .setup(|app| {
+        .setup(move |app| {
+            if let Err(e) = schema_check {
+                use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+                error!("{}", e);
+                app.dialog()
+                    .message(e)
+                    .kind(MessageDialogKind::Error)
+                    .show(|_| std::process::exit(1));
+                return Ok(());
+            }
             info!("Setup starting...");
             modules::tray::create_tray(app.handle())?;
             info!("Tray created");
@@ -75,8 +87,82 @@ pub fn run() {
                 }
             });
             
+            // 监听配置文件外部修改
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let result = modules::config_watcher::spawn(move |config| {
+                    let handle = handle.clone();
+                    async move {
+                        use tauri::Emitter;
+                        let _ = handle.emit("config://updated", ());
+                        proxy::pricing::update(&config.pricing);
+                        modules::i18n::update(&config);
+                        let state = handle.state::<commands::proxy::ProxyServiceState>();
+                        let instance_lock = state.instance.read().await;
+                        if let Some(instance) = instance_lock.as_ref() {
+                            instance.axum_server.update_mapping(&config.proxy).await;
+                            instance
+                                .axum_server
+                                .update_proxy(config.proxy.upstream_proxy.clone())
+                                .await;
+                            if instance.axum_server.update_security(&config.proxy).await {
+                                let summary = instance.axum_server.security().await.summary();
+                                let _ = handle.emit("proxy://security-updated", summary);
+                            }
+                            instance.axum_server.update_zai(&config.proxy).await;
+                            instance.axum_server.update_experimental(&config.proxy).await;
+                            instance.axum_server.update_prompt_rules(&config.proxy).await;
+                            instance.axum_server.update_response_headers(&config.proxy).await;
+                            instance.axum_server.update_upstream(&config.proxy);
+                        }
+                    }
+                });
+                if let Err(e) = result {
+                    error!("{}", e);
+                }
+            });
+
+            // 监听账号文件外部修改 (如同步工具)
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let watch = modules::accounts_watcher::watch(move || {
+                    let handle = handle.clone();
+                    async move {
+                        use tauri::Emitter;
+                        modules::account::account_store().invalidate();
+                        let state = handle.state::<commands::proxy::ProxyServiceState>();
+                        let instance_lock = state.instance.read().await;
+                        if let Some(instance) = instance_lock.as_ref() {
+                            if let Err(e) = instance.token_manager.load_accounts().await {
+                                error!("重新加载反代账号失败: {}", e);
+                            }
+                        }
+                        let _ = handle.emit("accounts://reloaded", ());
+                    }
+                });
+                match watch {
+                    Ok(watch) => watch.await,
+                    Err(e) => error!("{}", e),
+                }
+            });
+
+            // 当前账号配额耗尽自动切换后通知前端并刷新托盘
+            let handle = app.handle().clone();
+            modules::auto_switch::set_listener(move |switch| {
+                use tauri::Emitter;
+                let _ = handle.emit("tray://account-switched", switch.account.id.clone());
+                modules::tray::update_tray_menus(&handle);
+            });
+
             // 启动智能调度器
             modules::scheduler::start_scheduler(app.handle().clone());
+
+            // 定时轮换当前账号，与反代后台任务由同一监管器管理
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                let state = handle.state::<commands::proxy::ProxyServiceState>();
+                state.tasks.register("account-rotation", modules::rotation::check_loop);
+            });
             
             Ok(())
         })
@@ -99,7 +185,19 @@ pub fn run() {
             commands::delete_account,
             commands::delete_accounts,
             commands::reorder_accounts,
+            commands::move_account,
+            commands::set_account_pinned,
+            commands::set_account_endpoint,
+            commands::discover_account_project,
+            commands::set_account_project_id,
+            commands::set_account_region_blocked,
+            commands::set_account_tags,
+            commands::clear_account_forbidden,
+            commands::dedupe_accounts,
+            commands::repair_accounts,
             commands::switch_account,
+            commands::rotate_account_now,
+            commands::get_rotation_status,
             // 设备指纹
             commands::get_device_profiles,
             commands::bind_device_profile,
@@ -144,10 +242,19 @@ pub fn run() {
             commands::proxy::start_proxy_service,
             commands::proxy::stop_proxy_service,
             commands::proxy::get_proxy_status,
+            commands::proxy::get_proxy_security,
+            commands::proxy::get_proxy_unauthorized,
+            commands::proxy::clear_proxy_bans,
             commands::proxy::get_proxy_stats,
+            commands::proxy::get_proxy_logs_after,
+            commands::proxy::get_proxy_daily_stats,
+            commands::proxy::get_usage_heatmap,
+            commands::proxy::get_usage_cost,
             commands::proxy::get_proxy_logs,
             commands::proxy::get_proxy_logs_paginated,
             commands::proxy::get_proxy_log_detail,
+            commands::proxy::replay_proxy_log,
+            commands::proxy::get_proxy_monitor_status,
             commands::proxy::set_proxy_monitor_enabled,
             commands::proxy::clear_proxy_logs,
             commands::proxy::generate_api_key,
@@ -1,9 +1,10 @@
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 use std::collections::HashSet;
 use super::{token::TokenData, quota::QuotaData};
 
 /// 账号数据结构
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Account {
     pub id: String,
     pub email: String,
@@ -39,6 +40,27 @@ pub struct Account {
     pub protected_models: HashSet<String>,
     pub created_at: i64,
     pub last_used: i64,
+    /// 最近一次被反代使用的时间
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<i64>,
+    /// 反代累计使用次数
+    #[serde(default)]
+    pub total_requests: u64,
+    /// 置顶账号始终排在未置顶账号之前
+    #[serde(default)]
+    pub pinned: bool,
+    /// 该账号使用的上游端点 (须在白名单内)，覆盖反代的默认端点
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream_endpoint: Option<String>,
+    /// 上游返回地区不受支持，反代不再调度该账号，需手动清除
+    #[serde(default)]
+    pub region_blocked: bool,
+    /// 标记地区限制的时间 (Unix 秒)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region_blocked_at: Option<i64>,
+    /// 账号标签，用于按分组调度
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 impl Account {
@@ -61,6 +83,13 @@ impl Account {
             protected_models: HashSet::new(),
             created_at: now,
             last_used: now,
+            last_used_at: None,
+            total_requests: 0,
+            pinned: false,
+            upstream_endpoint: None,
+            region_blocked: false,
+            region_blocked_at: None,
+            tags: Vec::new(),
         }
     }
 
@@ -108,7 +137,7 @@ impl Default for AccountIndex {
 }
 
 /// 设备指纹（storage.json 中 telemetry 相关字段）
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct DeviceProfile {
     pub machine_id: String,
     pub mac_machine_id: String,
@@ -117,7 +146,7 @@ pub struct DeviceProfile {
 }
 
 /// 指纹历史版本
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct DeviceProfileVersion {
     pub id: String,
     pub created_at: i64,
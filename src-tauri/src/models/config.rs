@@ -1,8 +1,11 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
-use crate::proxy::ProxyConfig;
+use utoipa::ToSchema;
+use crate::proxy::{ConfigIssue, ProxyConfig};
 
 /// 应用配置
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct AppConfig {
     pub language: String,
     pub theme: String,
@@ -21,10 +24,325 @@ pub struct AppConfig {
     pub scheduled_warmup: ScheduledWarmupConfig, // [NEW] 定时预热配置
     #[serde(default)]
     pub quota_protection: QuotaProtectionConfig, // [NEW] 配额保护配置
+    #[serde(default)]
+    pub update_channel: UpdateChannel, // [NEW] 更新通道
+    #[serde(default)]
+    pub oauth: OAuthConfig, // [NEW] Web 模式 OAuth 回调配置
+    #[serde(default)]
+    pub auto_switch_on_exhaustion: bool, // [NEW] 当前账号配额耗尽时自动切换
+    #[serde(default)]
+    pub web_auth: WebAuthConfig, // [NEW] 管理 API 访问令牌
+    /// 管理页面的外部访问地址 (如 `https://tools.example.com/antigravity`)，经反向代理部署时用于生成绝对链接；为空时根据请求推断
+    #[serde(default)]
+    pub public_url: Option<String>,
+    /// 服务端定时与 IDE 数据库同步的间隔 (分钟)，0 表示关闭
+    #[serde(default)]
+    pub db_sync_interval_minutes: u32,
+    /// 上游未返回重置时间时，假定配额在每天该 UTC 小时 (0-23) 重置；为空时不推算
+    #[serde(default)]
+    pub quota_reset_hour_utc: Option<u32>,
+    /// SSE 事件流推送 `StatsTick` 的间隔 (秒)，0 表示关闭
+    #[serde(default = "default_sse_stats_interval_secs")]
+    pub sse_stats_interval_secs: u64,
+    /// `/api/events` 同时连接数上限，0 表示不限制
+    #[serde(default = "default_sse_max_subscribers")]
+    pub sse_max_subscribers: usize,
+    /// SSE 广播通道容量 (事件条数)，订阅者落后超过该值时丢弃事件；重启后生效
+    #[serde(default = "default_sse_channel_capacity")]
+    pub sse_channel_capacity: usize,
+    /// 定时轮换当前账号
+    #[serde(default)]
+    pub rotation: RotationConfig,
+    /// 模型单价表，键为模型名或通配模式 (如 `gemini-2.5-*`)，用于估算请求费用；保存后立即生效
+    #[serde(default)]
+    pub pricing: BTreeMap<String, ModelPrice>,
+    /// 配额查询的重试与退避策略
+    #[serde(default)]
+    pub quota_retry: QuotaRetryPolicy,
+    /// 日志与审计日志中错误信息的语言 (`zh` / `en`)，不随请求语言变化
+    #[serde(default = "default_log_language")]
+    pub log_language: String,
+}
+
+/// 配额查询失败时可重试的情况
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum QuotaRetryOn {
+    /// 连接失败或超时
+    #[serde(rename = "timeout")]
+    Timeout,
+    /// 上游返回 5xx
+    #[serde(rename = "5xx")]
+    ServerError,
+    /// 上游返回 429
+    #[serde(rename = "429")]
+    RateLimited,
+}
+
+/// 配额查询的重试策略：第 n 次重试前等待 `initial_backoff_ms * 2^(n-1)`，不超过 `max_backoff_ms`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct QuotaRetryPolicy {
+    /// 最多尝试次数 (含首次)
+    #[serde(default = "default_quota_max_attempts")]
+    pub max_attempts: u32,
+    #[serde(default = "default_quota_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    #[serde(default = "default_quota_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    /// 哪些失败需要重试，其余失败直接返回
+    #[serde(default = "default_quota_retry_on")]
+    pub retry_on: Vec<QuotaRetryOn>,
+}
+
+fn default_quota_max_attempts() -> u32 {
+    3
+}
+
+fn default_quota_initial_backoff_ms() -> u64 {
+    1000
+}
+
+fn default_quota_max_backoff_ms() -> u64 {
+    30_000
+}
+
+fn default_quota_retry_on() -> Vec<QuotaRetryOn> {
+    vec![QuotaRetryOn::Timeout, QuotaRetryOn::ServerError, QuotaRetryOn::RateLimited]
+}
+
+impl Default for QuotaRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_quota_max_attempts(),
+            initial_backoff_ms: default_quota_initial_backoff_ms(),
+            max_backoff_ms: default_quota_max_backoff_ms(),
+            retry_on: default_quota_retry_on(),
+        }
+    }
+}
+
+/// 模型单价 (每 1000 Token)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ModelPrice {
+    pub input_per_1k: f64,
+    pub output_per_1k: f64,
+    /// 货币代码，默认 USD
+    #[serde(default = "default_price_currency")]
+    pub currency: String,
+}
+
+fn default_price_currency() -> String {
+    "USD".to_string()
+}
+
+/// 定时轮换当前 (IDE 使用的) 账号
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RotationConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 轮换间隔 (小时)
+    #[serde(default = "default_rotation_interval_hours")]
+    pub interval_hours: u32,
+    /// 按顺序轮换的账号 ID，为空时在全部可用账号中按列表顺序轮换
+    #[serde(default)]
+    pub candidates: Vec<String>,
+}
+
+fn default_rotation_interval_hours() -> u32 {
+    24
+}
+
+impl Default for RotationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_hours: default_rotation_interval_hours(),
+            candidates: Vec::new(),
+        }
+    }
+}
+
+/// 管理 API 访问控制，未配置任何令牌时不鉴权
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct WebAuthConfig {
+    #[serde(default)]
+    pub tokens: Vec<WebApiToken>,
+    /// 允许访问管理 API 的客户端网段 (CIDR)，为空时不限制；`/api/health` 不受限制
+    #[serde(default)]
+    pub allowed_ips: Vec<String>,
+    /// 可信反向代理 (CIDR)，仅来自这些地址的请求才采信 `X-Forwarded-For` / `X-Real-IP`
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
+    /// 高开销接口的限流
+    #[serde(default)]
+    pub rate_limits: WebRateLimits,
+}
+
+/// 管理 API 高开销接口的限流，按客户端 (管理令牌或 IP) 分别计数；数值为每分钟请求数，0 表示不限制
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct WebRateLimits {
+    #[serde(default = "default_rate_limit_enabled")]
+    pub enabled: bool,
+    /// 配额刷新 (refresh-all、单账号刷新)
+    #[serde(default = "default_quota_refresh_per_minute")]
+    pub quota_refresh_per_minute: u32,
+    /// Token 检查 (validate-all、单账号检查)
+    #[serde(default = "default_token_validation_per_minute")]
+    pub token_validation_per_minute: u32,
+    /// OAuth 授权
+    #[serde(default = "default_oauth_per_minute")]
+    pub oauth_per_minute: u32,
+    /// 账号导入与 IDE 数据库同步
+    #[serde(default = "default_import_per_minute")]
+    pub import_per_minute: u32,
+}
+
+fn default_sse_stats_interval_secs() -> u64 {
+    5
+}
+
+fn default_sse_max_subscribers() -> usize {
+    50
+}
+
+fn default_log_language() -> String {
+    "zh".to_string()
+}
+
+pub fn default_sse_channel_capacity() -> usize {
+    256
+}
+
+fn default_rate_limit_enabled() -> bool {
+    true
+}
+
+fn default_quota_refresh_per_minute() -> u32 {
+    10
+}
+
+fn default_token_validation_per_minute() -> u32 {
+    10
+}
+
+fn default_oauth_per_minute() -> u32 {
+    20
+}
+
+fn default_import_per_minute() -> u32 {
+    10
+}
+
+impl Default for WebRateLimits {
+    fn default() -> Self {
+        Self {
+            enabled: default_rate_limit_enabled(),
+            quota_refresh_per_minute: default_quota_refresh_per_minute(),
+            token_validation_per_minute: default_token_validation_per_minute(),
+            oauth_per_minute: default_oauth_per_minute(),
+            import_per_minute: default_import_per_minute(),
+        }
+    }
+}
+
+/// 管理 API 访问令牌
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct WebApiToken {
+    pub name: String,
+    pub token: String,
+    #[serde(default)]
+    pub role: WebApiRole,
+}
+
+/// 管理 API 角色
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WebApiRole {
+    /// 可调用全部接口
+    #[default]
+    Admin,
+    /// 仅可读取 (GET 接口与事件流)
+    Readonly,
+}
+
+impl WebApiRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebApiRole::Admin => "admin",
+            WebApiRole::Readonly => "readonly",
+        }
+    }
+
+    /// 是否具备 `required` 所需的权限
+    pub fn allows(&self, required: WebApiRole) -> bool {
+        matches!((self, required), (WebApiRole::Admin, _) | (_, WebApiRole::Readonly))
+    }
+}
+
+impl WebAuthConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.tokens.is_empty()
+    }
+
+    /// 校验令牌，返回匹配的令牌项
+    pub fn authenticate(&self, token: &str) -> Option<&WebApiToken> {
+        if token.is_empty() {
+            return None;
+        }
+        self.tokens.iter().find(|t| t.token == token)
+    }
+}
+
+/// Web 模式 OAuth 回调配置
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct OAuthConfig {
+    /// 默认回调端口 (redirect_uri 为 `http://localhost:<port>/callback`)
+    #[serde(default = "default_oauth_redirect_port")]
+    pub redirect_port: u16,
+
+    /// 允许使用的备用端口，默认端口被占用时依次尝试
+    #[serde(default)]
+    pub fallback_ports: Vec<u16>,
+}
+
+fn default_oauth_redirect_port() -> u16 {
+    9004
+}
+
+impl OAuthConfig {
+    /// 允许的端口，默认端口在前
+    pub fn allowed_ports(&self) -> Vec<u16> {
+        let mut ports = vec![self.redirect_port];
+        for port in &self.fallback_ports {
+            if !ports.contains(port) {
+                ports.push(*port);
+            }
+        }
+        ports
+    }
+}
+
+impl Default for OAuthConfig {
+    fn default() -> Self {
+        Self {
+            redirect_port: default_oauth_redirect_port(),
+            fallback_ports: Vec::new(),
+        }
+    }
+}
+
+/// 更新通道
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateChannel {
+    /// 仅正式版
+    #[default]
+    Stable,
+    /// 包含预发布版本
+    Beta,
 }
 
 /// 定时预热配置
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ScheduledWarmupConfig {
     /// 是否启用智能预热
     pub enabled: bool,
@@ -59,7 +377,7 @@ impl Default for ScheduledWarmupConfig {
 }
 
 /// 配额保护配置
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct QuotaProtectionConfig {
     /// 是否启用配额保护
     pub enabled: bool,
@@ -108,6 +426,20 @@ impl AppConfig {
             auto_launch: false,
             scheduled_warmup: ScheduledWarmupConfig::default(),
             quota_protection: QuotaProtectionConfig::default(),
+            update_channel: UpdateChannel::default(),
+            oauth: OAuthConfig::default(),
+            auto_switch_on_exhaustion: false,
+            web_auth: WebAuthConfig::default(),
+            public_url: None,
+            db_sync_interval_minutes: 0,
+            quota_reset_hour_utc: None,
+            sse_stats_interval_secs: default_sse_stats_interval_secs(),
+            sse_max_subscribers: default_sse_max_subscribers(),
+            sse_channel_capacity: default_sse_channel_capacity(),
+            rotation: RotationConfig::default(),
+            pricing: BTreeMap::new(),
+            quota_retry: QuotaRetryPolicy::default(),
+            log_language: default_log_language(),
         }
     }
 }
@@ -117,3 +449,152 @@ impl Default for AppConfig {
         Self::new()
     }
 }
+
+impl AppConfig {
+    /// 校验配置，返回所有问题 (为空表示通过)
+    pub fn validate(&self) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+
+        if self.auto_refresh && self.refresh_interval < 1 {
+            issues.push(ConfigIssue::new("/refresh_interval", "刷新间隔至少为 1 分钟"));
+        }
+        if self.auto_sync && self.sync_interval < 1 {
+            issues.push(ConfigIssue::new("/sync_interval", "同步间隔至少为 1 分钟"));
+        }
+        if !(1..=99).contains(&self.quota_protection.threshold_percentage) {
+            issues.push(ConfigIssue::new(
+                "/quota_protection/threshold_percentage",
+                "保留配额百分比必须在 1-99 之间",
+            ));
+        }
+        if self.quota_reset_hour_utc.is_some_and(|h| h > 23) {
+            issues.push(ConfigIssue::new("/quota_reset_hour_utc", "重置小时必须在 0-23 之间"));
+        }
+        if !(16..=65536).contains(&self.sse_channel_capacity) {
+            issues.push(ConfigIssue::new("/sse_channel_capacity", "SSE 通道容量必须在 16-65536 之间"));
+        }
+        if !(1..=8760).contains(&self.rotation.interval_hours) {
+            issues.push(ConfigIssue::new("/rotation/interval_hours", "轮换间隔必须在 1-8760 小时之间"));
+        }
+
+        if crate::modules::i18n::Lang::parse(&self.log_language).is_none() {
+            issues.push(ConfigIssue::new("/log_language", "日志语言只支持 zh 或 en"));
+        }
+
+        if !(1..=10).contains(&self.quota_retry.max_attempts) {
+            issues.push(ConfigIssue::new("/quota_retry/max_attempts", "尝试次数必须在 1-10 之间"));
+        }
+        if self.quota_retry.max_backoff_ms > 600_000 {
+            issues.push(ConfigIssue::new("/quota_retry/max_backoff_ms", "最大退避时间不能超过 600000 毫秒"));
+        }
+        if self.quota_retry.initial_backoff_ms > self.quota_retry.max_backoff_ms {
+            issues.push(ConfigIssue::new("/quota_retry/initial_backoff_ms", "初始退避时间不能大于最大退避时间"));
+        }
+
+        for (pattern, price) in &self.pricing {
+            let path = format!("/pricing/{}", pattern);
+            if pattern.trim().is_empty() || pattern.matches('*').count() > 1 {
+                issues.push(ConfigIssue::new(path.clone(), "模型模式不能为空，且最多包含一个 *"));
+            }
+            for (field, value) in [("input_per_1k", price.input_per_1k), ("output_per_1k", price.output_per_1k)] {
+                if !value.is_finite() || value < 0.0 {
+                    issues.push(ConfigIssue::new(format!("{}/{}", path, field), "单价必须为非负数"));
+                }
+            }
+            if price.currency.trim().is_empty() {
+                issues.push(ConfigIssue::new(format!("{}/currency", path), "货币代码不能为空"));
+            }
+        }
+
+        if self.oauth.redirect_port == 0 {
+            issues.push(ConfigIssue::new("/oauth/redirect_port", "端口必须在 1-65535 之间"));
+        }
+        for (i, port) in self.oauth.fallback_ports.iter().enumerate() {
+            if *port == 0 {
+                issues.push(ConfigIssue::new(
+                    format!("/oauth/fallback_ports/{}", i),
+                    "端口必须在 1-65535 之间",
+                ));
+            }
+        }
+
+        for (i, token) in self.web_auth.tokens.iter().enumerate() {
+            let path = format!("/web_auth/tokens/{}", i);
+            let name = token.name.trim();
+            if name.is_empty() {
+                issues.push(ConfigIssue::new(format!("{}/name", path), "令牌名称不能为空"));
+            } else if self.web_auth.tokens[..i].iter().any(|other| other.name.trim() == name) {
+                issues.push(ConfigIssue::new(format!("{}/name", path), format!("令牌名称 {} 重复", name)));
+            }
+            if token.token.trim().is_empty() {
+                issues.push(ConfigIssue::new(format!("{}/token", path), "令牌不能为空"));
+            } else if self.web_auth.tokens[..i].iter().any(|other| other.token == token.token) {
+                issues.push(ConfigIssue::new(format!("{}/token", path), "令牌与其他令牌重复"));
+            }
+        }
+
+        for (field, list) in [("allowed_ips", &self.web_auth.allowed_ips), ("trusted_proxies", &self.web_auth.trusted_proxies)] {
+            for (i, message) in crate::proxy::ip_filter::validate_cidrs(list) {
+                issues.push(ConfigIssue::new(format!("/web_auth/{}/{}", field, i), message));
+            }
+        }
+        if let Some(url) = self.public_url.as_deref().filter(|u| !u.trim().is_empty()) {
+            if let Err(message) = crate::proxy::config::validate_url(url, &["http", "https"]) {
+                issues.push(ConfigIssue::new("/public_url", message));
+            }
+        }
+
+        issues.extend(self.proxy.validate("/proxy"));
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config_is_valid() {
+        assert!(AppConfig::new().validate().is_empty());
+    }
+
+    #[test]
+    fn test_validate_reports_paths() {
+        let mut config = AppConfig::new();
+        config.proxy.request_timeout = 0;
+        config.proxy.upstream_proxy.enabled = true;
+        config.proxy.upstream_proxy.url = "not a url".to_string();
+        config.proxy.auth_mode = crate::proxy::ProxyAuthMode::Strict;
+        config.proxy.api_key = String::new();
+        config.proxy.custom_mapping.insert("gpt-*-*".to_string(), "gemini-3-flash".to_string());
+        config.proxy.zai.enabled = true;
+        config.proxy.zai.base_url = "ftp://example.com".to_string();
+        config.public_url = Some("tools.example.com".to_string());
+        config.proxy.public_url = Some("ftp://api.example.com".to_string());
+
+        let paths: Vec<String> = config.validate().into_iter().map(|issue| issue.path).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "/public_url",
+                "/proxy/request_timeout",
+                "/proxy/api_key",
+                "/proxy/public_url",
+                "/proxy/upstream_proxy/url",
+                "/proxy/zai/base_url",
+                "/proxy/custom_mapping/gpt-*-*",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_oauth_allowed_ports() {
+        let oauth: OAuthConfig = serde_json::from_str(r#"{"fallback_ports": [9005, 9004, 9006]}"#).unwrap();
+        assert_eq!(oauth.allowed_ports(), vec![9004, 9005, 9006]);
+
+        let mut config = AppConfig::new();
+        config.oauth.fallback_ports = vec![9005, 0];
+        let paths: Vec<String> = config.validate().into_iter().map(|issue| issue.path).collect();
+        assert_eq!(paths, vec!["/oauth/fallback_ports/1"]);
+    }
+}
@@ -1,7 +1,9 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
 
 /// 模型配额信息
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ModelQuota {
     pub name: String,
     pub percentage: i32,  // 剩余百分比 0-100
@@ -9,15 +11,43 @@ pub struct ModelQuota {
 }
 
 /// 配额数据结构
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct QuotaData {
     pub models: Vec<ModelQuota>,
     pub last_updated: i64,
     #[serde(default)]
     pub is_forbidden: bool,
+    /// 被标记为 forbidden 的原因 (上游错误信息)，手动解除后清空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forbidden_reason: Option<String>,
     /// 订阅等级 (FREE/PRO/ULTRA)
     #[serde(default)]
     pub subscription_tier: Option<String>,
+    /// 按模型族汇总的剩余百分比 (族内最小值)，查询配额时生成；旧数据为空
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub families: BTreeMap<String, i32>,
+    /// 下次配额重置时间 (Unix 秒)：取各模型最早的未来重置时间，上游未提供时按配置的重置小时推算
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resets_at: Option<i64>,
+    /// 距下次重置的秒数，读取账号时按当前时间刷新
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resets_in_seconds: Option<i64>,
+}
+
+/// 模型所属的配额族 (image / claude / flash / pro / other)，同族模型共享上游配额
+pub fn model_family(name: &str) -> &'static str {
+    let name = name.to_ascii_lowercase();
+    if name.contains("image") {
+        "image"
+    } else if name.contains("claude") {
+        "claude"
+    } else if name.contains("flash") {
+        "flash"
+    } else if name.contains("pro") {
+        "pro"
+    } else {
+        "other"
+    }
 }
 
 impl QuotaData {
@@ -26,10 +56,68 @@ impl QuotaData {
             models: Vec::new(),
             last_updated: chrono::Utc::now().timestamp(),
             is_forbidden: false,
+            forbidden_reason: None,
             subscription_tier: None,
+            families: BTreeMap::new(),
+            resets_at: None,
+            resets_in_seconds: None,
+        }
+    }
+
+    /// 各模型 reset_time 中最早的、晚于 now 的时间
+    fn earliest_model_reset(&self, now: i64) -> Option<i64> {
+        self.models
+            .iter()
+            .filter_map(|m| chrono::DateTime::parse_from_rfc3339(&m.reset_time).ok())
+            .map(|t| t.timestamp())
+            .filter(|ts| *ts > now)
+            .min()
+    }
+
+    /// 计算 resets_at；上游没有给出重置时间时，取 UTC 下一个 `fallback_hour_utc` 整点
+    pub fn compute_reset(&mut self, fallback_hour_utc: Option<u32>, now: i64) {
+        self.resets_at = self.earliest_model_reset(now).or_else(|| {
+            let hour = fallback_hour_utc.filter(|h| *h < 24)? as i64;
+            let day_start = now - now.rem_euclid(86_400);
+            let candidate = day_start + hour * 3600;
+            Some(if candidate > now { candidate } else { candidate + 86_400 })
+        });
+        self.update_countdown(now);
+    }
+
+    /// 按当前时间刷新 resets_in_seconds；旧数据没有 resets_at 时从模型重置时间推导
+    pub fn update_countdown(&mut self, now: i64) {
+        if self.resets_at.is_none() {
+            self.resets_at = self.earliest_model_reset(now);
+        }
+        self.resets_in_seconds = self.resets_at.map(|ts| (ts - now).max(0));
+    }
+
+    /// 根据 models 重新计算 families
+    pub fn refresh_families(&mut self) {
+        self.families.clear();
+        for model in &self.models {
+            let entry = self
+                .families
+                .entry(model_family(&model.name).to_string())
+                .or_insert(model.percentage);
+            *entry = (*entry).min(model.percentage);
         }
     }
 
+    /// 请求模型对应的剩余百分比：优先精确匹配模型名，其次取同族模型中的最小值
+    pub fn percentage_for(&self, model: &str) -> Option<i32> {
+        if let Some(quota) = self.models.iter().find(|m| m.name == model) {
+            return Some(quota.percentage);
+        }
+        let family = model_family(model);
+        self.models
+            .iter()
+            .filter(|m| model_family(&m.name) == family)
+            .map(|m| m.percentage)
+            .min()
+    }
+
     pub fn add_model(&mut self, name: String, percentage: i32, reset_time: String) {
         self.models.push(ModelQuota {
             name,
@@ -44,3 +132,58 @@ impl Default for QuotaData {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_per_family_quota() {
+        let mut quota = QuotaData::new();
+        quota.add_model("gemini-3-flash".to_string(), 80, String::new());
+        quota.add_model("gemini-3-pro-high".to_string(), 40, String::new());
+        quota.add_model("gemini-3-pro-low".to_string(), 60, String::new());
+        quota.add_model("gemini-3-pro-image".to_string(), 0, String::new());
+        quota.refresh_families();
+
+        assert_eq!(quota.families.get("pro"), Some(&40));
+        assert_eq!(quota.families.get("image"), Some(&0));
+        assert_eq!(quota.percentage_for("gemini-3-pro-low"), Some(60));
+        assert_eq!(quota.percentage_for("gemini-3-pro-preview"), Some(40));
+        assert_eq!(quota.percentage_for("gemini-3-pro-image-preview"), Some(0));
+        assert_eq!(quota.percentage_for("claude-sonnet-4-5"), None);
+
+        // 没有 families 字段的旧数据仍可读取
+        let old: QuotaData = serde_json::from_str(r#"{"models":[],"last_updated":0}"#).unwrap();
+        assert!(old.families.is_empty());
+    }
+
+    #[test]
+    fn test_reset_countdown() {
+        // 2025-01-01T10:00:00Z
+        let now = 1_735_725_600;
+        let mut quota = QuotaData::new();
+        quota.add_model("a".to_string(), 0, "2025-01-01T09:00:00Z".to_string());
+        quota.add_model("b".to_string(), 0, "2025-01-01T15:00:00+02:00".to_string());
+        quota.add_model("c".to_string(), 0, "2025-01-01T18:00:00Z".to_string());
+        quota.compute_reset(Some(0), now);
+        // 已过去的时间被忽略，时区换算后 13:00Z 最早
+        assert_eq!(quota.resets_at, Some(now + 3 * 3600));
+        assert_eq!(quota.resets_in_seconds, Some(3 * 3600));
+
+        // 上游没有重置时间时按配置的小时推算，已过则取次日
+        let mut quota = QuotaData::new();
+        quota.add_model("a".to_string(), 0, String::new());
+        quota.compute_reset(Some(12), now);
+        assert_eq!(quota.resets_at, Some(now + 2 * 3600));
+        quota.compute_reset(Some(8), now);
+        assert_eq!(quota.resets_at, Some(now + 22 * 3600));
+        quota.compute_reset(None, now);
+        assert_eq!(quota.resets_at, None);
+
+        // 过了重置时间后倒计时归零
+        quota.resets_at = Some(now - 10);
+        quota.update_countdown(now);
+        assert_eq!(quota.resets_in_seconds, Some(0));
+    }
+}
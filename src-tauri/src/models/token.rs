@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct TokenData {
     pub access_token: String,
     pub refresh_token: String,
@@ -36,4 +37,45 @@ impl TokenData {
             session_id,
         }
     }
+
+    /// 脱敏副本，用于接口响应
+    pub fn masked(&self) -> Self {
+        Self {
+            access_token: mask_secret(&self.access_token),
+            refresh_token: mask_secret(&self.refresh_token),
+            ..self.clone()
+        }
+    }
+}
+
+/// 仅保留首尾少量字符
+pub(crate) fn mask_secret(secret: &str) -> String {
+    let chars: Vec<char> = secret.chars().collect();
+    if chars.len() <= 12 {
+        return "*".repeat(chars.len());
+    }
+    let head: String = chars[..6].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}...{}", head, tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_masked_hides_secrets() {
+        let token = TokenData::new(
+            "ya29.a0AfB_byC-access-token".to_string(),
+            "1//0g-refresh-token-value".to_string(),
+            3600,
+            None,
+            None,
+            None,
+        );
+        let masked = token.masked();
+        assert_eq!(masked.access_token, "ya29.a...oken");
+        assert_eq!(masked.refresh_token, "1//0g-...alue");
+        assert_eq!(mask_secret("short"), "*****");
+    }
 }
@@ -1,8 +1,13 @@
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::RwLock;
+use std::time::SystemTime;
 use serde_json;
 use uuid::Uuid;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 use crate::models::{Account, AccountIndex, AccountSummary, TokenData, QuotaData, DeviceProfile, DeviceProfileVersion,};
 use crate::modules;
@@ -16,19 +21,168 @@ static ACCOUNT_INDEX_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
 const DATA_DIR: &str = ".antigravity_tools";
 const ACCOUNTS_INDEX: &str = "accounts.json";
 const ACCOUNTS_DIR: &str = "accounts";
+const TRASH_DIR: &str = "trash";
+
+pub(crate) const PROFILES_DIR: &str = "profiles";
+
+/// 数据目录来源，按优先级从高到低排列
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DataDirSource {
+    /// --data-dir 或配置文件 data_dir
+    Explicit,
+    /// --profile
+    Profile,
+    /// ANTIGRAVITY_DATA_DIR 环境变量
+    Env,
+    /// ~/.antigravity_tools
+    Default,
+}
+
+impl DataDirSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataDirSource::Explicit => "data-dir",
+            DataDirSource::Profile => "profile",
+            DataDirSource::Env => "env",
+            DataDirSource::Default => "default",
+        }
+    }
+}
+
+/// 启动时选定的数据目录 (独立服务端的 --data-dir / --profile)
+#[derive(Default)]
+struct DataDirSelection {
+    explicit: Option<PathBuf>,
+    profile: Option<String>,
+}
+
+static DATA_DIR_SELECTION: Lazy<RwLock<DataDirSelection>> = Lazy::new(Default::default);
+
+/// 校验 profile 名称：仅允许字母、数字、`-` 与 `_`
+pub fn validate_profile_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("profile 名称无效: {:?} (仅允许字母、数字、- 和 _)", name))
+    }
+}
+
+/// 设置显式数据目录与 profile，需在首次访问数据目录前调用
+pub fn select_data_dir(explicit: Option<PathBuf>, profile: Option<String>) -> Result<(), String> {
+    if let Some(name) = &profile {
+        validate_profile_name(name)?;
+    }
+    let mut selection = DATA_DIR_SELECTION.write().map_err(|e| e.to_string())?;
+    *selection = DataDirSelection { explicit, profile };
+    Ok(())
+}
+
+/// 按优先级解析数据目录: 显式目录 > profile > 环境变量 > 默认
+pub fn resolve_data_dir(
+    explicit: Option<&Path>,
+    profile: Option<&str>,
+    env_dir: Option<&Path>,
+    home: &Path,
+) -> (PathBuf, DataDirSource) {
+    let default_dir = home.join(DATA_DIR);
+    if let Some(dir) = explicit {
+        (dir.to_path_buf(), DataDirSource::Explicit)
+    } else if let Some(name) = profile {
+        (default_dir.join(PROFILES_DIR).join(name), DataDirSource::Profile)
+    } else if let Some(dir) = env_dir {
+        (dir.to_path_buf(), DataDirSource::Env)
+    } else {
+        (default_dir, DataDirSource::Default)
+    }
+}
+
+/// 解析当前进程使用的数据目录 (不创建目录)
+pub fn current_data_dir() -> Result<(PathBuf, DataDirSource), String> {
+    let home = dirs::home_dir().ok_or("无法获取用户主目录")?;
+    let env_dir = std::env::var_os("ANTIGRAVITY_DATA_DIR")
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from);
+    let selection = DATA_DIR_SELECTION.read().map_err(|e| e.to_string())?;
+    Ok(resolve_data_dir(
+        selection.explicit.as_deref(),
+        selection.profile.as_deref(),
+        env_dir.as_deref(),
+        &home,
+    ))
+}
+
+/// 当前生效的 profile；显式指定了 --data-dir 时 profile 不生效
+pub fn active_profile() -> Option<String> {
+    let selection = DATA_DIR_SELECTION.read().ok()?;
+    match selection.explicit {
+        Some(_) => None,
+        None => selection.profile.clone(),
+    }
+}
+
+/// profile 信息
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ProfileInfo {
+    pub name: String,
+    pub path: String,
+    pub active: bool,
+}
+
+/// 列出 ~/.antigravity_tools/profiles 下已存在的 profile
+pub fn list_profiles() -> Result<Vec<ProfileInfo>, String> {
+    let home = dirs::home_dir().ok_or("无法获取用户主目录")?;
+    list_profiles_in(&home.join(DATA_DIR).join(PROFILES_DIR), active_profile().as_deref())
+}
+
+fn list_profiles_in(root: &Path, active: Option<&str>) -> Result<Vec<ProfileInfo>, String> {
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("读取 profile 目录失败: {}", e)),
+    };
+    let mut profiles: Vec<ProfileInfo> = entries
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_str()?.to_string();
+            validate_profile_name(&name).ok()?;
+            Some(ProfileInfo {
+                active: active == Some(name.as_str()),
+                path: entry.path().to_string_lossy().to_string(),
+                name,
+            })
+        })
+        .collect();
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(profiles)
+}
+
+/// 首次使用 profile 时写入默认配置与 schema 版本
+fn init_profile(data_dir: &Path) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(&crate::models::AppConfig::new())
+        .map_err(|e| format!("序列化配置失败: {}", e))?;
+    fs::write(data_dir.join(modules::config::CONFIG_FILE), content)
+        .map_err(|e| format!("初始化 profile 配置失败: {}", e))?;
+    modules::schema::mark_current(data_dir)
+}
 
-// ... existing functions get_data_dir, get_accounts_dir, load_account_index, save_account_index ...
 /// 获取数据目录路径
 pub fn get_data_dir() -> Result<PathBuf, String> {
-    let home = dirs::home_dir().ok_or("无法获取用户主目录")?;
-    let data_dir = home.join(DATA_DIR);
-    
+    let (data_dir, source) = current_data_dir()?;
+
     // 确保目录存在
     if !data_dir.exists() {
         fs::create_dir_all(&data_dir)
             .map_err(|e| format!("创建数据目录失败: {}", e))?;
+        if source == DataDirSource::Profile {
+            init_profile(&data_dir)?;
+        }
     }
-    
+
     Ok(data_dir)
 }
 
@@ -45,96 +199,278 @@ pub fn get_accounts_dir() -> Result<PathBuf, String> {
     Ok(accounts_dir)
 }
 
-/// 加载账号索引
-pub fn load_account_index() -> Result<AccountIndex, String> {
-    let data_dir = get_data_dir()?;
-    let index_path = data_dir.join(ACCOUNTS_INDEX);
-    // modules::logger::log_info(&format!("正在加载账号索引: {:?}", index_path)); // Optional: reduce noise
-    
-    if !index_path.exists() {
-        crate::modules::logger::log_warn("账号索引文件不存在");
-        return Ok(AccountIndex::new());
+/// 文件指纹：修改时间 + 长度，用于发现外部修改
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok()?,
+            len: metadata.len(),
+        })
     }
-    
-    let content = fs::read_to_string(&index_path)
-        .map_err(|e| format!("读取账号索引失败: {}", e))?;
-    
-    // 如果文件内容为空，视为新索引
-    if content.trim().is_empty() {
-        crate::modules::logger::log_warn("账号索引文件内容为空，正在初始化新索引");
-        return Ok(AccountIndex::new());
+}
+
+/// 账号数据的内存缓存
+///
+/// 读取时只比对文件指纹，未变化则直接返回内存中的副本；写入时同步落盘。
+/// 其他代码 (如反代的 TokenManager) 直接修改文件时，指纹变化会触发重新加载。
+pub struct AccountStore {
+    /// 账号目录，为空时使用默认数据目录
+    dir: Option<PathBuf>,
+    accounts: RwLock<HashMap<String, (FileStamp, Account)>>,
+    index: RwLock<Option<(FileStamp, AccountIndex)>>,
+    /// 从磁盘解析文件的次数
+    disk_reads: AtomicUsize,
+}
+
+static ACCOUNT_STORE: Lazy<AccountStore> = Lazy::new(|| AccountStore::new(None));
+
+/// 全局账号缓存
+pub fn account_store() -> &'static AccountStore {
+    &ACCOUNT_STORE
+}
+
+impl AccountStore {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self {
+            dir,
+            accounts: RwLock::new(HashMap::new()),
+            index: RwLock::new(None),
+            disk_reads: AtomicUsize::new(0),
+        }
     }
-    
-    let index: AccountIndex = serde_json::from_str(&content)
-        .map_err(|e| format!("解析账号索引失败: {}", e))?;
+
+    fn accounts_dir(&self) -> Result<PathBuf, String> {
+        match &self.dir {
+            Some(dir) => Ok(dir.join(ACCOUNTS_DIR)),
+            None => get_accounts_dir(),
+        }
+    }
+
+    fn index_path(&self) -> Result<PathBuf, String> {
+        match &self.dir {
+            Some(dir) => Ok(dir.join(ACCOUNTS_INDEX)),
+            None => Ok(get_data_dir()?.join(ACCOUNTS_INDEX)),
+        }
+    }
+
+    fn account_path(&self, account_id: &str) -> Result<PathBuf, String> {
+        Ok(self.accounts_dir()?.join(format!("{}.json", account_id)))
+    }
+
+    /// 读取账号，文件未变化时直接使用缓存
+    pub fn get(&self, account_id: &str) -> Result<Account, String> {
+        let path = self.account_path(account_id)?;
+        let Some(stamp) = FileStamp::of(&path) else {
+            self.accounts.write().unwrap_or_else(|e| e.into_inner()).remove(account_id);
+            return Err(format!("账号不存在: {}", account_id));
+        };
+
+        if let Some((cached, account)) = self.accounts.read().unwrap_or_else(|e| e.into_inner()).get(account_id) {
+            if *cached == stamp {
+                return Ok(account.clone());
+            }
+        }
+
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("读取账号数据失败: {}", e))?;
+        let account: Account = serde_json::from_str(&content)
+            .map_err(|e| format!("解析账号数据失败: {}", e))?;
+        self.disk_reads.fetch_add(1, Ordering::Relaxed);
+
+        self.accounts
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(account_id.to_string(), (stamp, account.clone()));
+        Ok(account)
+    }
+
+    /// 写入账号并更新缓存
+    pub fn put(&self, account: &Account) -> Result<(), String> {
+        let path = self.account_path(&account.id)?;
+        let content = serde_json::to_string_pretty(account)
+            .map_err(|e| format!("序列化账号数据失败: {}", e))?;
+        modules::accounts_watcher::write(&path, content)
+            .map_err(|e| format!("保存账号数据失败: {}", e))?;
+
+        let mut accounts = self.accounts.write().unwrap_or_else(|e| e.into_inner());
+        match FileStamp::of(&path) {
+            Some(stamp) => {
+                accounts.insert(account.id.clone(), (stamp, account.clone()));
+            }
+            None => {
+                accounts.remove(&account.id);
+            }
+        }
+        Ok(())
+    }
+
+    /// 删除账号文件并移出缓存
+    pub fn remove(&self, account_id: &str) -> Result<(), String> {
+        self.accounts.write().unwrap_or_else(|e| e.into_inner()).remove(account_id);
+        let path = self.account_path(account_id)?;
+        modules::accounts_watcher::remove(&path)
+            .map_err(|e| format!("删除账号文件失败: {}", e))
+    }
+
+    /// 读取账号索引，文件未变化时直接使用缓存
+    pub fn index(&self) -> Result<AccountIndex, String> {
+        let index_path = self.index_path()?;
+        let Some(stamp) = FileStamp::of(&index_path) else {
+            crate::modules::logger::log_warn("账号索引文件不存在");
+            return Ok(AccountIndex::new());
+        };
+
+        if let Some((cached, index)) = self.index.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+            if *cached == stamp {
+                return Ok(index.clone());
+            }
+        }
+
+        let content = fs::read_to_string(&index_path)
+            .map_err(|e| format!("读取账号索引失败: {}", e))?;
+        
+        // 如果文件内容为空，视为新索引
+        if content.trim().is_empty() {
+            crate::modules::logger::log_warn("账号索引文件内容为空，正在初始化新索引");
+            return Ok(AccountIndex::new());
+        }
         
-    crate::modules::logger::log_info(&format!("成功加载索引，包含 {} 个账号", index.accounts.len()));
-    Ok(index)
+        let index: AccountIndex = serde_json::from_str(&content)
+            .map_err(|e| format!("解析账号索引失败: {}", e))?;
+        self.disk_reads.fetch_add(1, Ordering::Relaxed);
+        crate::modules::logger::log_info(&format!("成功加载索引，包含 {} 个账号", index.accounts.len()));
+
+        *self.index.write().unwrap_or_else(|e| e.into_inner()) = Some((stamp, index.clone()));
+        Ok(index)
+    }
+
+    /// 保存账号索引 (原子化写入) 并更新缓存
+    pub fn save_index(&self, index: &AccountIndex) -> Result<(), String> {
+        let index_path = self.index_path()?;
+        let temp_path = index_path.with_extension("json.tmp");
+        
+        let content = serde_json::to_string_pretty(index)
+            .map_err(|e| format!("序列化账号索引失败: {}", e))?;
+        
+        // 写入临时文件
+        fs::write(&temp_path, &content)
+            .map_err(|e| format!("写入临时索引文件失败: {}", e))?;
+            
+        // 原子重命名
+        fs::rename(temp_path, &index_path)
+            .map_err(|e| format!("替换索引文件失败: {}", e))?;
+        modules::accounts_watcher::note_written(&index_path, Some(content.as_bytes()));
+
+        *self.index.write().unwrap_or_else(|e| e.into_inner()) =
+            FileStamp::of(&index_path).map(|stamp| (stamp, index.clone()));
+        Ok(())
+    }
+
+    /// 清空缓存，下次读取时重新加载
+    pub fn invalidate(&self) {
+        self.accounts.write().unwrap_or_else(|e| e.into_inner()).clear();
+        *self.index.write().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// 从磁盘解析文件的累计次数
+    pub fn disk_reads(&self) -> usize {
+        self.disk_reads.load(Ordering::Relaxed)
+    }
+}
+
+/// 加载账号索引
+pub fn load_account_index() -> Result<AccountIndex, String> {
+    account_store().index()
 }
 
 /// 保存账号索引 (原子化写入)
 pub fn save_account_index(index: &AccountIndex) -> Result<(), String> {
-    let data_dir = get_data_dir()?;
-    let index_path = data_dir.join(ACCOUNTS_INDEX);
-    let temp_path = data_dir.join(format!("{}.tmp", ACCOUNTS_INDEX));
-    
-    let content = serde_json::to_string_pretty(index)
-        .map_err(|e| format!("序列化账号索引失败: {}", e))?;
-    
-    // 写入临时文件
-    fs::write(&temp_path, content)
-        .map_err(|e| format!("写入临时索引文件失败: {}", e))?;
-        
-    // 原子重命名
-    fs::rename(temp_path, index_path)
-        .map_err(|e| format!("替换索引文件失败: {}", e))
+    account_store().save_index(index)
 }
 
 /// 加载账号数据
 pub fn load_account(account_id: &str) -> Result<Account, String> {
-    let accounts_dir = get_accounts_dir()?;
-    let account_path = accounts_dir.join(format!("{}.json", account_id));
-    
-    if !account_path.exists() {
-        return Err(format!("账号不存在: {}", account_id));
-    }
-    
-    let content = fs::read_to_string(&account_path)
-        .map_err(|e| format!("读取账号数据失败: {}", e))?;
-    
-    serde_json::from_str(&content)
-        .map_err(|e| format!("解析账号数据失败: {}", e))
+    account_store().get(account_id)
 }
 
 /// 保存账号数据
 pub fn save_account(account: &Account) -> Result<(), String> {
-    let accounts_dir = get_accounts_dir()?;
-    let account_path = accounts_dir.join(format!("{}.json", account.id));
-    
-    let content = serde_json::to_string_pretty(account)
-        .map_err(|e| format!("序列化账号数据失败: {}", e))?;
-    
-    fs::write(&account_path, content)
-        .map_err(|e| format!("保存账号数据失败: {}", e))
+    account_store().put(account)
+}
+
+/// 以 JSON 形式读取账号文件；位于默认账号目录的文件经由缓存读取
+pub fn load_account_json(path: &Path) -> Result<serde_json::Value, String> {
+    let account_id = path.file_stem().and_then(|s| s.to_str());
+    let in_store = path.parent().is_some_and(|dir| get_accounts_dir().is_ok_and(|d| d == dir));
+    if let (Some(account_id), true) = (account_id, in_store) {
+        let account = account_store().get(account_id)?;
+        return serde_json::to_value(&account).map_err(|e| format!("序列化账号数据失败: {}", e));
+    }
+
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("读取文件失败: {}", e))?;
+    serde_json::from_str(&content)
+        .map_err(|e| format!("解析 JSON 失败: {}", e))
+}
+
+/// 无法读取或解析的账号文件
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct CorruptedAccount {
+    /// 账号目录下的文件名，如 `<id>.json`
+    pub file: String,
+    pub error: String,
+}
+
+impl CorruptedAccount {
+    /// 供 API 响应 `warnings` 使用的说明
+    pub fn warning(&self) -> String {
+        format!("账号文件 {} 已跳过: {}", self.file, self.error)
+    }
 }
 
-/// 列出所有账号
 /// 列出所有账号
 pub fn list_accounts() -> Result<Vec<Account>, String> {
+    list_accounts_checked().map(|(accounts, _)| accounts)
+}
+
+/// 列出所有账号，同时返回被跳过的损坏文件
+pub fn list_accounts_checked() -> Result<(Vec<Account>, Vec<CorruptedAccount>), String> {
     crate::modules::logger::log_info("已开始列出账号...");
     let mut index = load_account_index()?;
     let mut accounts = Vec::new();
     let mut invalid_ids = Vec::new();
+    let mut corrupted = Vec::new();
     
+    let now = chrono::Utc::now().timestamp();
     for summary in &index.accounts {
         match load_account(&summary.id) {
-            Ok(account) => accounts.push(account),
+            Ok(mut account) => {
+                // 倒计时随读取时间变化，不依赖落盘时的值
+                if let Some(quota) = account.quota.as_mut() {
+                    quota.update_countdown(now);
+                }
+                accounts.push(account)
+            }
             Err(e) => {
-                crate::modules::logger::log_error(&format!("加载账号 {} 失败: {}", summary.id, e));
                 // 如果是文件不存在导致的错误，标记为无效 ID
                 // load_account 返回 "账号不存在: id" 或者底层 io error
                 if e.contains("账号不存在") || e.contains("Os { code: 2,") || e.contains("No such file") {
+                    crate::modules::logger::log_error(&format!("加载账号 {} 失败: {}", summary.id, e));
                     invalid_ids.push(summary.id.clone());
+                } else {
+                    // 文件损坏时跳过，不影响其余账号
+                    crate::modules::logger::log_warn(&format!("账号文件 {}.json 无法读取，已跳过: {}", summary.id, e));
+                    corrupted.push(CorruptedAccount {
+                        file: format!("{}.json", summary.id),
+                        error: e,
+                    });
                 }
             },
         }
@@ -160,8 +496,11 @@ pub fn list_accounts() -> Result<Vec<Account>, String> {
         }
     }
     
+    // 置顶账号在前，其余保持索引顺序
+    accounts.sort_by_key(|account| !account.pinned);
+
     // modules::logger::log_info(&format!("共找到 {} 个有效账号", accounts.len()));
-    Ok(accounts)
+    Ok((accounts, corrupted))
 }
 
 /// 添加账号
@@ -267,6 +606,116 @@ pub fn upsert_account(email: String, name: Option<String>, token: TokenData) ->
     add_account(email, name, token)
 }
 
+/// 导入时遇到同邮箱账号的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OnDuplicate {
+    /// 原地更新已有账号的 Token
+    #[default]
+    Update,
+    /// 保留已有账号，不做修改
+    Skip,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportAction {
+    Created,
+    Updated,
+    Skipped,
+}
+
+/// 单个账号的导入结果
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ImportOutcome {
+    pub action: ImportAction,
+    pub account: Account,
+}
+
+/// 导入单个账号：邮箱 (不区分大小写) 已存在时按 `on_duplicate` 更新或跳过
+pub fn import_account(
+    email: String,
+    name: Option<String>,
+    token: TokenData,
+    on_duplicate: OnDuplicate,
+) -> Result<ImportOutcome, String> {
+    let existing = {
+        let _lock = ACCOUNT_INDEX_LOCK.lock().map_err(|e| format!("获取锁失败: {}", e))?;
+        load_account_index()?
+            .accounts
+            .into_iter()
+            .find(|s| s.email.eq_ignore_ascii_case(&email))
+    };
+
+    let (action, account) = match (existing, on_duplicate) {
+        (Some(summary), OnDuplicate::Skip) => (ImportAction::Skipped, load_account(&summary.id)?),
+        // 沿用已有账号的邮箱写法，确保 upsert 命中同一账号
+        (Some(summary), OnDuplicate::Update) => (ImportAction::Updated, upsert_account(summary.email, name, token)?),
+        (None, _) => (ImportAction::Created, add_account(email, name, token)?),
+    };
+    Ok(ImportOutcome { action, account })
+}
+
+/// 为已有账号替换 refresh_token，保留标签、顺序与配额等数据
+///
+/// 新 Token 对应的邮箱必须与账号一致，`force` 为 true 时允许改绑到新邮箱。
+/// 因授权失败导致的禁用状态会一并清除。
+pub async fn update_account_token(
+    account_id: &str,
+    refresh_token: &str,
+    force: bool,
+) -> Result<Account, String> {
+    let token_res = crate::modules::oauth::refresh_access_token(refresh_token).await?;
+    let user_info = crate::modules::oauth::get_user_info(&token_res.access_token).await?;
+
+    let _lock = ACCOUNT_INDEX_LOCK.lock().map_err(|e| format!("获取锁失败: {}", e))?;
+    let mut account = load_account(account_id)?;
+
+    let email_changed = !user_info.email.eq_ignore_ascii_case(&account.email);
+    if email_changed {
+        if !force {
+            return Err(format!(
+                "Token 属于 {}，与账号 {} 不一致 (如需改绑请传入 force: true)",
+                user_info.email, account.email
+            ));
+        }
+        let mut index = load_account_index()?;
+        if index.accounts.iter().any(|s| s.id != account.id && s.email == user_info.email) {
+            return Err(format!("账号 {} 已存在", user_info.email));
+        }
+        if let Some(summary) = index.accounts.iter_mut().find(|s| s.id == account.id) {
+            summary.email = user_info.email.clone();
+        }
+        save_account_index(&index)?;
+        account.email = user_info.email.clone();
+    }
+
+    account.token = TokenData::new(
+        token_res.access_token,
+        refresh_token.to_string(),
+        token_res.expires_in,
+        Some(user_info.email.clone()),
+        // 同一账号沿用原 project_id，改绑后需重新获取
+        if email_changed { None } else { account.token.project_id.clone() },
+        None,
+    );
+
+    account.disabled = false;
+    account.disabled_reason = None;
+    account.disabled_at = None;
+    if account.proxy_disabled
+        && account.proxy_disabled_reason.as_deref() == Some(crate::modules::token_health::INVALID_TOKEN_REASON)
+    {
+        account.proxy_disabled = false;
+        account.proxy_disabled_reason = None;
+        account.proxy_disabled_at = None;
+    }
+
+    save_account(&account)?;
+    crate::modules::logger::log_info(&format!("已更新账号 {} 的 Token", account.email));
+    Ok(account)
+}
+
 /// 删除账号
 pub fn delete_account(account_id: &str) -> Result<(), String> {
     let _lock = ACCOUNT_INDEX_LOCK.lock().map_err(|e| format!("获取锁失败: {}", e))?;
@@ -288,15 +737,7 @@ pub fn delete_account(account_id: &str) -> Result<(), String> {
     save_account_index(&index)?;
     
     // 删除账号文件
-    let accounts_dir = get_accounts_dir()?;
-    let account_path = accounts_dir.join(format!("{}.json", account_id));
-    
-    if account_path.exists() {
-        fs::remove_file(&account_path)
-            .map_err(|e| format!("删除账号文件失败: {}", e))?;
-    }
-    
-    Ok(())
+    account_store().remove(account_id)
 }
 
 /// 批量删除账号 (原子性操作索引)
@@ -304,8 +745,6 @@ pub fn delete_accounts(account_ids: &[String]) -> Result<(), String> {
     let _lock = ACCOUNT_INDEX_LOCK.lock().map_err(|e| format!("获取锁失败: {}", e))?;
     let mut index = load_account_index()?;
     
-    let accounts_dir = get_accounts_dir()?;
-    
     for account_id in account_ids {
         // 从索引中移除
         index.accounts.retain(|s| &s.id != account_id);
@@ -316,10 +755,7 @@ pub fn delete_accounts(account_ids: &[String]) -> Result<(), String> {
         }
         
         // 删除账号文件
-        let account_path = accounts_dir.join(format!("{}.json", account_id));
-        if account_path.exists() {
-            let _ = fs::remove_file(&account_path);
-        }
+        let _ = account_store().remove(account_id);
     }
     
     // 如果当前账号为空，尝试选取第一个作为默认
@@ -330,40 +766,313 @@ pub fn delete_accounts(account_ids: &[String]) -> Result<(), String> {
     save_account_index(&index)
 }
 
-/// 重新排序账号列表
-/// 根据传入的账号ID顺序更新索引文件中的账号排列顺序
-pub fn reorder_accounts(account_ids: &[String]) -> Result<(), String> {
+/// 同一邮箱的一组重复账号
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DuplicateGroup {
+    pub email: String,
+    /// 保留的账号 (Token 过期时间最晚)
+    pub kept: String,
+    /// 移入回收站的账号
+    pub removed: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct DedupeReport {
+    pub dry_run: bool,
+    pub groups: Vec<DuplicateGroup>,
+    /// 移入回收站的账号总数
+    pub removed: usize,
+}
+
+/// 按邮箱 (不区分大小写) 分组，只返回有重复的组，组内保持列表顺序
+fn group_duplicates(accounts: Vec<Account>) -> Vec<Vec<Account>> {
+    let mut groups: Vec<Vec<Account>> = Vec::new();
+    let mut by_email: HashMap<String, usize> = HashMap::new();
+    for account in accounts {
+        match by_email.get(&account.email.to_lowercase()) {
+            Some(&i) => groups[i].push(account),
+            None => {
+                by_email.insert(account.email.to_lowercase(), groups.len());
+                groups.push(vec![account]);
+            }
+        }
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+/// 保留 Token 最新的账号，并把其余账号的名称、置顶、受保护模型与使用次数合并进来
+fn merge_duplicates(mut group: Vec<Account>) -> (Account, Vec<Account>) {
+    group.sort_by_key(|a| std::cmp::Reverse((a.token.expiry_timestamp, a.last_used)));
+    let mut kept = group.remove(0);
+    for other in &group {
+        if kept.name.is_none() {
+            kept.name = other.name.clone();
+        }
+        if kept.device_profile.is_none() {
+            kept.device_profile = other.device_profile.clone();
+        }
+        kept.pinned |= other.pinned;
+        kept.protected_models.extend(other.protected_models.iter().cloned());
+        kept.total_requests += other.total_requests;
+        kept.last_used_at = kept.last_used_at.max(other.last_used_at);
+    }
+    (kept, group)
+}
+
+/// 把账号文件移入数据目录下的回收站
+fn move_to_trash(account_id: &str) -> Result<(), String> {
+    let trash_dir = get_data_dir()?.join(TRASH_DIR);
+    fs::create_dir_all(&trash_dir).map_err(|e| format!("创建回收站目录失败: {}", e))?;
+    let source = get_accounts_dir()?.join(format!("{}.json", account_id));
+    if source.exists() {
+        let target = trash_dir.join(format!("{}-{}.json", account_id, chrono::Utc::now().timestamp()));
+        fs::rename(&source, &target).map_err(|e| format!("移入回收站失败: {}", e))?;
+    }
+    account_store().remove(account_id)
+}
+
+/// 合并同邮箱的重复账号，多余的账号移入回收站；`dry_run` 时只返回计划
+pub fn dedupe_accounts(dry_run: bool) -> Result<DedupeReport, String> {
     let _lock = ACCOUNT_INDEX_LOCK.lock().map_err(|e| format!("获取锁失败: {}", e))?;
     let mut index = load_account_index()?;
-    
-    // 创建一个映射，记录每个账号ID对应的摘要信息
-    let id_to_summary: std::collections::HashMap<_, _> = index.accounts
+    let accounts: Vec<Account> = index.accounts
         .iter()
-        .map(|s| (s.id.clone(), s.clone()))
+        .filter_map(|s| load_account(&s.id).ok())
         .collect();
-    
-    // 按照新顺序重建账号列表
-    let mut new_accounts = Vec::new();
-    for id in account_ids {
-        if let Some(summary) = id_to_summary.get(id) {
-            new_accounts.push(summary.clone());
+
+    let mut report = DedupeReport { dry_run, ..Default::default() };
+    let mut merged = Vec::new();
+    for group in group_duplicates(accounts) {
+        let (kept, removed) = merge_duplicates(group);
+        report.groups.push(DuplicateGroup {
+            email: kept.email.clone(),
+            kept: kept.id.clone(),
+            removed: removed.iter().map(|a| a.id.clone()).collect(),
+        });
+        merged.push(kept);
+    }
+    report.removed = report.groups.iter().map(|g| g.removed.len()).sum();
+    if dry_run || report.removed == 0 {
+        return Ok(report);
+    }
+
+    for (kept, group) in merged.iter().zip(&report.groups) {
+        save_account(kept)?;
+        if let Some(summary) = index.accounts.iter_mut().find(|s| s.id == kept.id) {
+            summary.name = kept.name.clone();
+        }
+        if index.current_account_id.as_ref().is_some_and(|id| group.removed.contains(id)) {
+            index.current_account_id = Some(kept.id.clone());
         }
+        index.accounts.retain(|s| !group.removed.contains(&s.id));
     }
-    
-    // 添加未在新顺序中出现的账号（保持原有顺序追加到末尾）
-    for summary in &index.accounts {
-        if !account_ids.contains(&summary.id) {
-            new_accounts.push(summary.clone());
+    save_account_index(&index)?;
+
+    for account_id in report.groups.iter().flat_map(|g| &g.removed) {
+        move_to_trash(account_id)?;
+    }
+    crate::modules::logger::log_info(&format!("已合并重复账号，{} 个账号移入回收站", report.removed));
+    Ok(report)
+}
+
+/// 提交的账号 ID 列表与现有账号的差异
+#[derive(Debug, Clone, Default, PartialEq, Serialize, ToSchema)]
+pub struct AccountIdMismatch {
+    /// 现有但未提交的账号
+    pub missing: Vec<String>,
+    /// 提交了但不存在的账号
+    pub unknown: Vec<String>,
+    /// 重复提交的账号
+    pub duplicated: Vec<String>,
+}
+
+impl AccountIdMismatch {
+    fn between(existing: &[AccountSummary], submitted: &[String]) -> Option<Self> {
+        let existing: HashSet<&str> = existing.iter().map(|s| s.id.as_str()).collect();
+        let mut seen = HashSet::new();
+        let mut mismatch = Self::default();
+        for id in submitted {
+            if !existing.contains(id.as_str()) {
+                mismatch.unknown.push(id.clone());
+            } else if !seen.insert(id.as_str()) {
+                mismatch.duplicated.push(id.clone());
+            }
         }
+        mismatch.missing = existing
+            .iter()
+            .filter(|id| !seen.contains(*id))
+            .map(|id| id.to_string())
+            .collect();
+        mismatch.missing.sort();
+
+        let matched = mismatch.missing.is_empty() && mismatch.unknown.is_empty() && mismatch.duplicated.is_empty();
+        (!matched).then_some(mismatch)
     }
-    
-    index.accounts = new_accounts;
+
+    pub fn summarize(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.missing.is_empty() {
+            parts.push(format!("缺少: {}", self.missing.join(", ")));
+        }
+        if !self.unknown.is_empty() {
+            parts.push(format!("未知: {}", self.unknown.join(", ")));
+        }
+        if !self.duplicated.is_empty() {
+            parts.push(format!("重复: {}", self.duplicated.join(", ")));
+        }
+        format!("提交的账号列表与现有账号不一致 ({})", parts.join("; "))
+    }
+}
+
+/// 检查完整排序列表是否恰好包含所有现有账号
+pub fn check_reorder_ids(account_ids: &[String]) -> Result<Option<AccountIdMismatch>, String> {
+    let index = load_account_index()?;
+    Ok(AccountIdMismatch::between(&index.accounts, account_ids))
+}
+
+/// 重新排序账号列表
+/// 根据传入的账号ID顺序更新索引文件中的账号排列顺序，列表须恰好包含所有现有账号
+pub fn reorder_accounts(account_ids: &[String]) -> Result<(), String> {
+    let _lock = ACCOUNT_INDEX_LOCK.lock().map_err(|e| format!("获取锁失败: {}", e))?;
+    let mut index = load_account_index()?;
+
+    if let Some(mismatch) = AccountIdMismatch::between(&index.accounts, account_ids) {
+        return Err(mismatch.summarize());
+    }
+
+    let mut id_to_summary: HashMap<String, AccountSummary> = index.accounts
+        .drain(..)
+        .map(|s| (s.id.clone(), s))
+        .collect();
+    index.accounts = account_ids
+        .iter()
+        .filter_map(|id| id_to_summary.remove(id))
+        .collect();
     
     crate::modules::logger::log_info(&format!("账号顺序已更新，共 {} 个账号", index.accounts.len()));
     
     save_account_index(&index)
 }
 
+/// 单个账号的移动目标
+#[derive(Debug, Clone, PartialEq)]
+pub enum MoveTarget {
+    /// 移动到列表中的第 n 位 (从 0 开始，超出时放到末尾)
+    Position(usize),
+    /// 移动到指定账号之前
+    Before(String),
+}
+
+/// 按展示顺序 (置顶在前) 移动 `order` 中的一项
+fn apply_move(order: &mut Vec<String>, account_id: &str, target: &MoveTarget) -> Result<(), String> {
+    let from = order
+        .iter()
+        .position(|id| id == account_id)
+        .ok_or_else(|| format!("账号不存在: {}", account_id))?;
+    let id = order.remove(from);
+    let to = match target {
+        MoveTarget::Position(position) => (*position).min(order.len()),
+        MoveTarget::Before(before) => {
+            if before == account_id {
+                return Err("不能移动到自身之前".to_string());
+            }
+            order
+                .iter()
+                .position(|id| id == before)
+                .ok_or_else(|| format!("账号不存在: {}", before))?
+        }
+    };
+    order.insert(to, id);
+    Ok(())
+}
+
+/// 移动单个账号，只改写索引文件
+///
+/// 位置按展示顺序计算；置顶账号仍排在未置顶账号之前。
+pub fn move_account(account_id: &str, target: MoveTarget) -> Result<(), String> {
+    let _lock = ACCOUNT_INDEX_LOCK.lock().map_err(|e| format!("获取锁失败: {}", e))?;
+    let mut index = load_account_index()?;
+
+    let pinned: HashSet<String> = index.accounts
+        .iter()
+        .filter(|s| load_account(&s.id).is_ok_and(|account| account.pinned))
+        .map(|s| s.id.clone())
+        .collect();
+    let mut order: Vec<String> = index.accounts.iter().map(|s| s.id.clone()).collect();
+    order.sort_by_key(|id| !pinned.contains(id));
+    apply_move(&mut order, account_id, &target)?;
+
+    let mut id_to_summary: HashMap<String, AccountSummary> = index.accounts
+        .drain(..)
+        .map(|s| (s.id.clone(), s))
+        .collect();
+    index.accounts = order
+        .iter()
+        .filter_map(|id| id_to_summary.remove(id))
+        .collect();
+
+    save_account_index(&index)
+}
+
+/// 设置账号置顶
+pub fn set_account_pinned(account_id: &str, pinned: bool) -> Result<Account, String> {
+    let mut account = load_account(account_id)?;
+    if account.pinned != pinned {
+        account.pinned = pinned;
+        save_account(&account)?;
+    }
+    Ok(account)
+}
+
+/// 设置账号的上游端点，`None` 表示使用反代的默认端点；只接受白名单内的主机
+pub fn set_account_endpoint(account_id: &str, endpoint: Option<&str>) -> Result<Account, String> {
+    let endpoint = endpoint
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .map(crate::proxy::config::normalize_endpoint)
+        .transpose()?;
+    let mut account = load_account(account_id)?;
+    if account.upstream_endpoint != endpoint {
+        account.upstream_endpoint = endpoint;
+        save_account(&account)?;
+    }
+    Ok(account)
+}
+
+/// 设置或清除账号的地区限制标记
+pub fn set_region_blocked(account_id: &str, blocked: bool) -> Result<Account, String> {
+    let mut account = load_account(account_id)?;
+    if account.region_blocked != blocked {
+        account.region_blocked = blocked;
+        account.region_blocked_at = blocked.then(|| chrono::Utc::now().timestamp());
+        save_account(&account)?;
+    }
+    Ok(account)
+}
+
+/// 规范化账号标签：去除首尾空白与空标签，去重并保持顺序
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+        if !normalized.iter().any(|t| t == tag) {
+            normalized.push(tag.to_string());
+        }
+    }
+    normalized
+}
+
+/// 设置账号标签 (覆盖原有标签)
+pub fn set_tags(account_id: &str, tags: &[String]) -> Result<Account, String> {
+    let mut account = load_account(account_id)?;
+    let tags = normalize_tags(tags);
+    if account.tags != tags {
+        account.tags = tags;
+        save_account(&account)?;
+    }
+    Ok(account)
+}
+
 /// 切换当前账号
 pub async fn switch_account(account_id: &str) -> Result<(), String> {
     use crate::modules::{oauth, process, db, device};
@@ -681,7 +1390,9 @@ pub fn update_account_quota(account_id: &str, quota: QuotaData) -> Result<(), St
     }
     // --- 配额保护逻辑结束 ---
 
-    save_account(&account)
+    save_account(&account)?;
+    crate::modules::auto_switch::on_quota_updated(account_id);
+    Ok(())
 }
 
 /// 导出所有账号的 refresh_token
@@ -697,11 +1408,13 @@ pub fn export_accounts() -> Result<Vec<(String, String)>, String> {
     Ok(exports)
 }
 
-/// 带有重试机制的配额查询 (从 commands 移动到 modules 以便共享)
+/// 带有重试机制的配额查询 (从 commands 移动到 modules 以便共享)，重试策略见配置中的 `quota_retry`
 pub async fn fetch_quota_with_retry(account: &mut Account) -> crate::error::AppResult<QuotaData> {
     use crate::modules::oauth;
     use crate::error::AppError;
     use reqwest::StatusCode;
+
+    let policy = modules::config::load_app_config().map(|c| c.quota_retry).unwrap_or_default();
     
     // 1. 基于时间的检查 (Time-based check) - 先确保 Token 有效
     let token = match oauth::ensure_fresh_token(&account.token).await {
@@ -760,7 +1473,7 @@ pub async fn fetch_quota_with_retry(account: &mut Account) -> crate::error::AppR
     }
 
     // 2. 尝试查询
-    let result: crate::error::AppResult<(QuotaData, Option<String>)> = modules::fetch_quota(&account.token.access_token, &account.email).await;
+    let result: crate::error::AppResult<(QuotaData, Option<String>)> = modules::quota::fetch_quota_with_policy(&account.token.access_token, &account.email, None, &policy).await;
     
     // 捕获可能更新的 project_id 并保存
     if let Ok((ref _q, ref project_id)) = result {
@@ -821,7 +1534,7 @@ pub async fn fetch_quota_with_retry(account: &mut Account) -> crate::error::AppR
                 upsert_account(account.email.clone(), name, new_token.clone()).map_err(AppError::Account)?;
                 
                 // 重试查询
-                let retry_result: crate::error::AppResult<(QuotaData, Option<String>)> = modules::fetch_quota(&new_token.access_token, &account.email).await;
+                let retry_result: crate::error::AppResult<(QuotaData, Option<String>)> = modules::quota::fetch_quota_with_policy(&new_token.access_token, &account.email, None, &policy).await;
                 
                 // 同样处理重试时的 project_id 保存
                 if let Ok((ref _q, ref project_id)) = retry_result {
@@ -837,6 +1550,7 @@ pub async fn fetch_quota_with_retry(account: &mut Account) -> crate::error::AppR
                         if s == StatusCode::FORBIDDEN {
                             let mut q = QuotaData::new();
                             q.is_forbidden = true;
+                            q.forbidden_reason = Some(format!("HTTP {}", s.as_u16()));
                             return Ok(q);
                         }
                     }
@@ -855,9 +1569,30 @@ pub struct RefreshStats {
     pub total: usize,
     pub success: usize,
     pub failed: usize,
+    /// 本次因永久性失败 (403 / 账号停用) 被标记为 forbidden 的账号数，后续刷新自动跳过
+    pub forbidden: usize,
     pub details: Vec<String>,
 }
 
+/// 单个账号的刷新结果
+enum RefreshOutcome {
+    Success,
+    Forbidden(String),
+    Failed(String),
+}
+
+/// 解除账号的 forbidden 标记 (申诉恢复后)，下次刷新配额时重新查询
+pub fn clear_forbidden(account_id: &str) -> Result<Account, String> {
+    let mut account = load_account(account_id)?;
+    if let Some(quota) = account.quota.as_mut().filter(|q| q.is_forbidden || q.forbidden_reason.is_some()) {
+        quota.is_forbidden = false;
+        quota.forbidden_reason = None;
+        save_account(&account)?;
+        modules::logger::log_info(&format!("已解除账号 {} 的 forbidden 标记", account.email));
+    }
+    Ok(account)
+}
+
 /// 批量刷新所有账号配额的核心逻辑 (不依赖 Tauri 状态)
 pub async fn refresh_all_quotas_logic() -> Result<RefreshStats, String> {
     use futures::future::join_all;
@@ -888,6 +1623,10 @@ pub async fn refresh_all_quotas_logic() -> Result<RefreshStats, String> {
                     return false;
                 }
             }
+            if account.region_blocked {
+                crate::modules::logger::log_info(&format!("  - Skipping {} (Region blocked)", account.email));
+                return false;
+            }
             true
         })
         .map(|mut account| {
@@ -899,19 +1638,26 @@ pub async fn refresh_all_quotas_logic() -> Result<RefreshStats, String> {
                 crate::modules::logger::log_info(&format!("  - Processing {}", email));
                 match fetch_quota_with_retry(&mut account).await {
                     Ok(quota) => {
+                        let forbidden = quota.is_forbidden.then(|| {
+                            quota.forbidden_reason.clone().unwrap_or_else(|| "403 Forbidden".to_string())
+                        });
                         if let Err(e) = update_account_quota(&account_id, quota) {
                             let msg = format!("Account {}: Save quota failed - {}", email, e);
                             crate::modules::logger::log_error(&msg);
-                            Err(msg)
+                            RefreshOutcome::Failed(msg)
+                        } else if let Some(reason) = forbidden {
+                            let msg = format!("Account {}: Marked forbidden - {}", email, reason);
+                            crate::modules::logger::log_warn(&msg);
+                            RefreshOutcome::Forbidden(msg)
                         } else {
                             crate::modules::logger::log_info(&format!("    ✅ {} Success", email));
-                            Ok(())
+                            RefreshOutcome::Success
                         }
                     }
                     Err(e) => {
                         let msg = format!("Account {}: Fetch quota failed - {}", email, e);
                         crate::modules::logger::log_error(&msg);
-                        Err(msg)
+                        RefreshOutcome::Failed(msg)
                     }
                 }
             }
@@ -923,12 +1669,17 @@ pub async fn refresh_all_quotas_logic() -> Result<RefreshStats, String> {
 
     let mut success = 0;
     let mut failed = 0;
+    let mut forbidden = 0;
     let mut details = Vec::new();
 
     for result in results {
         match result {
-            Ok(()) => success += 1,
-            Err(msg) => {
+            RefreshOutcome::Success => success += 1,
+            RefreshOutcome::Forbidden(msg) => {
+                forbidden += 1;
+                details.push(msg);
+            }
+            RefreshOutcome::Failed(msg) => {
                 failed += 1;
                 details.push(msg);
             }
@@ -937,9 +1688,10 @@ pub async fn refresh_all_quotas_logic() -> Result<RefreshStats, String> {
 
     let elapsed = start.elapsed();
     crate::modules::logger::log_info(&format!(
-        "批量刷新完成: {} 成功, {} 失败, 耗时: {}ms",
+        "批量刷新完成: {} 成功, {} 失败, {} 标记为 forbidden, 耗时: {}ms",
         success,
         failed,
+        forbidden,
         elapsed.as_millis()
     ));
 
@@ -947,6 +1699,173 @@ pub async fn refresh_all_quotas_logic() -> Result<RefreshStats, String> {
         total,
         success,
         failed,
+        forbidden,
         details,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store() -> (PathBuf, AccountStore) {
+        let dir = std::env::temp_dir().join(format!("ag-store-{}", Uuid::new_v4()));
+        fs::create_dir_all(dir.join(ACCOUNTS_DIR)).unwrap();
+        let store = AccountStore::new(Some(dir.clone()));
+        (dir, store)
+    }
+
+    #[test]
+    fn test_store_serves_writes_from_memory() {
+        let (dir, store) = temp_store();
+        let token = TokenData::new("at".to_string(), "rt".to_string(), 3600, None, None, None);
+        let mut account = Account::new("acc-1".to_string(), "a@example.com".to_string(), token);
+        store.put(&account).unwrap();
+
+        // 一个处理器修改后，后续读取直接命中缓存
+        account.proxy_disabled = true;
+        store.put(&account).unwrap();
+        assert!(store.get("acc-1").unwrap().proxy_disabled);
+        assert!(store.get("acc-1").unwrap().proxy_disabled);
+        assert_eq!(store.disk_reads(), 0);
+
+        // 外部直接改写文件后重新加载
+        account.email = "b@example.com".to_string();
+        let path = dir.join(ACCOUNTS_DIR).join("acc-1.json");
+        fs::write(&path, serde_json::to_string(&account).unwrap()).unwrap();
+        assert_eq!(store.get("acc-1").unwrap().email, "b@example.com");
+        assert_eq!(store.disk_reads(), 1);
+
+        store.remove("acc-1").unwrap();
+        assert!(store.get("acc-1").is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn ids(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_apply_move() {
+        let mut order = ids(&["a", "b", "c", "d"]);
+        apply_move(&mut order, "d", &MoveTarget::Position(0)).unwrap();
+        assert_eq!(order, ids(&["d", "a", "b", "c"]));
+        apply_move(&mut order, "d", &MoveTarget::Before("c".to_string())).unwrap();
+        assert_eq!(order, ids(&["a", "b", "d", "c"]));
+        apply_move(&mut order, "a", &MoveTarget::Position(99)).unwrap();
+        assert_eq!(order, ids(&["b", "d", "c", "a"]));
+        assert!(apply_move(&mut order, "x", &MoveTarget::Position(0)).is_err());
+        assert!(apply_move(&mut order, "a", &MoveTarget::Before("x".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_reorder_mismatch() {
+        let existing: Vec<AccountSummary> = ["a", "b", "c"]
+            .iter()
+            .map(|id| AccountSummary {
+                id: id.to_string(),
+                email: format!("{}@example.com", id),
+                name: None,
+                created_at: 0,
+                last_used: 0,
+            })
+            .collect();
+
+        assert_eq!(AccountIdMismatch::between(&existing, &ids(&["c", "a", "b"])), None);
+        let mismatch = AccountIdMismatch::between(&existing, &ids(&["a", "a", "x"])).unwrap();
+        assert_eq!(mismatch.missing, ids(&["b", "c"]));
+        assert_eq!(mismatch.unknown, ids(&["x"]));
+        assert_eq!(mismatch.duplicated, ids(&["a"]));
+    }
+
+    #[test]
+    fn test_merge_duplicates_keeps_newest_token() {
+        let account = |id: &str, email: &str, expiry: i64| {
+            let mut token = TokenData::new("at".to_string(), "rt".to_string(), 3600, None, None, None);
+            token.expiry_timestamp = expiry;
+            Account::new(id.to_string(), email.to_string(), token)
+        };
+        let mut old = account("old", "A@example.com", 100);
+        old.name = Some("Alice".to_string());
+        old.pinned = true;
+        old.total_requests = 3;
+        let mut new = account("new", "a@example.com", 200);
+        new.total_requests = 2;
+        let other = account("other", "b@example.com", 100);
+
+        let groups = group_duplicates(vec![old, other, new]);
+        assert_eq!(groups.len(), 1);
+        let (kept, removed) = merge_duplicates(groups.into_iter().next().unwrap());
+        assert_eq!(kept.id, "new");
+        assert_eq!(kept.name.as_deref(), Some("Alice"));
+        assert!(kept.pinned);
+        assert_eq!(kept.total_requests, 5);
+        assert_eq!(removed.iter().map(|a| a.id.as_str()).collect::<Vec<_>>(), ["old"]);
+    }
+
+    #[test]
+    fn test_store_caches_index() {
+        let (dir, store) = temp_store();
+        let mut index = AccountIndex::new();
+        index.current_account_id = Some("acc-1".to_string());
+        store.save_index(&index).unwrap();
+
+        assert_eq!(store.index().unwrap().current_account_id.as_deref(), Some("acc-1"));
+        assert_eq!(store.disk_reads(), 0);
+
+        store.invalidate();
+        assert_eq!(store.index().unwrap().current_account_id.as_deref(), Some("acc-1"));
+        assert_eq!(store.disk_reads(), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_data_dir_resolution_order() {
+        let home = Path::new("/home/u");
+        let explicit = Path::new("/data/explicit");
+        let env = Path::new("/data/env");
+
+        let (dir, source) = resolve_data_dir(Some(explicit), Some("work"), Some(env), home);
+        assert_eq!((dir.as_path(), source), (explicit, DataDirSource::Explicit));
+
+        let (dir, source) = resolve_data_dir(None, Some("work"), Some(env), home);
+        assert_eq!(dir, home.join(DATA_DIR).join(PROFILES_DIR).join("work"));
+        assert_eq!(source, DataDirSource::Profile);
+
+        let (dir, source) = resolve_data_dir(None, None, Some(env), home);
+        assert_eq!((dir.as_path(), source), (env, DataDirSource::Env));
+
+        let (dir, source) = resolve_data_dir(None, None, None, home);
+        assert_eq!((dir, source), (home.join(DATA_DIR), DataDirSource::Default));
+
+        assert!(validate_profile_name("work_2-b").is_ok());
+        for name in ["", "../etc", "a b", "中文"] {
+            assert!(validate_profile_name(name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn test_profile_init_and_listing() {
+        let root = std::env::temp_dir().join(format!("ag-profiles-{}", Uuid::new_v4()));
+        assert!(list_profiles_in(&root, None).unwrap().is_empty());
+
+        let work = root.join("work");
+        fs::create_dir_all(&work).unwrap();
+        init_profile(&work).unwrap();
+        fs::create_dir_all(root.join("home")).unwrap();
+        fs::create_dir_all(root.join("bad name")).unwrap();
+
+        let config: crate::models::AppConfig =
+            serde_json::from_str(&fs::read_to_string(work.join(modules::config::CONFIG_FILE)).unwrap()).unwrap();
+        assert_eq!(config.proxy.port, crate::models::AppConfig::new().proxy.port);
+        assert_eq!(modules::schema::read_version(&work).unwrap(), modules::schema::SCHEMA_VERSION);
+
+        let profiles = list_profiles_in(&root, Some("work")).unwrap();
+        let names: Vec<_> = profiles.iter().map(|p| (p.name.as_str(), p.active)).collect();
+        assert_eq!(names, vec![("home", false), ("work", true)]);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
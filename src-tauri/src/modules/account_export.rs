@@ -0,0 +1,117 @@
+//! 账号 CSV 导出，用于表格报表；不包含任何 token
+
+use chrono::{TimeZone, Utc};
+
+use crate::models::Account;
+
+use super::dashboard::AccountDigest;
+
+/// 表头，列顺序固定
+pub const CSV_HEADER: &[&str] = &[
+    "email",
+    "name",
+    "tags",
+    "status",
+    "quota_limit",
+    "quota_remaining",
+    "quota_updated_at",
+    "last_used_at",
+    "total_requests",
+];
+
+/// 导出文件名，例如 `antigravity-accounts-20250101-120000.csv`
+pub fn export_filename(now: chrono::DateTime<Utc>) -> String {
+    format!("antigravity-accounts-{}.csv", now.format("%Y%m%d-%H%M%S"))
+}
+
+/// 转义单个字段：含逗号、引号或换行时加引号并双写引号；
+/// 以公式字符开头时加 `'` 前缀，避免表格软件将其当作公式执行
+pub fn escape_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+fn join_line<S: AsRef<str>>(fields: &[S]) -> String {
+    let mut line = fields
+        .iter()
+        .map(|field| escape_field(field.as_ref()))
+        .collect::<Vec<_>>()
+        .join(",");
+    line.push_str("\r\n");
+    line
+}
+
+fn format_ts(ts: i64) -> String {
+    Utc.timestamp_opt(ts, 0)
+        .single()
+        .map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+        .unwrap_or_default()
+}
+
+/// 表头行，带 UTF-8 BOM 以便 Excel 正确识别中文
+pub fn header_line() -> String {
+    format!("\u{feff}{}", join_line(CSV_HEADER))
+}
+
+/// 单个账号一行；配额以百分点计 (每个模型满额为 100)，与看板一致。
+/// 多个标签以 `;` 分隔
+pub fn account_line(account: &Account) -> String {
+    let digest = AccountDigest::of(account);
+    let has_quota = account.quota.is_some();
+    join_line(&[
+        account.email.clone(),
+        account.name.clone().unwrap_or_default(),
+        account.tags.join(";"),
+        digest.status.as_str().to_string(),
+        if has_quota { digest.quota_total.to_string() } else { String::new() },
+        if has_quota { digest.quota_remaining.to_string() } else { String::new() },
+        account.quota.as_ref().map(|q| format_ts(q.last_updated)).unwrap_or_default(),
+        account.last_used_at.map(format_ts).unwrap_or_default(),
+        account.total_requests.to_string(),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{QuotaData, TokenData};
+
+    #[test]
+    fn test_escape_field() {
+        assert_eq!(escape_field("a@example.com"), "a@example.com");
+        assert_eq!(escape_field("Team, A"), "\"Team, A\"");
+        assert_eq!(escape_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape_field("line1\nline2"), "\"line1\nline2\"");
+        assert_eq!(escape_field("=HYPERLINK(1)"), "'=HYPERLINK(1)");
+    }
+
+    #[test]
+    fn test_account_line_excludes_tokens() {
+        let token = TokenData::new("secret-at".to_string(), "secret-rt".to_string(), 3600, None, None, None);
+        let mut account = Account::new("acc-1".to_string(), "a@example.com".to_string(), token);
+        account.name = Some("Ops, Team".to_string());
+        account.proxy_disabled = true;
+        account.total_requests = 42;
+        account.last_used_at = Some(0);
+        let mut quota = QuotaData::new();
+        quota.add_model("gemini".to_string(), 60, String::new());
+        quota.add_model("claude".to_string(), 20, String::new());
+        account.quota = Some(quota);
+
+        let line = account_line(&account);
+        assert!(line.starts_with(
+            "a@example.com,\"Ops, Team\",,proxy_disabled,200,80,"
+        ));
+        assert!(line.ends_with(",1970-01-01T00:00:00Z,42\r\n"));
+        assert!(!line.contains("secret"));
+        assert!(header_line().starts_with("\u{feff}email,name,tags,status,"));
+    }
+}
@@ -0,0 +1,242 @@
+//! 账号文件修复：为缺失或类型不符的可选字段补上默认值后重新保存，
+//! 缺少邮箱或 refresh_token 等无法恢复的文件移入 `accounts/.corrupt/`
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::Path;
+use utoipa::ToSchema;
+
+use crate::models::{Account, TokenData};
+use crate::modules::account::CorruptedAccount;
+
+/// 隔离目录，位于账号目录下；账号加载只读取 `*.json` 文件，不会进入该目录
+pub const QUARANTINE_DIR: &str = ".corrupt";
+
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct RepairedAccount {
+    pub file: String,
+    /// 缺失或无法解析、已改用默认值的字段 (如 `quota`、`token.expires_in`)
+    pub defaulted_fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct RepairReport {
+    /// 无需修复的文件数
+    pub healthy: usize,
+    pub repaired: Vec<RepairedAccount>,
+    /// 无法修复、已移入 `accounts/.corrupt/` 的文件
+    pub quarantined: Vec<CorruptedAccount>,
+    /// 无法修复且未能移走的文件
+    pub failed: Vec<CorruptedAccount>,
+}
+
+fn non_empty_str<'a>(object: &'a Map<String, Value>, key: &str) -> Option<&'a str> {
+    object.get(key).and_then(Value::as_str).map(str::trim).filter(|s| !s.is_empty())
+}
+
+/// 以 `base` 为基础逐个合入 `overlay` 的字段，合入后无法解析为 `T` 的字段保留默认值并记入 `defaulted`
+fn merge_fields<T: DeserializeOwned>(
+    base: &mut Map<String, Value>,
+    overlay: &Map<String, Value>,
+    prefix: &str,
+    defaulted: &mut Vec<String>,
+) {
+    for (key, value) in overlay {
+        let previous = base.insert(key.clone(), value.clone());
+        if serde_json::from_value::<T>(Value::Object(base.clone())).is_err() {
+            match previous {
+                Some(previous) => base.insert(key.clone(), previous),
+                None => base.remove(key),
+            };
+            defaulted.push(format!("{}{}", prefix, key));
+        }
+    }
+}
+
+/// 尽量恢复账号数据，返回账号与改用默认值的字段；缺少邮箱或 refresh_token 时无法恢复
+fn recover(account_id: &str, content: &str) -> Result<(Account, Vec<String>), String> {
+    let value: Value = serde_json::from_str(content).map_err(|e| format!("不是有效的 JSON: {}", e))?;
+    let Value::Object(object) = value else {
+        return Err("内容不是 JSON 对象".to_string());
+    };
+    let email = non_empty_str(&object, "email").ok_or("缺少 email")?.to_string();
+    let token = object.get("token").and_then(Value::as_object).cloned().unwrap_or_default();
+    let refresh_token = non_empty_str(&token, "refresh_token").ok_or("缺少 token.refresh_token")?.to_string();
+
+    // 默认的 access_token 为空且已过期，反代使用前会先刷新
+    let mut default_token = TokenData::new(String::new(), refresh_token, 0, None, None, None);
+    default_token.expiry_timestamp = 0;
+    let skeleton = Account::new(account_id.to_string(), email, default_token);
+    let Ok(Value::Object(mut base)) = serde_json::to_value(&skeleton) else {
+        return Err("序列化默认账号数据失败".to_string());
+    };
+    let Some(Value::Object(mut base_token)) = base.remove("token") else {
+        return Err("序列化默认 Token 数据失败".to_string());
+    };
+
+    let mut fields = object;
+    fields.remove("token");
+    // 以文件名为准，避免与索引中的 ID 不一致
+    fields.remove("id");
+
+    // 缺失的必填字段同样改用默认值 (有 serde 默认值的字段缺失属正常情况，不记录)
+    let mut defaulted: Vec<String> = missing_required::<TokenData>(&base_token, &token)
+        .into_iter()
+        .map(|key| format!("token.{}", key))
+        .chain(missing_required::<Account>(&base, &fields).into_iter().filter(|key| key != "id"))
+        .collect();
+
+    merge_fields::<TokenData>(&mut base_token, &token, "token.", &mut defaulted);
+    base.insert("token".to_string(), Value::Object(base_token));
+    merge_fields::<Account>(&mut base, &fields, "", &mut defaulted);
+
+    let account = serde_json::from_value(Value::Object(base)).map_err(|e| format!("恢复账号数据失败: {}", e))?;
+    defaulted.sort();
+    Ok((account, defaulted))
+}
+
+/// `base` 中去掉后无法解析为 `T` 的字段 (即必填字段) 里，`present` 没有提供的那些
+fn missing_required<T: DeserializeOwned>(base: &Map<String, Value>, present: &Map<String, Value>) -> Vec<String> {
+    base.keys()
+        .filter(|key| !present.contains_key(*key))
+        .filter(|key| {
+            let mut without = base.clone();
+            without.remove(*key);
+            serde_json::from_value::<T>(Value::Object(without)).is_err()
+        })
+        .cloned()
+        .collect()
+}
+
+/// 移入隔离目录，同名文件已存在时追加时间戳
+fn quarantine(accounts_dir: &Path, file: &str) -> Result<(), String> {
+    let dir = accounts_dir.join(QUARANTINE_DIR);
+    fs::create_dir_all(&dir).map_err(|e| format!("创建隔离目录失败: {}", e))?;
+    let mut target = dir.join(file);
+    if target.exists() {
+        target = dir.join(format!("{}-{}", file, chrono::Utc::now().timestamp()));
+    }
+    let source = accounts_dir.join(file);
+    fs::rename(&source, &target).map_err(|e| format!("移入隔离目录失败: {}", e))?;
+    crate::modules::accounts_watcher::note_written(&source, None);
+    Ok(())
+}
+
+/// 检查账号目录下的全部账号文件，修复可恢复的文件并隔离其余损坏文件
+pub fn repair_dir(accounts_dir: &Path) -> Result<RepairReport, String> {
+    let entries = fs::read_dir(accounts_dir).map_err(|e| format!("读取账号目录失败: {}", e))?;
+    let mut files: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_file()))
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| name.ends_with(".json"))
+        .collect();
+    files.sort();
+
+    let mut report = RepairReport::default();
+    for file in files {
+        let path = accounts_dir.join(&file);
+        let account_id = file.trim_end_matches(".json");
+        let result = fs::read_to_string(&path)
+            .map_err(|e| format!("读取文件失败: {}", e))
+            .and_then(|content| {
+                if serde_json::from_str::<Account>(&content).is_ok() {
+                    return Ok(None);
+                }
+                recover(account_id, &content).map(Some)
+            });
+
+        match result {
+            Ok(None) => report.healthy += 1,
+            Ok(Some((account, defaulted_fields))) => {
+                let saved = serde_json::to_string_pretty(&account)
+                    .map_err(|e| format!("序列化账号数据失败: {}", e))
+                    .and_then(|content| crate::modules::accounts_watcher::write(&path, content).map_err(|e| format!("保存账号数据失败: {}", e)));
+                match saved {
+                    Ok(()) => {
+                        tracing::info!("已修复账号文件 {} (默认值字段: {})", file, defaulted_fields.join(", "));
+                        report.repaired.push(RepairedAccount { file, defaulted_fields });
+                    }
+                    Err(error) => report.failed.push(CorruptedAccount { file, error }),
+                }
+            }
+            Err(error) => match quarantine(accounts_dir, &file) {
+                Ok(()) => {
+                    tracing::warn!("账号文件 {} 无法修复，已移入 {}: {}", file, QUARANTINE_DIR, error);
+                    report.quarantined.push(CorruptedAccount { file, error });
+                }
+                Err(move_error) => report.failed.push(CorruptedAccount {
+                    file,
+                    error: format!("{}; {}", error, move_error),
+                }),
+            },
+        }
+    }
+    Ok(report)
+}
+
+/// 修复默认账号目录，完成后清空账号缓存
+pub fn repair() -> Result<RepairReport, String> {
+    let report = repair_dir(&crate::modules::account::get_accounts_dir()?)?;
+    crate::modules::account::account_store().invalidate();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repair_dir() {
+        let dir = std::env::temp_dir().join(format!("ag-repair-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let token = TokenData::new("at".into(), "rt".into(), 3600, None, None, None);
+        let healthy = Account::new("healthy".into(), "ok@example.com".into(), token);
+        fs::write(dir.join("healthy.json"), serde_json::to_string(&healthy).unwrap()).unwrap();
+        // 缺少必填的时间字段，quota 类型错误
+        fs::write(
+            dir.join("partial.json"),
+            r#"{"id": "other", "email": "partial@example.com", "name": "P", "quota": "broken",
+                "token": {"access_token": "at", "refresh_token": "rt-partial", "expires_in": "soon"}}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("truncated.json"), r#"{"id": "truncated", "email": "#).unwrap();
+        fs::write(dir.join("no-token.json"), r#"{"email": "x@example.com"}"#).unwrap();
+        fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        let report = repair_dir(&dir).unwrap();
+        assert_eq!(report.healthy, 1);
+        assert!(report.failed.is_empty());
+
+        assert_eq!(report.repaired.len(), 1);
+        let repaired = &report.repaired[0];
+        assert_eq!(repaired.file, "partial.json");
+        for field in ["quota", "created_at", "last_used", "token.expires_in", "token.expiry_timestamp"] {
+            assert!(repaired.defaulted_fields.contains(&field.to_string()), "{}", field);
+        }
+        assert!(!repaired.defaulted_fields.contains(&"name".to_string()));
+
+        let account: Account = serde_json::from_str(&fs::read_to_string(dir.join("partial.json")).unwrap()).unwrap();
+        assert_eq!(account.id, "partial");
+        assert_eq!(account.name.as_deref(), Some("P"));
+        assert_eq!(account.token.refresh_token, "rt-partial");
+        assert_eq!(account.token.access_token, "at");
+        assert!(account.quota.is_none());
+
+        let quarantined: Vec<&str> = report.quarantined.iter().map(|c| c.file.as_str()).collect();
+        assert_eq!(quarantined, vec!["no-token.json", "truncated.json"]);
+        assert!(dir.join(QUARANTINE_DIR).join("truncated.json").exists());
+        assert!(!dir.join("truncated.json").exists());
+        assert!(dir.join("notes.txt").exists());
+
+        // 再次运行时全部正常
+        let again = repair_dir(&dir).unwrap();
+        assert_eq!(again.healthy, 2);
+        assert!(again.repaired.is_empty() && again.quarantined.is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,383 @@
+//! 账号测试请求：固定使用指定账号，经与反代相同的模型映射、请求转换与上游客户端发送一次最小的生成请求
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+use tokio::sync::Semaphore;
+use utoipa::ToSchema;
+
+use crate::models::Account;
+use crate::proxy::mappers::claude::models::{ClaudeRequest, Message, MessageContent};
+use crate::proxy::token_manager::TokenManager;
+use crate::proxy::upstream::client::{used_endpoint, EndpointSelection, UpstreamClient, ENDPOINT_SELECTION};
+
+pub const DEFAULT_MODEL: &str = "gemini-2.5-flash";
+pub const DEFAULT_PROMPT: &str = "Say OK";
+const MAX_OUTPUT_TOKENS: u32 = 5;
+/// 返回的响应文本与错误信息的最大字符数
+const MAX_PREVIEW_CHARS: usize = 200;
+/// 批量测试的最大并发数
+const MAX_CONCURRENT: usize = 3;
+/// 测试结果的缓存时长 (秒)
+pub const RESULT_TTL_SECS: i64 = 3600;
+
+/// 批量测试因鉴权失败禁用反代时记录的原因
+pub const AUTH_FAILURE_REASON: &str = "测试请求鉴权失败";
+
+/// 最近的测试结果 (账号 ID -> 结果)
+static RESULTS: Lazy<RwLock<HashMap<String, AccountTestResult>>> = Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// 失败原因分类
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TestErrorKind {
+    /// Token 无效或无项目权限
+    Auth,
+    /// 配额耗尽或被限流
+    Quota,
+    /// 所在地区不受支持
+    Region,
+    /// 无法连接上游
+    Network,
+    /// 其他上游错误
+    Upstream,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AccountTestResult {
+    pub account_id: String,
+    pub email: String,
+    pub success: bool,
+    /// 映射后实际请求的上游模型
+    pub model: String,
+    pub latency_ms: u64,
+    /// 上游 HTTP 状态码，未收到响应时为空
+    pub status: Option<u16>,
+    /// 截断后的响应文本
+    pub response: Option<String>,
+    pub error_kind: Option<TestErrorKind>,
+    /// 截断后的错误信息
+    pub error: Option<String>,
+    /// 测试时间 (Unix 秒)
+    pub tested_at: i64,
+    /// 本次测试是否禁用了该账号的反代
+    pub proxy_disabled: bool,
+    /// 实际请求的上游端点
+    pub upstream_endpoint: Option<String>,
+}
+
+fn store_result(result: &AccountTestResult) {
+    let mut results = RESULTS.write().unwrap_or_else(|e| e.into_inner());
+    results.retain(|_, r| r.tested_at + RESULT_TTL_SECS > result.tested_at);
+    results.insert(result.account_id.clone(), result.clone());
+}
+
+/// 一小时内的测试结果，按邮箱排序
+pub fn cached_results() -> Vec<AccountTestResult> {
+    let now = chrono::Utc::now().timestamp();
+    let results = RESULTS.read().unwrap_or_else(|e| e.into_inner());
+    let mut fresh: Vec<AccountTestResult> = results
+        .values()
+        .filter(|r| r.tested_at + RESULT_TTL_SECS > now)
+        .cloned()
+        .collect();
+    fresh.sort_by(|a, b| a.email.cmp(&b.email));
+    fresh
+}
+
+fn truncate(text: &str) -> String {
+    let mut chars = text.chars();
+    let head: String = chars.by_ref().take(MAX_PREVIEW_CHARS).collect();
+    if chars.next().is_some() {
+        format!("{}…", head)
+    } else {
+        head
+    }
+}
+
+/// 按上游状态码与错误内容归类
+pub fn classify_failure(status: u16, body: &str) -> TestErrorKind {
+    if crate::proxy::monitor::ErrorClass::is_region_blocked(body) {
+        return TestErrorKind::Region;
+    }
+    let lower = body.to_ascii_lowercase();
+    match status {
+        401 | 403 => TestErrorKind::Auth,
+        429 => TestErrorKind::Quota,
+        _ if lower.contains("resource_exhausted") || lower.contains("quota") => TestErrorKind::Quota,
+        _ => TestErrorKind::Upstream,
+    }
+}
+
+/// 提取 v1internal 非流式响应中的文本
+pub fn extract_text(body: &Value) -> String {
+    let response = body.get("response").unwrap_or(body);
+    response["candidates"]
+        .as_array()
+        .and_then(|candidates| candidates.first())
+        .and_then(|candidate| candidate["content"]["parts"].as_array())
+        .map(|parts| {
+            parts
+                .iter()
+                .filter(|part| !part["thought"].as_bool().unwrap_or(false))
+                .filter_map(|part| part["text"].as_str())
+                .collect::<String>()
+        })
+        .unwrap_or_default()
+}
+
+/// 构建 v1internal 请求体：Claude 模型走 Claude 转换，其余按 Gemini 包装
+fn build_body(mapped_model: &str, prompt: &str, project_id: &str) -> Result<Value, String> {
+    if mapped_model.to_lowercase().contains("claude") {
+        let request = ClaudeRequest {
+            model: mapped_model.to_string(),
+            messages: vec![Message {
+                role: "user".to_string(),
+                content: MessageContent::String(prompt.to_string()),
+            }],
+            max_tokens: Some(MAX_OUTPUT_TOKENS),
+            stream: false,
+            system: None,
+            temperature: None,
+            top_p: None,
+            top_k: None,
+            tools: None,
+            metadata: None,
+            thinking: None,
+            output_config: None,
+        };
+        crate::proxy::mappers::claude::transform_claude_request_in(&request, project_id)
+    } else {
+        let base = json!({
+            "model": mapped_model,
+            "contents": [{"role": "user", "parts": [{"text": prompt}]}],
+            "generationConfig": {"maxOutputTokens": MAX_OUTPUT_TOKENS}
+        });
+        Ok(crate::proxy::mappers::gemini::wrapper::wrap_request(&base, project_id, mapped_model))
+    }
+}
+
+/// 获取账号的 Token 与项目 ID：优先使用反代账号池中的缓存，不在池中 (如已禁用反代) 时从账号文件获取
+async fn resolve_token(token_manager: &TokenManager, account: &Account) -> Result<(String, String), String> {
+    match token_manager.get_token_by_email(&account.email).await {
+        Ok((access_token, project_id, _)) => Ok((access_token, project_id)),
+        Err(_) => crate::modules::quota::get_valid_token_for_warmup(account).await,
+    }
+}
+
+/// 发送一次测试请求，并计入该账号的使用统计；结果缓存一小时
+pub async fn run(
+    upstream: &UpstreamClient,
+    token_manager: &TokenManager,
+    custom_mapping: &HashMap<String, String>,
+    account: &Account,
+    model: &str,
+    prompt: &str,
+) -> AccountTestResult {
+    let result = send_test(upstream, token_manager, custom_mapping, account, model, prompt).await;
+    store_result(&result);
+    result
+}
+
+async fn send_test(
+    upstream: &UpstreamClient,
+    token_manager: &TokenManager,
+    custom_mapping: &HashMap<String, String>,
+    account: &Account,
+    model: &str,
+    prompt: &str,
+) -> AccountTestResult {
+    let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(model, custom_mapping);
+    let mut result = AccountTestResult {
+        account_id: account.id.clone(),
+        email: account.email.clone(),
+        success: false,
+        model: mapped_model.clone(),
+        latency_ms: 0,
+        status: None,
+        response: None,
+        error_kind: None,
+        error: None,
+        tested_at: chrono::Utc::now().timestamp(),
+        proxy_disabled: false,
+        upstream_endpoint: None,
+    };
+
+    let (access_token, project_id) = match resolve_token(token_manager, account).await {
+        Ok(token) => token,
+        Err(e) => {
+            result.error_kind = Some(TestErrorKind::Auth);
+            result.error = Some(truncate(&e));
+            return result;
+        }
+    };
+    let body = match build_body(&mapped_model, prompt, &project_id) {
+        Ok(body) => body,
+        Err(e) => {
+            result.error_kind = Some(TestErrorKind::Upstream);
+            result.error = Some(truncate(&format!("请求转换失败: {}", e)));
+            return result;
+        }
+    };
+
+    token_manager.record_external_usage(&account.id);
+    // 不经过反代中间件，按账号设置建立端点上下文
+    let selection = EndpointSelection {
+        account: account
+            .upstream_endpoint
+            .as_deref()
+            .and_then(|e| crate::proxy::config::normalize_endpoint(e).ok()),
+        ..Default::default()
+    };
+    let start = Instant::now();
+    let (response, endpoint) = ENDPOINT_SELECTION
+        .scope(std::cell::RefCell::new(selection), async {
+            let response = upstream
+                .call_v1_internal("generateContent", &access_token, body, None)
+                .await;
+            (response, used_endpoint())
+        })
+        .await;
+    result.upstream_endpoint = endpoint;
+    let response = match response {
+        Ok(response) => response,
+        Err(e) => {
+            result.latency_ms = start.elapsed().as_millis() as u64;
+            result.error_kind = Some(TestErrorKind::Network);
+            result.error = Some(truncate(&e));
+            return result;
+        }
+    };
+
+    let status = response.status().as_u16();
+    let text = response.text().await.unwrap_or_default();
+    result.latency_ms = start.elapsed().as_millis() as u64;
+    result.status = Some(status);
+    if (200..300).contains(&status) {
+        let value: Value = serde_json::from_str(&text).unwrap_or(Value::Null);
+        result.success = true;
+        result.response = Some(truncate(&extract_text(&value)));
+    } else {
+        result.error_kind = Some(classify_failure(status, &text));
+        result.error = Some(truncate(&text));
+    }
+    result
+}
+
+/// 并发测试所有可用账号 (未禁用且未禁用反代)，每完成一个调用一次 `progress(结果, 已完成数, 总数)`；
+/// `disable_auth_failures` 为 true 时，鉴权失败的账号会被禁用反代
+pub async fn run_all(
+    upstream: Arc<UpstreamClient>,
+    token_manager: Arc<TokenManager>,
+    custom_mapping: HashMap<String, String>,
+    model: &str,
+    prompt: &str,
+    disable_auth_failures: bool,
+    progress: impl Fn(&AccountTestResult, usize, usize),
+) -> Result<Vec<AccountTestResult>, String> {
+    let accounts: Vec<Account> = crate::modules::account::list_accounts()?
+        .into_iter()
+        .filter(|a| !a.disabled && !a.proxy_disabled)
+        .collect();
+    let total = accounts.len();
+    crate::modules::logger::log_info(&format!("开始测试 {} 个账号", total));
+
+    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT));
+    let custom_mapping = Arc::new(custom_mapping);
+    let mut tasks: FuturesUnordered<_> = accounts
+        .into_iter()
+        .map(|account| {
+            let semaphore = semaphore.clone();
+            let upstream = upstream.clone();
+            let token_manager = token_manager.clone();
+            let custom_mapping = custom_mapping.clone();
+            async move {
+                let _permit = semaphore.acquire().await;
+                let mut result =
+                    send_test(&upstream, &token_manager, &custom_mapping, &account, model, prompt).await;
+                if disable_auth_failures && result.error_kind == Some(TestErrorKind::Auth) {
+                    match disable_proxy(&account.id) {
+                        Ok(()) => result.proxy_disabled = true,
+                        Err(e) => crate::modules::logger::log_warn(&format!("禁用 {} 失败: {}", account.email, e)),
+                    }
+                }
+                store_result(&result);
+                result
+            }
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(total);
+    while let Some(result) = tasks.next().await {
+        results.push(result);
+        progress(results.last().unwrap(), results.len(), total);
+    }
+
+    let failed = results.iter().filter(|r| !r.success).count();
+    crate::modules::logger::log_info(&format!("账号测试完成: {} 个账号, {} 个失败", total, failed));
+    Ok(results)
+}
+
+fn disable_proxy(account_id: &str) -> Result<(), String> {
+    let mut account = crate::modules::account::load_account(account_id)?;
+    account.proxy_disabled = true;
+    account.proxy_disabled_reason = Some(AUTH_FAILURE_REASON.to_string());
+    account.proxy_disabled_at = Some(chrono::Utc::now().timestamp());
+    crate::modules::account::save_account(&account)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_failure() {
+        assert_eq!(classify_failure(401, "UNAUTHENTICATED"), TestErrorKind::Auth);
+        assert_eq!(classify_failure(403, "PERMISSION_DENIED on project"), TestErrorKind::Auth);
+        assert_eq!(
+            classify_failure(400, "FAILED_PRECONDITION: User location is not supported for the API use."),
+            TestErrorKind::Region
+        );
+        assert_eq!(classify_failure(429, "Too Many Requests"), TestErrorKind::Quota);
+        assert_eq!(classify_failure(400, "RESOURCE_EXHAUSTED"), TestErrorKind::Quota);
+        assert_eq!(classify_failure(500, "internal"), TestErrorKind::Upstream);
+    }
+
+    #[test]
+    fn test_extract_text_skips_thoughts() {
+        let body = json!({"response": {"candidates": [{"content": {"parts": [
+            {"text": "thinking...", "thought": true},
+            {"text": "OK"}
+        ]}}]}});
+        assert_eq!(extract_text(&body), "OK");
+        assert_eq!(truncate(&"a".repeat(300)).chars().count(), MAX_PREVIEW_CHARS + 1);
+    }
+
+    #[test]
+    fn test_cached_results_expire() {
+        let now = chrono::Utc::now().timestamp();
+        let result = |id: &str, tested_at: i64| AccountTestResult {
+            account_id: id.to_string(),
+            email: format!("{}@example.com", id),
+            success: true,
+            model: DEFAULT_MODEL.to_string(),
+            latency_ms: 1,
+            status: Some(200),
+            response: Some("OK".to_string()),
+            error_kind: None,
+            error: None,
+            tested_at,
+            proxy_disabled: false,
+            upstream_endpoint: None,
+        };
+        store_result(&result("cache-stale", now - RESULT_TTL_SECS - 1));
+        store_result(&result("cache-fresh", now));
+        let cached = cached_results();
+        assert!(cached.iter().any(|r| r.account_id == "cache-fresh"));
+        assert!(!cached.iter().any(|r| r.account_id == "cache-stale"));
+    }
+}
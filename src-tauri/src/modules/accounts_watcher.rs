@@ -0,0 +1,207 @@
+//! 监听账号目录的外部修改 (如 Syncthing 同步)，合并连续事件后交给调用方重新加载账号
+//!
+//! 本进程写入账号文件时通过 `write`/`remove`/`note_written` 记录写入后的内容指纹，
+//! 监听到的变化与记录一致时视为自身写入并忽略，避免刷新 Token、统计用量等写入触发重复加载。
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use once_cell::sync::Lazy;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// 连续写入的合并窗口
+const DEBOUNCE: Duration = Duration::from_millis(500);
+const ACCOUNTS_DIR: &str = "accounts";
+const ACCOUNTS_INDEX: &str = "accounts.json";
+
+type Fingerprint = [u8; 32];
+
+/// 本进程最近一次写入后各文件的内容指纹，文件被删除时为 None
+static SELF_WRITES: Lazy<Mutex<HashMap<PathBuf, Option<Fingerprint>>>> = Lazy::new(Mutex::default);
+
+fn fingerprint(content: &[u8]) -> Fingerprint {
+    Sha256::digest(content).into()
+}
+
+/// 记录本进程对账号文件的写入 (`content` 为 None 表示已删除或移走)
+pub fn note_written(path: &Path, content: Option<&[u8]>) {
+    SELF_WRITES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(path.to_path_buf(), content.map(fingerprint));
+}
+
+/// 写入账号文件并记录为自身写入，用法同 `std::fs::write`
+pub fn write(path: impl AsRef<Path>, content: impl AsRef<[u8]>) -> std::io::Result<()> {
+    let (path, content) = (path.as_ref(), content.as_ref());
+    std::fs::write(path, content)?;
+    note_written(path, Some(content));
+    Ok(())
+}
+
+/// 删除账号文件并记录为自身写入，文件不存在时忽略
+pub fn remove(path: &Path) -> std::io::Result<()> {
+    match std::fs::remove_file(path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    note_written(path, None);
+    Ok(())
+}
+
+/// 文件当前内容是否与本进程最近一次写入一致
+fn is_self_write(path: &Path) -> bool {
+    let current = std::fs::read(path).ok().map(|content| fingerprint(&content));
+    SELF_WRITES.lock().unwrap_or_else(|e| e.into_inner()).get(path) == Some(&current)
+}
+
+enum Change {
+    /// 账号文件或账号索引
+    File(PathBuf),
+    /// 账号目录本身被创建或删除
+    Dir,
+}
+
+/// 启动账号目录监听
+pub fn watch<F, Fut>(on_change: F) -> Result<impl Future<Output = ()> + Send, String>
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    watch_dir(crate::modules::account::get_data_dir()?, on_change)
+}
+
+/// 建立 `data_dir` 下账号目录与账号索引的监听，返回处理修改事件的循环；监听随循环结束而停止
+///
+/// 同时监听数据目录，账号目录被删除后重新创建 (同步工具替换目录) 时重新建立监听。
+fn watch_dir<F, Fut>(data_dir: PathBuf, on_change: F) -> Result<impl Future<Output = ()> + Send, String>
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let accounts_dir = data_dir.join(ACCOUNTS_DIR);
+    let index_path = data_dir.join(ACCOUNTS_INDEX);
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+    let watched_dir = accounts_dir.clone();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        let Ok(event) = res else { return };
+        if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)) {
+            return;
+        }
+        for path in event.paths {
+            let change = if path == watched_dir {
+                Change::Dir
+            } else if path == index_path
+                || (path.parent() == Some(watched_dir.as_path())
+                    && path.extension().is_some_and(|ext| ext == "json"))
+            {
+                Change::File(path)
+            } else {
+                continue;
+            };
+            let _ = tx.send(change);
+        }
+    })
+    .map_err(|e| format!("创建账号目录监听失败: {}", e))?;
+    watcher
+        .watch(&data_dir, RecursiveMode::NonRecursive)
+        .map_err(|e| format!("监听数据目录失败: {}", e))?;
+    if accounts_dir.is_dir() {
+        watcher
+            .watch(&accounts_dir, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("监听账号目录失败: {}", e))?;
+    }
+
+    Ok(async move {
+        let mut watcher = watcher;
+
+        while let Some(first) = rx.recv().await {
+            let mut changed = HashSet::new();
+            let mut dir_changed = false;
+            let mut record = |change| match change {
+                Change::File(path) => {
+                    changed.insert(path);
+                }
+                Change::Dir => dir_changed = true,
+            };
+            record(first);
+            // 防抖：直到窗口内不再有新事件
+            while let Ok(Some(change)) = tokio::time::timeout(DEBOUNCE, rx.recv()).await {
+                record(change);
+            }
+
+            if dir_changed {
+                if !accounts_dir.is_dir() {
+                    // 目录暂时不存在时保持当前账号，等待重新创建
+                    debug!("账号目录已被移除，等待重新创建");
+                    continue;
+                }
+                // 目录被替换后原监听已失效，重新建立；期间的修改无法逐个识别，直接重新加载
+                if let Err(e) = watcher.watch(&accounts_dir, RecursiveMode::NonRecursive) {
+                    warn!("重新监听账号目录失败: {}", e);
+                }
+            } else if changed.iter().all(|path| is_self_write(path)) {
+                continue;
+            }
+
+            info!("检测到账号文件外部修改，重新加载账号");
+            on_change().await;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn expect_reload(rx: &mut tokio::sync::mpsc::UnboundedReceiver<()>, expected: bool) {
+        let received = tokio::time::timeout(Duration::from_secs(2), rx.recv()).await.is_ok();
+        assert_eq!(received, expected);
+    }
+
+    #[tokio::test]
+    async fn test_watch_filters_self_writes_and_survives_dir_recreate() {
+        let data_dir = std::env::temp_dir().join(format!("ag-accounts-watch-{}", uuid::Uuid::new_v4()));
+        let accounts_dir = data_dir.join(ACCOUNTS_DIR);
+        std::fs::create_dir_all(&accounts_dir).unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let watch = watch_dir(data_dir.clone(), move || {
+            let tx = tx.clone();
+            async move {
+                let _ = tx.send(());
+            }
+        })
+        .unwrap();
+        let task = tokio::spawn(watch);
+
+        // 自身写入与删除不触发重新加载
+        write(accounts_dir.join("self.json"), "{}").unwrap();
+        remove(&accounts_dir.join("self.json")).unwrap();
+        expect_reload(&mut rx, false).await;
+
+        std::fs::write(accounts_dir.join("synced.json"), "{}").unwrap();
+        expect_reload(&mut rx, true).await;
+
+        // 非账号文件被忽略
+        std::fs::write(accounts_dir.join("notes.txt"), "x").unwrap();
+        expect_reload(&mut rx, false).await;
+
+        // 目录被移除时不重新加载，重新创建后恢复监听
+        std::fs::remove_dir_all(&accounts_dir).unwrap();
+        tokio::time::sleep(DEBOUNCE * 2).await;
+        std::fs::create_dir_all(&accounts_dir).unwrap();
+        expect_reload(&mut rx, true).await;
+        std::fs::write(accounts_dir.join("later.json"), "{}").unwrap();
+        expect_reload(&mut rx, true).await;
+
+        task.abort();
+        std::fs::remove_dir_all(&data_dir).unwrap();
+    }
+}
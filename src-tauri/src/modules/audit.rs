@@ -0,0 +1,259 @@
+//! 管理操作审计日志：数据目录下只追加的 JSONL 文件，超过大小阈值时轮转
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use utoipa::ToSchema;
+
+use super::account::get_data_dir;
+
+const AUDIT_FILE: &str = "audit.jsonl";
+
+/// 单个文件的大小上限，超过后轮转为 `audit.jsonl.1` (仅保留一份)
+const MAX_FILE_BYTES: u64 = 5 * 1024 * 1024;
+
+/// 未指定操作者 (未启用管理 API 鉴权) 时记录的名称
+pub const ANONYMOUS_ACTOR: &str = "anonymous";
+
+/// 脱敏时替换敏感字段的值
+const REDACTED: &str = "***";
+
+/// 串行化写入与轮转
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// 一条审计记录
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntry {
+    /// Unix 时间戳 (秒)
+    pub timestamp: i64,
+    pub method: String,
+    /// 路由模板，如 `/api/accounts/:id`
+    pub route: String,
+    /// 实际请求路径
+    pub path: String,
+    /// 管理令牌名，未鉴权时为 `anonymous`
+    pub actor: String,
+    /// 脱敏后的请求体摘要
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub request: Option<Value>,
+    /// 配置保存时变化的顶层字段 (不记录配置内容)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changed_keys: Option<Vec<String>>,
+    pub status: u16,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+fn audit_path() -> Result<PathBuf, String> {
+    Ok(get_data_dir()?.join(AUDIT_FILE))
+}
+
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".1");
+    PathBuf::from(name)
+}
+
+/// 追加一条审计记录
+pub fn append(entry: &AuditEntry) -> Result<(), String> {
+    append_to(&audit_path()?, entry, MAX_FILE_BYTES)
+}
+
+fn append_to(path: &Path, entry: &AuditEntry, max_bytes: u64) -> Result<(), String> {
+    let line = serde_json::to_string(entry).map_err(|e| format!("序列化审计记录失败: {}", e))?;
+    let _guard = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+    if fs::metadata(path).is_ok_and(|m| m.len() >= max_bytes) {
+        fs::rename(path, rotated_path(path)).map_err(|e| format!("轮转审计日志失败: {}", e))?;
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("打开审计日志失败: {}", e))?;
+    writeln!(file, "{}", line).map_err(|e| format!("写入审计日志失败: {}", e))
+}
+
+/// 读取审计记录 (按时间先后)，仅返回 `since` (含) 之后的最近 `limit` 条
+pub fn read(limit: usize, since: Option<i64>) -> Result<Vec<AuditEntry>, String> {
+    read_from(&audit_path()?, limit, since)
+}
+
+fn read_from(path: &Path, limit: usize, since: Option<i64>) -> Result<Vec<AuditEntry>, String> {
+    let mut entries = Vec::new();
+    for file in [rotated_path(path), path.to_path_buf()] {
+        let file = match fs::File::open(&file) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("读取审计日志失败: {}", e)),
+        };
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| format!("读取审计日志失败: {}", e))?;
+            // 跳过写入中断产生的残缺行
+            let Ok(entry) = serde_json::from_str::<AuditEntry>(&line) else {
+                continue;
+            };
+            if !matches!(since, Some(since) if entry.timestamp < since) {
+                entries.push(entry);
+            }
+        }
+    }
+
+    let skip = entries.len().saturating_sub(limit);
+    entries.drain(..skip);
+    Ok(entries)
+}
+
+/// 字段名是否可能包含凭据 (URL 可能携带授权码或代理账号密码)
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    ["token", "key", "secret", "password", "code", "cookie", "authorization", "url"]
+        .iter()
+        .any(|needle| key.contains(needle))
+}
+
+/// 文本中常见的凭据形态：API 密钥、Google access/refresh token、Bearer 头与 `xxx_token=` 参数
+static SECRET_PATTERN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?i)(sk-[A-Za-z0-9_\-]{6,}|ya29\.[A-Za-z0-9_.\-]+|1//[A-Za-z0-9_.\-]+|bearer\s+[A-Za-z0-9_.\-=]+|\b((?:refresh|access|id)_token|api_key|key|secret|password)(["']?\s*[:=]\s*["']?)[^\s"'&,}]+)"#,
+    )
+    .expect("invalid secret pattern")
+});
+
+/// 文本脱敏：替换日志行等自由文本中的凭据
+pub fn redact_text(text: &str) -> String {
+    SECRET_PATTERN
+        .replace_all(text, |caps: &regex::Captures| match (caps.get(2), caps.get(3)) {
+            // `refresh_token=xxx` 保留字段名
+            (Some(name), Some(sep)) => format!("{}{}{}", name.as_str(), sep.as_str(), REDACTED),
+            _ => REDACTED.to_string(),
+        })
+        .into_owned()
+}
+
+/// 递归脱敏：凭据类字段的值替换为 `***`，其余字符串按文本规则脱敏
+pub fn redact(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    let value = if is_secret_key(key) && !value.is_null() {
+                        Value::String(REDACTED.to_string())
+                    } else {
+                        redact(value)
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(redact).collect()),
+        Value::String(text) => Value::String(redact_text(text)),
+        other => other.clone(),
+    }
+}
+
+/// 比较两份配置，返回值发生变化的顶层字段名
+pub fn changed_top_level_keys(before: &Value, after: &Value) -> Vec<String> {
+    let empty = serde_json::Map::new();
+    let before = before.as_object().unwrap_or(&empty);
+    let after = after.as_object().unwrap_or(&empty);
+
+    let mut keys: Vec<String> = before
+        .keys()
+        .chain(after.keys())
+        .filter(|key| before.get(*key) != after.get(*key))
+        .cloned()
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(timestamp: i64) -> AuditEntry {
+        AuditEntry {
+            timestamp,
+            method: "DELETE".to_string(),
+            route: "/api/accounts/:id".to_string(),
+            path: "/api/accounts/acc-1".to_string(),
+            actor: ANONYMOUS_ACTOR.to_string(),
+            request: None,
+            changed_keys: None,
+            status: 200,
+            success: true,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_redact_strips_credentials() {
+        let body = json!({
+            "refresh_token": "1//secret",
+            "name": "alice",
+            "proxy": { "api_key": "sk-1", "port": 8045 },
+            "accounts": [{ "email": "a@example.com", "access_token": "ya29" }],
+            "key": null
+        });
+        let redacted = redact(&body);
+        assert_eq!(redacted["refresh_token"], "***");
+        assert_eq!(redacted["name"], "alice");
+        assert_eq!(redacted["proxy"]["api_key"], "***");
+        assert_eq!(redacted["proxy"]["port"], 8045);
+        assert_eq!(redacted["accounts"][0]["access_token"], "***");
+        assert!(redacted["key"].is_null());
+    }
+
+    #[test]
+    fn test_redact_text_masks_inline_credentials() {
+        let line = "refresh failed: refresh_token=1//0gAbc-def status=400 auth=Bearer ya29.xyz key sk-abcdef123456";
+        let redacted = redact_text(line);
+        assert!(!redacted.contains("1//0gAbc"));
+        assert!(!redacted.contains("ya29"));
+        assert!(!redacted.contains("sk-"));
+        assert!(redacted.contains("refresh_token=***"));
+        assert!(redacted.contains("status=400"));
+    }
+
+    #[test]
+    fn test_changed_top_level_keys() {
+        let before = json!({ "theme": "dark", "proxy": { "port": 8045 }, "language": "zh" });
+        let after = json!({ "theme": "dark", "proxy": { "port": 8046 }, "auto_sync": true });
+        assert_eq!(
+            changed_top_level_keys(&before, &after),
+            vec!["auto_sync", "language", "proxy"]
+        );
+    }
+
+    #[test]
+    fn test_append_rotates_and_reads_across_files() {
+        let dir = std::env::temp_dir().join(format!("ag-audit-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(AUDIT_FILE);
+
+        for ts in 1..=5 {
+            // 阈值很小，每次追加前都会轮转，磁盘上最多保留两条
+            append_to(&path, &entry(ts), 1).unwrap();
+        }
+        assert!(rotated_path(&path).exists());
+
+        let all: Vec<i64> = read_from(&path, 100, None).unwrap().iter().map(|e| e.timestamp).collect();
+        assert_eq!(all, vec![4, 5]);
+        let recent: Vec<i64> = read_from(&path, 1, Some(0)).unwrap().iter().map(|e| e.timestamp).collect();
+        assert_eq!(recent, vec![5]);
+        assert!(read_from(&path, 100, Some(6)).unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
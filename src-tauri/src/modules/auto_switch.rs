@@ -0,0 +1,234 @@
+//! 当前账号配额耗尽时自动切换到剩余配额最多的账号
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::models::{Account, QuotaData};
+use crate::modules::{account, config, logger};
+
+/// 切换当前账号的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SwitchReason {
+    /// 用户手动切换
+    Manual,
+    /// 当前账号配额耗尽
+    QuotaExhausted,
+    /// 定时轮换
+    ScheduledRotation,
+}
+
+/// 一次自动切换
+#[derive(Debug, Clone)]
+pub struct AccountSwitch {
+    pub previous_account_id: String,
+    pub account: Account,
+    pub reason: SwitchReason,
+}
+
+type SwitchListener = Box<dyn Fn(&AccountSwitch) + Send + Sync>;
+
+/// 切换完成后的通知 (Web 模式推送 SSE，桌面端发送 Tauri 事件)，自动切换与定时轮换共用
+static LISTENER: Lazy<RwLock<Option<SwitchListener>>> = Lazy::new(|| RwLock::new(None));
+
+/// 防止并发的配额更新同时触发多次切换
+static SWITCHING: AtomicBool = AtomicBool::new(false);
+
+/// 注册切换通知
+pub fn set_listener(listener: impl Fn(&AccountSwitch) + Send + Sync + 'static) {
+    if let Ok(mut slot) = LISTENER.write() {
+        *slot = Some(Box::new(listener));
+    }
+}
+
+/// 配额是否已耗尽：被禁止访问，或所有模型剩余均为 0
+pub fn is_exhausted(quota: &QuotaData) -> bool {
+    quota.is_forbidden || max_remaining(quota) == Some(0)
+}
+
+fn max_remaining(quota: &QuotaData) -> Option<i32> {
+    quota.models.iter().map(|m| m.percentage.max(0)).max()
+}
+
+/// 选择剩余配额最多的可用账号 (同等剩余时保持列表顺序)
+///
+/// 跳过当前账号、已禁用、反代禁用、被禁止访问、配额未知或已耗尽的账号。
+pub fn select_candidate<'a>(accounts: &'a [Account], current_id: &str) -> Option<&'a Account> {
+    let mut best: Option<(&Account, i32)> = None;
+    for candidate in accounts {
+        if candidate.id == current_id || candidate.disabled || candidate.proxy_disabled {
+            continue;
+        }
+        let Some(quota) = candidate.quota.as_ref().filter(|q| !is_exhausted(q)) else {
+            continue;
+        };
+        let Some(remaining) = max_remaining(quota) else {
+            continue;
+        };
+        let better = match best {
+            Some((_, best_remaining)) => remaining > best_remaining,
+            None => true,
+        };
+        if better {
+            best = Some((candidate, remaining));
+        }
+    }
+    best.map(|(account, _)| account)
+}
+
+/// 账号配额更新后调用：若为当前账号且已耗尽，在后台尝试自动切换
+pub fn on_quota_updated(account_id: &str) {
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    if !matches!(account::get_current_account_id(), Ok(Some(ref id)) if id == account_id) {
+        return;
+    }
+    runtime.spawn(async {
+        if let Err(e) = check_and_switch().await {
+            logger::log_warn(&format!("[AutoSwitch] 自动切换失败: {}", e));
+        }
+    });
+}
+
+/// 反代观察到当前账号配额耗尽 (429 QUOTA_EXHAUSTED) 时调用：刷新其配额后按结果判断是否切换
+pub fn on_quota_error(account_id: &str) {
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return;
+    };
+    if !config::load_app_config().is_ok_and(|c| c.auto_switch_on_exhaustion) {
+        return;
+    }
+    if !matches!(account::get_current_account_id(), Ok(Some(ref id)) if id == account_id) {
+        return;
+    }
+    let account_id = account_id.to_string();
+    runtime.spawn(async move {
+        let result = async {
+            let mut current = account::load_account(&account_id)?;
+            let quota = account::fetch_quota_with_retry(&mut current)
+                .await
+                .map_err(|e| e.to_string())?;
+            // 写入配额后由 on_quota_updated 触发检查
+            account::update_account_quota(&account_id, quota)
+        }
+        .await;
+        if let Err(e) = result {
+            logger::log_warn(&format!("[AutoSwitch] 刷新当前账号配额失败: {}", e));
+        }
+    });
+}
+
+/// 检查当前账号，配额耗尽时切换到剩余配额最多的账号
+pub async fn check_and_switch() -> Result<Option<AccountSwitch>, String> {
+    if !config::load_app_config()?.auto_switch_on_exhaustion {
+        return Ok(None);
+    }
+    if SWITCHING.swap(true, Ordering::SeqCst) {
+        return Ok(None);
+    }
+    let result = switch_if_exhausted().await;
+    SWITCHING.store(false, Ordering::SeqCst);
+
+    if let Ok(Some(switch)) = &result {
+        notify(switch);
+    }
+    result
+}
+
+/// 通知切换完成
+pub(crate) fn notify(switch: &AccountSwitch) {
+    if let Ok(listener) = LISTENER.read() {
+        if let Some(listener) = listener.as_ref() {
+            listener(switch);
+        }
+    }
+}
+
+async fn switch_if_exhausted() -> Result<Option<AccountSwitch>, String> {
+    let Some(current) = account::get_current_account()? else {
+        return Ok(None);
+    };
+    if !current.quota.as_ref().is_some_and(is_exhausted) {
+        return Ok(None);
+    }
+
+    let accounts = account::list_accounts()?;
+    let Some(next) = select_candidate(&accounts, &current.id) else {
+        logger::log_warn(&format!(
+            "[AutoSwitch] 当前账号 {} 配额已耗尽，但没有可切换的账号 (其余账号均已禁用、被禁止或配额耗尽)",
+            current.email
+        ));
+        return Ok(None);
+    };
+
+    logger::log_info(&format!(
+        "[AutoSwitch] 当前账号 {} 配额已耗尽，自动切换到 {}",
+        current.email, next.email
+    ));
+    account::switch_account(&next.id).await?;
+
+    Ok(Some(AccountSwitch {
+        previous_account_id: current.id,
+        account: next.clone(),
+        reason: SwitchReason::QuotaExhausted,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TokenData;
+
+    fn account(id: &str, percentages: &[i32]) -> Account {
+        let token = TokenData::new(String::new(), String::new(), 0, None, None, None);
+        let mut account = Account::new(id.to_string(), format!("{}@example.com", id), token);
+        let mut quota = QuotaData::new();
+        for (i, pct) in percentages.iter().enumerate() {
+            quota.add_model(format!("model-{}", i), *pct, String::new());
+        }
+        account.quota = Some(quota);
+        account
+    }
+
+    #[test]
+    fn test_is_exhausted() {
+        assert!(is_exhausted(account("a", &[0, 0]).quota.as_ref().unwrap()));
+        assert!(!is_exhausted(account("a", &[0, 5]).quota.as_ref().unwrap()));
+        // 无模型数据时无法判断
+        assert!(!is_exhausted(&QuotaData::new()));
+
+        let mut forbidden = QuotaData::new();
+        forbidden.is_forbidden = true;
+        assert!(is_exhausted(&forbidden));
+    }
+
+    #[test]
+    fn test_select_candidate_skips_unusable_accounts() {
+        let mut disabled = account("disabled", &[100]);
+        disabled.disabled = true;
+        let mut proxy_disabled = account("proxy-disabled", &[100]);
+        proxy_disabled.proxy_disabled = true;
+        let mut forbidden = account("forbidden", &[100]);
+        forbidden.quota.as_mut().unwrap().is_forbidden = true;
+        let mut unknown = account("unknown", &[]);
+        unknown.quota = None;
+
+        let accounts = vec![
+            account("current", &[0]),
+            disabled,
+            proxy_disabled,
+            forbidden,
+            unknown,
+            account("empty", &[0]),
+            account("low", &[20, 10]),
+            account("high", &[60]),
+            account("tie", &[60]),
+        ];
+        assert_eq!(select_candidate(&accounts, "current").unwrap().id, "high");
+        assert!(select_candidate(&accounts[..6], "current").is_none());
+    }
+}
@@ -0,0 +1,460 @@
+//! 数据目录的完整备份与恢复 (zip)：账号、配置、排序等，可选包含日志
+
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Cursor, Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+use utoipa::ToSchema;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::models::{Account, AccountIndex, AppConfig};
+
+use super::account::get_data_dir;
+use super::config::CONFIG_FILE;
+
+/// 归档内的清单文件，恢复时据此确认是本应用生成的备份
+const MANIFEST_FILE: &str = "backup_manifest.json";
+const BACKUP_FORMAT_VERSION: u32 = 1;
+const ACCOUNTS_INDEX: &str = "accounts.json";
+const ACCOUNTS_DIR: &str = "accounts";
+/// 应用日志目录：由当前进程持有，恢复时跳过
+const LOG_DIR: &str = "logs";
+const STAGING_PREFIX: &str = ".restore-";
+const PREVIOUS_PREFIX: &str = "pre-restore-";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BackupManifest {
+    format_version: u32,
+    app_version: String,
+    created_at: i64,
+    include_logs: bool,
+}
+
+/// 恢复结果
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct RestoreSummary {
+    /// 恢复的账号数
+    pub accounts: usize,
+    /// 是否恢复了配置文件
+    pub config: bool,
+    /// 写入的文件数
+    pub files: usize,
+    /// 被替换的顶层条目
+    pub restored: Vec<String>,
+    /// 未恢复的条目 (应用日志)
+    pub skipped: Vec<String>,
+    /// 恢复前的数据移动到的目录
+    pub previous_data_dir: Option<String>,
+    /// 反代服务是否已重新启动
+    pub proxy_restarted: bool,
+}
+
+/// 打包完成的临时文件，释放时删除
+pub struct BackupArchive {
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+impl Drop for BackupArchive {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// 日志类条目：应用日志、请求日志数据库与审计日志
+fn is_log_entry(rel: &Path) -> bool {
+    let first = first_component(rel);
+    first == LOG_DIR || first.starts_with("proxy_logs.db") || first == "audit.jsonl"
+}
+
+/// 临时文件、历次恢复留下的目录与其他 profile 的数据不进入备份
+fn is_excluded(rel: &Path) -> bool {
+    let first = first_component(rel);
+    first == super::account::PROFILES_DIR
+        || first.starts_with(STAGING_PREFIX)
+        || first.starts_with(PREVIOUS_PREFIX)
+        || first.starts_with(super::schema::PRE_MIGRATION_PREFIX)
+        || rel.extension().is_some_and(|ext| ext == "tmp")
+}
+
+fn first_component(rel: &Path) -> String {
+    rel.components()
+        .next()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn collect_files(base: &Path, dir: &Path, include_logs: bool, out: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let rel = path.strip_prefix(base).unwrap_or(&path).to_path_buf();
+        if is_excluded(&rel) || (!include_logs && is_log_entry(&rel)) {
+            continue;
+        }
+        // 不跟随符号链接
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(base, &path, include_logs, out)?;
+        } else if file_type.is_file() {
+            out.push(rel);
+        }
+    }
+    Ok(())
+}
+
+fn archive_name(rel: &Path) -> String {
+    rel.components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// 将数据目录写入 zip，返回文件数
+fn write_backup<W: Write + Seek>(data_dir: &Path, writer: W, include_logs: bool) -> Result<usize, String> {
+    let mut files = Vec::new();
+    collect_files(data_dir, data_dir, include_logs, &mut files)
+        .map_err(|e| format!("读取数据目录失败: {}", e))?;
+    files.sort();
+
+    let manifest = BackupManifest {
+        format_version: BACKUP_FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: chrono::Utc::now().timestamp(),
+        include_logs,
+    };
+    let manifest = serde_json::to_string_pretty(&manifest).map_err(|e| format!("序列化备份清单失败: {}", e))?;
+
+    let mut zip = ZipWriter::new(writer);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file(MANIFEST_FILE, options)
+        .map_err(|e| format!("写入备份失败: {}", e))?;
+    zip.write_all(manifest.as_bytes())
+        .map_err(|e| format!("写入备份失败: {}", e))?;
+
+    for rel in &files {
+        let mut file = match fs::File::open(data_dir.join(rel)) {
+            Ok(file) => file,
+            // 打包期间被删除的文件 (如轮转的日志) 直接跳过
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("读取 {} 失败: {}", rel.display(), e)),
+        };
+        let large = file.metadata().map(|m| m.len() >= u32::MAX as u64).unwrap_or(false);
+        zip.start_file(archive_name(rel), options.large_file(large))
+            .map_err(|e| format!("写入备份失败: {}", e))?;
+        io::copy(&mut file, &mut zip).map_err(|e| format!("写入备份失败: {}", e))?;
+    }
+    zip.finish()
+        .map_err(|e| format!("写入备份失败: {}", e))?
+        .flush()
+        .map_err(|e| format!("写入备份失败: {}", e))?;
+    Ok(files.len())
+}
+
+/// 将数据目录打包到临时文件
+pub fn build_backup(include_logs: bool) -> Result<BackupArchive, String> {
+    let data_dir = get_data_dir()?;
+    let path = std::env::temp_dir().join(format!("ag-backup-{}.zip", uuid::Uuid::new_v4()));
+    let file = fs::File::create(&path).map_err(|e| format!("创建临时文件失败: {}", e))?;
+    let mut archive = BackupArchive { path, size: 0 };
+    write_backup(&data_dir, io::BufWriter::new(file), include_logs)?;
+    archive.size = fs::metadata(&archive.path)
+        .map(|m| m.len())
+        .map_err(|e| format!("读取备份文件失败: {}", e))?;
+    Ok(archive)
+}
+
+/// 带时间戳的下载文件名
+pub fn backup_filename(now: chrono::DateTime<chrono::Utc>) -> String {
+    format!("antigravity-backup-{}.zip", now.format("%Y%m%d-%H%M%S"))
+}
+
+/// 校验通过的归档条目
+struct ValidatedArchive {
+    /// (条目序号, 相对路径)
+    entries: Vec<(usize, PathBuf)>,
+    accounts: usize,
+    config: bool,
+}
+
+fn read_entry<R: Read + Seek>(archive: &mut ZipArchive<R>, index: usize) -> Result<String, String> {
+    let mut file = archive.by_index(index).map_err(|e| format!("读取归档失败: {}", e))?;
+    let mut content = String::new();
+    file.read_to_string(&mut content)
+        .map_err(|e| format!("读取 {} 失败: {}", file.name(), e))?;
+    Ok(content)
+}
+
+/// 检查路径安全与文件结构：清单、账号索引、账号文件与配置必须可解析
+fn validate<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<ValidatedArchive, String> {
+    let mut entries = Vec::new();
+    let mut manifest = None;
+    let mut accounts = 0;
+    let mut has_index = false;
+    let mut config = false;
+
+    for index in 0..archive.len() {
+        let (rel, is_dir) = {
+            let file = archive.by_index(index).map_err(|e| format!("读取归档失败: {}", e))?;
+            let rel = file
+                .enclosed_name()
+                .filter(|p| p.components().all(|c| matches!(c, Component::Normal(_))))
+                .ok_or_else(|| format!("归档包含非法路径: {}", file.name()))?;
+            (rel, file.is_dir())
+        };
+        if is_dir || rel.as_os_str().is_empty() {
+            continue;
+        }
+
+        let name = archive_name(&rel);
+        if name == MANIFEST_FILE {
+            let parsed: BackupManifest = serde_json::from_str(&read_entry(archive, index)?)
+                .map_err(|e| format!("备份清单无效: {}", e))?;
+            if parsed.format_version > BACKUP_FORMAT_VERSION {
+                return Err(format!("备份格式版本 {} 高于当前支持的版本，请先升级应用", parsed.format_version));
+            }
+            manifest = Some(parsed);
+            continue;
+        }
+        if is_excluded(&rel) {
+            continue;
+        }
+
+        if name == ACCOUNTS_INDEX {
+            let index_file: AccountIndex = serde_json::from_str(&read_entry(archive, index)?)
+                .map_err(|e| format!("{} 无效: {}", name, e))?;
+            accounts = index_file.accounts.len();
+            has_index = true;
+        } else if name == CONFIG_FILE {
+            serde_json::from_str::<AppConfig>(&read_entry(archive, index)?)
+                .map_err(|e| format!("{} 无效: {}", name, e))?;
+            config = true;
+        } else if rel.parent() == Some(Path::new(ACCOUNTS_DIR)) && rel.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str::<Account>(&read_entry(archive, index)?)
+                .map_err(|e| format!("{} 无效: {}", name, e))?;
+        }
+        entries.push((index, rel));
+    }
+
+    if manifest.is_none() {
+        return Err("不是有效的备份文件: 缺少 backup_manifest.json".to_string());
+    }
+    if !has_index && !config {
+        return Err("备份中没有账号索引或配置文件".to_string());
+    }
+    Ok(ValidatedArchive { entries, accounts, config })
+}
+
+/// 只校验归档，不写入
+pub fn validate_backup(bytes: &[u8]) -> Result<(), String> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).map_err(|e| format!("解析备份文件失败: {}", e))?;
+    validate(&mut archive).map(|_| ())
+}
+
+/// 暂存目录，释放时删除
+struct StagingDir(PathBuf);
+
+impl Drop for StagingDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn remove_path(path: &Path) -> io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// 撤销已替换的条目
+fn rollback(data_dir: &Path, previous: &Path, swapped: &[(OsString, bool)]) {
+    for (name, had_old) in swapped.iter().rev() {
+        let target = data_dir.join(name);
+        let _ = remove_path(&target);
+        if *had_old {
+            let _ = fs::rename(previous.join(name), &target);
+        }
+    }
+    let _ = fs::remove_dir(previous);
+}
+
+/// 恢复到指定数据目录：先解压到暂存目录，再按顶层条目逐个重命名替换；
+/// 原有条目移入 pre-restore-* 目录，替换失败时回滚
+fn restore_into(data_dir: &Path, bytes: &[u8]) -> Result<RestoreSummary, String> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).map_err(|e| format!("解析备份文件失败: {}", e))?;
+    let validated = validate(&mut archive)?;
+
+    let staging = StagingDir(data_dir.join(format!("{}{}", STAGING_PREFIX, uuid::Uuid::new_v4())));
+    fs::create_dir_all(&staging.0).map_err(|e| format!("创建暂存目录失败: {}", e))?;
+
+    let mut summary = RestoreSummary {
+        accounts: validated.accounts,
+        config: validated.config,
+        ..Default::default()
+    };
+    for (index, rel) in &validated.entries {
+        if first_component(rel) == LOG_DIR {
+            summary.skipped.push(archive_name(rel));
+            continue;
+        }
+        let target = staging.0.join(rel);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("创建目录失败: {}", e))?;
+        }
+        let mut file = archive.by_index(*index).map_err(|e| format!("读取归档失败: {}", e))?;
+        let mut out = fs::File::create(&target).map_err(|e| format!("写入 {} 失败: {}", rel.display(), e))?;
+        io::copy(&mut file, &mut out).map_err(|e| format!("写入 {} 失败: {}", rel.display(), e))?;
+        summary.files += 1;
+    }
+
+    let mut names: Vec<_> = fs::read_dir(&staging.0)
+        .map_err(|e| format!("读取暂存目录失败: {}", e))?
+        .filter_map(|entry| entry.ok().map(|e| e.file_name()))
+        .collect();
+    names.sort();
+
+    let previous = data_dir.join(format!(
+        "{}{}",
+        PREVIOUS_PREFIX,
+        chrono::Local::now().format("%Y%m%d-%H%M%S%.3f")
+    ));
+    fs::create_dir_all(&previous).map_err(|e| format!("创建目录失败: {}", e))?;
+
+    // (条目名, 是否存在旧数据)
+    let mut swapped: Vec<(OsString, bool)> = Vec::new();
+    for name in names {
+        let target = data_dir.join(&name);
+        let had_old = target.symlink_metadata().is_ok();
+        let moved = if had_old { fs::rename(&target, previous.join(&name)) } else { Ok(()) };
+        let result = moved.and_then(|_| {
+            fs::rename(staging.0.join(&name), &target).inspect_err(|_| {
+                if had_old {
+                    let _ = fs::rename(previous.join(&name), &target);
+                }
+            })
+        });
+        if let Err(e) = result {
+            rollback(data_dir, &previous, &swapped);
+            return Err(format!("替换 {} 失败，已回滚: {}", name.to_string_lossy(), e));
+        }
+        summary.restored.push(name.to_string_lossy().into_owned());
+        swapped.push((name, had_old));
+    }
+
+    if swapped.iter().any(|(_, had_old)| *had_old) {
+        summary.previous_data_dir = Some(previous.to_string_lossy().into_owned());
+    } else {
+        let _ = fs::remove_dir(&previous);
+    }
+    Ok(summary)
+}
+
+/// 恢复备份到数据目录；调用方需先停止反代服务，之后重新加载账号与配置
+pub fn restore_backup(bytes: &[u8]) -> Result<RestoreSummary, String> {
+    restore_into(&get_data_dir()?, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TokenData;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ag-backup-test-{}-{}", name, uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_data_dir(dir: &Path, email: &str) {
+        let account = Account::new(
+            "acc-1".to_string(),
+            email.to_string(),
+            TokenData::new("access".to_string(), "refresh".to_string(), 3600, None, None, None),
+        );
+        let index = AccountIndex {
+            version: "2.0".to_string(),
+            accounts: vec![crate::models::AccountSummary {
+                id: account.id.clone(),
+                email: account.email.clone(),
+                name: None,
+                created_at: account.created_at,
+                last_used: account.last_used,
+            }],
+            current_account_id: Some(account.id.clone()),
+        };
+        fs::create_dir_all(dir.join(ACCOUNTS_DIR)).unwrap();
+        fs::write(dir.join(ACCOUNTS_INDEX), serde_json::to_string(&index).unwrap()).unwrap();
+        fs::write(dir.join(ACCOUNTS_DIR).join("acc-1.json"), serde_json::to_string(&account).unwrap()).unwrap();
+        fs::write(dir.join(CONFIG_FILE), serde_json::to_string(&AppConfig::new()).unwrap()).unwrap();
+        fs::create_dir_all(dir.join(LOG_DIR)).unwrap();
+        fs::write(dir.join(LOG_DIR).join("app.log"), "log").unwrap();
+    }
+
+    fn backup_bytes(dir: &Path, include_logs: bool) -> Vec<u8> {
+        let mut cursor = Cursor::new(Vec::new());
+        write_backup(dir, &mut cursor, include_logs).unwrap();
+        cursor.into_inner()
+    }
+
+    #[test]
+    fn test_backup_and_restore_roundtrip() {
+        let source = temp_dir("source");
+        write_data_dir(&source, "old@example.com");
+        let bytes = backup_bytes(&source, false);
+
+        let names: Vec<String> = ZipArchive::new(Cursor::new(&bytes)).unwrap().file_names().map(str::to_string).collect();
+        assert!(names.contains(&"accounts/acc-1.json".to_string()));
+        assert!(!names.iter().any(|n| n.starts_with("logs/")));
+
+        let target = temp_dir("target");
+        write_data_dir(&target, "new@example.com");
+        let summary = restore_into(&target, &bytes).unwrap();
+        assert_eq!(summary.accounts, 1);
+        assert!(summary.config);
+        assert_eq!(summary.files, 3);
+
+        let restored = fs::read_to_string(target.join(ACCOUNTS_DIR).join("acc-1.json")).unwrap();
+        assert!(restored.contains("old@example.com"));
+        // 不在备份中的日志保持不动，被替换的数据可在 pre-restore 目录找回
+        assert!(target.join(LOG_DIR).join("app.log").exists());
+        let previous = PathBuf::from(summary.previous_data_dir.unwrap());
+        assert!(previous.join(ACCOUNTS_INDEX).exists());
+        assert!(!fs::read_dir(&target)
+            .unwrap()
+            .any(|e| e.unwrap().file_name().to_string_lossy().starts_with(STAGING_PREFIX)));
+
+        let _ = fs::remove_dir_all(&source);
+        let _ = fs::remove_dir_all(&target);
+    }
+
+    #[test]
+    fn test_restore_rejects_path_traversal() {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        zip.start_file(MANIFEST_FILE, options).unwrap();
+        zip.write_all(br#"{"format_version":1,"app_version":"0","created_at":0,"include_logs":false}"#)
+            .unwrap();
+        zip.start_file("../evil.json", options).unwrap();
+        zip.write_all(b"{}").unwrap();
+        let bytes = zip.finish().unwrap().into_inner();
+
+        let target = temp_dir("traversal");
+        let err = restore_into(&target, &bytes).unwrap_err();
+        assert!(err.contains("非法路径"), "{}", err);
+        assert!(fs::read_dir(&target).unwrap().next().is_none());
+        let _ = fs::remove_dir_all(&target);
+    }
+
+    #[test]
+    fn test_restore_requires_manifest() {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file(CONFIG_FILE, SimpleFileOptions::default()).unwrap();
+        zip.write_all(serde_json::to_string(&AppConfig::new()).unwrap().as_bytes()).unwrap();
+        let bytes = zip.finish().unwrap().into_inner();
+        assert!(validate_backup(&bytes).unwrap_err().contains(MANIFEST_FILE));
+    }
+}
@@ -1,10 +1,52 @@
 use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 use serde_json;
+use sha2::{Digest, Sha256};
 
 use crate::models::AppConfig;
+use crate::proxy::ConfigIssue;
 use super::account::get_data_dir;
 
-const CONFIG_FILE: &str = "gui_config.json";
+pub(crate) const CONFIG_FILE: &str = "gui_config.json";
+
+/// 串行化带版本校验的保存，保证「比较 + 写入」不被并发请求打断
+static CONFIG_WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// 环境变量覆盖的前缀，路径各段以双下划线分隔 (如 `ANTIGRAVITY_PROXY__PORT`)
+const ENV_OVERRIDE_PREFIX: &str = "ANTIGRAVITY_";
+
+/// 是否启用环境变量覆盖 (仅独立服务端启用)
+static ENV_OVERRIDES_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// 最近一次由本进程写入或观察到的配置版本号，供文件监听忽略自身写入
+static KNOWN_REVISION: Mutex<Option<String>> = Mutex::new(None);
+
+/// 记录当前磁盘上的配置版本号；若与上次记录的不同则返回新版本号
+pub fn mark_revision_seen() -> Result<Option<String>, String> {
+    let current = config_revision()?;
+    let mut known = KNOWN_REVISION.lock().unwrap_or_else(|e| e.into_inner());
+    if known.as_deref() == Some(current.as_str()) {
+        return Ok(None);
+    }
+    *known = Some(current.clone());
+    Ok(Some(current))
+}
+
+/// 启用环境变量覆盖配置
+pub fn enable_env_overrides() {
+    ENV_OVERRIDES_ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// 带版本校验的保存结果
+pub enum RevisionedSave {
+    /// 已保存，附带新的版本号
+    Saved(String),
+    /// 版本不匹配，附带当前版本号
+    Conflict(String),
+    /// 配置未通过校验，未保存
+    Invalid(Vec<ConfigIssue>),
+}
 
 /// 加载应用配置
 pub fn load_app_config() -> Result<AppConfig, String> {
@@ -12,7 +54,7 @@ pub fn load_app_config() -> Result<AppConfig, String> {
     let config_path = data_dir.join(CONFIG_FILE);
     
     if !config_path.exists() {
-        return Ok(AppConfig::new());
+        return with_env_overrides(AppConfig::new());
     }
     
     let content = fs::read_to_string(&config_path)
@@ -21,9 +63,22 @@ pub fn load_app_config() -> Result<AppConfig, String> {
     let mut v: serde_json::Value = serde_json::from_str(&content)
         .map_err(|e| format!("解析配置文件失败: {}", e))?;
     
-    let mut modified = false;
+    let modified = migrate_legacy_mappings(&mut v);
 
-    // 迁移逻辑
+    let config: AppConfig = serde_json::from_value(v)
+        .map_err(|e| format!("迁移后转换配置失败: {}", e))?;
+    
+    // 如果发生了迁移，自动保存一次以清理文件
+    if modified {
+        let _ = save_app_config(&config);
+    }
+
+    with_env_overrides(config)
+}
+
+/// 旧版 `proxy.anthropic_mapping` / `proxy.openai_mapping` 合并到 `custom_mapping`，返回是否有修改
+pub(crate) fn migrate_legacy_mappings(v: &mut serde_json::Value) -> bool {
+    let mut modified = false;
     if let Some(proxy) = v.get_mut("proxy") {
         let mut custom_mapping = proxy.get("custom_mapping")
             .and_then(|m| m.as_object())
@@ -63,16 +118,7 @@ pub fn load_app_config() -> Result<AppConfig, String> {
             proxy.as_object_mut().unwrap().insert("custom_mapping".to_string(), serde_json::Value::Object(custom_mapping));
         }
     }
-
-    let config: AppConfig = serde_json::from_value(v)
-        .map_err(|e| format!("迁移后转换配置失败: {}", e))?;
-    
-    // 如果发生了迁移，自动保存一次以清理文件
-    if modified {
-        let _ = save_app_config(&config);
-    }
-
-    Ok(config)
+    modified
 }
 
 /// 保存应用配置
@@ -80,9 +126,292 @@ pub fn save_app_config(config: &AppConfig) -> Result<(), String> {
     let data_dir = get_data_dir()?;
     let config_path = data_dir.join(CONFIG_FILE);
     
-    let content = serde_json::to_string_pretty(config)
+    let mut value = serde_json::to_value(config)
+        .map_err(|e| format!("序列化配置失败: {}", e))?;
+
+    // 被环境变量覆盖的字段保持磁盘上的原值，不回写覆盖值
+    let overrides = env_overrides();
+    if !overrides.is_empty() {
+        let on_disk = match fs::read_to_string(&config_path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or(serde_json::Value::Null),
+            Err(_) => serde_json::Value::Null,
+        };
+        let fallback = serde_json::to_value(AppConfig::new()).unwrap_or_default();
+        for (path, _) in &overrides {
+            let original = lookup(&on_disk, path).or_else(|| lookup(&fallback, path)).cloned();
+            match original {
+                Some(original) => {
+                    if let Some(slot) = lookup_mut(&mut value, path) {
+                        *slot = original;
+                    }
+                }
+                None => remove_path(&mut value, path),
+            }
+        }
+    }
+
+    let content = serde_json::to_string_pretty(&value)
         .map_err(|e| format!("序列化配置失败: {}", e))?;
     
-    fs::write(&config_path, content)
-        .map_err(|e| format!("保存配置失败: {}", e))
+    fs::write(&config_path, &content)
+        .map_err(|e| format!("保存配置失败: {}", e))?;
+    *KNOWN_REVISION.lock().unwrap_or_else(|e| e.into_inner()) = Some(revision_of(content.as_bytes()));
+    Ok(())
+}
+
+/// 配置版本号：配置文件内容的 SHA256 前 16 位，文件不存在时为空内容的哈希
+///
+/// 任何途径 (桌面端、Web API、反代启动时回写) 修改配置文件都会改变版本号。
+pub fn config_revision() -> Result<String, String> {
+    let config_path = get_data_dir()?.join(CONFIG_FILE);
+    let content = match fs::read(&config_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(format!("读取配置文件失败: {}", e)),
+    };
+    Ok(revision_of(&content))
+}
+
+/// 加载配置及其版本号
+pub fn load_app_config_with_revision() -> Result<(AppConfig, String), String> {
+    let _guard = CONFIG_WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let config = load_app_config()?;
+    Ok((config, config_revision()?))
+}
+
+/// 仅当当前版本号等于 `expected` 且配置通过校验时保存
+pub fn save_app_config_if_match(config: &AppConfig, expected: &str) -> Result<RevisionedSave, String> {
+    let _guard = CONFIG_WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let current = config_revision()?;
+    if current != expected {
+        return Ok(RevisionedSave::Conflict(current));
+    }
+    let issues = config.validate();
+    if !issues.is_empty() {
+        return Ok(RevisionedSave::Invalid(issues));
+    }
+    save_app_config(config)?;
+    Ok(RevisionedSave::Saved(config_revision()?))
+}
+
+/// 在写锁内读取、修改、校验并保存配置，返回 `f` 的结果与保存后的配置
+pub fn update_app_config<T>(
+    f: impl FnOnce(&mut AppConfig) -> Result<T, String>,
+) -> Result<(T, AppConfig), String> {
+    let _guard = CONFIG_WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut config = load_app_config()?;
+    let result = f(&mut config)?;
+    let issues = config.validate();
+    if !issues.is_empty() {
+        return Err(ConfigIssue::summarize(&issues));
+    }
+    save_app_config(&config)?;
+    Ok((result, config))
+}
+
+/// 对当前配置应用 JSON Merge Patch (RFC 7396) 并保存
+///
+/// 合并结果需能反序列化为 `AppConfig`，否则返回出错字段的 JSON Pointer。
+/// 提供 `expected` 时与 [`save_app_config_if_match`] 一样校验版本号。
+pub fn patch_app_config(patch: &serde_json::Value, expected: Option<&str>) -> Result<RevisionedSave, String> {
+    let _guard = CONFIG_WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let current = config_revision()?;
+    if expected.is_some_and(|expected| expected != current) {
+        return Ok(RevisionedSave::Conflict(current));
+    }
+
+    let mut merged = serde_json::to_value(load_app_config()?)
+        .map_err(|e| format!("序列化配置失败: {}", e))?;
+    merge_patch(&mut merged, patch);
+    let config = deserialize_config(merged)?;
+    let issues = config.validate();
+    if !issues.is_empty() {
+        return Ok(RevisionedSave::Invalid(issues));
+    }
+
+    save_app_config(&config)?;
+    Ok(RevisionedSave::Saved(config_revision()?))
+}
+
+/// RFC 7396: 对象递归合并，`null` 删除字段，其余值整体替换
+fn merge_patch(target: &mut serde_json::Value, patch: &serde_json::Value) {
+    let serde_json::Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = serde_json::Value::Object(Default::default());
+    }
+    let target = target.as_object_mut().unwrap();
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(target.entry(key.clone()).or_insert(serde_json::Value::Null), value);
+        }
+    }
+}
+
+/// 反序列化配置，出错时附带字段的 JSON Pointer (如 `/proxy/port`)
+fn deserialize_config(value: serde_json::Value) -> Result<AppConfig, String> {
+    serde_path_to_error::deserialize(value).map_err(|e| {
+        let pointer: String = e
+            .path()
+            .iter()
+            .map(|segment| match segment {
+                serde_path_to_error::Segment::Seq { index } => format!("/{}", index),
+                serde_path_to_error::Segment::Map { key } => {
+                    format!("/{}", key.replace('~', "~0").replace('/', "~1"))
+                }
+                serde_path_to_error::Segment::Enum { variant } => format!("/{}", variant),
+                serde_path_to_error::Segment::Unknown => "/?".to_string(),
+            })
+            .collect();
+        let pointer = if pointer.is_empty() { "/".to_string() } else { pointer };
+        format!("配置字段 {} 无效: {}", pointer, e.inner())
+    })
+}
+
+/// 当前生效的环境变量覆盖路径 (JSON Pointer)
+pub fn overridden_paths() -> Vec<String> {
+    env_overrides().into_iter().map(|(path, _)| to_pointer(&path)).collect()
+}
+
+/// 收集 `ANTIGRAVITY_A__B=...` 形式的环境变量；未启用覆盖时为空
+fn env_overrides() -> Vec<(Vec<String>, String)> {
+    if !ENV_OVERRIDES_ENABLED.load(Ordering::Relaxed) {
+        return Vec::new();
+    }
+    let schema = serde_json::to_value(AppConfig::new()).unwrap_or_default();
+    let mut overrides = parse_env_overrides(std::env::vars(), &schema);
+    overrides.sort();
+    overrides
+}
+
+/// 只接受指向 `AppConfig` 已有字段的变量，避免拼写错误被静默忽略
+fn parse_env_overrides(
+    vars: impl Iterator<Item = (String, String)>,
+    schema: &serde_json::Value,
+) -> Vec<(Vec<String>, String)> {
+    vars.filter_map(|(key, value)| {
+        let rest = key.strip_prefix(ENV_OVERRIDE_PREFIX)?;
+        if !rest.contains("__") {
+            return None;
+        }
+        let path: Vec<String> = rest.split("__").map(|segment| segment.to_lowercase()).collect();
+        if lookup(schema, &path).is_none() {
+            tracing::warn!("忽略未知的配置覆盖变量 {}", key);
+            return None;
+        }
+        Some((path, value))
+    })
+    .collect()
+}
+
+/// 对配置应用环境变量覆盖
+fn with_env_overrides(config: AppConfig) -> Result<AppConfig, String> {
+    let overrides = env_overrides();
+    if overrides.is_empty() {
+        return Ok(config);
+    }
+    let mut value = serde_json::to_value(&config).map_err(|e| format!("序列化配置失败: {}", e))?;
+    apply_overrides(&mut value, &overrides);
+    deserialize_config(value).map_err(|e| format!("环境变量覆盖无效: {}", e))
+}
+
+/// 按目标字段的现有类型解析覆盖值：字符串字段原样使用，其余按 JSON 解析
+fn apply_overrides(value: &mut serde_json::Value, overrides: &[(Vec<String>, String)]) {
+    for (path, raw) in overrides {
+        let Some(slot) = lookup_mut(value, path) else {
+            continue;
+        };
+        *slot = if slot.is_string() {
+            serde_json::Value::String(raw.clone())
+        } else {
+            serde_json::from_str(raw).unwrap_or_else(|_| serde_json::Value::String(raw.clone()))
+        };
+    }
+}
+
+fn lookup<'a>(value: &'a serde_json::Value, path: &[String]) -> Option<&'a serde_json::Value> {
+    path.iter().try_fold(value, |current, key| current.get(key))
+}
+
+fn lookup_mut<'a>(value: &'a mut serde_json::Value, path: &[String]) -> Option<&'a mut serde_json::Value> {
+    path.iter().try_fold(value, |current, key| current.get_mut(key))
+}
+
+fn remove_path(value: &mut serde_json::Value, path: &[String]) {
+    if let Some((last, parent)) = path.split_last() {
+        if let Some(serde_json::Value::Object(map)) = lookup_mut(value, parent) {
+            map.remove(last);
+        }
+    }
+}
+
+fn to_pointer(path: &[String]) -> String {
+    path.iter().map(|segment| format!("/{}", segment)).collect()
+}
+
+fn revision_of(content: &[u8]) -> String {
+    let digest = format!("{:x}", Sha256::digest(content));
+    digest[..16].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_revision_changes_with_content() {
+        let empty = revision_of(b"");
+        assert_eq!(empty.len(), 16);
+        assert_eq!(empty, revision_of(b""));
+        assert_ne!(empty, revision_of(b"{}"));
+    }
+
+    #[test]
+    fn test_merge_patch_follows_rfc7396() {
+        let mut target = serde_json::json!({"a": "b", "c": {"d": "e", "f": "g"}});
+        merge_patch(&mut target, &serde_json::json!({"a": "z", "c": {"f": null}, "h": [1]}));
+        assert_eq!(target, serde_json::json!({"a": "z", "c": {"d": "e"}, "h": [1]}));
+
+        merge_patch(&mut target, &serde_json::json!(["replaced"]));
+        assert_eq!(target, serde_json::json!(["replaced"]));
+    }
+
+    #[test]
+    fn test_env_overrides_follow_field_types() {
+        let schema = serde_json::to_value(AppConfig::new()).unwrap();
+        let vars = vec![
+            ("ANTIGRAVITY_PROXY__PORT".to_string(), "9045".to_string()),
+            ("ANTIGRAVITY_PROXY__API_KEY".to_string(), "12345".to_string()),
+            ("ANTIGRAVITY_PROXY__UPSTREAM_PROXY__URL".to_string(), "http://127.0.0.1:7890".to_string()),
+            ("ANTIGRAVITY_PROXY__NO_SUCH_FIELD".to_string(), "1".to_string()),
+            ("ANTIGRAVITY_PORT".to_string(), "8765".to_string()),
+        ];
+        let overrides = parse_env_overrides(vars.into_iter(), &schema);
+        assert_eq!(overrides.len(), 3);
+
+        let mut value = schema.clone();
+        apply_overrides(&mut value, &overrides);
+        let config = deserialize_config(value).unwrap();
+        assert_eq!(config.proxy.port, 9045);
+        assert_eq!(config.proxy.api_key, "12345");
+        assert_eq!(config.proxy.upstream_proxy.url, "http://127.0.0.1:7890");
+        assert_eq!(to_pointer(&overrides[0].0), "/proxy/port");
+    }
+
+    #[test]
+    fn test_invalid_patch_reports_json_pointer() {
+        let mut merged = serde_json::to_value(AppConfig::new()).unwrap();
+        merge_patch(&mut merged, &serde_json::json!({"proxy": {"port": "not-a-port"}}));
+        let err = deserialize_config(merged).unwrap_err();
+        assert!(err.contains("/proxy/port"), "{}", err);
+
+        let mut merged = serde_json::to_value(AppConfig::new()).unwrap();
+        merge_patch(&mut merged, &serde_json::json!({"language": null}));
+        let err = deserialize_config(merged).unwrap_err();
+        assert!(err.contains("language"), "{}", err);
+    }
 }
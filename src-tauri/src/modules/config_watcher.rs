@@ -0,0 +1,96 @@
+//! 监听配置文件的外部修改 (如 Ansible、手动编辑)，重新加载并交给调用方热更新
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::future::Future;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::models::AppConfig;
+use crate::modules::config::{self, CONFIG_FILE};
+
+/// 连续写入的合并窗口
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// 启动配置文件监听
+///
+/// 外部修改通过校验后调用 `on_change`；本进程通过 `save_app_config` 写入的内容会被忽略。
+pub fn spawn<F, Fut>(on_change: F) -> Result<(), String>
+where
+    F: Fn(AppConfig) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    tokio::spawn(watch(on_change)?);
+    Ok(())
+}
+
+/// 建立配置文件监听，返回处理修改事件的循环；监听随循环结束而停止
+pub fn watch<F, Fut>(on_change: F) -> Result<impl Future<Output = ()> + Send, String>
+where
+    F: Fn(AppConfig) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let data_dir = crate::modules::account::get_data_dir()?;
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+    // 监听数据目录而非文件本身，兼容编辑器「写临时文件再重命名」的保存方式
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+        if let Ok(event) = res {
+            let relevant = matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+            ) && event
+                .paths
+                .iter()
+                .any(|path| path.file_name().is_some_and(|name| name == CONFIG_FILE));
+            if relevant {
+                let _ = tx.send(());
+            }
+        }
+    })
+    .map_err(|e| format!("创建配置文件监听失败: {}", e))?;
+    watcher
+        .watch(&data_dir, RecursiveMode::NonRecursive)
+        .map_err(|e| format!("监听配置目录失败: {}", e))?;
+
+    // 以启动时的内容为基准
+    let _ = config::mark_revision_seen();
+
+    Ok(async move {
+        // watcher 随任务存活
+        let _watcher = watcher;
+
+        while rx.recv().await.is_some() {
+            // 防抖：直到窗口内不再有新事件
+            while let Ok(Some(())) = tokio::time::timeout(DEBOUNCE, rx.recv()).await {}
+
+            match config::mark_revision_seen() {
+                Ok(Some(_)) => {}
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!("读取配置文件失败: {}", e);
+                    continue;
+                }
+            }
+
+            let loaded = match config::load_app_config() {
+                Ok(loaded) => loaded,
+                Err(e) => {
+                    warn!("配置文件被外部修改但无法解析，保持当前配置: {}", e);
+                    continue;
+                }
+            };
+
+            let issues = loaded.validate();
+            if !issues.is_empty() {
+                warn!(
+                    "配置文件被外部修改但未通过校验，保持当前配置: {}",
+                    crate::proxy::ConfigIssue::summarize(&issues)
+                );
+                continue;
+            }
+
+            info!("检测到配置文件外部修改，已重新加载");
+            on_change(loaded).await;
+        }
+    })
+}
@@ -0,0 +1,142 @@
+//! 首页看板：账号状态与配额的汇总
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::models::Account;
+
+use super::account::list_accounts;
+
+/// 按状态统计的账号数，各状态互斥 (优先级: 已禁用 > 403 > 反代禁用 > 可用)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, ToSchema)]
+pub struct AccountCounts {
+    pub total: usize,
+    pub active: usize,
+    pub disabled: usize,
+    pub proxy_disabled: usize,
+    pub forbidden: usize,
+}
+
+/// 所有账号各模型配额之和，以百分点计 (每个模型满额为 100)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, ToSchema)]
+pub struct QuotaTotals {
+    pub total: u64,
+    pub remaining: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum AccountStatus {
+    Active,
+    Disabled,
+    ProxyDisabled,
+    Forbidden,
+}
+
+impl AccountStatus {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            AccountStatus::Active => "active",
+            AccountStatus::Disabled => "disabled",
+            AccountStatus::ProxyDisabled => "proxy_disabled",
+            AccountStatus::Forbidden => "forbidden",
+        }
+    }
+}
+
+/// 看板所需的单个账号信息
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct AccountDigest {
+    pub(crate) status: AccountStatus,
+    pub(crate) quota_total: u64,
+    pub(crate) quota_remaining: u64,
+}
+
+impl AccountDigest {
+    pub(crate) fn of(account: &Account) -> Self {
+        let forbidden = account.quota.as_ref().is_some_and(|q| q.is_forbidden);
+        let status = if account.disabled {
+            AccountStatus::Disabled
+        } else if forbidden {
+            AccountStatus::Forbidden
+        } else if account.proxy_disabled {
+            AccountStatus::ProxyDisabled
+        } else {
+            AccountStatus::Active
+        };
+        let models = account.quota.as_ref().map(|q| q.models.as_slice()).unwrap_or_default();
+        Self {
+            status,
+            quota_total: models.len() as u64 * 100,
+            quota_remaining: models.iter().map(|m| m.percentage.clamp(0, 100) as u64).sum(),
+        }
+    }
+}
+
+fn summarize(digests: impl IntoIterator<Item = AccountDigest>) -> (AccountCounts, QuotaTotals) {
+    let mut counts = AccountCounts::default();
+    let mut quota = QuotaTotals::default();
+    for digest in digests {
+        counts.total += 1;
+        match digest.status {
+            AccountStatus::Active => counts.active += 1,
+            AccountStatus::Disabled => counts.disabled += 1,
+            AccountStatus::ProxyDisabled => counts.proxy_disabled += 1,
+            AccountStatus::Forbidden => counts.forbidden += 1,
+        }
+        quota.total += digest.quota_total;
+        quota.remaining += digest.quota_remaining;
+    }
+    (counts, quota)
+}
+
+/// 单个账号的状态名 (`active` / `disabled` / `proxy_disabled` / `forbidden`)
+pub fn account_status(account: &Account) -> &'static str {
+    AccountDigest::of(account).status.as_str()
+}
+
+pub fn summarize_accounts(accounts: &[Account]) -> (AccountCounts, QuotaTotals) {
+    summarize(accounts.iter().map(AccountDigest::of))
+}
+
+/// 汇总账号状态与配额 (账号数据来自内存缓存)
+pub fn account_summary() -> Result<(AccountCounts, QuotaTotals), String> {
+    Ok(summarize_accounts(&list_accounts()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{QuotaData, TokenData};
+
+    fn account(disabled: bool, proxy_disabled: bool, quota: Option<QuotaData>) -> Account {
+        let token = TokenData::new("at".to_string(), "rt".to_string(), 3600, None, None, None);
+        let mut account = Account::new("id".to_string(), "a@example.com".to_string(), token);
+        account.disabled = disabled;
+        account.proxy_disabled = proxy_disabled;
+        account.quota = quota;
+        account
+    }
+
+    #[test]
+    fn test_summarize_counts_statuses_and_quota() {
+        let mut quota = QuotaData::new();
+        quota.add_model("gemini-3-pro".to_string(), 40, String::new());
+        quota.add_model("claude-sonnet".to_string(), 100, String::new());
+        let mut forbidden = QuotaData::new();
+        forbidden.is_forbidden = true;
+
+        let accounts = [
+            account(false, false, Some(quota)),
+            account(true, true, None),
+            account(false, true, None),
+            account(false, true, Some(forbidden)),
+        ];
+        let (counts, totals) = summarize(accounts.iter().map(AccountDigest::of));
+
+        assert_eq!(
+            counts,
+            AccountCounts { total: 4, active: 1, disabled: 1, proxy_disabled: 1, forbidden: 1 }
+        );
+        assert_eq!(totals, QuotaTotals { total: 200, remaining: 140 });
+    }
+}
@@ -0,0 +1,119 @@
+//! 与本机 Antigravity IDE 数据库保持同步：IDE 轮换 Refresh Token 后自动导入
+
+use std::fmt;
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::models::Account;
+
+use super::account;
+use super::migration::{self, ImportOptions};
+use super::logger;
+
+/// 一次同步的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DbSyncOutcome {
+    /// Token 未变化
+    Unchanged,
+    /// 检测到新 Token 并已导入
+    Imported,
+    /// 数据库不存在、被锁定或无登录状态
+    Unavailable,
+    /// 导入失败 (如网络错误)
+    Failed,
+}
+
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct DbSyncStatus {
+    /// 后台同步间隔 (分钟)，0 表示未开启
+    pub interval_minutes: u32,
+    /// 最近一次同步的 Unix 时间戳 (秒)
+    pub last_sync_at: Option<i64>,
+    pub last_outcome: Option<DbSyncOutcome>,
+    /// 最近一次导入的账号
+    pub last_imported_email: Option<String>,
+    pub last_error: Option<String>,
+    /// 连续失败次数 (unavailable / failed)
+    pub consecutive_failures: u32,
+}
+
+#[derive(Debug)]
+pub enum DbSyncError {
+    Unavailable(String),
+    Failed(String),
+}
+
+impl fmt::Display for DbSyncError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbSyncError::Unavailable(e) | DbSyncError::Failed(e) => f.write_str(e),
+        }
+    }
+}
+
+static STATUS: Lazy<RwLock<DbSyncStatus>> = Lazy::new(|| RwLock::new(DbSyncStatus::default()));
+
+/// 当前同步状态
+pub fn status() -> DbSyncStatus {
+    let mut status = STATUS.read().unwrap_or_else(|e| e.into_inner()).clone();
+    status.interval_minutes = super::config::load_app_config()
+        .map(|c| c.db_sync_interval_minutes)
+        .unwrap_or(0);
+    status
+}
+
+fn record(result: &Result<Option<Account>, DbSyncError>) {
+    let mut status = STATUS.write().unwrap_or_else(|e| e.into_inner());
+    status.last_sync_at = Some(chrono::Utc::now().timestamp());
+    let outcome = match result {
+        Ok(None) => DbSyncOutcome::Unchanged,
+        Ok(Some(account)) => {
+            status.last_imported_email = Some(account.email.clone());
+            DbSyncOutcome::Imported
+        }
+        Err(DbSyncError::Unavailable(_)) => DbSyncOutcome::Unavailable,
+        Err(DbSyncError::Failed(_)) => DbSyncOutcome::Failed,
+    };
+    match result {
+        Ok(_) => {
+            status.last_error = None;
+            status.consecutive_failures = 0;
+        }
+        Err(e) => {
+            // 只在开始失败时记一次日志，避免每个周期重复输出
+            if status.consecutive_failures == 0 {
+                logger::log_warn(&format!("[DbSync] 同步数据库失败，将在下个周期重试: {}", e));
+            }
+            status.last_error = Some(e.to_string());
+            status.consecutive_failures += 1;
+        }
+    }
+    status.last_outcome = Some(outcome);
+}
+
+async fn sync_inner() -> Result<Option<Account>, DbSyncError> {
+    let db_refresh_token = migration::get_refresh_token_from_db().map_err(DbSyncError::Unavailable)?;
+    let current = account::get_current_account().map_err(DbSyncError::Failed)?;
+    if current.is_some_and(|acc| acc.token.refresh_token == db_refresh_token) {
+        return Ok(None);
+    }
+
+    let outcome = migration::import_from_db(ImportOptions::default())
+        .await
+        .and_then(|report| report.into_single())
+        .map_err(DbSyncError::Failed)?;
+    let _ = account::set_current_account_id(&outcome.account.id);
+    logger::log_info(&format!("[DbSync] 已从 IDE 数据库同步账号: {}", outcome.account.email));
+    Ok(Some(outcome.account))
+}
+
+/// 读取 IDE 数据库，Refresh Token 与当前账号不同时导入并设为当前账号；返回新导入的账号
+pub async fn sync_from_db() -> Result<Option<Account>, DbSyncError> {
+    let result = sync_inner().await;
+    record(&result);
+    result
+}
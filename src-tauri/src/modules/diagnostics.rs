@@ -0,0 +1,178 @@
+//! 问题反馈用的诊断包 (zip)：脱敏配置、应用日志、请求日志、统计、账号概况与系统信息
+
+use serde::Serialize;
+use std::io::{Cursor, Write};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::models::{Account, AppConfig};
+use crate::proxy::monitor::{ProxyRequestLog, ProxyStats};
+
+use super::audit::{redact, redact_text};
+use super::dashboard::{account_status, summarize_accounts, AccountCounts};
+use super::system_info::SystemInfo;
+
+/// 诊断包包含的应用日志行数
+pub const APP_LOG_LINES: usize = 1000;
+/// 诊断包包含的请求日志条数
+pub const PROXY_LOG_ENTRIES: usize = 500;
+
+/// 打包所需的原始数据，脱敏在打包时统一进行
+pub struct DiagnosticsInput {
+    pub config: AppConfig,
+    pub app_log: Vec<String>,
+    pub proxy_logs: Vec<ProxyRequestLog>,
+    pub proxy_stats: ProxyStats,
+    pub accounts: Vec<Account>,
+    pub system: SystemInfo,
+    /// Token 刷新熔断状态
+    pub oauth: crate::modules::oauth::RefreshBreakerStatus,
+}
+
+/// 账号概况：只含状态，不含邮箱与令牌
+#[derive(Serialize)]
+struct AccountsOverview {
+    counts: AccountCounts,
+    accounts: Vec<AccountStatusEntry>,
+}
+
+#[derive(Serialize)]
+struct AccountStatusEntry {
+    id: String,
+    status: &'static str,
+    subscription_tier: Option<String>,
+    quota_updated_at: Option<i64>,
+}
+
+fn accounts_overview(accounts: &[Account]) -> AccountsOverview {
+    let (counts, _) = summarize_accounts(accounts);
+    AccountsOverview {
+        counts,
+        accounts: accounts
+            .iter()
+            .map(|account| AccountStatusEntry {
+                id: account.id.clone(),
+                status: account_status(account),
+                subscription_tier: account.quota.as_ref().and_then(|q| q.subscription_tier.clone()),
+                quota_updated_at: account.quota.as_ref().map(|q| q.last_updated),
+            })
+            .collect(),
+    }
+}
+
+/// 序列化并脱敏
+fn redacted_json<T: Serialize>(value: &T) -> Result<String, String> {
+    let value = serde_json::to_value(value).map_err(|e| format!("序列化诊断数据失败: {}", e))?;
+    serde_json::to_string_pretty(&redact(&value)).map_err(|e| format!("序列化诊断数据失败: {}", e))
+}
+
+/// 生成诊断包
+pub fn build_bundle(input: DiagnosticsInput) -> Result<Vec<u8>, String> {
+    // 请求日志去掉请求/响应体
+    let proxy_logs: Vec<ProxyRequestLog> = input
+        .proxy_logs
+        .into_iter()
+        .take(PROXY_LOG_ENTRIES)
+        .map(|mut log| {
+            log.request_body = None;
+            log.response_body = None;
+            log
+        })
+        .collect();
+    let app_log: String = input
+        .app_log
+        .iter()
+        .map(|line| redact_text(line) + "\n")
+        .collect();
+
+    let files: Vec<(&str, String)> = vec![
+        ("config.json", redacted_json(&input.config)?),
+        ("app.log", app_log),
+        ("proxy_logs.json", redacted_json(&proxy_logs)?),
+        ("proxy_stats.json", redacted_json(&input.proxy_stats)?),
+        ("accounts.json", redacted_json(&accounts_overview(&input.accounts))?),
+        ("system.json", redacted_json(&input.system)?),
+        ("oauth.json", redacted_json(&input.oauth)?),
+    ];
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, content) in files {
+        writer
+            .start_file(name, options)
+            .map_err(|e| format!("写入诊断包失败: {}", e))?;
+        writer
+            .write_all(content.as_bytes())
+            .map_err(|e| format!("写入诊断包失败: {}", e))?;
+    }
+    let cursor = writer.finish().map_err(|e| format!("写入诊断包失败: {}", e))?;
+    Ok(cursor.into_inner())
+}
+
+/// 带时间戳的下载文件名
+pub fn bundle_filename(now: chrono::DateTime<chrono::Utc>) -> String {
+    format!("antigravity-diagnostics-{}.zip", now.format("%Y%m%d-%H%M%S"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TokenData;
+    use crate::proxy::ApiKeyEntry;
+    use std::io::Read;
+
+    #[test]
+    fn test_bundle_does_not_leak_secrets() {
+        let mut config = AppConfig::new();
+        config.proxy.api_key = "sk-0123456789abcdef".to_string();
+        config.proxy.api_keys.push(ApiKeyEntry {
+            name: "alice".to_string(),
+            key: "sk-alice-0123456789".to_string(),
+            enabled: true,
+            requests_per_minute: None,
+            concurrent_requests: None,
+            group: None,
+            admin: false,
+        });
+
+        let token = TokenData::new(
+            "ya29.access-secret".to_string(),
+            "1//refresh-secret".to_string(),
+            3600,
+            None,
+            None,
+            None,
+        );
+        let account = Account::new("acc-1".to_string(), "a@example.com".to_string(), token);
+
+        let log = ProxyRequestLog {
+            duration: 10,
+            error: Some("upstream rejected Bearer ya29.leaked".to_string()),
+            request_body: Some("{\"api_key\":\"sk-in-body-0123\"}".to_string()),
+            api_key_name: Some("alice".to_string()),
+            ..ProxyRequestLog::test_default()
+        };
+
+        let bundle = build_bundle(DiagnosticsInput {
+            config,
+            app_log: vec!["token refresh failed: refresh_token=1//refresh-secret".to_string()],
+            proxy_logs: vec![log],
+            proxy_stats: ProxyStats::default(),
+            accounts: vec![account],
+            system: crate::modules::system_info::collect(None),
+            oauth: crate::modules::oauth::refresh_breaker_status(),
+        })
+        .unwrap();
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(bundle)).unwrap();
+        assert_eq!(archive.len(), 7);
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i).unwrap();
+            let mut content = String::new();
+            file.read_to_string(&mut content).unwrap();
+            for secret in ["sk-", "1//refresh-secret", "ya29."] {
+                assert!(!content.contains(secret), "{} leaks {}", file.name(), secret);
+            }
+        }
+    }
+}
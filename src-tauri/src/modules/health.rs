@@ -0,0 +1,151 @@
+//! 健康检查：数据目录可写性、账号存储、反代服务状态与后台任务错误
+//!
+//! 文件系统探测结果缓存一段时间，负载均衡频繁探测时不会反复读写磁盘。
+
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+
+use crate::utils::panic::{last_task_error, TaskError};
+
+/// 文件系统探测结果的缓存时间
+const PROBE_CACHE_TTL: Duration = Duration::from_secs(10);
+
+/// 反代服务状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProxyHealth {
+    Running,
+    Stopped,
+    /// 服务实例仍在，但服务器任务已退出
+    Crashed,
+}
+
+#[derive(Debug, Clone)]
+struct StorageProbe {
+    data_dir_writable: bool,
+    accounts_loaded: bool,
+    account_count: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct HealthReport {
+    /// 各项均正常时为 `ok`，否则为 `degraded`
+    pub status: String,
+    pub version: String,
+    pub mode: String,
+    /// 数据目录的数据格式版本
+    pub schema_version: u32,
+    /// 当前生效的 profile (--profile)
+    pub profile: Option<String>,
+    /// 数据目录可写 (写入并删除临时文件)
+    pub data_dir_writable: bool,
+    /// 账号索引可读
+    pub accounts_loaded: bool,
+    pub account_count: Option<usize>,
+    pub proxy: ProxyHealth,
+    /// 最近一次后台任务失败 (panic) 的信息
+    pub last_task_error: Option<TaskError>,
+    /// 客户端视角的服务地址 (配置的 `public_url` 或由转发头推断)，便于排查反向代理配置
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_url: Option<String>,
+}
+
+static PROBE_CACHE: Mutex<Option<(Instant, StorageProbe)>> = Mutex::new(None);
+
+/// 在目录中写入并删除临时文件，判断是否可写
+pub(crate) fn probe_writable(dir: &Path) -> bool {
+    let path = dir.join(format!(".health-probe-{}", std::process::id()));
+    let ok = std::fs::write(&path, b"ok").is_ok();
+    let _ = std::fs::remove_file(&path);
+    ok
+}
+
+fn probe_storage() -> StorageProbe {
+    let data_dir_writable = super::account::get_data_dir()
+        .map(|dir| probe_writable(&dir))
+        .unwrap_or(false);
+    let index = super::account::load_account_index();
+    StorageProbe {
+        data_dir_writable,
+        accounts_loaded: index.is_ok(),
+        account_count: index.ok().map(|index| index.accounts.len()),
+    }
+}
+
+/// 读取缓存的探测结果，过期时在阻塞线程池中重新探测
+async fn cached_storage_probe() -> StorageProbe {
+    if let Some((at, probe)) = PROBE_CACHE.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        if at.elapsed() < PROBE_CACHE_TTL {
+            return probe.clone();
+        }
+    }
+    let probe = tokio::task::spawn_blocking(probe_storage)
+        .await
+        .unwrap_or(StorageProbe {
+            data_dir_writable: false,
+            accounts_loaded: false,
+            account_count: None,
+        });
+    *PROBE_CACHE.lock().unwrap_or_else(|e| e.into_inner()) = Some((Instant::now(), probe.clone()));
+    probe
+}
+
+fn build_report(storage: StorageProbe, proxy: ProxyHealth, mode: &str, last_task_error: Option<TaskError>) -> HealthReport {
+    let healthy = storage.data_dir_writable && storage.accounts_loaded && proxy != ProxyHealth::Crashed;
+    HealthReport {
+        status: if healthy { "ok" } else { "degraded" }.to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        mode: mode.to_string(),
+        schema_version: super::schema::SCHEMA_VERSION,
+        profile: super::account::active_profile(),
+        data_dir_writable: storage.data_dir_writable,
+        accounts_loaded: storage.accounts_loaded,
+        account_count: storage.account_count,
+        proxy,
+        last_task_error,
+        public_url: None,
+    }
+}
+
+/// 生成完整的健康报告
+pub async fn report(proxy: ProxyHealth, mode: &str) -> HealthReport {
+    build_report(cached_storage_probe().await, proxy, mode, last_task_error())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn storage(writable: bool, loaded: bool) -> StorageProbe {
+        StorageProbe {
+            data_dir_writable: writable,
+            accounts_loaded: loaded,
+            account_count: loaded.then_some(2),
+        }
+    }
+
+    #[test]
+    fn test_probe_writable() {
+        let dir = std::env::temp_dir().join(format!("ag-health-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(probe_writable(&dir));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!probe_writable(&dir));
+    }
+
+    #[test]
+    fn test_report_status() {
+        let ok = build_report(storage(true, true), ProxyHealth::Stopped, "web", None);
+        assert_eq!(ok.status, "ok");
+        assert_eq!(ok.account_count, Some(2));
+
+        assert_eq!(build_report(storage(false, true), ProxyHealth::Running, "web", None).status, "degraded");
+        assert_eq!(build_report(storage(true, false), ProxyHealth::Running, "web", None).status, "degraded");
+        assert_eq!(build_report(storage(true, true), ProxyHealth::Crashed, "web", None).status, "degraded");
+    }
+}
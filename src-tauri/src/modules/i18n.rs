@@ -1,5 +1,9 @@
+use once_cell::sync::Lazy;
 use serde_json::Value;
 use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::models::AppConfig;
 
 /// 托盘文本结构
 #[derive(Debug, Clone)]
@@ -54,3 +58,178 @@ pub fn get_tray_texts(lang: &str) -> TrayTexts {
         forbidden: t.get("forbidden").cloned().unwrap_or_else(|| "Account Forbidden".to_string()),
     }
 }
+
+/// 管理 API 错误信息与日志使用的语言
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lang {
+    Zh,
+    En,
+}
+
+impl Lang {
+    /// 服务端错误详情与日志文本的编写语言
+    pub const SOURCE: Lang = Lang::Zh;
+
+    /// 解析语言标签 (如 `zh-CN`、`en-US`)，不支持的语言返回 None
+    pub fn parse(tag: &str) -> Option<Lang> {
+        let primary = tag.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+        match primary.as_str() {
+            "zh" => Some(Lang::Zh),
+            "en" => Some(Lang::En),
+            _ => None,
+        }
+    }
+
+    /// 配置中的界面语言 (`AppConfig.language`)；中文以外的语言使用英文
+    pub fn from_config(language: &str) -> Lang {
+        Lang::parse(language).unwrap_or(Lang::En)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Lang::Zh => "zh",
+            Lang::En => "en",
+        }
+    }
+
+    fn other(self) -> Lang {
+        match self {
+            Lang::Zh => Lang::En,
+            Lang::En => Lang::Zh,
+        }
+    }
+}
+
+/// 按 `Accept-Language` 的权重选出第一个支持的语言
+pub fn from_accept_language(header: &str) -> Option<Lang> {
+    let mut candidates: Vec<(f32, usize, Lang)> = header
+        .split(',')
+        .enumerate()
+        .filter_map(|(index, item)| {
+            let mut parts = item.split(';');
+            let lang = Lang::parse(parts.next()?)?;
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+            (quality > 0.0).then_some((quality, index, lang))
+        })
+        .collect();
+    // 权重相同时保持原顺序
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+    candidates.first().map(|(_, _, lang)| *lang)
+}
+
+/// 管理 API 错误码对应的文本，来自前端语言包的 `errors.api`
+static API_ERRORS: Lazy<HashMap<(Lang, String), String>> = Lazy::new(|| {
+    let mut map = HashMap::new();
+    for (lang, json_content) in [
+        (Lang::Zh, include_str!("../../../src/locales/zh.json")),
+        (Lang::En, include_str!("../../../src/locales/en.json")),
+    ] {
+        let v: Value = serde_json::from_str(json_content).unwrap_or_else(|_| serde_json::json!({}));
+        let Some(errors) = v.pointer("/errors/api").and_then(|e| e.as_object()) else {
+            continue;
+        };
+        for (code, text) in errors {
+            if let Some(text) = text.as_str().filter(|s| !s.trim().is_empty()) {
+                map.insert((lang, code.clone()), text.to_string());
+            }
+        }
+    }
+    map
+});
+
+/// 错误码的文本；缺少该语言的翻译时使用另一种语言，均缺失时返回错误码本身
+pub fn api_error_text(code: &str, lang: Lang) -> String {
+    lookup(&API_ERRORS, code, lang)
+}
+
+fn lookup(catalog: &HashMap<(Lang, String), String>, code: &str, lang: Lang) -> String {
+    catalog
+        .get(&(lang, code.to_string()))
+        .or_else(|| catalog.get(&(lang.other(), code.to_string())))
+        .cloned()
+        .unwrap_or_else(|| code.to_string())
+}
+
+/// 面向 `lang` 的错误信息：详情以中文编写，中文时直接使用详情，其他语言使用错误码的翻译
+pub fn localize(code: &str, detail: Option<&str>, lang: Lang) -> String {
+    match detail {
+        Some(detail) if lang == Lang::SOURCE => detail.to_string(),
+        _ => api_error_text(code, lang),
+    }
+}
+
+/// 写入日志的错误信息：使用配置的日志语言，非中文时附上原始详情以便排查
+pub fn log_text(code: &str, detail: Option<&str>) -> String {
+    let lang = log_lang();
+    match detail {
+        Some(detail) if lang != Lang::SOURCE => format!("{}: {}", api_error_text(code, lang), detail),
+        _ => localize(code, detail, lang),
+    }
+}
+
+tokio::task_local! {
+    /// 当前管理 API 请求选定的语言，由语言中间件建立
+    static REQUEST_LANG: Lang;
+}
+
+/// 在指定语言下处理请求
+pub async fn scope<F: std::future::Future>(lang: Lang, f: F) -> F::Output {
+    REQUEST_LANG.scope(lang, f).await
+}
+
+/// 配置的 (界面语言, 日志语言)，配置保存后热更新，避免每次生成错误信息都读取配置文件
+static CONFIGURED_LANGS: Lazy<RwLock<(Lang, Lang)>> = Lazy::new(|| {
+    RwLock::new(
+        crate::modules::config::load_app_config()
+            .map(|config| langs_of(&config))
+            .unwrap_or((Lang::SOURCE, Lang::SOURCE)),
+    )
+});
+
+fn langs_of(config: &AppConfig) -> (Lang, Lang) {
+    (Lang::from_config(&config.language), Lang::from_config(&config.log_language))
+}
+
+/// 配置保存后更新缓存的界面语言与日志语言
+pub fn update(config: &AppConfig) {
+    *CONFIGURED_LANGS.write().unwrap_or_else(|e| e.into_inner()) = langs_of(config);
+}
+
+/// 配置的界面语言，读取失败时为中文
+pub fn configured_lang() -> Lang {
+    CONFIGURED_LANGS.read().unwrap_or_else(|e| e.into_inner()).0
+}
+
+/// 当前请求的语言，不在请求上下文中时使用配置的界面语言
+pub fn request_lang() -> Lang {
+    REQUEST_LANG.try_with(|lang| *lang).unwrap_or_else(|_| configured_lang())
+}
+
+/// 日志 (含审计日志) 统一使用的语言，与请求语言无关
+pub fn log_lang() -> Lang {
+    CONFIGURED_LANGS.read().unwrap_or_else(|e| e.into_inner()).1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_language_and_fallback() {
+        assert_eq!(from_accept_language("en-US,en;q=0.9,zh-CN;q=0.8"), Some(Lang::En));
+        assert_eq!(from_accept_language("ja, zh-TW;q=0.7, en;q=0.5"), Some(Lang::Zh));
+        assert_eq!(from_accept_language("zh;q=0.2, en;q=0.9"), Some(Lang::En));
+        assert_eq!(from_accept_language("en;q=0, fr"), None);
+        assert_eq!(Lang::from_config("zh-TW"), Lang::Zh);
+        assert_eq!(Lang::from_config("ja"), Lang::En);
+
+        assert_eq!(localize("not_found", Some("账号不存在: a"), Lang::Zh), "账号不存在: a");
+        assert_eq!(localize("not_found", Some("账号不存在: a"), Lang::En), api_error_text("not_found", Lang::En));
+        // 缺少翻译时使用另一种语言，均缺失时退回错误码本身，不返回空字符串
+        let catalog = HashMap::from([((Lang::Zh, "only_zh".to_string()), "仅中文".to_string())]);
+        assert_eq!(lookup(&catalog, "only_zh", Lang::En), "仅中文");
+        assert_eq!(lookup(&catalog, "no_such_code", Lang::En), "no_such_code");
+    }
+}
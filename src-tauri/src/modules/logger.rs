@@ -1,9 +1,78 @@
 use tracing::{info, warn, error};
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use serde::Serialize;
+use std::collections::VecDeque;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::Mutex;
+use utoipa::ToSchema;
 use crate::modules::account::get_data_dir;
 
+/// 内存中保留的最近警告条数
+const RECENT_WARNINGS_CAPACITY: usize = 50;
+
+static RECENT_WARNINGS: Mutex<VecDeque<WarningEvent>> = Mutex::new(VecDeque::new());
+
+/// 一条 WARN/ERROR 级别的日志
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WarningEvent {
+    /// Unix 时间戳 (秒)
+    pub timestamp: i64,
+    pub level: String,
+    pub message: String,
+}
+
+fn push_warning(event: WarningEvent) {
+    let mut warnings = RECENT_WARNINGS.lock().unwrap_or_else(|e| e.into_inner());
+    if warnings.len() >= RECENT_WARNINGS_CAPACITY {
+        warnings.pop_front();
+    }
+    warnings.push_back(event);
+}
+
+/// 最近的警告/错误日志，最新的在前
+pub fn recent_warnings(limit: usize) -> Vec<WarningEvent> {
+    let warnings = RECENT_WARNINGS.lock().unwrap_or_else(|e| e.into_inner());
+    warnings.iter().rev().take(limit).cloned().collect()
+}
+
+/// 提取事件的 message 字段
+#[derive(Default)]
+struct MessageVisitor(String);
+
+impl tracing::field::Visit for MessageVisitor {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        if field.name() == "message" {
+            self.0 = value.to_string();
+        }
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{:?}", value);
+        }
+    }
+}
+
+/// 把 WARN 及以上的事件记入内存环形缓冲，供看板展示
+struct WarningBufferLayer;
+
+impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for WarningBufferLayer {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: tracing_subscriber::layer::Context<'_, S>) {
+        let level = *event.metadata().level();
+        if level > tracing::Level::WARN {
+            return;
+        }
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        push_warning(WarningEvent {
+            timestamp: chrono::Utc::now().timestamp(),
+            level: level.to_string(),
+            message: visitor.0,
+        });
+    }
+}
+
 // 自定义本地时区时间格式化器
 struct LocalTimer;
 
@@ -51,11 +120,13 @@ pub fn init_logger() {
         .with_timer(LocalTimer);
         
     // 3. 文件输出层 (关闭 ANSI 格式化，使用本地时区)
+    //    span 结束时输出耗时 (请求阶段 span 为 debug 级别，默认不输出)
     let file_layer = fmt::Layer::new()
         .with_writer(non_blocking)
         .with_ansi(false)
         .with_target(true)
         .with_level(true)
+        .with_span_events(fmt::format::FmtSpan::CLOSE)
         .with_timer(LocalTimer);
 
     // 4. 设置过滤层 (默认使用 INFO 级别以减少日志体积)
@@ -67,11 +138,15 @@ pub fn init_logger() {
         .with(filter_layer)
         .with(console_layer)
         .with(file_layer)
+        .with(WarningBufferLayer)
         .try_init();
 
     // 泄漏 _guard 以确保其生命周期持续到程序退出
     // 这是使用 tracing_appender::non_blocking 时的推荐做法（如果不需要手动刷盘）
     std::mem::forget(_guard);
+
+    // panic 信息与调用栈写入日志
+    crate::utils::panic::install_hook();
     
     info!("日志系统已完成初始化 (终端控制台 + 文件持久化)");
     
@@ -146,6 +221,38 @@ pub fn cleanup_old_logs(days_to_keep: u64) -> Result<(), String> {
     Ok(())
 }
 
+/// 读取应用日志最后 `limit` 行 (按时间先后)，跨越按天滚动的多个文件
+pub fn tail_lines(limit: usize) -> Result<Vec<String>, String> {
+    let log_dir = get_log_dir()?;
+    let mut files: Vec<PathBuf> = fs::read_dir(&log_dir)
+        .map_err(|e| format!("读取日志目录失败: {}", e))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("app.log"))
+        })
+        .collect();
+    // 文件名带日期后缀，按名称排序即按时间排序
+    files.sort();
+
+    let mut lines: VecDeque<String> = VecDeque::with_capacity(limit);
+    for path in files.iter().rev() {
+        if lines.len() >= limit {
+            break;
+        }
+        let content = fs::read(path).map_err(|e| format!("读取日志文件失败: {}", e))?;
+        let content = String::from_utf8_lossy(&content);
+        for line in content.lines().rev() {
+            if lines.len() >= limit {
+                break;
+            }
+            lines.push_front(line.to_string());
+        }
+    }
+    Ok(lines.into())
+}
+
 /// 清理日志缓存 (采用截断模式以保持文件句柄有效)
 pub fn clear_logs() -> Result<(), String> {
     let log_dir = get_log_dir()?;
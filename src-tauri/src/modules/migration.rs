@@ -1,231 +1,475 @@
+//! 从 V1 数据、Antigravity 数据库或上传的文件导入账号
+//!
+//! 导入分为三步：读取来源条目 → 解析邮箱并生成计划 (不写入任何文件) → 按计划写入。
+//! dry-run 只执行前两步。
+
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use utoipa::ToSchema;
 use base64::{Engine as _, engine::general_purpose};
-use crate::models::{TokenData, Account};
-use crate::modules::{account, db};
+use crate::models::{AccountSummary, TokenData};
+use crate::modules::{account, db, logger};
+use crate::modules::account::{ImportOutcome, OnDuplicate};
 use crate::utils::protobuf;
 
-/// 扫描并导入 V1 数据
-pub async fn import_from_v1() -> Result<Vec<Account>, String> {
+/// 导入选项
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+pub struct ImportOptions {
+    /// 邮箱已存在时的处理方式，缺省为 `update`
+    #[serde(default)]
+    pub on_duplicate: OnDuplicate,
+    /// 只返回导入计划，不写入任何文件
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// 计划中的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PlannedAction {
+    Create,
+    Update,
+    Skip,
+    Error,
+}
+
+/// 单个来源条目的导入计划
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ImportPlanEntry {
+    /// 来源条目 (V1 账号 ID、数据库路径等)
+    pub source: String,
+    pub email: Option<String>,
+    pub action: PlannedAction,
+    /// 将被更新或跳过的已有账号
+    pub existing_account_id: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub plan: Vec<ImportPlanEntry>,
+    /// 实际导入结果，dry-run 时为空
+    pub outcomes: Vec<ImportOutcome>,
+}
+
+impl ImportReport {
+    /// 单账号导入的结果，未导入时返回计划中的错误
+    pub fn into_single(self) -> Result<ImportOutcome, String> {
+        if let Some(outcome) = self.outcomes.into_iter().next() {
+            return Ok(outcome);
+        }
+        Err(self
+            .plan
+            .into_iter()
+            .find_map(|entry| entry.error)
+            .unwrap_or_else(|| "没有导入任何账号".to_string()))
+    }
+}
+
+/// 来源中的一个账号条目
+struct ImportSource {
+    label: String,
+    /// 来源中记录的邮箱，Token 刷新失败时作为占位
+    email_hint: Option<String>,
+    refresh_token: Result<String, String>,
+    /// Token 刷新失败时仍以占位邮箱导入 (V1 的兼容行为)
+    allow_unverified: bool,
+}
+
+struct ResolvedAccount {
+    email: String,
+    name: Option<String>,
+    token: TokenData,
+}
+
+/// 已解析邮箱的条目
+struct ResolvedEntry {
+    source: String,
+    account: Result<ResolvedAccount, String>,
+}
+
+/// 刷新 Token 并获取邮箱，只发起网络请求，不写入文件
+async fn resolve(source: ImportSource) -> ResolvedEntry {
+    let account = match source.refresh_token {
+        Ok(refresh_token) => resolve_token(refresh_token, source.email_hint, source.allow_unverified).await,
+        Err(e) => Err(e),
+    };
+    ResolvedEntry { source: source.label, account }
+}
+
+async fn resolve_token(
+    refresh_token: String,
+    email_hint: Option<String>,
+    allow_unverified: bool,
+) -> Result<ResolvedAccount, String> {
     use crate::modules::oauth;
 
+    let placeholder = || email_hint.clone().unwrap_or_else(|| "Unknown".to_string());
+    let token_resp = match oauth::refresh_access_token(&refresh_token).await {
+        Ok(token_resp) => token_resp,
+        Err(e) if allow_unverified => {
+            logger::log_warn(&format!("Token 刷新失败 (可能过期): {}", e));
+            let email = placeholder();
+            let token = TokenData::new("imported_access_token".to_string(), refresh_token, 0, Some(email.clone()), None, None);
+            return Ok(ResolvedAccount { email, name: None, token });
+        }
+        Err(e) => return Err(e),
+    };
+    let (email, name) = match oauth::get_user_info(&token_resp.access_token).await {
+        Ok(user_info) => (user_info.email, user_info.name),
+        Err(_) if allow_unverified => (placeholder(), None),
+        Err(e) => return Err(e),
+    };
+
+    let token = TokenData::new(
+        token_resp.access_token,
+        refresh_token,
+        token_resp.expires_in,
+        Some(email.clone()),
+        None, // project_id 将在需要时获取
+        None, // session_id 将在 token_manager 中生成
+    );
+    Ok(ResolvedAccount { email, name, token })
+}
+
+/// 对照已有账号 (邮箱不区分大小写) 生成计划；同一批次中重复的邮箱按已存在处理
+fn plan_imports(entries: &[ResolvedEntry], existing: &[AccountSummary], on_duplicate: OnDuplicate) -> Vec<ImportPlanEntry> {
+    let mut known: HashMap<String, Option<String>> = existing
+        .iter()
+        .map(|s| (s.email.to_lowercase(), Some(s.id.clone())))
+        .collect();
+
+    entries
+        .iter()
+        .map(|entry| match &entry.account {
+            Err(e) => ImportPlanEntry {
+                source: entry.source.clone(),
+                email: None,
+                action: PlannedAction::Error,
+                existing_account_id: None,
+                error: Some(e.clone()),
+            },
+            Ok(resolved) => {
+                let key = resolved.email.to_lowercase();
+                let (action, existing_account_id) = match known.get(&key) {
+                    Some(id) if on_duplicate == OnDuplicate::Skip => (PlannedAction::Skip, id.clone()),
+                    Some(id) => (PlannedAction::Update, id.clone()),
+                    None => {
+                        known.insert(key, None);
+                        (PlannedAction::Create, None)
+                    }
+                };
+                ImportPlanEntry {
+                    source: entry.source.clone(),
+                    email: Some(resolved.email.clone()),
+                    action,
+                    existing_account_id,
+                    error: None,
+                }
+            }
+        })
+        .collect()
+}
+
+/// 按计划写入，写入失败的条目在计划中改记为错误
+fn apply_plan(entries: Vec<ResolvedEntry>, plan: &mut [ImportPlanEntry], on_duplicate: OnDuplicate) -> Vec<ImportOutcome> {
+    let mut outcomes = Vec::new();
+    for (entry, planned) in entries.into_iter().zip(plan.iter_mut()) {
+        let Ok(resolved) = entry.account else {
+            continue;
+        };
+        let email = resolved.email.clone();
+        match account::import_account(resolved.email, resolved.name, resolved.token, on_duplicate) {
+            Ok(outcome) => {
+                logger::log_info(&format!("导入成功 ({:?}): {}", outcome.action, email));
+                outcomes.push(outcome);
+            }
+            Err(e) => {
+                logger::log_error(&format!("导入保存失败 {}: {}", email, e));
+                planned.action = PlannedAction::Error;
+                planned.error = Some(e);
+            }
+        }
+    }
+    outcomes
+}
+
+async fn run_import(sources: Vec<ImportSource>, options: ImportOptions) -> Result<ImportReport, String> {
+    let mut entries = Vec::with_capacity(sources.len());
+    for source in sources {
+        logger::log_info(&format!("正在解析账号: {}", source.label));
+        entries.push(resolve(source).await);
+    }
+
+    let existing = account::load_account_index()?.accounts;
+    let mut plan = plan_imports(&entries, &existing, options.on_duplicate);
+    let outcomes = if options.dry_run {
+        Vec::new()
+    } else {
+        apply_plan(entries, &mut plan, options.on_duplicate)
+    };
+    Ok(ImportReport { dry_run: options.dry_run, plan, outcomes })
+}
+
+// ============================================================================
+// V1 数据
+// ============================================================================
+
+/// V1 索引文件的可能文件名
+const V1_INDEX_FILES: [&str; 2] = ["antigravity_accounts.json", "accounts.json"];
+
+/// 扫描并导入 V1 数据
+pub async fn import_from_v1(options: ImportOptions) -> Result<ImportReport, String> {
     let home = dirs::home_dir().ok_or("无法获取主目录")?;
-    
+
     // V1 数据目录 (根据 utils.py 确认全平台统一)
     let v1_dir = home.join(".antigravity-agent");
-    
-    let mut imported_accounts = Vec::new();
-    
-    // 尝试多个可能的文件名
-    let index_files = vec![
-        "antigravity_accounts.json", // Directly use string literal
-        "accounts.json"
-    ];
-    
+    run_import(collect_v1_sources(&v1_dir)?, options).await
+}
+
+/// 读取 V1 索引中的账号条目，无法读取的条目以错误形式保留
+fn collect_v1_sources(v1_dir: &Path) -> Result<Vec<ImportSource>, String> {
+    let mut sources = Vec::new();
     let mut found_index = false;
 
-    for index_filename in index_files {
+    for index_filename in V1_INDEX_FILES {
         let v1_accounts_path = v1_dir.join(index_filename);
-        
         if !v1_accounts_path.exists() {
             continue;
         }
-        
         found_index = true;
-        crate::modules::logger::log_info(&format!("发现 V1 数据: {:?}", v1_accounts_path));
-        
-        let content = match fs::read_to_string(&v1_accounts_path) {
-            Ok(c) => c,
-            Err(e) => {
-                crate::modules::logger::log_warn(&format!("读取索引失败: {}", e));
-                continue;
-            }
-        };
-        
-        let v1_index: Value = match serde_json::from_str(&content) {
+        logger::log_info(&format!("发现 V1 数据: {:?}", v1_accounts_path));
+
+        let v1_index = fs::read_to_string(&v1_accounts_path)
+            .map_err(|e| format!("读取索引失败: {}", e))
+            .and_then(|content| serde_json::from_str::<Value>(&content).map_err(|e| format!("解析索引 JSON 失败: {}", e)));
+        let v1_index = match v1_index {
             Ok(v) => v,
             Err(e) => {
-                crate::modules::logger::log_warn(&format!("解析索引 JSON 失败: {}", e));
+                logger::log_warn(&e);
                 continue;
             }
         };
-        
+
         // 兼容两种格式：直接是 map，或者包含 "accounts" 字段
-        let accounts_map = if let Some(map) = v1_index.as_object() {
-            if let Some(accounts) = map.get("accounts").and_then(|v| v.as_object()) {
-                accounts 
-            } else {
-                map
-            }
-        } else {
+        let Some(map) = v1_index.as_object() else {
             continue;
         };
-        
+        let accounts_map = map.get("accounts").and_then(|v| v.as_object()).unwrap_or(map);
+
         for (id, acc_info) in accounts_map {
-            let email_placeholder = acc_info.get("email").and_then(|v| v.as_str()).unwrap_or("Unknown").to_string();
-            
             // 跳过非账号的 key (如 "current_account_id")
             if !acc_info.is_object() {
                 continue;
             }
-            
-            let backup_file_str = acc_info.get("backup_file").and_then(|v| v.as_str());
-            let data_file_str = acc_info.get("data_file").and_then(|v| v.as_str());
-            
-            // 优先使用 backup_file, 其次 data_file
-            let target_file = backup_file_str.or(data_file_str);
-            
-            if target_file.is_none() {
-                crate::modules::logger::log_warn(&format!("账号 {} ({}) 缺少数据文件路径", id, email_placeholder));
-                continue;
-            }
-            
-            let mut backup_path = PathBuf::from(target_file.unwrap());
-            
-            // 如果是相对路径，尝试拼接
-            if !backup_path.exists() {
-                 backup_path = v1_dir.join(backup_path.file_name().unwrap_or_default());
-            }
-            
-            // 再次尝试拼接 data/ 或 backups/ 子目录
-            if !backup_path.exists() {
-                 let file_name = backup_path.file_name().unwrap_or_default();
-                 let try_backups = v1_dir.join("backups").join(file_name);
-                 if try_backups.exists() {
-                     backup_path = try_backups;
-                 } else {
-                     let try_accounts = v1_dir.join("accounts").join(file_name);
-                     if try_accounts.exists() {
-                         backup_path = try_accounts;
-                     }
-                 }
-            }
-            
-            if !backup_path.exists() {
-                crate::modules::logger::log_warn(&format!("账号 {} ({}) 备份文件不存在: {:?}", id, email_placeholder, backup_path));
-                continue;
-            }
-            
-            // 读取备份文件
-            if let Ok(backup_content) = fs::read_to_string(&backup_path) {
-                if let Ok(backup_json) = serde_json::from_str::<Value>(&backup_content) {
-                    
-                    // 兼容两种格式：
-                    // 1. V1 备份: jetskiStateSync.agentManagerInitState -> Protobuf
-                    // 2. V2/Script 数据: 包含 "token" 字段的 JSON
-                    
-                    let mut refresh_token_opt = None;
-                    
-                    // 尝试格式 2
-                    if let Some(token_data) = backup_json.get("token") {
-                        if let Some(rt) = token_data.get("refresh_token").and_then(|v| v.as_str()) {
-                            refresh_token_opt = Some(rt.to_string());
-                        }
-                    }
-                    
-                    // 尝试格式 1
-                    if refresh_token_opt.is_none() {
-                         if let Some(state_b64) = backup_json.get("jetskiStateSync.agentManagerInitState").and_then(|v| v.as_str()) {
-                            // 解析 Protobuf
-                            if let Ok(blob) = general_purpose::STANDARD.decode(state_b64) {
-                                if let Ok(Some(oauth_data)) = protobuf::find_field(&blob, 6) {
-                                    if let Ok(Some(refresh_bytes)) = protobuf::find_field(&oauth_data, 3) {
-                                        if let Ok(rt) = String::from_utf8(refresh_bytes) {
-                                            refresh_token_opt = Some(rt);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    
-                    if let Some(refresh_token) = refresh_token_opt {
-                         crate::modules::logger::log_info(&format!("正在导入账号: {}", email_placeholder));
-                         
-                         let (email, access_token, expires_in) = match oauth::refresh_access_token(&refresh_token).await {
-                            Ok(token_resp) => {
-                                match oauth::get_user_info(&token_resp.access_token).await {
-                                    Ok(user_info) => (user_info.email, token_resp.access_token, token_resp.expires_in),
-                                    Err(_) => (email_placeholder.clone(), token_resp.access_token, token_resp.expires_in), 
-                                }
-                            },
-                            Err(e) => {
-                                crate::modules::logger::log_warn(&format!("Token 刷新失败 (可能过期): {}", e));
-                                (email_placeholder.clone(), "imported_access_token".to_string(), 0)
-                            }, 
-                        };
-
-                        let token_data = TokenData::new(
-                            access_token, 
-                            refresh_token,
-                            expires_in,
-                            Some(email.clone()),
-                            None, // project_id 将在需要时获取
-                            None, // session_id
-                    );
-                        
-                        // 在第153行的get_user_info中已经获取name，但这里是在match语句外，我们巴安全起见使用None
-                        match account::upsert_account(email.clone(), None, token_data) {
-                            Ok(acc) => {
-                                crate::modules::logger::log_info(&format!("导入成功: {}", email));
-                                imported_accounts.push(acc);
-                            },
-                            Err(e) => crate::modules::logger::log_error(&format!("导入保存失败 {}: {}", email, e)),
-                        }
-
-                    } else {
-                        crate::modules::logger::log_warn(&format!("账号 {} 数据文件中未找到 Refresh Token", email_placeholder));
-                    }
-                }
+            let refresh_token = read_v1_backup(v1_dir, acc_info);
+            if let Err(e) = &refresh_token {
+                logger::log_warn(&format!("账号 {} 无法导入: {}", id, e));
             }
+            sources.push(ImportSource {
+                label: id.clone(),
+                email_hint: acc_info.get("email").and_then(|v| v.as_str()).map(str::to_string),
+                refresh_token,
+                allow_unverified: true,
+            });
         }
     }
-    
+
     if !found_index {
         return Err("未找到 V1 版本账号数据文件".to_string());
     }
-    
-    Ok(imported_accounts)
+    Ok(sources)
 }
 
-/// 从自定义数据库路径导入账号
-pub async fn import_from_custom_db_path(path_str: String) -> Result<Account, String> {
-    use crate::modules::oauth;
+/// 读取 V1 条目指向的备份文件中的 Refresh Token
+fn read_v1_backup(v1_dir: &Path, acc_info: &Value) -> Result<String, String> {
+    // 优先使用 backup_file, 其次 data_file
+    let target_file = acc_info
+        .get("backup_file")
+        .and_then(|v| v.as_str())
+        .or_else(|| acc_info.get("data_file").and_then(|v| v.as_str()))
+        .ok_or("缺少数据文件路径")?;
+    let backup_path = locate_v1_backup(v1_dir, target_file)
+        .ok_or_else(|| format!("备份文件不存在: {}", target_file))?;
+
+    let content = fs::read_to_string(&backup_path)
+        .map_err(|e| format!("读取备份文件失败: {}", e))?;
+    let backup_json: Value = serde_json::from_str(&content)
+        .map_err(|e| format!("解析备份文件失败: {}", e))?;
+    v1_refresh_token(&backup_json).ok_or_else(|| "数据文件中未找到 Refresh Token".to_string())
+}
+
+/// 备份文件路径可能是相对路径，依次尝试 V1 目录及其 backups/、accounts/ 子目录
+fn locate_v1_backup(v1_dir: &Path, target_file: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(target_file);
+    if path.exists() {
+        return Some(path);
+    }
+    let file_name = path.file_name()?;
+    [
+        v1_dir.join(file_name),
+        v1_dir.join("backups").join(file_name),
+        v1_dir.join("accounts").join(file_name),
+    ]
+    .into_iter()
+    .find(|p| p.exists())
+}
+
+/// 从 V1 备份数据中提取 Refresh Token
+///
+/// 兼容两种格式：
+/// 1. V1 备份: jetskiStateSync.agentManagerInitState -> Protobuf
+/// 2. V2/Script 数据: 包含 "token" 字段的 JSON
+fn v1_refresh_token(backup_json: &Value) -> Option<String> {
+    // 尝试格式 2
+    if let Some(rt) = backup_json.get("token").and_then(|t| t.get("refresh_token")).and_then(|v| v.as_str()) {
+        return Some(rt.to_string());
+    }
+
+    // 尝试格式 1
+    let state_b64 = backup_json.get("jetskiStateSync.agentManagerInitState")?.as_str()?;
+    let blob = general_purpose::STANDARD.decode(state_b64).ok()?;
+    let oauth_data = protobuf::find_field(&blob, 6).ok()??;
+    let refresh_bytes = protobuf::find_field(&oauth_data, 3).ok()??;
+    String::from_utf8(refresh_bytes).ok()
+}
+
+// ============================================================================
+// 上传的文件
+// ============================================================================
+
+/// 上传文件的格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadFormat {
+    /// V1 导出的 JSON (单个备份文件，或账号列表/索引)
+    V1Json,
+    /// Antigravity 的 SQLite 数据库 (state.vscdb)
+    Sqlite,
+}
+
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+/// 根据文件内容判断格式
+pub fn detect_upload_format(bytes: &[u8]) -> Result<UploadFormat, String> {
+    if bytes.starts_with(SQLITE_HEADER) {
+        return Ok(UploadFormat::Sqlite);
+    }
+    match bytes.iter().find(|b| !b.is_ascii_whitespace()) {
+        Some(b'{') | Some(b'[') => Ok(UploadFormat::V1Json),
+        _ => Err("无法识别的文件格式，请上传 V1 导出的 JSON 或 SQLite 数据库".to_string()),
+    }
+}
+
+/// 从 V1 JSON 中找出各账号的数据：文件本身是单个备份时返回自身，否则遍历列表/索引中的条目
+fn v1_json_entries(value: &Value) -> Vec<&Value> {
+    if v1_refresh_token(value).is_some() {
+        return vec![value];
+    }
+    match value {
+        Value::Array(items) => items.iter().collect(),
+        // 兼容两种格式：直接是 map，或者包含 "accounts" 字段
+        Value::Object(map) => match map.get("accounts") {
+            Some(Value::Object(accounts)) => accounts.values().collect(),
+            Some(Value::Array(accounts)) => accounts.iter().collect(),
+            _ => map.values().collect(),
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// 上传的 V1 JSON 中的账号条目，缺少 Token 的条目以错误形式保留
+fn v1_json_sources(bytes: &[u8]) -> Result<Vec<ImportSource>, String> {
+    let value: Value = serde_json::from_slice(bytes)
+        .map_err(|e| format!("解析 JSON 失败: {}", e))?;
+
+    let sources: Vec<ImportSource> = v1_json_entries(&value)
+        .into_iter()
+        .filter(|entry| entry.is_object())
+        .enumerate()
+        .map(|(i, entry)| ImportSource {
+            label: entry
+                .get("id")
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("#{}", i + 1)),
+            email_hint: entry
+                .get("email")
+                .or_else(|| entry.get("token").and_then(|t| t.get("email")))
+                .and_then(|v| v.as_str())
+                .map(str::to_string),
+            refresh_token: v1_refresh_token(entry).ok_or_else(|| "条目中未找到 Refresh Token".to_string()),
+            allow_unverified: true,
+        })
+        .collect();
+
+    if sources.is_empty() {
+        return Err("文件中未找到账号数据".to_string());
+    }
+    Ok(sources)
+}
+
+/// 导入期间落盘的临时文件，离开作用域时删除
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// 导入上传的文件；`format` 为空时按内容判断。SQLite 需写入临时文件后再读取 (dry-run 同样如此)
+pub async fn import_from_upload(
+    bytes: &[u8],
+    format: Option<UploadFormat>,
+    options: ImportOptions,
+) -> Result<ImportReport, String> {
+    let format = match format {
+        Some(format) => format,
+        None => detect_upload_format(bytes)?,
+    };
+    match format {
+        UploadFormat::V1Json => run_import(v1_json_sources(bytes)?, options).await,
+        UploadFormat::Sqlite => {
+            let temp = TempFile(std::env::temp_dir().join(format!("ag-import-{}.vscdb", uuid::Uuid::new_v4())));
+            fs::write(&temp.0, bytes).map_err(|e| format!("写入临时文件失败: {}", e))?;
+            import_from_db_file(&temp.0, "upload".to_string(), options).await
+        }
+    }
+}
+
+// ============================================================================
+// Antigravity 数据库
+// ============================================================================
+
+async fn import_from_db_file(path: &Path, label: String, options: ImportOptions) -> Result<ImportReport, String> {
+    let refresh_token = extract_refresh_token_from_file(&path.to_path_buf())?;
+    let source = ImportSource {
+        label,
+        email_hint: None,
+        refresh_token: Ok(refresh_token),
+        allow_unverified: false,
+    };
+    run_import(vec![source], options).await
+}
 
-    let path = PathBuf::from(path_str);
+/// 从自定义数据库路径导入账号
+pub async fn import_from_custom_db_path(path_str: String, options: ImportOptions) -> Result<ImportReport, String> {
+    let path = PathBuf::from(&path_str);
     if !path.exists() {
         return Err(format!("文件不存在: {:?}", path));
     }
-
-    let refresh_token = extract_refresh_token_from_file(&path)?;
-        
-    // 3. 使用 Refresh Token 获取最新的 Access Token 和用户信息
-    crate::modules::logger::log_info("正在使用 Refresh Token 获取用户信息...");
-    let token_resp = oauth::refresh_access_token(&refresh_token).await?;
-    let user_info = oauth::get_user_info(&token_resp.access_token).await?;
-    
-    let email = user_info.email;
-    
-    crate::modules::logger::log_info(&format!("成功获取账号信息: {}", email));
-    
-    let token_data = TokenData::new(
-        token_resp.access_token,
-        refresh_token,
-        token_resp.expires_in,
-        Some(email.clone()),
-        None, // project_id 将在需要时获取
-        None, // session_id 将在 token_manager 中生成
-    );
-    
-    // 4. 添加或更新账号
-    account::upsert_account(email.clone(), user_info.name, token_data)
+    import_from_db_file(&path, path_str, options).await
 }
 
 /// 从默认 IDE 数据库导入当前登录账号
-pub async fn import_from_db() -> Result<Account, String> {
+pub async fn import_from_db(options: ImportOptions) -> Result<ImportReport, String> {
     let db_path = db::get_db_path()?;
-    import_from_custom_db_path(db_path.to_string_lossy().to_string()).await
+    import_from_custom_db_path(db_path.to_string_lossy().to_string(), options).await
 }
 
 /// 从数据库获取当前 Refresh Token (通用逻辑)
@@ -233,11 +477,11 @@ pub fn extract_refresh_token_from_file(db_path: &PathBuf) -> Result<String, Stri
     if !db_path.exists() {
         return Err(format!("找不到数据库文件: {:?}", db_path));
     }
-    
+
     // 连接数据库
     let conn = rusqlite::Connection::open(db_path)
         .map_err(|e| format!("打开数据库失败: {}", e))?;
-        
+
     // 从 ItemTable 读取
     let current_data: String = conn
         .query_row(
@@ -246,22 +490,22 @@ pub fn extract_refresh_token_from_file(db_path: &PathBuf) -> Result<String, Stri
             |row| row.get(0),
         )
         .map_err(|_| "未找到登录状态数据 (jetskiStateSync.agentManagerInitState)".to_string())?;
-        
+
     // Base64 解码
     let blob = general_purpose::STANDARD
         .decode(&current_data)
         .map_err(|e| format!("Base64 解码失败: {}", e))?;
-        
+
     // 1. 查找 oauthTokenInfo (Field 6)
     let oauth_data = protobuf::find_field(&blob, 6)
         .map_err(|e| format!("解析 Protobuf 失败: {}", e))?
         .ok_or("未找到 OAuth 数据 (Field 6)")?;
-        
+
     // 2. 提取 refresh_token (Field 3)
     let refresh_bytes = protobuf::find_field(&oauth_data, 3)
         .map_err(|e| format!("解析 OAuth 数据失败: {}", e))?
         .ok_or("数据中未包含 Refresh Token (Field 3)")?;
-        
+
     String::from_utf8(refresh_bytes)
         .map_err(|_| "Refresh Token 非 UTF-8 编码".to_string())
 }
@@ -271,3 +515,104 @@ pub fn get_refresh_token_from_db() -> Result<String, String> {
     let db_path = db::get_db_path()?;
     extract_refresh_token_from_file(&db_path)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_detect_upload_format() {
+        assert_eq!(detect_upload_format(b"SQLite format 3\0rest").unwrap(), UploadFormat::Sqlite);
+        assert_eq!(detect_upload_format(b"  \n{\"token\": {}}").unwrap(), UploadFormat::V1Json);
+        assert_eq!(detect_upload_format(b"[]").unwrap(), UploadFormat::V1Json);
+        assert!(detect_upload_format(b"PK\x03\x04").is_err());
+    }
+
+    #[test]
+    fn test_v1_json_sources() {
+        let backup = json!({"email": "a@example.com", "token": {"refresh_token": "rt-a"}});
+        let sources = v1_json_sources(backup.to_string().as_bytes()).unwrap();
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].refresh_token.as_deref(), Ok("rt-a"));
+
+        let list = json!([
+            {"id": "1", "email": "a@example.com", "token": {"refresh_token": "rt-a"}},
+            {"id": "2", "email": "b@example.com"},
+        ]);
+        let sources = v1_json_sources(list.to_string().as_bytes()).unwrap();
+        assert_eq!(sources.len(), 2);
+        assert!(sources[0].refresh_token.is_ok());
+        assert!(sources[1].refresh_token.is_err());
+    }
+
+    #[test]
+    fn test_collect_v1_sources_keeps_corrupted_entries() {
+        let dir = std::env::temp_dir().join(format!("ag-v1-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(dir.join("backups")).unwrap();
+        fs::write(dir.join("backups").join("ok.json"), json!({"token": {"refresh_token": "rt-ok"}}).to_string()).unwrap();
+        fs::write(dir.join("broken.json"), "{not json").unwrap();
+        fs::write(dir.join("empty.json"), "{}").unwrap();
+        let index = json!({
+            "current_account_id": "ok",
+            "accounts": {
+                "ok": {"email": "ok@example.com", "backup_file": "/elsewhere/ok.json"},
+                "no-path": {"email": "a@example.com"},
+                "missing": {"email": "b@example.com", "data_file": "missing.json"},
+                "broken": {"email": "c@example.com", "data_file": "broken.json"},
+                "empty": {"email": "d@example.com", "backup_file": "empty.json"},
+            }
+        });
+        fs::write(dir.join("accounts.json"), index.to_string()).unwrap();
+
+        let sources = collect_v1_sources(&dir).unwrap();
+        let by_label: HashMap<&str, &ImportSource> = sources.iter().map(|s| (s.label.as_str(), s)).collect();
+        assert_eq!(sources.len(), 5);
+        assert_eq!(by_label["ok"].refresh_token.as_deref(), Ok("rt-ok"));
+        assert_eq!(by_label["ok"].email_hint.as_deref(), Some("ok@example.com"));
+        for label in ["no-path", "missing", "broken", "empty"] {
+            assert!(by_label[label].refresh_token.is_err(), "{} should fail", label);
+        }
+
+        assert!(collect_v1_sources(&dir.join("nothing")).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_plan_imports() {
+        let resolved = |source: &str, email: &str| ResolvedEntry {
+            source: source.to_string(),
+            account: Ok(ResolvedAccount {
+                email: email.to_string(),
+                name: None,
+                token: TokenData::new("at".to_string(), "rt".to_string(), 3600, None, None, None),
+            }),
+        };
+        let entries = vec![
+            resolved("1", "A@example.com"),
+            resolved("2", "new@example.com"),
+            resolved("3", "new@example.com"),
+            ResolvedEntry { source: "4".to_string(), account: Err("备份文件不存在".to_string()) },
+        ];
+        let existing = vec![AccountSummary {
+            id: "acc-a".to_string(),
+            email: "a@example.com".to_string(),
+            name: None,
+            created_at: 0,
+            last_used: 0,
+        }];
+
+        let actions = |plan: Vec<ImportPlanEntry>| plan.into_iter().map(|e| e.action).collect::<Vec<_>>();
+        let plan = plan_imports(&entries, &existing, OnDuplicate::Update);
+        assert_eq!(plan[0].existing_account_id.as_deref(), Some("acc-a"));
+        assert_eq!(plan[3].error.as_deref(), Some("备份文件不存在"));
+        assert_eq!(
+            actions(plan),
+            [PlannedAction::Update, PlannedAction::Create, PlannedAction::Update, PlannedAction::Error]
+        );
+        assert_eq!(
+            actions(plan_imports(&entries, &existing, OnDuplicate::Skip)),
+            [PlannedAction::Skip, PlannedAction::Create, PlannedAction::Skip, PlannedAction::Error]
+        );
+    }
+}
@@ -1,6 +1,8 @@
 pub mod account;
 pub mod quota;
 pub mod config;
+pub mod config_watcher;
+pub mod accounts_watcher;
 pub mod logger;
 pub mod db;
 pub mod process;
@@ -14,7 +16,30 @@ pub mod i18n;
 pub mod proxy_db;
 pub mod device;
 pub mod update_checker;
+pub mod self_update;
+pub mod token_health;
+pub mod auto_switch;
 pub mod scheduler;
+pub mod audit;
+pub mod dashboard;
+pub mod account_export;
+pub mod account_repair;
+pub mod system_info;
+pub mod diagnostics;
+pub mod db_sync;
+pub mod backup;
+pub mod stats_history;
+pub mod upstream_probe;
+pub mod health;
+pub mod schema;
+pub mod web_rate_limit;
+pub mod account_test;
+pub mod proxy_warmup;
+pub mod project;
+pub mod replay;
+pub mod rotation;
+pub mod seed;
+pub mod setup;
 
 use crate::models;
 
@@ -1,4 +1,10 @@
+use base64::Engine;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 // Google OAuth 配置
 const CLIENT_ID: &str = "1071006060591-tmhssin2h21lcre235vtolojh4g403ep.apps.googleusercontent.com";
@@ -7,6 +13,15 @@ const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
 const USERINFO_URL: &str = "https://www.googleapis.com/oauth2/v2/userinfo";
 
 const AUTH_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
+const DEVICE_CODE_URL: &str = "https://oauth2.googleapis.com/device/code";
+
+const SCOPES: &[&str] = &[
+    "https://www.googleapis.com/auth/cloud-platform",
+    "https://www.googleapis.com/auth/userinfo.email",
+    "https://www.googleapis.com/auth/userinfo.profile",
+    "https://www.googleapis.com/auth/cclog",
+    "https://www.googleapis.com/auth/experimentsandconfigs",
+];
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TokenResponse {
@@ -48,41 +63,120 @@ impl UserInfo {
 }
 
 
-/// 生成 OAuth 授权 URL
-pub fn get_auth_url(redirect_uri: &str) -> String {
-    let scopes = vec![
-        "https://www.googleapis.com/auth/cloud-platform",
-        "https://www.googleapis.com/auth/userinfo.email",
-        "https://www.googleapis.com/auth/userinfo.profile",
-        "https://www.googleapis.com/auth/cclog",
-        "https://www.googleapis.com/auth/experimentsandconfigs"
-    ].join(" ");
+/// 授权请求未完成时 state 的有效期
+const OAUTH_STATE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// 单次授权请求的 state 与 PKCE (RFC 7636) 参数
+#[derive(Debug, Clone)]
+pub struct PkceParams {
+    pub state: String,
+    pub code_verifier: String,
+    pub code_challenge: String,
+}
+
+impl PkceParams {
+    pub fn generate() -> Self {
+        const UNRESERVED: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-._~";
+        let mut rng = rand::thread_rng();
+        let code_verifier: String = (0..64)
+            .map(|_| UNRESERVED[rng.gen_range(0..UNRESERVED.len())] as char)
+            .collect();
+        let state = format!("{:032x}", rng.gen::<u128>());
+        let code_challenge = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .encode(Sha256::digest(code_verifier.as_bytes()));
+
+        Self {
+            state,
+            code_verifier,
+            code_challenge,
+        }
+    }
+}
+
+/// 等待回调的授权请求
+#[derive(Debug, Clone)]
+pub struct PendingOAuth {
+    pub code_verifier: String,
+    pub redirect_uri: String,
+    created_at: Instant,
+}
+
+/// 按 state 保存等待回调的授权请求，过期 (10 分钟) 或取出后即失效
+#[derive(Default)]
+pub struct PendingOAuthStore {
+    entries: Mutex<HashMap<String, PendingOAuth>>,
+}
+
+impl PendingOAuthStore {
+    pub fn insert(&self, pkce: &PkceParams, redirect_uri: &str) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, pending| pending.created_at.elapsed() < OAUTH_STATE_TTL);
+        entries.insert(
+            pkce.state.clone(),
+            PendingOAuth {
+                code_verifier: pkce.code_verifier.clone(),
+                redirect_uri: redirect_uri.to_string(),
+                created_at: Instant::now(),
+            },
+        );
+    }
+
+    /// 取出 state 对应的请求 (一次性)
+    pub fn take(&self, state: &str) -> Result<PendingOAuth, String> {
+        let pending = self
+            .entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(state)
+            .ok_or_else(|| "未知的 OAuth state，请重新生成授权链接".to_string())?;
+        if pending.created_at.elapsed() >= OAUTH_STATE_TTL {
+            return Err("OAuth 授权已过期，请重新生成授权链接".to_string());
+        }
+        Ok(pending)
+    }
+}
+
+/// 生成 OAuth 授权 URL，提供 `pkce` 时附带 state 与 code_challenge
+pub fn get_auth_url(redirect_uri: &str, pkce: Option<&PkceParams>) -> String {
+    let scopes = SCOPES.join(" ");
 
-    let params = vec![
+    let mut params = vec![
         ("client_id", CLIENT_ID),
         ("redirect_uri", redirect_uri),
         ("response_type", "code"),
-        ("scope", &scopes),
+        ("scope", scopes.as_str()),
         ("access_type", "offline"),
         ("prompt", "consent"),
         ("include_granted_scopes", "true"),
     ];
+    if let Some(pkce) = pkce {
+        params.push(("state", pkce.state.as_str()));
+        params.push(("code_challenge", pkce.code_challenge.as_str()));
+        params.push(("code_challenge_method", "S256"));
+    }
     
     let url = url::Url::parse_with_params(AUTH_URL, &params).expect("无效的 Auth URL");
     url.to_string()
 }
 
 /// 使用 Authorization Code 交换 Token
-pub async fn exchange_code(code: &str, redirect_uri: &str) -> Result<TokenResponse, String> {
+pub async fn exchange_code(
+    code: &str,
+    redirect_uri: &str,
+    code_verifier: Option<&str>,
+) -> Result<TokenResponse, String> {
     let client = crate::utils::http::create_client(15);
     
-    let params = [
+    let mut params = vec![
         ("client_id", CLIENT_ID),
         ("client_secret", CLIENT_SECRET),
         ("code", code),
         ("redirect_uri", redirect_uri),
         ("grant_type", "authorization_code"),
     ];
+    if let Some(code_verifier) = code_verifier {
+        params.push(("code_verifier", code_verifier));
+    }
 
     let response = client
         .post(TOKEN_URL)
@@ -120,8 +214,335 @@ pub async fn exchange_code(code: &str, redirect_uri: &str) -> Result<TokenRespon
     }
 }
 
+/// 设备授权响应 (RFC 8628)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceCodeResponse {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_url: String,
+    pub expires_in: u64,
+    #[serde(default = "default_device_interval")]
+    pub interval: u64,
+}
+
+fn default_device_interval() -> u64 {
+    5
+}
+
+/// 设备授权轮询结果
+#[derive(Debug)]
+pub enum DevicePoll {
+    /// 用户尚未完成授权
+    Pending,
+    /// 轮询过快，需要加大间隔
+    SlowDown,
+    Approved(TokenResponse),
+    Denied,
+    Expired,
+}
+
+/// 发起设备授权，适用于无法访问 localhost 回调的无头服务器
+pub async fn start_device_flow() -> Result<DeviceCodeResponse, String> {
+    let client = crate::utils::http::create_client(15);
+    let scopes = SCOPES.join(" ");
+
+    let response = client
+        .post(DEVICE_CODE_URL)
+        .form(&[("client_id", CLIENT_ID), ("scope", scopes.as_str())])
+        .send()
+        .await
+        .map_err(|e| format!("设备授权请求失败: {}", e))?;
+
+    if response.status().is_success() {
+        response
+            .json::<DeviceCodeResponse>()
+            .await
+            .map_err(|e| format!("设备授权响应解析失败: {}", e))
+    } else {
+        let error_text = response.text().await.unwrap_or_default();
+        Err(format!("设备授权失败: {}", error_text))
+    }
+}
+
+/// 轮询一次设备授权结果
+pub async fn poll_device_token(device_code: &str) -> Result<DevicePoll, String> {
+    let client = crate::utils::http::create_client(15);
+
+    let params = [
+        ("client_id", CLIENT_ID),
+        ("client_secret", CLIENT_SECRET),
+        ("device_code", device_code),
+        ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+    ];
+
+    let response = client
+        .post(TOKEN_URL)
+        .form(&params)
+        .send()
+        .await
+        .map_err(|e| format!("设备授权轮询失败: {}", e))?;
+
+    if response.status().is_success() {
+        return response
+            .json::<TokenResponse>()
+            .await
+            .map(DevicePoll::Approved)
+            .map_err(|e| format!("Token 解析失败: {}", e));
+    }
+
+    let error_text = response.text().await.unwrap_or_default();
+    let error = serde_json::from_str::<serde_json::Value>(&error_text)
+        .ok()
+        .and_then(|v| v["error"].as_str().map(str::to_string))
+        .unwrap_or_default();
+    match error.as_str() {
+        "authorization_pending" => Ok(DevicePoll::Pending),
+        "slow_down" => Ok(DevicePoll::SlowDown),
+        "access_denied" => Ok(DevicePoll::Denied),
+        "expired_token" => Ok(DevicePoll::Expired),
+        _ => Err(format!("设备授权失败: {}", error_text)),
+    }
+}
+
+/// 刷新 Token 失败的原因
+#[derive(Debug)]
+pub enum RefreshError {
+    /// Google 拒绝了 refresh_token (已撤销、密码修改等)，需要重新授权
+    Rejected(String),
+    /// 网络错误或服务端临时故障，稍后可重试
+    Transient(String),
+    /// 连续失败触发熔断，未发起请求；附带建议的重试秒数
+    Throttled(u64),
+}
+
+impl std::fmt::Display for RefreshError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RefreshError::Rejected(msg) | RefreshError::Transient(msg) => f.write_str(msg),
+            RefreshError::Throttled(secs) => write!(
+                f,
+                "{}: Token 刷新连续失败，已暂停刷新，{} 秒后重试",
+                REFRESH_THROTTLED, secs
+            ),
+        }
+    }
+}
+
+/// 熔断期间刷新失败的错误标识，调用方据此区分熔断与账号本身的问题
+pub const REFRESH_THROTTLED: &str = "oauth_refresh_throttled";
+
+/// 窗口内连续临时失败达到该次数后熔断
+const BREAKER_FAILURE_THRESHOLD: u32 = 5;
+/// 统计连续失败的时间窗口 (秒)
+const BREAKER_WINDOW_SECS: i64 = 60;
+/// 首次熔断的冷却时间 (秒)，半开探测失败后翻倍
+const BREAKER_BASE_COOLDOWN_SECS: u64 = 60;
+const BREAKER_MAX_COOLDOWN_SECS: u64 = 600;
+/// 半开探测进行中时其他请求的建议重试秒数
+const BREAKER_PROBE_RETRY_SECS: u64 = 5;
+/// 探测请求的最长等待时间 (秒)，超时未回报 (如请求被取消) 时放行新的探测
+const BREAKER_PROBE_TIMEOUT_SECS: i64 = 30;
+
+/// 熔断器状态
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// 正常刷新
+    #[default]
+    Closed,
+    /// 冷却中，刷新直接失败
+    Open,
+    /// 冷却结束，放行一次探测
+    HalfOpen,
+}
+
+/// Token 刷新熔断状态
+#[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
+pub struct RefreshBreakerStatus {
+    pub state: BreakerState,
+    /// 当前窗口内的连续临时失败次数
+    pub consecutive_failures: u32,
+    pub failure_threshold: u32,
+    pub window_secs: i64,
+    /// 当前冷却时间 (秒)
+    pub cooldown_secs: u64,
+    /// 最近一次熔断的 Unix 时间戳 (秒)
+    pub opened_at: Option<i64>,
+    /// 下一次允许探测的 Unix 时间戳 (秒)
+    pub next_probe_at: Option<i64>,
+    /// 熔断期间被直接拒绝的刷新次数
+    pub throttled_count: u64,
+    pub last_error: Option<String>,
+}
+
+/// 跨账号共享的刷新熔断器
+#[derive(Debug)]
+struct RefreshBreaker {
+    state: BreakerState,
+    consecutive_failures: u32,
+    window_started_at: i64,
+    cooldown_secs: u64,
+    opened_at: Option<i64>,
+    next_probe_at: Option<i64>,
+    probe_in_flight: bool,
+    throttled_count: u64,
+    last_error: Option<String>,
+}
+
+impl RefreshBreaker {
+    const fn new() -> Self {
+        Self {
+            state: BreakerState::Closed,
+            consecutive_failures: 0,
+            window_started_at: 0,
+            cooldown_secs: BREAKER_BASE_COOLDOWN_SECS,
+            opened_at: None,
+            next_probe_at: None,
+            probe_in_flight: false,
+            throttled_count: 0,
+            last_error: None,
+        }
+    }
+
+    /// 发起刷新前检查；熔断时返回建议的重试秒数
+    fn before_attempt(&mut self, now: i64) -> Result<(), u64> {
+        match self.state {
+            BreakerState::Closed => Ok(()),
+            BreakerState::Open => {
+                let next_probe_at = self.next_probe_at.unwrap_or(now);
+                if now >= next_probe_at {
+                    self.state = BreakerState::HalfOpen;
+                    self.start_probe(now);
+                    Ok(())
+                } else {
+                    self.throttled_count += 1;
+                    Err((next_probe_at - now) as u64)
+                }
+            }
+            BreakerState::HalfOpen if !self.probe_in_flight || self.next_probe_at.is_some_and(|at| now >= at) => {
+                self.start_probe(now);
+                Ok(())
+            }
+            BreakerState::HalfOpen => {
+                self.throttled_count += 1;
+                Err(BREAKER_PROBE_RETRY_SECS)
+            }
+        }
+    }
+
+    fn start_probe(&mut self, now: i64) {
+        self.probe_in_flight = true;
+        self.next_probe_at = Some(now + BREAKER_PROBE_TIMEOUT_SECS);
+    }
+
+    /// 上游有正常响应 (包括拒绝某个 refresh_token)，说明服务可用
+    fn on_reachable(&mut self) {
+        if self.state != BreakerState::Closed {
+            tracing::info!("OAuth 刷新熔断解除");
+        }
+        self.state = BreakerState::Closed;
+        self.consecutive_failures = 0;
+        self.cooldown_secs = BREAKER_BASE_COOLDOWN_SECS;
+        self.next_probe_at = None;
+        self.probe_in_flight = false;
+    }
+
+    /// 网络错误或服务端故障
+    fn on_transient_failure(&mut self, now: i64, error: &str) {
+        self.last_error = Some(error.to_string());
+        match self.state {
+            BreakerState::HalfOpen => {
+                self.cooldown_secs = (self.cooldown_secs * 2).min(BREAKER_MAX_COOLDOWN_SECS);
+                self.open(now);
+            }
+            BreakerState::Open => {}
+            BreakerState::Closed => {
+                if self.consecutive_failures == 0 || now - self.window_started_at > BREAKER_WINDOW_SECS {
+                    self.window_started_at = now;
+                    self.consecutive_failures = 0;
+                }
+                self.consecutive_failures += 1;
+                if self.consecutive_failures >= BREAKER_FAILURE_THRESHOLD {
+                    self.open(now);
+                }
+            }
+        }
+    }
+
+    fn open(&mut self, now: i64) {
+        self.state = BreakerState::Open;
+        self.opened_at = Some(now);
+        self.next_probe_at = Some(now + self.cooldown_secs as i64);
+        self.probe_in_flight = false;
+        tracing::warn!(
+            "OAuth 刷新连续失败 {} 次，暂停刷新 {} 秒",
+            self.consecutive_failures,
+            self.cooldown_secs
+        );
+    }
+
+    fn status(&self) -> RefreshBreakerStatus {
+        RefreshBreakerStatus {
+            state: self.state,
+            consecutive_failures: self.consecutive_failures,
+            failure_threshold: BREAKER_FAILURE_THRESHOLD,
+            window_secs: BREAKER_WINDOW_SECS,
+            cooldown_secs: self.cooldown_secs,
+            opened_at: self.opened_at,
+            next_probe_at: self.next_probe_at,
+            throttled_count: self.throttled_count,
+            last_error: self.last_error.clone(),
+        }
+    }
+}
+
+static REFRESH_BREAKER: Mutex<RefreshBreaker> = Mutex::new(RefreshBreaker::new());
+
+fn refresh_breaker() -> std::sync::MutexGuard<'static, RefreshBreaker> {
+    REFRESH_BREAKER.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 当前刷新熔断状态
+pub fn refresh_breaker_status() -> RefreshBreakerStatus {
+    refresh_breaker().status()
+}
+
+/// 熔断中时距离下一次探测的秒数，未熔断时为空
+pub fn refresh_retry_after() -> Option<u64> {
+    let breaker = refresh_breaker();
+    let now = chrono::Utc::now().timestamp();
+    match breaker.state {
+        BreakerState::Closed => None,
+        BreakerState::Open => Some(breaker.next_probe_at.map_or(1, |at| (at - now).max(1) as u64)),
+        BreakerState::HalfOpen => Some(BREAKER_PROBE_RETRY_SECS),
+    }
+}
+
 /// 使用 refresh_token 刷新 access_token
 pub async fn refresh_access_token(refresh_token: &str) -> Result<TokenResponse, String> {
+    try_refresh_access_token(refresh_token).await.map_err(|e| e.to_string())
+}
+
+/// 同 [`refresh_access_token`]，但区分 Token 失效与临时故障
+///
+/// 所有账号共享一个熔断器：连续的临时故障达到阈值后直接返回 [`RefreshError::Throttled`]，
+/// 冷却结束后放行一次探测，成功则恢复。
+pub async fn try_refresh_access_token(refresh_token: &str) -> Result<TokenResponse, RefreshError> {
+    refresh_breaker()
+        .before_attempt(chrono::Utc::now().timestamp())
+        .map_err(RefreshError::Throttled)?;
+
+    let result = request_refresh(refresh_token).await;
+    let mut breaker = refresh_breaker();
+    match &result {
+        Ok(_) | Err(RefreshError::Rejected(_)) => breaker.on_reachable(),
+        Err(RefreshError::Transient(e)) => breaker.on_transient_failure(chrono::Utc::now().timestamp(), e),
+        Err(RefreshError::Throttled(_)) => {}
+    }
+    result
+}
+
+async fn request_refresh(refresh_token: &str) -> Result<TokenResponse, RefreshError> {
     let client = crate::utils::http::create_client(15);
     
     let params = [
@@ -138,19 +559,26 @@ pub async fn refresh_access_token(refresh_token: &str) -> Result<TokenResponse,
         .form(&params)
         .send()
         .await
-        .map_err(|e| format!("刷新请求失败: {}", e))?;
+        .map_err(|e| RefreshError::Transient(format!("刷新请求失败: {}", e)))?;
 
-    if response.status().is_success() {
+    let status = response.status();
+    if status.is_success() {
         let token_data = response
             .json::<TokenResponse>()
             .await
-            .map_err(|e| format!("刷新数据解析失败: {}", e))?;
+            .map_err(|e| RefreshError::Transient(format!("刷新数据解析失败: {}", e)))?;
         
         crate::modules::logger::log_info(&format!("Token 刷新成功！有效期: {} 秒", token_data.expires_in));
         Ok(token_data)
     } else {
         let error_text = response.text().await.unwrap_or_default();
-        Err(format!("刷新失败: {}", error_text))
+        let msg = format!("刷新失败: {}", error_text);
+        // 400/401 (invalid_grant、unauthorized_client 等) 表示凭证本身失效
+        if status == reqwest::StatusCode::BAD_REQUEST || status == reqwest::StatusCode::UNAUTHORIZED {
+            Err(RefreshError::Rejected(msg))
+        } else {
+            Err(RefreshError::Transient(msg))
+        }
     }
 }
 
@@ -201,3 +629,72 @@ pub async fn ensure_fresh_token(
         None,  // session_id 会在 token_manager 中生成
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refresh_breaker_opens_and_probes() {
+        let mut breaker = RefreshBreaker::new();
+        for _ in 0..BREAKER_FAILURE_THRESHOLD - 1 {
+            assert!(breaker.before_attempt(100).is_ok());
+            breaker.on_transient_failure(100, "timeout");
+        }
+        assert_eq!(breaker.state, BreakerState::Closed);
+
+        // 窗口外的失败重新计数
+        breaker.on_transient_failure(100 + BREAKER_WINDOW_SECS + 1, "timeout");
+        assert_eq!(breaker.consecutive_failures, 1);
+        for _ in 1..BREAKER_FAILURE_THRESHOLD {
+            breaker.on_transient_failure(200, "timeout");
+        }
+        assert_eq!(breaker.state, BreakerState::Open);
+        assert_eq!(breaker.before_attempt(210), Err(BREAKER_BASE_COOLDOWN_SECS - 10));
+
+        // 冷却结束放行一次探测，其余请求继续快速失败
+        let probe_at = 200 + BREAKER_BASE_COOLDOWN_SECS as i64;
+        assert!(breaker.before_attempt(probe_at).is_ok());
+        assert_eq!(breaker.state, BreakerState::HalfOpen);
+        assert_eq!(breaker.before_attempt(probe_at), Err(BREAKER_PROBE_RETRY_SECS));
+
+        // 探测失败后冷却翻倍
+        breaker.on_transient_failure(probe_at, "503");
+        assert_eq!(breaker.state, BreakerState::Open);
+        assert_eq!(breaker.cooldown_secs, BREAKER_BASE_COOLDOWN_SECS * 2);
+
+        // 探测成功 (或上游明确拒绝某个 Token) 后恢复
+        assert!(breaker.before_attempt(probe_at + 120).is_ok());
+        breaker.on_reachable();
+        assert_eq!(breaker.state, BreakerState::Closed);
+        assert_eq!(breaker.status().throttled_count, 2);
+        assert!(RefreshError::Throttled(30).to_string().starts_with(REFRESH_THROTTLED));
+    }
+
+    #[test]
+    fn test_pkce_challenge_matches_verifier() {
+        let pkce = PkceParams::generate();
+        assert_eq!(pkce.code_verifier.len(), 64);
+        assert_eq!(pkce.state.len(), 32);
+        let expected = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .encode(Sha256::digest(pkce.code_verifier.as_bytes()));
+        assert_eq!(pkce.code_challenge, expected);
+
+        let url = url::Url::parse(&get_auth_url("http://localhost:9004/callback", Some(&pkce))).unwrap();
+        let query: HashMap<_, _> = url.query_pairs().into_owned().collect();
+        assert_eq!(query["state"], pkce.state);
+        assert_eq!(query["code_challenge_method"], "S256");
+    }
+
+    #[test]
+    fn test_pending_state_is_single_use() {
+        let store = PendingOAuthStore::default();
+        let pkce = PkceParams::generate();
+        store.insert(&pkce, "http://localhost:9004/callback");
+
+        let pending = store.take(&pkce.state).unwrap();
+        assert_eq!(pending.code_verifier, pkce.code_verifier);
+        assert!(store.take(&pkce.state).is_err());
+        assert!(store.take("unknown").is_err());
+    }
+}
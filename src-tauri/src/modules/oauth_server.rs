@@ -119,7 +119,7 @@ async fn ensure_oauth_flow_prepared(app_handle: &tauri::AppHandle) -> Result<Str
         format!("http://[::1]:{}/oauth-callback", port)
     };
 
-    let auth_url = oauth::get_auth_url(&redirect_uri);
+    let auth_url = oauth::get_auth_url(&redirect_uri, None);
 
     // 取消信号（支持多消费者）
     let (cancel_tx, cancel_rx) = watch::channel(false);
@@ -279,7 +279,7 @@ pub async fn start_oauth_flow(app_handle: tauri::AppHandle) -> Result<oauth::Tok
         *lock = None;
     }
 
-    oauth::exchange_code(&code, &redirect_uri).await
+    oauth::exchange_code(&code, &redirect_uri, None).await
 }
 
 /// Завершить OAuth flow без открытия браузера.
@@ -314,5 +314,5 @@ pub async fn complete_oauth_flow(app_handle: tauri::AppHandle) -> Result<oauth::
         *lock = None;
     }
 
-    oauth::exchange_code(&code, &redirect_uri).await
+    oauth::exchange_code(&code, &redirect_uri, None).await
 }
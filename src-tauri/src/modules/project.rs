@@ -0,0 +1,160 @@
+//! 账号 project_id 的发现与手动设置
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::models::Account;
+use crate::proxy::project_resolver;
+
+/// 候选项目来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectSource {
+    /// loadCodeAssist 返回的 cloudaicompanionProject
+    Antigravity,
+    /// Cloud Resource Manager 列出的项目
+    Cloud,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ProjectCandidate {
+    pub project_id: String,
+    pub name: Option<String>,
+    pub source: ProjectSource,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ProjectDiscovery {
+    pub account_id: String,
+    pub email: String,
+    /// 保存到账号的 project_id
+    pub project_id: String,
+    /// 发现前的 project_id
+    pub previous: Option<String>,
+    /// 候选项目，Antigravity 项目在前
+    pub candidates: Vec<ProjectCandidate>,
+    /// 获取失败的来源及原因 (另一来源成功时仍返回候选)
+    pub errors: Vec<String>,
+}
+
+/// 合并两个来源的候选项目，按 project_id 去重
+fn merge_candidates(
+    antigravity: Option<String>,
+    cloud: Vec<(String, Option<String>)>,
+) -> Vec<ProjectCandidate> {
+    let mut candidates: Vec<ProjectCandidate> = antigravity
+        .into_iter()
+        .map(|project_id| ProjectCandidate { project_id, name: None, source: ProjectSource::Antigravity })
+        .collect();
+    for (project_id, name) in cloud {
+        match candidates.iter_mut().find(|c| c.project_id == project_id) {
+            Some(existing) => existing.name = existing.name.take().or(name),
+            None => candidates.push(ProjectCandidate { project_id, name, source: ProjectSource::Cloud }),
+        }
+    }
+    candidates
+}
+
+/// 选择要保存的项目：当前 project_id 仍在候选中时保留，否则取第一个
+fn choose_project(current: Option<&str>, candidates: &[ProjectCandidate]) -> Option<String> {
+    current
+        .filter(|pid| candidates.iter().any(|c| c.project_id == *pid))
+        .map(|pid| pid.to_string())
+        .or_else(|| candidates.first().map(|c| c.project_id.clone()))
+}
+
+/// 使用账号的 Access Token 发现可用项目，保存选中的 project_id 并返回全部候选
+pub async fn discover_project(account_id: &str) -> Result<ProjectDiscovery, String> {
+    let mut account = crate::modules::load_account(account_id)?;
+    let token = crate::modules::oauth::ensure_fresh_token(&account.token).await?;
+    let token_refreshed = token.access_token != account.token.access_token;
+    account.token = token;
+
+    let mut errors = Vec::new();
+    let (antigravity, cloud) = tokio::join!(
+        project_resolver::load_code_assist_project(&account.token.access_token),
+        project_resolver::list_cloud_projects(&account.token.access_token),
+    );
+    let antigravity = antigravity.unwrap_or_else(|e| {
+        errors.push(e);
+        None
+    });
+    let cloud = cloud.unwrap_or_else(|e| {
+        errors.push(e);
+        Vec::new()
+    });
+
+    let candidates = merge_candidates(antigravity, cloud);
+    let previous = account.token.project_id.clone();
+    let Some(project_id) = choose_project(previous.as_deref(), &candidates) else {
+        if token_refreshed {
+            crate::modules::save_account(&account)?;
+        }
+        return Err(if errors.is_empty() {
+            format!("账号 {} 没有可用的项目", account.email)
+        } else {
+            format!("发现项目失败: {}", errors.join("; "))
+        });
+    };
+
+    if token_refreshed || previous.as_deref() != Some(project_id.as_str()) {
+        account.token.project_id = Some(project_id.clone());
+        crate::modules::save_account(&account)?;
+    }
+    crate::modules::logger::log_info(&format!(
+        "账号 {} 发现 {} 个候选项目，使用 {}",
+        account.email,
+        candidates.len(),
+        project_id
+    ));
+
+    Ok(ProjectDiscovery {
+        account_id: account.id,
+        email: account.email,
+        project_id,
+        previous,
+        candidates,
+        errors,
+    })
+}
+
+/// 手动设置账号的 project_id，为空时清除 (反代将在下次使用时自动获取)
+pub fn set_project_id(account_id: &str, project_id: Option<&str>) -> Result<Account, String> {
+    let project_id = project_id.map(str::trim).filter(|p| !p.is_empty());
+    if let Some(pid) = project_id {
+        if pid.chars().any(|c| c.is_whitespace() || c == '/') {
+            return Err(format!("无效的 project_id: {}", pid));
+        }
+    }
+    let mut account = crate::modules::load_account(account_id)?;
+    if account.token.project_id.as_deref() != project_id {
+        account.token.project_id = project_id.map(|p| p.to_string());
+        crate::modules::save_account(&account)?;
+    }
+    Ok(account)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_and_choose() {
+        let candidates = merge_candidates(
+            Some("ag-project".to_string()),
+            vec![
+                ("cloud-a".to_string(), Some("A".to_string())),
+                ("ag-project".to_string(), Some("AG".to_string())),
+            ],
+        );
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0].project_id, "ag-project");
+        assert_eq!(candidates[0].source, ProjectSource::Antigravity);
+        assert_eq!(candidates[0].name.as_deref(), Some("AG"));
+
+        assert_eq!(choose_project(None, &candidates).as_deref(), Some("ag-project"));
+        assert_eq!(choose_project(Some("cloud-a"), &candidates).as_deref(), Some("cloud-a"));
+        assert_eq!(choose_project(Some("gone"), &candidates).as_deref(), Some("ag-project"));
+        assert_eq!(choose_project(Some("gone"), &[]), None);
+    }
+}
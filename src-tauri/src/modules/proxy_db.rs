@@ -1,6 +1,11 @@
 use rusqlite::{params, Connection};
 use std::path::PathBuf;
-use crate::proxy::monitor::ProxyRequestLog;
+use crate::proxy::monitor::{ErrorClass, PhaseTimings, ProxyRequestLog, RequestKind};
+
+/// `top_clients` 统计的时间窗口 (最近 24 小时)
+const TOP_CLIENTS_WINDOW_SECS: i64 = 24 * 3600;
+/// `top_clients` 返回的客户端数量
+const TOP_CLIENTS_LIMIT: i64 = 10;
 
 pub fn get_proxy_db_path() -> Result<PathBuf, String> {
     let data_dir = crate::modules::account::get_data_dir()?;
@@ -32,6 +37,29 @@ pub fn init_db() -> Result<(), String> {
     let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN output_tokens INTEGER", []);
     let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN account_email TEXT", []);
     let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN mapped_model TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN api_key_name TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN client_ip TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN user_agent TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN seq INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN kind TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN vector_count INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN client_disconnected INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN bytes_relayed INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN timeout_secs INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN select_ms INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN auth_ms INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN upstream_ttfb_ms INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN stream_ms INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN upstream_endpoint TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN error_class TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN replay_of TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN finish_reason TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN blocked INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN cost REAL", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN currency TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN mirrored INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN prompt_rules TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN pinned INTEGER", []);
 
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_timestamp ON request_logs (timestamp DESC)",
@@ -44,6 +72,16 @@ pub fn init_db() -> Result<(), String> {
         [],
     ).map_err(|e| e.to_string())?;
 
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_client_ip ON request_logs (client_ip, timestamp)",
+        [],
+    ).map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_account_email ON request_logs (account_email, timestamp)",
+        [],
+    ).map_err(|e| e.to_string())?;
+
     Ok(())
 }
 
@@ -52,8 +90,8 @@ pub fn save_log(log: &ProxyRequestLog) -> Result<(), String> {
     let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
 
     conn.execute(
-        "INSERT INTO request_logs (id, timestamp, method, url, status, duration, model, error, request_body, response_body, input_tokens, output_tokens, account_email, mapped_model)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+        "INSERT INTO request_logs (id, timestamp, method, url, status, duration, model, error, request_body, response_body, input_tokens, output_tokens, account_email, mapped_model, api_key_name, client_ip, user_agent, seq, kind, vector_count, client_disconnected, bytes_relayed, timeout_secs, select_ms, auth_ms, upstream_ttfb_ms, stream_ms, upstream_endpoint, error_class, replay_of, finish_reason, blocked, cost, currency, mirrored, prompt_rules, pinned)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30, ?31, ?32, ?33, ?34, ?35, ?36, ?37)",
         params![
             log.id,
             log.timestamp,
@@ -69,6 +107,29 @@ pub fn save_log(log: &ProxyRequestLog) -> Result<(), String> {
             log.output_tokens,
             log.account_email,
             log.mapped_model,
+            log.api_key_name,
+            log.client_ip,
+            log.user_agent,
+            log.seq as i64,
+            log.kind.as_str(),
+            log.vector_count,
+            log.client_disconnected,
+            log.bytes_relayed.map(|b| b as i64),
+            log.timeout_secs.map(|t| t as i64),
+            log.phase_timings.select_ms.map(|t| t as i64),
+            log.phase_timings.auth_ms.map(|t| t as i64),
+            log.phase_timings.upstream_ttfb_ms.map(|t| t as i64),
+            log.phase_timings.stream_ms.map(|t| t as i64),
+            log.upstream_endpoint,
+            log.error_class.map(|c| c.as_str()),
+            log.replay_of,
+            log.finish_reason,
+            log.blocked,
+            log.cost,
+            log.currency,
+            log.mirrored,
+            (!log.prompt_rules.is_empty()).then(|| serde_json::to_string(&log.prompt_rules).unwrap_or_default()),
+            log.pinned,
         ],
     ).map_err(|e| e.to_string())?;
 
@@ -77,20 +138,37 @@ pub fn save_log(log: &ProxyRequestLog) -> Result<(), String> {
 
 /// Get logs summary (without large request_body and response_body fields) with pagination
 pub fn get_logs_summary(limit: usize, offset: usize) -> Result<Vec<ProxyRequestLog>, String> {
+    get_logs_filtered(limit, offset, None, None, None)
+}
+
+/// Get logs summary, optionally only those from the given client IP, served by the given account and/or matching the blocked flag
+pub fn get_logs_filtered(
+    limit: usize,
+    offset: usize,
+    client_ip: Option<&str>,
+    account_email: Option<&str>,
+    blocked: Option<bool>,
+) -> Result<Vec<ProxyRequestLog>, String> {
     let db_path = get_proxy_db_path()?;
     let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
 
     let mut stmt = conn.prepare(
         "SELECT id, timestamp, method, url, status, duration, model, error, 
                 NULL as request_body, NULL as response_body,
-                input_tokens, output_tokens, account_email, mapped_model
+                input_tokens, output_tokens, account_email, mapped_model, api_key_name,
+                client_ip, user_agent, seq, kind, vector_count, client_disconnected, bytes_relayed, timeout_secs,
+                select_ms, auth_ms, upstream_ttfb_ms, stream_ms, upstream_endpoint, error_class, replay_of, finish_reason, blocked, cost, currency, mirrored, prompt_rules, pinned
          FROM request_logs 
+         WHERE (?3 IS NULL OR client_ip = ?3) AND (?4 IS NULL OR account_email = ?4)
+           AND (?5 IS NULL OR COALESCE(blocked, 0) = ?5)
          ORDER BY timestamp DESC 
          LIMIT ?1 OFFSET ?2"
     ).map_err(|e| e.to_string())?;
 
-    let logs_iter = stmt.query_map([limit, offset], |row| {
+    let logs_iter = stmt.query_map(params![limit as i64, offset as i64, client_ip, account_email, blocked], |row| {
         Ok(ProxyRequestLog {
+            seq: row.get::<_, Option<i64>>(17).unwrap_or(None).unwrap_or(0) as u64,
+            kind: row.get::<_, Option<String>>(18).unwrap_or(None).map(|k| RequestKind::parse(&k)).unwrap_or_default(),
             id: row.get(0)?,
             timestamp: row.get(1)?,
             method: row.get(2)?,
@@ -105,6 +183,28 @@ pub fn get_logs_summary(limit: usize, offset: usize) -> Result<Vec<ProxyRequestL
             response_body: None, // Don't query large fields for list view
             input_tokens: row.get(10).unwrap_or(None),
             output_tokens: row.get(11).unwrap_or(None),
+            vector_count: row.get(19).unwrap_or(None),
+            api_key_name: row.get(14).unwrap_or(None),
+            client_ip: row.get(15).unwrap_or(None),
+            user_agent: row.get(16).unwrap_or(None),
+            client_disconnected: row.get::<_, Option<bool>>(20).unwrap_or(None).unwrap_or(false),
+            bytes_relayed: row.get::<_, Option<i64>>(21).unwrap_or(None).map(|b| b.max(0) as u64),
+            timeout_secs: row.get::<_, Option<i64>>(22).unwrap_or(None).map(|t| t.max(0) as u64),
+            phase_timings: read_phase_timings(row),
+            upstream_endpoint: row.get(27).unwrap_or(None),
+            error_class: row.get::<_, Option<String>>(28).unwrap_or(None).and_then(|c| ErrorClass::parse(&c)),
+            replay_of: row.get(29).unwrap_or(None),
+            finish_reason: row.get(30).unwrap_or(None),
+            blocked: row.get::<_, Option<bool>>(31).unwrap_or(None).unwrap_or(false),
+            cost: row.get(32).unwrap_or(None),
+            currency: row.get(33).unwrap_or(None),
+            mirrored: row.get::<_, Option<bool>>(34).unwrap_or(None).unwrap_or(false),
+            prompt_rules: row
+                .get::<_, Option<String>>(35)
+                .unwrap_or(None)
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default(),
+            pinned: row.get::<_, Option<bool>>(36).unwrap_or(None).unwrap_or(false),
         })
     }).map_err(|e| e.to_string())?;
 
@@ -125,23 +225,116 @@ pub fn get_stats() -> Result<crate::proxy::monitor::ProxyStats, String> {
     let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
 
     // Optimized: Use single query instead of three separate queries
-    let (total_requests, success_count, error_count): (u64, u64, u64) = conn.query_row(
+    let (total_requests, success_count, error_count, cancelled_count, blocked_count, avg_duration_ms, avg_ttfb_ms): (u64, u64, u64, u64, u64, Option<f64>, Option<f64>) = conn.query_row(
         "SELECT 
             COUNT(*) as total,
             SUM(CASE WHEN status >= 200 AND status < 400 THEN 1 ELSE 0 END) as success,
-            SUM(CASE WHEN status < 200 OR status >= 400 THEN 1 ELSE 0 END) as error
+            SUM(CASE WHEN status < 200 OR status >= 400 THEN 1 ELSE 0 END) as error,
+            SUM(CASE WHEN client_disconnected = 1 THEN 1 ELSE 0 END) as cancelled,
+            SUM(CASE WHEN blocked = 1 THEN 1 ELSE 0 END) as blocked,
+            AVG(duration) as avg_duration,
+            AVG(upstream_ttfb_ms) as avg_ttfb
          FROM request_logs",
         [],
-        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        |row| Ok((
+            row.get::<_, Option<u64>>(0)?.unwrap_or(0),
+            row.get::<_, Option<u64>>(1)?.unwrap_or(0),
+            row.get::<_, Option<u64>>(2)?.unwrap_or(0),
+            row.get::<_, Option<u64>>(3)?.unwrap_or(0),
+            row.get::<_, Option<u64>>(4)?.unwrap_or(0),
+            row.get::<_, Option<f64>>(5)?,
+            row.get::<_, Option<f64>>(6)?,
+        )),
     ).map_err(|e| e.to_string())?;
 
+    let mut stmt = conn.prepare(
+        "SELECT api_key_name, COUNT(*) FROM request_logs
+         WHERE api_key_name IS NOT NULL
+         GROUP BY api_key_name"
+    ).map_err(|e| e.to_string())?;
+    let requests_by_key = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let since = chrono::Utc::now().timestamp_millis() - TOP_CLIENTS_WINDOW_SECS * 1000;
+    let mut stmt = conn.prepare(
+        "SELECT client_ip, COUNT(*) AS requests FROM request_logs
+         WHERE client_ip IS NOT NULL AND timestamp >= ?1
+         GROUP BY client_ip
+         ORDER BY requests DESC
+         LIMIT ?2"
+    ).map_err(|e| e.to_string())?;
+    let top_clients = stmt
+        .query_map(params![since, TOP_CLIENTS_LIMIT], |row| {
+            Ok(crate::proxy::monitor::ClientTraffic {
+                ip: row.get(0)?,
+                requests: row.get(1)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let tokens_by_account = token_usage_by(&conn, "account_email")?;
+    let tokens_by_model = token_usage_by(&conn, "COALESCE(mapped_model, model)")?;
+
     Ok(crate::proxy::monitor::ProxyStats {
         total_requests,
         success_count,
         error_count,
+        cancelled_count,
+        blocked_count,
+        avg_duration_ms,
+        avg_ttfb_ms,
+        requests_by_key,
+        top_clients,
+        tokens_by_account,
+        tokens_by_model,
+        ..Default::default()
     })
 }
 
+/// 读取阶段耗时列 (列序号 23-26)
+fn read_phase_timings(row: &rusqlite::Row) -> PhaseTimings {
+    let ms = |idx: usize| row.get::<_, Option<i64>>(idx).unwrap_or(None).map(|t| t.max(0) as u64);
+    PhaseTimings {
+        select_ms: ms(23),
+        auth_ms: ms(24),
+        upstream_ttfb_ms: ms(25),
+        stream_ms: ms(26),
+    }
+}
+
+/// 按指定列 (表达式) 分组汇总 Token 用量
+fn token_usage_by(
+    conn: &Connection,
+    column: &str,
+) -> Result<std::collections::BTreeMap<String, crate::proxy::monitor::TokenUsage>, String> {
+    let sql = format!(
+        "SELECT {col}, COALESCE(SUM(input_tokens), 0), COALESCE(SUM(output_tokens), 0) FROM request_logs
+         WHERE {col} IS NOT NULL AND (input_tokens IS NOT NULL OR output_tokens IS NOT NULL)
+         GROUP BY {col}",
+        col = column
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                crate::proxy::monitor::TokenUsage {
+                    input_tokens: row.get::<_, i64>(1)?.max(0) as u64,
+                    output_tokens: row.get::<_, i64>(2)?.max(0) as u64,
+                },
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(rows)
+}
+
 /// Get single log detail (with request_body and response_body)
 pub fn get_log_detail(log_id: &str) -> Result<ProxyRequestLog, String> {
     let db_path = get_proxy_db_path()?;
@@ -150,13 +343,16 @@ pub fn get_log_detail(log_id: &str) -> Result<ProxyRequestLog, String> {
     let mut stmt = conn.prepare(
         "SELECT id, timestamp, method, url, status, duration, model, error, 
                 request_body, response_body, input_tokens, output_tokens, 
-                account_email, mapped_model
+                account_email, mapped_model, api_key_name, client_ip, user_agent, seq, kind, vector_count, client_disconnected, bytes_relayed, timeout_secs,
+                select_ms, auth_ms, upstream_ttfb_ms, stream_ms, upstream_endpoint, error_class, replay_of, finish_reason, blocked, cost, currency, mirrored, prompt_rules, pinned
          FROM request_logs 
          WHERE id = ?1"
     ).map_err(|e| e.to_string())?;
 
     stmt.query_row([log_id], |row| {
         Ok(ProxyRequestLog {
+            seq: row.get::<_, Option<i64>>(17).unwrap_or(None).unwrap_or(0) as u64,
+            kind: row.get::<_, Option<String>>(18).unwrap_or(None).map(|k| RequestKind::parse(&k)).unwrap_or_default(),
             id: row.get(0)?,
             timestamp: row.get(1)?,
             method: row.get(2)?,
@@ -171,11 +367,49 @@ pub fn get_log_detail(log_id: &str) -> Result<ProxyRequestLog, String> {
             response_body: row.get(9).unwrap_or(None),
             input_tokens: row.get(10).unwrap_or(None),
             output_tokens: row.get(11).unwrap_or(None),
+            vector_count: row.get(19).unwrap_or(None),
+            api_key_name: row.get(14).unwrap_or(None),
+            client_ip: row.get(15).unwrap_or(None),
+            user_agent: row.get(16).unwrap_or(None),
+            client_disconnected: row.get::<_, Option<bool>>(20).unwrap_or(None).unwrap_or(false),
+            bytes_relayed: row.get::<_, Option<i64>>(21).unwrap_or(None).map(|b| b.max(0) as u64),
+            timeout_secs: row.get::<_, Option<i64>>(22).unwrap_or(None).map(|t| t.max(0) as u64),
+            phase_timings: read_phase_timings(row),
+            upstream_endpoint: row.get(27).unwrap_or(None),
+            error_class: row.get::<_, Option<String>>(28).unwrap_or(None).and_then(|c| ErrorClass::parse(&c)),
+            replay_of: row.get(29).unwrap_or(None),
+            finish_reason: row.get(30).unwrap_or(None),
+            blocked: row.get::<_, Option<bool>>(31).unwrap_or(None).unwrap_or(false),
+            cost: row.get(32).unwrap_or(None),
+            currency: row.get(33).unwrap_or(None),
+            mirrored: row.get::<_, Option<bool>>(34).unwrap_or(None).unwrap_or(false),
+            prompt_rules: row
+                .get::<_, Option<String>>(35)
+                .unwrap_or(None)
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default(),
+            pinned: row.get::<_, Option<bool>>(36).unwrap_or(None).unwrap_or(false),
         })
     }).map_err(|e| e.to_string())
 }
 
 /// Cleanup old logs (keep last N days)
+/// 统计 `since_ms` (毫秒时间戳) 之后的请求数与失败数
+pub fn get_counts_since(since_ms: i64) -> Result<(u64, u64), String> {
+    let db_path = get_proxy_db_path()?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    conn.query_row(
+        "SELECT 
+            COUNT(*),
+            COALESCE(SUM(CASE WHEN status < 200 OR status >= 400 THEN 1 ELSE 0 END), 0)
+         FROM request_logs
+         WHERE timestamp >= ?1",
+        [since_ms],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).map_err(|e| e.to_string())
+}
+
 pub fn cleanup_old_logs(days: i64) -> Result<usize, String> {
     let db_path = get_proxy_db_path()?;
     let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
@@ -211,9 +445,30 @@ pub fn limit_max_logs(max_count: usize) -> Result<usize, String> {
     Ok(deleted)
 }
 
-pub fn clear_logs() -> Result<(), String> {
+/// 已保存日志中最大的序号，用于重启后继续递增
+pub fn max_seq() -> Result<u64, String> {
     let db_path = get_proxy_db_path()?;
     let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
-    conn.execute("DELETE FROM request_logs", []).map_err(|e| e.to_string())?;
-    Ok(())
+    conn.query_row("SELECT COALESCE(MAX(seq), 0) FROM request_logs", [], |row| row.get::<_, i64>(0))
+        .map(|seq| seq.max(0) as u64)
+        .map_err(|e| e.to_string())
+}
+
+pub fn clear_logs() -> Result<usize, String> {
+    let db_path = get_proxy_db_path()?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM request_logs", []).map_err(|e| e.to_string())
+}
+
+/// 删除匹配条件的日志，返回删除的条数
+pub fn delete_logs(filter: &crate::proxy::monitor::LogFilter) -> Result<usize, String> {
+    let db_path = get_proxy_db_path()?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    conn.execute(
+        "DELETE FROM request_logs
+         WHERE (?1 IS NULL OR timestamp < ?1)
+           AND (?2 IS NULL OR status / 100 = ?2)
+           AND (?3 IS NULL OR model = ?3 OR mapped_model = ?3)",
+        params![filter.before, filter.status_class, filter.model],
+    ).map_err(|e| e.to_string())
 }
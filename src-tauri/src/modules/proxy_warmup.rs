@@ -0,0 +1,145 @@
+//! 反代启动预热：按调度顺序为排在最前的账号提前刷新 Token，可选发送一次最小的生成请求；
+//! 失败不影响启动，只按反代处理上游错误的方式提前计入冷却与禁用状态
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+use utoipa::ToSchema;
+
+use crate::proxy::token_manager::TokenManager;
+use crate::proxy::upstream::client::UpstreamClient;
+
+/// 启动响应等待预热完成的最长时间 (秒)，超时后其余进度只通过事件推送
+pub const START_WAIT_SECS: u64 = 10;
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WarmupResult {
+    pub account_id: String,
+    pub email: String,
+    pub success: bool,
+    /// 预热时是否刷新了 Token (未过期的 Token 不会刷新)
+    pub refreshed: bool,
+    /// 探测请求的上游 HTTP 状态码，未开启探测或未收到响应时为空
+    pub probe_status: Option<u16>,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+/// 探测请求失败时，与反代处理器相同的状态码计入限流冷却
+fn is_cooldown_status(status: u16) -> bool {
+    matches!(status, 401 | 403 | 429 | 500 | 503 | 529)
+}
+
+async fn warm_up(
+    token_manager: &TokenManager,
+    upstream: &UpstreamClient,
+    custom_mapping: &HashMap<String, String>,
+    account_id: String,
+    email: String,
+    probe: bool,
+) -> WarmupResult {
+    let start = Instant::now();
+    let mut result = WarmupResult {
+        account_id,
+        email,
+        success: false,
+        refreshed: false,
+        probe_status: None,
+        latency_ms: 0,
+        error: None,
+    };
+
+    match token_manager.warm_up_account(&result.account_id).await {
+        Ok(refreshed) => result.refreshed = refreshed,
+        Err(e) => {
+            result.error = Some(e);
+            result.latency_ms = start.elapsed().as_millis() as u64;
+            return result;
+        }
+    }
+
+    if probe {
+        let test = match crate::modules::account::load_account(&result.account_id) {
+            Ok(account) => {
+                crate::modules::account_test::run(
+                    upstream,
+                    token_manager,
+                    custom_mapping,
+                    &account,
+                    crate::modules::account_test::DEFAULT_MODEL,
+                    crate::modules::account_test::DEFAULT_PROMPT,
+                )
+                .await
+            }
+            Err(e) => {
+                result.error = Some(e);
+                result.latency_ms = start.elapsed().as_millis() as u64;
+                return result;
+            }
+        };
+        result.probe_status = test.status;
+        if test.success {
+            token_manager.mark_account_success(&result.account_id);
+        } else {
+            let error = test.error.unwrap_or_default();
+            let status = test.status.unwrap_or(0);
+            if !token_manager.mark_region_blocked(&result.email, &error) && is_cooldown_status(status) {
+                token_manager.mark_rate_limited(&result.email, status, None, &error);
+            }
+            result.error = Some(error);
+        }
+    }
+
+    result.success = result.error.is_none();
+    result.latency_ms = start.elapsed().as_millis() as u64;
+    result
+}
+
+/// 并发预热调度顺序最前的 `limit` 个可用账号，每完成一个调用一次 `progress(结果, 已完成数, 总数)`
+pub async fn run(
+    token_manager: Arc<TokenManager>,
+    upstream: Arc<UpstreamClient>,
+    custom_mapping: HashMap<String, String>,
+    limit: usize,
+    probe: bool,
+    progress: impl Fn(&WarmupResult, usize, usize),
+) -> Vec<WarmupResult> {
+    let candidates = token_manager.warmup_candidates(limit);
+    let total = candidates.len();
+    tracing::info!("开始预热 {} 个账号 (探测请求: {})", total, probe);
+
+    let mut tasks: FuturesUnordered<_> = candidates
+        .into_iter()
+        .map(|(account_id, email)| warm_up(&token_manager, &upstream, &custom_mapping, account_id, email, probe))
+        .collect();
+
+    let mut results = Vec::with_capacity(total);
+    while let Some(result) = tasks.next().await {
+        if let Some(error) = &result.error {
+            tracing::warn!("账号 {} 预热失败: {}", result.email, error);
+        }
+        results.push(result);
+        progress(results.last().unwrap(), results.len(), total);
+    }
+
+    let failed = results.iter().filter(|r| !r.success).count();
+    tracing::info!("预热完成: {} 个账号, {} 个失败", total, failed);
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cooldown_status_matches_handlers() {
+        for status in [401, 403, 429, 500, 503, 529] {
+            assert!(is_cooldown_status(status));
+        }
+        for status in [0, 200, 400, 404, 502] {
+            assert!(!is_cooldown_status(status));
+        }
+    }
+}
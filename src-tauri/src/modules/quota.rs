@@ -1,7 +1,9 @@
 use reqwest;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use crate::models::QuotaData;
+use std::future::Future;
+use std::time::Duration;
+use crate::models::{QuotaData, QuotaRetryOn, QuotaRetryPolicy};
 use crate::modules::config;
 
 const QUOTA_API_URL: &str = "https://cloudcode-pa.googleapis.com/v1internal:fetchAvailableModels";
@@ -12,6 +14,26 @@ const NEAR_READY_THRESHOLD: i32 = 95;
 const MAX_RETRIES: u32 = 3;
 const RETRY_DELAY_SECS: u64 = 30;
 
+/// 上游提示账号已停用/封禁的错误特征 (小写)
+const SUSPENDED_MARKERS: &[&str] = &["suspended", "account_disabled", "user_disabled", "account has been disabled", "account is disabled"];
+
+/// 永久性失败的原因：403 或上游提示账号已停用，重试无意义；其余错误视为暂时失败，返回 None
+pub fn permanent_failure_reason(status: reqwest::StatusCode, body: &str) -> Option<String> {
+    let lower = body.to_ascii_lowercase();
+    if status != reqwest::StatusCode::FORBIDDEN && !SUSPENDED_MARKERS.iter().any(|m| lower.contains(m)) {
+        return None;
+    }
+    let message = serde_json::from_str::<serde_json::Value>(body)
+        .ok()
+        .and_then(|json| json.pointer("/error/message").and_then(|m| m.as_str()).map(str::to_string))
+        .unwrap_or_else(|| body.trim().chars().take(200).collect());
+    Some(if message.is_empty() {
+        format!("HTTP {}", status.as_u16())
+    } else {
+        format!("HTTP {}: {}", status.as_u16(), message)
+    })
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct QuotaResponse {
     models: std::collections::HashMap<String, ModelInfo>,
@@ -116,11 +138,131 @@ pub async fn fetch_quota(access_token: &str, email: &str) -> crate::error::AppRe
     fetch_quota_with_cache(access_token, email, None).await
 }
 
-/// 带缓存的配额查询
+/// 单次配额查询的失败
+#[derive(Debug)]
+pub struct AttemptFailure {
+    pub message: String,
+    /// 可重试的失败类别，为空表示不重试
+    pub kind: Option<QuotaRetryOn>,
+    /// 上游 429 响应的 Retry-After
+    pub retry_after: Option<Duration>,
+}
+
+impl AttemptFailure {
+    /// 按 HTTP 状态归类失败：429 与 5xx 可重试，其余直接返回
+    pub fn from_status(status: reqwest::StatusCode, retry_after: Option<Duration>, text: &str) -> Self {
+        let kind = if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            Some(QuotaRetryOn::RateLimited)
+        } else if status.is_server_error() {
+            Some(QuotaRetryOn::ServerError)
+        } else {
+            None
+        };
+        Self {
+            message: format!("API 错误: {} - {}", status, text),
+            kind,
+            retry_after: retry_after.filter(|_| kind == Some(QuotaRetryOn::RateLimited)),
+        }
+    }
+
+    /// 请求未得到响应 (连接失败、超时)
+    pub fn transport(e: &reqwest::Error) -> Self {
+        Self {
+            message: format!("请求失败: {}", e),
+            kind: Some(QuotaRetryOn::Timeout),
+            retry_after: None,
+        }
+    }
+}
+
+/// 解析 Retry-After 响应头 (秒数或 HTTP 日期)
+pub fn parse_retry_after(value: &str, now: chrono::DateTime<chrono::Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.with_timezone(&chrono::Utc) - now).to_std().unwrap_or(Duration::ZERO))
+}
+
+/// 第 `retry` 次重试 (从 1 开始) 前的退避时间
+fn backoff_delay(policy: &QuotaRetryPolicy, retry: u32) -> Duration {
+    let factor = 1u64.checked_shl(retry.saturating_sub(1)).unwrap_or(u64::MAX);
+    Duration::from_millis(policy.initial_backoff_ms.saturating_mul(factor).min(policy.max_backoff_ms))
+}
+
+/// 第 `attempt` 次尝试失败后下一次重试前的等待时间，不再重试时返回 None；
+/// Retry-After 超过 `max_backoff_ms` 时放弃，避免长时间占用刷新任务
+fn next_delay(policy: &QuotaRetryPolicy, failure: &AttemptFailure, attempt: u32) -> Option<Duration> {
+    let kind = failure.kind?;
+    if attempt >= policy.max_attempts || !policy.retry_on.contains(&kind) {
+        return None;
+    }
+    match failure.retry_after {
+        Some(wait) if wait > Duration::from_millis(policy.max_backoff_ms) => None,
+        Some(wait) => Some(wait),
+        None => Some(backoff_delay(policy, attempt)),
+    }
+}
+
+/// 按策略重试 `op` (参数为从 1 开始的尝试序号)，最终失败时错误信息包含尝试次数
+pub async fn run_with_retry<T, Op, Fut, Sleep, SleepFut>(
+    policy: &QuotaRetryPolicy,
+    mut op: Op,
+    mut sleep: Sleep,
+) -> Result<T, String>
+where
+    Op: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<T, AttemptFailure>>,
+    Sleep: FnMut(Duration) -> SleepFut,
+    SleepFut: Future<Output = ()>,
+{
+    let mut attempt = 1;
+    loop {
+        let failure = match op(attempt).await {
+            Ok(value) => return Ok(value),
+            Err(failure) => failure,
+        };
+        match next_delay(policy, &failure, attempt) {
+            Some(delay) => {
+                crate::modules::logger::log_warn(&format!(
+                    "{} (尝试 {}/{})，{}ms 后重试",
+                    failure.message,
+                    attempt,
+                    policy.max_attempts,
+                    delay.as_millis()
+                ));
+                sleep(delay).await;
+                attempt += 1;
+            }
+            None => return Err(format!("{} (共尝试 {} 次)", failure.message, attempt)),
+        }
+    }
+}
+
+/// 单次查询的结果
+enum QuotaFetch {
+    Models(QuotaResponse),
+    /// 永久性失败 (403 / 账号停用)，不重试
+    Forbidden(String),
+}
+
+/// 带缓存的配额查询，按配置中的 `quota_retry` 重试
 pub async fn fetch_quota_with_cache(
     access_token: &str,
     email: &str,
     cached_project_id: Option<&str>,
+) -> crate::error::AppResult<(QuotaData, Option<String>)> {
+    let policy = config::load_app_config().map(|c| c.quota_retry).unwrap_or_default();
+    fetch_quota_with_policy(access_token, email, cached_project_id, &policy).await
+}
+
+/// 按指定重试策略查询配额
+pub async fn fetch_quota_with_policy(
+    access_token: &str,
+    email: &str,
+    cached_project_id: Option<&str>,
+    policy: &QuotaRetryPolicy,
 ) -> crate::error::AppResult<(QuotaData, Option<String>)> {
     use crate::error::AppError;
     
@@ -138,89 +280,90 @@ pub async fn fetch_quota_with_cache(
         "project": final_project_id
     });
     
-    let url = QUOTA_API_URL;
-    let max_retries = 3;
-    let mut last_error: Option<AppError> = None;
-
-    for attempt in 1..=max_retries {
-        match client
-            .post(url)
-            .bearer_auth(access_token)
-            .header("User-Agent", USER_AGENT)
-            .json(&json!(payload))
-            .send()
-            .await
-        {
-            Ok(response) => {
-                // 将 HTTP 错误状态转换为 AppError
-                if let Err(_) = response.error_for_status_ref() {
-                    let status = response.status();
-                    
-                    // ✅ 特殊处理 403 Forbidden - 直接返回,不重试
-                    if status == reqwest::StatusCode::FORBIDDEN {
-                        crate::modules::logger::log_warn(&format!(
-                            "账号无权限 (403 Forbidden),标记为 forbidden 状态"
-                        ));
-                        let mut q = QuotaData::new();
-                        q.is_forbidden = true;
-                        q.subscription_tier = subscription_tier.clone();
-                        return Ok((q, project_id.clone()));
-                    }
-                    
-                    // 其他错误继续重试逻辑
-                    if attempt < max_retries {
-                         let text = response.text().await.unwrap_or_default();
-                         crate::modules::logger::log_warn(&format!("API 错误: {} - {} (尝试 {}/{})", status, text, attempt, max_retries));
-                         last_error = Some(AppError::Unknown(format!("HTTP {} - {}", status, text)));
-                         tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                         continue;
-                    } else {
-                         let text = response.text().await.unwrap_or_default();
-                         return Err(AppError::Unknown(format!("API 错误: {} - {}", status, text)));
-                    }
-                }
-
-                let quota_response: QuotaResponse = response
-                    .json()
+    let fetched = run_with_retry(
+        policy,
+        |_| {
+            let (client, payload) = (&client, &payload);
+            async move {
+                let response = client
+                    .post(QUOTA_API_URL)
+                    .bearer_auth(access_token)
+                    .header("User-Agent", USER_AGENT)
+                    .json(payload)
+                    .send()
                     .await
-                    .map_err(|e| AppError::Network(e))?;
-                
-                let mut quota_data = QuotaData::new();
-                
-                // 使用 debug 级别记录详细信息，避免控制台噪音
-                tracing::debug!("Quota API 返回了 {} 个模型", quota_response.models.len());
-
-                for (name, info) in quota_response.models {
-                    if let Some(quota_info) = info.quota_info {
-                        let percentage = quota_info.remaining_fraction
-                            .map(|f| (f * 100.0) as i32)
-                            .unwrap_or(0);
-                        
-                        let reset_time = quota_info.reset_time.unwrap_or_default();
-                        
-                        // 只保存我们关心的模型
-                        if name.contains("gemini") || name.contains("claude") {
-                            quota_data.add_model(name, percentage, reset_time);
-                        }
+                    .map_err(|e| AttemptFailure::transport(&e))?;
+
+                let status = response.status();
+                if !status.is_success() {
+                    let retry_after = response
+                        .headers()
+                        .get(reqwest::header::RETRY_AFTER)
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(|v| parse_retry_after(v, chrono::Utc::now()));
+                    let text = response.text().await.unwrap_or_default();
+                    // ✅ 永久性失败 (403 Forbidden / 账号停用) - 标记为 forbidden 后直接返回,不重试
+                    if let Some(reason) = permanent_failure_reason(status, &text) {
+                        return Ok(QuotaFetch::Forbidden(reason));
                     }
+                    return Err(AttemptFailure::from_status(status, retry_after, &text));
                 }
-                
-                // 设置订阅类型
-                quota_data.subscription_tier = subscription_tier.clone();
-                
-                return Ok((quota_data, project_id.clone()));
-            },
-            Err(e) => {
-                crate::modules::logger::log_warn(&format!("请求失败: {} (尝试 {}/{})", e, attempt, max_retries));
-                last_error = Some(AppError::Network(e));
-                if attempt < max_retries {
-                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                }
+
+                response.json::<QuotaResponse>().await.map(QuotaFetch::Models).map_err(|e| AttemptFailure {
+                    message: format!("解析配额响应失败: {}", e),
+                    kind: None,
+                    retry_after: None,
+                })
+            }
+        },
+        tokio::time::sleep,
+    )
+    .await
+    .map_err(AppError::Unknown)?;
+
+    let quota_response = match fetched {
+        QuotaFetch::Models(response) => response,
+        QuotaFetch::Forbidden(reason) => {
+            crate::modules::logger::log_warn(&format!(
+                "账号 {} 无权限 ({}),标记为 forbidden 状态", email, reason
+            ));
+            let mut q = QuotaData::new();
+            q.is_forbidden = true;
+            q.forbidden_reason = Some(reason);
+            q.subscription_tier = subscription_tier.clone();
+            return Ok((q, project_id.clone()));
+        }
+    };
+
+    let mut quota_data = QuotaData::new();
+    
+    // 使用 debug 级别记录详细信息，避免控制台噪音
+    tracing::debug!("Quota API 返回了 {} 个模型", quota_response.models.len());
+
+    for (name, info) in quota_response.models {
+        if let Some(quota_info) = info.quota_info {
+            let percentage = quota_info.remaining_fraction
+                .map(|f| (f * 100.0) as i32)
+                .unwrap_or(0);
+            
+            let reset_time = quota_info.reset_time.unwrap_or_default();
+            
+            // 只保存我们关心的模型
+            if name.contains("gemini") || name.contains("claude") {
+                quota_data.add_model(name, percentage, reset_time);
             }
         }
     }
     
-    Err(last_error.unwrap_or_else(|| AppError::Unknown("配额查询失败".to_string())))
+    quota_data.refresh_families();
+    let reset_hour = crate::modules::config::load_app_config()
+        .ok()
+        .and_then(|c| c.quota_reset_hour_utc);
+    quota_data.compute_reset(reset_hour, chrono::Utc::now().timestamp());
+    // 设置订阅类型
+    quota_data.subscription_tier = subscription_tier.clone();
+    
+    Ok((quota_data, project_id.clone()))
 }
 
 /// 查询账号配额逻辑
@@ -505,3 +648,91 @@ pub async fn warm_up_account(account_id: &str) -> Result<String, String> {
 
     Ok(format!("成功触发 {} 个系列的模型预热", warmed_count))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permanent_failure_reason() {
+        let forbidden = r#"{"error":{"code":403,"message":"The caller does not have permission","status":"PERMISSION_DENIED"}}"#;
+        assert_eq!(
+            permanent_failure_reason(reqwest::StatusCode::FORBIDDEN, forbidden).as_deref(),
+            Some("HTTP 403: The caller does not have permission")
+        );
+        assert!(permanent_failure_reason(reqwest::StatusCode::BAD_REQUEST, "Your account has been suspended").is_some());
+        assert_eq!(permanent_failure_reason(reqwest::StatusCode::FORBIDDEN, "").as_deref(), Some("HTTP 403"));
+        // 暂时性失败
+        assert!(permanent_failure_reason(reqwest::StatusCode::INTERNAL_SERVER_ERROR, "backend error").is_none());
+        assert!(permanent_failure_reason(reqwest::StatusCode::TOO_MANY_REQUESTS, "quota").is_none());
+    }
+
+    /// 模拟上游：按顺序返回预设的状态码与 Retry-After，记录每次等待时间
+    async fn run_scripted(policy: &QuotaRetryPolicy, script: &[(u16, Option<&str>)]) -> (Result<u16, String>, Vec<u64>) {
+        let now = chrono::Utc::now();
+        let responses = std::cell::RefCell::new(script.iter());
+        let sleeps = std::cell::RefCell::new(Vec::new());
+        let result = run_with_retry(
+            policy,
+            |_| {
+                let (status, retry_after) = *responses.borrow_mut().next().expect("unexpected extra attempt");
+                async move {
+                    let status = reqwest::StatusCode::from_u16(status).unwrap();
+                    if status.is_success() {
+                        return Ok(status.as_u16());
+                    }
+                    let retry_after = retry_after.and_then(|v| parse_retry_after(v, now));
+                    Err(AttemptFailure::from_status(status, retry_after, "error"))
+                }
+            },
+            |delay: Duration| {
+                sleeps.borrow_mut().push(delay.as_millis() as u64);
+                async {}
+            },
+        )
+        .await;
+        (result, sleeps.into_inner())
+    }
+
+    #[tokio::test]
+    async fn test_retry_backoff_sequence() {
+        let policy = QuotaRetryPolicy {
+            max_attempts: 5,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 300,
+            ..Default::default()
+        };
+
+        // 指数退避并封顶
+        let (result, sleeps) = run_scripted(&policy, &[(503, None), (500, None), (502, None), (200, None)]).await;
+        assert_eq!(result, Ok(200));
+        assert_eq!(sleeps, vec![100, 200, 300]);
+
+        // 429 按 Retry-After 等待；超过 max_backoff_ms 时放弃
+        let (result, sleeps) = run_scripted(&policy, &[(429, Some("0")), (429, Some("60"))]).await;
+        assert!(result.unwrap_err().contains("(共尝试 2 次)"));
+        assert_eq!(sleeps, vec![0]);
+
+        // 用尽尝试次数
+        let (result, sleeps) = run_scripted(&policy, &[(500, None); 5]).await;
+        assert!(result.unwrap_err().ends_with("(共尝试 5 次)"));
+        assert_eq!(sleeps, vec![100, 200, 300, 300]);
+
+        // 不在 retry_on 中的失败与 4xx 不重试
+        let only_timeouts = QuotaRetryPolicy { retry_on: vec![QuotaRetryOn::Timeout], ..policy.clone() };
+        let (result, sleeps) = run_scripted(&only_timeouts, &[(503, None)]).await;
+        assert!(result.unwrap_err().contains("(共尝试 1 次)"));
+        assert!(sleeps.is_empty());
+        let (_, sleeps) = run_scripted(&policy, &[(400, None)]).await;
+        assert!(sleeps.is_empty());
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = chrono::DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z").unwrap().with_timezone(&chrono::Utc);
+        assert_eq!(parse_retry_after("120", now), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now), Some(Duration::from_secs(30)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:27:00 GMT", now), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+}
@@ -0,0 +1,246 @@
+//! 请求重放：将日志中记录的入站请求经当前反代链路 (模型映射、账号调度、监控) 重新发送一次
+//!
+//! 重放以原请求的身份 (密钥与客户端地址) 经过完整的 IP 过滤与鉴权，当前策略不再允许原客户端时重放同样被拒绝。
+
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{header, Method, Request};
+use serde::Serialize;
+use serde_json::Value;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Instant;
+use utoipa::ToSchema;
+
+use crate::proxy::config::ProxyAuthMode;
+use crate::proxy::monitor::{ProxyMonitor, ProxyRequestLog, ReplayOf, RequestLogId};
+use crate::proxy::token_manager::PINNED_ACCOUNT;
+use crate::proxy::AxumServer;
+
+/// 返回的响应内容的最大字符数
+const MAX_RESPONSE_CHARS: usize = 2000;
+/// 读取响应体的上限
+const MAX_RESPONSE_BYTES: usize = 10 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReplayResult {
+    /// 重放请求写入的日志 ID (监控未开启时为空)
+    pub log_id: Option<String>,
+    /// 被重放的日志 ID
+    pub replay_of: String,
+    pub status: u16,
+    pub duration_ms: u64,
+    /// 实际使用的账号
+    pub account_email: Option<String>,
+    /// 路由后使用的模型
+    pub mapped_model: Option<String>,
+    /// 原请求为流式，重放时改为非流式
+    pub non_stream: bool,
+    /// 响应内容 (截断)
+    pub response: String,
+}
+
+/// 待重放的请求
+#[derive(Debug, PartialEq)]
+struct ReplayRequest {
+    url: String,
+    body: String,
+    non_stream: bool,
+}
+
+/// 由日志构建重放请求：未记录请求体或请求体不是 JSON 时拒绝，流式请求改为非流式
+fn prepare(log: &ProxyRequestLog) -> Result<ReplayRequest, String> {
+    if !log.method.eq_ignore_ascii_case("POST") {
+        return Err(format!("只能重放 POST 请求 (日志为 {})", log.method));
+    }
+    let body = match log.request_body.as_deref() {
+        None | Some("") => return Err("该日志未记录请求体，无法重放".to_string()),
+        Some("[Binary Request Data]") => return Err("该日志的请求体为二进制数据，无法重放".to_string()),
+        Some(body) => body,
+    };
+    let mut json: Value = serde_json::from_str(body).map_err(|e| format!("请求体不是有效的 JSON: {}", e))?;
+
+    let mut non_stream = false;
+    if let Some(stream) = json.get_mut("stream") {
+        if stream.as_bool() == Some(true) {
+            *stream = Value::Bool(false);
+            non_stream = true;
+        }
+    }
+
+    // Gemini 原生协议通过路径区分流式
+    let (path, query) = log.url.split_once('?').unwrap_or((log.url.as_str(), ""));
+    let mut url = path.to_string();
+    if url.ends_with(":streamGenerateContent") {
+        url = url.replace(":streamGenerateContent", ":generateContent");
+        non_stream = true;
+    }
+    let query: Vec<&str> = query.split('&').filter(|p| !p.is_empty() && *p != "alt=sse").collect();
+    if !query.is_empty() {
+        url = format!("{}?{}", url, query.join("&"));
+    }
+
+    Ok(ReplayRequest {
+        url,
+        body: serde_json::to_string(&json).map_err(|e| format!("序列化请求体失败: {}", e))?,
+        non_stream,
+    })
+}
+
+/// 以原请求的身份构建重放请求：`api_key` 为原密钥的当前值，客户端地址沿用日志记录的地址
+fn build_request(
+    prepared: ReplayRequest,
+    original: &ProxyRequestLog,
+    api_key: Option<&str>,
+) -> Result<Request<Body>, String> {
+    let mut builder = Request::builder()
+        .method(Method::POST)
+        .uri(&prepared.url)
+        .header(header::CONTENT_TYPE, "application/json");
+    if let Some(key) = api_key {
+        builder = builder.header(header::AUTHORIZATION, format!("Bearer {}", key));
+    }
+    let mut request = builder
+        .body(Body::from(prepared.body))
+        .map_err(|e| format!("构建重放请求失败: {}", e))?;
+    request.extensions_mut().insert(ReplayOf(original.id.clone()));
+    // 未记录地址时不设置，IP 白名单生效时按未知来源拒绝
+    if let Some(ip) = original.client_ip.as_deref().and_then(|ip| ip.parse::<IpAddr>().ok()) {
+        request.extensions_mut().insert(ConnectInfo(SocketAddr::new(ip, 0)));
+    }
+    Ok(request)
+}
+
+fn truncate(text: &str) -> String {
+    let mut chars = text.chars();
+    let head: String = chars.by_ref().take(MAX_RESPONSE_CHARS).collect();
+    if chars.next().is_some() {
+        format!("{}…", head)
+    } else {
+        head
+    }
+}
+
+fn header_value(response: &axum::response::Response, name: &str) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string())
+}
+
+/// 重放指定日志的请求；`same_account` 为 true 时固定使用原请求的账号
+pub async fn replay(
+    server: &AxumServer,
+    monitor: &ProxyMonitor,
+    log_id: &str,
+    same_account: bool,
+) -> Result<ReplayResult, String> {
+    let original = monitor.find_log(log_id).await?;
+    let prepared = prepare(&original)?;
+    let pinned = if same_account {
+        Some(original.account_email.clone().ok_or("原请求没有记录使用的账号，无法固定账号重放")?)
+    } else {
+        None
+    };
+
+    let security = server.security().await;
+    let api_key = match original.api_key_name.as_deref() {
+        Some(name) => Some(
+            security
+                .key_for(name)
+                .ok_or_else(|| format!("原请求使用的密钥 {} 已删除或禁用，无法重放", name))?,
+        ),
+        None if !matches!(security.effective_auth_mode(), ProxyAuthMode::Off) => {
+            return Err("原请求未使用密钥，当前反代已开启鉴权，无法重放".to_string());
+        }
+        None => None,
+    };
+    let (url, non_stream) = (prepared.url.clone(), prepared.non_stream);
+    let request = build_request(prepared, &original, api_key)?;
+
+    crate::modules::logger::log_info(&format!(
+        "重放请求 {} ({}{})",
+        original.id,
+        url,
+        pinned.as_deref().map(|e| format!(", 账号 {}", e)).unwrap_or_default()
+    ));
+    let start = Instant::now();
+    let response = match pinned {
+        Some(email) => PINNED_ACCOUNT.scope(email, server.dispatch(request)).await,
+        None => server.dispatch(request).await,
+    };
+
+    let status = response.status().as_u16();
+    let new_log_id = response.extensions().get::<RequestLogId>().map(|id| id.0.clone());
+    let account_email = header_value(&response, "X-Account-Email");
+    let mapped_model = header_value(&response, "X-Mapped-Model");
+    let body = axum::body::to_bytes(response.into_body(), MAX_RESPONSE_BYTES)
+        .await
+        .map_err(|e| format!("读取重放响应失败: {}", e))?;
+
+    Ok(ReplayResult {
+        log_id: new_log_id,
+        replay_of: original.id,
+        status,
+        duration_ms: start.elapsed().as_millis() as u64,
+        account_email,
+        mapped_model,
+        non_stream,
+        response: truncate(&String::from_utf8_lossy(&body)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::monitor::RequestKind;
+
+    fn log(url: &str, body: Option<&str>) -> ProxyRequestLog {
+        ProxyRequestLog {
+            kind: RequestKind::from_url(url),
+            url: url.to_string(),
+            request_body: body.map(|b| b.to_string()),
+            ..ProxyRequestLog::test_default()
+        }
+    }
+
+    #[test]
+    fn test_prepare() {
+        let openai = prepare(&log("/v1/chat/completions", Some(r#"{"model":"m","stream":true}"#))).unwrap();
+        assert!(openai.non_stream);
+        assert_eq!(serde_json::from_str::<Value>(&openai.body).unwrap()["stream"], false);
+
+        let gemini = prepare(&log(
+            "/v1beta/models/gemini-2.5-flash:streamGenerateContent?alt=sse&key=k",
+            Some(r#"{"contents":[]}"#),
+        ))
+        .unwrap();
+        assert!(gemini.non_stream);
+        assert_eq!(gemini.url, "/v1beta/models/gemini-2.5-flash:generateContent?key=k");
+
+        let plain = prepare(&log("/v1/messages", Some(r#"{"model":"m"}"#))).unwrap();
+        assert!(!plain.non_stream);
+        assert_eq!(plain.url, "/v1/messages");
+
+        assert!(prepare(&log("/v1/messages", None)).is_err());
+        assert!(prepare(&log("/v1/messages", Some("[Binary Request Data]"))).is_err());
+        assert!(prepare(&log("/v1/messages", Some("not json"))).is_err());
+    }
+
+    #[test]
+    fn test_build_request_keeps_original_identity() {
+        let mut original = log("/v1/messages", Some(r#"{"model":"m"}"#));
+        original.client_ip = Some("10.0.0.7".to_string());
+        let request = build_request(prepare(&original).unwrap(), &original, Some("sk-alice")).unwrap();
+        assert_eq!(request.headers()[header::AUTHORIZATION], "Bearer sk-alice");
+        let ConnectInfo(peer) = request.extensions().get::<ConnectInfo<SocketAddr>>().unwrap();
+        assert_eq!(peer.ip().to_string(), "10.0.0.7");
+        assert_eq!(request.extensions().get::<ReplayOf>().unwrap().0, "log-1");
+
+        // 原请求未经鉴权、未记录地址时不附带任何身份
+        original.client_ip = None;
+        let request = build_request(prepare(&original).unwrap(), &original, None).unwrap();
+        assert!(request.headers().get(header::AUTHORIZATION).is_none());
+        assert!(request.extensions().get::<ConnectInfo<SocketAddr>>().is_none());
+    }
+}
@@ -0,0 +1,230 @@
+//! 定时轮换当前账号：按配置的间隔依次切换到候选列表中的下一个可用账号
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use utoipa::ToSchema;
+
+use crate::models::{Account, RotationConfig};
+use crate::modules::auto_switch::{self, AccountSwitch, SwitchReason};
+use crate::modules::{account, config, logger};
+use crate::utils::tasks::TaskContext;
+
+/// 检查是否到达轮换时间的间隔 (秒)
+const CHECK_INTERVAL_SECS: u64 = 60;
+
+/// 防止定时任务与手动触发同时切换
+static ROTATING: AtomicBool = AtomicBool::new(false);
+
+/// 持久化的轮换记录
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct RotationState {
+    /// 轮换计划的起点：上次轮换时间，或启用后首次检查的时间
+    #[serde(default)]
+    anchor_at: Option<i64>,
+    #[serde(default)]
+    last_rotated_at: Option<i64>,
+    #[serde(default)]
+    last_account_id: Option<String>,
+}
+
+/// 轮换计划状态
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RotationStatus {
+    pub enabled: bool,
+    pub interval_hours: u32,
+    /// 下次轮换时间 (Unix 秒)，未启用时为空
+    pub next_rotation_at: Option<i64>,
+    /// 下次轮换将切换到的账号
+    pub next_account_id: Option<String>,
+    /// 上次轮换时间 (Unix 秒)
+    pub last_rotated_at: Option<i64>,
+    /// 上次轮换切换到的账号
+    pub last_account_id: Option<String>,
+}
+
+fn state_path() -> Result<PathBuf, String> {
+    Ok(account::get_data_dir()?.join("rotation_state.json"))
+}
+
+fn load_state() -> RotationState {
+    state_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_state(state: &RotationState) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(state).map_err(|e| format!("序列化轮换记录失败: {}", e))?;
+    std::fs::write(state_path()?, content).map_err(|e| format!("保存轮换记录失败: {}", e))
+}
+
+/// 账号可参与轮换：未禁用、未被禁止访问、未受地区限制
+fn is_usable(account: &Account) -> bool {
+    !account.disabled
+        && !account.region_blocked
+        && !account.quota.as_ref().is_some_and(|q| q.is_forbidden)
+}
+
+/// 选择当前账号之后的下一个候选账号 (循环)；当前账号不在候选中时取第一个可用账号
+fn next_candidate<'a>(
+    accounts: &'a [Account],
+    rotation: &RotationConfig,
+    current_id: Option<&str>,
+) -> Option<&'a Account> {
+    let ordered: Vec<&Account> = if rotation.candidates.is_empty() {
+        accounts.iter().collect()
+    } else {
+        rotation
+            .candidates
+            .iter()
+            .filter_map(|id| accounts.iter().find(|a| a.id == *id))
+            .collect()
+    };
+    let start = current_id
+        .and_then(|id| ordered.iter().position(|a| a.id == id))
+        .map(|pos| pos + 1)
+        .unwrap_or(0);
+    (0..ordered.len())
+        .map(|offset| ordered[(start + offset) % ordered.len()])
+        .find(|a| Some(a.id.as_str()) != current_id && is_usable(a))
+}
+
+fn next_rotation_at(rotation: &RotationConfig, state: &RotationState) -> Option<i64> {
+    if !rotation.enabled {
+        return None;
+    }
+    state
+        .anchor_at
+        .map(|anchor| anchor + rotation.interval_hours as i64 * 3600)
+}
+
+/// 查询轮换计划状态
+pub fn status() -> Result<RotationStatus, String> {
+    let rotation = config::load_app_config()?.rotation;
+    let state = load_state();
+    let accounts = account::list_accounts()?;
+    let current_id = account::get_current_account_id()?;
+    Ok(RotationStatus {
+        enabled: rotation.enabled,
+        interval_hours: rotation.interval_hours,
+        next_rotation_at: next_rotation_at(&rotation, &state),
+        next_account_id: next_candidate(&accounts, &rotation, current_id.as_deref()).map(|a| a.id.clone()),
+        last_rotated_at: state.last_rotated_at,
+        last_account_id: state.last_account_id,
+    })
+}
+
+/// 立即轮换到下一个候选账号 (不要求启用定时轮换)，并从此刻重新计时
+pub async fn rotate_now() -> Result<Option<AccountSwitch>, String> {
+    if ROTATING.swap(true, Ordering::SeqCst) {
+        return Err("正在轮换账号，请稍后重试".to_string());
+    }
+    let result = rotate().await;
+    ROTATING.store(false, Ordering::SeqCst);
+    if let Ok(Some(switch)) = &result {
+        auto_switch::notify(switch);
+    }
+    result
+}
+
+async fn rotate() -> Result<Option<AccountSwitch>, String> {
+    let rotation = config::load_app_config()?.rotation;
+    let accounts = account::list_accounts()?;
+    let current_id = account::get_current_account_id()?;
+    let Some(next) = next_candidate(&accounts, &rotation, current_id.as_deref()) else {
+        logger::log_warn("[Rotation] 没有可轮换的账号 (候选账号均已禁用、被禁止访问或为当前账号)");
+        // 顺延到下一个周期，避免每次检查重复告警
+        let mut state = load_state();
+        state.anchor_at = Some(chrono::Utc::now().timestamp());
+        save_state(&state)?;
+        return Ok(None);
+    };
+
+    logger::log_info(&format!("[Rotation] 定时轮换当前账号到 {}", next.email));
+    account::switch_account(&next.id).await?;
+
+    let now = chrono::Utc::now().timestamp();
+    save_state(&RotationState {
+        anchor_at: Some(now),
+        last_rotated_at: Some(now),
+        last_account_id: Some(next.id.clone()),
+    })?;
+
+    Ok(Some(AccountSwitch {
+        previous_account_id: current_id.unwrap_or_default(),
+        account: next.clone(),
+        reason: SwitchReason::ScheduledRotation,
+    }))
+}
+
+/// 检查一次：启用后首次检查时开始计时，到达间隔时轮换
+async fn tick() -> Result<(), String> {
+    let rotation = config::load_app_config()?.rotation;
+    let mut state = load_state();
+    if !rotation.enabled {
+        // 关闭后清除计时起点，重新启用时从启用时刻开始计时
+        if state.anchor_at.take().is_some() {
+            save_state(&state)?;
+        }
+        return Ok(());
+    }
+    let now = chrono::Utc::now().timestamp();
+    match next_rotation_at(&rotation, &state) {
+        None => {
+            state.anchor_at = Some(now);
+            save_state(&state)
+        }
+        Some(due) if now >= due => rotate_now().await.map(|_| ()),
+        Some(_) => Ok(()),
+    }
+}
+
+/// 定时轮换检查循环，任务被停止时退出
+pub async fn check_loop(ctx: TaskContext) {
+    loop {
+        let result = tick().await;
+        if let Err(e) = &result {
+            logger::log_warn(&format!("[Rotation] 定时轮换失败: {}", e));
+        }
+        ctx.record_run(result);
+        if !ctx.sleep(std::time::Duration::from_secs(CHECK_INTERVAL_SECS)).await {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{QuotaData, TokenData};
+
+    fn account(id: &str) -> Account {
+        let token = TokenData::new(String::new(), String::new(), 0, None, None, None);
+        Account::new(id.to_string(), format!("{}@example.com", id), token)
+    }
+
+    #[test]
+    fn test_next_candidate() {
+        let mut disabled = account("b");
+        disabled.disabled = true;
+        let mut forbidden = account("c");
+        let mut quota = QuotaData::new();
+        quota.is_forbidden = true;
+        forbidden.quota = Some(quota);
+        let accounts = vec![account("a"), disabled, forbidden, account("d")];
+
+        let all = RotationConfig::default();
+        assert_eq!(next_candidate(&accounts, &all, Some("a")).unwrap().id, "d");
+        assert_eq!(next_candidate(&accounts, &all, Some("d")).unwrap().id, "a");
+        assert_eq!(next_candidate(&accounts, &all, None).unwrap().id, "a");
+
+        let listed = RotationConfig {
+            candidates: vec!["d".to_string(), "missing".to_string(), "c".to_string()],
+            ..RotationConfig::default()
+        };
+        assert_eq!(next_candidate(&accounts, &listed, Some("a")).unwrap().id, "d");
+        assert!(next_candidate(&accounts, &listed, Some("d")).is_none());
+    }
+}
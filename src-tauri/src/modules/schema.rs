@@ -0,0 +1,308 @@
+//! 数据目录的 schema 版本与启动时迁移
+//!
+//! 数据目录中的 `schema_version` 文件记录数据格式版本。启动时按版本依次执行迁移，
+//! 修改前先把配置与账号文件复制到 `pre-migration-v<旧版本>-<时间>` 目录。
+//! 数据目录由更新版本的程序写入时拒绝启动，避免旧程序按默认值覆盖新字段。
+
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::models::{Account, AppConfig};
+
+use super::account::get_data_dir;
+use super::config::CONFIG_FILE;
+
+/// 当前程序使用的数据格式版本
+pub const SCHEMA_VERSION: u32 = 2;
+/// 版本标记文件名
+pub const SCHEMA_FILE: &str = "schema_version";
+/// 迁移前备份目录的前缀
+pub const PRE_MIGRATION_PREFIX: &str = "pre-migration-";
+/// 没有标记文件但已有数据时视为该版本 (引入版本标记之前的数据)
+const LEGACY_VERSION: u32 = 1;
+
+const ACCOUNTS_INDEX: &str = "accounts.json";
+const ACCOUNTS_DIR: &str = "accounts";
+
+/// 一次迁移：从 `from` 升级到 `from + 1`，返回修改的文件数
+struct Migration {
+    from: u32,
+    describe: &'static str,
+    run: fn(&Path) -> Result<usize, String>,
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: 1,
+    describe: "补全配置与账号文件中缺失的字段，合并旧版模型映射",
+    run: migrate_v1_fill_defaults,
+}];
+
+/// 迁移结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationReport {
+    pub from: u32,
+    pub to: u32,
+    /// 修改的文件数
+    pub files: usize,
+    /// 迁移前的备份目录 (未执行迁移时为空)
+    pub backup_dir: Option<PathBuf>,
+}
+
+/// 读取数据目录的 schema 版本；没有标记文件时按是否已有数据判断
+pub fn read_version(data_dir: &Path) -> Result<u32, String> {
+    match fs::read_to_string(data_dir.join(SCHEMA_FILE)) {
+        Ok(content) => content
+            .trim()
+            .parse()
+            .map_err(|_| format!("schema 版本文件内容无效: {:?}", content.trim())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let has_data = data_dir.join(CONFIG_FILE).exists()
+                || data_dir.join(ACCOUNTS_INDEX).exists()
+                || data_dir.join(ACCOUNTS_DIR).exists();
+            Ok(if has_data { LEGACY_VERSION } else { SCHEMA_VERSION })
+        }
+        Err(e) => Err(format!("读取 schema 版本失败: {}", e)),
+    }
+}
+
+fn write_version(data_dir: &Path, version: u32) -> Result<(), String> {
+    fs::write(data_dir.join(SCHEMA_FILE), format!("{}\n", version))
+        .map_err(|e| format!("写入 schema 版本失败: {}", e))
+}
+
+/// 标记新建的数据目录为当前版本
+pub(crate) fn mark_current(data_dir: &Path) -> Result<(), String> {
+    write_version(data_dir, SCHEMA_VERSION)
+}
+
+/// 启动时调用：检查版本并执行所需的迁移
+pub fn ensure_current() -> Result<MigrationReport, String> {
+    let report = migrate_dir(&get_data_dir()?)?;
+    if report.from != report.to {
+        tracing::info!(
+            "数据目录已从 schema v{} 迁移到 v{} (修改 {} 个文件，备份: {:?})",
+            report.from,
+            report.to,
+            report.files,
+            report.backup_dir
+        );
+    }
+    Ok(report)
+}
+
+fn migrate_dir(data_dir: &Path) -> Result<MigrationReport, String> {
+    let from = read_version(data_dir)?;
+    if from > SCHEMA_VERSION {
+        return Err(format!(
+            "数据目录 {} 由更新版本的程序写入 (schema v{}，当前程序支持 v{})，请升级程序后再启动",
+            data_dir.display(),
+            from,
+            SCHEMA_VERSION
+        ));
+    }
+
+    let mut report = MigrationReport { from, to: from, files: 0, backup_dir: None };
+    if from < SCHEMA_VERSION {
+        report.backup_dir = Some(backup_before_migration(data_dir, from)?);
+        for migration in MIGRATIONS.iter().filter(|m| m.from >= from) {
+            tracing::info!("执行数据迁移 v{} -> v{}: {}", migration.from, migration.from + 1, migration.describe);
+            report.files += (migration.run)(data_dir)
+                .map_err(|e| format!("数据迁移 v{} -> v{} 失败: {}", migration.from, migration.from + 1, e))?;
+            report.to = migration.from + 1;
+        }
+    }
+    // 新建的数据目录也写入标记
+    if report.to != from || !data_dir.join(SCHEMA_FILE).exists() {
+        write_version(data_dir, report.to)?;
+    }
+    Ok(report)
+}
+
+/// 迁移前复制配置、账号索引与账号目录
+fn backup_before_migration(data_dir: &Path, from: u32) -> Result<PathBuf, String> {
+    let backup = data_dir.join(format!(
+        "{}v{}-{}",
+        PRE_MIGRATION_PREFIX,
+        from,
+        chrono::Utc::now().format("%Y%m%d-%H%M%S")
+    ));
+    fs::create_dir_all(&backup).map_err(|e| format!("创建迁移备份目录失败: {}", e))?;
+    for name in [CONFIG_FILE, ACCOUNTS_INDEX] {
+        let source = data_dir.join(name);
+        if source.is_file() {
+            fs::copy(&source, backup.join(name)).map_err(|e| format!("备份 {} 失败: {}", name, e))?;
+        }
+    }
+    let accounts = data_dir.join(ACCOUNTS_DIR);
+    if accounts.is_dir() {
+        let target = backup.join(ACCOUNTS_DIR);
+        fs::create_dir_all(&target).map_err(|e| format!("创建迁移备份目录失败: {}", e))?;
+        for entry in fs::read_dir(&accounts).map_err(|e| format!("读取账号目录失败: {}", e))? {
+            let path = entry.map_err(|e| format!("读取账号目录失败: {}", e))?.path();
+            if path.is_file() {
+                if let Some(name) = path.file_name() {
+                    fs::copy(&path, target.join(name)).map_err(|e| format!("备份 {:?} 失败: {}", name, e))?;
+                }
+            }
+        }
+    }
+    Ok(backup)
+}
+
+/// 用 `defaults` 补全 `target` 中缺失的字段 (逐层递归对象，已有的值不变)，返回是否有修改
+fn fill_missing(target: &mut Value, defaults: &Value) -> bool {
+    let (Some(target), Some(defaults)) = (target.as_object_mut(), defaults.as_object()) else {
+        return false;
+    };
+    let mut changed = false;
+    for (key, default) in defaults {
+        match target.get_mut(key) {
+            Some(existing) => changed |= fill_missing(existing, default),
+            None => {
+                target.insert(key.clone(), default.clone());
+                changed = true;
+            }
+        }
+    }
+    changed
+}
+
+/// 将 JSON 文件按类型 `T` 解析再序列化，补全缺失字段后写回；返回是否写入
+fn fill_file<T>(path: &Path, prepare: impl FnOnce(&mut Value) -> bool) -> Result<bool, String>
+where
+    T: serde::de::DeserializeOwned + serde::Serialize,
+{
+    let content = fs::read_to_string(path).map_err(|e| format!("读取 {} 失败: {}", path.display(), e))?;
+    let mut value: Value =
+        serde_json::from_str(&content).map_err(|e| format!("解析 {} 失败: {}", path.display(), e))?;
+    let mut changed = prepare(&mut value);
+    let typed: T = serde_json::from_value(value.clone())
+        .map_err(|e| format!("转换 {} 失败: {}", path.display(), e))?;
+    let defaults = serde_json::to_value(&typed).map_err(|e| format!("序列化失败: {}", e))?;
+    changed |= fill_missing(&mut value, &defaults);
+    if changed {
+        let content = serde_json::to_string_pretty(&value).map_err(|e| format!("序列化失败: {}", e))?;
+        fs::write(path, content).map_err(|e| format!("写入 {} 失败: {}", path.display(), e))?;
+    }
+    Ok(changed)
+}
+
+/// v1 -> v2：配置与账号文件补全缺失字段，旧版 `anthropic_mapping` / `openai_mapping` 合并到 `custom_mapping`
+///
+/// 无法解析的文件保持原样，仅记录警告
+fn migrate_v1_fill_defaults(data_dir: &Path) -> Result<usize, String> {
+    let mut files = 0;
+
+    let config = data_dir.join(CONFIG_FILE);
+    if config.is_file() {
+        match fill_file::<AppConfig>(&config, super::config::migrate_legacy_mappings) {
+            Ok(changed) => files += changed as usize,
+            Err(e) => tracing::warn!("跳过配置迁移: {}", e),
+        }
+    }
+
+    let accounts = data_dir.join(ACCOUNTS_DIR);
+    if accounts.is_dir() {
+        for entry in fs::read_dir(&accounts).map_err(|e| format!("读取账号目录失败: {}", e))? {
+            let path = entry.map_err(|e| format!("读取账号目录失败: {}", e))?.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("json") {
+                continue;
+            }
+            match fill_file::<Account>(&path, |_| false) {
+                Ok(changed) => files += changed as usize,
+                Err(e) => tracing::warn!("跳过账号文件迁移: {}", e),
+            }
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ag-schema-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_fresh_dir_gets_marker() {
+        let dir = temp_dir();
+        let report = migrate_dir(&dir).unwrap();
+        assert_eq!((report.from, report.to, report.backup_dir), (SCHEMA_VERSION, SCHEMA_VERSION, None));
+        assert_eq!(read_version(&dir).unwrap(), SCHEMA_VERSION);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_refuses_newer_schema() {
+        let dir = temp_dir();
+        write_version(&dir, SCHEMA_VERSION + 1).unwrap();
+        let err = migrate_dir(&dir).unwrap_err();
+        assert!(err.contains("请升级程序"), "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_migrates_legacy_files() {
+        let dir = temp_dir();
+        let legacy_config = json!({
+            "language": "zh",
+            "theme": "system",
+            "auto_refresh": false,
+            "refresh_interval": 15,
+            "auto_sync": false,
+            "sync_interval": 5,
+            "proxy": {
+                "enabled": false,
+                "port": 8045,
+                "api_key": "sk-test",
+                "auto_start": false,
+                "anthropic_mapping": { "claude-3-opus": "gemini-3-pro", "claude-series": "x" }
+            }
+        });
+        fs::write(dir.join(CONFIG_FILE), legacy_config.to_string()).unwrap();
+        fs::create_dir_all(dir.join(ACCOUNTS_DIR)).unwrap();
+        let legacy_account = json!({
+            "id": "a1",
+            "email": "a@example.com",
+            "name": null,
+            "token": { "access_token": "at", "refresh_token": "rt", "expires_in": 3600, "expiry_timestamp": 0, "token_type": "Bearer" },
+            "quota": null,
+            "created_at": 1,
+            "last_used": 1,
+            "custom_field": "kept"
+        });
+        let account_path = dir.join(ACCOUNTS_DIR).join("a1.json");
+        fs::write(&account_path, legacy_account.to_string()).unwrap();
+        fs::write(dir.join(ACCOUNTS_DIR).join("broken.json"), "{").unwrap();
+
+        let report = migrate_dir(&dir).unwrap();
+        assert_eq!((report.from, report.to, report.files), (1, SCHEMA_VERSION, 2));
+        let backup = report.backup_dir.unwrap();
+        assert!(backup.join(CONFIG_FILE).exists());
+        assert!(backup.join(ACCOUNTS_DIR).join("a1.json").exists());
+        assert_eq!(read_version(&dir).unwrap(), SCHEMA_VERSION);
+
+        let config: Value = serde_json::from_str(&fs::read_to_string(dir.join(CONFIG_FILE)).unwrap()).unwrap();
+        assert!(config["proxy"].get("anthropic_mapping").is_none());
+        assert_eq!(config["proxy"]["custom_mapping"]["claude-3-opus"], "gemini-3-pro");
+        assert_eq!(config["language"], "zh");
+        assert_eq!(config["proxy"]["request_timeout"], 120);
+
+        let account: Value = serde_json::from_str(&fs::read_to_string(&account_path).unwrap()).unwrap();
+        assert_eq!(account["disabled"], false);
+        assert_eq!(account["total_requests"], 0);
+        assert_eq!(account["custom_field"], "kept");
+        assert_eq!(fs::read_to_string(dir.join(ACCOUNTS_DIR).join("broken.json")).unwrap(), "{");
+
+        // 再次启动不重复迁移
+        let again = migrate_dir(&dir).unwrap();
+        assert_eq!((again.from, again.to, again.backup_dir), (SCHEMA_VERSION, SCHEMA_VERSION, None));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,175 @@
+//! 启动时从种子文件导入账号，用于 CI 等临时部署
+//!
+//! 种子文件为 `[{"email": "...", "refresh_token": "..."}]` 形式的 JSON 数组，`email` 可省略。
+//! 文件只读不写；单个条目失败不影响其他条目，也不会中止启动。
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::TokenData;
+
+use super::{account, logger, oauth};
+
+/// 种子文件中的一个条目
+#[derive(Debug, Clone, Deserialize)]
+pub struct SeedEntry {
+    /// 仅用于日志标识，实际邮箱以 Token 查询结果为准
+    #[serde(default)]
+    pub email: Option<String>,
+    pub refresh_token: String,
+}
+
+/// 单个条目的处理结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SeedOutcome {
+    /// 已添加或更新账号
+    Imported,
+    /// Refresh Token 已存在，未做修改
+    Skipped,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SeedEntryResult {
+    /// 条目序号 (从 1 开始)
+    pub index: usize,
+    /// 导入成功时为账号邮箱，否则为条目中填写的邮箱
+    pub email: Option<String>,
+    pub outcome: SeedOutcome,
+    pub error: Option<String>,
+}
+
+/// 最近一次导入的汇总
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct SeedStatus {
+    /// 种子文件路径，未配置时为空
+    pub source: Option<String>,
+    /// 导入完成的 Unix 时间戳 (秒)
+    pub finished_at: Option<i64>,
+    pub imported: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub entries: Vec<SeedEntryResult>,
+    /// 读取或解析种子文件失败的原因
+    pub error: Option<String>,
+}
+
+static STATUS: Lazy<RwLock<SeedStatus>> = Lazy::new(|| RwLock::new(SeedStatus::default()));
+
+/// 最近一次导入的汇总
+pub fn status() -> SeedStatus {
+    STATUS.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// 解析种子文件内容
+pub fn parse_entries(content: &str) -> Result<Vec<SeedEntry>, String> {
+    let entries: Vec<SeedEntry> = serde_json::from_str(content)
+        .map_err(|e| format!("解析种子文件失败: {}", e))?;
+    if let Some(i) = entries.iter().position(|entry| entry.refresh_token.trim().is_empty()) {
+        return Err(format!("种子文件第 {} 个条目缺少 refresh_token", i + 1));
+    }
+    Ok(entries)
+}
+
+/// 刷新 Token、获取邮箱并写入账号，与手动添加账号的流程一致
+async fn add_from_refresh_token(refresh_token: &str) -> Result<String, String> {
+    let token_res = oauth::refresh_access_token(refresh_token).await?;
+    let user_info = oauth::get_user_info(&token_res.access_token).await?;
+    let token = TokenData::new(
+        token_res.access_token,
+        refresh_token.to_string(),
+        token_res.expires_in,
+        Some(user_info.email.clone()),
+        None,
+        None,
+    );
+    let account = account::upsert_account(user_info.email.clone(), user_info.get_display_name(), token)?;
+    Ok(account.email)
+}
+
+/// 读取种子文件并逐条导入，结果记录到 [`status`]；返回是否导入了新账号
+pub async fn seed_from_file(path: &Path) -> bool {
+    let mut summary = SeedStatus {
+        source: Some(path.display().to_string()),
+        ..Default::default()
+    };
+
+    let entries = std::fs::read_to_string(path)
+        .map_err(|e| format!("读取种子文件 {} 失败: {}", path.display(), e))
+        .and_then(|content| parse_entries(&content));
+    let entries = match entries {
+        Ok(entries) => entries,
+        Err(e) => {
+            logger::log_error(&e);
+            summary.error = Some(e);
+            summary.finished_at = Some(chrono::Utc::now().timestamp());
+            *STATUS.write().unwrap_or_else(|e| e.into_inner()) = summary;
+            return false;
+        }
+    };
+
+    let mut known: HashSet<String> = account::list_accounts()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|account| account.token.refresh_token)
+        .collect();
+
+    for (i, entry) in entries.into_iter().enumerate() {
+        let index = i + 1;
+        let label = entry.email.clone().unwrap_or_else(|| format!("#{}", index));
+        let refresh_token = entry.refresh_token.trim().to_string();
+
+        let result = if known.contains(&refresh_token) {
+            logger::log_info(&format!("种子账号 {} 的 Refresh Token 已存在，跳过", label));
+            summary.skipped += 1;
+            SeedEntryResult { index, email: entry.email, outcome: SeedOutcome::Skipped, error: None }
+        } else {
+            match add_from_refresh_token(&refresh_token).await {
+                Ok(email) => {
+                    logger::log_info(&format!("种子账号 {} 导入成功: {}", label, email));
+                    known.insert(refresh_token);
+                    summary.imported += 1;
+                    SeedEntryResult { index, email: Some(email), outcome: SeedOutcome::Imported, error: None }
+                }
+                Err(e) => {
+                    logger::log_warn(&format!("种子账号 {} 导入失败: {}", label, e));
+                    summary.failed += 1;
+                    SeedEntryResult { index, email: entry.email, outcome: SeedOutcome::Failed, error: Some(e) }
+                }
+            }
+        };
+        summary.entries.push(result);
+    }
+
+    logger::log_info(&format!(
+        "种子账号导入完成: 成功 {}，跳过 {}，失败 {}",
+        summary.imported, summary.skipped, summary.failed
+    ));
+    let imported = summary.imported > 0;
+    summary.finished_at = Some(chrono::Utc::now().timestamp());
+    *STATUS.write().unwrap_or_else(|e| e.into_inner()) = summary;
+    imported
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_entries() {
+        let entries = parse_entries(r#"[{"email":"a@example.com","refresh_token":"1//a"},{"refresh_token":"1//b"}]"#).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].email.as_deref(), Some("a@example.com"));
+        assert_eq!(entries[1].email, None);
+
+        assert!(parse_entries(r#"{"refresh_token":"1//a"}"#).is_err());
+        assert!(parse_entries(r#"[{"email":"a@example.com"}]"#).is_err());
+        assert!(parse_entries(r#"[{"refresh_token":"  "}]"#).unwrap_err().contains("第 1 个"));
+    }
+}
@@ -0,0 +1,384 @@
+//! 独立服务端自更新：从 GitHub Release 下载与当前平台匹配的二进制，校验 SHA256 后原地替换
+
+use futures::StreamExt;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use utoipa::ToSchema;
+
+use crate::modules::logger;
+use crate::modules::update_checker::{self, GitHubAsset};
+
+const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// 编译目标三元组，由 build.rs 注入 (如 `x86_64-unknown-linux-gnu`)
+pub const TARGET: &str = env!("BUILD_TARGET");
+
+/// Release 中独立服务端二进制的文件名前缀，完整名称为 `antigravity-server-<目标三元组>[.exe]`
+const ASSET_PREFIX: &str = "antigravity-server-";
+
+/// 汇总多个资产校验和的文件名
+const CHECKSUM_FILES: &[&str] = &["SHA256SUMS", "SHA256SUMS.txt", "checksums.txt", "sha256sums.txt"];
+
+/// 待执行的更新
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UpdatePlan {
+    pub current_version: String,
+    pub latest_version: String,
+    pub target: String,
+    pub asset_name: String,
+    pub download_url: String,
+    pub size: u64,
+    pub checksum_url: String,
+    /// 将被替换的可执行文件
+    pub install_path: String,
+}
+
+/// 更新进度，通过 SSE 推送
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum UpdateProgress {
+    Downloading { downloaded: u64, total: Option<u64> },
+    Verifying,
+    Installing,
+    Done { path: String },
+    Failed { error: String },
+}
+
+/// 按配置的更新通道查询最新 Release，有新版本且存在匹配当前平台的资产时返回更新计划
+pub async fn plan_update() -> Result<Option<UpdatePlan>, String> {
+    let release = update_checker::fetch_latest_release().await?;
+
+    let latest_version = release.tag_name.trim_start_matches('v').to_string();
+    if !update_checker::compare_versions(&latest_version, CURRENT_VERSION) {
+        return Ok(None);
+    }
+
+    let asset = select_asset(&release.assets, TARGET)
+        .ok_or_else(|| format!("版本 {} 未提供 {}", latest_version, asset_name(TARGET)))?;
+    let checksum = select_checksum_asset(&release.assets, &asset.name)
+        .ok_or_else(|| format!("版本 {} 未提供校验和文件", latest_version))?;
+
+    let install_path = std::env::current_exe()
+        .map_err(|e| format!("获取当前可执行文件路径失败: {}", e))?;
+
+    Ok(Some(UpdatePlan {
+        current_version: CURRENT_VERSION.to_string(),
+        latest_version,
+        target: TARGET.to_string(),
+        asset_name: asset.name.clone(),
+        download_url: asset.browser_download_url.clone(),
+        size: asset.size,
+        checksum_url: checksum.browser_download_url.clone(),
+        install_path: install_path.to_string_lossy().to_string(),
+    }))
+}
+
+/// 下载、校验并替换当前可执行文件，返回安装路径
+pub async fn apply_update(
+    plan: &UpdatePlan,
+    progress: impl Fn(UpdateProgress),
+) -> Result<PathBuf, String> {
+    let result = download_and_install(plan, &progress).await;
+    match &result {
+        Ok(path) => progress(UpdateProgress::Done {
+            path: path.to_string_lossy().to_string(),
+        }),
+        Err(e) => {
+            logger::log_error(&format!("自更新失败: {}", e));
+            progress(UpdateProgress::Failed { error: e.clone() });
+        }
+    }
+    result
+}
+
+async fn download_and_install(
+    plan: &UpdatePlan,
+    progress: &impl Fn(UpdateProgress),
+) -> Result<PathBuf, String> {
+    let client = crate::utils::http::create_client(600);
+
+    // 1. 先取校验和，避免下载完才发现缺失
+    let checksums = client
+        .get(&plan.checksum_url)
+        .header("User-Agent", "Antigravity-Tools")
+        .send()
+        .await
+        .map_err(|e| format!("下载校验和失败: {}", e))?
+        .error_for_status()
+        .map_err(|e| format!("下载校验和失败: {}", e))?
+        .text()
+        .await
+        .map_err(|e| format!("读取校验和失败: {}", e))?;
+    let expected = parse_checksum(&checksums, &plan.asset_name)
+        .ok_or_else(|| format!("校验和文件中没有 {} 的记录", plan.asset_name))?;
+
+    // 2. 流式下载到内存并计算哈希
+    logger::log_info(&format!("正在下载 {}", plan.download_url));
+    let response = client
+        .get(&plan.download_url)
+        .header("User-Agent", "Antigravity-Tools")
+        .send()
+        .await
+        .map_err(|e| format!("下载安装包失败: {}", e))?
+        .error_for_status()
+        .map_err(|e| format!("下载安装包失败: {}", e))?;
+    let total = response.content_length();
+
+    let mut hasher = Sha256::new();
+    let mut data = Vec::with_capacity(total.unwrap_or(plan.size) as usize);
+    let mut stream = response.bytes_stream();
+    let mut last_reported = 0u64;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| format!("下载安装包失败: {}", e))?;
+        hasher.update(&chunk);
+        data.extend_from_slice(&chunk);
+
+        // 每 1MB 推送一次进度
+        let downloaded = data.len() as u64;
+        if downloaded - last_reported >= 1024 * 1024 || Some(downloaded) == total {
+            last_reported = downloaded;
+            progress(UpdateProgress::Downloading { downloaded, total });
+        }
+    }
+
+    // 3. 校验
+    progress(UpdateProgress::Verifying);
+    let actual = format!("{:x}", hasher.finalize());
+    if !actual.eq_ignore_ascii_case(&expected) {
+        return Err(format!("SHA256 校验失败: 期望 {}, 实际 {}", expected, actual));
+    }
+    if !is_executable(&data) {
+        return Err(format!("{} 不是可执行文件，拒绝替换", plan.asset_name));
+    }
+
+    // 4. 写入同目录临时文件后替换
+    progress(UpdateProgress::Installing);
+    let install_path = PathBuf::from(&plan.install_path);
+    install(&data, &install_path)?;
+
+    logger::log_info(&format!(
+        "已更新至 v{}，重启后生效: {}",
+        plan.latest_version,
+        install_path.display()
+    ));
+    Ok(install_path)
+}
+
+/// 以相同参数启动安装路径上的新版本
+///
+/// Unix 下直接 exec 替换进程映像，Windows 下启动新进程后退出。
+/// 必须传入 [`apply_update`] 返回的安装路径：替换后 Linux 的 `current_exe()`
+/// 指向已删除的旧文件 (`<路径> (deleted)`)，无法再执行。
+pub fn restart(install_path: &Path) -> Result<(), String> {
+    let mut command = restart_command(install_path);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let err = command.exec();
+        Err(format!("重启失败: {}", err))
+    }
+
+    #[cfg(not(unix))]
+    {
+        command.spawn().map_err(|e| format!("重启失败: {}", e))?;
+        std::process::exit(0);
+    }
+}
+
+fn restart_command(install_path: &Path) -> std::process::Command {
+    let mut command = std::process::Command::new(install_path);
+    command.args(std::env::args_os().skip(1));
+    command
+}
+
+/// 当前平台对应的服务端二进制资产名
+fn asset_name(target: &str) -> String {
+    let suffix = if target.contains("windows") { ".exe" } else { "" };
+    format!("{}{}{}", ASSET_PREFIX, target, suffix)
+}
+
+/// 只选择名称完全匹配的裸二进制；压缩包、安装包 (.tar.gz/.zip/.dmg 等) 不能直接替换可执行文件
+fn select_asset<'a>(assets: &'a [GitHubAsset], target: &str) -> Option<&'a GitHubAsset> {
+    let expected = asset_name(target);
+    assets.iter().find(|asset| asset.name.eq_ignore_ascii_case(&expected))
+}
+
+/// 是否为可执行文件格式 (ELF / Mach-O / PE)，防止把其他文件写到可执行文件位置
+fn is_executable(data: &[u8]) -> bool {
+    const MAGICS: &[&[u8]] = &[
+        b"\x7fELF",
+        b"MZ",
+        &[0xcf, 0xfa, 0xed, 0xfe],
+        &[0xce, 0xfa, 0xed, 0xfe],
+        // 通用 (fat) Mach-O
+        &[0xca, 0xfe, 0xba, 0xbe],
+    ];
+    MAGICS.iter().any(|magic| data.starts_with(magic))
+}
+
+/// 优先使用 `<asset>.sha256`，否则回退到汇总校验和文件
+fn select_checksum_asset<'a>(assets: &'a [GitHubAsset], asset_name: &str) -> Option<&'a GitHubAsset> {
+    let sidecar = format!("{}.sha256", asset_name);
+    assets
+        .iter()
+        .find(|asset| asset.name == sidecar)
+        .or_else(|| {
+            assets
+                .iter()
+                .find(|asset| CHECKSUM_FILES.iter().any(|name| asset.name.eq_ignore_ascii_case(name)))
+        })
+}
+
+/// 解析 `sha256sum` 格式 (`<hash>  <name>`) 的校验和文件；单行且无文件名时直接作为哈希
+fn parse_checksum(content: &str, asset_name: &str) -> Option<String> {
+    let lines: Vec<&str> = content.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+
+    for line in &lines {
+        let mut parts = line.split_whitespace();
+        let (Some(hash), name) = (parts.next(), parts.next()) else {
+            continue;
+        };
+        if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            continue;
+        }
+        match name {
+            // 二进制模式下文件名前带 `*`
+            Some(name) if name.trim_start_matches('*') == asset_name => return Some(hash.to_lowercase()),
+            None if lines.len() == 1 => return Some(hash.to_lowercase()),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// 写入同目录的 `.new` 文件后替换安装路径，失败时清理临时文件
+fn install(data: &[u8], install_path: &Path) -> Result<(), String> {
+    let staged = staged_path(install_path, "new");
+    std::fs::write(&staged, data).map_err(|e| format!("写入新版本失败: {}", e))?;
+    if let Err(e) = replace_executable(&staged, install_path) {
+        let _ = std::fs::remove_file(&staged);
+        return Err(e);
+    }
+    Ok(())
+}
+
+fn staged_path(install_path: &Path, suffix: &str) -> PathBuf {
+    let mut name = install_path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}", suffix));
+    install_path.with_file_name(name)
+}
+
+/// Unix 下同目录 rename 为原子操作，正在运行的旧进程不受影响
+#[cfg(unix)]
+fn replace_executable(staged: &Path, install_path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::set_permissions(staged, std::fs::Permissions::from_mode(0o755))
+        .map_err(|e| format!("设置可执行权限失败: {}", e))?;
+    std::fs::rename(staged, install_path).map_err(|e| format!("替换可执行文件失败: {}", e))
+}
+
+/// Windows 不能覆盖运行中的 exe，但可以重命名：先把旧文件移到 `.old`，再放入新文件
+#[cfg(not(unix))]
+fn replace_executable(staged: &Path, install_path: &Path) -> Result<(), String> {
+    let backup = staged_path(install_path, "old");
+    let _ = std::fs::remove_file(&backup);
+    std::fs::rename(install_path, &backup).map_err(|e| format!("备份旧版本失败: {}", e))?;
+    if let Err(e) = std::fs::rename(staged, install_path) {
+        let _ = std::fs::rename(&backup, install_path);
+        return Err(format!("替换可执行文件失败: {}", e));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn asset(name: &str) -> GitHubAsset {
+        GitHubAsset {
+            name: name.to_string(),
+            browser_download_url: format!("https://example.com/{}", name),
+            size: 0,
+        }
+    }
+
+    #[test]
+    fn test_select_asset_and_checksum() {
+        let assets = vec![
+            asset("Antigravity.Tools_x86_64-unknown-linux-gnu.tar.gz"),
+            asset("antigravity-server-aarch64-apple-darwin"),
+            asset("antigravity-server-x86_64-unknown-linux-gnu.sha256"),
+            asset("antigravity-server-x86_64-unknown-linux-gnu"),
+            asset("antigravity-server-x86_64-pc-windows-msvc.zip"),
+            asset("SHA256SUMS"),
+        ];
+
+        let selected = select_asset(&assets, "x86_64-unknown-linux-gnu").unwrap();
+        assert_eq!(selected.name, "antigravity-server-x86_64-unknown-linux-gnu");
+        assert_eq!(
+            select_checksum_asset(&assets, &selected.name).unwrap().name,
+            "antigravity-server-x86_64-unknown-linux-gnu.sha256"
+        );
+        assert_eq!(
+            select_checksum_asset(&assets, "antigravity-server-aarch64-apple-darwin").unwrap().name,
+            "SHA256SUMS"
+        );
+        // 只有压缩包时不选择
+        assert!(select_asset(&assets, "x86_64-pc-windows-msvc").is_none());
+        let windows = vec![asset("antigravity-server-x86_64-pc-windows-msvc.exe")];
+        assert!(select_asset(&windows, "x86_64-pc-windows-msvc").is_some());
+    }
+
+    #[test]
+    fn test_is_executable() {
+        assert!(is_executable(b"\x7fELF\x02\x01"));
+        assert!(is_executable(b"MZ\x90\x00"));
+        assert!(is_executable(&[0xcf, 0xfa, 0xed, 0xfe, 0x07]));
+        // gzip 与 zip
+        assert!(!is_executable(&[0x1f, 0x8b, 0x08]));
+        assert!(!is_executable(b"PK\x03\x04"));
+    }
+
+    #[test]
+    fn test_parse_checksum() {
+        let a = "a".repeat(64);
+        let b = "B".repeat(64);
+        let sums = format!("{}  server-linux\n{} *server-macos\n", a, b);
+        assert_eq!(parse_checksum(&sums, "server-linux"), Some(a.clone()));
+        assert_eq!(parse_checksum(&sums, "server-macos"), Some("b".repeat(64)));
+        assert_eq!(parse_checksum(&sums, "server-windows.exe"), None);
+
+        assert_eq!(parse_checksum(&format!("{}\n", a), "anything"), Some(a));
+        assert_eq!(parse_checksum("not-a-hash server-linux", "server-linux"), None);
+    }
+
+    #[test]
+    fn test_restart_runs_install_path() {
+        let install_path = std::env::temp_dir().join("antigravity-server-restart-test");
+        let command = restart_command(&install_path);
+        assert_eq!(Path::new(command.get_program()), install_path);
+        assert_ne!(Path::new(command.get_program()), std::env::current_exe().unwrap());
+    }
+
+    #[test]
+    fn test_install_replaces_and_cleans_up_staged_file() {
+        let dir = std::env::temp_dir().join(format!("antigravity-self-update-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let install_path = dir.join("antigravity-server");
+        std::fs::write(&install_path, b"old").unwrap();
+
+        install(b"new", &install_path).unwrap();
+        assert_eq!(std::fs::read(&install_path).unwrap(), b"new");
+        assert!(!staged_path(&install_path, "new").exists());
+
+        // 安装路径是目录时替换失败，临时文件不应残留
+        let blocked = dir.join("blocked");
+        std::fs::create_dir_all(blocked.join("child")).unwrap();
+        assert!(install(b"new", &blocked).is_err());
+        assert!(!staged_path(&blocked, "new").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,183 @@
+//! 首次运行引导：各步骤是否完成均根据当前数据判断 (不记录完成标记)，
+//! 之后删除账号或配置文件时对应步骤会重新变为未完成
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use utoipa::ToSchema;
+
+use crate::models::AppConfig;
+use crate::modules::config::CONFIG_FILE;
+use crate::modules::schema::SCHEMA_FILE;
+use crate::proxy::{ProxyAuthMode, ProxyConfig};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SetupStep {
+    /// 数据目录已初始化 (写入数据格式版本) 且可写
+    DataDir,
+    /// 至少有一个账号
+    Account,
+    /// 配置文件中已保存反代 API 密钥
+    ApiKey,
+    /// 已保存通过校验的反代配置
+    ProxyConfig,
+}
+
+/// 引导步骤的建议顺序
+pub const STEPS: [SetupStep; 4] = [SetupStep::DataDir, SetupStep::Account, SetupStep::ApiKey, SetupStep::ProxyConfig];
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SetupStatus {
+    pub data_dir: bool,
+    pub account: bool,
+    pub api_key: bool,
+    pub proxy_config: bool,
+    /// 全部步骤均已完成
+    pub complete: bool,
+    /// 按建议顺序排在最前的未完成步骤
+    pub next_step: Option<SetupStep>,
+    pub account_count: usize,
+    /// 反代服务是否正在运行
+    pub proxy_running: bool,
+}
+
+impl SetupStatus {
+    pub fn is_done(&self, step: SetupStep) -> bool {
+        match step {
+            SetupStep::DataDir => self.data_dir,
+            SetupStep::Account => self.account,
+            SetupStep::ApiKey => self.api_key,
+            SetupStep::ProxyConfig => self.proxy_config,
+        }
+    }
+}
+
+fn has_api_key(proxy: &ProxyConfig) -> bool {
+    !proxy.api_key.trim().is_empty() || proxy.api_keys.iter().any(|k| k.enabled && !k.key.trim().is_empty())
+}
+
+/// 由数据目录状态、账号数与已保存的配置 (配置文件不存在时为 None) 得出各步骤状态
+fn evaluate(data_dir: bool, account_count: usize, saved: Option<&AppConfig>) -> SetupStatus {
+    let mut status = SetupStatus {
+        data_dir,
+        account: account_count > 0,
+        api_key: saved.is_some_and(|config| has_api_key(&config.proxy)),
+        proxy_config: saved.is_some_and(|config| config.proxy.validate("").is_empty()),
+        complete: false,
+        next_step: None,
+        account_count,
+        proxy_running: false,
+    };
+    status.next_step = STEPS.into_iter().find(|step| !status.is_done(*step));
+    status.complete = status.next_step.is_none();
+    status
+}
+
+fn data_dir_ready(data_dir: &Path) -> bool {
+    data_dir.join(SCHEMA_FILE).exists() && crate::modules::health::probe_writable(data_dir)
+}
+
+/// 读取已保存的配置；配置文件不存在时返回 None (此时加载到的是每次都重新生成密钥的默认配置)
+fn saved_config(data_dir: &Path) -> Result<Option<AppConfig>, String> {
+    if !data_dir.join(CONFIG_FILE).exists() {
+        return Ok(None);
+    }
+    crate::modules::config::load_app_config().map(Some)
+}
+
+/// 当前引导状态 (`proxy_running` 由调用方填写)
+pub fn status() -> Result<SetupStatus, String> {
+    let data_dir = crate::modules::account::get_data_dir()?;
+    let account_count = crate::modules::account::list_accounts()?.len();
+    let saved = saved_config(&data_dir)?;
+    Ok(evaluate(data_dir_ready(&data_dir), account_count, saved.as_ref()))
+}
+
+/// 快速配置使用的反代配置：保留端口与密钥，其余恢复默认；
+/// 服务端部署默认允许局域网访问、除健康检查外要求鉴权，并随服务启动
+pub fn quickstart_proxy_config(current: &ProxyConfig) -> ProxyConfig {
+    ProxyConfig {
+        port: current.port,
+        api_key: current.api_key.clone(),
+        api_keys: current.api_keys.clone(),
+        allow_lan_access: true,
+        auth_mode: ProxyAuthMode::AllExceptHealth,
+        auto_start: true,
+        ..ProxyConfig::default()
+    }
+}
+
+/// 完成指定步骤，已完成的步骤保持不变；返回是否修改了配置
+///
+/// 账号需通过 OAuth 登录或导入添加，无法自动完成。
+pub fn complete_step(step: SetupStep) -> Result<bool, String> {
+    let data_dir = crate::modules::account::get_data_dir()?;
+    match step {
+        SetupStep::DataDir => {
+            if data_dir_ready(&data_dir) {
+                return Ok(false);
+            }
+            crate::modules::schema::ensure_current()?;
+            if !crate::modules::health::probe_writable(&data_dir) {
+                return Err(format!("数据目录不可写: {}", data_dir.display()));
+            }
+            Ok(false)
+        }
+        SetupStep::Account => {
+            if crate::modules::account::list_accounts()?.is_empty() {
+                return Err("请先通过 OAuth 登录、Refresh Token 或导入添加账号".to_string());
+            }
+            Ok(false)
+        }
+        SetupStep::ApiKey | SetupStep::ProxyConfig => {
+            let saved = saved_config(&data_dir)?;
+            let done = match step {
+                SetupStep::ApiKey => saved.as_ref().is_some_and(|config| has_api_key(&config.proxy)),
+                _ => saved.as_ref().is_some_and(|config| config.proxy.validate("").is_empty()),
+            };
+            if done {
+                return Ok(false);
+            }
+            crate::modules::config::update_app_config(|config| {
+                // 首次保存或反代配置无效时使用快速配置的默认值
+                if saved.is_none() || step == SetupStep::ProxyConfig {
+                    config.proxy = quickstart_proxy_config(&config.proxy);
+                }
+                if !has_api_key(&config.proxy) {
+                    config.proxy.api_key = crate::proxy::config::new_api_key();
+                }
+                Ok(())
+            })?;
+            Ok(true)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate_follows_saved_state() {
+        let fresh = evaluate(true, 0, None);
+        assert!(!fresh.api_key && !fresh.proxy_config && !fresh.complete);
+        assert_eq!(fresh.next_step, Some(SetupStep::Account));
+
+        let mut config = AppConfig::new();
+        config.proxy = quickstart_proxy_config(&config.proxy);
+        let done = evaluate(true, 2, Some(&config));
+        assert!(done.complete);
+        assert_eq!(done.next_step, None);
+
+        // 密钥被清空后鉴权配置不再有效，两个步骤都回到未完成
+        config.proxy.api_key.clear();
+        let cleared = evaluate(true, 2, Some(&config));
+        assert!(!cleared.api_key && !cleared.proxy_config);
+        assert_eq!(cleared.next_step, Some(SetupStep::ApiKey));
+
+        // 账号被删除后重新提示添加账号
+        config.proxy.api_key = crate::proxy::config::new_api_key();
+        assert_eq!(evaluate(true, 0, Some(&config)).next_step, Some(SetupStep::Account));
+        assert_eq!(evaluate(false, 1, Some(&config)).next_step, Some(SetupStep::DataDir));
+    }
+}
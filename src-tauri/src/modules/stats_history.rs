@@ -0,0 +1,591 @@
+//! 按天汇总的请求统计，持久化到 stats_history.json，重启后继续累计
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use utoipa::ToSchema;
+
+use crate::proxy::monitor::ProxyRequestLog;
+
+use super::account::get_data_dir;
+
+const STATS_HISTORY_FILE: &str = "stats_history.json";
+/// 最多保留的天数
+const MAX_HISTORY_DAYS: usize = 400;
+
+/// 串行化文件读写 (定时落盘与跨天落盘可能同时发生)
+static FILE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// 单日汇总
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DailyStats {
+    /// 本地日期 (YYYY-MM-DD)
+    pub date: String,
+    pub requests: u64,
+    pub errors: u64,
+    #[serde(default)]
+    pub input_tokens: u64,
+    #[serde(default)]
+    pub output_tokens: u64,
+    /// 按模型 (路由后的实际模型) 统计的请求数
+    #[serde(default)]
+    pub models: BTreeMap<String, u64>,
+    /// 按账号与本地小时 (0-23) 统计的请求数，只记录非零小时
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub account_hours: BTreeMap<String, BTreeMap<u8, u64>>,
+    /// 按账号累计的 Token 与估算费用
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub cost_by_account: BTreeMap<String, CostTotal>,
+    /// 按 API 密钥名称累计的 Token 与估算费用
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub cost_by_key: BTreeMap<String, CostTotal>,
+    /// 按模型 (路由后的实际模型) 累计的 Token 与估算费用
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub cost_by_model: BTreeMap<String, CostTotal>,
+}
+
+/// 缺少账号或 API 密钥时使用的分组名
+pub const UNATTRIBUTED: &str = "(none)";
+
+/// 一个分组的 Token 与估算费用累计
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct CostTotal {
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// 有 Token 用量但模型没有单价的请求数，其费用未计入
+    #[serde(default)]
+    pub unpriced_requests: u64,
+    /// 按货币累计的费用
+    #[serde(default)]
+    pub costs: BTreeMap<String, f64>,
+}
+
+impl CostTotal {
+    fn add(&mut self, log: &ProxyRequestLog) {
+        self.requests += 1;
+        self.input_tokens += log.input_tokens.unwrap_or(0) as u64;
+        self.output_tokens += log.output_tokens.unwrap_or(0) as u64;
+        match (log.cost, &log.currency) {
+            (Some(cost), Some(currency)) => *self.costs.entry(currency.clone()).or_default() += cost,
+            _ if log.input_tokens.is_some() || log.output_tokens.is_some() => self.unpriced_requests += 1,
+            _ => {}
+        }
+    }
+
+    fn merge(&mut self, other: &CostTotal) {
+        self.requests += other.requests;
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.unpriced_requests += other.unpriced_requests;
+        for (currency, cost) in &other.costs {
+            *self.costs.entry(currency.clone()).or_default() += cost;
+        }
+    }
+}
+
+impl DailyStats {
+    pub fn new(date: String) -> Self {
+        Self { date, ..Default::default() }
+    }
+
+    pub fn record(&mut self, log: &ProxyRequestLog) {
+        self.requests += 1;
+        if log.status < 200 || log.status >= 400 {
+            self.errors += 1;
+        }
+        self.input_tokens += log.input_tokens.unwrap_or(0) as u64;
+        self.output_tokens += log.output_tokens.unwrap_or(0) as u64;
+        if let Some(model) = log.mapped_model.as_ref().or(log.model.as_ref()) {
+            *self.models.entry(model.clone()).or_default() += 1;
+        }
+        if let Some(email) = &log.account_email {
+            let hours = self.account_hours.entry(email.clone()).or_default();
+            *hours.entry(hour_of(log.timestamp)).or_default() += 1;
+        }
+
+        let account = log.account_email.as_deref().unwrap_or(UNATTRIBUTED);
+        self.cost_by_account.entry(account.to_string()).or_default().add(log);
+        let key = log.api_key_name.as_deref().unwrap_or(UNATTRIBUTED);
+        self.cost_by_key.entry(key.to_string()).or_default().add(log);
+        let model = log.mapped_model.as_deref().or(log.model.as_deref()).unwrap_or(UNATTRIBUTED);
+        self.cost_by_model.entry(model.to_string()).or_default().add(log);
+    }
+
+    fn costs(&self, group_by: CostGroupBy) -> &BTreeMap<String, CostTotal> {
+        match group_by {
+            CostGroupBy::Account => &self.cost_by_account,
+            CostGroupBy::Key => &self.cost_by_key,
+            CostGroupBy::Model => &self.cost_by_model,
+        }
+    }
+}
+
+/// 费用报表的分组方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CostGroupBy {
+    Account,
+    Key,
+    #[default]
+    Model,
+}
+
+/// 费用报表中的一行
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct CostRow {
+    /// 账号邮箱、API 密钥名称或模型名，缺失时为 `(none)`
+    pub name: String,
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub unpriced_requests: u64,
+    /// 估算费用；没有任何请求命中单价，或混用多种货币时为空 (此时见 `costs`)
+    pub cost: Option<f64>,
+    pub currency: Option<String>,
+    /// 按货币的估算费用
+    pub costs: BTreeMap<String, f64>,
+}
+
+/// 截至今天的最近若干个自然日的费用报表
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct CostReport {
+    pub group_by: CostGroupBy,
+    /// 窗口首日 (本地日期 YYYY-MM-DD，含)
+    pub start_date: String,
+    /// 窗口末日 (本地日期，含，即今天)
+    pub end_date: String,
+    pub days: usize,
+    /// 窗口内没有汇总记录的日期 (当天未开启监控)
+    pub missing_dates: Vec<String>,
+    /// 按货币的费用合计
+    pub totals: BTreeMap<String, f64>,
+    pub unpriced_requests: u64,
+    /// 按费用降序，无费用的行排在最后
+    pub rows: Vec<CostRow>,
+}
+
+/// 账号在热力图窗口内的小时请求数
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct AccountHeatmap {
+    pub email: String,
+    /// 窗口内的总请求数
+    pub total: u64,
+    /// 非零格子的序号 (天序号 * 24 + 本地小时，天序号从 start_date 起算)，升序
+    pub hours: Vec<u32>,
+    /// 与 `hours` 一一对应的请求数
+    pub counts: Vec<u64>,
+}
+
+/// 按账号的小时请求热力图 (共 days * 24 格，未列出的格子为 0)
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct UsageHeatmap {
+    /// 窗口首日 (本地日期 YYYY-MM-DD，含)
+    pub start_date: String,
+    /// 窗口末日 (本地日期，含，即今天)
+    pub end_date: String,
+    /// 窗口起点 (首日本地零点，毫秒时间戳)
+    pub start_ms: i64,
+    /// 窗口终点 (末日次日本地零点，毫秒时间戳，不含)
+    pub end_ms: i64,
+    pub days: usize,
+    /// 窗口内没有汇总记录的日期 (当天未开启监控)，这些格子均为 0
+    pub missing_dates: Vec<String>,
+    /// 按总请求数降序
+    pub accounts: Vec<AccountHeatmap>,
+}
+
+/// 毫秒时间戳对应的本地日期
+pub fn date_of(timestamp_ms: i64) -> String {
+    use chrono::TimeZone;
+    chrono::Local
+        .timestamp_millis_opt(timestamp_ms)
+        .single()
+        .unwrap_or_else(chrono::Local::now)
+        .format("%Y-%m-%d")
+        .to_string()
+}
+
+/// 毫秒时间戳对应的本地小时 (0-23)
+fn hour_of(timestamp_ms: i64) -> u8 {
+    use chrono::{TimeZone, Timelike};
+    chrono::Local
+        .timestamp_millis_opt(timestamp_ms)
+        .single()
+        .unwrap_or_else(chrono::Local::now)
+        .hour() as u8
+}
+
+/// 本地日期零点的毫秒时间戳
+fn local_midnight_ms(date: chrono::NaiveDate) -> i64 {
+    use chrono::TimeZone;
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+    chrono::Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map(|t| t.timestamp_millis())
+        .unwrap_or_else(|| midnight.and_utc().timestamp_millis())
+}
+
+pub fn today() -> String {
+    chrono::Local::now().format("%Y-%m-%d").to_string()
+}
+
+fn history_path() -> Result<PathBuf, String> {
+    Ok(get_data_dir()?.join(STATS_HISTORY_FILE))
+}
+
+fn read_history() -> Result<BTreeMap<String, DailyStats>, String> {
+    let path = history_path()?;
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let content = std::fs::read_to_string(&path).map_err(|e| format!("读取统计历史失败: {}", e))?;
+    let days: Vec<DailyStats> = serde_json::from_str(&content).map_err(|e| format!("解析统计历史失败: {}", e))?;
+    Ok(days.into_iter().map(|day| (day.date.clone(), day)).collect())
+}
+
+fn write_history(history: &BTreeMap<String, DailyStats>) -> Result<(), String> {
+    let path = history_path()?;
+    let days: Vec<&DailyStats> = history.values().collect();
+    let content = serde_json::to_string(&days).map_err(|e| format!("序列化统计历史失败: {}", e))?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, content).map_err(|e| format!("保存统计历史失败: {}", e))?;
+    std::fs::rename(&tmp, &path).map_err(|e| format!("保存统计历史失败: {}", e))
+}
+
+/// 写入 (覆盖) 某一天的汇总，并淘汰过旧的记录
+fn upsert(history: &mut BTreeMap<String, DailyStats>, day: DailyStats) {
+    history.insert(day.date.clone(), day);
+    while history.len() > MAX_HISTORY_DAYS {
+        history.pop_first();
+    }
+}
+
+/// 最近 `days` 条记录 (按日期先后)
+fn tail(history: &BTreeMap<String, DailyStats>, days: usize) -> Vec<DailyStats> {
+    let skip = history.len().saturating_sub(days);
+    history.values().skip(skip).cloned().collect()
+}
+
+/// 读取某一天已落盘的汇总，不存在时返回空记录
+pub fn load_day(date: &str) -> DailyStats {
+    let _guard = FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    match read_history() {
+        Ok(mut history) => history.remove(date).unwrap_or_else(|| DailyStats::new(date.to_string())),
+        Err(e) => {
+            tracing::warn!("{}", e);
+            DailyStats::new(date.to_string())
+        }
+    }
+}
+
+pub fn save_day(day: &DailyStats) -> Result<(), String> {
+    let _guard = FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut history = read_history().unwrap_or_default();
+    upsert(&mut history, day.clone());
+    write_history(&history)
+}
+
+/// 最近 `days` 天的汇总 (不含按账号的小时明细)；`live` 为内存中尚未落盘的当天数据
+pub fn recent(days: usize, live: Option<DailyStats>) -> Result<Vec<DailyStats>, String> {
+    let _guard = FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut history = read_history()?;
+    if let Some(day) = live {
+        upsert(&mut history, day);
+    }
+    let mut days = tail(&history, days);
+    for day in &mut days {
+        day.account_hours.clear();
+        day.cost_by_account.clear();
+        day.cost_by_key.clear();
+        day.cost_by_model.clear();
+    }
+    Ok(days)
+}
+
+/// 截至今天的最近 `days` 个自然日的账号小时热力图；`live` 为内存中尚未落盘的当天数据
+pub fn heatmap(days: usize, live: Option<DailyStats>) -> Result<UsageHeatmap, String> {
+    let mut history = {
+        let _guard = FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        read_history()?
+    };
+    if let Some(day) = live {
+        history.insert(day.date.clone(), day);
+    }
+    Ok(build_heatmap(&history, chrono::Local::now().date_naive(), days))
+}
+
+fn build_heatmap(history: &BTreeMap<String, DailyStats>, end: chrono::NaiveDate, days: usize) -> UsageHeatmap {
+    let days = days.max(1);
+    let start = end - chrono::Days::new(days as u64 - 1);
+    let mut missing_dates = Vec::new();
+    let mut cells: BTreeMap<&str, BTreeMap<u32, u64>> = BTreeMap::new();
+    for (offset, date) in start.iter_days().take(days).enumerate() {
+        let date = date.format("%Y-%m-%d").to_string();
+        let Some(day) = history.get(&date) else {
+            missing_dates.push(date);
+            continue;
+        };
+        for (email, hours) in &day.account_hours {
+            let account = cells.entry(email.as_str()).or_default();
+            for (&hour, &count) in hours {
+                if count > 0 && hour < 24 {
+                    *account.entry(offset as u32 * 24 + hour as u32).or_default() += count;
+                }
+            }
+        }
+    }
+
+    let mut accounts: Vec<AccountHeatmap> = cells
+        .into_iter()
+        .map(|(email, hours)| AccountHeatmap {
+            email: email.to_string(),
+            total: hours.values().sum(),
+            hours: hours.keys().copied().collect(),
+            counts: hours.values().copied().collect(),
+        })
+        .collect();
+    accounts.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.email.cmp(&b.email)));
+
+    UsageHeatmap {
+        start_date: start.format("%Y-%m-%d").to_string(),
+        end_date: end.format("%Y-%m-%d").to_string(),
+        start_ms: local_midnight_ms(start),
+        end_ms: local_midnight_ms(end + chrono::Days::new(1)),
+        days,
+        missing_dates,
+        accounts,
+    }
+}
+
+/// 解析费用报表窗口，如 `30d` 或 `30` (天数，1-400)
+pub fn parse_window_days(window: &str) -> Result<usize, String> {
+    let trimmed = window.trim();
+    let number = trimmed.strip_suffix('d').unwrap_or(trimmed);
+    match number.parse::<usize>() {
+        Ok(days) if (1..=MAX_HISTORY_DAYS).contains(&days) => Ok(days),
+        _ => Err(format!("无效的统计窗口 {}，应为 1-{} 天，如 30d", window, MAX_HISTORY_DAYS)),
+    }
+}
+
+/// 截至今天的最近 `days` 个自然日的费用报表；`live` 为内存中尚未落盘的当天数据
+pub fn cost_report(group_by: CostGroupBy, days: usize, live: Option<DailyStats>) -> Result<CostReport, String> {
+    let mut history = {
+        let _guard = FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        read_history()?
+    };
+    if let Some(day) = live {
+        history.insert(day.date.clone(), day);
+    }
+    Ok(build_cost_report(&history, chrono::Local::now().date_naive(), group_by, days))
+}
+
+fn build_cost_report(
+    history: &BTreeMap<String, DailyStats>,
+    end: chrono::NaiveDate,
+    group_by: CostGroupBy,
+    days: usize,
+) -> CostReport {
+    let days = days.max(1);
+    let start = end - chrono::Days::new(days as u64 - 1);
+    let mut missing_dates = Vec::new();
+    let mut groups: BTreeMap<&str, CostTotal> = BTreeMap::new();
+    for date in start.iter_days().take(days) {
+        let date = date.format("%Y-%m-%d").to_string();
+        let Some(day) = history.get(&date) else {
+            missing_dates.push(date);
+            continue;
+        };
+        for (name, total) in day.costs(group_by) {
+            groups.entry(name.as_str()).or_default().merge(total);
+        }
+    }
+
+    let mut totals: BTreeMap<String, f64> = BTreeMap::new();
+    let mut unpriced_requests = 0;
+    let mut rows: Vec<CostRow> = groups
+        .into_iter()
+        .map(|(name, total)| {
+            for (currency, cost) in &total.costs {
+                *totals.entry(currency.clone()).or_default() += cost;
+            }
+            unpriced_requests += total.unpriced_requests;
+            let single = (total.costs.len() == 1).then(|| total.costs.iter().next()).flatten();
+            CostRow {
+                name: name.to_string(),
+                requests: total.requests,
+                input_tokens: total.input_tokens,
+                output_tokens: total.output_tokens,
+                unpriced_requests: total.unpriced_requests,
+                cost: single.map(|(_, cost)| *cost),
+                currency: single.map(|(currency, _)| currency.clone()),
+                costs: total.costs,
+            }
+        })
+        .collect();
+    rows.sort_by(|a, b| {
+        let cost = |row: &CostRow| row.costs.values().sum::<f64>();
+        (!b.costs.is_empty())
+            .cmp(&!a.costs.is_empty())
+            .then_with(|| cost(b).total_cmp(&cost(a)))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+
+    CostReport {
+        group_by,
+        start_date: start.format("%Y-%m-%d").to_string(),
+        end_date: end.format("%Y-%m-%d").to_string(),
+        days,
+        missing_dates,
+        totals,
+        unpriced_requests,
+        rows,
+    }
+}
+
+pub fn clear() -> Result<(), String> {
+    let _guard = FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let path = history_path()?;
+    if path.exists() {
+        std::fs::remove_file(&path).map_err(|e| format!("删除统计历史失败: {}", e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(status: u16, model: Option<&str>, input: u32, output: u32) -> ProxyRequestLog {
+        log_at(status, model, input, output, 0, None)
+    }
+
+    fn log_at(status: u16, model: Option<&str>, input: u32, output: u32, timestamp: i64, account: Option<&str>) -> ProxyRequestLog {
+        ProxyRequestLog {
+            id: "log".to_string(),
+            timestamp,
+            status,
+            duration: 1,
+            model: Some("claude-sonnet".to_string()),
+            mapped_model: model.map(str::to_string),
+            account_email: account.map(str::to_string),
+            input_tokens: Some(input),
+            output_tokens: Some(output),
+            ..ProxyRequestLog::test_default()
+        }
+    }
+
+    #[test]
+    fn test_daily_stats_record() {
+        let mut day = DailyStats::new("2024-01-01".to_string());
+        day.record(&log(200, Some("gemini-3-pro"), 10, 5));
+        day.record(&log(429, Some("gemini-3-pro"), 0, 0));
+        day.record(&log(200, None, 3, 2));
+
+        assert_eq!(day.requests, 3);
+        assert_eq!(day.errors, 1);
+        assert_eq!((day.input_tokens, day.output_tokens), (13, 7));
+        assert_eq!(day.models["gemini-3-pro"], 2);
+        assert_eq!(day.models["claude-sonnet"], 1);
+    }
+
+    #[test]
+    fn test_upsert_and_tail() {
+        let mut history = BTreeMap::new();
+        for i in 0..(MAX_HISTORY_DAYS + 5) {
+            let date = (chrono::NaiveDate::from_ymd_opt(2020, 1, 1).unwrap() + chrono::Days::new(i as u64))
+                .format("%Y-%m-%d")
+                .to_string();
+            upsert(&mut history, DailyStats::new(date));
+        }
+        assert_eq!(history.len(), MAX_HISTORY_DAYS);
+        assert!(!history.contains_key("2020-01-01"));
+
+        let mut live = DailyStats::new(history.keys().last().unwrap().clone());
+        live.requests = 7;
+        upsert(&mut history, live.clone());
+        let recent = tail(&history, 3);
+        assert_eq!(recent.len(), 3);
+        assert_eq!(recent.last(), Some(&live));
+    }
+
+    #[test]
+    fn test_build_heatmap() {
+        let end = chrono::NaiveDate::from_ymd_opt(2024, 1, 7).unwrap();
+        let at = |date: chrono::NaiveDate, hour: u32| local_midnight_ms(date) + hour as i64 * 3600 * 1000;
+
+        let mut first = DailyStats::new("2024-01-01".to_string());
+        first.record(&log_at(200, None, 0, 0, at(end - chrono::Days::new(6), 3), Some("a@x.com")));
+        let mut last = DailyStats::new("2024-01-07".to_string());
+        for _ in 0..2 {
+            last.record(&log_at(200, None, 0, 0, at(end, 23), Some("a@x.com")));
+        }
+        last.record(&log_at(500, None, 0, 0, at(end, 0), Some("b@x.com")));
+        last.record(&log_at(500, None, 0, 0, at(end, 0), None));
+        let history = BTreeMap::from([(first.date.clone(), first), (last.date.clone(), last)]);
+
+        let heatmap = build_heatmap(&history, end, 7);
+        assert_eq!(heatmap.start_date, "2024-01-01");
+        assert_eq!(heatmap.end_date, "2024-01-07");
+        assert_eq!(heatmap.end_ms - heatmap.start_ms, 7 * 24 * 3600 * 1000);
+        assert_eq!(heatmap.missing_dates.len(), 5);
+        assert_eq!(heatmap.accounts.len(), 2);
+        let a = &heatmap.accounts[0];
+        assert_eq!((a.email.as_str(), a.total), ("a@x.com", 3));
+        assert_eq!(a.hours, vec![3, 6 * 24 + 23]);
+        assert_eq!(a.counts, vec![1, 2]);
+        assert_eq!(heatmap.accounts[1].hours, vec![6 * 24]);
+
+        // 没有任何汇总记录时返回空列表
+        let empty = build_heatmap(&BTreeMap::new(), end, 7);
+        assert!(empty.accounts.is_empty());
+        assert_eq!(empty.missing_dates.len(), 7);
+    }
+
+    #[test]
+    fn test_build_cost_report() {
+        let end = chrono::NaiveDate::from_ymd_opt(2024, 1, 7).unwrap();
+        let priced = |model: &str, key: &str, cost: f64| ProxyRequestLog {
+            api_key_name: Some(key.to_string()),
+            cost: Some(cost),
+            currency: Some("USD".to_string()),
+            ..log(200, Some(model), 1000, 500)
+        };
+
+        let mut first = DailyStats::new("2024-01-01".to_string());
+        first.record(&priced("gemini-3-pro", "ci", 0.5));
+        let mut last = DailyStats::new("2024-01-07".to_string());
+        last.record(&priced("gemini-3-pro", "ci", 0.25));
+        last.record(&priced("gemini-3-flash", "dev", 0.1));
+        last.record(&log(200, Some("claude-opus"), 100, 10));
+        let history = BTreeMap::from([(first.date.clone(), first), (last.date.clone(), last)]);
+
+        let report = build_cost_report(&history, end, CostGroupBy::Model, 7);
+        assert_eq!(report.missing_dates.len(), 5);
+        assert_eq!(report.unpriced_requests, 1);
+        assert!((report.totals["USD"] - 0.85).abs() < 1e-9);
+        let names: Vec<&str> = report.rows.iter().map(|row| row.name.as_str()).collect();
+        assert_eq!(names, vec!["gemini-3-pro", "gemini-3-flash", "claude-opus"]);
+        assert_eq!(report.rows[0].requests, 2);
+        assert_eq!(report.rows[0].currency.as_deref(), Some("USD"));
+        // 没有单价的模型费用为空而不是 0
+        assert_eq!(report.rows[2].cost, None);
+        assert_eq!(report.rows[2].unpriced_requests, 1);
+
+        let by_key = build_cost_report(&history, end, CostGroupBy::Key, 7);
+        let names: Vec<&str> = by_key.rows.iter().map(|row| row.name.as_str()).collect();
+        assert_eq!(names, vec!["ci", "dev", UNATTRIBUTED]);
+
+        assert_eq!(parse_window_days("30d"), Ok(30));
+        assert_eq!(parse_window_days("7"), Ok(7));
+        assert!(parse_window_days("0d").is_err());
+        assert!(parse_window_days("1w").is_err());
+
+        // 窗口外的日期不计入
+        let today_only = build_cost_report(&history, end, CostGroupBy::Key, 1);
+        assert!((today_only.rows[0].cost.unwrap() - 0.25).abs() < 1e-9);
+    }
+}
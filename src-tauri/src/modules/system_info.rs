@@ -0,0 +1,163 @@
+//! 进程与运行环境信息，供远程排查问题使用
+//!
+//! 运行时长、常驻内存与线程数通过 `sysinfo` 获取；当前使用的 sysinfo 0.31 不提供文件描述符数，
+//! 因此改为统计 `/proc/self/fd` (仅 Linux)。平台相关的指标获取失败时返回空值而非报错。
+
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
+use utoipa::ToSchema;
+
+use super::account::get_accounts_dir;
+
+/// 构建信息
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BuildInfo {
+    pub version: String,
+    pub git_commit: String,
+    pub target: String,
+}
+
+/// 数据目录占用 (字节)
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DiskUsage {
+    pub accounts_bytes: Option<u64>,
+    /// 应用日志目录与请求日志数据库
+    pub logs_bytes: Option<u64>,
+}
+
+/// 请求监控的内存缓冲
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MonitorBuffer {
+    pub len: usize,
+    pub capacity: usize,
+}
+
+/// `/api/events` 订阅者
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct EventSubscribers {
+    pub active: usize,
+    /// 0 表示不限制
+    pub max: usize,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SystemInfo {
+    /// 进程运行时长 (秒)
+    pub uptime_secs: Option<u64>,
+    /// 常驻内存 (字节)
+    pub rss_bytes: Option<u64>,
+    /// 打开的文件描述符数 (仅 Linux)
+    pub open_fds: Option<usize>,
+    /// tokio 工作线程数
+    pub runtime_workers: Option<usize>,
+    /// tokio 存活任务数
+    pub runtime_tasks: Option<usize>,
+    /// 进程线程数 (仅 Linux)
+    pub threads: Option<usize>,
+    pub disk: DiskUsage,
+    /// 反代未运行时为空
+    pub monitor_buffer: Option<MonitorBuffer>,
+    pub build: BuildInfo,
+    /// 管理 API 限流状态 (按接口类别与客户端)
+    pub rate_limits: Vec<super::web_rate_limit::RateLimitState>,
+    /// 事件流订阅者 (仅 Web 模式)
+    pub event_subscribers: Option<EventSubscribers>,
+}
+
+/// 采集当前进程信息
+pub fn collect(monitor_buffer: Option<MonitorBuffer>) -> SystemInfo {
+    let (uptime_secs, rss_bytes, threads) = process_stats();
+    let metrics = tokio::runtime::Handle::try_current().ok().map(|h| h.metrics());
+
+    SystemInfo {
+        uptime_secs,
+        rss_bytes,
+        open_fds: open_fds(),
+        runtime_workers: metrics.as_ref().map(|m| m.num_workers()),
+        runtime_tasks: metrics.as_ref().map(|m| m.num_alive_tasks()),
+        threads,
+        disk: DiskUsage {
+            accounts_bytes: get_accounts_dir().ok().map(|dir| dir_size(&dir)),
+            logs_bytes: logs_size(),
+        },
+        monitor_buffer,
+        build: BuildInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_commit: env!("GIT_HASH").to_string(),
+            target: super::self_update::TARGET.to_string(),
+        },
+        rate_limits: super::web_rate_limit::WebRateLimiter::global().snapshot(),
+        event_subscribers: None,
+    }
+}
+
+/// 运行时长 (秒)、常驻内存 (字节) 与线程数
+fn process_stats() -> (Option<u64>, Option<u64>, Option<usize>) {
+    let Ok(pid) = sysinfo::get_current_pid() else {
+        return (None, None, None);
+    };
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[pid]),
+        ProcessRefreshKind::new().with_memory(),
+    );
+    match system.process(pid) {
+        Some(process) => (
+            Some(process.run_time()),
+            Some(process.memory()),
+            process.tasks().map(|tasks| tasks.len()),
+        ),
+        None => (None, None, None),
+    }
+}
+
+/// sysinfo 0.31 没有对应接口，Linux 下直接统计 procfs
+fn open_fds() -> Option<usize> {
+    if cfg!(target_os = "linux") {
+        fs::read_dir("/proc/self/fd").ok().map(|entries| entries.count())
+    } else {
+        None
+    }
+}
+
+fn logs_size() -> Option<u64> {
+    let log_dir = super::logger::get_log_dir().ok()?;
+    let db_bytes = super::proxy_db::get_proxy_db_path()
+        .and_then(|path| fs::metadata(path).map_err(|e| e.to_string()))
+        .map(|m| m.len())
+        .unwrap_or(0);
+    Some(dir_size(&log_dir) + db_bytes)
+}
+
+/// 目录下文件的总大小，读取失败的项忽略
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(m) if m.is_dir() => dir_size(&entry.path()),
+            Ok(m) => m.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_collect_reports_process_metrics() {
+        let info = collect(None);
+        assert!(info.runtime_workers.is_some());
+        assert!(!info.build.version.is_empty());
+        if cfg!(target_os = "linux") {
+            assert!(info.rss_bytes.is_some_and(|rss| rss > 0));
+            assert!(info.open_fds.is_some_and(|fds| fds > 0));
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! 账号 Token 健康检查：尝试刷新每个账号的 refresh_token，找出已失效的账号
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use utoipa::ToSchema;
+
+use crate::models::Account;
+use crate::modules::account::{list_accounts, load_account, save_account};
+use crate::modules::logger;
+use crate::modules::oauth::{self, RefreshError};
+
+/// 批量检查的最大并发数
+const MAX_CONCURRENT: usize = 5;
+
+/// 健康检查禁用反代时记录的原因，更新 Token 后据此自动恢复
+pub const INVALID_TOKEN_REASON: &str = "Token 失效 (健康检查)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenStatus {
+    Valid,
+    /// refresh_token 已被撤销或失效，需要重新授权
+    Invalid,
+    /// 网络错误或服务端临时故障，无法判断
+    NetworkError,
+}
+
+/// 单个账号的检查结果
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TokenCheck {
+    pub account_id: String,
+    pub email: String,
+    pub status: TokenStatus,
+    pub error: Option<String>,
+    /// 本次检查是否禁用了该账号的反代
+    pub proxy_disabled: bool,
+}
+
+/// 检查单个账号
+///
+/// 刷新成功时保存新的 access_token；`disable_invalid` 为 true 时，失效账号会被禁用反代。
+pub async fn check_account(account: &Account, disable_invalid: bool) -> TokenCheck {
+    let mut check = TokenCheck {
+        account_id: account.id.clone(),
+        email: account.email.clone(),
+        status: TokenStatus::Valid,
+        error: None,
+        proxy_disabled: false,
+    };
+
+    match oauth::try_refresh_access_token(&account.token.refresh_token).await {
+        Ok(token) => {
+            // 重新读取账号文件，避免覆盖检查期间的其他修改
+            let result = load_account(&account.id).and_then(|mut latest| {
+                latest.token.access_token = token.access_token;
+                latest.token.expires_in = token.expires_in;
+                latest.token.expiry_timestamp = chrono::Utc::now().timestamp() + token.expires_in;
+                save_account(&latest)
+            });
+            if let Err(e) = result {
+                logger::log_warn(&format!("保存 {} 的新 Token 失败: {}", account.email, e));
+            }
+        }
+        Err(RefreshError::Rejected(e)) => {
+            check.status = TokenStatus::Invalid;
+            check.error = Some(e);
+            if disable_invalid && !account.proxy_disabled {
+                match disable_proxy(&account.id) {
+                    Ok(()) => check.proxy_disabled = true,
+                    Err(e) => logger::log_warn(&format!("禁用 {} 失败: {}", account.email, e)),
+                }
+            }
+        }
+        Err(RefreshError::Transient(e)) => {
+            check.status = TokenStatus::NetworkError;
+            check.error = Some(e);
+        }
+        // 熔断中未发起请求，不能据此判断账号状态
+        Err(e @ RefreshError::Throttled(_)) => {
+            check.status = TokenStatus::NetworkError;
+            check.error = Some(e.to_string());
+        }
+    }
+
+    check
+}
+
+/// 并发检查所有账号，每完成一个调用一次 `progress(结果, 已完成数, 总数)`
+pub async fn check_all_accounts(
+    disable_invalid: bool,
+    progress: impl Fn(&TokenCheck, usize, usize),
+) -> Result<Vec<TokenCheck>, String> {
+    let accounts = list_accounts()?;
+    let total = accounts.len();
+    logger::log_info(&format!("开始检查 {} 个账号的 Token", total));
+
+    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT));
+    let mut tasks: FuturesUnordered<_> = accounts
+        .into_iter()
+        .map(|account| {
+            let semaphore = semaphore.clone();
+            async move {
+                let _permit = semaphore.acquire().await;
+                check_account(&account, disable_invalid).await
+            }
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(total);
+    while let Some(check) = tasks.next().await {
+        results.push(check);
+        progress(results.last().unwrap(), results.len(), total);
+    }
+
+    let invalid = results.iter().filter(|c| c.status == TokenStatus::Invalid).count();
+    logger::log_info(&format!("Token 检查完成: {} 个账号, {} 个失效", total, invalid));
+    Ok(results)
+}
+
+fn disable_proxy(account_id: &str) -> Result<(), String> {
+    let mut account = load_account(account_id)?;
+    account.proxy_disabled = true;
+    account.proxy_disabled_reason = Some(INVALID_TOKEN_REASON.to_string());
+    account.proxy_disabled_at = Some(chrono::Utc::now().timestamp());
+    save_account(&account)
+}
@@ -1,8 +1,13 @@
+use once_cell::sync::Lazy;
+use semver::Version;
 use serde::{Deserialize, Serialize};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use crate::models::UpdateChannel;
 use crate::modules::logger;
 
-const GITHUB_API_URL: &str = "https://api.github.com/repos/lbjlaq/Antigravity-Manager/releases/latest";
+const GITHUB_LATEST_URL: &str = "https://api.github.com/repos/lbjlaq/Antigravity-Manager/releases/latest";
+const GITHUB_RELEASES_URL: &str = "https://api.github.com/repos/lbjlaq/Antigravity-Manager/releases?per_page=30";
 const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
 const DEFAULT_CHECK_INTERVAL_HOURS: u64 = 24;
 
@@ -14,6 +19,8 @@ pub struct UpdateInfo {
     pub download_url: String, // 原为 release_url
     pub release_notes: String,
     pub published_at: String,
+    #[serde(default)]
+    pub prerelease: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,30 +45,68 @@ impl Default for UpdateSettings {
     }
 }
 
-#[derive(Debug, Deserialize)]
-struct GitHubRelease {
-    tag_name: String,
-    html_url: String,
-    body: String,
-    published_at: String,
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct GitHubRelease {
+    pub tag_name: String,
+    pub html_url: String,
+    #[serde(default)]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub published_at: Option<String>,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub assets: Vec<GitHubAsset>,
 }
 
-/// Check for updates from GitHub releases
-pub async fn check_for_updates() -> Result<UpdateInfo, String> {
-    let client = reqwest::Client::builder()
-        .user_agent("Antigravity-Manager")
-        .timeout(std::time::Duration::from_secs(10))
-        .build()
-        .map_err(|e| {
-            let err_msg = format!("Failed to create HTTP client: {}", e);
-            logger::log_error(&err_msg);
-            err_msg
-        })?;
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct GitHubAsset {
+    pub name: String,
+    pub browser_download_url: String,
+    #[serde(default)]
+    pub size: u64,
+}
+
+/// Release 查询结果缓存 1 小时，避免设置页频繁请求 GitHub API
+const RELEASE_CACHE_TTL: Duration = Duration::from_secs(3600);
+
+static RELEASE_CACHE: Lazy<Mutex<Option<(UpdateChannel, Instant, GitHubRelease)>>> =
+    Lazy::new(|| Mutex::new(None));
+
+/// 按配置的更新通道获取最新 Release (带缓存)
+///
+/// stable 通道使用 `/releases/latest` (不含预发布)；beta 通道列出近期 Release，
+/// 按语义化版本取最新的一个 (含预发布)。请求走配置的上游代理。
+pub(crate) async fn fetch_latest_release() -> Result<GitHubRelease, String> {
+    let channel = crate::modules::config::load_app_config()
+        .map(|config| config.update_channel)
+        .unwrap_or_default();
 
-    logger::log_info("正在从 GitHub 检查新版本...");
+    if let Some((cached_channel, fetched_at, release)) = RELEASE_CACHE.lock().unwrap().as_ref() {
+        if *cached_channel == channel && fetched_at.elapsed() < RELEASE_CACHE_TTL {
+            return Ok(release.clone());
+        }
+    }
+
+    let release = fetch_release_uncached(channel).await?;
+    *RELEASE_CACHE.lock().unwrap() = Some((channel, Instant::now(), release.clone()));
+    Ok(release)
+}
+
+async fn fetch_release_uncached(channel: UpdateChannel) -> Result<GitHubRelease, String> {
+    let client = crate::utils::http::create_client(10);
 
+    logger::log_info(&format!("正在从 GitHub 检查新版本 (通道: {:?})...", channel));
+
+    let url = match channel {
+        UpdateChannel::Stable => GITHUB_LATEST_URL,
+        UpdateChannel::Beta => GITHUB_RELEASES_URL,
+    };
     let response = client
-        .get(GITHUB_API_URL)
+        .get(url)
+        .header("User-Agent", "Antigravity-Manager")
         .send()
         .await
         .map_err(|e| {
@@ -74,10 +119,34 @@ pub async fn check_for_updates() -> Result<UpdateInfo, String> {
         return Err(format!("GitHub API returned status: {}", response.status()));
     }
 
-    let release: GitHubRelease = response
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse release info: {}", e))?;
+    match channel {
+        UpdateChannel::Stable => response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse release info: {}", e)),
+        UpdateChannel::Beta => {
+            let releases: Vec<GitHubRelease> = response
+                .json()
+                .await
+                .map_err(|e| format!("Failed to parse release info: {}", e))?;
+            newest_release(releases).ok_or_else(|| "No releases found".to_string())
+        }
+    }
+}
+
+/// 取版本号最高的非草稿 Release；无法解析版本号的 tag 会被忽略
+fn newest_release(releases: Vec<GitHubRelease>) -> Option<GitHubRelease> {
+    releases
+        .into_iter()
+        .filter(|release| !release.draft)
+        .filter_map(|release| parse_version(&release.tag_name).map(|version| (version, release)))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, release)| release)
+}
+
+/// Check for updates from GitHub releases
+pub async fn check_for_updates() -> Result<UpdateInfo, String> {
+    let release = fetch_latest_release().await?;
 
     // Remove 'v' prefix if present
     let latest_version = release.tag_name.trim_start_matches('v').to_string();
@@ -96,34 +165,34 @@ pub async fn check_for_updates() -> Result<UpdateInfo, String> {
         latest_version,
         has_update,
         download_url: release.html_url,
-        release_notes: release.body,
-        published_at: release.published_at,
+        release_notes: release.body.unwrap_or_default(),
+        published_at: release.published_at.unwrap_or_default(),
+        prerelease: release.prerelease,
     })
 }
 
-/// Compare two semantic versions (e.g., "3.3.30" vs "3.3.29")
-fn compare_versions(latest: &str, current: &str) -> bool {
-    let parse_version = |v: &str| -> Vec<u32> {
-        v.split('.')
-            .filter_map(|s| s.parse::<u32>().ok())
-            .collect()
-    };
-
-    let latest_parts = parse_version(latest);
-    let current_parts = parse_version(current);
-
-    for i in 0..latest_parts.len().max(current_parts.len()) {
-        let latest_part = latest_parts.get(i).unwrap_or(&0);
-        let current_part = current_parts.get(i).unwrap_or(&0);
-
-        if latest_part > current_part {
-            return true;
-        } else if latest_part < current_part {
-            return false; // e.g. local: 3.3.30, remote: 3.3.30 => false
+/// 解析版本号，兼容 `v` 前缀与缺省的补丁号 (如 `v3.4`)
+fn parse_version(v: &str) -> Option<Version> {
+    let v = v.trim().trim_start_matches('v');
+    Version::parse(v).ok().or_else(|| {
+        // 补齐缺失的 minor/patch，保留预发布与构建元数据
+        let split = v.find(['-', '+']).unwrap_or(v.len());
+        let (core, rest) = v.split_at(split);
+        let mut parts: Vec<&str> = core.split('.').collect();
+        if parts.len() >= 3 {
+            return None;
         }
-    }
+        parts.resize(3, "0");
+        Version::parse(&format!("{}{}", parts.join("."), rest)).ok()
+    })
+}
 
-    false
+/// 按语义化版本比较 (预发布低于对应正式版，如 3.4.0-beta.2 < 3.4.0)
+pub(crate) fn compare_versions(latest: &str, current: &str) -> bool {
+    match (parse_version(latest), parse_version(current)) {
+        (Some(latest), Some(current)) => latest > current,
+        _ => false,
+    }
 }
 
 /// Check if enough time has passed since last check
@@ -199,6 +268,37 @@ mod tests {
         assert!(!compare_versions("3.3.32", "3.3.32"));
     }
 
+    #[test]
+    fn test_compare_prerelease_versions() {
+        assert!(compare_versions("3.4.0", "3.4.0-beta.2"));
+        assert!(compare_versions("3.4.0-beta.10", "3.4.0-beta.2"));
+        assert!(compare_versions("3.4.0-beta.1", "3.3.32"));
+        assert!(!compare_versions("3.4.0-beta.2", "3.4.0"));
+        assert!(compare_versions("v3.4", "3.3.32"));
+        assert!(!compare_versions("nightly", "3.3.32"));
+    }
+
+    #[test]
+    fn test_newest_release_includes_prereleases() {
+        let release = |tag: &str, prerelease: bool, draft: bool| GitHubRelease {
+            tag_name: tag.to_string(),
+            html_url: String::new(),
+            body: None,
+            published_at: None,
+            prerelease,
+            draft,
+            assets: Vec::new(),
+        };
+        let newest = newest_release(vec![
+            release("v3.3.32", false, false),
+            release("v3.4.0-beta.2", true, false),
+            release("v3.4.0-beta.10", true, false),
+            release("v3.5.0", false, true),
+        ])
+        .unwrap();
+        assert_eq!(newest.tag_name, "v3.4.0-beta.10");
+    }
+
     #[test]
     fn test_should_check_for_updates() {
         let mut settings = UpdateSettings::default();
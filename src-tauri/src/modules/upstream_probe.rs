@@ -0,0 +1,121 @@
+//! 上游连通性检查
+//!
+//! 按当前的上游代理与自定义 DNS 解析配置连接上游主机，报告实际连接的地址，用于确认 DNS 覆盖是否生效。
+
+use futures::future::join_all;
+use serde::Serialize;
+use std::net::IpAddr;
+use std::time::Instant;
+use utoipa::ToSchema;
+
+use crate::proxy::config::{DnsOverride, ProxyConfig};
+
+/// 默认检查的上游主机 (v1internal 与 OAuth)
+const PROBE_HOSTS: [&str; 3] = [
+    "cloudcode-pa.googleapis.com",
+    "daily-cloudcode-pa.sandbox.googleapis.com",
+    "oauth2.googleapis.com",
+];
+
+const PROBE_TIMEOUT_SECS: u64 = 10;
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UpstreamProbe {
+    pub host: String,
+    /// 建立连接并收到响应 (任意状态码)
+    pub reachable: bool,
+    pub status: Option<u16>,
+    /// 实际连接的对端地址；经上游代理时为代理地址
+    pub remote_addr: Option<String>,
+    /// 该主机配置了 DNS 覆盖
+    pub dns_override: bool,
+    /// 对端地址是否为覆盖的 IP；经上游代理或未连通时无法判断，为空
+    pub override_in_effect: Option<bool>,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+/// 默认主机加上 DNS 覆盖中的其余主机
+fn probe_hosts(overrides: &[DnsOverride]) -> Vec<String> {
+    let mut hosts: Vec<String> = PROBE_HOSTS.iter().map(|h| h.to_string()).collect();
+    for entry in overrides {
+        let host = entry.host_key();
+        if !host.is_empty() && !hosts.contains(&host) {
+            hosts.push(host);
+        }
+    }
+    hosts
+}
+
+fn override_ips(overrides: &[DnsOverride], host: &str) -> Vec<IpAddr> {
+    overrides
+        .iter()
+        .filter(|entry| entry.host_key() == host)
+        .filter_map(|entry| entry.socket_addr().map(|addr| addr.ip()))
+        .collect()
+}
+
+async fn probe_host(client: &reqwest::Client, config: &ProxyConfig, host: String) -> UpstreamProbe {
+    let ips = override_ips(&config.dns_overrides, &host);
+    let via_proxy = config.upstream_proxy.enabled && !config.upstream_proxy.url.is_empty();
+    let start = Instant::now();
+    let result = client.get(format!("https://{}/", host)).send().await;
+    let latency_ms = start.elapsed().as_millis() as u64;
+
+    match result {
+        Ok(resp) => {
+            let remote = resp.remote_addr();
+            UpstreamProbe {
+                reachable: true,
+                status: Some(resp.status().as_u16()),
+                remote_addr: remote.map(|addr| addr.to_string()),
+                dns_override: !ips.is_empty(),
+                override_in_effect: match remote {
+                    Some(addr) if !ips.is_empty() && !via_proxy => Some(ips.contains(&addr.ip())),
+                    _ => None,
+                },
+                latency_ms,
+                error: None,
+                host,
+            }
+        }
+        Err(e) => UpstreamProbe {
+            reachable: false,
+            status: None,
+            remote_addr: None,
+            dns_override: !ips.is_empty(),
+            override_in_effect: None,
+            latency_ms,
+            error: Some(e.to_string()),
+            host,
+        },
+    }
+}
+
+/// 并发检查所有上游主机
+pub async fn probe(config: &ProxyConfig) -> Vec<UpstreamProbe> {
+    let client = crate::utils::http::create_client_with_proxy(
+        PROBE_TIMEOUT_SECS,
+        Some(config.upstream_proxy.clone()),
+        &config.dns_overrides,
+    );
+    let hosts = probe_hosts(&config.dns_overrides);
+    join_all(hosts.into_iter().map(|host| probe_host(&client, config, host))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_hosts_include_overrides() {
+        let overrides = vec![
+            DnsOverride { host: "OAuth2.googleapis.com".to_string(), ip: "10.0.0.1".to_string(), port: None },
+            DnsOverride { host: "example.internal".to_string(), ip: "10.0.0.2".to_string(), port: None },
+        ];
+        let hosts = probe_hosts(&overrides);
+        assert_eq!(hosts.len(), PROBE_HOSTS.len() + 1);
+        assert_eq!(hosts.last().unwrap(), "example.internal");
+        assert_eq!(override_ips(&overrides, "oauth2.googleapis.com"), vec!["10.0.0.1".parse::<IpAddr>().unwrap()]);
+    }
+}
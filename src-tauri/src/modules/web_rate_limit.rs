@@ -0,0 +1,181 @@
+//! 管理 API 高开销接口限流：按 (接口类别, 客户端) 分别维护令牌桶
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::models::WebRateLimits;
+use crate::proxy::key_limiter::TokenBucket;
+
+/// 客户端数超过该值时清理已回满的桶，避免按 IP 无限增长
+const MAX_TRACKED: usize = 1024;
+
+/// 受限流的接口类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteClass {
+    QuotaRefresh,
+    TokenValidation,
+    OAuth,
+    Import,
+}
+
+impl RouteClass {
+    /// 按请求方法与路由模板归类；读取请求一律不限流
+    pub fn of(method: &str, route: &str) -> Option<Self> {
+        if method != "POST" {
+            return None;
+        }
+        match route {
+            "/api/accounts/refresh-all" | "/api/accounts/:id/quota" => Some(Self::QuotaRefresh),
+            "/api/accounts/validate-all" | "/api/accounts/:id/validate" => Some(Self::TokenValidation),
+            "/api/sync/db" => Some(Self::Import),
+            _ if route.starts_with("/api/oauth/") => Some(Self::OAuth),
+            _ if route.starts_with("/api/import/") => Some(Self::Import),
+            _ => None,
+        }
+    }
+
+    /// 每分钟请求数，0 表示不限制
+    pub fn per_minute(&self, limits: &WebRateLimits) -> u32 {
+        if !limits.enabled {
+            return 0;
+        }
+        match self {
+            Self::QuotaRefresh => limits.quota_refresh_per_minute,
+            Self::TokenValidation => limits.token_validation_per_minute,
+            Self::OAuth => limits.oauth_per_minute,
+            Self::Import => limits.import_per_minute,
+        }
+    }
+}
+
+/// 某个客户端在某类接口上的限流状态
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RateLimitState {
+    pub class: RouteClass,
+    /// `token:<名称>` 或 `ip:<地址>`
+    pub client: String,
+    pub per_minute: u32,
+    pub tokens_available: f64,
+    /// 自状态创建以来被拒绝的请求数
+    pub rejected: u64,
+}
+
+struct Entry {
+    per_minute: u32,
+    bucket: TokenBucket,
+    rejected: u64,
+}
+
+#[derive(Default)]
+pub struct WebRateLimiter {
+    entries: Mutex<HashMap<(RouteClass, String), Entry>>,
+}
+
+static LIMITER: Lazy<WebRateLimiter> = Lazy::new(WebRateLimiter::default);
+
+impl WebRateLimiter {
+    pub fn global() -> &'static WebRateLimiter {
+        &LIMITER
+    }
+
+    /// 申请一次请求；超出限制时返回建议的重试等待秒数
+    pub fn check(&self, class: RouteClass, client: &str, per_minute: u32) -> Result<(), u64> {
+        self.check_at(class, client, per_minute, Instant::now())
+    }
+
+    fn check_at(&self, class: RouteClass, client: &str, per_minute: u32, now: Instant) -> Result<(), u64> {
+        if per_minute == 0 {
+            return Ok(());
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= MAX_TRACKED {
+            entries.retain(|_, entry| !entry.bucket.is_full(now));
+        }
+        let key = (class, client.to_string());
+        let entry = entries.entry(key).or_insert_with(|| Entry {
+            per_minute,
+            bucket: TokenBucket::new(per_minute),
+            rejected: 0,
+        });
+        // 限制变化时重建
+        if entry.per_minute != per_minute {
+            entry.per_minute = per_minute;
+            entry.bucket = TokenBucket::new(per_minute);
+        }
+        entry.bucket.try_take(now).inspect_err(|_| entry.rejected += 1)
+    }
+
+    /// 当前所有客户端的限流状态
+    pub fn snapshot(&self) -> Vec<RateLimitState> {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let mut states: Vec<RateLimitState> = entries
+            .iter_mut()
+            .map(|((class, client), entry)| RateLimitState {
+                class: *class,
+                client: client.clone(),
+                per_minute: entry.per_minute,
+                tokens_available: entry.bucket.available(now),
+                rejected: entry.rejected,
+            })
+            .collect();
+        states.sort_by(|a, b| a.client.cmp(&b.client));
+        states
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_route_classification() {
+        assert_eq!(RouteClass::of("POST", "/api/accounts/refresh-all"), Some(RouteClass::QuotaRefresh));
+        assert_eq!(RouteClass::of("POST", "/api/accounts/:id/validate"), Some(RouteClass::TokenValidation));
+        assert_eq!(RouteClass::of("POST", "/api/oauth/device/start"), Some(RouteClass::OAuth));
+        assert_eq!(RouteClass::of("POST", "/api/import/upload"), Some(RouteClass::Import));
+        assert_eq!(RouteClass::of("GET", "/api/oauth/device/:id"), None);
+        assert_eq!(RouteClass::of("POST", "/api/accounts/reorder"), None);
+
+        let mut limits = WebRateLimits::default();
+        assert_eq!(RouteClass::QuotaRefresh.per_minute(&limits), 10);
+        limits.enabled = false;
+        assert_eq!(RouteClass::QuotaRefresh.per_minute(&limits), 0);
+    }
+
+    #[test]
+    fn test_limits_per_client() {
+        let limiter = WebRateLimiter::default();
+        let now = Instant::now();
+        for _ in 0..2 {
+            limiter.check_at(RouteClass::QuotaRefresh, "ip:1.1.1.1", 2, now).unwrap();
+        }
+        let retry_after = limiter.check_at(RouteClass::QuotaRefresh, "ip:1.1.1.1", 2, now).unwrap_err();
+        assert_eq!(retry_after, 30);
+
+        // 其他客户端、其他类别互不影响
+        limiter.check_at(RouteClass::QuotaRefresh, "ip:2.2.2.2", 2, now).unwrap();
+        limiter.check_at(RouteClass::Import, "ip:1.1.1.1", 2, now).unwrap();
+        // 0 表示不限制
+        limiter.check_at(RouteClass::QuotaRefresh, "ip:1.1.1.1", 0, now).unwrap();
+
+        limiter
+            .check_at(RouteClass::QuotaRefresh, "ip:1.1.1.1", 2, now + Duration::from_secs(30))
+            .unwrap();
+
+        let state = limiter
+            .snapshot()
+            .into_iter()
+            .find(|s| s.client == "ip:1.1.1.1" && s.class == RouteClass::QuotaRefresh)
+            .unwrap();
+        assert_eq!(state.rejected, 1);
+        assert_eq!(state.per_minute, 2);
+    }
+}
@@ -0,0 +1,471 @@
+// 多模态图片处理
+// 上游只接受内联图片数据，远程 URL 需先下载再转为 inlineData
+// 远程 URL 由客户端提供，下载前解析域名并拒绝内网、回环等非公网地址，防止借反代访问内部服务 (SSRF)
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use base64::Engine as _;
+
+use crate::proxy::config::UpstreamProxyConfig;
+use crate::proxy::mappers::claude::models::{ContentBlock, MessageContent};
+use crate::proxy::mappers::claude::ClaudeRequest;
+use crate::proxy::mappers::openai::{OpenAIContent, OpenAIContentBlock, OpenAIRequest};
+
+/// 单张远程图片的大小上限
+pub const MAX_REMOTE_IMAGE_BYTES: usize = 20 * 1024 * 1024;
+/// 下载远程图片的超时
+const FETCH_TIMEOUT_SECS: u64 = 15;
+/// 跟随重定向的最大次数，每一跳都重新校验目标地址
+const MAX_REDIRECTS: usize = 5;
+
+/// 内联图片 (base64)
+#[derive(Debug, Clone, PartialEq)]
+pub struct InlineImage {
+    pub mime_type: String,
+    pub data: String,
+}
+
+impl InlineImage {
+    pub fn to_data_url(&self) -> String {
+        format!("data:{};base64,{}", self.mime_type, self.data)
+    }
+}
+
+/// 解析 `data:<mime>;base64,<data>` 形式的 Data URL
+pub fn parse_data_url(url: &str) -> Option<InlineImage> {
+    let rest = url.strip_prefix("data:")?;
+    let (meta, data) = rest.split_once(',')?;
+    let mime_type = meta.split(';').next().filter(|m| !m.is_empty()).unwrap_or("image/jpeg");
+    Some(InlineImage {
+        mime_type: mime_type.to_string(),
+        data: data.trim().to_string(),
+    })
+}
+
+/// 根据文件头识别常见图片格式
+pub fn sniff_image_mime(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(&[0x89, b'P', b'N', b'G']) {
+        Some("image/png")
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"GIF8") {
+        Some("image/gif")
+    } else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+pub fn is_remote_url(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// 根据 URL 扩展名推测图片类型
+pub fn guess_mime_from_path(path: &str) -> &'static str {
+    let lower = path.split('?').next().unwrap_or(path).to_lowercase();
+    if lower.ends_with(".png") {
+        "image/png"
+    } else if lower.ends_with(".gif") {
+        "image/gif"
+    } else if lower.ends_with(".webp") {
+        "image/webp"
+    } else {
+        "image/jpeg"
+    }
+}
+
+/// 是否为公网地址；回环、私有网段、链路本地 (含 169.254.169.254 元数据服务)、CGNAT、组播、保留地址等一律视为非公网
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_public_ipv4(v4),
+        IpAddr::V6(v6) => {
+            // IPv4 映射 / NAT64 地址按内嵌的 IPv4 判断
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_public_ipv4(v4);
+            }
+            let segments = v6.segments();
+            if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                let [a, b] = segments[6].to_be_bytes();
+                let [c, d] = segments[7].to_be_bytes();
+                return is_public_ipv4(Ipv4Addr::new(a, b, c, d));
+            }
+            !(v6.is_unspecified()
+                || v6.is_loopback()
+                || v6.is_multicast()
+                // fc00::/7 唯一本地地址
+                || (segments[0] & 0xfe00) == 0xfc00
+                // fe80::/10 链路本地地址
+                || (segments[0] & 0xffc0) == 0xfe80
+                // 2001:db8::/32 文档地址
+                || (segments[0] == 0x2001 && segments[1] == 0x0db8))
+        }
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // 0.0.0.0/8
+        || a == 0
+        // 100.64.0.0/10 运营商级 NAT
+        || (a == 100 && (64..128).contains(&b))
+        // 192.0.0.0/24 协议分配
+        || (a == 192 && b == 0 && ip.octets()[2] == 0)
+        // 198.18.0.0/15 基准测试
+        || (a == 198 && (b == 18 || b == 19))
+        // 240.0.0.0/4 保留地址
+        || a >= 240)
+}
+
+/// 校验图片 URL 并解析出允许连接的地址；任一解析结果为非公网地址时拒绝，避免 DNS 轮询绕过
+async fn resolve_public_target(url: &url::Url) -> Result<Vec<SocketAddr>, String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("Unsupported image url scheme".to_string());
+    }
+    let host = match url.host() {
+        Some(url::Host::Domain(domain)) => domain.to_string(),
+        Some(url::Host::Ipv4(ip)) => ip.to_string(),
+        Some(url::Host::Ipv6(ip)) => ip.to_string(),
+        None => return Err("Image url has no host".to_string()),
+    };
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), port))
+        .await
+        .map_err(|_| "Failed to resolve image host".to_string())?
+        .collect();
+    if addrs.is_empty() {
+        return Err("Failed to resolve image host".to_string());
+    }
+    if addrs.iter().any(|addr| !is_public_ip(addr.ip())) {
+        return Err("Image url points to a non-public address".to_string());
+    }
+    Ok(addrs)
+}
+
+/// 为单个目标构建客户端：不自动跟随重定向，直连时固定使用已校验的地址，防止连接时再次解析到内网
+fn build_client(
+    upstream_proxy: Option<&UpstreamProxyConfig>,
+    url: &url::Url,
+    addrs: &[SocketAddr],
+) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder()
+        .timeout(Duration::from_secs(FETCH_TIMEOUT_SECS))
+        .redirect(reqwest::redirect::Policy::none());
+    match upstream_proxy.filter(|config| config.enabled && !config.url.is_empty()) {
+        Some(config) => {
+            let proxy = reqwest::Proxy::all(&config.url).map_err(|e| format!("Invalid upstream proxy url: {}", e))?;
+            builder = builder.proxy(proxy);
+        }
+        None => {
+            if let Some(url::Host::Domain(domain)) = url.host() {
+                builder = builder.resolve_to_addrs(domain, addrs);
+            }
+        }
+    }
+    builder.build().map_err(|e| format!("Failed to build HTTP client: {}", e))
+}
+
+/// 下载远程图片并转为 base64，超出大小上限、不是图片或指向非公网地址时报错
+///
+/// 错误信息会返回给客户端，因此不包含图片 URL。
+pub async fn fetch_remote_image(upstream_proxy: Option<&UpstreamProxyConfig>, url: &str) -> Result<InlineImage, String> {
+    let mut target = url::Url::parse(url).map_err(|_| "Invalid image url".to_string())?;
+    let mut redirects = 0;
+    let mut response = loop {
+        let addrs = resolve_public_target(&target).await?;
+        let client = build_client(upstream_proxy, &target, &addrs)?;
+        let response = client
+            .get(target.clone())
+            .send()
+            .await
+            .map_err(|e| format!("Failed to download image: {}", e.without_url()))?;
+        if !response.status().is_redirection() {
+            break response;
+        }
+        redirects += 1;
+        if redirects > MAX_REDIRECTS {
+            return Err("Failed to download image: too many redirects".to_string());
+        }
+        let location = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| format!("Failed to download image: HTTP {}", response.status().as_u16()))?;
+        target = target.join(location).map_err(|_| "Failed to download image: invalid redirect".to_string())?;
+    };
+    if !response.status().is_success() {
+        return Err(format!("Failed to download image: HTTP {}", response.status().as_u16()));
+    }
+    if response.content_length().is_some_and(|len| len as usize > MAX_REMOTE_IMAGE_BYTES) {
+        return Err(format!("Image exceeds the {} MB limit", MAX_REMOTE_IMAGE_BYTES / 1024 / 1024));
+    }
+    let header_mime = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.split(';').next().unwrap_or(v).trim().to_lowercase());
+
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to download image: {}", e.without_url()))?
+    {
+        if bytes.len() + chunk.len() > MAX_REMOTE_IMAGE_BYTES {
+            return Err(format!("Image exceeds the {} MB limit", MAX_REMOTE_IMAGE_BYTES / 1024 / 1024));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    let mime_type = match header_mime.filter(|m| m.starts_with("image/")) {
+        Some(mime) => mime,
+        None => sniff_image_mime(&bytes)
+            .ok_or_else(|| "Image url did not return a supported image".to_string())?
+            .to_string(),
+    };
+    Ok(InlineImage {
+        mime_type,
+        data: base64::engine::general_purpose::STANDARD.encode(&bytes),
+    })
+}
+
+/// 将 OpenAI 请求中的远程 image_url 下载并替换为 Data URL，返回替换数量
+pub async fn inline_openai_images(
+    request: &mut OpenAIRequest,
+    upstream_proxy: Option<&UpstreamProxyConfig>,
+) -> Result<usize, String> {
+    let mut count = 0;
+    for msg in request.messages.iter_mut() {
+        let Some(OpenAIContent::Array(blocks)) = msg.content.as_mut() else {
+            continue;
+        };
+        for block in blocks.iter_mut() {
+            if let OpenAIContentBlock::ImageUrl { image_url } = block {
+                if !is_remote_url(&image_url.url) {
+                    continue;
+                }
+                let image = fetch_remote_image(upstream_proxy, &image_url.url).await?;
+                image_url.url = image.to_data_url();
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
+
+/// 将 Claude 请求中 `source.type = "url"` 的图片下载并替换为 base64，返回替换数量
+pub async fn inline_claude_images(
+    request: &mut ClaudeRequest,
+    upstream_proxy: Option<&UpstreamProxyConfig>,
+) -> Result<usize, String> {
+    let mut count = 0;
+    for msg in request.messages.iter_mut() {
+        let MessageContent::Array(blocks) = &mut msg.content else {
+            continue;
+        };
+        for block in blocks.iter_mut() {
+            let ContentBlock::Image { source, .. } = block else {
+                continue;
+            };
+            if source.source_type != "url" {
+                continue;
+            }
+            let url = source.url.clone().unwrap_or_default();
+            if !is_remote_url(&url) {
+                return Err("Unsupported image url scheme".to_string());
+            }
+            let image = fetch_remote_image(upstream_proxy, &url).await?;
+            source.source_type = "base64".to_string();
+            source.media_type = image.mime_type;
+            source.data = image.data;
+            source.url = None;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// 上游因图片无效或格式不支持而拒绝请求的错误
+pub fn is_image_error(error_text: &str) -> bool {
+    let lower = error_text.to_lowercase();
+    lower.contains("image")
+        && (lower.contains("invalid")
+            || lower.contains("unsupported")
+            || lower.contains("unable to process")
+            || lower.contains("could not process")
+            || lower.contains("mime type"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use serde_json::json;
+
+    /// 1x1 PNG
+    const FIXTURE_PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8z8BQDwAEhQGAhKmMIQAAAABJRU5ErkJggg==";
+
+    fn safety_chunk() -> serde_json::Value {
+        json!({
+            "candidates": [{
+                "content": { "role": "model", "parts": [{ "text": "" }] },
+                "finishReason": "IMAGE_SAFETY",
+                "index": 0
+            }]
+        })
+    }
+
+    #[test]
+    fn test_fixture_is_png() {
+        let bytes = base64::engine::general_purpose::STANDARD.decode(FIXTURE_PNG).unwrap();
+        assert_eq!(sniff_image_mime(&bytes), Some("image/png"));
+    }
+
+    #[tokio::test]
+    async fn test_openai_image_round_trip() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4o",
+            "messages": [{
+                "role": "user",
+                "content": [
+                    { "type": "text", "text": "Describe" },
+                    { "type": "image_url", "image_url": { "url": format!("data:image/png;base64,{}", FIXTURE_PNG) } }
+                ]
+            }]
+        }))
+        .unwrap();
+        let body = crate::proxy::mappers::openai::transform_openai_request(&req, "proj", "gemini-2.5-flash");
+        let image = &body["request"]["contents"][0]["parts"][1]["inlineData"];
+        assert_eq!(image["mimeType"], "image/png");
+        assert_eq!(image["data"], FIXTURE_PNG);
+
+        // 非流式：图片安全拦截映射为 content_filter
+        let resp = crate::proxy::mappers::openai::transform_openai_response(&safety_chunk());
+        assert_eq!(resp.choices[0].finish_reason.as_deref(), Some("content_filter"));
+
+        // 流式
+        let sse = format!("data: {}\n\n", safety_chunk());
+        let upstream = futures::stream::iter(vec![Ok::<bytes::Bytes, reqwest::Error>(bytes::Bytes::from(sse))]);
+        let out: Vec<_> = crate::proxy::mappers::openai::streaming::create_openai_sse_stream(Box::pin(upstream), "gpt-4o".to_string(), false)
+            .collect()
+            .await;
+        let text: String = out.into_iter().flatten().map(|b| String::from_utf8_lossy(&b).to_string()).collect();
+        assert!(text.contains("\"finish_reason\":\"content_filter\""));
+        assert!(!text.contains("IMAGE_SAFETY"));
+    }
+
+    #[test]
+    fn test_claude_image_round_trip() {
+        use crate::proxy::mappers::claude::{transform_claude_request_in, transform_response, GeminiResponse, StreamingState};
+
+        let req: ClaudeRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{
+                "role": "user",
+                "content": [
+                    { "type": "image", "source": { "type": "base64", "media_type": "image/png", "data": FIXTURE_PNG } },
+                    { "type": "image", "source": { "type": "url", "url": "https://example.com/cat.webp" } },
+                    { "type": "text", "text": "Describe" }
+                ]
+            }]
+        }))
+        .unwrap();
+        let body = transform_claude_request_in(&req, "proj").unwrap();
+        let parts = body["request"]["contents"][0]["parts"].as_array().unwrap();
+        let inline = parts.iter().find_map(|p| p.get("inlineData")).unwrap();
+        assert_eq!(inline["mimeType"], "image/png");
+        assert_eq!(inline["data"], FIXTURE_PNG);
+        let file = parts.iter().find_map(|p| p.get("fileData")).unwrap();
+        assert_eq!(file["mimeType"], "image/webp");
+
+        // 非流式：图片安全拦截映射为 refusal
+        let gemini: GeminiResponse = serde_json::from_value(safety_chunk()).unwrap();
+        let resp = transform_response(&gemini, false, 1_000_000).unwrap();
+        assert_eq!(resp.stop_reason, "refusal");
+
+        // 流式
+        let mut state = StreamingState::new();
+        let chunks = state.emit_finish(Some("IMAGE_SAFETY"), None);
+        let text: String = chunks.iter().map(|b| String::from_utf8_lossy(b).to_string()).collect();
+        assert!(text.contains("\"stop_reason\":\"refusal\""));
+    }
+
+    #[test]
+    fn test_parse_data_url() {
+        let image = parse_data_url("data:image/png;base64,AAAA").unwrap();
+        assert_eq!(image.mime_type, "image/png");
+        assert_eq!(image.data, "AAAA");
+        assert_eq!(image.to_data_url(), "data:image/png;base64,AAAA");
+        assert!(parse_data_url("https://example.com/a.png").is_none());
+    }
+
+    #[test]
+    fn test_sniff_and_guess_mime() {
+        assert_eq!(sniff_image_mime(&[0x89, b'P', b'N', b'G', 0x0D]), Some("image/png"));
+        assert_eq!(sniff_image_mime(&[0xFF, 0xD8, 0xFF, 0xE0]), Some("image/jpeg"));
+        assert_eq!(sniff_image_mime(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(sniff_image_mime(b"<html>"), None);
+        assert_eq!(guess_mime_from_path("https://x/y.PNG?size=2"), "image/png");
+        assert_eq!(guess_mime_from_path("https://x/y"), "image/jpeg");
+    }
+
+    #[test]
+    fn test_is_public_ip() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "224.0.0.1",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            "64:ff9b::a00:1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["8.8.8.8", "1.1.1.1", "2606:4700:4700::1111", "::ffff:8.8.8.8"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejects_non_public_image_urls() {
+        for url in [
+            "http://127.0.0.1:8045/v1/models",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://localhost/a.png",
+            "http://[::1]/a.png",
+            "http://10.0.0.5/a.png",
+            "http://0x7f000001/a.png",
+        ] {
+            // 错误信息会返回给客户端，不回显 URL 或解析出的地址
+            let err = fetch_remote_image(None, url).await.unwrap_err();
+            assert_eq!(err, "Image url points to a non-public address", "{}", url);
+        }
+        let err = fetch_remote_image(None, "ftp://example.com/a.png").await.unwrap_err();
+        assert_eq!(err, "Unsupported image url scheme");
+    }
+
+    #[test]
+    fn test_is_image_error() {
+        assert!(is_image_error("Provided image is not valid: invalid argument"));
+        assert!(is_image_error("Unsupported MIME type: image/bmp"));
+        assert!(!is_image_error("Resource has been exhausted"));
+    }
+}
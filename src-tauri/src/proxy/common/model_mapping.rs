@@ -132,7 +132,7 @@ pub async fn get_all_dynamic_models(
 /// - `gpt-4*` 匹配 `gpt-4`, `gpt-4-turbo`, `gpt-4-0613` 等
 /// - `claude-3-5-sonnet-*` 匹配所有 3.5 sonnet 版本
 /// - `*-thinking` 匹配所有以 `-thinking` 结尾的模型
-fn wildcard_match(pattern: &str, text: &str) -> bool {
+pub(crate) fn wildcard_match(pattern: &str, text: &str) -> bool {
     if let Some(star_pos) = pattern.find('*') {
         let prefix = &pattern[..star_pos];
         let suffix = &pattern[star_pos + 1..];
@@ -142,6 +142,42 @@ fn wildcard_match(pattern: &str, text: &str) -> bool {
     }
 }
 
+/// 按用户自定义映射解析：精确匹配 > 通配符匹配，未命中返回 None
+fn resolve_custom_route(
+    original_model: &str,
+    custom_mapping: &std::collections::HashMap<String, String>,
+) -> Option<String> {
+    if let Some(target) = custom_mapping.get(original_model) {
+        crate::modules::logger::log_info(&format!("[Router] 精确映射: {} -> {}", original_model, target));
+        return Some(target.clone());
+    }
+
+    for (pattern, target) in custom_mapping.iter() {
+        if pattern.contains('*') && wildcard_match(pattern, original_model) {
+            crate::modules::logger::log_info(&format!("[Router] 通配符映射: {} -> {} (规则: {})", original_model, target, pattern));
+            return Some(target.clone());
+        }
+    }
+    None
+}
+
+/// 未配置映射时 Embeddings 请求使用的默认模型
+pub const DEFAULT_EMBEDDING_MODEL: &str = "gemini-embedding-001";
+
+/// Embeddings 模型路由：自定义映射优先；Gemini 向量模型直接透传，其余 (如 text-embedding-3-small) 使用默认向量模型
+pub fn resolve_embedding_route(
+    original_model: &str,
+    custom_mapping: &std::collections::HashMap<String, String>,
+) -> String {
+    if let Some(target) = resolve_custom_route(original_model, custom_mapping) {
+        return target;
+    }
+    if original_model.starts_with("gemini-embedding") || original_model == "text-embedding-004" {
+        return original_model.to_string();
+    }
+    DEFAULT_EMBEDDING_MODEL.to_string()
+}
+
 /// 核心模型路由解析引擎
 /// 优先级：精确匹配 > 通配符匹配 > 系统默认映射
 /// 
@@ -155,18 +191,9 @@ pub fn resolve_model_route(
     original_model: &str,
     custom_mapping: &std::collections::HashMap<String, String>,
 ) -> String {
-    // 1. 精确匹配 (最高优先级)
-    if let Some(target) = custom_mapping.get(original_model) {
-        crate::modules::logger::log_info(&format!("[Router] 精确映射: {} -> {}", original_model, target));
-        return target.clone();
-    }
-    
-    // 2. 通配符匹配
-    for (pattern, target) in custom_mapping.iter() {
-        if pattern.contains('*') && wildcard_match(pattern, original_model) {
-            crate::modules::logger::log_info(&format!("[Router] 通配符映射: {} -> {} (规则: {})", original_model, target, pattern));
-            return target.clone();
-        }
+    // 1-2. 自定义映射 (精确 > 通配符)
+    if let Some(target) = resolve_custom_route(original_model, custom_mapping) {
+        return target;
     }
     
     // 3. 系统默认映射
@@ -201,4 +228,14 @@ mod tests {
             "claude-sonnet-4-5"
         );
     }
+
+    #[test]
+    fn test_embedding_route() {
+        let mut mapping = HashMap::new();
+        assert_eq!(resolve_embedding_route("text-embedding-3-small", &mapping), DEFAULT_EMBEDDING_MODEL);
+        assert_eq!(resolve_embedding_route("text-embedding-004", &mapping), "text-embedding-004");
+
+        mapping.insert("text-embedding-3-*".to_string(), "text-embedding-004".to_string());
+        assert_eq!(resolve_embedding_route("text-embedding-3-large", &mapping), "text-embedding-004");
+    }
 }
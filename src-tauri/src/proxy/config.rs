@@ -1,8 +1,9 @@
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 // use std::path::PathBuf;
 use std::collections::HashMap;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ProxyAuthMode {
     Off,
@@ -17,7 +18,7 @@ impl Default for ProxyAuthMode {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ZaiDispatchMode {
     /// Never use z.ai.
@@ -36,7 +37,7 @@ impl Default for ZaiDispatchMode {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ZaiModelDefaults {
     /// Default model for "opus" family (when the incoming model is a Claude id).
     #[serde(default = "default_zai_opus_model")]
@@ -59,7 +60,7 @@ impl Default for ZaiModelDefaults {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ZaiMcpConfig {
     #[serde(default)]
     pub enabled: bool,
@@ -82,7 +83,7 @@ impl Default for ZaiMcpConfig {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ZaiConfig {
     #[serde(default)]
     pub enabled: bool,
@@ -117,7 +118,7 @@ impl Default for ZaiConfig {
 }
 
 /// 实验性功能配置 (Feature Flags)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ExperimentalConfig {
     /// 启用双层签名缓存 (Signature Cache)
     #[serde(default = "default_true")]
@@ -135,6 +136,56 @@ pub struct ExperimentalConfig {
     /// 用于解决客户端因 Gemini 上下文过大而错误触发压缩的问题
     #[serde(default = "default_true")]
     pub enable_usage_scaling: bool,
+
+    /// 允许客户端通过 `X-Antigravity-Endpoint` 请求头为单个请求选择上游端点
+    #[serde(default)]
+    pub enable_endpoint_header: bool,
+
+    /// 安全拦截时按客户端协议返回错误，而非带 `content_filter` / `refusal` 标记的空回复
+    #[serde(default)]
+    pub enable_safety_block_errors: bool,
+
+    /// 允许管理员密钥通过 `X-Antigravity-Account` 请求头让单个请求固定使用指定账号
+    #[serde(default)]
+    pub allow_account_pinning: bool,
+
+    /// 将部分请求异步镜像到另一个反代实例，用于迁移前对比
+    #[serde(default)]
+    pub mirror: MirrorConfig,
+}
+
+/// 请求镜像：按比例把客户端请求复制一份发往 `url`，镜像响应只计入统计
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct MirrorConfig {
+    /// 镜像实例的根地址 (如 `http://10.0.0.2:8045`)，为空时关闭
+    #[serde(default)]
+    pub url: String,
+    /// 抽样比例 (0-100)
+    #[serde(default)]
+    pub sample_percent: f64,
+    /// 是否同时镜像流式请求
+    #[serde(default)]
+    pub include_streaming: bool,
+    /// 发往镜像实例的 API 密钥；客户端原有的鉴权头不会转发
+    #[serde(default)]
+    pub api_key: Option<String>,
+}
+
+impl Default for MirrorConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            sample_percent: 0.0,
+            include_streaming: false,
+            api_key: None,
+        }
+    }
+}
+
+impl MirrorConfig {
+    pub fn is_active(&self) -> bool {
+        !self.url.trim().is_empty() && self.sample_percent > 0.0
+    }
 }
 
 impl Default for ExperimentalConfig {
@@ -144,14 +195,168 @@ impl Default for ExperimentalConfig {
             enable_tool_loop_recovery: true,
             enable_cross_model_checks: true,
             enable_usage_scaling: true,
+            enable_endpoint_header: false,
+            enable_safety_block_errors: false,
+            allow_account_pinning: false,
+            mirror: MirrorConfig::default(),
+        }
+    }
+}
+
+/// 单个实验性开关的当前值与说明
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ExperimentalFlag {
+    pub name: String,
+    pub description: String,
+    pub enabled: bool,
+    pub default: bool,
+    /// 修改后是否需要重启反代才能生效
+    pub requires_restart: bool,
+}
+
+impl ExperimentalConfig {
+    /// (名称, 说明, 是否需要重启)；新增开关时需同步补充
+    const FLAGS: &'static [(&'static str, &'static str, bool)] = &[
+        ("enable_signature_cache", "双层签名缓存 (Signature Cache)", false),
+        ("enable_tool_loop_recovery", "工具循环自动恢复 (Tool Loop Recovery)", false),
+        ("enable_cross_model_checks", "跨模型兼容性检查 (Cross-Model Checks)", false),
+        ("enable_usage_scaling", "上下文用量缩放，避免客户端因 Gemini 上下文过大误触发压缩", false),
+        ("enable_endpoint_header", "允许通过 X-Antigravity-Endpoint 请求头为单个请求选择上游端点", false),
+        ("enable_safety_block_errors", "安全拦截时返回协议错误，而非带标记的空回复", false),
+        ("allow_account_pinning", "允许管理员密钥通过 X-Antigravity-Account 请求头固定使用指定账号", false),
+    ];
+
+    fn flag(&self, name: &str) -> Option<bool> {
+        match name {
+            "enable_signature_cache" => Some(self.enable_signature_cache),
+            "enable_tool_loop_recovery" => Some(self.enable_tool_loop_recovery),
+            "enable_cross_model_checks" => Some(self.enable_cross_model_checks),
+            "enable_usage_scaling" => Some(self.enable_usage_scaling),
+            "enable_endpoint_header" => Some(self.enable_endpoint_header),
+            "enable_safety_block_errors" => Some(self.enable_safety_block_errors),
+            "allow_account_pinning" => Some(self.allow_account_pinning),
+            _ => None,
+        }
+    }
+
+    fn flag_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "enable_signature_cache" => Some(&mut self.enable_signature_cache),
+            "enable_tool_loop_recovery" => Some(&mut self.enable_tool_loop_recovery),
+            "enable_cross_model_checks" => Some(&mut self.enable_cross_model_checks),
+            "enable_usage_scaling" => Some(&mut self.enable_usage_scaling),
+            "enable_endpoint_header" => Some(&mut self.enable_endpoint_header),
+            "enable_safety_block_errors" => Some(&mut self.enable_safety_block_errors),
+            "allow_account_pinning" => Some(&mut self.allow_account_pinning),
+            _ => None,
+        }
+    }
+
+    /// 所有开关及其元数据
+    pub fn flags(&self) -> Vec<ExperimentalFlag> {
+        let defaults = Self::default();
+        Self::FLAGS
+            .iter()
+            .map(|(name, description, requires_restart)| ExperimentalFlag {
+                name: name.to_string(),
+                description: description.to_string(),
+                enabled: self.flag(name).unwrap_or_default(),
+                default: defaults.flag(name).unwrap_or_default(),
+                requires_restart: *requires_restart,
+            })
+            .collect()
+    }
+
+    /// 按名称部分更新；存在未知名称时不做任何修改并列出可用名称
+    pub fn apply_updates(&mut self, updates: &HashMap<String, bool>) -> Result<(), String> {
+        let mut unknown: Vec<&str> = updates
+            .keys()
+            .map(String::as_str)
+            .filter(|name| !Self::FLAGS.iter().any(|(flag, _, _)| flag == name))
+            .collect();
+        if !unknown.is_empty() {
+            unknown.sort_unstable();
+            let valid: Vec<&str> = Self::FLAGS.iter().map(|(name, _, _)| *name).collect();
+            return Err(format!(
+                "未知的实验性开关: {}，可选: {}",
+                unknown.join(", "),
+                valid.join(", ")
+            ));
+        }
+        for (name, value) in updates {
+            if let Some(flag) = self.flag_mut(name) {
+                *flag = *value;
+            }
         }
+        Ok(())
     }
 }
 
 fn default_true() -> bool { true }
 
+/// 可选的上游主机；账号设置与请求头只能从中选择，避免被用来请求任意地址 (SSRF)
+pub const ALLOWED_UPSTREAM_HOSTS: &[&str] = &[
+    "cloudcode-pa.googleapis.com",
+    "daily-cloudcode-pa.sandbox.googleapis.com",
+    "autopush-cloudcode-pa.sandbox.googleapis.com",
+];
+
+/// 将端点 (主机名或 `https://主机[/v1internal]`) 规范化为 v1internal 基础地址；不在白名单内时报错
+pub fn normalize_endpoint(raw: &str) -> Result<String, String> {
+    let value = raw.trim().to_ascii_lowercase();
+    let host = match value.split_once("://") {
+        Some(("https", rest)) => rest,
+        Some((scheme, _)) => return Err(format!("不支持的协议 {}，仅支持 https", scheme)),
+        None => value.as_str(),
+    };
+    let host = host.trim_end_matches('/');
+    let host = host.strip_suffix("/v1internal").unwrap_or(host);
+    if ALLOWED_UPSTREAM_HOSTS.contains(&host) {
+        Ok(format!("https://{}/v1internal", host))
+    } else {
+        Err(format!(
+            "不允许的上游端点 {}，可选: {}",
+            raw.trim(),
+            ALLOWED_UPSTREAM_HOSTS.join(", ")
+        ))
+    }
+}
+
+pub(crate) fn validate_url(raw: &str, schemes: &[&str]) -> Result<(), String> {
+    if raw.trim().is_empty() {
+        return Err("地址不能为空".to_string());
+    }
+    let url = url::Url::parse(raw.trim()).map_err(|e| format!("地址格式无效: {}", e))?;
+    if !schemes.contains(&url.scheme()) {
+        return Err(format!("不支持的协议 {}，可选: {}", url.scheme(), schemes.join(", ")));
+    }
+    if url.host_str().is_none() {
+        return Err("地址缺少主机名".to_string());
+    }
+    Ok(())
+}
+
+/// 映射规则：键最多包含一个 `*` 通配符，目标模型不能为空或包含通配符
+fn validate_mapping(mapping: &HashMap<String, String>, prefix: &str, issues: &mut Vec<ConfigIssue>) {
+    let mut keys: Vec<&String> = mapping.keys().collect();
+    keys.sort();
+    for key in keys {
+        let path = format!("{}/{}", prefix, key.replace('~', "~0").replace('/', "~1"));
+        let target = &mapping[key];
+        if key.trim().is_empty() {
+            issues.push(ConfigIssue::new(path, "模型名不能为空"));
+        } else if key.matches('*').count() > 1 {
+            issues.push(ConfigIssue::new(path, "每条规则最多只能包含一个 * 通配符"));
+        } else if target.trim().is_empty() {
+            issues.push(ConfigIssue::new(path, "目标模型不能为空"));
+        } else if target.contains('*') {
+            issues.push(ConfigIssue::new(path, "目标模型不能包含通配符"));
+        }
+    }
+}
+
 /// 反代服务配置
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ProxyConfig {
     /// 是否启用反代服务
     pub enabled: bool,
@@ -162,6 +367,18 @@ pub struct ProxyConfig {
     #[serde(default)]
     pub allow_lan_access: bool,
 
+    /// 允许访问反代的客户端网段 (CIDR)，为空时不限制；`/healthz` 不受限制
+    #[serde(default)]
+    pub allowed_ips: Vec<String>,
+
+    /// 可信反向代理 (CIDR)，仅来自这些地址的请求才采信 `X-Forwarded-For` / `X-Real-IP`
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
+
+    /// 反代 API 的外部访问地址 (如 `https://api.example.com`)，用于反代状态中的 `base_url`；为空时根据管理页面的访问地址推断
+    #[serde(default)]
+    pub public_url: Option<String>,
+
     /// Authorization policy for the proxy.
     /// - off: no auth required
     /// - strict: auth required for all routes
@@ -175,11 +392,35 @@ pub struct ProxyConfig {
     
     /// API 密钥
     pub api_key: String,
+
+    /// 具名 API 密钥 (分发给不同使用者，按密钥统计用量)；`api_key` 仍作为名为 `default` 的密钥生效
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyEntry>,
+
+    /// 账号分组，具名密钥通过 `group` 绑定后只调度分组内的账号
+    #[serde(default)]
+    pub account_groups: Vec<AccountGroup>,
+
+    /// 鉴权失败过多时临时封禁来源 IP
+    #[serde(default)]
+    pub unauthorized_lockout: UnauthorizedLockout,
     
 
     /// 是否自动启动
     pub auto_start: bool,
 
+    /// 启动后按调度顺序预先刷新前 `warmup_accounts` 个账号的 Token，不影响启动结果
+    #[serde(default)]
+    pub warmup: bool,
+
+    /// 启动预热的账号数
+    #[serde(default = "default_warmup_accounts")]
+    pub warmup_accounts: usize,
+
+    /// 预热时为每个账号额外发送一次最小的生成请求 (消耗少量配额)
+    #[serde(default)]
+    pub warmup_probe: bool,
+
     /// 自定义精确模型映射表 (key: 原始模型名, value: 目标模型名)
     #[serde(default)]
     pub custom_mapping: std::collections::HashMap<String, String>,
@@ -188,14 +429,47 @@ pub struct ProxyConfig {
     #[serde(default = "default_request_timeout")]
     pub request_timeout: u64,
 
+    /// 按路由覆盖的上游请求超时，未设置的项使用 `request_timeout`
+    #[serde(default)]
+    pub timeouts: TimeoutOverrides,
+
     /// 是否开启请求日志记录 (监控)
     #[serde(default)]
     pub enable_logging: bool,
 
+    /// 内存中保留的最近请求条数
+    #[serde(default = "default_monitor_buffer_size")]
+    pub monitor_buffer_size: usize,
+
+    /// 内存中请求记录的估算占用上限 (MB)，超出时淘汰最旧的记录；0 表示不限制
+    #[serde(default)]
+    pub monitor_max_memory_mb: u64,
+
     /// 上游代理配置
     #[serde(default)]
     pub upstream_proxy: UpstreamProxyConfig,
 
+    /// 自定义 DNS 解析 (同 `curl --resolve`)，对反代与 OAuth / 配额请求均生效
+    #[serde(default)]
+    pub dns_overrides: Vec<DnsOverride>,
+
+    /// 默认上游端点 (主机名，须在 `ALLOWED_UPSTREAM_HOSTS` 中)，为空时按 prod → daily 顺序回退；
+    /// 账号设置与请求头可以覆盖
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream_endpoint: Option<String>,
+
+    /// 上游连接池中每个主机保留的最大空闲连接数
+    #[serde(default = "default_upstream_pool_max_idle_per_host")]
+    pub upstream_pool_max_idle_per_host: usize,
+
+    /// 上游空闲连接的保留时间 (秒)
+    #[serde(default = "default_upstream_pool_idle_timeout_secs")]
+    pub upstream_pool_idle_timeout_secs: u64,
+
+    /// 上游连接的 TCP 保活探测间隔 (秒)，0 表示关闭
+    #[serde(default = "default_tcp_keepalive_secs")]
+    pub tcp_keepalive_secs: u64,
+
     /// z.ai provider configuration (Anthropic-compatible).
     #[serde(default)]
     pub zai: ZaiConfig,
@@ -207,10 +481,247 @@ pub struct ProxyConfig {
     /// 实验性功能配置
     #[serde(default)]
     pub experimental: ExperimentalConfig,
+
+    /// 系统提示词规则，按顺序对命中的请求改写 system 提示词
+    #[serde(default)]
+    pub prompt_rules: Vec<PromptRule>,
+
+    /// 反代响应的上游响应头透传与固定响应头注入
+    #[serde(default)]
+    pub response_headers: ResponseHeadersConfig,
+}
+
+/// 反代响应头配置
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ResponseHeadersConfig {
+    /// 原样透传给客户端的上游响应头名 (不区分大小写)
+    #[serde(default)]
+    pub passthrough: Vec<String>,
+    /// 注入到每个反代响应的固定响应头；与透传或反代自身的响应头同名时以此为准
+    #[serde(default)]
+    pub inject: std::collections::BTreeMap<String, String>,
+}
+
+/// 系统提示词规则
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PromptRule {
+    /// 规则名，记录在请求日志中；为空时使用 `#序号`
+    #[serde(default)]
+    pub name: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 匹配条件，未设置的条件视为全部匹配
+    #[serde(default, rename = "match")]
+    pub matcher: PromptRuleMatch,
+    pub action: PromptRuleAction,
+    pub text: String,
+}
+
+/// 提示词规则的匹配条件
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PromptRuleMatch {
+    /// 客户端请求的模型名，支持一个 `*` 通配符
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// 具名 API 密钥的名称 (`api_key` 对应 `default`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptRuleAction {
+    /// 插入到客户端 system 提示词之前
+    PrependSystem,
+    /// 追加到客户端 system 提示词之后
+    AppendSystem,
+    /// 替换客户端 system 提示词
+    ReplaceSystem,
+}
+
+impl PromptRule {
+    /// 日志中记录的规则名
+    pub fn label(&self, index: usize) -> String {
+        if self.name.trim().is_empty() {
+            format!("#{}", index + 1)
+        } else {
+            self.name.trim().to_string()
+        }
+    }
+
+    pub fn matches(&self, model: Option<&str>, api_key_name: Option<&str>) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        if let Some(pattern) = self.matcher.model.as_deref().filter(|p| !p.trim().is_empty()) {
+            match model {
+                Some(model) if crate::proxy::common::model_mapping::wildcard_match(pattern.trim(), model) => {}
+                _ => return false,
+            }
+        }
+        if let Some(key) = self.matcher.api_key.as_deref().filter(|k| !k.trim().is_empty()) {
+            if api_key_name != Some(key.trim()) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// 具名 API 密钥
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ApiKeyEntry {
+    pub name: String,
+    pub key: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 每分钟最大请求数，未设置时不限制
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+    /// 最大并发请求数，未设置时不限制
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub concurrent_requests: Option<u32>,
+    /// 绑定的账号分组，未设置时使用全部账号
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// 管理员密钥，可使用调试用的请求头 (如 `X-Antigravity-Account`)
+    #[serde(default)]
+    pub admin: bool,
+}
+
+/// 账号分组：带有任一所列标签的账号属于该分组
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct AccountGroup {
+    pub name: String,
+    pub tags: Vec<String>,
+}
+
+/// 鉴权失败封禁：同一 IP 连续失败 `ban_after` 次后封禁 `ban_minutes` 分钟 (本机地址除外)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct UnauthorizedLockout {
+    /// 连续鉴权失败多少次后封禁，0 表示只记录不封禁
+    #[serde(default)]
+    pub ban_after: u32,
+    /// 封禁时长 (分钟)
+    #[serde(default = "default_ban_minutes")]
+    pub ban_minutes: u32,
+}
+
+fn default_ban_minutes() -> u32 {
+    60
+}
+
+impl Default for UnauthorizedLockout {
+    fn default() -> Self {
+        Self {
+            ban_after: 0,
+            ban_minutes: default_ban_minutes(),
+        }
+    }
+}
+
+/// 按路由覆盖的上游请求超时 (秒)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TimeoutOverrides {
+    /// 非流式对话 / 生成请求
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat: Option<u64>,
+    /// 流式对话 / 生成请求
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat_stream: Option<u64>,
+    /// 向量嵌入
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embeddings: Option<u64>,
+    /// Token 计数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count_tokens: Option<u64>,
+    /// 模型列表
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub models: Option<u64>,
+}
+
+/// 上游请求的路由类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutRoute {
+    Chat,
+    Embeddings,
+    CountTokens,
+    Models,
+}
+
+impl TimeoutRoute {
+    /// 由 v1internal 方法名推断路由类别及是否流式
+    pub fn from_upstream_method(method: &str) -> (Self, bool) {
+        match method {
+            "streamGenerateContent" => (Self::Chat, true),
+            "batchEmbedContents" | "embedContent" => (Self::Embeddings, false),
+            "countTokens" => (Self::CountTokens, false),
+            "fetchAvailableModels" => (Self::Models, false),
+            _ => (Self::Chat, false),
+        }
+    }
 }
 
+/// 流式请求未单独配置时的最低超时 (秒)，避免长时间生成被 `request_timeout` 截断
+pub const MIN_STREAM_TIMEOUT: u64 = 600;
+
+impl TimeoutOverrides {
+    /// 计算实际生效的超时 (秒)
+    pub fn effective(&self, route: TimeoutRoute, stream: bool, request_timeout: u64) -> u64 {
+        let value = match route {
+            TimeoutRoute::Chat if stream => self.chat_stream,
+            TimeoutRoute::Chat => self.chat,
+            TimeoutRoute::Embeddings => self.embeddings,
+            TimeoutRoute::CountTokens => self.count_tokens,
+            TimeoutRoute::Models => self.models,
+        };
+        value.unwrap_or(if stream {
+            request_timeout.max(MIN_STREAM_TIMEOUT)
+        } else {
+            request_timeout
+        })
+    }
+
+    fn entries(&self) -> [(&'static str, Option<u64>); 5] {
+        [
+            ("chat", self.chat),
+            ("chat_stream", self.chat_stream),
+            ("embeddings", self.embeddings),
+            ("count_tokens", self.count_tokens),
+            ("models", self.models),
+        ]
+    }
+}
+
+/// 自定义 DNS 解析条目，同一主机的多条记录依次作为候选地址
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DnsOverride {
+    /// 主机名，如 `cloudcode-pa.googleapis.com`
+    pub host: String,
+    pub ip: String,
+    /// 连接端口，未设置时使用协议默认端口；URL 中显式指定的端口优先
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+}
+
+impl DnsOverride {
+    /// 规范化的主机名 (小写、去空白)
+    pub fn host_key(&self) -> String {
+        self.host.trim().trim_end_matches('.').to_ascii_lowercase()
+    }
+
+    /// 覆盖到的地址；端口 0 表示沿用协议默认端口
+    pub fn socket_addr(&self) -> Option<std::net::SocketAddr> {
+        let ip: std::net::IpAddr = self.ip.trim().parse().ok()?;
+        Some(std::net::SocketAddr::new(ip, self.port.unwrap_or(0)))
+    }
+}
+
+/// 旧版单一 `api_key` 对应的密钥名
+pub const DEFAULT_API_KEY_NAME: &str = "default";
+
 /// 上游代理配置
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default, ToSchema)]
 pub struct UpstreamProxyConfig {
     /// 是否启用
     pub enabled: bool,
@@ -218,22 +729,46 @@ pub struct UpstreamProxyConfig {
     pub url: String,
 }
 
+/// 生成新的反代 API 密钥
+pub fn new_api_key() -> String {
+    format!("sk-{}", uuid::Uuid::new_v4().simple())
+}
+
 impl Default for ProxyConfig {
     fn default() -> Self {
         Self {
             enabled: false,
             allow_lan_access: true, // 默认允许局域网访问
+            allowed_ips: Vec::new(),
+            trusted_proxies: Vec::new(),
+            public_url: None,
             auth_mode: ProxyAuthMode::default(),
             port: 8045,
-            api_key: format!("sk-{}", uuid::Uuid::new_v4().simple()),
+            api_key: new_api_key(),
+            api_keys: Vec::new(),
+            account_groups: Vec::new(),
+            unauthorized_lockout: UnauthorizedLockout::default(),
             auto_start: true,
+            warmup: false,
+            warmup_accounts: default_warmup_accounts(),
+            warmup_probe: false,
             custom_mapping: std::collections::HashMap::new(),
             request_timeout: default_request_timeout(),
+            timeouts: TimeoutOverrides::default(),
             enable_logging: false, // 默认关闭，节省性能
+            monitor_buffer_size: default_monitor_buffer_size(),
+            monitor_max_memory_mb: 0,
             upstream_proxy: UpstreamProxyConfig::default(),
+            dns_overrides: Vec::new(),
+            upstream_endpoint: None,
+            upstream_pool_max_idle_per_host: default_upstream_pool_max_idle_per_host(),
+            upstream_pool_idle_timeout_secs: default_upstream_pool_idle_timeout_secs(),
+            tcp_keepalive_secs: default_tcp_keepalive_secs(),
             zai: ZaiConfig::default(),
             scheduling: crate::proxy::sticky_config::StickySessionConfig::default(),
             experimental: ExperimentalConfig::default(),
+            prompt_rules: Vec::new(),
+            response_headers: ResponseHeadersConfig::default(),
         }
     }
 }
@@ -242,6 +777,26 @@ fn default_request_timeout() -> u64 {
     120  // 默认 120 秒,原来 60 秒太短
 }
 
+fn default_monitor_buffer_size() -> usize {
+    1000
+}
+
+fn default_warmup_accounts() -> usize {
+    3
+}
+
+fn default_upstream_pool_max_idle_per_host() -> usize {
+    16
+}
+
+fn default_upstream_pool_idle_timeout_secs() -> u64 {
+    90
+}
+
+fn default_tcp_keepalive_secs() -> u64 {
+    60
+}
+
 fn default_zai_base_url() -> String {
     "https://api.z.ai/api/anthropic".to_string()
 }
@@ -258,7 +813,259 @@ fn default_zai_haiku_model() -> String {
     "glm-4.5-air".to_string()
 }
 
+/// 配置校验问题
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct ConfigIssue {
+    /// 出错字段的 JSON Pointer (如 `/proxy/port`)
+    pub path: String,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    pub fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
+
+    /// 将问题列表拼成一行错误信息
+    pub fn summarize(issues: &[ConfigIssue]) -> String {
+        let details: Vec<String> = issues
+            .iter()
+            .map(|issue| format!("{}: {}", issue.path, issue.message))
+            .collect();
+        format!("配置校验失败: {}", details.join("; "))
+    }
+}
+
+/// 请求超时上限 (秒)
+const MAX_REQUEST_TIMEOUT: u64 = 3600;
+/// 监控缓冲条数上限
+const MAX_MONITOR_BUFFER_SIZE: usize = 100_000;
+/// 启动预热账号数上限
+const MAX_WARMUP_ACCOUNTS: usize = 50;
+/// 每主机空闲连接数上限
+const MAX_POOL_IDLE_PER_HOST: usize = 1024;
+/// 空闲连接保留时间 / TCP 保活间隔上限 (秒)
+const MAX_CONNECTION_SECS: u64 = 7200;
+
 impl ProxyConfig {
+    /// 校验配置，`prefix` 为本配置在整体配置中的 JSON Pointer 前缀 (如 `/proxy`)
+    pub fn validate(&self, prefix: &str) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+
+        if self.port == 0 {
+            issues.push(ConfigIssue::new(format!("{}/port", prefix), "端口必须在 1-65535 之间"));
+        }
+
+        if !(1..=MAX_REQUEST_TIMEOUT).contains(&self.request_timeout) {
+            issues.push(ConfigIssue::new(
+                format!("{}/request_timeout", prefix),
+                format!("超时时间必须在 1-{} 秒之间", MAX_REQUEST_TIMEOUT),
+            ));
+        }
+
+        for (key, value) in self.timeouts.entries() {
+            if let Some(secs) = value {
+                if !(1..=MAX_REQUEST_TIMEOUT).contains(&secs) {
+                    issues.push(ConfigIssue::new(
+                        format!("{}/timeouts/{}", prefix, key),
+                        format!("超时时间必须在 1-{} 秒之间", MAX_REQUEST_TIMEOUT),
+                    ));
+                }
+            }
+        }
+
+        if !(1..=MAX_MONITOR_BUFFER_SIZE).contains(&self.monitor_buffer_size) {
+            issues.push(ConfigIssue::new(
+                format!("{}/monitor_buffer_size", prefix),
+                format!("监控缓冲条数必须在 1-{} 之间", MAX_MONITOR_BUFFER_SIZE),
+            ));
+        }
+
+        if self.warmup && !(1..=MAX_WARMUP_ACCOUNTS).contains(&self.warmup_accounts) {
+            issues.push(ConfigIssue::new(
+                format!("{}/warmup_accounts", prefix),
+                format!("预热账号数必须在 1-{} 之间", MAX_WARMUP_ACCOUNTS),
+            ));
+        }
+
+        for (i, entry) in self.dns_overrides.iter().enumerate() {
+            let path = format!("{}/dns_overrides/{}", prefix, i);
+            let host = entry.host_key();
+            if host.is_empty() {
+                issues.push(ConfigIssue::new(format!("{}/host", path), "主机名不能为空"));
+            } else if host.contains(|c: char| c.is_whitespace() || c == '/' || c == ':') {
+                issues.push(ConfigIssue::new(format!("{}/host", path), "主机名只能包含域名，不能带协议、端口或路径"));
+            }
+            if entry.ip.trim().parse::<std::net::IpAddr>().is_err() {
+                issues.push(ConfigIssue::new(format!("{}/ip", path), format!("无效的 IP 地址: {}", entry.ip)));
+            }
+            if entry.port == Some(0) {
+                issues.push(ConfigIssue::new(format!("{}/port", path), "端口必须在 1-65535 之间"));
+            }
+        }
+
+        if let Some(endpoint) = &self.upstream_endpoint {
+            if let Err(e) = normalize_endpoint(endpoint) {
+                issues.push(ConfigIssue::new(format!("{}/upstream_endpoint", prefix), e));
+            }
+        }
+
+        if self.upstream_pool_max_idle_per_host > MAX_POOL_IDLE_PER_HOST {
+            issues.push(ConfigIssue::new(
+                format!("{}/upstream_pool_max_idle_per_host", prefix),
+                format!("空闲连接数必须在 0-{} 之间", MAX_POOL_IDLE_PER_HOST),
+            ));
+        }
+        if !(1..=MAX_CONNECTION_SECS).contains(&self.upstream_pool_idle_timeout_secs) {
+            issues.push(ConfigIssue::new(
+                format!("{}/upstream_pool_idle_timeout_secs", prefix),
+                format!("空闲连接保留时间必须在 1-{} 秒之间", MAX_CONNECTION_SECS),
+            ));
+        }
+        if self.tcp_keepalive_secs > MAX_CONNECTION_SECS {
+            issues.push(ConfigIssue::new(
+                format!("{}/tcp_keepalive_secs", prefix),
+                format!("TCP 保活间隔必须在 0-{} 秒之间", MAX_CONNECTION_SECS),
+            ));
+        }
+
+        let auth_required = match self.auth_mode {
+            ProxyAuthMode::Off => false,
+            ProxyAuthMode::Strict | ProxyAuthMode::AllExceptHealth => true,
+            ProxyAuthMode::Auto => self.allow_lan_access,
+        };
+        let has_named_key = self.api_keys.iter().any(|k| k.enabled);
+        if auth_required && self.api_key.trim().is_empty() && !has_named_key {
+            issues.push(ConfigIssue::new(format!("{}/api_key", prefix), "启用鉴权时 API 密钥不能为空"));
+        }
+        for (i, entry) in self.api_keys.iter().enumerate() {
+            let path = format!("{}/api_keys/{}", prefix, i);
+            let name = entry.name.trim();
+            if name.is_empty() {
+                issues.push(ConfigIssue::new(format!("{}/name", path), "密钥名称不能为空"));
+            } else if name == DEFAULT_API_KEY_NAME
+                || self.api_keys[..i].iter().any(|other| other.name.trim() == name)
+            {
+                issues.push(ConfigIssue::new(format!("{}/name", path), format!("密钥名称 {} 重复", name)));
+            }
+            if entry.key.trim().is_empty() {
+                issues.push(ConfigIssue::new(format!("{}/key", path), "密钥不能为空"));
+            } else if entry.key == self.api_key || self.api_keys[..i].iter().any(|other| other.key == entry.key) {
+                issues.push(ConfigIssue::new(format!("{}/key", path), "密钥与其他密钥重复"));
+            }
+            if entry.requests_per_minute == Some(0) {
+                issues.push(ConfigIssue::new(format!("{}/requests_per_minute", path), "每分钟请求数必须大于 0"));
+            }
+            if entry.concurrent_requests == Some(0) {
+                issues.push(ConfigIssue::new(format!("{}/concurrent_requests", path), "并发请求数必须大于 0"));
+            }
+            if let Some(group) = &entry.group {
+                if !self.account_groups.iter().any(|g| g.name == *group) {
+                    issues.push(ConfigIssue::new(format!("{}/group", path), format!("账号分组 {} 不存在", group)));
+                }
+            }
+        }
+        for (i, group) in self.account_groups.iter().enumerate() {
+            let path = format!("{}/account_groups/{}", prefix, i);
+            let name = group.name.trim();
+            if name.is_empty() {
+                issues.push(ConfigIssue::new(format!("{}/name", path), "分组名称不能为空"));
+            } else if self.account_groups[..i].iter().any(|other| other.name.trim() == name) {
+                issues.push(ConfigIssue::new(format!("{}/name", path), format!("分组名称 {} 重复", name)));
+            }
+            if group.tags.iter().all(|tag| tag.trim().is_empty()) {
+                issues.push(ConfigIssue::new(format!("{}/tags", path), "分组至少需要一个标签"));
+            }
+        }
+
+        if self.unauthorized_lockout.ban_after > 0 && !(1..=10080).contains(&self.unauthorized_lockout.ban_minutes) {
+            issues.push(ConfigIssue::new(
+                format!("{}/unauthorized_lockout/ban_minutes", prefix),
+                "封禁时长必须在 1-10080 分钟之间",
+            ));
+        }
+
+        for (field, list) in [("allowed_ips", &self.allowed_ips), ("trusted_proxies", &self.trusted_proxies)] {
+            for (i, message) in crate::proxy::ip_filter::validate_cidrs(list) {
+                issues.push(ConfigIssue::new(format!("{}/{}/{}", prefix, field, i), message));
+            }
+        }
+        if let Some(url) = self.public_url.as_deref().filter(|u| !u.trim().is_empty()) {
+            if let Err(message) = validate_url(url, &["http", "https"]) {
+                issues.push(ConfigIssue::new(format!("{}/public_url", prefix), message));
+            }
+        }
+
+        let mirror = &self.experimental.mirror;
+        if !mirror.url.trim().is_empty() {
+            if let Err(message) = validate_url(&mirror.url, &["http", "https"]) {
+                issues.push(ConfigIssue::new(format!("{}/experimental/mirror/url", prefix), message));
+            }
+        }
+        if !mirror.sample_percent.is_finite() || !(0.0..=100.0).contains(&mirror.sample_percent) {
+            issues.push(ConfigIssue::new(
+                format!("{}/experimental/mirror/sample_percent", prefix),
+                "抽样比例必须在 0-100 之间",
+            ));
+        }
+
+        if self.upstream_proxy.enabled {
+            if let Err(message) = validate_url(&self.upstream_proxy.url, &["http", "https", "socks5", "socks5h"]) {
+                issues.push(ConfigIssue::new(format!("{}/upstream_proxy/url", prefix), message));
+            }
+        }
+
+        if self.zai.enabled {
+            if let Err(message) = validate_url(&self.zai.base_url, &["http", "https"]) {
+                issues.push(ConfigIssue::new(format!("{}/zai/base_url", prefix), message));
+            }
+            if self.zai.dispatch_mode != ZaiDispatchMode::Off && self.zai.api_key.trim().is_empty() {
+                issues.push(ConfigIssue::new(format!("{}/zai/api_key", prefix), "启用 z.ai 时 API 密钥不能为空"));
+            }
+        }
+
+        for (i, rule) in self.prompt_rules.iter().enumerate() {
+            let path = format!("{}/prompt_rules/{}", prefix, i);
+            if rule.text.trim().is_empty() {
+                issues.push(ConfigIssue::new(format!("{}/text", path), "提示词不能为空"));
+            }
+            if rule.matcher.model.as_deref().is_some_and(|m| m.matches('*').count() > 1) {
+                issues.push(ConfigIssue::new(format!("{}/match/model", path), "模型匹配最多只能包含一个 * 通配符"));
+            }
+            let name = rule.name.trim();
+            if !name.is_empty() && self.prompt_rules[..i].iter().any(|other| other.name.trim() == name) {
+                issues.push(ConfigIssue::new(format!("{}/name", path), format!("规则名称 {} 重复", name)));
+            }
+        }
+
+        let response_headers = self.response_headers.passthrough.iter().enumerate().map(|(i, name)| (format!("passthrough/{}", i), name));
+        let injected = self.response_headers.inject.keys().map(|name| (format!("inject/{}", name), name));
+        for (key, name) in response_headers.chain(injected) {
+            let path = format!("{}/response_headers/{}", prefix, key);
+            if axum::http::HeaderName::from_bytes(name.trim().as_bytes()).is_err() {
+                issues.push(ConfigIssue::new(path, format!("无效的响应头名: {}", name)));
+            } else if crate::proxy::middleware::response_headers::is_filtered_header(name) {
+                issues.push(ConfigIssue::new(path, format!("{} 为逐跳或由反代生成的响应头，不能透传或注入", name)));
+            }
+        }
+        for (name, value) in &self.response_headers.inject {
+            if axum::http::HeaderValue::from_str(value).is_err() {
+                issues.push(ConfigIssue::new(
+                    format!("{}/response_headers/inject/{}", prefix, name),
+                    "响应头的值包含非法字符",
+                ));
+            }
+        }
+
+        validate_mapping(&self.custom_mapping, &format!("{}/custom_mapping", prefix), &mut issues);
+        validate_mapping(&self.zai.model_mapping, &format!("{}/zai/model_mapping", prefix), &mut issues);
+
+        issues
+    }
+
     /// 获取实际的监听地址
     /// - allow_lan_access = false: 返回 "127.0.0.1"（默认，隐私优先）
     /// - allow_lan_access = true: 返回 "0.0.0.0"（允许局域网访问）
@@ -269,4 +1076,202 @@ impl ProxyConfig {
             "127.0.0.1"
         }
     }
+
+    /// 反代监听的完整地址，与管理 API 的监听地址相互独立
+    pub fn bind_socket_addr(&self) -> std::net::SocketAddr {
+        let ip = self
+            .get_bind_address()
+            .parse()
+            .unwrap_or(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST));
+        std::net::SocketAddr::new(ip, self.port)
+    }
+}
+
+/// 两个监听地址是否会争用同一端口：端口相同，且地址相同或任一方为通配地址
+pub fn listeners_conflict(a: std::net::SocketAddr, b: std::net::SocketAddr) -> bool {
+    a.port() == b.port() && (a.ip() == b.ip() || a.ip().is_unspecified() || b.ip().is_unspecified())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_effective_timeout() {
+        let overrides = TimeoutOverrides {
+            embeddings: Some(30),
+            chat_stream: Some(1200),
+            ..Default::default()
+        };
+        assert_eq!(overrides.effective(TimeoutRoute::Embeddings, false, 120), 30);
+        assert_eq!(overrides.effective(TimeoutRoute::Chat, true, 120), 1200);
+        assert_eq!(overrides.effective(TimeoutRoute::Chat, false, 120), 120);
+        assert_eq!(overrides.effective(TimeoutRoute::Models, false, 120), 120);
+        // 未配置时流式请求至少保留 MIN_STREAM_TIMEOUT
+        assert_eq!(TimeoutOverrides::default().effective(TimeoutRoute::Chat, true, 120), MIN_STREAM_TIMEOUT);
+
+        assert_eq!(TimeoutRoute::from_upstream_method("streamGenerateContent"), (TimeoutRoute::Chat, true));
+        assert_eq!(TimeoutRoute::from_upstream_method("countTokens"), (TimeoutRoute::CountTokens, false));
+    }
+
+    #[test]
+    fn test_validate_timeouts() {
+        let mut config = ProxyConfig::default();
+        config.timeouts.embeddings = Some(0);
+        config.timeouts.chat_stream = Some(MAX_REQUEST_TIMEOUT + 1);
+        config.timeouts.models = Some(10);
+        let paths: Vec<String> = config.validate("/proxy").into_iter().map(|issue| issue.path).collect();
+        assert_eq!(paths, vec!["/proxy/timeouts/chat_stream", "/proxy/timeouts/embeddings"]);
+    }
+
+    #[test]
+    fn test_validate_warmup_accounts() {
+        let mut config = ProxyConfig {
+            warmup_accounts: 0,
+            ..Default::default()
+        };
+        assert!(config.validate("/proxy").is_empty());
+        config.warmup = true;
+        let paths: Vec<String> = config.validate("/proxy").into_iter().map(|issue| issue.path).collect();
+        assert_eq!(paths, vec!["/proxy/warmup_accounts"]);
+        config.warmup_accounts = MAX_WARMUP_ACCOUNTS;
+        assert!(config.validate("/proxy").is_empty());
+    }
+
+    #[test]
+    fn test_validate_dns_overrides() {
+        let entry = |host: &str, ip: &str, port: Option<u16>| DnsOverride {
+            host: host.to_string(),
+            ip: ip.to_string(),
+            port,
+        };
+        let config = ProxyConfig {
+            dns_overrides: vec![
+                entry("cloudcode-pa.googleapis.com", "142.250.72.10", None),
+                entry("oauth2.googleapis.com", "2607:f8b0:4005:80c::200a", Some(443)),
+                entry("", "1.2.3.4", None),
+                entry("https://example.com", "not-an-ip", Some(0)),
+            ],
+            ..Default::default()
+        };
+        let paths: Vec<String> = config.validate("/proxy").into_iter().map(|issue| issue.path).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "/proxy/dns_overrides/2/host",
+                "/proxy/dns_overrides/3/host",
+                "/proxy/dns_overrides/3/ip",
+                "/proxy/dns_overrides/3/port",
+            ]
+        );
+        assert_eq!(config.dns_overrides[1].socket_addr().unwrap().port(), 443);
+        assert_eq!(config.dns_overrides[0].socket_addr().unwrap().port(), 0);
+    }
+
+    #[test]
+    fn test_validate_pool_settings() {
+        let config = ProxyConfig {
+            upstream_pool_max_idle_per_host: MAX_POOL_IDLE_PER_HOST + 1,
+            upstream_pool_idle_timeout_secs: 0,
+            tcp_keepalive_secs: 0,
+            ..Default::default()
+        };
+        let paths: Vec<String> = config.validate("/proxy").into_iter().map(|issue| issue.path).collect();
+        assert_eq!(
+            paths,
+            vec!["/proxy/upstream_pool_max_idle_per_host", "/proxy/upstream_pool_idle_timeout_secs"]
+        );
+    }
+
+    #[test]
+    fn test_validate_account_groups() {
+        let group = |name: &str, tags: &[&str]| AccountGroup {
+            name: name.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+        };
+        let key = |name: &str, group: Option<&str>| ApiKeyEntry {
+            name: name.to_string(),
+            key: format!("sk-{}", name),
+            enabled: true,
+            requests_per_minute: None,
+            concurrent_requests: None,
+            group: group.map(|g| g.to_string()),
+            admin: false,
+        };
+        let config = ProxyConfig {
+            account_groups: vec![group("team-a", &["a"]), group("team-a", &[" "])],
+            api_keys: vec![key("alice", Some("team-a")), key("bob", Some("missing")), key("carol", None)],
+            ..Default::default()
+        };
+        let paths: Vec<String> = config.validate("/proxy").into_iter().map(|issue| issue.path).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "/proxy/api_keys/1/group",
+                "/proxy/account_groups/1/name",
+                "/proxy/account_groups/1/tags",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_normalize_endpoint() {
+        let daily = "https://daily-cloudcode-pa.sandbox.googleapis.com/v1internal";
+        assert_eq!(normalize_endpoint("daily-cloudcode-pa.sandbox.googleapis.com").unwrap(), daily);
+        assert_eq!(normalize_endpoint(" https://Daily-Cloudcode-PA.sandbox.googleapis.com/ ").unwrap(), daily);
+        assert_eq!(normalize_endpoint(daily).unwrap(), daily);
+        assert!(normalize_endpoint("http://cloudcode-pa.googleapis.com").is_err());
+        assert!(normalize_endpoint("169.254.169.254").is_err());
+        assert!(normalize_endpoint("cloudcode-pa.googleapis.com.evil.com").is_err());
+        assert!(normalize_endpoint("cloudcode-pa.googleapis.com/other").is_err());
+        assert!(normalize_endpoint("cloudcode-pa.googleapis.com@evil.com").is_err());
+    }
+
+    #[test]
+    fn test_experimental_flags_cover_all_fields() {
+        let fields = serde_json::to_value(ExperimentalConfig::default()).unwrap();
+        let fields: Vec<&String> = fields.as_object().unwrap().iter().filter(|(_, v)| v.is_boolean()).map(|(k, _)| k).collect();
+        let flags = ExperimentalConfig::default().flags();
+        assert_eq!(flags.len(), fields.len());
+        for flag in &flags {
+            assert!(fields.contains(&&flag.name), "{}", flag.name);
+        }
+    }
+
+    #[test]
+    fn test_experimental_partial_update() {
+        let mut config = ExperimentalConfig::default();
+        let updates = HashMap::from([("enable_usage_scaling".to_string(), false)]);
+        config.apply_updates(&updates).unwrap();
+        assert!(!config.enable_usage_scaling);
+        assert!(config.enable_tool_loop_recovery);
+        let flag = config.flags().into_iter().find(|f| f.name == "enable_usage_scaling").unwrap();
+        assert!(!flag.enabled && flag.default);
+
+        let updates = HashMap::from([
+            ("enable_usage_scaling".to_string(), true),
+            ("enable_turbo".to_string(), true),
+        ]);
+        let err = config.apply_updates(&updates).unwrap_err();
+        assert!(err.contains("enable_turbo") && err.contains("enable_signature_cache"));
+        assert!(!config.enable_usage_scaling);
+    }
+
+    #[test]
+    fn test_listeners_conflict() {
+        let addr = |s: &str| s.parse::<std::net::SocketAddr>().unwrap();
+        assert!(listeners_conflict(addr("127.0.0.1:8045"), addr("127.0.0.1:8045")));
+        assert!(listeners_conflict(addr("0.0.0.0:8045"), addr("127.0.0.1:8045")));
+        assert!(!listeners_conflict(addr("0.0.0.0:8045"), addr("0.0.0.0:8765")));
+        assert!(!listeners_conflict(addr("127.0.0.1:8765"), addr("192.168.1.5:8765")));
+
+        let mut config = ProxyConfig {
+            port: 8765,
+            allow_lan_access: false,
+            ..Default::default()
+        };
+        assert_eq!(config.bind_socket_addr(), addr("127.0.0.1:8765"));
+        config.allow_lan_access = true;
+        assert!(listeners_conflict(config.bind_socket_addr(), addr("127.0.0.1:8765")));
+    }
 }
@@ -14,7 +14,7 @@ use tracing::{debug, error, info};
 
 use crate::proxy::mappers::claude::{
     transform_claude_request_in, transform_response, create_claude_sse_stream, ClaudeRequest,
-    close_tool_loop_for_thinking,
+    close_tool_loop_for_thinking, token_count,
 };
 use crate::proxy::server::AppState;
 use axum::http::HeaderMap;
@@ -398,9 +398,25 @@ pub async fn handle_messages(
     
     // Google Flow 继续使用 request 对象
     // (后续代码不需要再次 filter_invalid_thinking_blocks)
+
+    // 上游只接受内联图片，预先下载 URL 图片
+    let upstream_proxy = state.upstream_proxy.read().await.clone();
+    if let Err(e) = crate::proxy::common::image::inline_claude_images(&mut request, Some(&upstream_proxy)).await {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "type": "error",
+                "error": {
+                    "type": "invalid_request_error",
+                    "message": e
+                }
+            }))
+        ).into_response();
+    }
     
     // [NEW] 获取上下文缩放配置
     let scaling_enabled = state.experimental.read().await.enable_usage_scaling;
+    let block_as_error = state.experimental.read().await.enable_safety_block_errors;
 
     // 获取最新一条“有意义”的消息内容（用于日志记录和后台任务检测）
     // 策略：反向遍历，首先筛选出所有角色为 "user" 的消息，然后从中找到第一条非 "Warmup" 且非空的文本消息
@@ -686,7 +702,9 @@ pub async fn handle_messages(
                     email.clone(),
                     Some(session_id_str.clone()),
                     scaling_enabled,
-                    context_limit
+                    context_limit,
+                    // 非流式客户端在收集完成后再转为错误响应
+                    block_as_error && client_wants_stream,
                 );
 
                 // [FIX #530/#529] Peek first chunk to detect empty response and allow retry
@@ -730,6 +748,20 @@ pub async fn handle_messages(
                             match collect_stream_to_json(combined_stream).await {
                                 Ok(full_response) => {
                                     info!("[{}] ✓ Stream collected and converted to JSON", trace_id);
+                                    if block_as_error && full_response.stop_reason == "refusal" && full_response.content.is_empty() {
+                                        return (
+                                            StatusCode::BAD_REQUEST,
+                                            [("X-Account-Email", email.as_str()), ("X-Mapped-Model", request_with_mapped.model.as_str())],
+                                            Json(json!({
+                                                "type": "error",
+                                                "error": {
+                                                    "type": "invalid_request_error",
+                                                    "message": crate::proxy::mappers::common_utils::safety_block_message("refusal"),
+                                                    "code": "content_filter"
+                                                }
+                                            }))
+                                        ).into_response();
+                                    }
                                     return Response::builder()
                                         .status(StatusCode::OK)
                                         .header(header::CONTENT_TYPE, "application/json")
@@ -816,7 +848,12 @@ pub async fn handle_messages(
         
         // 2. 获取错误文本并转移 Response 所有权
         let error_text = response.text().await.unwrap_or_else(|_| format!("HTTP {}", status));
+        let error_text = crate::proxy::project_resolver::with_project_hint(error_text, &email);
         last_error = format!("HTTP {}: {}", status_code, error_text);
+        // 地区限制的账号持久标记后轮换，不再参与调度
+        if token_manager.mark_region_blocked(&email, &error_text) {
+            continue;
+        }
         debug!("[{}] Upstream Error Response: {}", trace_id, error_text);
         
         // 3. 标记限流状态(用于 UI 显示) - 使用异步版本以支持实时配额刷新
@@ -914,6 +951,21 @@ pub async fn handle_messages(
                 ).into_response();
             }
 
+            // 图片无效或格式不支持：按 Claude 错误格式返回
+            if status_code == 400 && crate::proxy::common::image::is_image_error(&error_text) {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    [("X-Account-Email", email.as_str())],
+                    Json(json!({
+                        "type": "error",
+                        "error": {
+                            "type": "invalid_request_error",
+                            "message": format!("Image could not be processed by upstream: {}", error_text)
+                        }
+                    }))
+                ).into_response();
+            }
+
             // 不可重试的错误，直接返回
             error!("[{}] Non-retryable error {}: {}", trace_id, status_code, error_text);
             return (status, [("X-Account-Email", email.as_str())], error_text).into_response();
@@ -982,11 +1034,86 @@ pub async fn handle_count_tokens(
         .await;
     }
 
-    Json(json!({
-        "input_tokens": 0,
-        "output_tokens": 0
-    }))
-    .into_response()
+    let request: ClaudeRequest = match serde_json::from_value(body) {
+        Ok(r) => r,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "type": "error",
+                    "error": {
+                        "type": "invalid_request_error",
+                        "message": format!("Invalid request body: {}", e)
+                    }
+                }))
+            ).into_response();
+        }
+    };
+
+    let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
+        &request.model,
+        &*state.custom_mapping.read().await,
+    );
+
+    // 优先使用上游计数，失败时回退到本地估算，并在响应中标明来源
+    let (input_tokens, source, email) = match count_tokens_upstream(&state, &request, &mapped_model).await {
+        Ok((count, email)) => (count, "upstream", Some(email)),
+        Err(e) => {
+            debug!("[CountTokens] 上游计数不可用，使用本地估算: {}", e);
+            (token_count::estimate_input_tokens(&request), "estimate", None)
+        }
+    };
+
+    let mut payload = json!({ "input_tokens": input_tokens });
+    if source == "estimate" {
+        payload["estimated"] = json!(true);
+    }
+    let mut response = Json(payload).into_response();
+    let headers = response.headers_mut();
+    headers.insert("X-Token-Count-Source", header::HeaderValue::from_static(source));
+    if let Ok(v) = header::HeaderValue::from_str(&mapped_model) {
+        headers.insert("X-Mapped-Model", v);
+    }
+    if let Some(v) = email.and_then(|e| header::HeaderValue::from_str(&e).ok()) {
+        headers.insert("X-Account-Email", v);
+    }
+    response
+}
+
+/// 通过上游 countTokens 接口计数，返回 (token 数, 使用的账号)
+async fn count_tokens_upstream(
+    state: &AppState,
+    request: &ClaudeRequest,
+    mapped_model: &str,
+) -> Result<(u32, String), String> {
+    let tools_val: Option<Vec<Value>> = request.tools.as_ref().map(|list| {
+        list.iter().map(|t| serde_json::to_value(t).unwrap_or(json!({}))).collect()
+    });
+    let config = crate::proxy::mappers::common_utils::resolve_request_config(&request.model, mapped_model, &tools_val);
+
+    let (access_token, project_id, email) = state
+        .token_manager
+        .get_token(&config.request_type, false, None, &config.final_model)
+        .await?;
+
+    let mut request_with_mapped = request.clone();
+    request_with_mapped.model = mapped_model.to_string();
+    let gemini_body = transform_claude_request_in(&request_with_mapped, &project_id)?;
+    let body = token_count::build_count_request(request, &gemini_body, &config.final_model);
+
+    let response = state
+        .upstream
+        .call_v1_internal("countTokens", &access_token, body, None)
+        .await?;
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        return Err(format!("HTTP {}: {}", status.as_u16(), text));
+    }
+    let json: Value = response.json().await.map_err(|e| format!("解析计数响应失败: {}", e))?;
+    token_count::parse_count_response(&json)
+        .map(|count| (count, email))
+        .ok_or_else(|| "计数响应缺少 totalTokens".to_string())
 }
 
 // 移除已失效的简单单元测试，后续将补全完整的集成测试
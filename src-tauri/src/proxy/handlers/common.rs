@@ -1,4 +1,4 @@
-use axum::{extract::State, extract::Json, http::StatusCode, response::IntoResponse};
+use axum::{extract::State, extract::Json, http::StatusCode, response::{IntoResponse, Response}};
 use serde_json::{json, Value};
 use crate::proxy::server::AppState;
 
@@ -46,3 +46,28 @@ pub async fn handle_detect_model(
 
     Json(response).into_response()
 }
+
+/// OpenAI 格式的错误响应
+pub fn openai_error(status: StatusCode, error_type: &str, message: impl Into<String>) -> Response {
+    (
+        status,
+        Json(json!({
+            "error": {
+                "message": message.into(),
+                "type": error_type,
+                "param": null,
+                "code": null
+            }
+        })),
+    )
+        .into_response()
+}
+
+pub fn openai_error_type(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        400 | 404 | 413 => "invalid_request_error",
+        401 | 403 => "authentication_error",
+        429 => "rate_limit_error",
+        _ => "api_error",
+    }
+}
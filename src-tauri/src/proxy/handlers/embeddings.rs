@@ -0,0 +1,130 @@
+// OpenAI Embeddings 处理器 (/v1/embeddings)
+
+use axum::{
+    extract::{Json, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde_json::{json, Value};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::proxy::mappers::openai::embeddings::{
+    build_batch_embed_request, transform_embed_response, EmbeddingRequest,
+};
+use crate::proxy::handlers::common::{openai_error, openai_error_type};
+use crate::proxy::server::AppState;
+
+const MAX_RETRY_ATTEMPTS: usize = 3;
+
+/// 处理 Embeddings 请求，转换为上游 batchEmbedContents
+pub async fn handle_embeddings(State(state): State<AppState>, Json(body): Json<Value>) -> Response {
+    let request: EmbeddingRequest = match serde_json::from_value(body) {
+        Ok(r) => r,
+        Err(e) => {
+            return openai_error(StatusCode::BAD_REQUEST, "invalid_request_error", format!("Invalid request: {}", e));
+        }
+    };
+    let inputs = match request.inputs() {
+        Ok(list) => list,
+        Err(e) => return openai_error(StatusCode::BAD_REQUEST, "invalid_request_error", e),
+    };
+
+    let mapped_model = crate::proxy::common::model_mapping::resolve_embedding_route(
+        &request.model,
+        &*state.custom_mapping.read().await,
+    );
+    debug!("[Embeddings] {} -> {}, {} 条输入", request.model, mapped_model, inputs.len());
+
+    let token_manager = state.token_manager.clone();
+    let max_attempts = MAX_RETRY_ATTEMPTS.min(token_manager.len()).max(1);
+    let mut last_error = String::new();
+    let mut last_status = StatusCode::TOO_MANY_REQUESTS;
+
+    for attempt in 0..max_attempts {
+        let (access_token, project_id, email) = match token_manager
+            .get_token("text", attempt > 0, None, &mapped_model)
+            .await
+        {
+            Ok(t) => t,
+            Err(e) => {
+                return openai_error(StatusCode::SERVICE_UNAVAILABLE, "api_error", format!("No available accounts: {}", e));
+            }
+        };
+
+        let wrapped_body = json!({
+            "project": project_id,
+            "requestId": format!("embed-{}", Uuid::new_v4()),
+            "request": build_batch_embed_request(&inputs, &mapped_model, request.dimensions),
+            "model": mapped_model,
+            "userAgent": "antigravity",
+            "requestType": "text"
+        });
+
+        let response = match state
+            .upstream
+            .call_v1_internal("batchEmbedContents", &access_token, wrapped_body, None)
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                debug!("[Embeddings] 第 {}/{} 次请求失败: {}", attempt + 1, max_attempts, e);
+                last_error = e;
+                last_status = StatusCode::BAD_GATEWAY;
+                continue;
+            }
+        };
+
+        let status = response.status();
+        if status.is_success() {
+            let upstream_body: Value = match response.json().await {
+                Ok(v) => v,
+                Err(e) => {
+                    return openai_error(StatusCode::BAD_GATEWAY, "api_error", format!("Parse error: {}", e));
+                }
+            };
+            return match transform_embed_response(&upstream_body, &request, &inputs) {
+                Ok(out) => {
+                    token_manager.mark_account_success(&email);
+                    info!("[Embeddings] ✓ {} 条向量 (账号: {})", inputs.len(), email);
+                    (
+                        StatusCode::OK,
+                        [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str())],
+                        Json(out),
+                    )
+                        .into_response()
+                }
+                Err(e) => openai_error(StatusCode::BAD_GATEWAY, "api_error", e),
+            };
+        }
+
+        let status_code = status.as_u16();
+        let retry_after = response.headers().get("Retry-After").and_then(|h| h.to_str().ok()).map(|s| s.to_string());
+        let error_text = response.text().await.unwrap_or_else(|_| format!("HTTP {}", status_code));
+        let error_text = crate::proxy::project_resolver::with_project_hint(error_text, &email);
+        last_error = format!("HTTP {}: {}", status_code, error_text);
+        last_status = status;
+        // 地区限制的账号持久标记后轮换，不再参与调度
+        if token_manager.mark_region_blocked(&email, &error_text) {
+            continue;
+        }
+
+        // 限流 / 过载与鉴权失败时轮换账号，其余错误 (如模型不存在) 直接返回
+        if matches!(status_code, 429 | 500 | 503 | 529) {
+            token_manager.mark_rate_limited(&email, status_code, retry_after.as_deref(), &error_text);
+            warn!("[Embeddings] 上游 {} (账号 {})，轮换账号重试", status_code, email);
+            continue;
+        }
+        if status_code == 401 || status_code == 403 {
+            warn!("[Embeddings] 上游 {} (账号 {})，轮换账号重试", status_code, email);
+            continue;
+        }
+        return openai_error(status, openai_error_type(status), error_text);
+    }
+
+    openai_error(
+        last_status,
+        openai_error_type(last_status),
+        format!("All accounts exhausted. Last error: {}", last_error),
+    )
+}
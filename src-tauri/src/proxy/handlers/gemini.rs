@@ -184,7 +184,12 @@ pub async fn handle_generate(
         let status_code = status.as_u16();
         let retry_after = response.headers().get("Retry-After").and_then(|h| h.to_str().ok()).map(|s| s.to_string());
         let error_text = response.text().await.unwrap_or_else(|_| format!("HTTP {}", status_code));
+        let error_text = crate::proxy::project_resolver::with_project_hint(error_text, &email);
         last_error = format!("HTTP {}: {}", status_code, error_text);
+        // 地区限制的账号持久标记后轮换，不再参与调度
+        if token_manager.mark_region_blocked(&email, &error_text) {
+            continue;
+        }
  
         // 只有 429 (限流), 529 (过载), 503, 403 (权限) 和 401 (认证失效) 触发账号轮换
         if status_code == 429 || status_code == 529 || status_code == 503 || status_code == 500 || status_code == 403 || status_code == 401 {
@@ -9,11 +9,42 @@ use crate::proxy::mappers::openai::{
     transform_openai_request, transform_openai_response, OpenAIRequest,
 };
 // use crate::proxy::upstream::client::UpstreamClient; // 通过 state 获取
+use crate::proxy::handlers::common::openai_error;
 use crate::proxy::server::AppState;
 
 const MAX_RETRY_ATTEMPTS: usize = 3;
 use crate::proxy::session_manager::SessionManager;
 
+/// 未生成任何内容即被安全拦截 (finish_reason 为 content_filter 的空回复)
+fn is_blocked_response(response: &crate::proxy::mappers::openai::OpenAIResponse) -> bool {
+    !response.choices.is_empty()
+        && response.choices.iter().all(|choice| {
+            let empty = match &choice.message.content {
+                None => true,
+                Some(crate::proxy::mappers::openai::OpenAIContent::String(text)) => text.is_empty(),
+                Some(_) => false,
+            };
+            choice.finish_reason.as_deref() == Some("content_filter") && empty && choice.message.tool_calls.is_none()
+        })
+}
+
+/// 安全拦截的 OpenAI 错误响应 (`code` 为 content_filter)
+fn content_filter_error(email: &str, mapped_model: &str) -> axum::response::Response {
+    (
+        StatusCode::BAD_REQUEST,
+        [("X-Account-Email", email), ("X-Mapped-Model", mapped_model)],
+        Json(json!({
+            "error": {
+                "message": crate::proxy::mappers::common_utils::safety_block_message("content_filter"),
+                "type": "invalid_request_error",
+                "param": null,
+                "code": "content_filter"
+            }
+        })),
+    )
+        .into_response()
+}
+
 pub async fn handle_chat_completions(
     State(state): State<AppState>,
     Json(body): Json<Value>,
@@ -40,8 +71,15 @@ pub async fn handle_chat_completions(
 
     debug!("Received OpenAI request for model: {}", openai_req.model);
 
+    // 上游只接受内联图片，预先下载远程 image_url
+    let upstream_proxy = state.upstream_proxy.read().await.clone();
+    if let Err(e) = crate::proxy::common::image::inline_openai_images(&mut openai_req, Some(&upstream_proxy)).await {
+        return Ok(openai_error(StatusCode::BAD_REQUEST, "invalid_request_error", e));
+    }
+
     // 1. 获取 UpstreamClient (Clone handle)
     let upstream = state.upstream.clone();
+    let block_as_error = state.experimental.read().await.enable_safety_block_errors;
     let token_manager = state.token_manager;
     let pool_size = token_manager.len();
     let max_attempts = MAX_RETRY_ATTEMPTS.min(pool_size).max(1);
@@ -138,8 +176,12 @@ pub async fn handle_chat_completions(
                 use axum::response::Response;
 
                 let gemini_stream = response.bytes_stream();
-                let openai_stream =
-                    create_openai_sse_stream(Box::pin(gemini_stream), openai_req.model.clone());
+                // 非流式客户端在收集完成后再转为错误响应
+                let openai_stream = create_openai_sse_stream(
+                    Box::pin(gemini_stream),
+                    openai_req.model.clone(),
+                    block_as_error && client_wants_stream,
+                );
                 
                 // 判断客户端期望的格式
                 if client_wants_stream {
@@ -170,6 +212,9 @@ pub async fn handle_chat_completions(
                     match collect_openai_stream_to_json(sse_stream).await {
                         Ok(full_response) => {
                             info!("[OpenAI] ✓ Stream collected and converted to JSON");
+                            if block_as_error && is_blocked_response(&full_response) {
+                                return Ok(content_filter_error(&email, &mapped_model));
+                            }
                             return Ok((StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str())], Json(full_response)).into_response());
                         }
                         Err(e) => {
@@ -185,6 +230,9 @@ pub async fn handle_chat_completions(
                 .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)))?;
 
             let openai_response = transform_openai_response(&gemini_resp);
+            if block_as_error && is_blocked_response(&openai_response) {
+                return Ok(content_filter_error(&email, &mapped_model));
+            }
             return Ok((StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str())], Json(openai_response)).into_response());
         }
 
@@ -192,7 +240,12 @@ pub async fn handle_chat_completions(
         let status_code = status.as_u16();
         let retry_after = response.headers().get("Retry-After").and_then(|h| h.to_str().ok()).map(|s| s.to_string());
         let error_text = response.text().await.unwrap_or_else(|_| format!("HTTP {}", status_code));
+        let error_text = crate::proxy::project_resolver::with_project_hint(error_text, &email);
         last_error = format!("HTTP {}: {}", status_code, error_text);
+        // 地区限制的账号持久标记后轮换，不再参与调度
+        if token_manager.mark_region_blocked(&email, &error_text) {
+            continue;
+        }
 
         // [New] 打印错误报文日志
         tracing::error!(
@@ -255,6 +308,15 @@ pub async fn handle_chat_completions(
             continue;
         }
 
+        // 图片无效或格式不支持：按 OpenAI 错误格式返回
+        if status_code == 400 && crate::proxy::common::image::is_image_error(&error_text) {
+            return Ok(openai_error(
+                StatusCode::BAD_REQUEST,
+                "invalid_request_error",
+                format!("Image could not be processed by upstream: {}", error_text),
+            ));
+        }
+
         // 404 等由于模型配置或路径错误的 HTTP 异常，直接报错，不进行无效轮换
         error!(
             "OpenAI Upstream non-retryable error {} on account {}: {}",
@@ -557,6 +619,12 @@ pub async fn handle_completions(
             });
     }
 
+    // 上游只接受内联图片，预先下载远程 image_url
+    let upstream_proxy = state.upstream_proxy.read().await.clone();
+    if let Err(e) = crate::proxy::common::image::inline_openai_images(&mut openai_req, Some(&upstream_proxy)).await {
+        return Ok(openai_error(StatusCode::BAD_REQUEST, "invalid_request_error", e));
+    }
+
     let upstream = state.upstream.clone();
     let token_manager = state.token_manager;
     let pool_size = token_manager.len();
@@ -684,11 +752,23 @@ pub async fn handle_completions(
         // Handle errors and retry
         let status_code = status.as_u16();
         let error_text = response.text().await.unwrap_or_default();
+        let error_text = crate::proxy::project_resolver::with_project_hint(error_text, &email);
         last_error = format!("HTTP {}: {}", status_code, error_text);
+        // 地区限制的账号持久标记后轮换，不再参与调度
+        if token_manager.mark_region_blocked(&email, &error_text) {
+            continue;
+        }
 
         if status_code == 429 || status_code == 403 || status_code == 401 {
             continue;
         }
+        if status_code == 400 && crate::proxy::common::image::is_image_error(&error_text) {
+            return Ok(openai_error(
+                StatusCode::BAD_REQUEST,
+                "invalid_request_error",
+                format!("Image could not be processed by upstream: {}", error_text),
+            ));
+        }
         return Err((status, error_text));
     }
 
@@ -0,0 +1,222 @@
+// 客户端 IP 解析与 CIDR 白名单 (管理 API 与反代共用)
+use axum::extract::ConnectInfo;
+use axum::http::{Extensions, HeaderMap};
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+
+/// 解析后的客户端地址，由 IP 过滤中间件写入请求扩展
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+/// CIDR 网段，不带前缀长度时视为单个地址
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl FromStr for IpNet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("无效的 IP 地址: {}", s))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(|| format!("无效的前缀长度: {}", s))?,
+            None => max,
+        };
+        Ok(Self { addr, prefix })
+    }
+}
+
+impl std::fmt::Display for IpNet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+impl IpNet {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, canonical(ip)) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// IPv4 映射的 IPv6 地址 (::ffff:a.b.c.d，双栈监听时常见) 还原为 IPv4
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6
+            .to_ipv4_mapped()
+            .map(IpAddr::V4)
+            .unwrap_or(IpAddr::V6(v6)),
+        v4 => v4,
+    }
+}
+
+/// 校验 CIDR 列表，返回 (序号, 错误) 列表
+pub fn validate_cidrs(list: &[String]) -> Vec<(usize, String)> {
+    list.iter()
+        .enumerate()
+        .filter_map(|(i, s)| s.parse::<IpNet>().err().map(|e| (i, e)))
+        .collect()
+}
+
+fn parse_list(list: &[String]) -> Vec<IpNet> {
+    list.iter().filter_map(|s| s.parse().ok()).collect()
+}
+
+/// 访问控制：允许的网段 + 可信反向代理
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IpAccessList {
+    allowed: Vec<IpNet>,
+    trusted_proxies: Vec<IpNet>,
+}
+
+impl IpAccessList {
+    /// 无效项会被忽略 (配置保存时已校验)
+    pub fn new(allowed: &[String], trusted_proxies: &[String]) -> Self {
+        Self {
+            allowed: parse_list(allowed),
+            trusted_proxies: parse_list(trusted_proxies),
+        }
+    }
+
+    pub fn is_restricted(&self) -> bool {
+        !self.allowed.is_empty()
+    }
+
+    /// 生效的白名单网段
+    pub fn allowed(&self) -> Vec<String> {
+        self.allowed.iter().map(|net| net.to_string()).collect()
+    }
+
+    /// 生效的可信代理网段
+    pub fn trusted_proxies(&self) -> Vec<String> {
+        self.trusted_proxies.iter().map(|net| net.to_string()).collect()
+    }
+
+    /// 未配置白名单时允许所有地址
+    pub fn is_allowed(&self, ip: IpAddr) -> bool {
+        self.allowed.is_empty() || self.allowed.iter().any(|net| net.contains(ip))
+    }
+
+    /// 直连对端是否为可信代理 (可采信其转发头)
+    pub fn is_trusted_proxy(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|net| net.contains(ip))
+    }
+
+    /// 解析真实客户端地址
+    ///
+    /// 仅当直连对端是可信代理时才采信 `X-Forwarded-For` / `X-Real-IP`：
+    /// 从 `X-Forwarded-For` 右侧向左跳过可信代理，取第一个不可信的地址。
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let peer = canonical(peer);
+        if !self.is_trusted_proxy(peer) {
+            return peer;
+        }
+
+        let forwarded: Vec<IpAddr> = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .filter_map(|s| s.trim().parse::<IpAddr>().ok())
+            .map(canonical)
+            .collect();
+        if let Some(ip) = forwarded.iter().rev().find(|ip| !self.is_trusted_proxy(**ip)) {
+            return *ip;
+        }
+        if let Some(first) = forwarded.first() {
+            // 整条链都是可信代理时取最初的发起方
+            return *first;
+        }
+
+        headers
+            .get("x-real-ip")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<IpAddr>().ok())
+            .map(canonical)
+            .unwrap_or(peer)
+    }
+}
+
+/// 直连对端地址，由接收连接时写入的 `ConnectInfo` 提供
+pub fn peer_ip(extensions: &Extensions) -> Option<IpAddr> {
+    extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_ipnet_contains() {
+        let net: IpNet = "10.8.0.0/16".parse().unwrap();
+        assert!(net.contains(ip("10.8.3.4")));
+        assert!(!net.contains(ip("10.9.0.1")));
+        // 双栈监听下的 IPv4 映射地址
+        assert!(net.contains(ip("::ffff:10.8.0.1")));
+
+        let single: IpNet = "192.168.1.5".parse().unwrap();
+        assert!(single.contains(ip("192.168.1.5")));
+        assert!(!single.contains(ip("192.168.1.6")));
+
+        let any: IpNet = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains(ip("8.8.8.8")));
+        assert!(!any.contains(ip("::1")));
+
+        let v6: IpNet = "fd00::/8".parse().unwrap();
+        assert!(v6.contains(ip("fd12::1")));
+
+        assert!("10.0.0.0/33".parse::<IpNet>().is_err());
+        assert!("not-an-ip".parse::<IpNet>().is_err());
+    }
+
+    #[test]
+    fn test_client_ip_honors_only_trusted_proxies() {
+        let list = IpAccessList::new(&["10.8.0.0/16".to_string()], &["127.0.0.1".to_string()]);
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", HeaderValue::from_static("1.2.3.4, 10.8.0.7"));
+
+        // 直连对端不可信：忽略转发头
+        assert_eq!(list.client_ip(ip("203.0.113.9"), &headers), ip("203.0.113.9"));
+        // 可信代理：取最右侧的不可信地址
+        assert_eq!(list.client_ip(ip("127.0.0.1"), &headers), ip("10.8.0.7"));
+        assert!(list.is_allowed(ip("10.8.0.7")));
+        assert!(!list.is_allowed(ip("1.2.3.4")));
+
+        let mut real_ip = HeaderMap::new();
+        real_ip.insert("x-real-ip", HeaderValue::from_static("10.8.1.1"));
+        assert_eq!(list.client_ip(ip("127.0.0.1"), &real_ip), ip("10.8.1.1"));
+
+        // 未配置白名单时不限制
+        assert!(IpAccessList::default().is_allowed(ip("1.2.3.4")));
+    }
+}
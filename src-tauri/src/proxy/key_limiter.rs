@@ -0,0 +1,321 @@
+// 按 API 密钥限流：每分钟请求数 (令牌桶) 与并发请求数
+use dashmap::DashMap;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use utoipa::ToSchema;
+
+use crate::proxy::config::ApiKeyEntry;
+
+/// 并发已满时的最长排队时间
+const CONCURRENCY_WAIT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct KeyLimits {
+    pub requests_per_minute: Option<u32>,
+    pub concurrent_requests: Option<u32>,
+}
+
+impl KeyLimits {
+    pub fn from_entry(entry: &ApiKeyEntry) -> Self {
+        Self {
+            requests_per_minute: entry.requests_per_minute.filter(|n| *n > 0),
+            concurrent_requests: entry.concurrent_requests.filter(|n| *n > 0),
+        }
+    }
+
+    fn is_unlimited(&self) -> bool {
+        self.requests_per_minute.is_none() && self.concurrent_requests.is_none()
+    }
+}
+
+/// 超出限制
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LimitExceeded {
+    /// 超出每分钟请求数，附带建议的重试等待秒数
+    RateLimited { retry_after: u64 },
+    /// 排队后仍无空闲并发槽位
+    TooManyConcurrent,
+}
+
+/// 某个密钥当前的限流状态
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct KeyUsage {
+    pub name: String,
+    pub requests_per_minute: Option<u32>,
+    pub concurrent_requests: Option<u32>,
+    /// 令牌桶剩余可用请求数 (未限制 RPM 时为空)
+    pub tokens_available: Option<f64>,
+    /// 正在处理的请求数 (未限制并发时为空)
+    pub in_flight: Option<u32>,
+    /// 自限流状态创建以来被拒绝的请求数
+    pub rejected: u64,
+    /// 反代启动以来为该密钥提供服务的账号 (按请求数降序)
+    pub accounts: Vec<KeyAccountUsage>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct KeyAccountUsage {
+    pub email: String,
+    pub requests: u64,
+}
+
+pub(crate) struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub(crate) fn new(requests_per_minute: u32) -> Self {
+        let capacity = requests_per_minute as f64;
+        Self {
+            capacity,
+            tokens: capacity,
+            refill_per_sec: capacity / 60.0,
+            last_refill: Instant::now(),
+        }
+    }
+
+    pub(crate) fn refill(&mut self, now: Instant) {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    /// 取一个令牌；不足时返回需要等待的秒数
+    pub(crate) fn try_take(&mut self, now: Instant) -> Result<(), u64> {
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            let wait = (1.0 - self.tokens) / self.refill_per_sec;
+            Err(wait.ceil().max(1.0) as u64)
+        }
+    }
+
+    /// 当前剩余令牌数 (保留两位小数)
+    pub(crate) fn available(&mut self, now: Instant) -> f64 {
+        self.refill(now);
+        (self.tokens * 100.0).floor() / 100.0
+    }
+
+    pub(crate) fn is_full(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens >= self.capacity
+    }
+}
+
+struct KeyState {
+    limits: KeyLimits,
+    bucket: Option<Mutex<TokenBucket>>,
+    concurrency: Option<Arc<Semaphore>>,
+    rejected: AtomicU64,
+}
+
+impl KeyState {
+    fn new(limits: KeyLimits) -> Self {
+        Self {
+            limits,
+            bucket: limits
+                .requests_per_minute
+                .map(|rpm| Mutex::new(TokenBucket::new(rpm))),
+            concurrency: limits
+                .concurrent_requests
+                .map(|n| Arc::new(Semaphore::new(n as usize))),
+            rejected: AtomicU64::new(0),
+        }
+    }
+}
+
+/// 各密钥的限流状态，随反代实例存活，热更新配置时保留
+#[derive(Default)]
+pub struct KeyRateLimiter {
+    keys: DashMap<String, Arc<KeyState>>,
+    /// 密钥名 -> (账号邮箱 -> 请求数)
+    served: DashMap<String, HashMap<String, u64>>,
+}
+
+impl KeyRateLimiter {
+    /// 获取密钥的状态，限制变化时重建
+    fn state(&self, name: &str, limits: KeyLimits) -> Arc<KeyState> {
+        if let Some(state) = self.keys.get(name) {
+            if state.limits == limits {
+                return state.clone();
+            }
+        }
+        let state = Arc::new(KeyState::new(limits));
+        self.keys.insert(name.to_string(), state.clone());
+        state
+    }
+
+    /// 申请一次请求配额，返回的许可需持有到请求结束
+    pub async fn acquire(
+        &self,
+        name: &str,
+        limits: KeyLimits,
+    ) -> Result<Option<OwnedSemaphorePermit>, LimitExceeded> {
+        if limits.is_unlimited() {
+            return Ok(None);
+        }
+        let state = self.state(name, limits);
+
+        if let Some(bucket) = &state.bucket {
+            let result = bucket
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .try_take(Instant::now());
+            if let Err(retry_after) = result {
+                state.rejected.fetch_add(1, Ordering::Relaxed);
+                return Err(LimitExceeded::RateLimited { retry_after });
+            }
+        }
+
+        match &state.concurrency {
+            Some(semaphore) => {
+                match tokio::time::timeout(CONCURRENCY_WAIT, semaphore.clone().acquire_owned())
+                    .await
+                {
+                    Ok(Ok(permit)) => Ok(Some(permit)),
+                    _ => {
+                        state.rejected.fetch_add(1, Ordering::Relaxed);
+                        Err(LimitExceeded::TooManyConcurrent)
+                    }
+                }
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// 记录一次由某账号完成的请求
+    pub fn record_account(&self, name: &str, email: &str) {
+        *self
+            .served
+            .entry(name.to_string())
+            .or_default()
+            .entry(email.to_string())
+            .or_insert(0) += 1;
+    }
+
+    /// 移除已不存在的密钥的状态
+    pub fn retain(&self, entries: &[ApiKeyEntry]) {
+        self.keys
+            .retain(|name, _| entries.iter().any(|entry| &entry.name == name));
+        self.served
+            .retain(|name, _| entries.iter().any(|entry| &entry.name == name));
+    }
+
+    pub fn usage(&self, entry: &ApiKeyEntry) -> KeyUsage {
+        let limits = KeyLimits::from_entry(entry);
+        let state = self.state(&entry.name, limits);
+        let tokens_available = state.bucket.as_ref().map(|bucket| {
+            bucket.lock().unwrap_or_else(|e| e.into_inner()).available(Instant::now())
+        });
+        let in_flight = match (&state.concurrency, limits.concurrent_requests) {
+            (Some(semaphore), Some(max)) => {
+                Some(max.saturating_sub(semaphore.available_permits() as u32))
+            }
+            _ => None,
+        };
+
+        let mut accounts: Vec<KeyAccountUsage> = self
+            .served
+            .get(&entry.name)
+            .map(|served| {
+                served
+                    .iter()
+                    .map(|(email, requests)| KeyAccountUsage { email: email.clone(), requests: *requests })
+                    .collect()
+            })
+            .unwrap_or_default();
+        accounts.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.email.cmp(&b.email)));
+
+        KeyUsage {
+            name: entry.name.clone(),
+            requests_per_minute: limits.requests_per_minute,
+            concurrent_requests: limits.concurrent_requests,
+            tokens_available,
+            in_flight,
+            rejected: state.rejected.load(Ordering::Relaxed),
+            accounts,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(rpm: Option<u32>, concurrent: Option<u32>) -> ApiKeyEntry {
+        ApiKeyEntry {
+            name: "alice".to_string(),
+            key: "sk-alice".to_string(),
+            enabled: true,
+            requests_per_minute: rpm,
+            concurrent_requests: concurrent,
+            group: None,
+            admin: false,
+        }
+    }
+
+    #[test]
+    fn token_bucket_refills_over_time() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(2);
+        bucket.last_refill = start;
+        assert!(bucket.try_take(start).is_ok());
+        assert!(bucket.try_take(start).is_ok());
+        // 每 30 秒补充一个
+        assert_eq!(bucket.try_take(start), Err(30));
+        assert!(bucket.try_take(start + Duration::from_secs(30)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn limiter_enforces_rpm_and_reports_usage() {
+        let limiter = KeyRateLimiter::default();
+        let alice = entry(Some(1), None);
+        let limits = KeyLimits::from_entry(&alice);
+
+        assert!(limiter.acquire("alice", limits).await.is_ok());
+        assert!(matches!(
+            limiter.acquire("alice", limits).await,
+            Err(LimitExceeded::RateLimited { .. })
+        ));
+
+        limiter.record_account("alice", "a@example.com");
+        limiter.record_account("alice", "b@example.com");
+        limiter.record_account("alice", "b@example.com");
+        let usage = limiter.usage(&alice);
+        assert_eq!(usage.requests_per_minute, Some(1));
+        assert_eq!(usage.rejected, 1);
+        assert_eq!(usage.accounts[0].email, "b@example.com");
+        assert_eq!(usage.accounts[0].requests, 2);
+        assert_eq!(usage.accounts.len(), 2);
+
+        // 未设置限制时不限流
+        let unlimited = KeyLimits::from_entry(&entry(None, None));
+        for _ in 0..10 {
+            assert!(limiter.acquire("bob", unlimited).await.unwrap().is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn limiter_tracks_concurrency() {
+        let limiter = KeyRateLimiter::default();
+        let alice = entry(None, Some(1));
+        let permit = limiter
+            .acquire("alice", KeyLimits::from_entry(&alice))
+            .await
+            .unwrap();
+        assert_eq!(limiter.usage(&alice).in_flight, Some(1));
+        drop(permit);
+        assert_eq!(limiter.usage(&alice).in_flight, Some(0));
+    }
+}
@@ -8,6 +8,7 @@ pub mod streaming;
 pub mod utils;
 pub mod thinking_utils;
 pub mod collector;
+pub mod token_count;
 
 pub use models::*;
 pub use request::transform_claude_request_in;
@@ -19,6 +20,7 @@ pub use collector::collect_stream_to_json;
 use bytes::Bytes;
 use futures::Stream;
 use std::pin::Pin;
+use crate::proxy::mappers::common_utils::{is_refusal_finish_reason, prompt_block_reason};
 
 /// 创建从 Gemini SSE 流到 Claude SSE 流的转换
 pub fn create_claude_sse_stream(
@@ -28,6 +30,7 @@ pub fn create_claude_sse_stream(
     session_id: Option<String>, // [NEW v3.3.17] Session ID for signature caching
     scaling_enabled: bool, // [NEW] Flag for context usage scaling
     context_limit: u32,
+    block_as_error: bool, // 安全拦截时发送 error 事件
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
     use async_stream::stream;
     use bytes::BytesMut;
@@ -39,6 +42,7 @@ pub fn create_claude_sse_stream(
         state.session_id = session_id; // Set session ID for signature caching
         state.scaling_enabled = scaling_enabled; // Set scaling enabled flag
         state.context_limit = context_limit;
+        state.block_as_error = block_as_error;
         let mut buffer = BytesMut::new();
 
         loop {
@@ -109,6 +113,11 @@ fn process_sse_line(line: &str, state: &mut StreamingState, trace_id: &str, emai
         return Some(chunks);
     }
 
+    // message_stop 之后不能再有任何事件
+    if state.message_stop_sent {
+        return None;
+    }
+
     // 解析 JSON
     let json_value: serde_json::Value = match serde_json::from_str(data_str) {
         Ok(v) => v,
@@ -179,13 +188,21 @@ fn process_sse_line(line: &str, state: &mut StreamingState, trace_id: &str, emai
     }
     */
 
-    // 检查是否结束
-    if let Some(finish_reason) = raw_json
+    // 检查是否结束；提示词被安全策略拦截时没有候选结果，按拒绝处理
+    let finish_reason = raw_json
         .get("candidates")
         .and_then(|c| c.get(0))
         .and_then(|cand| cand.get("finishReason"))
         .and_then(|f| f.as_str())
-    {
+        .or_else(|| {
+            prompt_block_reason(raw_json).map(|reason| if is_refusal_finish_reason(reason) { reason } else { "SAFETY" })
+        });
+    if let Some(finish_reason) = finish_reason {
+        if state.block_as_error && is_refusal_finish_reason(finish_reason) && !state.has_output() {
+            chunks.push(state.emit_block_error(finish_reason));
+            return Some(chunks);
+        }
+
         let usage = raw_json
             .get("usageMetadata")
             .and_then(|u| serde_json::from_value::<UsageMetadata>(u.clone()).ok());
@@ -221,7 +238,12 @@ fn process_sse_line(line: &str, state: &mut StreamingState, trace_id: &str, emai
 /// 发送强制结束事件
 pub fn emit_force_stop(state: &mut StreamingState) -> Vec<Bytes> {
     if !state.message_stop_sent {
-        let mut chunks = state.emit_finish(None, None);
+        // 上游没有返回任何数据时也要以 message_start 开头
+        let mut chunks = Vec::new();
+        if !state.message_start_sent {
+            chunks.push(state.emit_message_start(&serde_json::json!({})));
+        }
+        chunks.extend(state.emit_finish(None, None));
         if chunks.is_empty() {
             chunks.push(Bytes::from(
                 "event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n",
@@ -396,4 +418,31 @@ mod tests {
         assert!(all_text.contains("content_block_start"));
         assert!(all_text.contains("Hello"));
     }
+
+    #[test]
+    fn test_process_sse_line_prompt_blocked() {
+        let blocked = r#"data: {"response":{"promptFeedback":{"blockReason":"OTHER"},"responseId":"123"}}"#;
+        let collect = |state: &mut StreamingState| -> String {
+            process_sse_line(blocked, state, "test_id", "test@example.com")
+                .unwrap()
+                .iter()
+                .map(|b| String::from_utf8(b.to_vec()).unwrap_or_default())
+                .collect()
+        };
+
+        // 默认：stop_reason 为 refusal 的空回复
+        let mut state = StreamingState::new();
+        let all_text = collect(&mut state);
+        assert!(all_text.contains("\"stop_reason\":\"refusal\""));
+        assert!(all_text.contains("message_stop"));
+
+        // 开启后：发送 error 事件
+        let mut state = StreamingState::new();
+        state.block_as_error = true;
+        let all_text = collect(&mut state);
+        assert!(all_text.contains("event: error"));
+        assert!(all_text.contains("content_filter"));
+        assert!(!all_text.contains("message_delta"));
+        assert!(emit_force_stop(&mut state).is_empty());
+    }
 }
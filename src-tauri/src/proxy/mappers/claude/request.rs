@@ -867,6 +867,14 @@ fn build_contents(
                                         "data": source.data
                                     }
                                 }));
+                            } else if let Some(url) = source.url.as_ref() {
+                                // 未经处理器预先下载的 URL 图片，交给上游按 fileData 处理
+                                parts.push(json!({
+                                    "fileData": {
+                                        "fileUri": url,
+                                        "mimeType": crate::proxy::common::image::guess_mime_from_path(url)
+                                    }
+                                }));
                             }
                         }
                         ContentBlock::Document { source, .. } => {
@@ -1553,6 +1561,7 @@ mod tests {
                                 source_type: "base64".to_string(),
                                 media_type: "image/png".to_string(),
                                 data: "iVBORw0KGgo=".to_string(),
+                                url: None,
                             },
                             cache_control: Some(json!({"type": "ephemeral"})), // 这个也应该被清理
                         },
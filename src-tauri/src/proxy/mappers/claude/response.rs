@@ -3,6 +3,7 @@
 
 use super::models::*;
 use super::utils::to_claude_usage;
+use crate::proxy::mappers::common_utils::is_refusal_finish_reason;
 
 /// [FIX #547] Helper function to coerce string values to boolean
 /// Gemini sometimes sends boolean parameters as strings (e.g., "true", "-n", "false")
@@ -519,6 +520,8 @@ impl NonStreamingProcessor {
             "tool_use"
         } else if finish_reason == Some("MAX_TOKENS") {
             "max_tokens"
+        } else if finish_reason.is_some_and(is_refusal_finish_reason) {
+            "refusal"
         } else {
             "end_turn"
         };
@@ -3,6 +3,7 @@
 
 use super::models::*;
 use super::utils::to_claude_usage;
+use crate::proxy::mappers::common_utils::{is_refusal_finish_reason, safety_block_message};
 // use crate::proxy::mappers::signature_store::store_thought_signature; // Deprecated
 use crate::proxy::SignatureCache;
 use bytes::Bytes;
@@ -230,6 +231,8 @@ pub struct StreamingState {
     // [NEW] MCP XML Bridge 缓冲区
     pub mcp_xml_buffer: String,
     pub in_mcp_xml: bool,
+    /// 未输出内容即被安全拦截时发送 error 事件，而非 stop_reason 为 refusal 的空回复
+    pub block_as_error: bool,
 }
 
 impl StreamingState {
@@ -253,6 +256,7 @@ impl StreamingState {
             context_limit: 1_048_576, // Default to 1M
             mcp_xml_buffer: String::new(),
             in_mcp_xml: false,
+            block_as_error: false,
         }
     }
 
@@ -390,6 +394,10 @@ impl StreamingState {
         finish_reason: Option<&str>,
         usage_metadata: Option<&UsageMetadata>,
     ) -> Vec<Bytes> {
+        // 已结束的消息不再发送 message_delta (上游可能在 finishReason 之后再发一个用量分片)
+        if self.message_stop_sent {
+            return vec![];
+        }
         let mut chunks = Vec::new();
 
         // 关闭最后一个块
@@ -464,6 +472,8 @@ impl StreamingState {
             "tool_use"
         } else if finish_reason == Some("MAX_TOKENS") {
             "max_tokens"
+        } else if finish_reason.is_some_and(is_refusal_finish_reason) {
+            "refusal"
         } else {
             "end_turn"
         };
@@ -497,6 +507,27 @@ impl StreamingState {
         chunks
     }
 
+    /// 是否已开始输出内容块
+    pub fn has_output(&self) -> bool {
+        self.block_index > 0 || self.block_type != BlockType::None || self.used_tool
+    }
+
+    /// 安全拦截时发送 error 事件并结束流
+    pub fn emit_block_error(&mut self, reason: &str) -> Bytes {
+        self.message_stop_sent = true;
+        self.emit(
+            "error",
+            json!({
+                "type": "error",
+                "error": {
+                    "type": "invalid_request_error",
+                    "message": safety_block_message(reason),
+                    "code": "content_filter"
+                }
+            }),
+        )
+    }
+
     /// 标记使用了工具
     pub fn mark_tool_used(&mut self) {
         self.used_tool = true;
@@ -948,16 +979,19 @@ impl<'a> PartProcessor<'a> {
 
         // 2. 发送 input_json_delta (完整的参数 JSON 字符串)
         // [FIX] Remap args before serialization for Gemini → Claude compatibility
-        if let Some(args) = &fc.args {
-            let mut remapped_args = args.clone();
-            remap_function_call_args(&fc.name, &mut remapped_args);
-            let json_str =
-                serde_json::to_string(&remapped_args).unwrap_or_else(|_| "{}".to_string());
-            chunks.push(
-                self.state
-                    .emit_delta("input_json_delta", json!({ "partial_json": json_str })),
-            );
-        }
+        // 无参数调用也发送 "{}"，否则客户端拼接出的 input 为空字符串而非对象
+        let json_str = match &fc.args {
+            Some(args) => {
+                let mut remapped_args = args.clone();
+                remap_function_call_args(&fc.name, &mut remapped_args);
+                serde_json::to_string(&remapped_args).unwrap_or_else(|_| "{}".to_string())
+            }
+            None => "{}".to_string(),
+        };
+        chunks.push(
+            self.state
+                .emit_delta("input_json_delta", json!({ "partial_json": json_str })),
+        );
 
         // 3. 结束块
         chunks.extend(self.state.end_block());
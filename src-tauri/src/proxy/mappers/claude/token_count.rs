@@ -0,0 +1,227 @@
+// Claude count_tokens 支持
+// 构造上游 countTokens 请求体；上游不可用时按字符规则本地估算
+
+use serde_json::{json, Value};
+
+use super::models::{ClaudeRequest, ContentBlock, MessageContent, SystemPrompt, Tool};
+
+/// 无尺寸信息时单张图片 / 单个文档的估算 token 数
+const ATTACHMENT_TOKENS: u32 = 1600;
+/// 每条消息的结构开销 (角色标记等)
+const MESSAGE_OVERHEAD: u32 = 3;
+/// 每个工具定义的结构开销
+const TOOL_OVERHEAD: u32 = 8;
+
+/// 近似 BPE 分词：拉丁字母数字按每 4 字符 1 token，CJK 等宽字符与标点各算 1 token，空白不计
+pub fn estimate_text_tokens(text: &str) -> u32 {
+    let mut tokens = 0u32;
+    let mut word_len = 0u32;
+    for c in text.chars() {
+        // 拉丁 / 西里尔 / 希腊等字母在常见词表中与 ASCII 单词合并得较好
+        if c.is_alphanumeric() && c.len_utf8() <= 2 {
+            word_len += 1;
+            continue;
+        }
+        tokens += word_len.div_ceil(4);
+        word_len = 0;
+        if !c.is_whitespace() {
+            tokens += 1;
+        }
+    }
+    tokens + word_len.div_ceil(4)
+}
+
+fn system_text(system: &SystemPrompt) -> String {
+    match system {
+        SystemPrompt::String(text) => text.clone(),
+        SystemPrompt::Array(blocks) => blocks
+            .iter()
+            .map(|b| b.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+fn tool_text(tool: &Tool) -> String {
+    serde_json::to_string(tool).unwrap_or_default()
+}
+
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn estimate_block_tokens(block: &ContentBlock) -> u32 {
+    match block {
+        ContentBlock::Text { text } => estimate_text_tokens(text),
+        ContentBlock::Thinking { thinking, .. } => estimate_text_tokens(thinking),
+        ContentBlock::Image { .. } | ContentBlock::Document { .. } => ATTACHMENT_TOKENS,
+        ContentBlock::RedactedThinking { .. } => 0,
+        ContentBlock::ToolUse { name, input, .. } | ContentBlock::ServerToolUse { name, input, .. } => {
+            estimate_text_tokens(name) + estimate_text_tokens(&input.to_string())
+        }
+        ContentBlock::ToolResult { content, .. } | ContentBlock::WebSearchToolResult { content, .. } => {
+            estimate_text_tokens(&value_text(content))
+        }
+    }
+}
+
+/// 本地估算请求的输入 token 数 (系统提示词 + 消息 + 工具定义)
+pub fn estimate_input_tokens(request: &ClaudeRequest) -> u32 {
+    let system = request
+        .system
+        .as_ref()
+        .map(|s| estimate_text_tokens(&system_text(s)))
+        .unwrap_or(0);
+
+    let messages: u32 = request
+        .messages
+        .iter()
+        .map(|msg| {
+            MESSAGE_OVERHEAD
+                + match &msg.content {
+                    MessageContent::String(text) => estimate_text_tokens(text),
+                    MessageContent::Array(blocks) => blocks.iter().map(estimate_block_tokens).sum(),
+                }
+        })
+        .sum();
+
+    let tools: u32 = request
+        .tools
+        .iter()
+        .flatten()
+        .map(|tool| TOOL_OVERHEAD + estimate_text_tokens(&tool_text(tool)))
+        .sum();
+
+    system + messages + tools
+}
+
+/// 由转换后的 Gemini 请求体构造上游 countTokens 请求
+///
+/// 转换时注入的系统指令不计入；客户端自己的系统提示词与工具定义作为首条 user 内容参与计数
+pub fn build_count_request(request: &ClaudeRequest, gemini_body: &Value, model: &str) -> Value {
+    let mut preamble: Vec<Value> = Vec::new();
+    if let Some(system) = request.system.as_ref() {
+        let text = system_text(system);
+        if !text.is_empty() {
+            preamble.push(json!({ "text": text }));
+        }
+    }
+    for tool in request.tools.iter().flatten() {
+        preamble.push(json!({ "text": tool_text(tool) }));
+    }
+
+    let mut contents: Vec<Value> = Vec::new();
+    if !preamble.is_empty() {
+        contents.push(json!({ "role": "user", "parts": preamble }));
+    }
+    if let Some(list) = gemini_body
+        .get("request")
+        .and_then(|r| r.get("contents"))
+        .and_then(|c| c.as_array())
+    {
+        contents.extend(list.iter().cloned());
+    }
+
+    json!({
+        "request": {
+            "model": format!("models/{}", model),
+            "contents": contents,
+        }
+    })
+}
+
+/// 解析上游 countTokens 响应中的 totalTokens
+pub fn parse_count_response(body: &Value) -> Option<u32> {
+    body.get("response")
+        .unwrap_or(body)
+        .get("totalTokens")
+        .and_then(|v| v.as_u64())
+        .map(|v| v as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(value: Value) -> ClaudeRequest {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_estimate_text_tokens() {
+        assert_eq!(estimate_text_tokens(""), 0);
+        assert_eq!(estimate_text_tokens("Hello, world!"), 6);
+        assert_eq!(estimate_text_tokens("你好世界"), 4);
+    }
+
+    #[test]
+    fn test_count_text_only() {
+        let req = request(json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{ "role": "user", "content": "Hello, world!" }]
+        }));
+        assert_eq!(estimate_input_tokens(&req), MESSAGE_OVERHEAD + 6);
+
+        let gemini = json!({ "request": { "contents": [{ "role": "user", "parts": [{ "text": "Hello, world!" }] }] } });
+        let body = build_count_request(&req, &gemini, "gemini-2.5-pro");
+        assert_eq!(body["request"]["model"], "models/gemini-2.5-pro");
+        assert_eq!(body["request"]["contents"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_count_with_system_prompt() {
+        let plain = request(json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{ "role": "user", "content": "Hi" }]
+        }));
+        let with_system = request(json!({
+            "model": "claude-sonnet-4-5",
+            "system": [{ "type": "text", "text": "You are a helpful assistant." }],
+            "messages": [{ "role": "user", "content": "Hi" }]
+        }));
+        assert_eq!(
+            estimate_input_tokens(&with_system) - estimate_input_tokens(&plain),
+            estimate_text_tokens("You are a helpful assistant.")
+        );
+
+        let gemini = json!({
+            "request": {
+                "systemInstruction": { "parts": [{ "text": "injected" }] },
+                "contents": [{ "role": "user", "parts": [{ "text": "Hi" }] }]
+            }
+        });
+        let body = build_count_request(&with_system, &gemini, "gemini-2.5-pro");
+        let contents = body["request"]["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 2);
+        assert_eq!(contents[0]["parts"][0]["text"], "You are a helpful assistant.");
+        assert!(body["request"].get("systemInstruction").is_none());
+    }
+
+    #[test]
+    fn test_count_multi_turn() {
+        let req = request(json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [
+                { "role": "user", "content": "What is 2+2?" },
+                { "role": "assistant", "content": [{ "type": "text", "text": "4" }] },
+                { "role": "user", "content": [
+                    { "type": "text", "text": "And this?" },
+                    { "type": "image", "source": { "type": "base64", "media_type": "image/png", "data": "AAAA" } }
+                ] }
+            ]
+        }));
+        let expected = 3 * MESSAGE_OVERHEAD
+            + estimate_text_tokens("What is 2+2?")
+            + estimate_text_tokens("4")
+            + estimate_text_tokens("And this?")
+            + ATTACHMENT_TOKENS;
+        assert_eq!(estimate_input_tokens(&req), expected);
+
+        assert_eq!(parse_count_response(&json!({ "totalTokens": 42 })), Some(42));
+        assert_eq!(parse_count_response(&json!({ "response": { "totalTokens": 7 } })), Some(7));
+        assert_eq!(parse_count_response(&json!({})), None);
+    }
+}
@@ -16,6 +16,27 @@ pub struct RequestConfig {
     pub image_config: Option<Value>,
 }
 
+/// Gemini 因安全策略 (含图片安全) 拒绝生成时的 finishReason
+pub fn is_refusal_finish_reason(reason: &str) -> bool {
+    matches!(
+        reason,
+        "SAFETY" | "RECITATION" | "IMAGE_SAFETY" | "PROHIBITED_CONTENT" | "BLOCKLIST" | "SPII"
+    )
+}
+
+/// 提示词被 Gemini 安全策略拦截时的 `promptFeedback.blockReason` (兼容 v1internal 的 `response` 包装)
+pub fn prompt_block_reason(json: &Value) -> Option<&str> {
+    json.get("response")
+        .unwrap_or(json)
+        .pointer("/promptFeedback/blockReason")
+        .and_then(Value::as_str)
+}
+
+/// 安全拦截按错误返回时给客户端的说明
+pub fn safety_block_message(reason: &str) -> String {
+    format!("Request blocked by upstream safety filters ({})", reason)
+}
+
 pub fn resolve_request_config(
     original_model: &str, 
     mapped_model: &str,
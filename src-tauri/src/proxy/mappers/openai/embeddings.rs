@@ -0,0 +1,184 @@
+// OpenAI Embeddings ↔ Gemini batchEmbedContents 转换
+
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::proxy::mappers::claude::token_count::estimate_text_tokens;
+
+/// 单次请求允许的最大输入条数 (与 OpenAI 限制一致)
+pub const MAX_EMBEDDING_INPUTS: usize = 2048;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EmbeddingInput {
+    Single(String),
+    Batch(Vec<String>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingRequest {
+    pub model: String,
+    pub input: EmbeddingInput,
+    /// "float" (默认) 或 "base64"
+    #[serde(default)]
+    pub encoding_format: Option<String>,
+    #[serde(default)]
+    pub dimensions: Option<u32>,
+    #[serde(default)]
+    pub user: Option<String>,
+}
+
+impl EmbeddingRequest {
+    /// 校验并展开输入列表
+    pub fn inputs(&self) -> Result<Vec<String>, String> {
+        let inputs = match &self.input {
+            EmbeddingInput::Single(text) => vec![text.clone()],
+            EmbeddingInput::Batch(list) => list.clone(),
+        };
+        if inputs.is_empty() {
+            return Err("'input' must not be empty".to_string());
+        }
+        if inputs.len() > MAX_EMBEDDING_INPUTS {
+            return Err(format!("'input' supports at most {} items", MAX_EMBEDDING_INPUTS));
+        }
+        if inputs.iter().any(|s| s.is_empty()) {
+            return Err("'input' items must be non-empty strings".to_string());
+        }
+        match self.encoding_format.as_deref() {
+            None | Some("float") | Some("base64") => Ok(inputs),
+            Some(other) => Err(format!("Unsupported encoding_format: {}", other)),
+        }
+    }
+
+    fn wants_base64(&self) -> bool {
+        self.encoding_format.as_deref() == Some("base64")
+    }
+}
+
+/// 构造 Gemini batchEmbedContents 请求体 (不含 v1internal 外层包装)
+pub fn build_batch_embed_request(inputs: &[String], model: &str, dimensions: Option<u32>) -> Value {
+    let requests: Vec<Value> = inputs
+        .iter()
+        .map(|text| {
+            let mut req = json!({
+                "model": format!("models/{}", model),
+                "content": { "parts": [{ "text": text }] },
+            });
+            if let Some(d) = dimensions {
+                req["outputDimensionality"] = json!(d);
+            }
+            req
+        })
+        .collect();
+    json!({ "requests": requests })
+}
+
+fn encode_base64(values: &[f32]) -> String {
+    let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+/// 将上游响应转换为 OpenAI embeddings 响应 (model 字段保留客户端请求的模型名)
+pub fn transform_embed_response(
+    body: &Value,
+    request: &EmbeddingRequest,
+    inputs: &[String],
+) -> Result<Value, String> {
+    let inner = body.get("response").unwrap_or(body);
+    let embeddings = inner
+        .get("embeddings")
+        .and_then(|e| e.as_array())
+        .ok_or_else(|| "上游响应缺少 embeddings".to_string())?;
+    if embeddings.len() != inputs.len() {
+        return Err(format!(
+            "上游返回的向量数 ({}) 与输入数 ({}) 不一致",
+            embeddings.len(),
+            inputs.len()
+        ));
+    }
+
+    let mut data = Vec::with_capacity(embeddings.len());
+    for (index, item) in embeddings.iter().enumerate() {
+        let values: Vec<f32> = item
+            .get("values")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| format!("第 {} 个向量缺少 values", index))?
+            .iter()
+            .map(|v| v.as_f64().unwrap_or(0.0) as f32)
+            .collect();
+        let embedding = if request.wants_base64() {
+            json!(encode_base64(&values))
+        } else {
+            json!(values)
+        };
+        data.push(json!({ "object": "embedding", "index": index, "embedding": embedding }));
+    }
+
+    // 上游不返回用量，按本地估算给出输入规模
+    let prompt_tokens: u32 = inputs.iter().map(|s| estimate_text_tokens(s)).sum();
+    Ok(json!({
+        "object": "list",
+        "data": data,
+        "model": request.model,
+        "usage": { "prompt_tokens": prompt_tokens, "total_tokens": prompt_tokens },
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(value: Value) -> EmbeddingRequest {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_inputs() {
+        let single = request(json!({ "model": "text-embedding-3-small", "input": "hello" }));
+        assert_eq!(single.inputs().unwrap(), vec!["hello".to_string()]);
+
+        let batch = request(json!({ "model": "text-embedding-3-small", "input": ["a", "b"] }));
+        assert_eq!(batch.inputs().unwrap().len(), 2);
+
+        let empty = request(json!({ "model": "m", "input": [] }));
+        assert!(empty.inputs().is_err());
+
+        let bad_format = request(json!({ "model": "m", "input": "x", "encoding_format": "int8" }));
+        assert!(bad_format.inputs().is_err());
+
+        // 不支持 token 数组输入
+        assert!(serde_json::from_value::<EmbeddingRequest>(json!({ "model": "m", "input": [1, 2] })).is_err());
+    }
+
+    #[test]
+    fn test_build_and_transform_batch() {
+        let req = request(json!({ "model": "text-embedding-3-small", "input": ["a", "b"], "dimensions": 2 }));
+        let inputs = req.inputs().unwrap();
+        let body = build_batch_embed_request(&inputs, "gemini-embedding-001", req.dimensions);
+        let requests = body["requests"].as_array().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1]["model"], "models/gemini-embedding-001");
+        assert_eq!(requests[1]["content"]["parts"][0]["text"], "b");
+        assert_eq!(requests[0]["outputDimensionality"], 2);
+
+        let upstream = json!({ "response": { "embeddings": [{ "values": [0.5, 1.0] }, { "values": [0.25, 0.0] }] } });
+        let out = transform_embed_response(&upstream, &req, &inputs).unwrap();
+        assert_eq!(out["object"], "list");
+        assert_eq!(out["model"], "text-embedding-3-small");
+        assert_eq!(out["data"][1]["index"], 1);
+        assert_eq!(out["data"][0]["embedding"], json!([0.5, 1.0]));
+        assert_eq!(out["usage"]["prompt_tokens"], 2);
+
+        let mismatched = json!({ "embeddings": [{ "values": [0.1] }] });
+        assert!(transform_embed_response(&mismatched, &req, &inputs).is_err());
+    }
+
+    #[test]
+    fn test_transform_base64() {
+        let req = request(json!({ "model": "m", "input": "x", "encoding_format": "base64" }));
+        let upstream = json!({ "embeddings": [{ "values": [1.0] }] });
+        let out = transform_embed_response(&upstream, &req, &["x".to_string()]).unwrap();
+        assert_eq!(out["data"][0]["embedding"], encode_base64(&[1.0]));
+    }
+}
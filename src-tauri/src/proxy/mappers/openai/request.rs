@@ -84,18 +84,18 @@ pub fn transform_openai_request(request: &OpenAIRequest, project_id: &str, mappe
                                 }
                                 OpenAIContentBlock::ImageUrl { image_url } => {
                                     if image_url.url.starts_with("data:") {
-                                        if let Some(pos) = image_url.url.find(",") {
-                                            let mime_part = &image_url.url[5..pos];
-                                            let mime_type = mime_part.split(';').next().unwrap_or("image/jpeg");
-                                            let data = &image_url.url[pos + 1..];
-                                            
+                                        if let Some(image) = crate::proxy::common::image::parse_data_url(&image_url.url) {
                                             parts.push(json!({
-                                                "inlineData": { "mimeType": mime_type, "data": data }
+                                                "inlineData": { "mimeType": image.mime_type, "data": image.data }
                                             }));
                                         }
                                     } else if image_url.url.starts_with("http") {
+                                        // 处理器通常已将远程图片下载为 Data URL，这里仅作兜底
                                         parts.push(json!({
-                                            "fileData": { "fileUri": &image_url.url, "mimeType": "image/jpeg" }
+                                            "fileData": {
+                                                "fileUri": &image_url.url,
+                                                "mimeType": crate::proxy::common::image::guess_mime_from_path(&image_url.url)
+                                            }
                                         }));
                                     } else {
                                         // [NEW] 处理本地文件路径 (file:// 或 Windows/Unix 路径)
@@ -1,6 +1,7 @@
 // OpenAI 协议响应转换模块
 use super::models::*;
 use serde_json::Value;
+use crate::proxy::mappers::common_utils::{is_refusal_finish_reason, prompt_block_reason};
 
 pub fn transform_openai_response(gemini_response: &Value) -> OpenAIResponse {
     // 解包 response 字段
@@ -129,8 +130,7 @@ pub fn transform_openai_response(gemini_response: &Value) -> OpenAIResponse {
                 .map(|f| match f {
                     "STOP" => "stop",
                     "MAX_TOKENS" => "length",
-                    "SAFETY" => "content_filter",
-                    "RECITATION" => "content_filter",
+                    f if is_refusal_finish_reason(f) => "content_filter",
                     _ => "stop",
                 })
                 .unwrap_or("stop");
@@ -162,6 +162,22 @@ pub fn transform_openai_response(gemini_response: &Value) -> OpenAIResponse {
         }
     }
 
+    // 提示词被安全策略拦截时没有候选结果，返回带 content_filter 标记的空回复
+    if choices.is_empty() && prompt_block_reason(gemini_response).is_some() {
+        choices.push(Choice {
+            index: 0,
+            message: OpenAIMessage {
+                role: "assistant".to_string(),
+                content: None,
+                reasoning_content: None,
+                tool_calls: None,
+                tool_call_id: None,
+                name: None,
+            },
+            finish_reason: Some("content_filter".to_string()),
+        });
+    }
+
     OpenAIResponse {
         id: raw
             .get("responseId")
@@ -206,4 +222,19 @@ mod tests {
         assert_eq!(content, "Hello!");
         assert_eq!(result.choices[0].finish_reason, Some("stop".to_string()));
     }
+
+    #[test]
+    fn test_transform_prompt_blocked() {
+        let gemini_resp = json!({
+            "response": {
+                "promptFeedback": {"blockReason": "PROHIBITED_CONTENT"},
+                "responseId": "resp_456"
+            }
+        });
+
+        let result = transform_openai_response(&gemini_resp);
+        assert_eq!(result.choices.len(), 1);
+        assert!(result.choices[0].message.content.is_none());
+        assert_eq!(result.choices[0].finish_reason.as_deref(), Some("content_filter"));
+    }
 }
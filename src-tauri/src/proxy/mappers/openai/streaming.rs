@@ -8,6 +8,7 @@ use chrono::Utc;
 use uuid::Uuid;
 use tracing::debug;
 use rand::Rng;
+use crate::proxy::mappers::common_utils::{is_refusal_finish_reason, prompt_block_reason, safety_block_message};
 
 // === 全局 ThoughtSignature 存储 ===
 // 用于在流式响应和后续请求之间传递签名，避免嵌入到用户可见的文本中
@@ -50,9 +51,36 @@ pub fn get_thought_signature() -> Option<String> {
     }
 }
 
+/// 安全拦截按错误返回时的 SSE 错误块，格式与流错误一致
+fn content_filter_error_chunk(stream_id: &str, created_ts: i64, model: &str, reason: &str) -> Bytes {
+    let error_chunk = json!({
+        "id": stream_id,
+        "object": "chat.completion.chunk",
+        "created": created_ts,
+        "model": model,
+        "choices": [],
+        "error": {
+            "type": "invalid_request_error",
+            "message": safety_block_message(reason),
+            "code": "content_filter"
+        }
+    });
+    Bytes::from(format!("data: {}\n\n", serde_json::to_string(&error_chunk).unwrap_or_default()))
+}
+
+/// 在 choice 的第一个 delta 中加上 `role`
+fn with_role(mut delta: Value, first: bool) -> Value {
+    if first {
+        delta["role"] = json!("assistant");
+    }
+    delta
+}
+
+/// `block_as_error` 为 true 时，未输出任何内容即被安全拦截的请求以错误块结束，否则以 `content_filter` 标记的空回复结束
 pub fn create_openai_sse_stream(
     mut gemini_stream: Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
     model: String,
+    block_as_error: bool,
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
     let mut buffer = BytesMut::new();
     
@@ -62,7 +90,15 @@ pub fn create_openai_sse_stream(
     
     let stream = async_stream::stream! {
         let mut emitted_tool_calls = std::collections::HashSet::new();
-        while let Some(item) = gemini_stream.next().await {
+        // 是否已向客户端输出过内容 (正文、思考或工具调用)
+        let mut emitted_output = false;
+        // 每个 choice 已输出的工具调用数，作为 tool_calls 的 index
+        let mut tool_call_counts: std::collections::HashMap<usize, u32> = std::collections::HashMap::new();
+        // 已发送 finish_reason 的 choice，之后的数据忽略
+        let mut finished_choices = std::collections::HashSet::new();
+        // role 只在每个 choice 的第一个 delta 中出现
+        let mut role_sent = std::collections::HashSet::new();
+        'upstream: while let Some(item) = gemini_stream.next().await {
             match item {
                 Ok(bytes) => {
                     // Verbose logging for debugging image fragmentation
@@ -93,9 +129,36 @@ pub fn create_openai_sse_stream(
                                         json
                                     };
 
+                                    // 提示词被安全策略拦截：没有候选结果，只有 promptFeedback.blockReason
+                                    if actual_data.get("candidates").is_none() {
+                                        if let Some(reason) = prompt_block_reason(&actual_data) {
+                                            if block_as_error && !emitted_output {
+                                                yield Ok::<Bytes, String>(content_filter_error_chunk(&stream_id, created_ts, &model, reason));
+                                                break 'upstream;
+                                            }
+                                            let blocked_chunk = json!({
+                                                "id": &stream_id,
+                                                "object": "chat.completion.chunk",
+                                                "created": created_ts,
+                                                "model": &model,
+                                                "choices": [{
+                                                    "index": 0,
+                                                    "delta": { "role": "assistant", "content": "" },
+                                                    "finish_reason": "content_filter"
+                                                }]
+                                            });
+                                            let sse_out = format!("data: {}\n\n", serde_json::to_string(&blocked_chunk).unwrap_or_default());
+                                            yield Ok::<Bytes, String>(Bytes::from(sse_out));
+                                            continue;
+                                        }
+                                    }
+
                                     // Extract candidates
                                     if let Some(candidates) = actual_data.get("candidates").and_then(|c| c.as_array()) {
                                         for (idx, candidate) in candidates.iter().enumerate() {
+                                            if finished_choices.contains(&idx) {
+                                                continue;
+                                            }
                                             let parts = candidate.get("content").and_then(|c| c.get("parts")).and_then(|p| p.as_array());
 
                                             let mut content_out = String::new();
@@ -142,32 +205,43 @@ pub fn create_openai_sse_stream(
                                                             serde_json::to_string(func_call).unwrap_or_default().hash(&mut hasher);
                                                             let call_id = format!("call_{:x}", hasher.finish());
                                                             
-                                                            // Emit tool_calls delta
-                                                            let tool_call_chunk = json!({
-                                                                "id": &stream_id,
-                                                                "object": "chat.completion.chunk",
-                                                                "created": created_ts,
-                                                                "model": &model,
-                                                                "choices": [{
-                                                                    "index": idx as u32,
-                                                                    "delta": {
-                                                                        "role": "assistant",
-                                                                        "tool_calls": [{
-                                                                            "index": 0,
-                                                                            "id": call_id,
-                                                                            "type": "function",
-                                                                            "function": {
-                                                                                "name": name,
-                                                                                "arguments": args
-                                                                            }
-                                                                        }]
-                                                                    },
-                                                                    "finish_reason": serde_json::Value::Null
+                                                            // 与 OpenAI 一致：首个分片带 id、type 与 name (arguments 为空)，参数在后续分片中按同一 index 追加
+                                                            let count = tool_call_counts.entry(idx).or_insert(0);
+                                                            let call_index = *count;
+                                                            *count += 1;
+                                                            let mut header_delta = json!({
+                                                                "tool_calls": [{
+                                                                    "index": call_index,
+                                                                    "id": call_id,
+                                                                    "type": "function",
+                                                                    "function": { "name": name, "arguments": "" }
                                                                 }]
                                                             });
-                                                            
-                                                            let sse_out = format!("data: {}\n\n", serde_json::to_string(&tool_call_chunk).unwrap_or_default());
-                                                            yield Ok::<Bytes, String>(Bytes::from(sse_out));
+                                                            if role_sent.insert(idx) {
+                                                                header_delta["role"] = json!("assistant");
+                                                            }
+                                                            let args_delta = json!({
+                                                                "tool_calls": [{
+                                                                    "index": call_index,
+                                                                    "function": { "arguments": args }
+                                                                }]
+                                                            });
+                                                            emitted_output = true;
+                                                            for delta in [header_delta, args_delta] {
+                                                                let tool_call_chunk = json!({
+                                                                    "id": &stream_id,
+                                                                    "object": "chat.completion.chunk",
+                                                                    "created": created_ts,
+                                                                    "model": &model,
+                                                                    "choices": [{
+                                                                        "index": idx as u32,
+                                                                        "delta": delta,
+                                                                        "finish_reason": serde_json::Value::Null
+                                                                    }]
+                                                                });
+                                                                let sse_out = format!("data: {}\n\n", serde_json::to_string(&tool_call_chunk).unwrap_or_default());
+                                                                yield Ok::<Bytes, String>(Bytes::from(sse_out));
+                                                            }
                                                         }
                                                     }
                                                 }
@@ -216,16 +290,27 @@ pub fn create_openai_sse_stream(
                                                 }
                                             }
                                                 
-                                            // Extract finish reason
+                                            // 未输出任何内容即被安全拦截，按配置改为错误块
+                                            if let Some(reason) = candidate.get("finishReason").and_then(|f| f.as_str()) {
+                                                if block_as_error && is_refusal_finish_reason(reason) && !emitted_output && content_out.is_empty() && thought_out.is_empty() {
+                                                    yield Ok::<Bytes, String>(content_filter_error_chunk(&stream_id, created_ts, &model, reason));
+                                                    break 'upstream;
+                                                }
+                                            }
+
+                                            // Extract finish reason；本轮输出过工具调用时以 tool_calls 结束
                                             let finish_reason = candidate.get("finishReason")
                                                 .and_then(|f| f.as_str())
                                                 .map(|f| match f {
+                                                    "STOP" if tool_call_counts.contains_key(&idx) => "tool_calls",
                                                     "STOP" => "stop",
                                                     "MAX_TOKENS" => "length",
-                                                    "SAFETY" => "content_filter",
-                                                    "RECITATION" => "content_filter",
+                                                    f if is_refusal_finish_reason(f) => "content_filter",
                                                     _ => f,
                                                 });
+                                            if finish_reason.is_some() {
+                                                finished_choices.insert(idx);
+                                            }
 
                                             // Construct OpenAI SSE chunk
                                             // 如果有思考内容，先发送 reasoning_content chunk
@@ -238,21 +323,22 @@ pub fn create_openai_sse_stream(
                                                     "choices": [
                                                         {
                                                             "index": idx as u32,
-                                                            "delta": {
-                                                                "role": "assistant",
+                                                            "delta": with_role(json!({
                                                                 "content": serde_json::Value::Null,
                                                                 "reasoning_content": thought_out
-                                                            },
+                                                            }), role_sent.insert(idx)),
                                                             "finish_reason": serde_json::Value::Null
                                                         }
                                                     ]
                                                 });
                                                 let sse_out = format!("data: {}\n\n", serde_json::to_string(&reasoning_chunk).unwrap_or_default());
+                                                emitted_output = true;
                                                 yield Ok::<Bytes, String>(Bytes::from(sse_out));
                                             }
 
                                             // 发送正常 content chunk
                                             if !content_out.is_empty() || finish_reason.is_some() {
+                                                emitted_output |= !content_out.is_empty();
                                                 let openai_chunk = json!({
                                                     "id": &stream_id,
                                                     "object": "chat.completion.chunk",
@@ -261,9 +347,9 @@ pub fn create_openai_sse_stream(
                                                     "choices": [
                                                         {
                                                             "index": idx as u32,
-                                                            "delta": {
+                                                            "delta": with_role(json!({
                                                                 "content": content_out
-                                                            },
+                                                            }), role_sent.insert(idx)),
                                                             "finish_reason": finish_reason
                                                         }
                                                     ]
@@ -308,12 +394,11 @@ pub fn create_openai_sse_stream(
                     
                     let sse_out = format!("data: {}\n\n", serde_json::to_string(&error_chunk).unwrap_or_default());
                     yield Ok(Bytes::from(sse_out));
-                    yield Ok(Bytes::from("data: [DONE]\n\n"));
                     break;
                 }
             }
         }
-        // End of stream signal for OpenAI
+        // End of stream signal for OpenAI (出错时同样只发送一次)
         yield Ok::<Bytes, String>(Bytes::from("data: [DONE]\n\n"));
     };
 
@@ -385,7 +470,7 @@ pub fn create_legacy_sse_stream(
                                         .map(|f| match f {
                                             "STOP" => "stop",
                                             "MAX_TOKENS" => "length",
-                                            "SAFETY" => "content_filter",
+                                            f if is_refusal_finish_reason(f) => "content_filter",
                                             _ => f,
                                         });
 
@@ -512,6 +597,7 @@ pub fn create_codex_sse_stream(
                                             last_finish_reason = match reason {
                                                 "STOP" => "stop".to_string(),
                                                 "MAX_TOKENS" => "length".to_string(),
+                                                r if is_refusal_finish_reason(r) => "content_filter".to_string(),
                                                 _ => "stop".to_string(),
                                             };
                                         }
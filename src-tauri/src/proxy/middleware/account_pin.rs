@@ -0,0 +1,88 @@
+// 账号固定中间件：调试时按请求头将单个请求固定到指定账号
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+use crate::proxy::security::{ApiKeyName, SecuritySnapshot};
+use crate::proxy::server::AppState;
+use crate::proxy::token_manager::{pinned_account, PinnedAccountError, PINNED_ACCOUNT};
+
+/// 指定本次请求使用的账号 (email 或账号 ID) 的请求头
+pub const ACCOUNT_HEADER: &str = "x-antigravity-account";
+
+/// 响应扩展：本次请求经请求头固定了账号
+#[derive(Debug, Clone, Copy)]
+pub struct Pinned;
+
+fn error_response(status: StatusCode, error_type: &str, message: String) -> Response {
+    (
+        status,
+        Json(json!({
+            "type": "error",
+            "error": {
+                "type": error_type,
+                "message": message
+            }
+        })),
+    )
+        .into_response()
+}
+
+/// 开启 `allow_account_pinning` 时，管理员密钥可通过请求头固定账号；账号不可用时返回原因而不回退调度
+pub async fn account_pin_middleware(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(value) = request.headers().get(ACCOUNT_HEADER) else {
+        return next.run(request).await;
+    };
+    // 重放等已固定账号的请求沿用原有固定
+    if pinned_account().is_some() {
+        return next.run(request).await;
+    }
+    if !state.experimental.read().await.allow_account_pinning {
+        tracing::debug!("Ignored {} header (allow_account_pinning is off)", ACCOUNT_HEADER);
+        return next.run(request).await;
+    }
+
+    let account = value.to_str().unwrap_or_default().trim().to_string();
+    if account.is_empty() {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "invalid_request_error",
+            format!("{} header must not be empty", ACCOUNT_HEADER),
+        );
+    }
+
+    let is_admin = match (
+        request.extensions().get::<ApiKeyName>(),
+        request.extensions().get::<SecuritySnapshot>(),
+    ) {
+        (Some(ApiKeyName(name)), Some(SecuritySnapshot(security))) => security.is_admin(name),
+        _ => false,
+    };
+    if !is_admin {
+        return error_response(
+            StatusCode::FORBIDDEN,
+            "permission_error",
+            format!("{} header requires an authenticated admin API key", ACCOUNT_HEADER),
+        );
+    }
+
+    let email = match state.token_manager.resolve_pinned_account(&account) {
+        Ok(email) => email,
+        Err(e @ PinnedAccountError::NotFound(_)) => {
+            return error_response(StatusCode::NOT_FOUND, "not_found_error", e.to_string());
+        }
+        Err(e @ PinnedAccountError::Unusable { .. }) => {
+            return error_response(StatusCode::CONFLICT, "account_unavailable_error", e.to_string());
+        }
+    };
+
+    tracing::info!("请求已固定到账号: {}", email);
+    let mut response = PINNED_ACCOUNT.scope(email, next.run(request)).await;
+    response.extensions_mut().insert(Pinned);
+    response
+}
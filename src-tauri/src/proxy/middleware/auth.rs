@@ -1,20 +1,28 @@
 // API Key 认证中间件
 use axum::{
+    body::Body,
     extract::State,
     extract::Request,
     http::{header, StatusCode},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
+    Json,
 };
+use futures::StreamExt;
+use serde_json::json;
 use std::sync::Arc;
-use tokio::sync::RwLock;
 
-use crate::proxy::{ProxyAuthMode, ProxySecurityConfig};
+use crate::proxy::ip_filter::ClientIp;
+use crate::proxy::key_limiter::{KeyLimits, KeyRateLimiter, LimitExceeded};
+use crate::proxy::security::{ApiKeyName, SecuritySnapshot, SharedSecurity};
+use crate::proxy::token_manager::ACCOUNT_GROUP;
+use crate::proxy::unauthorized::UnauthorizedTracker;
+use crate::proxy::ProxyAuthMode;
 
 /// API Key 认证中间件
 pub async fn auth_middleware(
-    State(security): State<Arc<RwLock<ProxySecurityConfig>>>,
-    request: Request,
+    State((security, limiter, unauthorized)): State<(SharedSecurity, Arc<KeyRateLimiter>, Arc<UnauthorizedTracker>)>,
+    mut request: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
     let method = request.method().clone();
@@ -27,12 +35,23 @@ pub async fn auth_middleware(
         tracing::trace!("Heartbeat: {} {}", method, path);
     }
 
+    // 被封禁的来源直接拒绝，不做任何后续处理
+    let client_ip = request.extensions().get::<ClientIp>().map(|ClientIp(ip)| *ip);
+    let now = chrono::Utc::now().timestamp_millis();
+    if client_ip.is_some_and(|ip| unauthorized.banned_until(ip, now).is_some()) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
     // Allow CORS preflight regardless of auth policy.
     if method == axum::http::Method::OPTIONS {
         return Ok(next.run(request).await);
     }
 
-    let security = security.read().await.clone();
+    // 优先使用 IP 过滤中间件取得的快照，与其判断保持一致
+    let security = match request.extensions().get::<SecuritySnapshot>() {
+        Some(SecuritySnapshot(snapshot)) => snapshot.clone(),
+        None => security.read().await.clone(),
+    };
     let effective_mode = security.effective_auth_mode();
 
     if matches!(effective_mode, ProxyAuthMode::Off) {
@@ -56,21 +75,93 @@ pub async fn auth_middleware(
                 .and_then(|h| h.to_str().ok())
         });
 
-    if security.api_key.is_empty() {
-        tracing::error!("Proxy auth is enabled but api_key is empty; denying request");
+    if !security.has_keys() {
+        tracing::error!("Proxy auth is enabled but no api_key is configured; denying request");
         return Err(StatusCode::UNAUTHORIZED);
     }
 
     // Constant-time compare is unnecessary here, but keep strict equality and avoid leaking values.
-    let authorized = api_key.map(|k| k == security.api_key).unwrap_or(false);
+    let key_name = api_key.and_then(|k| security.authenticate(k)).map(str::to_string);
 
-    if authorized {
-        Ok(next.run(request).await)
-    } else {
-        Err(StatusCode::UNAUTHORIZED)
+    match key_name {
+        Some(name) => {
+            if let Some(ip) = client_ip {
+                unauthorized.record_success(ip);
+            }
+            let limits = security
+                .api_keys
+                .iter()
+                .find(|entry| entry.name == name)
+                .map(KeyLimits::from_entry)
+                .unwrap_or_default();
+            let permit = match limiter.acquire(&name, limits).await {
+                Ok(permit) => permit,
+                Err(exceeded) => return Ok(limit_exceeded_response(&name, exceeded)),
+            };
+
+            // 供监控中间件记录请求来自哪个密钥
+            request.extensions_mut().insert(ApiKeyName(name.clone()));
+            let response = match security.group_for_key(&name) {
+                Some(group) => ACCOUNT_GROUP.scope(group.clone(), next.run(request)).await,
+                None => next.run(request).await,
+            };
+            if let Some(email) = response.headers().get("X-Account-Email").and_then(|v| v.to_str().ok()) {
+                limiter.record_account(&name, email);
+            }
+
+            // 并发许可持有到响应体 (含流式输出) 发送完毕
+            Ok(match permit {
+                Some(permit) => response.map(|body| {
+                    Body::from_stream(body.into_data_stream().map(move |chunk| {
+                        let _ = &permit;
+                        chunk
+                    }))
+                }),
+                None => response,
+            })
+        }
+        None => {
+            if let Some(ip) = client_ip {
+                if unauthorized.record_failure(ip, &path, &security.lockout, now) {
+                    tracing::warn!(
+                        "Banned proxy client {} for {} minutes after {} failed auth attempts",
+                        ip,
+                        security.lockout.ban_minutes,
+                        security.lockout.ban_after
+                    );
+                }
+            }
+            Err(StatusCode::UNAUTHORIZED)
+        }
     }
 }
 
+fn limit_exceeded_response(name: &str, exceeded: LimitExceeded) -> Response {
+    let (retry_after, message) = match exceeded {
+        LimitExceeded::RateLimited { retry_after } => (
+            retry_after,
+            format!("API key '{}' exceeded its requests-per-minute limit", name),
+        ),
+        LimitExceeded::TooManyConcurrent => (
+            1,
+            format!("API key '{}' has too many concurrent requests", name),
+        ),
+    };
+    tracing::warn!("{}", message);
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(header::RETRY_AFTER, retry_after.to_string())],
+        Json(json!({
+            "type": "error",
+            "error": {
+                "type": "rate_limit_error",
+                "message": message
+            }
+        })),
+    )
+        .into_response()
+}
+
 #[cfg(test)]
 mod tests {
     // 移除未使用的 use super::*;
@@ -80,4 +171,76 @@ mod tests {
         // Placeholder test
         assert!(true);
     }
+
+    #[tokio::test]
+    async fn request_never_observes_half_updated_security() {
+        use super::*;
+        use crate::proxy::ip_filter::IpAccessList;
+        use crate::proxy::ProxySecurityConfig;
+        use axum::extract::ConnectInfo;
+        use std::net::SocketAddr;
+        use tokio::sync::RwLock;
+        use tower::ServiceExt;
+
+        fn config(key: &str, allowed: &str) -> Arc<ProxySecurityConfig> {
+            Arc::new(ProxySecurityConfig {
+                auth_mode: ProxyAuthMode::Strict,
+                api_key: key.to_string(),
+                api_keys: Vec::new(),
+                account_groups: Vec::new(),
+                allow_lan_access: true,
+                ip_access: IpAccessList::new(&[allowed.to_string()], &[]),
+                lockout: Default::default(),
+            })
+        }
+
+        // A 只放行本机且只认 sk-a，B 只放行 10.0.0.0/8 且只认 sk-b：
+        // 来自 10.0.0.1 携带 sk-a 的请求在任一完整配置下都会被拒绝，只有新旧混合时才会通过
+        let config_a = config("sk-a", "127.0.0.0/8");
+        let config_b = config("sk-b", "10.0.0.0/8");
+        let security: SharedSecurity = Arc::new(RwLock::new(config_a.clone()));
+        let app = axum::Router::new()
+            .route("/v1/models", axum::routing::get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn_with_state(
+                (security.clone(), Arc::new(KeyRateLimiter::default()), Arc::new(UnauthorizedTracker::default())),
+                auth_middleware,
+            ))
+            .layer(axum::middleware::from_fn_with_state(
+                security.clone(),
+                crate::proxy::middleware::ip_filter_middleware,
+            ));
+
+        let updater = {
+            let security = security.clone();
+            tokio::spawn(async move {
+                for i in 0..2000 {
+                    let next = if i % 2 == 0 { config_b.clone() } else { config_a.clone() };
+                    *security.write().await = next;
+                    tokio::task::yield_now().await;
+                }
+            })
+        };
+
+        let requests: Vec<_> = (0..200)
+            .map(|_| {
+                let app = app.clone();
+                tokio::spawn(async move {
+                    let mut request = Request::builder()
+                        .uri("/v1/models")
+                        .header(header::AUTHORIZATION, "Bearer sk-a")
+                        .body(Body::empty())
+                        .unwrap();
+                    request
+                        .extensions_mut()
+                        .insert(ConnectInfo("10.0.0.1:40000".parse::<SocketAddr>().unwrap()));
+                    app.oneshot(request).await.unwrap().status()
+                })
+            })
+            .collect();
+
+        for request in requests {
+            assert_ne!(request.await.unwrap(), StatusCode::OK);
+        }
+        updater.await.unwrap();
+    }
 }
@@ -0,0 +1,52 @@
+// 上游端点选择中间件
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::proxy::config::{normalize_endpoint, ExperimentalConfig};
+use crate::proxy::upstream::client::{EndpointSelection, ENDPOINT_SELECTION};
+
+/// 为单个请求选择上游端点的请求头
+pub const ENDPOINT_HEADER: &str = "x-antigravity-endpoint";
+
+/// 为每个请求建立端点上下文；开启 `enable_endpoint_header` 时按请求头选择端点，不在白名单内返回 400
+pub async fn endpoint_middleware(
+    State(experimental): State<Arc<RwLock<ExperimentalConfig>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut selection = EndpointSelection::default();
+    if let Some(value) = request.headers().get(ENDPOINT_HEADER) {
+        if experimental.read().await.enable_endpoint_header {
+            match normalize_endpoint(value.to_str().unwrap_or_default()) {
+                Ok(endpoint) => selection.requested = Some(endpoint),
+                Err(e) => {
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        Json(json!({
+                            "type": "error",
+                            "error": {
+                                "type": "invalid_request_error",
+                                "message": e
+                            }
+                        })),
+                    )
+                        .into_response();
+                }
+            }
+        } else {
+            tracing::debug!("Ignored {} header (enable_endpoint_header is off)", ENDPOINT_HEADER);
+        }
+    }
+
+    ENDPOINT_SELECTION
+        .scope(std::cell::RefCell::new(selection), next.run(request))
+        .await
+}
@@ -0,0 +1,235 @@
+// 上游错误转换中间件：将透传的 Google 原始错误按客户端协议 (OpenAI / Anthropic) 重新包装
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::{json, Value};
+
+use crate::proxy::monitor::ErrorClass;
+
+/// 读取错误响应体的上限
+const MAX_ERROR_BODY: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ClientProtocol {
+    OpenAI,
+    Anthropic,
+}
+
+impl ClientProtocol {
+    /// Gemini 原生协议与内部接口保持上游格式
+    fn from_path(path: &str) -> Option<Self> {
+        if path.starts_with("/v1/messages") {
+            Some(ClientProtocol::Anthropic)
+        } else if path.starts_with("/v1/") && !path.starts_with("/v1/api/") {
+            Some(ClientProtocol::OpenAI)
+        } else {
+            None
+        }
+    }
+
+    /// 响应体已是该协议的错误格式
+    fn is_native(&self, json: &Value) -> bool {
+        match self {
+            ClientProtocol::OpenAI => json.pointer("/error/type").is_some_and(Value::is_string),
+            ClientProtocol::Anthropic => json.get("type").and_then(Value::as_str) == Some("error"),
+        }
+    }
+}
+
+/// 提取上游错误消息：Google 错误的 `error.message`，或文本中嵌入的 JSON
+fn upstream_message(body: &str) -> String {
+    let json = serde_json::from_str::<Value>(body)
+        .ok()
+        .or_else(|| body.find('{').and_then(|start| serde_json::from_str::<Value>(&body[start..]).ok()));
+    json.as_ref()
+        .and_then(|json| json.pointer("/error/message").or_else(|| json.get("message")))
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .unwrap_or_else(|| body.trim().to_string())
+}
+
+fn summary(class: ErrorClass) -> &'static str {
+    match class {
+        ErrorClass::RegionBlocked => "Upstream account region is not supported",
+        ErrorClass::RateLimited => "Upstream quota exhausted or rate limited",
+        ErrorClass::Auth => "Upstream authentication failed",
+        ErrorClass::SafetyBlocked => "Request blocked by upstream safety filters",
+        ErrorClass::InvalidRequest => "Upstream rejected the request",
+        ErrorClass::Overloaded => "Upstream is overloaded",
+        ErrorClass::Upstream => "Upstream server error",
+    }
+}
+
+/// 客户端看到的状态码：安全拦截 400，配额 429，过载 503 (Anthropic 为 529)，其余沿用上游
+fn client_status(class: ErrorClass, protocol: ClientProtocol, status: StatusCode) -> StatusCode {
+    match class {
+        ErrorClass::SafetyBlocked => StatusCode::BAD_REQUEST,
+        ErrorClass::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+        ErrorClass::RegionBlocked => StatusCode::FORBIDDEN,
+        ErrorClass::Overloaded if protocol == ClientProtocol::Anthropic => {
+            StatusCode::from_u16(529).unwrap_or(StatusCode::SERVICE_UNAVAILABLE)
+        }
+        ErrorClass::Overloaded => StatusCode::SERVICE_UNAVAILABLE,
+        _ => status,
+    }
+}
+
+/// 按客户端协议生成错误响应体
+fn translate(protocol: ClientProtocol, class: ErrorClass, status: StatusCode, body: &str) -> Value {
+    let details = upstream_message(body);
+    let message = format!("{}: {}", summary(class), details);
+    match protocol {
+        ClientProtocol::OpenAI => {
+            let (error_type, code) = match class {
+                ErrorClass::RegionBlocked => ("permission_error", "unsupported_region"),
+                ErrorClass::RateLimited => ("rate_limit_error", "rate_limit_exceeded"),
+                ErrorClass::Auth => ("authentication_error", "upstream_auth_failed"),
+                ErrorClass::SafetyBlocked => ("invalid_request_error", "content_filter"),
+                ErrorClass::InvalidRequest => ("invalid_request_error", "invalid_request"),
+                ErrorClass::Overloaded => ("server_error", "overloaded"),
+                ErrorClass::Upstream => ("api_error", "upstream_error"),
+            };
+            json!({
+                "error": {
+                    "message": message,
+                    "type": error_type,
+                    "param": null,
+                    "code": code,
+                    "details": details
+                }
+            })
+        }
+        ClientProtocol::Anthropic => {
+            let error_type = match class {
+                ErrorClass::RegionBlocked => "permission_error",
+                ErrorClass::RateLimited => "rate_limit_error",
+                ErrorClass::Auth if status == StatusCode::FORBIDDEN => "permission_error",
+                ErrorClass::Auth => "authentication_error",
+                ErrorClass::InvalidRequest if status == StatusCode::NOT_FOUND => "not_found_error",
+                ErrorClass::SafetyBlocked | ErrorClass::InvalidRequest => "invalid_request_error",
+                ErrorClass::Overloaded => "overloaded_error",
+                ErrorClass::Upstream => "api_error",
+            };
+            json!({
+                "type": "error",
+                "error": {
+                    "type": error_type,
+                    "message": message,
+                    "details": details
+                }
+            })
+        }
+    }
+}
+
+/// Token 刷新熔断导致的 503 附加 `Retry-After`，提示客户端稍后重试
+async fn with_refresh_retry_after(response: Response) -> Response {
+    let Some(retry_after) = crate::modules::oauth::refresh_retry_after() else {
+        return response;
+    };
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, MAX_ERROR_BODY).await {
+        Ok(bytes) => bytes,
+        Err(_) => return Response::from_parts(parts, Body::empty()),
+    };
+    if String::from_utf8_lossy(&bytes).contains(crate::modules::oauth::REFRESH_THROTTLED) {
+        parts.headers.insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+    }
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+pub async fn error_translation_middleware(request: Request, next: Next) -> Response {
+    let protocol = ClientProtocol::from_path(request.uri().path());
+    let mut response = next.run(request).await;
+    let status = response.status();
+    if status == StatusCode::SERVICE_UNAVAILABLE {
+        response = with_refresh_retry_after(response).await;
+    }
+    let Some(protocol) = protocol.filter(|_| status.as_u16() >= 400) else {
+        return response;
+    };
+    let is_stream = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.contains("text/event-stream"));
+    if is_stream {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, MAX_ERROR_BODY).await {
+        Ok(bytes) => bytes,
+        Err(_) => return Response::from_parts(parts, Body::empty()),
+    };
+    let text = String::from_utf8_lossy(&bytes);
+    let Some(class) = ErrorClass::classify(status.as_u16(), &text) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    // 供监控记录分类，转换后的响应体不再含上游原始结构
+    parts.extensions.insert(class);
+
+    let already_native = serde_json::from_slice::<Value>(&bytes).is_ok_and(|json| protocol.is_native(&json));
+    if already_native {
+        return Response::from_parts(parts, Body::from(bytes));
+    }
+
+    let translated = translate(protocol, class, status, &text);
+    parts.status = client_status(class, protocol, status);
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts
+        .headers
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    Response::from_parts(parts, Body::from(translated.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOOGLE_QUOTA: &str = r#"{"error":{"code":429,"message":"Resource has been exhausted (e.g. check quota).","status":"RESOURCE_EXHAUSTED"}}"#;
+
+    #[test]
+    fn test_translate_openai_and_anthropic() {
+        let openai = translate(ClientProtocol::OpenAI, ErrorClass::RateLimited, StatusCode::TOO_MANY_REQUESTS, GOOGLE_QUOTA);
+        assert_eq!(openai["error"]["type"], "rate_limit_error");
+        assert_eq!(openai["error"]["code"], "rate_limit_exceeded");
+        assert_eq!(openai["error"]["details"], "Resource has been exhausted (e.g. check quota).");
+
+        let anthropic = translate(ClientProtocol::Anthropic, ErrorClass::Overloaded, StatusCode::SERVICE_UNAVAILABLE, "HTTP 503: busy");
+        assert_eq!(anthropic["type"], "error");
+        assert_eq!(anthropic["error"]["type"], "overloaded_error");
+        assert_eq!(anthropic["error"]["details"], "HTTP 503: busy");
+
+        // 文本中嵌入的上游 JSON
+        assert_eq!(
+            upstream_message(&format!("All accounts exhausted. Last error: HTTP 429: {}", GOOGLE_QUOTA)),
+            "Resource has been exhausted (e.g. check quota)."
+        );
+    }
+
+    #[test]
+    fn test_client_status_and_protocol() {
+        assert_eq!(
+            client_status(ErrorClass::SafetyBlocked, ClientProtocol::OpenAI, StatusCode::INTERNAL_SERVER_ERROR),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            client_status(ErrorClass::RateLimited, ClientProtocol::OpenAI, StatusCode::BAD_REQUEST),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(
+            client_status(ErrorClass::Overloaded, ClientProtocol::Anthropic, StatusCode::SERVICE_UNAVAILABLE).as_u16(),
+            529
+        );
+        assert_eq!(ClientProtocol::from_path("/v1/chat/completions"), Some(ClientProtocol::OpenAI));
+        assert_eq!(ClientProtocol::from_path("/v1/messages/count_tokens"), Some(ClientProtocol::Anthropic));
+        assert_eq!(ClientProtocol::from_path("/v1beta/models/gemini-2.5-pro:generateContent"), None);
+        assert!(ClientProtocol::OpenAI.is_native(&json!({"error": {"message": "m", "type": "invalid_request_error"}})));
+        assert!(!ClientProtocol::OpenAI.is_native(&serde_json::from_str(GOOGLE_QUOTA).unwrap()));
+    }
+}
@@ -0,0 +1,50 @@
+// IP 白名单中间件
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+
+use crate::proxy::ip_filter::{peer_ip, ClientIp};
+use crate::proxy::security::{SecuritySnapshot, SharedSecurity};
+
+/// 解析客户端地址并写入请求扩展；配置了 `allowed_ips` 时拒绝白名单外的请求 (`/healthz` 除外)
+pub async fn ip_filter_middleware(
+    State(security): State<SharedSecurity>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    // 整个请求 (含鉴权中间件) 使用同一份配置快照，热更新不会让请求看到新旧混合的配置
+    let security = security.read().await.clone();
+    let access = &security.ip_access;
+    let client_ip = peer_ip(request.extensions()).map(|peer| access.client_ip(peer, request.headers()));
+    if let Some(ip) = client_ip {
+        request.extensions_mut().insert(ClientIp(ip));
+    }
+    request.extensions_mut().insert(SecuritySnapshot(security.clone()));
+
+    if !access.is_restricted() || request.uri().path() == "/healthz" {
+        return next.run(request).await;
+    }
+    match client_ip {
+        Some(ip) if access.is_allowed(ip) => next.run(request).await,
+        _ => {
+            let client = client_ip.map(|ip| ip.to_string()).unwrap_or_else(|| "unknown".to_string());
+            tracing::warn!("Rejected proxy request from {} (not in allowed_ips)", client);
+            (
+                StatusCode::FORBIDDEN,
+                Json(json!({
+                    "type": "error",
+                    "error": {
+                        "type": "permission_error",
+                        "message": format!("Client address {} is not allowed", client)
+                    }
+                })),
+            )
+                .into_response()
+        }
+    }
+}
@@ -0,0 +1,238 @@
+// 请求镜像中间件：按比例将客户端请求异步复制到另一个反代实例，镜像结果只计入统计
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderName},
+    middleware::Next,
+    response::Response,
+};
+use bytes::Bytes;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{RwLock, Semaphore};
+use utoipa::ToSchema;
+
+use crate::proxy::config::{ExperimentalConfig, MirrorConfig};
+use crate::proxy::monitor::ReplayOf;
+
+/// 缓冲请求体的上限，与监控中间件一致
+const MAX_MIRROR_BODY_SIZE: usize = 100 * 1024 * 1024;
+/// 同时进行的镜像请求上限，超出时丢弃本次镜像
+const MAX_IN_FLIGHT: usize = 32;
+const MIRROR_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// 镜像请求携带的标记头
+pub const MIRROR_HEADER: &str = "x-antigravity-mirror";
+
+/// 不转发给镜像实例的请求头 (鉴权与逐跳头)
+const STRIPPED_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "x-api-key",
+    "x-goog-api-key",
+    "cookie",
+    "host",
+    "content-length",
+    "connection",
+    "transfer-encoding",
+];
+
+/// 请求扩展：本次请求已被抽中镜像
+#[derive(Debug, Clone, Copy)]
+pub struct Mirrored;
+
+/// 镜像请求统计 (反代进程启动以来)
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct MirrorStats {
+    /// 已发出的镜像请求数
+    pub sent: u64,
+    /// 镜像实例返回 2xx/3xx 的次数
+    pub succeeded: u64,
+    /// 镜像实例返回 4xx/5xx 的次数
+    pub failed: u64,
+    /// 连接失败或超时的次数
+    pub errors: u64,
+    /// 并发已满而放弃镜像的次数
+    pub dropped: u64,
+    /// 已完成的镜像请求平均耗时 (毫秒)
+    pub avg_latency_ms: Option<f64>,
+    pub last_status: Option<u16>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Default)]
+struct Counters {
+    sent: AtomicU64,
+    succeeded: AtomicU64,
+    failed: AtomicU64,
+    errors: AtomicU64,
+    dropped: AtomicU64,
+    latency_ms_total: AtomicU64,
+    last: Mutex<(Option<u16>, Option<String>)>,
+}
+
+static COUNTERS: Lazy<Counters> = Lazy::new(Counters::default);
+static IN_FLIGHT: Lazy<Arc<Semaphore>> = Lazy::new(|| Arc::new(Semaphore::new(MAX_IN_FLIGHT)));
+static CLIENT: Lazy<reqwest::Client> = Lazy::new(|| {
+    reqwest::Client::builder()
+        .timeout(MIRROR_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+});
+
+/// 当前镜像统计
+pub fn stats() -> MirrorStats {
+    let c = &*COUNTERS;
+    let succeeded = c.succeeded.load(Ordering::Relaxed);
+    let failed = c.failed.load(Ordering::Relaxed);
+    let completed = succeeded + failed;
+    let (last_status, last_error) = c.last.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    MirrorStats {
+        sent: c.sent.load(Ordering::Relaxed),
+        succeeded,
+        failed,
+        errors: c.errors.load(Ordering::Relaxed),
+        dropped: c.dropped.load(Ordering::Relaxed),
+        avg_latency_ms: (completed > 0).then(|| c.latency_ms_total.load(Ordering::Relaxed) as f64 / completed as f64),
+        last_status,
+        last_error,
+    }
+}
+
+/// 只镜像模型调用 (POST /v1/...、/v1beta/...)，不镜像重放与内部请求
+fn is_mirrorable(request: &Request) -> bool {
+    let path = request.uri().path();
+    request.method() == axum::http::Method::POST
+        && (path.starts_with("/v1/") || path.starts_with("/v1beta/"))
+        && !path.contains("event_logging")
+        && request.extensions().get::<ReplayOf>().is_none()
+        && !request.headers().contains_key(MIRROR_HEADER)
+}
+
+/// 按 URL (`streamGenerateContent`) 或请求体中的 `"stream": true` 判断是否为流式请求
+pub fn is_streaming(path: &str, body: &[u8]) -> bool {
+    path.contains(":streamGenerateContent")
+        || serde_json::from_slice::<serde_json::Value>(body)
+            .ok()
+            .and_then(|v| v.get("stream").and_then(|s| s.as_bool()))
+            .unwrap_or(false)
+}
+
+/// 复制请求头，去掉鉴权与逐跳头
+pub fn mirror_headers(headers: &HeaderMap, api_key: Option<&str>) -> HeaderMap {
+    let mut out = HeaderMap::new();
+    for (name, value) in headers {
+        if !STRIPPED_HEADERS.contains(&name.as_str()) {
+            out.append(name.clone(), value.clone());
+        }
+    }
+    if let Some(key) = api_key.map(str::trim).filter(|k| !k.is_empty()) {
+        if let Ok(value) = format!("Bearer {}", key).parse() {
+            out.insert(header::AUTHORIZATION, value);
+        }
+    }
+    out.insert(HeaderName::from_static(MIRROR_HEADER), header::HeaderValue::from_static("1"));
+    out
+}
+
+pub async fn mirror_middleware(
+    State(experimental): State<Arc<RwLock<ExperimentalConfig>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let config = experimental.read().await.mirror.clone();
+    if !config.is_active() || !is_mirrorable(&request) || rand::random::<f64>() * 100.0 >= config.sample_percent {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let bytes = match axum::body::to_bytes(body, MAX_MIRROR_BODY_SIZE).await {
+        Ok(bytes) => bytes,
+        Err(_) => return next.run(Request::from_parts(parts, Body::empty())).await,
+    };
+    let path_and_query = parts.uri.path_and_query().map(|p| p.as_str().to_string()).unwrap_or_default();
+    let mut request = Request::from_parts(parts, Body::from(bytes.clone()));
+
+    if config.include_streaming || !is_streaming(request.uri().path(), &bytes) {
+        let headers = mirror_headers(request.headers(), config.api_key.as_deref());
+        if spawn_mirror(&config, path_and_query, headers, bytes) {
+            request.extensions_mut().insert(Mirrored);
+        }
+    }
+    next.run(request).await
+}
+
+/// 在后台发出镜像请求；并发已满时放弃并返回 false
+fn spawn_mirror(config: &MirrorConfig, path_and_query: String, headers: HeaderMap, body: Bytes) -> bool {
+    let permit = match IN_FLIGHT.clone().try_acquire_owned() {
+        Ok(permit) => permit,
+        Err(_) => {
+            COUNTERS.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+    };
+    let url = format!("{}{}", config.url.trim().trim_end_matches('/'), path_and_query);
+    COUNTERS.sent.fetch_add(1, Ordering::Relaxed);
+    tokio::spawn(async move {
+        let _permit = permit;
+        let start = Instant::now();
+        let result = CLIENT.post(&url).headers(headers).body(body).send().await;
+        let c = &*COUNTERS;
+        match result {
+            Ok(response) => {
+                let status = response.status().as_u16();
+                // 读完响应体 (含流式) 后再计时，结果丢弃
+                let _ = response.bytes().await;
+                c.latency_ms_total.fetch_add(start.elapsed().as_millis() as u64, Ordering::Relaxed);
+                if status < 400 {
+                    c.succeeded.fetch_add(1, Ordering::Relaxed);
+                } else {
+                    c.failed.fetch_add(1, Ordering::Relaxed);
+                }
+                c.last.lock().unwrap_or_else(|e| e.into_inner()).0 = Some(status);
+            }
+            Err(e) => {
+                tracing::debug!("Mirror request to {} failed: {}", url, e);
+                c.errors.fetch_add(1, Ordering::Relaxed);
+                c.last.lock().unwrap_or_else(|e| e.into_inner()).1 = Some(e.to_string());
+            }
+        }
+    });
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mirror_headers_strip_auth() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, "Bearer primary".parse().unwrap());
+        headers.insert("x-api-key", "primary".parse().unwrap());
+        headers.insert("x-goog-api-key", "primary".parse().unwrap());
+        headers.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
+        headers.insert("anthropic-version", "2023-06-01".parse().unwrap());
+
+        let out = mirror_headers(&headers, None);
+        assert!(out.get(header::AUTHORIZATION).is_none());
+        assert!(out.get("x-api-key").is_none());
+        assert!(out.get("x-goog-api-key").is_none());
+        assert_eq!(out.get("anthropic-version").unwrap(), "2023-06-01");
+        assert_eq!(out.get(MIRROR_HEADER).unwrap(), "1");
+
+        let out = mirror_headers(&headers, Some("mirror-key"));
+        assert_eq!(out.get(header::AUTHORIZATION).unwrap(), "Bearer mirror-key");
+    }
+
+    #[test]
+    fn test_is_streaming() {
+        assert!(is_streaming("/v1/messages", br#"{"model":"x","stream":true}"#));
+        assert!(!is_streaming("/v1/messages", br#"{"model":"x","stream":false}"#));
+        assert!(!is_streaming("/v1/chat/completions", br#"{"model":"x"}"#));
+        assert!(is_streaming("/v1beta/models/gemini-3-pro:streamGenerateContent", b"{}"));
+    }
+}
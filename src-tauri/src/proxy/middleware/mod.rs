@@ -1,9 +1,25 @@
 // Middleware 模块 - Axum 中间件
 
+pub mod account_pin;
 pub mod auth;
 pub mod cors;
+pub mod endpoint;
+pub mod error_translation;
+pub mod ip_filter;
 pub mod logging;
+pub mod mirror;
 pub mod monitor;
+pub mod panic;
+pub mod prompt_rules;
+pub mod response_headers;
 
+pub use account_pin::account_pin_middleware;
 pub use auth::auth_middleware;
 pub use cors::cors_layer;
+pub use endpoint::endpoint_middleware;
+pub use error_translation::error_translation_middleware;
+pub use ip_filter::ip_filter_middleware;
+pub use mirror::mirror_middleware;
+pub use panic::catch_panic_middleware;
+pub use prompt_rules::prompt_rules_middleware;
+pub use response_headers::response_headers_middleware;
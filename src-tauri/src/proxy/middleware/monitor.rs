@@ -6,9 +6,19 @@ use axum::{
 };
 use std::time::Instant;
 use crate::proxy::server::AppState;
-use crate::proxy::monitor::ProxyRequestLog;
+use crate::proxy::monitor::{ErrorClass, PhaseTimings, ProxyRequestLog, ReplayOf, RequestKind, RequestLogId, PHASE_TIMINGS};
+use crate::proxy::ip_filter::{peer_ip, ClientIp};
+use crate::proxy::security::ApiKeyName;
+use crate::proxy::middleware::mirror::Mirrored;
+use crate::proxy::middleware::account_pin::Pinned;
+use crate::proxy::middleware::prompt_rules::PromptRulesFired;
+use crate::proxy::upstream::client::{used_endpoint, EFFECTIVE_TIMEOUT};
+use crate::proxy::mappers::common_utils::{is_refusal_finish_reason, prompt_block_reason};
 use serde_json::Value;
-use futures::StreamExt;
+use futures::{Stream, StreamExt};
+use bytes::Bytes;
+use tokio::sync::mpsc;
+use tracing::Instrument;
 
 const MAX_REQUEST_LOG_SIZE: usize = 100 * 1024 * 1024; // 100MB
 const MAX_RESPONSE_LOG_SIZE: usize = 100 * 1024 * 1024; // 100MB for image responses
@@ -29,7 +39,27 @@ pub async fn monitor_middleware(
     if uri.contains("event_logging") {
         return next.run(request).await;
     }
+    let in_flight = state.monitor.begin_request();
     
+    let api_key_name = request
+        .extensions()
+        .get::<ApiKeyName>()
+        .map(|name| name.0.clone());
+    // 由 IP 过滤中间件按可信代理解析；缺失时退回直连地址
+    let client_ip = request
+        .extensions()
+        .get::<ClientIp>()
+        .map(|ClientIp(ip)| *ip)
+        .or_else(|| peer_ip(request.extensions()))
+        .map(|ip| ip.to_string());
+    let user_agent = request
+        .headers()
+        .get(axum::http::header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+    let replay_of = request.extensions().get::<ReplayOf>().map(|r| r.0.clone());
+    let mirrored = request.extensions().get::<Mirrored>().is_some();
+
     let mut model = if uri.contains("/v1beta/models/") {
         uri.split("/v1beta/models/")
             .nth(1)
@@ -66,7 +96,24 @@ pub async fn monitor_middleware(
         request
     };
     
-    let response = next.run(request).await;
+    // 在请求上下文中记录上游调用实际使用的超时与各阶段耗时
+    let span = tracing::debug_span!("proxy_request", method = %method, path = %request.uri().path());
+    let (response, timeout_secs, phase_timings, upstream_endpoint) = EFFECTIVE_TIMEOUT
+        .scope(std::cell::Cell::new(None), async {
+            PHASE_TIMINGS
+                .scope(std::cell::Cell::new(PhaseTimings::default()), async {
+                    let response = next.run(request).await;
+                    (
+                        response,
+                        EFFECTIVE_TIMEOUT.with(|cell| cell.get()),
+                        PHASE_TIMINGS.with(|cell| cell.get()),
+                        used_endpoint(),
+                    )
+                })
+                .await
+        })
+        .instrument(span.clone())
+        .await;
     
     let duration = start.elapsed().as_millis() as u64;
     let status = response.status().as_u16();
@@ -90,8 +137,19 @@ pub async fn monitor_middleware(
         .and_then(|v| v.to_str().ok())
         .map(|s| s.to_string());
 
+    // 错误转换中间件已归类的错误
+    let translated_class = response.extensions().get::<ErrorClass>().copied();
+    let prompt_rules = response
+        .extensions()
+        .get::<PromptRulesFired>()
+        .map(|fired| fired.0.clone())
+        .unwrap_or_default();
+    let pinned = response.extensions().get::<Pinned>().is_some();
+
     let monitor = state.monitor.clone();
     let mut log = ProxyRequestLog {
+        seq: 0, // 由 ProxyMonitor 分配
+        kind: RequestKind::from_url(&uri),
         id: uuid::Uuid::new_v4().to_string(),
         timestamp: chrono::Utc::now().timestamp_millis(),
         method,
@@ -106,64 +164,56 @@ pub async fn monitor_middleware(
         response_body: None,
         input_tokens: None,
         output_tokens: None,
+        vector_count: None,
+        api_key_name,
+        client_ip,
+        user_agent,
+        client_disconnected: false,
+        bytes_relayed: None,
+        timeout_secs,
+        phase_timings,
+        upstream_endpoint,
+        error_class: translated_class.or_else(|| ErrorClass::classify(status, "")),
+        replay_of,
+        finish_reason: None,
+        blocked: false,
+        cost: None,
+        currency: None,
+        mirrored,
+        prompt_rules,
+        pinned,
     };
+    let log_id = RequestLogId(log.id.clone());
 
-    if content_type.contains("text/event-stream") {
+    let mut response = if content_type.contains("text/event-stream") {
         log.response_body = Some("[Stream Data]".to_string());
         let (parts, body) = response.into_parts();
-        let mut stream = body.into_data_stream();
-        let (tx, rx) = tokio::sync::mpsc::channel(64);
+        let stream = body.into_data_stream();
+        let (tx, rx) = mpsc::channel(64);
         
+        let relay_span = tracing::debug_span!(parent: &span, "relay_stream", bytes = tracing::field::Empty, elapsed_ms = tracing::field::Empty);
         tokio::spawn(async move {
-            let mut last_few_bytes = Vec::new();
-            while let Some(chunk_res) = stream.next().await {
-                if let Ok(chunk) = chunk_res {
-                    if chunk.len() > 8192 {
-                        last_few_bytes = chunk.slice(chunk.len()-8192..).to_vec();
-                    } else {
-                        last_few_bytes.extend_from_slice(&chunk);
-                        if last_few_bytes.len() > 8192 {
-                            last_few_bytes.drain(0..last_few_bytes.len()-8192);
-                        }
-                    }
-                    let _ = tx.send(Ok::<_, axum::Error>(chunk)).await;
-                } else if let Err(e) = chunk_res {
-                    let _ = tx.send(Err(axum::Error::new(e))).await;
-                }
-            }
-            
-            if let Ok(full_tail) = std::str::from_utf8(&last_few_bytes) {
-                for line in full_tail.lines().rev() {
-                    if line.starts_with("data: ") && (line.contains("\"usage\"") || line.contains("\"usageMetadata\"")) {
-                        let json_str = line.trim_start_matches("data: ").trim();
-                        if let Ok(json) = serde_json::from_str::<Value>(json_str) {
-                            // 支持 OpenAI "usage" 或 Gemini "usageMetadata"
-                            if let Some(usage) = json.get("usage").or(json.get("usageMetadata")) {
-                                log.input_tokens = usage.get("prompt_tokens")
-                                    .or(usage.get("input_tokens"))
-                                    .or(usage.get("promptTokenCount"))
-                                    .and_then(|v| v.as_u64())
-                                    .map(|v| v as u32);
-                                log.output_tokens = usage.get("completion_tokens")
-                                    .or(usage.get("output_tokens"))
-                                    .or(usage.get("candidatesTokenCount"))
-                                    .and_then(|v| v.as_u64())
-                                    .map(|v| v as u32);
-                                
-                                if log.input_tokens.is_none() && log.output_tokens.is_none() {
-                                    log.output_tokens = usage.get("total_tokens")
-                                        .or(usage.get("totalTokenCount"))
-                                        .and_then(|v| v.as_u64())
-                                        .map(|v| v as u32);
-                                }
-                                break;
-                            }
-                        }
-                    }
-                }
-            }
-            
-            if log.status >= 400 {
+            let _in_flight = in_flight;
+            // 逐行扫描用量信息，只保留未结束的一行，不缓存整个流
+            let mut usage = StreamUsage::default();
+            let relay_start = Instant::now();
+            let outcome = relay_stream(stream, tx, &mut usage).instrument(relay_span.clone()).await;
+            usage.finish();
+            let stream_ms = relay_start.elapsed().as_millis() as u64;
+            relay_span.record("bytes", outcome.bytes_relayed);
+            relay_span.record("elapsed_ms", stream_ms);
+            log.phase_timings.stream_ms = Some(stream_ms);
+
+            log.input_tokens = usage.input_tokens;
+            log.output_tokens = usage.output_tokens;
+            log.finish_reason = usage.finish_reason.take();
+            log.blocked = usage.blocked || log.error_class == Some(ErrorClass::SafetyBlocked);
+            log.duration = start.elapsed().as_millis() as u64;
+            log.bytes_relayed = Some(outcome.bytes_relayed);
+            log.client_disconnected = outcome.disconnected;
+            if outcome.disconnected {
+                log.error = Some(format!("Cancelled: client disconnected after {} bytes", outcome.bytes_relayed));
+            } else if log.status >= 400 {
                 log.error = Some("Stream Error or Failed".to_string());
             }
             monitor.log_request(log).await;
@@ -176,25 +226,16 @@ pub async fn monitor_middleware(
             Ok(bytes) => {
                 if let Ok(s) = std::str::from_utf8(&bytes) {
                     if let Ok(json) = serde_json::from_str::<Value>(&s) {
-                        // 支持 OpenAI "usage" 或 Gemini "usageMetadata"
-                        if let Some(usage) = json.get("usage").or(json.get("usageMetadata")) {
-                            log.input_tokens = usage.get("prompt_tokens")
-                                .or(usage.get("input_tokens"))
-                                .or(usage.get("promptTokenCount"))
-                                .and_then(|v| v.as_u64())
-                                .map(|v| v as u32);
-                            log.output_tokens = usage.get("completion_tokens")
-                                .or(usage.get("output_tokens"))
-                                .or(usage.get("candidatesTokenCount"))
-                                .and_then(|v| v.as_u64())
-                                .map(|v| v as u32);
-                                
-                            if log.input_tokens.is_none() && log.output_tokens.is_none() {
-                                log.output_tokens = usage.get("total_tokens")
-                                    .or(usage.get("totalTokenCount"))
-                                    .and_then(|v| v.as_u64())
-                                    .map(|v| v as u32);
-                            }
+                        if let Some((input, output)) = extract_usage(&json) {
+                            log.input_tokens = input;
+                            log.output_tokens = output;
+                        }
+                        if let Some((reason, blocked)) = extract_finish(&json) {
+                            log.finish_reason = Some(reason);
+                            log.blocked = blocked;
+                        }
+                        if log.kind == RequestKind::Embeddings {
+                            log.vector_count = json.get("data").and_then(|d| d.as_array()).map(|d| d.len() as u32);
                         }
                     }
                     log.response_body = Some(s.to_string());
@@ -204,7 +245,13 @@ pub async fn monitor_middleware(
                 
                 if log.status >= 400 {
                     log.error = log.response_body.clone();
+                    log.error_class = translated_class
+                        .or_else(|| ErrorClass::classify(log.status, log.error.as_deref().unwrap_or("")));
+                    if log.blocked {
+                        log.error_class = Some(ErrorClass::SafetyBlocked);
+                    }
                 }
+                log.blocked |= log.error_class == Some(ErrorClass::SafetyBlocked);
                 monitor.log_request(log).await;
                 Response::from_parts(parts, Body::from(bytes))
             }
@@ -218,5 +265,356 @@ pub async fn monitor_middleware(
         log.response_body = Some(format!("[{}]", content_type));
         monitor.log_request(log).await;
         response
+    };
+    response.extensions_mut().insert(log_id);
+    response
+}
+
+/// 流式转发的结果
+struct RelayOutcome {
+    bytes_relayed: u64,
+    /// 客户端在上游结束前断开 (上游请求已取消)
+    disconnected: bool,
+}
+
+/// 将上游流转发给客户端，同时扫描用量
+///
+/// 客户端断开 (接收端被丢弃) 时立即停止并丢弃上游流，从而中止上游请求，避免继续消耗配额
+async fn relay_stream<S, E>(
+    mut stream: S,
+    tx: mpsc::Sender<Result<Bytes, axum::Error>>,
+    usage: &mut StreamUsage,
+) -> RelayOutcome
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: Into<axum::BoxError>,
+{
+    let mut bytes_relayed = 0u64;
+    let mut disconnected = false;
+    loop {
+        // 等待上游数据的同时监听客户端断开，上游迟迟不出数据时也能及时取消
+        let next = tokio::select! {
+            biased;
+            _ = tx.closed() => {
+                disconnected = true;
+                break;
+            }
+            next = stream.next() => next,
+        };
+        let Some(chunk_res) = next else {
+            break;
+        };
+        let item = match chunk_res {
+            Ok(chunk) => {
+                usage.feed(&chunk);
+                Ok(chunk)
+            }
+            Err(e) => Err(axum::Error::new(e)),
+        };
+        let len = item.as_ref().map(|c| c.len() as u64).unwrap_or(0);
+        if tx.send(item).await.is_err() {
+            disconnected = true;
+            break;
+        }
+        bytes_relayed += len;
+    }
+    drop(stream);
+    RelayOutcome { bytes_relayed, disconnected }
+}
+
+/// 单行 SSE 的长度上限，超出的行 (如内联图片数据) 不参与用量解析
+const MAX_USAGE_LINE_BYTES: usize = 1024 * 1024;
+
+fn token_field(usage: &Value, keys: &[&str]) -> Option<u32> {
+    keys.iter()
+        .find_map(|k| usage.get(*k).and_then(|v| v.as_u64()))
+        .map(|v| v as u32)
+}
+
+/// 从响应 JSON (或单个 SSE 事件) 中提取 (输入, 输出) Token 数
+///
+/// 支持 OpenAI `usage`、Anthropic `usage` / `message.usage`、Gemini `usageMetadata` (含 v1internal 的 `response` 包装)、
+/// 以及 Responses API 的 `response.usage`
+fn extract_usage(json: &Value) -> Option<(Option<u32>, Option<u32>)> {
+    let usage = json
+        .get("usage")
+        .or_else(|| json.get("usageMetadata"))
+        .or_else(|| json.get("message").and_then(|m| m.get("usage")))
+        .or_else(|| json.get("response").and_then(|r| r.get("usage").or_else(|| r.get("usageMetadata"))))?;
+
+    let input = token_field(usage, &["prompt_tokens", "input_tokens", "promptTokenCount"]);
+    let mut output = token_field(usage, &["completion_tokens", "output_tokens", "candidatesTokenCount"]);
+    if input.is_none() && output.is_none() {
+        output = token_field(usage, &["total_tokens", "totalTokenCount"]);
+    }
+    if input.is_none() && output.is_none() {
+        return None;
+    }
+    Some((input, output))
+}
+
+/// 从响应 JSON (或单个 SSE 事件) 中提取结束原因，以及是否因安全策略被拦截
+///
+/// 支持 OpenAI `choices[].finish_reason` (`content_filter` 视为拦截)、Anthropic `stop_reason` /
+/// `delta.stop_reason` / `message.stop_reason` (`refusal` 视为拦截)、Gemini `candidates[].finishReason`
+/// 与 `promptFeedback.blockReason` (含 v1internal 的 `response` 包装)
+fn extract_finish(json: &Value) -> Option<(String, bool)> {
+    if let Some(reason) = prompt_block_reason(json) {
+        return Some((reason.to_string(), true));
+    }
+
+    let gemini = json.get("response").unwrap_or(json);
+    if let Some(reason) = gemini
+        .get("candidates")
+        .and_then(Value::as_array)
+        .and_then(|candidates| candidates.iter().find_map(|c| c.get("finishReason").and_then(Value::as_str)))
+    {
+        return Some((reason.to_string(), is_refusal_finish_reason(reason)));
+    }
+
+    if let Some(reason) = json
+        .get("choices")
+        .and_then(Value::as_array)
+        .and_then(|choices| choices.iter().find_map(|c| c.get("finish_reason").and_then(Value::as_str)))
+    {
+        return Some((reason.to_string(), reason == "content_filter"));
+    }
+
+    let reason = json
+        .get("stop_reason")
+        .or_else(|| json.pointer("/delta/stop_reason"))
+        .or_else(|| json.pointer("/message/stop_reason"))
+        .and_then(Value::as_str)?;
+    Some((reason.to_string(), reason == "refusal"))
+}
+
+/// 流式响应的用量与结束原因跟踪：按行解析 SSE 事件，后出现的数值覆盖之前的 (各协议的用量均为累计值)
+#[derive(Default)]
+struct StreamUsage {
+    line: Vec<u8>,
+    overflow: bool,
+    input_tokens: Option<u32>,
+    output_tokens: Option<u32>,
+    finish_reason: Option<String>,
+    blocked: bool,
+}
+
+impl StreamUsage {
+    fn feed(&mut self, chunk: &[u8]) {
+        let mut rest = chunk;
+        while let Some(pos) = rest.iter().position(|&b| b == b'\n') {
+            self.push(&rest[..pos]);
+            self.end_line();
+            rest = &rest[pos + 1..];
+        }
+        self.push(rest);
+    }
+
+    fn finish(&mut self) {
+        self.end_line();
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        if self.overflow || self.line.len() + bytes.len() > MAX_USAGE_LINE_BYTES {
+            self.overflow = true;
+            self.line.clear();
+            return;
+        }
+        self.line.extend_from_slice(bytes);
+    }
+
+    fn end_line(&mut self) {
+        let line = std::mem::take(&mut self.line);
+        if std::mem::take(&mut self.overflow) {
+            return;
+        }
+        let Ok(text) = std::str::from_utf8(&line) else {
+            return;
+        };
+        let Some(data) = text.trim_end_matches('\r').strip_prefix("data:") else {
+            return;
+        };
+        // 快速过滤不含用量与结束原因的事件
+        if !data.contains("sage") && !data.contains("eason") && !data.contains("content_filter") {
+            return;
+        }
+        let Ok(json) = serde_json::from_str::<Value>(data.trim()) else {
+            return;
+        };
+        if let Some((input, output)) = extract_usage(&json) {
+            if input.is_some() {
+                self.input_tokens = input;
+            }
+            if output.is_some() {
+                self.output_tokens = output;
+            }
+        }
+        if let Some((reason, blocked)) = extract_finish(&json) {
+            self.finish_reason = Some(reason);
+            self.blocked |= blocked;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(chunks: &[&str]) -> (Option<u32>, Option<u32>) {
+        let mut usage = StreamUsage::default();
+        for chunk in chunks {
+            usage.feed(chunk.as_bytes());
+        }
+        usage.finish();
+        (usage.input_tokens, usage.output_tokens)
+    }
+
+    #[test]
+    fn test_stream_usage_openai() {
+        let chunks = [
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"}}]}\n\n",
+            "data: {\"choices\":[],\"usage\":{\"prompt_tokens\":12,",
+            "\"completion_tokens\":5,\"total_tokens\":17}}\n\ndata: [DONE]\n\n",
+        ];
+        assert_eq!(track(&chunks), (Some(12), Some(5)));
+    }
+
+    #[test]
+    fn test_stream_usage_anthropic() {
+        let chunks = [
+            "event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":30,\"output_tokens\":1}}}\n\n",
+            "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"delta\":{\"text\":\"Hello\"}}\n\n",
+            "event: message_delta\r\ndata: {\"type\":\"message_delta\",\"usage\":{\"output_tokens\":42}}\r\n\r\n",
+        ];
+        assert_eq!(track(&chunks), (Some(30), Some(42)));
+    }
+
+    #[test]
+    fn test_stream_usage_gemini() {
+        // 最后一行没有换行符，需要在 finish 时处理
+        let chunks = [
+            "data: {\"response\":{\"candidates\":[],\"usageMetadata\":{\"promptTokenCount\":8,\"candidatesTokenCount\":1}}}\n\n",
+            "data: {\"candidates\":[],\"usageMetadata\":{\"promptTokenCount\":8,\"candidatesTokenCount\":20}}",
+        ];
+        assert_eq!(track(&chunks), (Some(8), Some(20)));
+    }
+
+    /// 被丢弃时发出通知的上游流：先产出一个分块，之后一直挂起
+    struct GuardedUpstream {
+        sent: bool,
+        dropped: Option<tokio::sync::oneshot::Sender<()>>,
+    }
+
+    impl Stream for GuardedUpstream {
+        type Item = Result<Bytes, std::io::Error>;
+
+        fn poll_next(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Option<Self::Item>> {
+            if self.sent {
+                return std::task::Poll::Pending;
+            }
+            self.sent = true;
+            std::task::Poll::Ready(Some(Ok(Bytes::from_static(b"data: {\"usage\":{\"output_tokens\":2}}\n\n"))))
+        }
+    }
+
+    impl Drop for GuardedUpstream {
+        fn drop(&mut self) {
+            if let Some(tx) = self.dropped.take() {
+                let _ = tx.send(());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_disconnect_cancels_upstream() {
+        let (dropped_tx, dropped_rx) = tokio::sync::oneshot::channel();
+        let upstream = GuardedUpstream { sent: false, dropped: Some(dropped_tx) };
+        let (tx, mut rx) = mpsc::channel(4);
+
+        let relay = tokio::spawn(async move {
+            let mut usage = StreamUsage::default();
+            let outcome = relay_stream(upstream, tx, &mut usage).await;
+            (outcome, usage.output_tokens)
+        });
+
+        // 客户端收到第一个分块后断开
+        let first = rx.recv().await.unwrap().unwrap();
+        drop(rx);
+
+        tokio::time::timeout(std::time::Duration::from_secs(2), dropped_rx)
+            .await
+            .expect("upstream stream was not dropped after client disconnect")
+            .unwrap();
+        let (outcome, output_tokens) = relay.await.unwrap();
+        assert!(outcome.disconnected);
+        assert_eq!(outcome.bytes_relayed, first.len() as u64);
+        assert_eq!(output_tokens, Some(2));
+    }
+
+    #[test]
+    fn test_extract_finish_openai() {
+        let stop = serde_json::json!({"choices": [{"message": {}, "finish_reason": "stop"}]});
+        assert_eq!(extract_finish(&stop), Some(("stop".to_string(), false)));
+        let filtered = serde_json::json!({"choices": [{"message": {}, "finish_reason": "content_filter"}]});
+        assert_eq!(extract_finish(&filtered), Some(("content_filter".to_string(), true)));
+        assert_eq!(extract_finish(&serde_json::json!({"choices": [{"finish_reason": null}]})), None);
+    }
+
+    #[test]
+    fn test_extract_finish_anthropic() {
+        let done = serde_json::json!({"type": "message", "stop_reason": "end_turn"});
+        assert_eq!(extract_finish(&done), Some(("end_turn".to_string(), false)));
+        let refusal = serde_json::json!({"type": "message_delta", "delta": {"stop_reason": "refusal"}});
+        assert_eq!(extract_finish(&refusal), Some(("refusal".to_string(), true)));
+        let start = serde_json::json!({"type": "message_start", "message": {"stop_reason": null}});
+        assert_eq!(extract_finish(&start), None);
+    }
+
+    #[test]
+    fn test_extract_finish_gemini() {
+        let stop = serde_json::json!({"candidates": [{"finishReason": "STOP"}]});
+        assert_eq!(extract_finish(&stop), Some(("STOP".to_string(), false)));
+        let safety = serde_json::json!({"response": {"candidates": [{"finishReason": "SAFETY"}]}});
+        assert_eq!(extract_finish(&safety), Some(("SAFETY".to_string(), true)));
+        let prompt = serde_json::json!({"promptFeedback": {"blockReason": "BLOCKLIST"}});
+        assert_eq!(extract_finish(&prompt), Some(("BLOCKLIST".to_string(), true)));
+    }
+
+    #[test]
+    fn test_stream_finish_reason() {
+        let track_finish = |chunks: &[&str]| {
+            let mut usage = StreamUsage::default();
+            for chunk in chunks {
+                usage.feed(chunk.as_bytes());
+            }
+            usage.finish();
+            (usage.finish_reason, usage.blocked)
+        };
+        assert_eq!(
+            track_finish(&["data: {\"choices\":[{\"delta\":{},\"finish_reason\":null}]}\n\n", "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n"]),
+            (Some("stop".to_string()), false)
+        );
+        assert_eq!(
+            track_finish(&["event: message_delta\ndata: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"refusal\"}}\n\n"]),
+            (Some("refusal".to_string()), true)
+        );
+        assert_eq!(
+            track_finish(&["data: {\"response\":{\"promptFeedback\":{\"blockReason\":\"PROHIBITED_CONTENT\"}}}\n\n"]),
+            (Some("PROHIBITED_CONTENT".to_string()), true)
+        );
+        assert_eq!(
+            track_finish(&["data: {\"candidates\":[{\"finishReason\":\"SAFETY\"}]}"]),
+            (Some("SAFETY".to_string()), true)
+        );
+    }
+
+    #[test]
+    fn test_stream_usage_skips_oversized_lines() {
+        let big = format!("data: {{\"usage\":{{\"output_tokens\":1}},\"pad\":\"{}\"}}\n", "x".repeat(MAX_USAGE_LINE_BYTES));
+        let chunks = [big.as_str(), "data: {\"usage\":{\"output_tokens\":3}}\n"];
+        assert_eq!(track(&chunks), (None, Some(3)));
     }
 }
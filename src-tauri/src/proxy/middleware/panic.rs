@@ -0,0 +1,71 @@
+// panic 兜底中间件：handler panic 时按客户端协议返回 500，而不是直接断开连接
+use axum::{
+    extract::Request,
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::json;
+
+use crate::utils::panic::{run_catching, with_request_id};
+
+/// 按请求路径生成对应协议的内部错误响应
+fn protocol_error(path: &str, message: String) -> Response {
+    if path.starts_with("/v1/messages") {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "type": "error",
+                "error": { "type": "api_error", "message": message }
+            })),
+        )
+            .into_response()
+    } else if path.starts_with("/v1beta") {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "error": { "code": 500, "message": message, "status": "INTERNAL" }
+            })),
+        )
+            .into_response()
+    } else {
+        crate::proxy::handlers::common::openai_error(StatusCode::INTERNAL_SERVER_ERROR, "api_error", message)
+    }
+}
+
+pub async fn catch_panic_middleware(request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+    match run_catching(request, next).await {
+        Ok(response) => response,
+        Err(caught) => with_request_id(
+            protocol_error(
+                &path,
+                format!("Internal server error (request_id: {}): {}", caught.request_id, caught.message),
+            ),
+            &caught.request_id,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_protocol_error_shapes() {
+        let body = |response: Response| async move {
+            let bytes = axum::body::to_bytes(response.into_body(), 4096).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+        };
+
+        let claude = body(protocol_error("/v1/messages", "boom".to_string())).await;
+        assert_eq!(claude["type"], "error");
+        assert_eq!(claude["error"]["type"], "api_error");
+
+        let gemini = body(protocol_error("/v1beta/models/gemini-2.5-pro:generateContent", "boom".to_string())).await;
+        assert_eq!(gemini["error"]["status"], "INTERNAL");
+
+        let openai = body(protocol_error("/v1/chat/completions", "boom".to_string())).await;
+        assert_eq!(openai["error"]["message"], "boom");
+    }
+}
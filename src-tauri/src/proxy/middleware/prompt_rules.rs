@@ -0,0 +1,293 @@
+// 系统提示词规则中间件：按 `prompt_rules` 在协议转换前改写客户端请求的 system 提示词
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::proxy::config::{PromptRule, PromptRuleAction};
+use crate::proxy::security::ApiKeyName;
+
+/// 缓冲请求体的上限，与监控中间件一致
+const MAX_BODY_SIZE: usize = 100 * 1024 * 1024;
+
+/// 响应扩展：本次请求命中的提示词规则名 (按执行顺序)
+#[derive(Debug, Clone)]
+pub struct PromptRulesFired(pub Vec<String>);
+
+/// 请求体中 system 提示词的位置，按路由区分协议
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptFormat {
+    /// Anthropic Messages：`system` 为字符串或文本块数组
+    Claude,
+    /// OpenAI Chat：`messages` 中 role 为 system / developer 的消息
+    OpenAi,
+    /// OpenAI Responses (Codex)：`instructions` 字符串
+    Responses,
+    /// Gemini：`systemInstruction.parts`
+    Gemini,
+}
+
+impl PromptFormat {
+    /// 按请求路径与请求体判断格式，不支持改写的路由返回 None
+    pub fn detect(path: &str, body: &Value) -> Option<Self> {
+        match path {
+            "/v1/messages" | "/v1/messages/count_tokens" => Some(Self::Claude),
+            "/v1/chat/completions" => Some(Self::OpenAi),
+            "/v1/completions" | "/v1/responses" => {
+                if body.get("messages").is_some_and(Value::is_array) {
+                    Some(Self::OpenAi)
+                } else if body.get("instructions").is_some()
+                    || body.get("input").is_some_and(Value::is_array)
+                {
+                    Some(Self::Responses)
+                } else {
+                    None
+                }
+            }
+            _ if path.starts_with("/v1beta/models/") => Some(Self::Gemini),
+            _ => None,
+        }
+    }
+}
+
+/// 客户端请求的模型名：Gemini 取自路径，其余取自请求体
+pub fn request_model(path: &str, body: &Value) -> Option<String> {
+    if let Some(rest) = path.strip_prefix("/v1beta/models/") {
+        return rest.split(':').next().filter(|m| !m.is_empty()).map(str::to_string);
+    }
+    body.get("model").and_then(|m| m.as_str()).map(str::to_string)
+}
+
+/// 按顺序执行命中的规则，返回实际生效的规则名
+pub fn apply_rules(
+    rules: &[PromptRule],
+    path: &str,
+    api_key_name: Option<&str>,
+    body: &mut Value,
+) -> Vec<String> {
+    let Some(format) = PromptFormat::detect(path, body) else {
+        return Vec::new();
+    };
+    let model = request_model(path, body);
+    let mut fired = Vec::new();
+    for (i, rule) in rules.iter().enumerate() {
+        if rule.matches(model.as_deref(), api_key_name) && apply_action(format, rule.action, &rule.text, body) {
+            fired.push(rule.label(i));
+        }
+    }
+    fired
+}
+
+/// 对请求体执行单条规则，请求体结构不符合预期时返回 false
+fn apply_action(format: PromptFormat, action: PromptRuleAction, text: &str, body: &mut Value) -> bool {
+    let Some(obj) = body.as_object_mut() else {
+        return false;
+    };
+    match format {
+        PromptFormat::Claude => {
+            let system = obj.entry("system").or_insert(Value::Null);
+            match (action, &mut *system) {
+                (PromptRuleAction::ReplaceSystem, _) | (_, Value::Null) => *system = json!(text),
+                (_, Value::String(existing)) if existing.is_empty() => *existing = text.to_string(),
+                (_, Value::String(existing)) => *existing = join_text(action, existing, text),
+                (_, Value::Array(blocks)) => {
+                    let block = json!({ "type": "text", "text": text });
+                    insert_at(action, blocks, block);
+                }
+                _ => return false,
+            }
+            true
+        }
+        PromptFormat::OpenAi => {
+            let Some(messages) = obj.get_mut("messages").and_then(Value::as_array_mut) else {
+                return false;
+            };
+            let is_system = |m: &Value| matches!(m.get("role").and_then(|r| r.as_str()), Some("system" | "developer"));
+            let message = json!({ "role": "system", "content": text });
+            match action {
+                PromptRuleAction::PrependSystem => messages.insert(0, message),
+                PromptRuleAction::AppendSystem => {
+                    let pos = messages.iter().rposition(is_system).map_or(0, |i| i + 1);
+                    messages.insert(pos, message);
+                }
+                PromptRuleAction::ReplaceSystem => {
+                    messages.retain(|m| !is_system(m));
+                    messages.insert(0, message);
+                }
+            }
+            true
+        }
+        PromptFormat::Responses => {
+            let instructions = obj.entry("instructions").or_insert(Value::Null);
+            match instructions.as_str() {
+                Some(existing) if !existing.is_empty() && action != PromptRuleAction::ReplaceSystem => {
+                    *instructions = json!(join_text(action, existing, text));
+                }
+                None if !instructions.is_null() => return false,
+                _ => *instructions = json!(text),
+            }
+            true
+        }
+        PromptFormat::Gemini => {
+            let key = if obj.contains_key("system_instruction") { "system_instruction" } else { "systemInstruction" };
+            let instruction = obj.entry(key).or_insert_with(|| json!({ "parts": [] }));
+            let Some(instruction) = instruction.as_object_mut() else {
+                return false;
+            };
+            let parts = instruction.entry("parts").or_insert_with(|| json!([]));
+            let Some(parts) = parts.as_array_mut() else {
+                return false;
+            };
+            let part = json!({ "text": text });
+            insert_at(action, parts, part);
+            true
+        }
+    }
+}
+
+fn join_text(action: PromptRuleAction, existing: &str, text: &str) -> String {
+    match action {
+        PromptRuleAction::PrependSystem => format!("{}\n\n{}", text, existing),
+        PromptRuleAction::AppendSystem => format!("{}\n\n{}", existing, text),
+        PromptRuleAction::ReplaceSystem => text.to_string(),
+    }
+}
+
+fn insert_at(action: PromptRuleAction, items: &mut Vec<Value>, item: Value) {
+    match action {
+        PromptRuleAction::PrependSystem => items.insert(0, item),
+        PromptRuleAction::AppendSystem => items.push(item),
+        PromptRuleAction::ReplaceSystem => {
+            items.clear();
+            items.push(item);
+        }
+    }
+}
+
+/// 对模型调用请求执行提示词规则，命中的规则名写入响应扩展供监控记录
+pub async fn prompt_rules_middleware(
+    State(rules): State<Arc<RwLock<Vec<PromptRule>>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let rules = rules.read().await.clone();
+    if rules.iter().all(|rule| !rule.enabled) || request.method() != axum::http::Method::POST {
+        return next.run(request).await;
+    }
+
+    let path = request.uri().path().to_string();
+    let api_key_name = request.extensions().get::<ApiKeyName>().map(|name| name.0.clone());
+    let (mut parts, body) = request.into_parts();
+    let bytes = match axum::body::to_bytes(body, MAX_BODY_SIZE).await {
+        Ok(bytes) => bytes,
+        Err(_) => return next.run(Request::from_parts(parts, Body::empty())).await,
+    };
+
+    let mut fired = Vec::new();
+    let bytes = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(mut json) => {
+            fired = apply_rules(&rules, &path, api_key_name.as_deref(), &mut json);
+            if fired.is_empty() {
+                bytes
+            } else {
+                tracing::debug!("提示词规则已生效: {} ({})", fired.join(", "), path);
+                serde_json::to_vec(&json).map(Into::into).unwrap_or(bytes)
+            }
+        }
+        Err(_) => bytes,
+    };
+
+    parts.headers.remove(axum::http::header::CONTENT_LENGTH);
+    let mut response = next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    if !fired.is_empty() {
+        response.extensions_mut().insert(PromptRulesFired(fired));
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::config::PromptRuleMatch;
+
+    fn rule(name: &str, action: PromptRuleAction, text: &str) -> PromptRule {
+        PromptRule {
+            name: name.to_string(),
+            enabled: true,
+            matcher: PromptRuleMatch::default(),
+            action,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_claude_system_rules() {
+        let rules = vec![
+            rule("policy", PromptRuleAction::PrependSystem, "P"),
+            rule("", PromptRuleAction::AppendSystem, "A"),
+        ];
+        let mut body = json!({ "model": "claude-sonnet-4-5", "system": "S", "messages": [] });
+        let fired = apply_rules(&rules, "/v1/messages", None, &mut body);
+        assert_eq!(fired, vec!["policy", "#2"]);
+        assert_eq!(body["system"], "P\n\nS\n\nA");
+
+        let mut body = json!({ "model": "m", "system": [{ "type": "text", "text": "S" }] });
+        apply_rules(&rules, "/v1/messages", None, &mut body);
+        let texts: Vec<_> = body["system"].as_array().unwrap().iter().map(|b| b["text"].as_str().unwrap()).collect();
+        assert_eq!(texts, vec!["P", "S", "A"]);
+
+        let mut body = json!({ "model": "m", "system": [{ "type": "text", "text": "S" }] });
+        apply_rules(&[rule("r", PromptRuleAction::ReplaceSystem, "R")], "/v1/messages", None, &mut body);
+        assert_eq!(body["system"], "R");
+    }
+
+    #[test]
+    fn test_openai_and_gemini_rules() {
+        let mut body = json!({
+            "model": "gpt-4o",
+            "messages": [
+                { "role": "system", "content": "S" },
+                { "role": "user", "content": "hi" }
+            ]
+        });
+        apply_rules(&[rule("a", PromptRuleAction::AppendSystem, "A")], "/v1/chat/completions", None, &mut body);
+        let roles: Vec<_> = body["messages"].as_array().unwrap().iter().map(|m| m["content"].as_str().unwrap()).collect();
+        assert_eq!(roles, vec!["S", "A", "hi"]);
+
+        apply_rules(&[rule("r", PromptRuleAction::ReplaceSystem, "R")], "/v1/chat/completions", None, &mut body);
+        let roles: Vec<_> = body["messages"].as_array().unwrap().iter().map(|m| m["content"].as_str().unwrap()).collect();
+        assert_eq!(roles, vec!["R", "hi"]);
+
+        let mut body = json!({ "contents": [] });
+        let fired = apply_rules(
+            &[rule("p", PromptRuleAction::PrependSystem, "P")],
+            "/v1beta/models/gemini-3-pro:generateContent",
+            None,
+            &mut body,
+        );
+        assert_eq!(fired, vec!["p"]);
+        assert_eq!(body["systemInstruction"]["parts"][0]["text"], "P");
+    }
+
+    #[test]
+    fn test_rule_matching() {
+        let mut scoped = rule("scoped", PromptRuleAction::PrependSystem, "P");
+        scoped.matcher = PromptRuleMatch { model: Some("gemini-*".to_string()), api_key: Some("team-a".to_string()) };
+        let rules = vec![scoped];
+
+        let mut body = json!({ "contents": [] });
+        let path = "/v1beta/models/gemini-3-pro:streamGenerateContent";
+        assert!(apply_rules(&rules, path, None, &mut body).is_empty());
+        assert!(apply_rules(&rules, path, Some("team-b"), &mut body).is_empty());
+        assert_eq!(apply_rules(&rules, path, Some("team-a"), &mut body), vec!["scoped"]);
+
+        let mut body = json!({ "model": "claude-opus-4", "messages": [] });
+        assert!(apply_rules(&rules, "/v1/messages", Some("team-a"), &mut body).is_empty());
+        assert!(apply_rules(&rules, "/v1/embeddings", Some("team-a"), &mut body).is_empty());
+    }
+}
@@ -0,0 +1,183 @@
+// 响应头中间件：按 `response_headers` 透传上游响应头并注入固定响应头
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use std::cell::RefCell;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::proxy::config::ResponseHeadersConfig;
+
+/// 不透传也不允许注入的响应头：逐跳头与由反代自行生成的长度/编码头
+const FILTERED_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "content-length",
+    "content-encoding",
+];
+
+tokio::task_local! {
+    /// 当前请求最近一次上游调用返回的响应头，由响应头中间件建立
+    static UPSTREAM_HEADERS: RefCell<Option<HeaderMap>>;
+}
+
+/// 响应头是否不可透传或注入
+pub fn is_filtered_header(name: &str) -> bool {
+    FILTERED_HEADERS.iter().any(|h| h.eq_ignore_ascii_case(name.trim()))
+}
+
+/// 记录当前请求的上游响应头 (未配置透传时不在请求上下文中，直接忽略)
+pub fn record_upstream_headers(headers: &HeaderMap) {
+    let _ = UPSTREAM_HEADERS.try_with(|cell| *cell.borrow_mut() = Some(headers.clone()));
+}
+
+/// 将透传与注入的响应头写入客户端响应；同名时注入优先
+pub fn apply_headers(config: &ResponseHeadersConfig, upstream: Option<&HeaderMap>, headers: &mut HeaderMap) {
+    if let Some(upstream) = upstream {
+        for name in &config.passthrough {
+            if is_filtered_header(name) {
+                continue;
+            }
+            let Ok(name) = HeaderName::from_bytes(name.trim().as_bytes()) else {
+                continue;
+            };
+            let values: Vec<HeaderValue> = upstream.get_all(&name).iter().cloned().collect();
+            if values.is_empty() {
+                continue;
+            }
+            headers.remove(&name);
+            for value in values {
+                headers.append(name.clone(), value);
+            }
+        }
+    }
+    for (name, value) in &config.inject {
+        if is_filtered_header(name) {
+            continue;
+        }
+        if let (Ok(name), Ok(value)) = (HeaderName::from_bytes(name.trim().as_bytes()), HeaderValue::from_str(value)) {
+            headers.insert(name, value);
+        }
+    }
+}
+
+/// 为反代响应 (含流式响应) 透传与注入响应头；响应头在响应体开始发送前写入
+pub async fn response_headers_middleware(
+    State(config): State<Arc<RwLock<ResponseHeadersConfig>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let config = config.read().await.clone();
+    if config.passthrough.is_empty() && config.inject.is_empty() {
+        return next.run(request).await;
+    }
+
+    let (mut response, upstream) = if config.passthrough.is_empty() {
+        (next.run(request).await, None)
+    } else {
+        UPSTREAM_HEADERS
+            .scope(RefCell::new(None), async {
+                let response = next.run(request).await;
+                (response, UPSTREAM_HEADERS.with(|cell| cell.borrow_mut().take()))
+            })
+            .await
+    };
+    apply_headers(&config, upstream.as_ref(), response.headers_mut());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::post, Router};
+    use std::collections::BTreeMap;
+    use tower::ServiceExt;
+
+    fn config(passthrough: &[&str], inject: &[(&str, &str)]) -> ResponseHeadersConfig {
+        ResponseHeadersConfig {
+            passthrough: passthrough.iter().map(|s| s.to_string()).collect(),
+            inject: inject.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<BTreeMap<_, _>>(),
+        }
+    }
+
+    #[test]
+    fn test_apply_headers_filters_and_prefers_injection() {
+        let mut upstream = HeaderMap::new();
+        upstream.insert("x-ratelimit-remaining", "42".parse().unwrap());
+        upstream.insert("x-trace-id", "upstream".parse().unwrap());
+        upstream.insert("transfer-encoding", "chunked".parse().unwrap());
+        upstream.insert("content-length", "10".parse().unwrap());
+
+        let config = config(
+            &["X-RateLimit-Remaining", "x-trace-id", "Transfer-Encoding", "content-length", "x-missing"],
+            &[("x-trace-id", "injected"), ("X-Served-By", "antigravity"), ("connection", "close")],
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert("content-length", "99".parse().unwrap());
+        apply_headers(&config, Some(&upstream), &mut headers);
+
+        assert_eq!(headers["x-ratelimit-remaining"], "42");
+        assert_eq!(headers["x-trace-id"], "injected");
+        assert_eq!(headers["x-served-by"], "antigravity");
+        assert_eq!(headers["content-length"], "99");
+        assert!(!headers.contains_key("transfer-encoding"));
+        assert!(!headers.contains_key("connection"));
+        assert!(!headers.contains_key("x-missing"));
+    }
+
+    #[tokio::test]
+    async fn test_headers_on_streamed_response() {
+        async fn stream_handler() -> Response {
+            let mut upstream = HeaderMap::new();
+            upstream.insert("x-ratelimit-remaining", "7".parse().unwrap());
+            upstream.insert("keep-alive", "timeout=5".parse().unwrap());
+            record_upstream_headers(&upstream);
+
+            let chunks = futures::stream::iter(vec![
+                Ok::<_, std::io::Error>(bytes::Bytes::from("data: {\"a\":1}\n\n")),
+                Ok(bytes::Bytes::from("data: [DONE]\n\n")),
+            ]);
+            Response::builder()
+                .header("content-type", "text/event-stream")
+                .header("x-served-by", "handler")
+                .body(Body::from_stream(chunks))
+                .unwrap()
+        }
+
+        let state = Arc::new(RwLock::new(config(
+            &["x-ratelimit-remaining", "keep-alive"],
+            &[("x-served-by", "antigravity")],
+        )));
+        let app = Router::new()
+            .route("/v1/messages", post(stream_handler))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), response_headers_middleware));
+
+        let response = app
+            .clone()
+            .oneshot(Request::post("/v1/messages").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+        assert_eq!(response.headers()["x-ratelimit-remaining"], "7");
+        assert_eq!(response.headers()["x-served-by"], "antigravity");
+        assert!(!response.headers().contains_key("keep-alive"));
+        let body = axum::body::to_bytes(response.into_body(), 1024).await.unwrap();
+        assert_eq!(&body[..], b"data: {\"a\":1}\n\ndata: [DONE]\n\n");
+
+        // 热更新后的配置对下一个请求生效
+        *state.write().await = ResponseHeadersConfig::default();
+        let response = app.oneshot(Request::post("/v1/messages").body(Body::empty()).unwrap()).await.unwrap();
+        assert!(!response.headers().contains_key("x-ratelimit-remaining"));
+        assert_eq!(response.headers()["x-served-by"], "handler");
+    }
+}
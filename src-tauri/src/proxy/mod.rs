@@ -6,6 +6,10 @@ pub mod token_manager;
 pub mod project_resolver;
 pub mod server;
 pub mod security;
+pub mod key_limiter;
+pub mod unauthorized;
+pub mod ip_filter;
+pub mod pricing;
 
 // 新架构模块
 pub mod mappers;           // 协议转换器
@@ -28,6 +32,13 @@ pub use config::ProxyConfig;
 pub use config::ProxyAuthMode;
 pub use config::ZaiConfig;
 pub use config::ZaiDispatchMode;
+pub use config::ConfigIssue;
+pub use config::ApiKeyEntry;
+pub use config::AccountGroup;
+pub use config::TimeoutOverrides;
+pub use config::DnsOverride;
+pub use config::PromptRule;
+pub use config::ResponseHeadersConfig;
 pub use token_manager::TokenManager;
 pub use server::AxumServer;
 pub use security::ProxySecurityConfig;
@@ -1,12 +1,203 @@
 use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
 use std::collections::VecDeque;
 use tokio::sync::RwLock;
 #[cfg(feature = "tauri-app")]
 use tauri::Emitter;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use crate::modules::stats_history::{self, DailyStats};
+use crate::utils::tasks::TaskSupervisor;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// 当天汇总的定时落盘间隔
+const DAILY_FLUSH_INTERVAL_SECS: u64 = 300;
+
+/// 请求类别，用于区分生成请求与计数等辅助请求
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestKind {
+    #[default]
+    Generate,
+    CountTokens,
+    Embeddings,
+}
+
+impl RequestKind {
+    /// 根据请求路径判断类别
+    pub fn from_url(url: &str) -> Self {
+        let path = url.split('?').next().unwrap_or(url);
+        if path.ends_with("/count_tokens") || path.ends_with(":countTokens") {
+            RequestKind::CountTokens
+        } else if path.ends_with("/embeddings") {
+            RequestKind::Embeddings
+        } else {
+            RequestKind::Generate
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RequestKind::Generate => "generate",
+            RequestKind::CountTokens => "count_tokens",
+            RequestKind::Embeddings => "embeddings",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "count_tokens" => RequestKind::CountTokens,
+            "embeddings" => RequestKind::Embeddings,
+            _ => RequestKind::Generate,
+        }
+    }
+}
+
+/// 失败请求的错误分类
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorClass {
+    /// 账号所在地区不受支持
+    RegionBlocked,
+    /// 429 限流或配额耗尽
+    RateLimited,
+    /// 401 / 403
+    Auth,
+    /// 内容被安全策略拦截
+    SafetyBlocked,
+    /// 其他 4xx
+    InvalidRequest,
+    /// 503 / 529 或上游提示过载
+    Overloaded,
+    /// 其他 5xx
+    Upstream,
+}
+
+impl ErrorClass {
+    /// 判断错误内容是否为地区限制 (unsupported location)
+    pub fn is_region_blocked(body: &str) -> bool {
+        let lower = body.to_ascii_lowercase();
+        lower.contains("location is not supported")
+            || lower.contains("user location")
+            || lower.contains("unsupported_country")
+            || lower.contains("not available in your country")
+    }
+
+    /// 判断错误内容是否为安全策略拦截
+    pub fn is_safety_blocked(body: &str) -> bool {
+        body.contains("blockReason")
+            || body.contains("PROHIBITED_CONTENT")
+            || body.contains("\"SAFETY\"")
+            || body.contains("BLOCKLIST")
+    }
+
+    /// 按状态码与错误内容归类，成功的请求返回 None
+    pub fn classify(status: u16, body: &str) -> Option<Self> {
+        if status < 400 {
+            return None;
+        }
+        if Self::is_region_blocked(body) {
+            return Some(ErrorClass::RegionBlocked);
+        }
+        if Self::is_safety_blocked(body) {
+            return Some(ErrorClass::SafetyBlocked);
+        }
+        Some(match status {
+            429 => ErrorClass::RateLimited,
+            401 | 403 => ErrorClass::Auth,
+            400..=499 if body.contains("RESOURCE_EXHAUSTED") => ErrorClass::RateLimited,
+            400..=499 => ErrorClass::InvalidRequest,
+            503 | 529 => ErrorClass::Overloaded,
+            _ if body.to_ascii_lowercase().contains("overloaded") => ErrorClass::Overloaded,
+            _ => ErrorClass::Upstream,
+        })
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorClass::RegionBlocked => "region_blocked",
+            ErrorClass::RateLimited => "rate_limited",
+            ErrorClass::Auth => "auth",
+            ErrorClass::SafetyBlocked => "safety_blocked",
+            ErrorClass::InvalidRequest => "invalid_request",
+            ErrorClass::Overloaded => "overloaded",
+            ErrorClass::Upstream => "upstream",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "region_blocked" => Some(ErrorClass::RegionBlocked),
+            "rate_limited" => Some(ErrorClass::RateLimited),
+            "auth" => Some(ErrorClass::Auth),
+            "safety_blocked" => Some(ErrorClass::SafetyBlocked),
+            "invalid_request" => Some(ErrorClass::InvalidRequest),
+            "overloaded" => Some(ErrorClass::Overloaded),
+            "upstream" => Some(ErrorClass::Upstream),
+            _ => None,
+        }
+    }
+}
+
+/// 请求各阶段耗时 (毫秒)，未经历的阶段为空
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct PhaseTimings {
+    /// 选择账号 (含等待锁，不含刷新 token)，重试时累加
+    pub select_ms: Option<u64>,
+    /// 刷新 access token，重试时累加
+    pub auth_ms: Option<u64>,
+    /// 最后一次上游调用收到响应头的耗时 (含端点切换)
+    pub upstream_ttfb_ms: Option<u64>,
+    /// 流式响应从收到响应头到转发结束的耗时
+    pub stream_ms: Option<u64>,
+}
+
+impl PhaseTimings {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+fn add_ms(slot: &mut Option<u64>, ms: u64) {
+    *slot = Some(slot.unwrap_or(0) + ms);
+}
+
+tokio::task_local! {
+    /// 当前请求的阶段耗时，由监控中间件建立并写入日志
+    pub static PHASE_TIMINGS: std::cell::Cell<PhaseTimings>;
+}
+
+/// 在当前请求上下文中更新阶段耗时 (不在请求上下文中时忽略)
+pub fn record_phase(f: impl FnOnce(&mut PhaseTimings)) {
+    let _ = PHASE_TIMINGS.try_with(|cell| {
+        let mut timings = cell.get();
+        f(&mut timings);
+        cell.set(timings);
+    });
+}
+
+/// 当前请求上下文中的阶段耗时
+pub fn current_phases() -> PhaseTimings {
+    PHASE_TIMINGS.try_with(|cell| cell.get()).unwrap_or_default()
+}
+
+/// 累加账号选择耗时
+pub fn record_select_ms(ms: u64) {
+    record_phase(|t| add_ms(&mut t.select_ms, ms));
+}
+
+/// 累加 token 刷新耗时
+pub fn record_auth_ms(ms: u64) {
+    record_phase(|t| add_ms(&mut t.auth_ms, ms));
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ProxyRequestLog {
+    /// 单调递增的序号，由 ProxyMonitor 分配，用于增量拉取
+    #[serde(default)]
+    pub seq: u64,
+    /// 请求类别
+    #[serde(default)]
+    pub kind: RequestKind,
     pub id: String,
     pub timestamp: i64,
     pub method: String,
@@ -21,25 +212,369 @@ pub struct ProxyRequestLog {
     pub response_body: Option<String>,
     pub input_tokens: Option<u32>,
     pub output_tokens: Option<u32>,
+    /// Embeddings 请求返回的向量数
+    #[serde(default)]
+    pub vector_count: Option<u32>,
+    /// 请求使用的 API 密钥名 (未启用鉴权时为空)
+    #[serde(default)]
+    pub api_key_name: Option<String>,
+    /// 客户端地址 (经可信代理解析)
+    #[serde(default)]
+    pub client_ip: Option<String>,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// 客户端在流式响应结束前断开连接
+    #[serde(default)]
+    pub client_disconnected: bool,
+    /// 流式响应已转发给客户端的字节数
+    #[serde(default)]
+    pub bytes_relayed: Option<u64>,
+    /// 本次请求实际使用的上游超时 (秒)
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// 各阶段耗时
+    #[serde(default)]
+    pub phase_timings: PhaseTimings,
+    /// 最近一次上游调用实际使用的端点
+    #[serde(default)]
+    pub upstream_endpoint: Option<String>,
+    /// 失败请求的错误分类
+    #[serde(default)]
+    pub error_class: Option<ErrorClass>,
+    /// 重放请求对应的原始日志 ID
+    #[serde(default)]
+    pub replay_of: Option<String>,
+    /// 上游返回的结束原因 (finish_reason / stop_reason / blockReason)
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+    /// 请求被安全策略或内容过滤拦截
+    #[serde(default)]
+    pub blocked: bool,
+    /// 按单价表估算的费用，模型无单价或无 Token 用量时为空
+    #[serde(default)]
+    pub cost: Option<f64>,
+    /// 费用的货币代码
+    #[serde(default)]
+    pub currency: Option<String>,
+    /// 请求被抽中并已异步镜像到 `experimental.mirror.url`
+    #[serde(default)]
+    pub mirrored: bool,
+    /// 命中的提示词规则名 (按执行顺序)
+    #[serde(default)]
+    pub prompt_rules: Vec<String>,
+    /// 请求经 `X-Antigravity-Account` 请求头固定到指定账号
+    #[serde(default)]
+    pub pinned: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+/// 请求扩展：标记本次请求是对指定日志的重放
+#[derive(Debug, Clone)]
+pub struct ReplayOf(pub String);
+
+/// 响应扩展：本次请求写入的日志 ID
+#[derive(Debug, Clone)]
+pub struct RequestLogId(pub String);
+
+/// Token 用量合计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn add(&mut self, log: &ProxyRequestLog) {
+        self.input_tokens += log.input_tokens.unwrap_or(0) as u64;
+        self.output_tokens += log.output_tokens.unwrap_or(0) as u64;
+    }
+}
+
+/// 上游连接复用情况
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct UpstreamConnectionStats {
+    /// 发往上游的请求数 (含换端点重试)
+    pub requests: u64,
+    /// 新建的连接数
+    pub new_connections: u64,
+    /// 复用已有连接的请求数 (按请求数减新建连接数估算)
+    pub reused_connections: u64,
+}
+
+/// 上游连接计数器，由上游客户端更新
+#[derive(Debug, Default)]
+pub struct ConnectionCounters {
+    requests: AtomicU64,
+    new_connections: AtomicU64,
+}
+
+impl ConnectionCounters {
+    pub fn record_request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_new_connection(&self) {
+        self.new_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> UpstreamConnectionStats {
+        let requests = self.requests.load(Ordering::Relaxed);
+        let new_connections = self.new_connections.load(Ordering::Relaxed);
+        UpstreamConnectionStats {
+            requests,
+            new_connections,
+            reused_connections: requests.saturating_sub(new_connections),
+        }
+    }
+}
+
+/// 正在处理的请求计数，守卫释放时减一
+pub struct InFlightGuard(Arc<AtomicUsize>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// 单个客户端地址的请求量
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ClientTraffic {
+    pub ip: String,
+    pub requests: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, ToSchema)]
 pub struct ProxyStats {
     pub total_requests: u64,
     pub success_count: u64,
     pub error_count: u64,
+    /// 因客户端断开而取消的流式请求数
+    #[serde(default)]
+    pub cancelled_count: u64,
+    /// 被安全策略或内容过滤拦截的请求数
+    #[serde(default)]
+    pub blocked_count: u64,
+    /// 平均总耗时 (毫秒，流式请求含转发时间)
+    #[serde(default)]
+    pub avg_duration_ms: Option<f64>,
+    /// 平均上游首字节耗时 (毫秒，仅统计有记录的请求)
+    #[serde(default)]
+    pub avg_ttfb_ms: Option<f64>,
+    /// 按 API 密钥名统计的请求数
+    #[serde(default)]
+    pub requests_by_key: std::collections::BTreeMap<String, u64>,
+    /// 最近 24 小时请求最多的客户端地址
+    #[serde(default)]
+    pub top_clients: Vec<ClientTraffic>,
+    /// 按账号统计的 Token 用量
+    #[serde(default)]
+    pub tokens_by_account: std::collections::BTreeMap<String, TokenUsage>,
+    /// 按模型 (路由后的实际模型) 统计的 Token 用量
+    #[serde(default)]
+    pub tokens_by_model: std::collections::BTreeMap<String, TokenUsage>,
+    /// 内存缓冲中的请求条数
+    #[serde(default)]
+    pub buffer_entries: usize,
+    /// 内存缓冲的估算占用 (字节)
+    #[serde(default)]
+    pub buffer_bytes: u64,
+    /// 上游连接复用情况 (进程启动以来)
+    #[serde(default)]
+    pub upstream_connections: UpstreamConnectionStats,
+}
+
+/// 增量拉取的结果
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LogCursorPage {
+    /// 序号大于 `after_seq` 的日志，按序号先后排列
+    pub logs: Vec<ProxyRequestLog>,
+    /// 下次拉取使用的 `after_seq`
+    pub latest_seq: u64,
+    /// 还有更多新日志未返回 (超出 limit)
+    pub has_more: bool,
+    /// 部分新日志已被缓冲淘汰，无法返回
+    pub truncated: bool,
+}
+
+/// 新日志的监听函数 (Web 模式用于推送 SSE)
+type LogListener = Arc<dyn Fn(&ProxyRequestLog) + Send + Sync>;
+
+impl ProxyRequestLog {
+    /// 测试用的空白日志：`POST /v1/messages` 返回 200，其余字段为空，测试中用结构体更新语法覆盖需要的字段
+    #[cfg(test)]
+    pub(crate) fn test_default() -> Self {
+        Self {
+            seq: 0,
+            kind: RequestKind::Generate,
+            id: "log-1".to_string(),
+            timestamp: 0,
+            method: "POST".to_string(),
+            url: "/v1/messages".to_string(),
+            status: 200,
+            duration: 0,
+            model: None,
+            mapped_model: None,
+            account_email: None,
+            error: None,
+            request_body: None,
+            response_body: None,
+            input_tokens: None,
+            output_tokens: None,
+            vector_count: None,
+            api_key_name: None,
+            client_ip: None,
+            user_agent: None,
+            client_disconnected: false,
+            bytes_relayed: None,
+            timeout_secs: None,
+            phase_timings: PhaseTimings::default(),
+            upstream_endpoint: None,
+            error_class: None,
+            replay_of: None,
+            finish_reason: None,
+            blocked: false,
+            cost: None,
+            currency: None,
+            mirrored: false,
+            prompt_rules: Vec::new(),
+            pinned: false,
+        }
+    }
+
+    /// 不含请求/响应体的摘要，用于事件推送
+    pub fn summary(&self) -> Self {
+        Self {
+            request_body: None,
+            response_body: None,
+            ..self.clone()
+        }
+    }
+
+    /// 估算在内存中的占用 (结构体本身加各字符串内容)
+    pub fn estimated_size(&self) -> u64 {
+        let strings = [
+            Some(&self.id),
+            Some(&self.method),
+            Some(&self.url),
+            self.model.as_ref(),
+            self.mapped_model.as_ref(),
+            self.account_email.as_ref(),
+            self.error.as_ref(),
+            self.request_body.as_ref(),
+            self.response_body.as_ref(),
+            self.api_key_name.as_ref(),
+            self.client_ip.as_ref(),
+            self.user_agent.as_ref(),
+        ];
+        let content: usize = strings.iter().flatten().map(|s| s.len()).sum();
+        (std::mem::size_of::<Self>() + content) as u64
+    }
+
+    /// 按当前单价表估算费用：优先使用映射后的模型，无 Token 用量或无单价时保持为空
+    pub fn apply_cost(&mut self) {
+        if self.input_tokens.is_none() && self.output_tokens.is_none() {
+            return;
+        }
+        let input = self.input_tokens.unwrap_or(0);
+        let output = self.output_tokens.unwrap_or(0);
+        let estimated = [self.mapped_model.as_deref(), self.model.as_deref()]
+            .into_iter()
+            .flatten()
+            .find_map(|model| crate::proxy::pricing::estimate(model, input, output));
+        if let Some((cost, currency)) = estimated {
+            self.cost = Some(cost);
+            self.currency = Some(currency);
+        }
+    }
+}
+
+/// 选择性清理日志的条件，多个条件同时满足才删除
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    /// 只删除早于该时间 (毫秒时间戳) 的日志
+    pub before: Option<i64>,
+    /// 状态码类别的首位数字 (如 `2xx` 为 2)
+    pub status_class: Option<u16>,
+    /// 请求模型或路由后的模型
+    pub model: Option<String>,
+}
+
+impl LogFilter {
+    /// 解析 `2xx` / `5xx` 形式的状态码类别
+    pub fn parse_status_class(raw: &str) -> Result<u16, String> {
+        let raw = raw.trim().to_ascii_lowercase();
+        match raw.strip_suffix("xx").and_then(|d| d.parse::<u16>().ok()) {
+            Some(class @ 1..=5) if raw.len() == 3 => Ok(class),
+            _ => Err(format!("状态码类别无效: {} (可选 1xx-5xx)", raw)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.before.is_none() && self.status_class.is_none() && self.model.is_none()
+    }
+
+    pub fn matches(&self, log: &ProxyRequestLog) -> bool {
+        self.before.is_none_or(|before| log.timestamp < before)
+            && self.status_class.is_none_or(|class| log.status / 100 == class)
+            && self.model.as_deref().is_none_or(|model| {
+                log.model.as_deref() == Some(model) || log.mapped_model.as_deref() == Some(model)
+            })
+    }
 }
 
 pub struct ProxyMonitor {
     pub logs: RwLock<VecDeque<ProxyRequestLog>>,
     pub stats: RwLock<ProxyStats>,
-    pub max_logs: usize,
+    /// 缓冲条数上限
+    max_logs: AtomicUsize,
+    /// 缓冲估算占用上限 (字节)，0 表示不限制
+    max_bytes: AtomicU64,
+    /// 缓冲当前估算占用 (字节)，在持有 `logs` 写锁时更新
+    buffer_bytes: AtomicU64,
     pub enabled: AtomicBool,
+    /// 临时开关覆盖值，不写入配置，优先于配置中的 `enable_logging`
+    transient: Mutex<Option<bool>>,
+    /// 当天的汇总，启动时从历史中恢复，定时与停止时落盘
+    today: Mutex<DailyStats>,
+    /// 最近分配的日志序号
+    last_seq: AtomicU64,
+    /// 因缓冲上限被淘汰的最大序号
+    evicted_seq: AtomicU64,
+    listener: std::sync::RwLock<Option<LogListener>>,
+    /// 上游连接计数，由上游客户端共享更新
+    pub connections: Arc<ConnectionCounters>,
+    /// 正在处理的请求数 (流式请求计到转发结束)
+    in_flight: Arc<AtomicUsize>,
     #[cfg(feature = "tauri-app")]
     app_handle: Option<tauri::AppHandle>,
-    /// SSE broadcast sender for web mode
-    #[cfg(not(feature = "tauri-app"))]
-    _phantom: std::marker::PhantomData<()>,
+}
+
+/// 请求监控的开关状态
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MonitorStatus {
+    /// 当前是否记录请求
+    pub enabled: bool,
+    /// 缓冲条数上限
+    pub buffer_size: usize,
+    /// 缓冲中的条数
+    pub entries: usize,
+    /// 配置中保存的开关，与 `enabled` 不同时表示存在临时切换
+    pub persisted: bool,
+}
+
+/// 实际生效的开关：临时切换优先于配置
+pub fn resolve_enabled(saved: bool, transient: Option<bool>) -> bool {
+    transient.unwrap_or(saved)
+}
+
+/// 将监控开关写入配置，重启后保持
+pub fn save_enabled(enabled: bool) -> Result<(), String> {
+    let mut config = crate::modules::config::load_app_config()?;
+    if config.proxy.enable_logging != enabled {
+        config.proxy.enable_logging = enabled;
+        crate::modules::config::save_app_config(&config)?;
+    }
+    Ok(())
 }
 
 impl ProxyMonitor {
@@ -65,10 +600,19 @@ impl ProxyMonitor {
         });
 
         Self {
-            logs: RwLock::new(VecDeque::with_capacity(max_logs)),
+            logs: RwLock::new(VecDeque::new()),
             stats: RwLock::new(ProxyStats::default()),
-            max_logs,
+            max_logs: AtomicUsize::new(max_logs),
+            max_bytes: AtomicU64::new(0),
+            buffer_bytes: AtomicU64::new(0),
             enabled: AtomicBool::new(false),
+            transient: Mutex::new(None),
+            today: Mutex::new(stats_history::load_day(&stats_history::today())),
+            last_seq: AtomicU64::new(Self::initial_seq()),
+            evicted_seq: AtomicU64::new(0),
+            listener: std::sync::RwLock::new(None),
+            connections: Arc::new(ConnectionCounters::default()),
+            in_flight: Arc::new(AtomicUsize::new(0)),
             app_handle,
         }
     }
@@ -81,28 +625,204 @@ impl ProxyMonitor {
         }
 
         Self {
-            logs: RwLock::new(VecDeque::with_capacity(max_logs)),
+            logs: RwLock::new(VecDeque::new()),
             stats: RwLock::new(ProxyStats::default()),
-            max_logs,
+            max_logs: AtomicUsize::new(max_logs),
+            max_bytes: AtomicU64::new(0),
+            buffer_bytes: AtomicU64::new(0),
             enabled: AtomicBool::new(false),
-            _phantom: std::marker::PhantomData,
+            transient: Mutex::new(None),
+            today: Mutex::new(stats_history::load_day(&stats_history::today())),
+            last_seq: AtomicU64::new(Self::initial_seq()),
+            evicted_seq: AtomicU64::new(0),
+            listener: std::sync::RwLock::new(None),
+            connections: Arc::new(ConnectionCounters::default()),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+
+    /// 不读写数据目录的监控器，仅用于测试缓冲逻辑
+    #[cfg(test)]
+    fn in_memory(max_logs: usize) -> Self {
+        Self {
+            logs: RwLock::new(VecDeque::new()),
+            stats: RwLock::new(ProxyStats::default()),
+            max_logs: AtomicUsize::new(max_logs),
+            max_bytes: AtomicU64::new(0),
+            buffer_bytes: AtomicU64::new(0),
+            enabled: AtomicBool::new(true),
+            transient: Mutex::new(None),
+            today: Mutex::new(DailyStats::default()),
+            last_seq: AtomicU64::new(0),
+            evicted_seq: AtomicU64::new(0),
+            listener: std::sync::RwLock::new(None),
+            connections: Arc::new(ConnectionCounters::default()),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            #[cfg(feature = "tauri-app")]
+            app_handle: None,
+        }
+    }
+
+    /// 序号从已保存日志的最大值继续，重启后不会重复
+    fn initial_seq() -> u64 {
+        crate::modules::proxy_db::max_seq().unwrap_or_else(|e| {
+            tracing::error!("Failed to read max log seq from DB: {}", e);
+            0
+        })
+    }
+
+    /// 设置新日志的监听函数
+    pub fn set_listener<F>(&self, listener: F)
+    where
+        F: Fn(&ProxyRequestLog) + Send + Sync + 'static,
+    {
+        *self.listener.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(listener));
+    }
+
+    /// 缓冲条数上限
+    pub fn capacity(&self) -> usize {
+        self.max_logs.load(Ordering::Relaxed)
+    }
+
+    /// 调整缓冲条数与内存上限，只淘汰超出新上限的最旧记录
+    pub async fn set_limits(&self, max_logs: usize, max_memory_mb: u64) {
+        self.max_logs.store(max_logs.max(1), Ordering::Relaxed);
+        self.max_bytes.store(max_memory_mb * 1024 * 1024, Ordering::Relaxed);
+        let mut logs = self.logs.write().await;
+        self.evict(&mut logs, 0);
+    }
+
+    /// 淘汰最旧的记录，直到能再放入 `incoming` 字节的新记录
+    fn evict(&self, logs: &mut VecDeque<ProxyRequestLog>, incoming: u64) {
+        let max_logs = self.capacity();
+        let max_bytes = self.max_bytes.load(Ordering::Relaxed);
+        let reserve = if incoming > 0 { 1 } else { 0 };
+        let mut bytes = self.buffer_bytes.load(Ordering::Relaxed);
+        while let Some(oldest) = logs.back() {
+            let over_count = logs.len() + reserve > max_logs;
+            let over_bytes = max_bytes > 0 && bytes + incoming > max_bytes;
+            if !over_count && !over_bytes {
+                break;
+            }
+            bytes = bytes.saturating_sub(oldest.estimated_size());
+            self.evicted_seq.fetch_max(oldest.seq, Ordering::Relaxed);
+            logs.pop_back();
+        }
+        self.buffer_bytes.store(bytes, Ordering::Relaxed);
+    }
+
+    /// 注册定时落盘当天汇总的后台任务，监控器释放或任务被停止后退出
+    pub fn register_daily_flush(self: &Arc<Self>, tasks: &TaskSupervisor) {
+        let weak: Weak<Self> = Arc::downgrade(self);
+        tasks.register("daily-stats-flush", move |ctx| {
+            let weak = weak.clone();
+            async move {
+                let mut interval = tokio::time::interval(std::time::Duration::from_secs(DAILY_FLUSH_INTERVAL_SECS));
+                interval.tick().await;
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => {}
+                        _ = ctx.cancelled() => break,
+                    }
+                    let Some(monitor) = weak.upgrade() else {
+                        break;
+                    };
+                    monitor.flush_daily_stats();
+                    ctx.record_run(Ok(()));
+                }
+            }
+        });
+    }
+
+    /// 将当天汇总写入统计历史
+    pub fn flush_daily_stats(&self) {
+        let today = self.today.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        if let Err(e) = stats_history::save_day(&today) {
+            tracing::error!("Failed to save daily stats: {}", e);
         }
     }
 
+    /// 累计到当天汇总；跨天时先落盘前一天
+    fn record_daily(&self, log: &ProxyRequestLog) {
+        let date = stats_history::date_of(log.timestamp);
+        let finished = {
+            let mut today = self.today.lock().unwrap_or_else(|e| e.into_inner());
+            let finished = if today.date != date {
+                Some(std::mem::replace(&mut *today, DailyStats::new(date)))
+            } else {
+                None
+            };
+            today.record(log);
+            finished
+        };
+        if let Some(day) = finished {
+            tokio::task::spawn_blocking(move || {
+                if let Err(e) = stats_history::save_day(&day) {
+                    tracing::error!("Failed to save daily stats: {}", e);
+                }
+            });
+        }
+    }
+
+    /// 最近 `days` 天的汇总 (含当天未落盘的部分)
+    pub fn daily_stats(&self, days: usize) -> Result<Vec<DailyStats>, String> {
+        let today = self.today.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        stats_history::recent(days, Some(today))
+    }
+
+    /// 最近 `days` 天的账号小时热力图 (含当天未落盘的部分)
+    pub fn usage_heatmap(&self, days: usize) -> Result<stats_history::UsageHeatmap, String> {
+        let today = self.today.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        stats_history::heatmap(days, Some(today))
+    }
 
+    /// 最近 `days` 天按账号 / API 密钥 / 模型分组的费用报表 (含当天未落盘的部分)
+    pub fn cost_report(&self, group_by: stats_history::CostGroupBy, days: usize) -> Result<stats_history::CostReport, String> {
+        let today = self.today.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        stats_history::cost_report(group_by, days, Some(today))
+    }
+
+    /// 设置开关并清除临时覆盖，用于已写入配置的切换
     pub fn set_enabled(&self, enabled: bool) {
+        *self.transient.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// 临时切换开关，不写入配置，反代重启后仍然保持
+    pub fn set_transient(&self, enabled: bool) {
+        *self.transient.lock().unwrap_or_else(|e| e.into_inner()) = Some(enabled);
         self.enabled.store(enabled, Ordering::Relaxed);
     }
 
+    /// 启动反代时按配置同步开关，存在临时覆盖时以覆盖值为准
+    pub fn apply_saved(&self, saved: bool) {
+        let transient = *self.transient.lock().unwrap_or_else(|e| e.into_inner());
+        self.enabled.store(resolve_enabled(saved, transient), Ordering::Relaxed);
+    }
+
+    /// 当前开关状态与缓冲占用
+    pub async fn status(&self, persisted: bool) -> MonitorStatus {
+        MonitorStatus {
+            enabled: self.is_enabled(),
+            buffer_size: self.capacity(),
+            entries: self.logs.read().await.len(),
+            persisted,
+        }
+    }
+
     pub fn is_enabled(&self) -> bool {
         self.enabled.load(Ordering::Relaxed)
     }
 
-    pub async fn log_request(&self, log: ProxyRequestLog) {
+    pub async fn log_request(&self, mut log: ProxyRequestLog) {
         if !self.is_enabled() {
             return;
         }
         tracing::info!("[Monitor] Logging request: {} {}", log.method, log.url);
+        if log.cost.is_none() {
+            log.apply_cost();
+        }
         // Update stats
         {
             let mut stats = self.stats.write().await;
@@ -112,16 +832,28 @@ impl ProxyMonitor {
             } else {
                 stats.error_count += 1;
             }
+            if log.client_disconnected {
+                stats.cancelled_count += 1;
+            }
+            if log.blocked {
+                stats.blocked_count += 1;
+            }
+            if let Some(name) = &log.api_key_name {
+                *stats.requests_by_key.entry(name.clone()).or_default() += 1;
+            }
+            if log.input_tokens.is_some() || log.output_tokens.is_some() {
+                if let Some(email) = &log.account_email {
+                    stats.tokens_by_account.entry(email.clone()).or_default().add(&log);
+                }
+                if let Some(model) = log.mapped_model.as_ref().or(log.model.as_ref()) {
+                    stats.tokens_by_model.entry(model.clone()).or_default().add(&log);
+                }
+            }
         }
+        self.record_daily(&log);
 
         // Add log to memory
-        {
-            let mut logs = self.logs.write().await;
-            if logs.len() >= self.max_logs {
-                logs.pop_back();
-            }
-            logs.push_front(log.clone());
-        }
+        self.buffer_log(&mut log).await;
 
         // Save to DB
         let log_to_save = log.clone();
@@ -132,60 +864,437 @@ impl ProxyMonitor {
         });
 
         // Emit event (send summary only, without body to reduce memory)
+        let listener = self.listener.read().unwrap_or_else(|e| e.into_inner()).clone();
+        if let Some(listener) = listener {
+            listener(&log.summary());
+        }
         #[cfg(feature = "tauri-app")]
         if let Some(app) = &self.app_handle {
-            let log_summary = ProxyRequestLog {
-                id: log.id.clone(),
-                timestamp: log.timestamp,
-                method: log.method.clone(),
-                url: log.url.clone(),
-                status: log.status,
-                duration: log.duration,
-                model: log.model.clone(),
-                mapped_model: log.mapped_model.clone(),
-                account_email: log.account_email.clone(),
-                error: log.error.clone(),
-                request_body: None,  // Don't send body in event
-                response_body: None, // Don't send body in event
-                input_tokens: log.input_tokens,
-                output_tokens: log.output_tokens,
-            };
-            let _ = app.emit("proxy://request", &log_summary);
+            let _ = app.emit("proxy://request", &log.summary());
         }
     }
 
+    /// 分配序号并放入内存缓冲，必要时淘汰最旧的记录
+    async fn buffer_log(&self, log: &mut ProxyRequestLog) {
+        let size = log.estimated_size();
+        let mut logs = self.logs.write().await;
+        // 持有写锁时分配序号，保证缓冲内按序号排列
+        log.seq = self.last_seq.fetch_add(1, Ordering::Relaxed) + 1;
+        self.evict(&mut logs, size);
+        logs.push_front(log.clone());
+        self.buffer_bytes.fetch_add(size, Ordering::Relaxed);
+    }
+
+    /// 序号大于 `after_seq` 的日志 (从内存缓冲读取)，最多 `limit` 条
+    pub async fn logs_after(&self, after_seq: u64, limit: usize) -> LogCursorPage {
+        let logs = self.logs.read().await;
+        // 缓冲按序号从新到旧排列
+        let newer: Vec<&ProxyRequestLog> = logs.iter().take_while(|log| log.seq > after_seq).collect();
+        let has_more = newer.len() > limit;
+        let page: Vec<ProxyRequestLog> = newer.iter().rev().take(limit).map(|log| log.summary()).collect();
+        let latest_seq = page
+            .last()
+            .map(|log| log.seq)
+            .unwrap_or_else(|| self.last_seq.load(Ordering::Relaxed).max(after_seq));
+        LogCursorPage {
+            logs: page,
+            latest_seq,
+            has_more,
+            truncated: self.evicted_seq.load(Ordering::Relaxed) > after_seq,
+        }
+    }
+
+
+    /// 按 ID 查找单条日志 (含请求体)，先查内存缓冲再查数据库
+    pub async fn find_log(&self, id: &str) -> Result<ProxyRequestLog, String> {
+        if let Some(log) = self.logs.read().await.iter().find(|log| log.id == id) {
+            return Ok(log.clone());
+        }
+        crate::modules::proxy_db::get_log_detail(id).map_err(|e| format!("未找到日志 {}: {}", id, e))
+    }
 
     pub async fn get_logs(&self, limit: usize) -> Vec<ProxyRequestLog> {
+        self.get_logs_filtered(limit, None, None, None).await
+    }
+
+    /// 获取日志，可按客户端地址、账号邮箱与是否被安全拦截过滤
+    pub async fn get_logs_filtered(
+        &self,
+        limit: usize,
+        client_ip: Option<&str>,
+        account_email: Option<&str>,
+        blocked: Option<bool>,
+    ) -> Vec<ProxyRequestLog> {
         // Try to get from DB first for true history
-        match crate::modules::proxy_db::get_logs(limit) {
+        match crate::modules::proxy_db::get_logs_filtered(limit, 0, client_ip, account_email, blocked) {
             Ok(logs) => logs,
             Err(e) => {
                 tracing::error!("Failed to get logs from DB: {}", e);
                 // Fallback to memory
                 let logs = self.logs.read().await;
-                logs.iter().take(limit).cloned().collect()
+                logs.iter()
+                    .filter(|log| client_ip.is_none() || log.client_ip.as_deref() == client_ip)
+                    .filter(|log| account_email.is_none() || log.account_email.as_deref() == account_email)
+                    .filter(|log| blocked.is_none() || Some(log.blocked) == blocked)
+                    .take(limit)
+                    .cloned()
+                    .collect()
             }
         }
     }
 
     pub async fn get_stats(&self) -> ProxyStats {
-        match crate::modules::proxy_db::get_stats() {
+        let mut stats = match crate::modules::proxy_db::get_stats() {
             Ok(stats) => stats,
             Err(e) => {
                 tracing::error!("Failed to get stats from DB: {}", e);
-                self.stats.read().await.clone()
+                let mut stats = self.stats.read().await.clone();
+                // 内存中只有缓冲内的日志，平均耗时按缓冲估算
+                let logs = self.logs.read().await;
+                stats.avg_duration_ms = average(logs.iter().map(|log| log.duration));
+                stats.avg_ttfb_ms = average(logs.iter().filter_map(|log| log.phase_timings.upstream_ttfb_ms));
+                stats
             }
-        }
+        };
+        let logs = self.logs.read().await;
+        stats.buffer_entries = logs.len();
+        stats.buffer_bytes = self.buffer_bytes.load(Ordering::Relaxed);
+        stats.upstream_connections = self.connections.snapshot();
+        stats
     }
     
-    pub async fn clear(&self) {
+    /// 开始处理一个请求，返回的守卫释放时计数减一
+    pub fn begin_request(&self) -> InFlightGuard {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlightGuard(self.in_flight.clone())
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// 最近 `window_secs` 秒内的请求数与失败数
+    pub async fn recent_counts(&self, window_secs: i64) -> (u64, u64) {
+        let since = chrono::Utc::now().timestamp_millis() - window_secs * 1000;
+        match crate::modules::proxy_db::get_counts_since(since) {
+            Ok(counts) => counts,
+            Err(e) => {
+                tracing::error!("Failed to get recent counts from DB: {}", e);
+                let logs = self.logs.read().await;
+                logs.iter()
+                    .filter(|log| log.timestamp >= since)
+                    .fold((0, 0), |(total, errors), log| {
+                        let failed = log.status < 200 || log.status >= 400;
+                        (total + 1, errors + failed as u64)
+                    })
+            }
+        }
+    }
+
+    /// 清空全部日志，返回删除的条数
+    pub async fn clear(&self) -> usize {
         let mut logs = self.logs.write().await;
+        let in_memory = logs.len();
         logs.clear();
+        self.buffer_bytes.store(0, Ordering::Relaxed);
         let mut stats = self.stats.write().await;
         *stats = ProxyStats::default();
 
-        if let Err(e) = crate::modules::proxy_db::clear_logs() {
-            tracing::error!("Failed to clear logs in DB: {}", e);
+        match crate::modules::proxy_db::clear_logs() {
+            Ok(deleted) => deleted,
+            Err(e) => {
+                tracing::error!("Failed to clear logs in DB: {}", e);
+                in_memory
+            }
         }
     }
-}
\ No newline at end of file
+
+    /// 只删除匹配条件的日志 (内存与数据库)，返回删除的条数；过滤条件为空时等同于 `clear`
+    pub async fn clear_matching(&self, filter: &LogFilter) -> usize {
+        if filter.is_empty() {
+            return self.clear().await;
+        }
+        // 持有写锁期间追加的日志会等待清理结束，不会丢失
+        let in_memory = {
+            let mut logs = self.logs.write().await;
+            let before = logs.len();
+            let mut freed = 0;
+            logs.retain(|log| {
+                let matched = filter.matches(log);
+                if matched {
+                    freed += log.estimated_size();
+                }
+                !matched
+            });
+            let bytes = self.buffer_bytes.load(Ordering::Relaxed);
+            self.buffer_bytes.store(bytes.saturating_sub(freed), Ordering::Relaxed);
+            before - logs.len()
+        };
+
+        match crate::modules::proxy_db::delete_logs(filter) {
+            Ok(deleted) => deleted,
+            Err(e) => {
+                tracing::error!("Failed to delete logs in DB: {}", e);
+                in_memory
+            }
+        }
+    }
+
+    /// 清空按天汇总的统计历史 (包括当天)
+    pub fn clear_history(&self) {
+        *self.today.lock().unwrap_or_else(|e| e.into_inner()) = DailyStats::new(stats_history::today());
+        if let Err(e) = stats_history::clear() {
+            tracing::error!("Failed to clear stats history: {}", e);
+        }
+    }
+}
+fn average(values: impl Iterator<Item = u64>) -> Option<f64> {
+    let (sum, count) = values.fold((0u64, 0u64), |(sum, count), v| (sum + v, count + 1));
+    (count > 0).then(|| sum as f64 / count as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(timestamp: i64, status: u16, model: &str) -> ProxyRequestLog {
+        ProxyRequestLog {
+            id: timestamp.to_string(),
+            timestamp,
+            status,
+            duration: 1,
+            model: Some(model.to_string()),
+            mapped_model: Some(format!("{}-mapped", model)),
+            ..ProxyRequestLog::test_default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_phase_timings_scope() {
+        // 不在请求上下文中时忽略
+        record_select_ms(5);
+        assert!(current_phases().is_empty());
+
+        let timings = PHASE_TIMINGS
+            .scope(std::cell::Cell::new(PhaseTimings::default()), async {
+                record_select_ms(3);
+                record_auth_ms(40);
+                record_select_ms(2);
+                record_phase(|t| t.upstream_ttfb_ms = Some(120));
+                current_phases()
+            })
+            .await;
+        assert_eq!(timings.select_ms, Some(5));
+        assert_eq!(timings.auth_ms, Some(40));
+        assert_eq!(timings.upstream_ttfb_ms, Some(120));
+        assert_eq!(timings.stream_ms, None);
+        assert_eq!(average([100, 200].into_iter()), Some(150.0));
+        assert_eq!(average(std::iter::empty()), None);
+    }
+
+    #[test]
+    fn test_request_kind_from_url() {
+        assert_eq!(RequestKind::from_url("/v1/messages"), RequestKind::Generate);
+        assert_eq!(RequestKind::from_url("/v1/messages/count_tokens?beta=true"), RequestKind::CountTokens);
+        assert_eq!(RequestKind::from_url("/v1beta/models/gemini-2.5-pro:countTokens"), RequestKind::CountTokens);
+        assert_eq!(RequestKind::from_url("/v1/embeddings"), RequestKind::Embeddings);
+        assert_eq!(RequestKind::parse(RequestKind::CountTokens.as_str()), RequestKind::CountTokens);
+    }
+
+    #[test]
+    fn test_error_class() {
+        assert_eq!(ErrorClass::classify(200, ""), None);
+        assert_eq!(
+            ErrorClass::classify(400, r#"{"error":{"status":"FAILED_PRECONDITION","message":"User location is not supported for the API use."}}"#),
+            Some(ErrorClass::RegionBlocked)
+        );
+        assert_eq!(ErrorClass::classify(400, "INVALID_ARGUMENT"), Some(ErrorClass::InvalidRequest));
+        assert_eq!(ErrorClass::classify(429, "RESOURCE_EXHAUSTED"), Some(ErrorClass::RateLimited));
+        assert_eq!(ErrorClass::classify(403, "PERMISSION_DENIED"), Some(ErrorClass::Auth));
+        assert_eq!(ErrorClass::classify(503, ""), Some(ErrorClass::Overloaded));
+        assert_eq!(ErrorClass::classify(500, "The model is overloaded"), Some(ErrorClass::Overloaded));
+        assert_eq!(ErrorClass::classify(500, "INTERNAL"), Some(ErrorClass::Upstream));
+        assert_eq!(
+            ErrorClass::classify(400, r#"{"promptFeedback":{"blockReason":"PROHIBITED_CONTENT"}}"#),
+            Some(ErrorClass::SafetyBlocked)
+        );
+        assert_eq!(ErrorClass::parse(ErrorClass::RegionBlocked.as_str()), Some(ErrorClass::RegionBlocked));
+    }
+
+    #[test]
+    fn test_log_filter_matches() {
+        assert_eq!(LogFilter::parse_status_class("5XX"), Ok(5));
+        assert!(LogFilter::parse_status_class("6xx").is_err());
+        assert!(LogFilter::parse_status_class("50x").is_err());
+
+        let filter = LogFilter {
+            before: Some(100),
+            status_class: Some(2),
+            model: Some("gemini-mapped".to_string()),
+        };
+        assert!(filter.matches(&log(50, 200, "gemini")));
+        assert!(!filter.matches(&log(150, 200, "gemini")));
+        assert!(!filter.matches(&log(50, 503, "gemini")));
+        assert!(!filter.matches(&log(50, 200, "claude")));
+        assert!(LogFilter::default().matches(&log(0, 500, "any")));
+    }
+
+    #[test]
+    fn test_monitor_enabled_precedence() {
+        // 没有临时切换时以配置为准
+        assert!(resolve_enabled(true, None));
+        assert!(!resolve_enabled(false, None));
+        // 临时切换优先于配置，启动时不会被配置覆盖
+        assert!(!resolve_enabled(true, Some(false)));
+        assert!(resolve_enabled(false, Some(true)));
+    }
+
+    /// 依次放入 `count` 条日志，每条附带 `body_bytes` 字节的请求体
+    async fn fill(monitor: &ProxyMonitor, count: usize, body_bytes: usize) {
+        for i in 0..count {
+            let mut entry = log(i as i64, 200, "gemini");
+            entry.request_body = Some("x".repeat(body_bytes));
+            monitor.buffer_log(&mut entry).await;
+        }
+    }
+
+    async fn buffered_seqs(monitor: &ProxyMonitor) -> Vec<u64> {
+        monitor.logs.read().await.iter().map(|log| log.seq).collect()
+    }
+
+    #[tokio::test]
+    async fn test_set_limits_shrinks_capacity() {
+        let monitor = ProxyMonitor::in_memory(10);
+        fill(&monitor, 5, 0).await;
+
+        monitor.set_limits(2, 0).await;
+        assert_eq!(monitor.capacity(), 2);
+        // 只淘汰最旧的记录，缓冲仍按新到旧排列
+        assert_eq!(buffered_seqs(&monitor).await, vec![5, 4]);
+        assert_eq!(monitor.evicted_seq.load(Ordering::Relaxed), 3);
+
+        // 上限至少为 1
+        monitor.set_limits(0, 0).await;
+        assert_eq!(monitor.capacity(), 1);
+        assert_eq!(buffered_seqs(&monitor).await, vec![5]);
+
+        // 新日志按新上限淘汰
+        fill(&monitor, 1, 0).await;
+        assert_eq!(buffered_seqs(&monitor).await, vec![6]);
+        assert_eq!(monitor.evicted_seq.load(Ordering::Relaxed), 5);
+    }
+
+    #[tokio::test]
+    async fn test_set_limits_byte_cap_evicts_oldest_first() {
+        const BODY: usize = 400 * 1024;
+        let monitor = ProxyMonitor::in_memory(100);
+        fill(&monitor, 3, BODY).await;
+        assert_eq!(buffered_seqs(&monitor).await, vec![3, 2, 1]);
+
+        // 1 MB 只能容纳两条
+        monitor.set_limits(100, 1).await;
+        assert_eq!(buffered_seqs(&monitor).await, vec![3, 2]);
+        assert_eq!(monitor.evicted_seq.load(Ordering::Relaxed), 1);
+        let buffered: u64 = monitor.logs.read().await.iter().map(|log| log.estimated_size()).sum();
+        assert_eq!(monitor.buffer_bytes.load(Ordering::Relaxed), buffered);
+        assert!(buffered <= 1024 * 1024);
+
+        // 写入新日志时先淘汰最旧的一条
+        fill(&monitor, 1, BODY).await;
+        assert_eq!(buffered_seqs(&monitor).await, vec![4, 3]);
+        assert_eq!(monitor.evicted_seq.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_set_limits_keeps_evicted_seq() {
+        let monitor = ProxyMonitor::in_memory(3);
+        fill(&monitor, 5, 0).await;
+        assert_eq!(monitor.evicted_seq.load(Ordering::Relaxed), 2);
+
+        // 放宽上限不会恢复或改写已淘汰的序号
+        monitor.set_limits(10, 0).await;
+        assert_eq!(buffered_seqs(&monitor).await, vec![5, 4, 3]);
+        assert_eq!(monitor.evicted_seq.load(Ordering::Relaxed), 2);
+
+        monitor.set_limits(1, 0).await;
+        assert_eq!(monitor.evicted_seq.load(Ordering::Relaxed), 4);
+        monitor.set_limits(10, 0).await;
+        assert_eq!(monitor.evicted_seq.load(Ordering::Relaxed), 4);
+    }
+
+    fn page_seqs(page: &LogCursorPage) -> Vec<u64> {
+        page.logs.iter().map(|log| log.seq).collect()
+    }
+
+    #[tokio::test]
+    async fn test_logs_after_pages_with_has_more() {
+        let monitor = ProxyMonitor::in_memory(10);
+        fill(&monitor, 5, 16).await;
+
+        let page = monitor.logs_after(0, 2).await;
+        assert_eq!(page_seqs(&page), vec![1, 2]);
+        assert_eq!(page.latest_seq, 2);
+        assert!(page.has_more);
+        assert!(!page.truncated);
+        // 增量结果不含请求体
+        assert!(page.logs.iter().all(|log| log.request_body.is_none()));
+
+        let page = monitor.logs_after(page.latest_seq, 10).await;
+        assert_eq!(page_seqs(&page), vec![3, 4, 5]);
+        assert_eq!(page.latest_seq, 5);
+        assert!(!page.has_more);
+    }
+
+    #[tokio::test]
+    async fn test_logs_after_truncated_after_eviction() {
+        let monitor = ProxyMonitor::in_memory(3);
+        fill(&monitor, 5, 0).await;
+
+        // 序号 1、2 已被淘汰，从 0 开始拉取会丢失部分日志
+        let page = monitor.logs_after(0, 10).await;
+        assert_eq!(page_seqs(&page), vec![3, 4, 5]);
+        assert!(page.truncated);
+        assert!(!page.has_more);
+
+        // 游标已越过淘汰位置时不算截断
+        let page = monitor.logs_after(2, 10).await;
+        assert_eq!(page_seqs(&page), vec![3, 4, 5]);
+        assert!(!page.truncated);
+
+        // 收缩上限后淘汰了游标之后的日志
+        monitor.set_limits(1, 0).await;
+        let page = monitor.logs_after(3, 10).await;
+        assert_eq!(page_seqs(&page), vec![5]);
+        assert!(page.truncated);
+    }
+
+    #[tokio::test]
+    async fn test_logs_after_latest_seq_is_monotonic() {
+        let monitor = ProxyMonitor::in_memory(10);
+        fill(&monitor, 3, 0).await;
+
+        let first = monitor.logs_after(0, 10).await.latest_seq;
+        assert_eq!(first, 3);
+        // 没有新日志时保持原游标
+        assert_eq!(monitor.logs_after(first, 10).await.latest_seq, 3);
+
+        fill(&monitor, 2, 0).await;
+        let second = monitor.logs_after(first, 10).await.latest_seq;
+        assert_eq!(second, 5);
+
+        // 缓冲被清空后不回退到更小的序号
+        monitor.logs.write().await.clear();
+        assert_eq!(monitor.logs_after(second, 10).await.latest_seq, 5);
+        // 客户端游标超前 (如服务端重启前的序号) 时保持客户端的值
+        assert_eq!(monitor.logs_after(9, 10).await.latest_seq, 9);
+    }
+
+    #[tokio::test]
+    async fn test_daily_flush_is_supervised() {
+        let tasks = TaskSupervisor::new();
+        let monitor = Arc::new(ProxyMonitor::in_memory(10));
+        monitor.register_daily_flush(&tasks);
+        assert!(tasks.list().iter().any(|task| task.name == "daily-stats-flush" && task.running));
+        assert_eq!(tasks.quiesce().await, vec!["daily-stats-flush"]);
+    }
+}
@@ -0,0 +1,81 @@
+// 模型单价表与请求费用估算，配置保存后热更新
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
+
+use crate::models::ModelPrice;
+use crate::proxy::common::model_mapping::wildcard_match;
+
+static PRICING: Lazy<RwLock<BTreeMap<String, ModelPrice>>> = Lazy::new(|| {
+    RwLock::new(
+        crate::modules::config::load_app_config()
+            .map(|config| config.pricing)
+            .unwrap_or_default(),
+    )
+});
+
+/// 替换当前单价表
+pub fn update(table: &BTreeMap<String, ModelPrice>) {
+    *PRICING.write().unwrap_or_else(|e| e.into_inner()) = table.clone();
+}
+
+/// 查找模型单价：精确匹配 > 通配符匹配 (模式越长越优先)
+pub fn lookup(table: &BTreeMap<String, ModelPrice>, model: &str) -> Option<ModelPrice> {
+    if let Some(price) = table.get(model) {
+        return Some(price.clone());
+    }
+    table
+        .iter()
+        .filter(|(pattern, _)| pattern.contains('*') && wildcard_match(pattern, model))
+        .max_by_key(|(pattern, _)| pattern.len())
+        .map(|(_, price)| price.clone())
+}
+
+/// 按单价表估算费用，返回 (费用, 货币)；模型无单价时返回 None
+pub fn estimate_with(
+    table: &BTreeMap<String, ModelPrice>,
+    model: &str,
+    input_tokens: u32,
+    output_tokens: u32,
+) -> Option<(f64, String)> {
+    let price = lookup(table, model)?;
+    let cost = input_tokens as f64 / 1000.0 * price.input_per_1k
+        + output_tokens as f64 / 1000.0 * price.output_per_1k;
+    Some((cost, price.currency))
+}
+
+/// 按当前单价表估算费用
+pub fn estimate(model: &str, input_tokens: u32, output_tokens: u32) -> Option<(f64, String)> {
+    let table = PRICING.read().unwrap_or_else(|e| e.into_inner());
+    estimate_with(&table, model, input_tokens, output_tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(input: f64, output: f64, currency: &str) -> ModelPrice {
+        ModelPrice { input_per_1k: input, output_per_1k: output, currency: currency.to_string() }
+    }
+
+    #[test]
+    fn test_estimate_precedence() {
+        let mut table = BTreeMap::new();
+        table.insert("gemini-*".to_string(), price(1.0, 1.0, "USD"));
+        table.insert("gemini-2.5-*".to_string(), price(0.5, 2.0, "USD"));
+        table.insert("gemini-2.5-flash".to_string(), price(0.1, 0.4, "CNY"));
+
+        let (cost, currency) = estimate_with(&table, "gemini-2.5-flash", 2000, 1000).unwrap();
+        assert!((cost - 0.6).abs() < 1e-9);
+        assert_eq!(currency, "CNY");
+
+        let (cost, _) = estimate_with(&table, "gemini-2.5-pro", 1000, 1000).unwrap();
+        assert!((cost - 2.5).abs() < 1e-9);
+
+        let (cost, _) = estimate_with(&table, "gemini-3-pro", 1000, 0).unwrap();
+        assert!((cost - 1.0).abs() < 1e-9);
+
+        assert!(estimate_with(&table, "claude-sonnet-4-5", 1000, 1000).is_none());
+    }
+}
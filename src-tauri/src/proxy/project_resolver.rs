@@ -3,6 +3,18 @@ use serde_json::Value;
 /// 使用 Antigravity 的 loadCodeAssist API 获取 project_id
 /// 这是获取 cloudaicompanionProject 的正确方式
 pub async fn fetch_project_id(access_token: &str) -> Result<String, String> {
+    if let Some(project_id) = load_code_assist_project(access_token).await? {
+        return Ok(project_id);
+    }
+
+    // 如果没有返回 project_id，说明账号无资格，使用内置随机生成逻辑作为兜底
+    let mock_id = generate_mock_project_id();
+    tracing::warn!("账号无资格获取官方 cloudaicompanionProject，将使用随机生成的 Project ID 作为兜底: {}", mock_id);
+    Ok(mock_id)
+}
+
+/// 调用 loadCodeAssist，返回账号的 cloudaicompanionProject (账号无资格时为 None)
+pub async fn load_code_assist_project(access_token: &str) -> Result<Option<String>, String> {
     let url = "https://cloudcode-pa.googleapis.com/v1internal:loadCodeAssist";
     
     let request_body = serde_json::json!({
@@ -34,15 +46,84 @@ pub async fn fetch_project_id(access_token: &str) -> Result<String, String> {
         .map_err(|e| format!("解析响应失败: {}", e))?;
     
     // 提取 cloudaicompanionProject
-    if let Some(project_id) = data.get("cloudaicompanionProject")
-        .and_then(|v| v.as_str()) {
-        return Ok(project_id.to_string());
+    Ok(data.get("cloudaicompanionProject")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string()))
+}
+
+/// 通过 Cloud Resource Manager 列出账号可访问的活跃项目，返回 (project_id, 名称)
+pub async fn list_cloud_projects(access_token: &str) -> Result<Vec<(String, Option<String>)>, String> {
+    let client = crate::utils::http::create_client(30);
+    let response = client
+        .get("https://cloudresourcemanager.googleapis.com/v1/projects")
+        .bearer_auth(access_token)
+        .query(&[("filter", "lifecycleState:ACTIVE"), ("pageSize", "100")])
+        .send()
+        .await
+        .map_err(|e| format!("获取项目列表失败: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("获取项目列表返回错误 {}: {}", status, body));
     }
-    
-    // 如果没有返回 project_id，说明账号无资格，使用内置随机生成逻辑作为兜底
-    let mock_id = generate_mock_project_id();
-    tracing::warn!("账号无资格获取官方 cloudaicompanionProject，将使用随机生成的 Project ID 作为兜底: {}", mock_id);
-    Ok(mock_id)
+
+    let data: Value = response.json()
+        .await
+        .map_err(|e| format!("解析响应失败: {}", e))?;
+
+    Ok(data.get("projects")
+        .and_then(|v| v.as_array())
+        .map(|projects| {
+            projects
+                .iter()
+                .filter_map(|p| {
+                    let id = p.get("projectId")?.as_str()?.to_string();
+                    let name = p.get("name").and_then(|n| n.as_str()).map(|n| n.to_string());
+                    Some((id, name))
+                })
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+/// 判断上游错误是否由 project_id 缺失或无效引起
+pub fn is_project_error(error_text: &str) -> bool {
+    let lower = error_text.to_lowercase();
+    if !lower.contains("project") {
+        return false;
+    }
+    [
+        "permission_denied",
+        "not_found",
+        "not found",
+        "has not been used",
+        "invalid project",
+        "missing project",
+        "project id",
+    ]
+    .iter()
+    .any(|pattern| lower.contains(pattern))
+}
+
+/// 为 project 相关的上游错误追加处理提示；JSON 错误体追加到 error.message，保持可解析
+pub fn with_project_hint(error_text: String, email: &str) -> String {
+    if !is_project_error(&error_text) {
+        return error_text;
+    }
+    let hint = format!(
+        "账号 {} 的 project_id 可能缺失或无效，可调用 POST /api/accounts/{{id}}/discover-project 重新获取，或通过 PATCH /api/accounts/{{id}} 手动设置",
+        email
+    );
+    if let Ok(mut json) = serde_json::from_str::<Value>(&error_text) {
+        if let Some(message) = json.pointer_mut("/error/message") {
+            if let Some(text) = message.as_str() {
+                *message = Value::String(format!("{} ({})", text, hint));
+                return json.to_string();
+            }
+        }
+    }
+    format!("{} ({})", error_text, hint)
 }
 
 /// 生成随机 project_id（当无法从 API 获取时使用）
@@ -68,3 +149,25 @@ pub fn generate_mock_project_id() -> String {
     
     format!("{}-{}-{}", adj, noun, random_num)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_hint() {
+        let body = r#"{"error":{"code":403,"message":"Permission denied on resource project foo-bar.","status":"PERMISSION_DENIED"}}"#;
+        let hinted = with_project_hint(body.to_string(), "a@example.com");
+        let json: Value = serde_json::from_str(&hinted).unwrap();
+        let message = json["error"]["message"].as_str().unwrap();
+        assert!(message.starts_with("Permission denied on resource project foo-bar."));
+        assert!(message.contains("discover-project"));
+        assert_eq!(json["error"]["code"], 403);
+
+        let plain = with_project_hint("Cloud Code API has not been used in project 123".to_string(), "a@example.com");
+        assert!(plain.contains("a@example.com"));
+
+        let unrelated = r#"{"error":{"code":429,"message":"Resource has been exhausted","status":"RESOURCE_EXHAUSTED"}}"#;
+        assert_eq!(with_project_hint(unrelated.to_string(), "a@example.com"), unrelated);
+    }
+}
@@ -196,6 +196,7 @@ pub async fn forward_anthropic_json(
     };
 
     let status = StatusCode::from_u16(resp.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    crate::proxy::middleware::response_headers::record_upstream_headers(resp.headers());
 
     let mut out = Response::builder().status(status);
     if let Some(ct) = resp.headers().get(header::CONTENT_TYPE) {
@@ -1,19 +1,83 @@
-use crate::proxy::config::{ProxyAuthMode, ProxyConfig};
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use utoipa::ToSchema;
 
-#[derive(Debug, Clone)]
+use crate::models::token::mask_secret;
+use crate::proxy::config::{AccountGroup, ApiKeyEntry, ProxyAuthMode, ProxyConfig, UnauthorizedLockout, DEFAULT_API_KEY_NAME};
+use crate::proxy::ip_filter::IpAccessList;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct ProxySecurityConfig {
     pub auth_mode: ProxyAuthMode,
     pub api_key: String,
+    pub api_keys: Vec<ApiKeyEntry>,
+    pub account_groups: Vec<AccountGroup>,
     pub allow_lan_access: bool,
+    pub ip_access: IpAccessList,
+    pub lockout: UnauthorizedLockout,
 }
 
+/// 通过鉴权的密钥名，由鉴权中间件写入请求扩展
+#[derive(Debug, Clone)]
+pub struct ApiKeyName(pub String);
+
+/// 反代当前生效的安全配置；热更新时整体替换为新的快照，读取方持有的旧快照不受影响
+pub type SharedSecurity = Arc<RwLock<Arc<ProxySecurityConfig>>>;
+
+/// 本次请求使用的安全配置快照，由 IP 过滤中间件写入请求扩展，鉴权中间件沿用同一份
+#[derive(Debug, Clone)]
+pub struct SecuritySnapshot(pub Arc<ProxySecurityConfig>);
+
 impl ProxySecurityConfig {
     pub fn from_proxy_config(config: &ProxyConfig) -> Self {
         Self {
             auth_mode: config.auth_mode.clone(),
             api_key: config.api_key.clone(),
+            api_keys: config.api_keys.clone(),
+            account_groups: config.account_groups.clone(),
             allow_lan_access: config.allow_lan_access,
+            ip_access: IpAccessList::new(&config.allowed_ips, &config.trusted_proxies),
+            lockout: config.unauthorized_lockout.clone(),
+        }
+    }
+
+    /// 是否配置了任何可用密钥
+    pub fn has_keys(&self) -> bool {
+        !self.api_key.is_empty() || self.api_keys.iter().any(|k| k.enabled)
+    }
+
+    /// 校验密钥，返回匹配的密钥名；已禁用的密钥视为无效
+    pub fn authenticate(&self, key: &str) -> Option<&str> {
+        if !self.api_key.is_empty() && key == self.api_key {
+            return Some(DEFAULT_API_KEY_NAME);
         }
+        self.api_keys
+            .iter()
+            .find(|entry| entry.enabled && !entry.key.is_empty() && entry.key == key)
+            .map(|entry| entry.name.as_str())
+    }
+
+    /// 按密钥名取当前可用的密钥值，用于以原请求的身份重放；密钥已删除或禁用时为空
+    pub fn key_for(&self, name: &str) -> Option<&str> {
+        if name == DEFAULT_API_KEY_NAME && !self.api_key.is_empty() {
+            return Some(&self.api_key);
+        }
+        self.api_keys
+            .iter()
+            .find(|entry| entry.enabled && !entry.key.is_empty() && entry.name == name)
+            .map(|entry| entry.key.as_str())
+    }
+
+    /// 是否为管理员密钥；旧版单一 `api_key` (`default`) 视为管理员
+    pub fn is_admin(&self, name: &str) -> bool {
+        name == DEFAULT_API_KEY_NAME || self.api_keys.iter().any(|entry| entry.name == name && entry.admin)
+    }
+
+    /// 密钥绑定的账号分组，未绑定时为空
+    pub fn group_for_key(&self, name: &str) -> Option<&AccountGroup> {
+        let group = self.api_keys.iter().find(|entry| entry.name == name)?.group.as_ref()?;
+        self.account_groups.iter().find(|g| g.name == *group)
     }
 
     pub fn effective_auth_mode(&self) -> ProxyAuthMode {
@@ -30,6 +94,68 @@ impl ProxySecurityConfig {
     }
 }
 
+/// 运行中反代实际执行的安全配置 (密钥已脱敏)
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SecuritySummary {
+    /// 配置的鉴权模式
+    pub auth_mode: ProxyAuthMode,
+    /// 解析 `auto` 后实际生效的鉴权模式
+    pub effective_auth_mode: ProxyAuthMode,
+    /// 是否需要 API 密钥 (`/healthz` 在 all_except_health 下除外)
+    pub auth_required: bool,
+    /// 默认密钥 (脱敏)，未设置时为空
+    pub api_key: Option<String>,
+    pub api_keys: Vec<ApiKeySummary>,
+    pub allow_lan_access: bool,
+    /// 生效的客户端白名单，为空表示不限制
+    pub allowed_ips: Vec<String>,
+    pub trusted_proxies: Vec<String>,
+    /// 鉴权失败封禁设置
+    pub unauthorized_lockout: UnauthorizedLockout,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ApiKeySummary {
+    pub name: String,
+    /// 脱敏后的密钥
+    pub key: String,
+    pub enabled: bool,
+    pub requests_per_minute: Option<u32>,
+    pub concurrent_requests: Option<u32>,
+    /// 绑定的账号分组
+    pub group: Option<String>,
+    pub admin: bool,
+}
+
+impl ProxySecurityConfig {
+    pub fn summary(&self) -> SecuritySummary {
+        let effective_auth_mode = self.effective_auth_mode();
+        SecuritySummary {
+            auth_mode: self.auth_mode.clone(),
+            auth_required: !matches!(effective_auth_mode, ProxyAuthMode::Off),
+            effective_auth_mode,
+            api_key: Some(self.api_key.as_str()).filter(|k| !k.is_empty()).map(mask_secret),
+            api_keys: self
+                .api_keys
+                .iter()
+                .map(|entry| ApiKeySummary {
+                    name: entry.name.clone(),
+                    key: mask_secret(&entry.key),
+                    enabled: entry.enabled,
+                    requests_per_minute: entry.requests_per_minute,
+                    concurrent_requests: entry.concurrent_requests,
+                    group: entry.group.clone(),
+                    admin: entry.admin,
+                })
+                .collect(),
+            allow_lan_access: self.allow_lan_access,
+            allowed_ips: self.ip_access.allowed(),
+            trusted_proxies: self.ip_access.trusted_proxies(),
+            unauthorized_lockout: self.lockout.clone(),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -39,7 +165,11 @@ mod tests {
         let s = ProxySecurityConfig {
             auth_mode: ProxyAuthMode::Auto,
             api_key: "sk-test".to_string(),
+            api_keys: Vec::new(),
+            account_groups: Vec::new(),
             allow_lan_access: false,
+            ip_access: IpAccessList::default(),
+            lockout: UnauthorizedLockout::default(),
         };
         assert!(matches!(s.effective_auth_mode(), ProxyAuthMode::Off));
     }
@@ -49,12 +179,58 @@ mod tests {
         let s = ProxySecurityConfig {
             auth_mode: ProxyAuthMode::Auto,
             api_key: "sk-test".to_string(),
+            api_keys: Vec::new(),
+            account_groups: Vec::new(),
             allow_lan_access: true,
+            ip_access: IpAccessList::default(),
+            lockout: UnauthorizedLockout::default(),
         };
         assert!(matches!(
             s.effective_auth_mode(),
             ProxyAuthMode::AllExceptHealth
         ));
     }
-}
 
+    #[test]
+    fn authenticate_resolves_named_keys() {
+        let s = ProxySecurityConfig {
+            auth_mode: ProxyAuthMode::Strict,
+            api_key: "sk-default".to_string(),
+            api_keys: vec![
+                ApiKeyEntry { name: "alice".to_string(), key: "sk-alice".to_string(), enabled: true, requests_per_minute: None, concurrent_requests: None, group: None, admin: false },
+                ApiKeyEntry { name: "bob".to_string(), key: "sk-bob".to_string(), enabled: false, requests_per_minute: None, concurrent_requests: None, group: None, admin: false },
+            ],
+            account_groups: Vec::new(),
+            allow_lan_access: false,
+            ip_access: IpAccessList::default(),
+            lockout: UnauthorizedLockout::default(),
+        };
+        assert_eq!(s.authenticate("sk-default"), Some(DEFAULT_API_KEY_NAME));
+        assert_eq!(s.authenticate("sk-alice"), Some("alice"));
+        assert_eq!(s.authenticate("sk-bob"), None);
+        assert_eq!(s.authenticate(""), None);
+
+        assert_eq!(s.key_for(DEFAULT_API_KEY_NAME), Some("sk-default"));
+        assert_eq!(s.key_for("alice"), Some("sk-alice"));
+        assert_eq!(s.key_for("bob"), None);
+        assert_eq!(s.key_for("carol"), None);
+    }
+
+    #[test]
+    fn summary_masks_keys() {
+        let s = ProxySecurityConfig {
+            auth_mode: ProxyAuthMode::Auto,
+            api_key: "sk-0123456789abcdef".to_string(),
+            api_keys: vec![ApiKeyEntry { name: "alice".to_string(), key: "sk-alice".to_string(), enabled: true, requests_per_minute: Some(10), concurrent_requests: None, group: None, admin: false }],
+            account_groups: Vec::new(),
+            allow_lan_access: true,
+            ip_access: IpAccessList::new(&["10.0.0.0/8".to_string(), "192.168.1.5".to_string()], &[]),
+            lockout: UnauthorizedLockout::default(),
+        };
+        let summary = s.summary();
+        assert!(summary.auth_required);
+        assert_eq!(summary.api_key.as_deref(), Some("sk-012...cdef"));
+        assert_eq!(summary.api_keys[0].key, "********");
+        assert_eq!(summary.allowed_ips, vec!["10.0.0.0/8", "192.168.1.5/32"]);
+    }
+}
@@ -39,9 +39,15 @@ pub struct AxumServer {
     shutdown_tx: Option<oneshot::Sender<()>>,
     custom_mapping: Arc<tokio::sync::RwLock<std::collections::HashMap<String, String>>>,
     proxy_state: Arc<tokio::sync::RwLock<crate::proxy::config::UpstreamProxyConfig>>,
-    security_state: Arc<RwLock<crate::proxy::ProxySecurityConfig>>,
+    security_state: crate::proxy::security::SharedSecurity,
+    key_limiter: Arc<crate::proxy::key_limiter::KeyRateLimiter>,
+    unauthorized: Arc<crate::proxy::unauthorized::UnauthorizedTracker>,
     zai_state: Arc<RwLock<crate::proxy::ZaiConfig>>,
     experimental: Arc<RwLock<crate::proxy::config::ExperimentalConfig>>,
+    prompt_rules: Arc<RwLock<Vec<crate::proxy::PromptRule>>>,
+    response_headers: Arc<RwLock<crate::proxy::ResponseHeadersConfig>>,
+    upstream: Arc<crate::proxy::upstream::client::UpstreamClient>,
+    replay_router: Router,
 }
 
 impl AxumServer {
@@ -53,6 +59,21 @@ impl AxumServer {
         tracing::debug!("模型映射 (Custom) 已全量热更新");
     }
 
+    /// 反代使用的上游客户端
+    pub fn upstream(&self) -> Arc<crate::proxy::upstream::client::UpstreamClient> {
+        self.upstream.clone()
+    }
+
+    /// 在进程内经完整的反代链路 (IP 过滤、鉴权、模型映射、账号调度、监控) 处理一个请求；
+    /// 客户端地址取请求扩展中的 `ConnectInfo`，API 密钥取请求头
+    pub async fn dispatch(&self, request: axum::extract::Request) -> Response {
+        use tower::ServiceExt;
+        match self.replay_router.clone().oneshot(request).await {
+            Ok(response) => response,
+            Err(never) => match never {},
+        }
+    }
+
     /// 更新代理配置
     pub async fn update_proxy(&self, new_config: crate::proxy::config::UpstreamProxyConfig) {
         let mut proxy = self.proxy_state.write().await;
@@ -60,10 +81,39 @@ impl AxumServer {
         tracing::info!("上游代理配置已热更新");
     }
 
-    pub async fn update_security(&self, config: &crate::proxy::config::ProxyConfig) {
-        let mut sec = self.security_state.write().await;
-        *sec = crate::proxy::ProxySecurityConfig::from_proxy_config(config);
-        tracing::info!("反代服务安全配置已热更新");
+    /// 热更新安全配置，返回配置是否有变化
+    pub async fn update_security(&self, config: &crate::proxy::config::ProxyConfig) -> bool {
+        // 先构建完整的新配置再整体替换，处理中的请求继续使用各自的旧快照
+        let security = Arc::new(crate::proxy::ProxySecurityConfig::from_proxy_config(config));
+        let changed = {
+            let mut current = self.security_state.write().await;
+            let changed = **current != *security;
+            *current = security;
+            changed
+        };
+        // 限流状态随密钥保留，限制变化时在下次请求时重建
+        self.key_limiter.retain(&config.api_keys);
+        if changed {
+            tracing::info!("反代服务安全配置已热更新");
+        }
+        changed
+    }
+
+    /// 当前生效的安全配置
+    pub async fn security(&self) -> Arc<crate::proxy::ProxySecurityConfig> {
+        self.security_state.read().await.clone()
+    }
+
+    /// 查询具名密钥当前的限流状态
+    pub async fn key_usage(&self, name: &str) -> Option<crate::proxy::key_limiter::KeyUsage> {
+        let sec = self.security_state.read().await;
+        let entry = sec.api_keys.iter().find(|entry| entry.name == name)?;
+        Some(self.key_limiter.usage(entry))
+    }
+
+    /// 鉴权失败的来源记录与封禁列表
+    pub fn unauthorized(&self) -> Arc<crate::proxy::unauthorized::UnauthorizedTracker> {
+        self.unauthorized.clone()
     }
 
     pub async fn update_zai(&self, config: &crate::proxy::config::ProxyConfig) {
@@ -77,28 +127,67 @@ impl AxumServer {
         *exp = config.experimental.clone();
         tracing::info!("实验性配置已热更新");
     }
+
+    pub async fn update_prompt_rules(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut rules = self.prompt_rules.write().await;
+        if *rules != config.prompt_rules {
+            *rules = config.prompt_rules.clone();
+            tracing::info!("提示词规则已热更新 ({} 条)", rules.len());
+        }
+    }
+
+    pub async fn update_response_headers(&self, config: &crate::proxy::config::ProxyConfig) {
+        let mut headers = self.response_headers.write().await;
+        if *headers != config.response_headers {
+            *headers = config.response_headers.clone();
+            tracing::info!("响应头配置已热更新");
+        }
+    }
+
+    /// 热更新上游超时与连接设置 (代理 / 连接池变化时重建客户端)
+    pub fn update_upstream(&self, config: &crate::proxy::config::ProxyConfig) {
+        use crate::proxy::upstream::client::{UpstreamConnectionSettings, UpstreamTimeouts};
+        self.upstream.set_timeouts(UpstreamTimeouts::from_proxy_config(config));
+        self.upstream.reconfigure(UpstreamConnectionSettings::from_proxy_config(config));
+        tracing::debug!("上游客户端配置已热更新");
+    }
     /// 启动 Axum 服务器
     pub async fn start(
         host: String,
         port: u16,
         token_manager: Arc<TokenManager>,
         custom_mapping: std::collections::HashMap<String, String>,
-        _request_timeout: u64,
+        request_timeout: u64,
+        timeouts: crate::proxy::TimeoutOverrides,
         upstream_proxy: crate::proxy::config::UpstreamProxyConfig,
+        upstream_connection: crate::proxy::upstream::client::UpstreamConnectionSettings,
         security_config: crate::proxy::ProxySecurityConfig,
         zai_config: crate::proxy::ZaiConfig,
         monitor: Arc<crate::proxy::monitor::ProxyMonitor>,
         experimental_config: crate::proxy::config::ExperimentalConfig,
-
+        prompt_rules: Vec<crate::proxy::PromptRule>,
+        response_headers: crate::proxy::ResponseHeadersConfig,
     ) -> Result<(Self, tokio::task::JoinHandle<()>), String> {
         let custom_mapping_state = Arc::new(tokio::sync::RwLock::new(custom_mapping));
 	        let proxy_state = Arc::new(tokio::sync::RwLock::new(upstream_proxy.clone()));
-	        let security_state = Arc::new(RwLock::new(security_config));
+	        let security_state = Arc::new(RwLock::new(Arc::new(security_config)));
+	        let key_limiter = Arc::new(crate::proxy::key_limiter::KeyRateLimiter::default());
+	        let unauthorized = Arc::new(crate::proxy::unauthorized::UnauthorizedTracker::default());
 	        let zai_state = Arc::new(RwLock::new(zai_config));
 	        let provider_rr = Arc::new(AtomicUsize::new(0));
 	        let zai_vision_mcp_state =
 	            Arc::new(crate::proxy::zai_vision_mcp::ZaiVisionMcpState::new());
 	        let experimental_state = Arc::new(RwLock::new(experimental_config));
+	        let prompt_rules_state = Arc::new(RwLock::new(prompt_rules));
+	        let response_headers_state = Arc::new(RwLock::new(response_headers));
+	        let upstream = Arc::new(crate::proxy::upstream::client::UpstreamClient::new(
+	            upstream_connection,
+	            crate::proxy::upstream::client::UpstreamTimeouts {
+	                request_timeout,
+	                overrides: timeouts,
+	            },
+	            monitor.connections.clone(),
+	        ));
 
 	        let state = AppState {
 	            token_manager: token_manager.clone(),
@@ -108,9 +197,7 @@ impl AxumServer {
                 std::collections::HashMap::new(),
             )),
             upstream_proxy: proxy_state.clone(),
-            upstream: Arc::new(crate::proxy::upstream::client::UpstreamClient::new(Some(
-                upstream_proxy.clone(),
-            ))),
+            upstream: upstream.clone(),
             zai: zai_state.clone(),
             provider_rr: provider_rr.clone(),
             zai_vision_mcp: zai_vision_mcp_state,
@@ -146,6 +233,10 @@ impl AxumServer {
                 "/v1/audio/transcriptions",
                 post(handlers::audio::handle_audio_transcription),
             ) // 音频转录 API (PR #311)
+            .route(
+                "/v1/embeddings",
+                post(handlers::embeddings::handle_embeddings),
+            ) // 向量嵌入 API
             // Claude Protocol
             .route("/v1/messages", post(handlers::claude::handle_messages))
             .route(
@@ -186,14 +277,41 @@ impl AxumServer {
             .route("/v1/api/event_logging", post(silent_ok_handler))
             .route("/healthz", get(health_check_handler))
             .layer(DefaultBodyLimit::max(100 * 1024 * 1024))
+            .layer(axum::middleware::from_fn_with_state(
+                prompt_rules_state.clone(),
+                crate::proxy::middleware::prompt_rules_middleware,
+            ))
+            .layer(axum::middleware::from_fn(crate::proxy::middleware::error_translation_middleware))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::account_pin_middleware))
             .layer(axum::middleware::from_fn_with_state(state.clone(), crate::proxy::middleware::monitor::monitor_middleware))
-            .layer(TraceLayer::new_for_http())
             .layer(axum::middleware::from_fn_with_state(
-                security_state.clone(),
+                experimental_state.clone(),
+                crate::proxy::middleware::mirror_middleware,
+            ))
+            .layer(axum::middleware::from_fn_with_state(
+                experimental_state.clone(),
+                crate::proxy::middleware::endpoint_middleware,
+            ))
+            .layer(TraceLayer::new_for_http());
+
+        let app = app
+            .layer(axum::middleware::from_fn_with_state(
+                (security_state.clone(), key_limiter.clone(), unauthorized.clone()),
                 crate::proxy::middleware::auth_middleware,
             ))
+            .layer(axum::middleware::from_fn_with_state(
+                security_state.clone(),
+                crate::proxy::middleware::ip_filter_middleware,
+            ))
+            .layer(axum::middleware::from_fn(crate::proxy::middleware::catch_panic_middleware))
+            .layer(axum::middleware::from_fn_with_state(
+                response_headers_state.clone(),
+                crate::proxy::middleware::response_headers_middleware,
+            ))
             .layer(crate::proxy::middleware::cors_layer())
             .with_state(state);
+        // 请求重放在进程内经完整链路处理，与客户端请求一样经过 IP 过滤、鉴权与密钥限流
+        let replay_router = app.clone();
 
         // 绑定地址
         let addr = format!("{}:{}", host, port);
@@ -211,8 +329,14 @@ impl AxumServer {
             custom_mapping: custom_mapping_state.clone(),
             proxy_state,
             security_state,
+            key_limiter,
+            unauthorized,
             zai_state,
             experimental: experimental_state.clone(),
+            prompt_rules: prompt_rules_state,
+            response_headers: response_headers_state,
+            upstream,
+            replay_router,
         };
 
         // 在新任务中启动服务器
@@ -225,7 +349,7 @@ impl AxumServer {
                 tokio::select! {
                     res = listener.accept() => {
                         match res {
-                            Ok((stream, _)) => {
+                            Ok((stream, peer)) => {
                                 // [FIX] 设置 TCP Keep-Alive 以防止 Docker/网络环境下的连接静默断开
                                 // 这对于长时间运行的 SSE 流式连接尤为重要
                                 if let Ok(sock_ref) = socket2::SockRef::try_from(&stream) {
@@ -239,7 +363,14 @@ impl AxumServer {
                                 }
 
                                 let io = TokioIo::new(stream);
-                                let service = TowerToHyperService::new(app.clone());
+                                // 写入对端地址，供 IP 白名单与请求日志使用
+                                let service = TowerToHyperService::new(tower::ServiceExt::map_request(
+                                    app.clone(),
+                                    move |mut req: hyper::Request<hyper::body::Incoming>| {
+                                        req.extensions_mut().insert(axum::extract::ConnectInfo(peer));
+                                        req
+                                    },
+                                ));
 
                                 tokio::task::spawn(async move {
                                     if let Err(err) = http1::Builder::new()
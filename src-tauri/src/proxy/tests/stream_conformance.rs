@@ -0,0 +1,335 @@
+// 流式协议一致性测试
+// 将 fixtures/streams/*.sse 中录制的 Gemini 上游流分别转换为 OpenAI 与 Claude SSE，
+// 先做协议结构校验，再与同名 golden 文件比对。设置 UPDATE_GOLDEN=1 重新生成 golden 文件。
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use futures::{Stream, StreamExt};
+    use serde_json::{json, Value};
+    use std::collections::{BTreeMap, HashMap};
+    use std::path::{Path, PathBuf};
+    use std::pin::Pin;
+
+    use crate::proxy::mappers::claude::create_claude_sse_stream;
+    use crate::proxy::mappers::openai::streaming::create_openai_sse_stream;
+
+    const FIXTURE_DIR: &str = "src/proxy/tests/fixtures/streams";
+    /// 调试日志中上游分片的前缀，便于直接粘贴日志作为 fixture
+    const LOG_PREFIX: &str = "Gemini SSE Chunk: ";
+    /// 上游字节按小块切分，覆盖跨分片的行缓冲
+    const CHUNK_SIZE: usize = 17;
+
+    type UpstreamStream = Pin<Box<dyn Stream<Item = Result<Bytes, reqwest::Error>> + Send>>;
+    type OutputStream = Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>>;
+
+    /// 读取 fixture：支持 `data: {...}` 行、裸 JSON 行与调试日志行，`#` 开头为注释
+    fn load_fixture(text: &str) -> Vec<String> {
+        text.lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#') && !l.starts_with(':') && !l.starts_with("event:"))
+            .map(|l| match l.find(LOG_PREFIX) {
+                Some(pos) => &l[pos + LOG_PREFIX.len()..],
+                None => l.strip_prefix("data:").unwrap_or(l),
+            })
+            .map(|l| l.trim().to_string())
+            .collect()
+    }
+
+    fn upstream(payloads: &[String]) -> UpstreamStream {
+        let raw: Vec<u8> = payloads
+            .iter()
+            .flat_map(|p| format!("data: {}\n\n", p).into_bytes())
+            .collect();
+        let chunks: Vec<Result<Bytes, reqwest::Error>> = raw
+            .chunks(CHUNK_SIZE)
+            .map(|c| Ok(Bytes::copy_from_slice(c)))
+            .collect();
+        Box::pin(futures::stream::iter(chunks))
+    }
+
+    async fn collect(stream: OutputStream) -> String {
+        let chunks: Vec<_> = stream.collect().await;
+        let bytes: Vec<u8> = chunks
+            .into_iter()
+            .flat_map(|c| c.expect("流转换出错").to_vec())
+            .collect();
+        String::from_utf8(bytes).expect("输出不是合法 UTF-8")
+    }
+
+    /// 拆分 SSE 输出为 (event, data)，忽略心跳注释
+    fn parse_sse(raw: &str) -> Vec<(String, String)> {
+        raw.split("\n\n")
+            .filter_map(|block| {
+                let mut event = String::new();
+                let mut data = None;
+                for line in block.lines() {
+                    if let Some(v) = line.strip_prefix("event: ") {
+                        event = v.to_string();
+                    } else if let Some(v) = line.strip_prefix("data: ") {
+                        data = Some(v.to_string());
+                    }
+                }
+                data.map(|d| (event, d))
+            })
+            .collect()
+    }
+
+    /// 对象键排序，保证 golden 输出与 serde_json 特性无关
+    fn canonical(value: &Value) -> Value {
+        match value {
+            Value::Object(map) => {
+                let sorted: BTreeMap<_, _> = map.iter().map(|(k, v)| (k.clone(), canonical(v))).collect();
+                Value::Object(sorted.into_iter().collect())
+            }
+            Value::Array(items) => Value::Array(items.iter().map(canonical).collect()),
+            other => other.clone(),
+        }
+    }
+
+    fn parse_json(name: &str, data: &str) -> Value {
+        serde_json::from_str(data).unwrap_or_else(|e| panic!("{}: 输出不是合法 JSON ({}): {}", name, e, data))
+    }
+
+    /// 替换随机的流 id、时间戳与工具调用 id
+    fn normalize_openai(name: &str, events: &[(String, String)]) -> Vec<String> {
+        let mut call_ids: HashMap<String, String> = HashMap::new();
+        events
+            .iter()
+            .map(|(_, data)| {
+                if data == "[DONE]" {
+                    return data.clone();
+                }
+                let mut v = parse_json(name, data);
+                if let Some(obj) = v.as_object_mut() {
+                    if obj.contains_key("id") {
+                        obj.insert("id".to_string(), json!("chatcmpl-test"));
+                    }
+                    if obj.contains_key("created") {
+                        obj.insert("created".to_string(), json!(0));
+                    }
+                }
+                if let Some(choices) = v.get_mut("choices").and_then(Value::as_array_mut) {
+                    let calls = choices
+                        .iter_mut()
+                        .filter_map(|c| c.pointer_mut("/delta/tool_calls"))
+                        .filter_map(Value::as_array_mut)
+                        .flatten();
+                    for call in calls {
+                        if let Some(id) = call.get_mut("id") {
+                            let key = id.as_str().unwrap_or_default().to_string();
+                            let next = format!("call_{}", call_ids.len());
+                            *id = json!(call_ids.entry(key).or_insert(next).clone());
+                        }
+                    }
+                }
+                serde_json::to_string(&canonical(&v)).unwrap()
+            })
+            .collect()
+    }
+
+    /// 上游未给出调用 id 时生成的 `<name>-<8 位随机串>` 替换为固定占位
+    fn normalize_claude(name: &str, events: &[(String, String)]) -> Vec<String> {
+        events
+            .iter()
+            .map(|(event, data)| {
+                let mut v = parse_json(name, data);
+                if let Some(block) = v.get_mut("content_block").filter(|b| b["type"] == "tool_use") {
+                    let prefix = format!("{}-", block["name"].as_str().unwrap_or_default());
+                    let generated = block["id"]
+                        .as_str()
+                        .and_then(|id| id.strip_prefix(&prefix))
+                        .is_some_and(|rest| rest.len() == 8 && rest.chars().all(|c| c.is_ascii_alphanumeric()));
+                    if generated {
+                        block["id"] = json!(format!("{}<generated>", prefix));
+                    }
+                }
+                format!("{} {}", event, serde_json::to_string(&canonical(&v)).unwrap())
+            })
+            .collect()
+    }
+
+    fn check_golden(path: &Path, lines: &[String]) {
+        let actual = lines.iter().map(|l| format!("{}\n", l)).collect::<String>();
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(path, &actual).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(path)
+            .unwrap_or_else(|_| panic!("缺少 golden 文件 {}，请使用 UPDATE_GOLDEN=1 生成", path.display()));
+        assert_eq!(
+            expected.replace("\r\n", "\n"),
+            actual,
+            "{} 与 golden 不一致 (确认改动无误后用 UPDATE_GOLDEN=1 更新)",
+            path.display()
+        );
+    }
+
+    /// Claude 结构校验：message_start 开头、块索引连续且成对、工具参数拼接后为 JSON 对象、
+    /// 恰好一个 message_delta 且 message_stop 为最后一个事件
+    fn check_claude(name: &str, events: &[(String, String)]) {
+        let events: Vec<(&str, Value)> = events.iter().map(|(e, d)| (e.as_str(), parse_json(name, d))).collect();
+        assert_eq!(events.first().map(|e| e.0), Some("message_start"), "{}: 第一个事件必须是 message_start", name);
+        assert_eq!(events.last().map(|e| e.0), Some("message_stop"), "{}: 最后一个事件必须是 message_stop", name);
+        assert_eq!(events.iter().filter(|e| e.0 == "message_start").count(), 1, "{}: message_start 重复", name);
+        assert_eq!(events.iter().filter(|e| e.0 == "message_stop").count(), 1, "{}: message_stop 重复", name);
+
+        let mut open: Option<(u64, String)> = None;
+        let mut next_index = 0u64;
+        let mut tool_json = String::new();
+        let mut used_tool = false;
+        let mut message_deltas = 0;
+        for (event, data) in &events {
+            match *event {
+                "content_block_start" => {
+                    assert!(open.is_none(), "{}: 上一个内容块未结束", name);
+                    assert_eq!(data["index"], next_index, "{}: 内容块索引不连续", name);
+                    let kind = data["content_block"]["type"].as_str().unwrap_or_default().to_string();
+                    if kind == "tool_use" {
+                        used_tool = true;
+                        assert_eq!(data["content_block"]["input"], json!({}), "{}: tool_use 起始 input 必须为空对象", name);
+                        tool_json.clear();
+                    }
+                    open = Some((next_index, kind));
+                }
+                "content_block_delta" => {
+                    let (index, kind) = open.as_ref().unwrap_or_else(|| panic!("{}: delta 不在内容块内", name));
+                    assert_eq!(data["index"], *index, "{}: delta 索引与当前块不一致", name);
+                    let expected_kind = match data["delta"]["type"].as_str().unwrap_or_default() {
+                        "input_json_delta" => {
+                            tool_json.push_str(data["delta"]["partial_json"].as_str().unwrap_or_default());
+                            "tool_use"
+                        }
+                        "text_delta" => "text",
+                        "thinking_delta" | "signature_delta" => "thinking",
+                        other => panic!("{}: 未知 delta 类型 {}", name, other),
+                    };
+                    assert_eq!(kind, expected_kind, "{}: delta 类型与内容块类型不符", name);
+                }
+                "content_block_stop" => {
+                    let (index, kind) = open.take().unwrap_or_else(|| panic!("{}: 多余的 content_block_stop", name));
+                    assert_eq!(data["index"], index, "{}: stop 索引与当前块不一致", name);
+                    if kind == "tool_use" {
+                        let input: Value = serde_json::from_str(&tool_json)
+                            .unwrap_or_else(|e| panic!("{}: 工具参数不是合法 JSON ({}): {:?}", name, e, tool_json));
+                        assert!(input.is_object(), "{}: 工具参数必须是 JSON 对象", name);
+                    }
+                    next_index += 1;
+                }
+                "message_delta" => {
+                    assert!(open.is_none(), "{}: message_delta 之前内容块未结束", name);
+                    assert!(data["usage"]["output_tokens"].is_u64(), "{}: message_delta 缺少 usage", name);
+                    if used_tool {
+                        assert_eq!(data["delta"]["stop_reason"], "tool_use", "{}: 使用工具时 stop_reason 必须为 tool_use", name);
+                    }
+                    message_deltas += 1;
+                }
+                "message_start" | "message_stop" => {}
+                other => panic!("{}: 未知事件 {}", name, other),
+            }
+        }
+        assert_eq!(message_deltas, 1, "{}: 必须恰好有一个 message_delta", name);
+    }
+
+    /// OpenAI 结构校验：[DONE] 只出现一次且在最后、role 只在首个 delta、工具调用首个分片带 id/type/name
+    /// 且 index 连续、参数拼接后为 JSON 对象、每个 choice 恰好一个 finish_reason
+    fn check_openai(name: &str, events: &[(String, String)]) {
+        assert_eq!(events.iter().filter(|e| e.1 == "[DONE]").count(), 1, "{}: [DONE] 必须恰好出现一次", name);
+        assert_eq!(events.last().map(|e| e.1.as_str()), Some("[DONE]"), "{}: [DONE] 必须是最后一个事件", name);
+
+        // choice index -> (已输出 delta, 各工具调用拼接的参数, 已结束)
+        let mut choices: BTreeMap<u64, (bool, Vec<String>, bool)> = BTreeMap::new();
+        for (_, data) in &events[..events.len() - 1] {
+            let v = parse_json(name, data);
+            assert_eq!(v["object"], "chat.completion.chunk", "{}: object 字段错误", name);
+            for choice in v["choices"].as_array().into_iter().flatten() {
+                let idx = choice["index"].as_u64().unwrap_or_else(|| panic!("{}: choice 缺少 index", name));
+                let (seen, calls, finished) = choices.entry(idx).or_default();
+                assert!(!*finished, "{}: finish_reason 之后 choice {} 仍有数据", name, idx);
+                assert_eq!(choice["delta"].get("role").is_some(), !*seen, "{}: role 只能出现在首个 delta", name);
+                *seen = true;
+
+                for call in choice["delta"]["tool_calls"].as_array().into_iter().flatten() {
+                    let i = call["index"].as_u64().unwrap_or_else(|| panic!("{}: tool_call 缺少 index", name)) as usize;
+                    if i == calls.len() {
+                        assert!(call["id"].is_string(), "{}: 工具调用首个分片缺少 id", name);
+                        assert_eq!(call["type"], "function", "{}: 工具调用首个分片缺少 type", name);
+                        assert!(call["function"]["name"].is_string(), "{}: 工具调用首个分片缺少 name", name);
+                        calls.push(String::new());
+                    }
+                    assert!(i < calls.len(), "{}: tool_calls index 不连续", name);
+                    calls[i].push_str(call["function"]["arguments"].as_str().unwrap_or_default());
+                }
+
+                if let Some(reason) = choice["finish_reason"].as_str() {
+                    if !calls.is_empty() {
+                        assert_eq!(reason, "tool_calls", "{}: 使用工具时 finish_reason 必须为 tool_calls", name);
+                    }
+                    *finished = true;
+                }
+            }
+        }
+
+        for (idx, (_, calls, finished)) in &choices {
+            assert!(*finished, "{}: choice {} 没有 finish_reason", name, idx);
+            for args in calls {
+                let parsed: Value = serde_json::from_str(args)
+                    .unwrap_or_else(|e| panic!("{}: 工具参数不是合法 JSON ({}): {:?}", name, e, args));
+                assert!(parsed.is_object(), "{}: 工具参数必须是 JSON 对象", name);
+            }
+        }
+    }
+
+    fn fixtures() -> Vec<PathBuf> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(FIXTURE_DIR);
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(&dir)
+            .unwrap_or_else(|e| panic!("读取 {} 失败: {}", dir.display(), e))
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "sse"))
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_load_fixture_formats() {
+        let text = "# 注释\n\
+                    data: {\"a\":1}\n\
+                    \n\
+                    {\"b\":2}\n\
+                    2026-01-01T00:00:00Z DEBUG Gemini SSE Chunk: {\"c\":3}\n\
+                    data: [DONE]\n";
+        assert_eq!(load_fixture(text), vec![r#"{"a":1}"#, r#"{"b":2}"#, r#"{"c":3}"#, "[DONE]"]);
+    }
+
+    #[tokio::test]
+    async fn test_stream_conformance() {
+        let paths = fixtures();
+        assert!(!paths.is_empty(), "没有找到 fixture");
+
+        for path in paths {
+            let name = path.file_stem().unwrap().to_string_lossy().to_string();
+            let payloads = load_fixture(&std::fs::read_to_string(&path).unwrap());
+
+            let openai = parse_sse(
+                &collect(create_openai_sse_stream(upstream(&payloads), "gemini-test".to_string(), false)).await,
+            );
+            check_openai(&name, &openai);
+            check_golden(&path.with_extension("openai.golden"), &normalize_openai(&name, &openai));
+
+            let claude = parse_sse(
+                &collect(create_claude_sse_stream(
+                    upstream(&payloads),
+                    "conformance".to_string(),
+                    "test@example.com".to_string(),
+                    None,
+                    false,
+                    1_048_576,
+                    false,
+                ))
+                .await,
+            );
+            check_claude(&name, &claude);
+            check_golden(&path.with_extension("claude.golden"), &normalize_claude(&name, &claude));
+        }
+    }
+}
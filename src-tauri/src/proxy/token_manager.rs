@@ -4,9 +4,44 @@ use std::collections::HashSet;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use tracing::Instrument;
 
+use crate::proxy::config::AccountGroup;
+use crate::proxy::monitor::{current_phases, record_auth_ms, record_select_ms, ErrorClass};
 use crate::proxy::rate_limit::RateLimitTracker;
 use crate::proxy::sticky_config::StickySessionConfig;
+use crate::modules::account::CorruptedAccount;
+use crate::utils::tasks::TaskSupervisor;
+
+tokio::task_local! {
+    /// 当前请求固定使用的账号 (email)，设置时 get_token 跳过调度直接使用该账号
+    pub static PINNED_ACCOUNT: String;
+    /// 当前请求的密钥绑定的账号分组，设置时只调度分组内的账号
+    pub static ACCOUNT_GROUP: AccountGroup;
+}
+
+/// 当前请求固定使用的账号
+pub fn pinned_account() -> Option<String> {
+    PINNED_ACCOUNT.try_with(|email| email.clone()).ok()
+}
+
+/// 当前请求限定的账号分组
+pub fn account_group() -> Option<AccountGroup> {
+    ACCOUNT_GROUP.try_with(|group| group.clone()).ok()
+}
+
+/// 只保留属于分组的账号；分组内没有可用账号时直接报错
+fn retain_group(tokens: &mut Vec<ProxyToken>, group: &AccountGroup) -> Result<(), String> {
+    tokens.retain(|t| t.in_group(group));
+    if tokens.is_empty() {
+        return Err(format!(
+            "No available accounts in account group '{}' (tags: {})",
+            group.name,
+            group.tags.join(", ")
+        ));
+    }
+    Ok(())
+}
 
 #[derive(Debug, Clone)]
 pub struct ProxyToken {
@@ -21,8 +56,59 @@ pub struct ProxyToken {
     pub subscription_tier: Option<String>, // "FREE" | "PRO" | "ULTRA"
     pub remaining_quota: Option<i32>, // [FIX #563] Remaining quota for priority sorting
     pub protected_models: HashSet<String>, // [NEW #621]
+    pub forbidden: bool, // 上游返回 403 (quota.is_forbidden)
+    pub quota: Option<crate::models::QuotaData>, // 各模型剩余配额，用于按请求模型排序
+    pub upstream_endpoint: Option<String>, // 账号指定的上游端点 (规范化后的基础地址)
+    pub tags: Vec<String>, // 账号标签，用于分组调度
 }
 
+impl ProxyToken {
+    /// 账号带有分组的任一标签
+    fn in_group(&self, group: &AccountGroup) -> bool {
+        self.tags.iter().any(|tag| group.tags.iter().any(|t| t.trim() == tag))
+    }
+
+    /// 请求模型对应的剩余配额百分比；没有该模型 (族) 的数据时退回最大剩余百分比
+    fn quota_for(&self, model: &str) -> i32 {
+        self.quota
+            .as_ref()
+            .and_then(|q| q.percentage_for(model))
+            .or(self.remaining_quota)
+            .unwrap_or(0)
+    }
+}
+
+
+/// 按调度优先级排序账号
+fn sort_by_priority(tokens: &mut [ProxyToken], target_model: &str) {
+    // ===== 【优化】根据订阅等级和剩余配额排序 =====
+    // [FIX #563] 优先级: ULTRA > PRO > FREE, 同tier内优先高配额账号
+    // 理由: ULTRA/PRO 重置快，优先消耗；FREE 重置慢，用于兜底
+    //       高配額账号优先使用，避免低配额账号被用光
+    tokens.sort_by(|a, b| {
+        let tier_priority = |tier: &Option<String>| match tier.as_deref() {
+            Some("ULTRA") => 0,
+            Some("PRO") => 1,
+            Some("FREE") => 2,
+            _ => 3,
+        };
+
+        // First: compare by subscription tier
+        let tier_cmp = tier_priority(&a.subscription_tier)
+            .cmp(&tier_priority(&b.subscription_tier));
+
+        if tier_cmp != std::cmp::Ordering::Equal {
+            return tier_cmp;
+        }
+
+        // [FIX #563] Second: compare by remaining quota percentage (higher is better)
+        // 有请求模型 (或同族模型) 的配额数据时按该模型比较，例如图片请求优先有图片配额的账号
+        // Accounts with unknown/zero percentage go last within their tier
+        let quota_a = a.quota_for(target_model);
+        let quota_b = b.quota_for(target_model);
+        quota_b.cmp(&quota_a)  // Descending: higher percentage first
+    });
+}
 
 pub struct TokenManager {
     tokens: Arc<DashMap<String, ProxyToken>>,  // account_id -> ProxyToken
@@ -32,8 +118,64 @@ pub struct TokenManager {
     rate_limit_tracker: Arc<RateLimitTracker>,  // 新增: 限流跟踪器
     sticky_config: Arc<tokio::sync::RwLock<StickySessionConfig>>, // 新增：调度配置
     session_accounts: Arc<DashMap<String, String>>, // 新增：会话与账号映射 (SessionID -> AccountID)
+    pending_usage: Arc<DashMap<String, AccountUsage>>, // 尚未落盘的使用统计 (AccountID -> 增量)
+    selections: Arc<DashMap<String, AccountUsage>>, // 进程启动以来的选中次数 (AccountID -> 累计)，仅用于调试
+    skipped_accounts: Arc<AtomicUsize>, // 上次加载时因禁用 / 反代禁用 / 配额保护 / 地区限制而跳过的账号数
+    corrupted_accounts: Arc<std::sync::Mutex<Vec<CorruptedAccount>>>, // 上次加载时无法读取或解析的账号文件
+}
+
+/// 不可用账号按原因的计数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub struct ExcludedAccounts {
+    /// 已禁用、反代禁用或触发配额保护，未进入调度池
+    pub proxy_disabled: usize,
+    /// 处于限流冷却中
+    pub cooldown: usize,
+    /// 上游返回 403
+    pub forbidden: usize,
+    /// 账号文件无法读取或解析
+    #[serde(default)]
+    pub corrupted: usize,
+}
+
+/// 账号总数与可用数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub struct AccountAvailability {
+    pub total_accounts: usize,
+    /// 可参与调度的账号 (未禁用、未冷却、未被 403)
+    pub usable_accounts: usize,
+    pub excluded: ExcludedAccounts,
+}
+
+/// 请求头指定的固定账号无法使用
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PinnedAccountError {
+    NotFound(String),
+    /// 账号存在但不可用，附带全部原因
+    Unusable { email: String, reasons: Vec<String> },
+}
+
+impl std::fmt::Display for PinnedAccountError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound(account) => write!(f, "Pinned account not found: {}", account),
+            Self::Unusable { email, reasons } => {
+                write!(f, "Pinned account {} is not usable: {}", email, reasons.join("; "))
+            }
+        }
+    }
+}
+
+/// 账号使用统计增量，定期合并写入账号文件
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AccountUsage {
+    pub requests: u64,
+    pub last_used_at: i64,
 }
 
+/// 使用统计落盘间隔
+const USAGE_FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
 impl TokenManager {
     /// 创建新的 TokenManager
     pub fn new(data_dir: PathBuf) -> Self {
@@ -45,8 +187,99 @@ impl TokenManager {
             rate_limit_tracker: Arc::new(RateLimitTracker::new()),
             sticky_config: Arc::new(tokio::sync::RwLock::new(StickySessionConfig::default())),
             session_accounts: Arc::new(DashMap::new()),
+            pending_usage: Arc::new(DashMap::new()),
+            selections: Arc::new(DashMap::new()),
+            skipped_accounts: Arc::new(AtomicUsize::new(0)),
+            corrupted_accounts: Arc::new(std::sync::Mutex::new(Vec::new())),
         }
     }
+
+    /// 注册定期落盘使用统计的后台任务，TokenManager 被释放或任务被停止后退出
+    pub fn register_usage_flusher(self: &Arc<Self>, tasks: &TaskSupervisor) {
+        let weak = Arc::downgrade(self);
+        tasks.register("usage-flusher", move |ctx| {
+            let manager = weak.clone();
+            async move {
+                let mut interval = tokio::time::interval(USAGE_FLUSH_INTERVAL);
+                interval.tick().await;
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => {}
+                        _ = ctx.cancelled() => break,
+                    }
+                    let Some(manager) = manager.upgrade() else {
+                        break;
+                    };
+                    manager.flush_usage();
+                    ctx.record_run(Ok(()));
+                }
+            }
+        });
+    }
+
+    /// 记录一次账号使用
+    fn record_usage(&self, account_id: &str) {
+        let now = chrono::Utc::now().timestamp();
+        for map in [&self.pending_usage, &self.selections] {
+            let mut usage = map.entry(account_id.to_string()).or_default();
+            usage.requests += 1;
+            usage.last_used_at = now;
+        }
+    }
+
+    /// 记录一次不经调度的使用 (如账号测试请求)，只计入使用统计
+    pub fn record_external_usage(&self, account_id: &str) {
+        let mut usage = self.pending_usage.entry(account_id.to_string()).or_default();
+        usage.requests += 1;
+        usage.last_used_at = chrono::Utc::now().timestamp();
+    }
+
+    /// 尚未落盘的使用统计
+    pub fn pending_usage(&self, account_id: &str) -> Option<AccountUsage> {
+        self.pending_usage.get(account_id).map(|u| *u)
+    }
+
+    /// 将累计的使用统计写入账号文件，返回写入的账号数
+    pub fn flush_usage(&self) -> usize {
+        let account_ids: Vec<String> = self.pending_usage.iter().map(|e| e.key().clone()).collect();
+        let mut flushed = 0;
+
+        for account_id in account_ids {
+            let Some((_, usage)) = self.pending_usage.remove(&account_id) else {
+                continue;
+            };
+            let path = self.data_dir.join("accounts").join(format!("{}.json", account_id));
+            match Self::write_usage(&path, usage) {
+                Ok(()) => flushed += 1,
+                Err(e) => {
+                    tracing::warn!("保存账号 {} 的使用统计失败: {}", account_id, e);
+                    // 放回，下次重试
+                    let mut pending = self.pending_usage.entry(account_id).or_default();
+                    pending.requests += usage.requests;
+                    pending.last_used_at = pending.last_used_at.max(usage.last_used_at);
+                }
+            }
+        }
+
+        if flushed > 0 {
+            tracing::debug!("已保存 {} 个账号的使用统计", flushed);
+        }
+        flushed
+    }
+
+    fn write_usage(path: &std::path::Path, usage: AccountUsage) -> Result<(), String> {
+        let mut content: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(path).map_err(|e| format!("读取文件失败: {}", e))?,
+        )
+        .map_err(|e| format!("解析 JSON 失败: {}", e))?;
+
+        let total = content["total_requests"].as_u64().unwrap_or(0) + usage.requests;
+        content["total_requests"] = serde_json::Value::Number(total.into());
+        content["last_used_at"] = serde_json::Value::Number(usage.last_used_at.into());
+
+        crate::modules::accounts_watcher::write(path, serde_json::to_string_pretty(&content).unwrap())
+            .map_err(|e| format!("写入文件失败: {}", e))
+    }
     
     /// 从主应用账号目录加载所有账号
     pub async fn load_accounts(&self) -> Result<usize, String> {
@@ -68,6 +301,8 @@ impl TokenManager {
             .map_err(|e| format!("读取账号目录失败: {}", e))?;
         
         let mut count = 0;
+        let mut skipped = 0;
+        let mut corrupted = Vec::new();
         
         for entry in entries {
             let entry = entry.map_err(|e| format!("读取目录项失败: {}", e))?;
@@ -85,13 +320,19 @@ impl TokenManager {
                     count += 1;
                 },
                 Ok(None) => {
-                    // 跳过无效账号
+                    // 已禁用 / 反代禁用 / 配额保护 / 地区限制
+                    skipped += 1;
                 },
                 Err(e) => {
-                    tracing::debug!("加载账号失败 {:?}: {}", path, e);
+                    // 单个文件损坏时跳过，不影响其余账号
+                    let file = entry.file_name().to_string_lossy().to_string();
+                    tracing::warn!("账号文件 {} 无法加载，已跳过: {}", file, e);
+                    corrupted.push(CorruptedAccount { file, error: e });
                 }
             }
         }
+        self.skipped_accounts.store(skipped, Ordering::Relaxed);
+        *self.corrupted_accounts.lock().unwrap_or_else(|e| e.into_inner()) = corrupted;
         
         Ok(count)
     }
@@ -120,11 +361,8 @@ impl TokenManager {
     
     /// 加载单个账号
     async fn load_single_account(&self, path: &PathBuf) -> Result<Option<ProxyToken>, String> {
-        let content = std::fs::read_to_string(path)
-            .map_err(|e| format!("读取文件失败: {}", e))?;
-        
-        let mut account: serde_json::Value = serde_json::from_str(&content)
-            .map_err(|e| format!("解析 JSON 失败: {}", e))?;
+        // 默认数据目录下的账号经由内存缓存读取，避免每次重载都重新解析全部文件
+        let mut account = crate::modules::account::load_account_json(path)?;
 
         if account
             .get("disabled")
@@ -164,6 +402,20 @@ impl TokenManager {
             return Ok(None);
         }
 
+        // 地区限制的账号需手动清除标记后才重新参与调度
+        if account
+            .get("region_blocked")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+        {
+            tracing::debug!(
+                "Skipping region-blocked account file: {:?} (email={})",
+                path,
+                account.get("email").and_then(|v| v.as_str()).unwrap_or("<unknown>")
+            );
+            return Ok(None);
+        }
+
         let account_id = account["id"].as_str()
             .ok_or("缺少 id 字段")?
             .to_string();
@@ -217,6 +469,33 @@ impl TokenManager {
             })
             .unwrap_or_default();
         
+        let forbidden = account
+            .get("quota")
+            .and_then(|q| q.get("is_forbidden"))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        let quota = account
+            .get("quota")
+            .and_then(|q| serde_json::from_value::<crate::models::QuotaData>(q.clone()).ok());
+
+        let upstream_endpoint = account
+            .get("upstream_endpoint")
+            .and_then(|v| v.as_str())
+            .and_then(|e| match crate::proxy::config::normalize_endpoint(e) {
+                Ok(endpoint) => Some(endpoint),
+                Err(err) => {
+                    tracing::warn!("账号 {} 的上游端点无效，已忽略: {}", email, err);
+                    None
+                }
+            });
+
+        let tags: Vec<String> = account
+            .get("tags")
+            .and_then(|v| v.as_array())
+            .map(|arr| arr.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect())
+            .unwrap_or_default();
+
         Ok(Some(ProxyToken {
             account_id,
             access_token,
@@ -229,6 +508,10 @@ impl TokenManager {
             subscription_tier,
             remaining_quota,
             protected_models,
+            forbidden,
+            quota,
+            upstream_endpoint,
+            tags,
         }))
     }
 
@@ -374,7 +657,7 @@ impl TokenManager {
             );
             
             // 3. 写入磁盘
-            std::fs::write(account_path, serde_json::to_string_pretty(account_json).unwrap())
+            crate::modules::accounts_watcher::write(account_path, serde_json::to_string_pretty(account_json).unwrap())
                 .map_err(|e| format!("写入文件失败: {}", e))?;
             
             return Ok(true);
@@ -419,7 +702,7 @@ impl TokenManager {
         
         account_json["protected_models"] = serde_json::Value::Array(protected_list);
         
-        let _ = std::fs::write(account_path, serde_json::to_string_pretty(account_json).unwrap());
+        let _ = crate::modules::accounts_watcher::write(account_path, serde_json::to_string_pretty(account_json).unwrap());
         
         false // 返回 false 表示现在已可以尝试加载该账号（模型级过滤会在 get_token 时发生）
     }
@@ -439,7 +722,7 @@ impl TokenManager {
             
             if arr.len() < original_len {
                 tracing::info!("账号 {} 的模型 {} 配额已恢复，移出保护列表", account_id, model_name);
-                std::fs::write(account_path, serde_json::to_string_pretty(account_json).unwrap())
+                crate::modules::accounts_watcher::write(account_path, serde_json::to_string_pretty(account_json).unwrap())
                     .map_err(|e| format!("写入文件失败: {}", e))?;
                 return Ok(true);
             }
@@ -461,12 +744,66 @@ impl TokenManager {
         session_id: Option<&str>,
         target_model: &str,
     ) -> Result<(String, String, String), String> {
+        let span = tracing::debug_span!(
+            "select_account",
+            quota_group,
+            model = target_model,
+            force_rotate,
+            elapsed_ms = tracing::field::Empty
+        );
+        let start = std::time::Instant::now();
+        let auth_before = current_phases().auth_ms.unwrap_or(0);
+
+        if let Some(email) = pinned_account() {
+            let result = self.get_token_by_email(&email).instrument(span.clone()).await;
+            let auth = current_phases().auth_ms.unwrap_or(0).saturating_sub(auth_before);
+            record_select_ms((start.elapsed().as_millis() as u64).saturating_sub(auth));
+            return result;
+        }
+
         // 【优化 Issue #284】添加 5 秒超时，防止死锁
         let timeout_duration = std::time::Duration::from_secs(5);
-        match tokio::time::timeout(timeout_duration, self.get_token_internal(quota_group, force_rotate, session_id, target_model)).await {
+        let result = match tokio::time::timeout(timeout_duration, self.get_token_internal(quota_group, force_rotate, session_id, target_model))
+            .instrument(span.clone())
+            .await
+        {
             Ok(result) => result,
             Err(_) => Err("Token acquisition timeout (5s) - system too busy or deadlock detected".to_string()),
+        };
+
+        // 选择耗时不含期间的 token 刷新 (单独计入 auth_ms)
+        let elapsed = start.elapsed().as_millis() as u64;
+        let auth = current_phases().auth_ms.unwrap_or(0).saturating_sub(auth_before);
+        record_select_ms(elapsed.saturating_sub(auth));
+        span.record("elapsed_ms", elapsed);
+        if let Ok((_, _, email)) = &result {
+            crate::proxy::upstream::client::set_account_endpoint(self.account_endpoint(email));
         }
+        result
+    }
+
+    /// 账号设置的上游端点
+    fn account_endpoint(&self, email: &str) -> Option<String> {
+        self.tokens
+            .iter()
+            .find(|entry| entry.value().email == *email)
+            .and_then(|entry| entry.value().upstream_endpoint.clone())
+    }
+
+    /// 刷新 access token，记录 refresh_token span 与耗时
+    async fn refresh_with_timing(
+        refresh_token: &str,
+        email: &str,
+    ) -> Result<crate::modules::oauth::TokenResponse, String> {
+        let span = tracing::debug_span!("refresh_token", email, elapsed_ms = tracing::field::Empty);
+        let start = std::time::Instant::now();
+        let result = crate::modules::oauth::refresh_access_token(refresh_token)
+            .instrument(span.clone())
+            .await;
+        let elapsed = start.elapsed().as_millis() as u64;
+        record_auth_ms(elapsed);
+        span.record("elapsed_ms", elapsed);
+        result
     }
 
     /// 内部实现：获取 Token 的核心逻辑
@@ -478,37 +815,15 @@ impl TokenManager {
         target_model: &str,
     ) -> Result<(String, String, String), String> {
         let mut tokens_snapshot: Vec<ProxyToken> = self.tokens.iter().map(|e| e.value().clone()).collect();
-        let total = tokens_snapshot.len();
-        if total == 0 {
+        if tokens_snapshot.is_empty() {
             return Err("Token pool is empty".to_string());
         }
+        if let Some(group) = account_group() {
+            retain_group(&mut tokens_snapshot, &group)?;
+        }
+        let total = tokens_snapshot.len();
 
-        // ===== 【优化】根据订阅等级和剩余配额排序 =====
-        // [FIX #563] 优先级: ULTRA > PRO > FREE, 同tier内优先高配额账号
-        // 理由: ULTRA/PRO 重置快，优先消耗；FREE 重置慢，用于兜底
-        //       高配額账号优先使用，避免低配额账号被用光
-        tokens_snapshot.sort_by(|a, b| {
-            let tier_priority = |tier: &Option<String>| match tier.as_deref() {
-                Some("ULTRA") => 0,
-                Some("PRO") => 1,
-                Some("FREE") => 2,
-                _ => 3,
-            };
-            
-            // First: compare by subscription tier
-            let tier_cmp = tier_priority(&a.subscription_tier)
-                .cmp(&tier_priority(&b.subscription_tier));
-            
-            if tier_cmp != std::cmp::Ordering::Equal {
-                return tier_cmp;
-            }
-            
-            // [FIX #563] Second: compare by remaining quota percentage (higher is better)
-            // Accounts with unknown/zero percentage go last within their tier
-            let quota_a = a.remaining_quota.unwrap_or(0);
-            let quota_b = b.remaining_quota.unwrap_or(0);
-            quota_b.cmp(&quota_a)  // Descending: higher percentage first
-        });
+        sort_by_priority(&mut tokens_snapshot, target_model);
 
 
         // 0. 读取当前调度配置
@@ -726,7 +1041,7 @@ impl TokenManager {
                 tracing::debug!("账号 {} 的 token 即将过期，正在刷新...", token.email);
 
                 // 调用 OAuth 刷新 token
-                match crate::modules::oauth::refresh_access_token(&token.refresh_token).await {
+                match Self::refresh_with_timing(&token.refresh_token, &token.email).await {
                     Ok(token_response) => {
                         tracing::debug!("Token 刷新成功！");
 
@@ -747,6 +1062,11 @@ impl TokenManager {
                             tracing::debug!("保存刷新后的 token 失败 ({}): {}", token.email, e);
                         }
                     }
+                    // 刷新熔断中：其他账号同样会快速失败，也不能据此禁用账号
+                    Err(e) if e.starts_with(crate::modules::oauth::REFRESH_THROTTLED) => {
+                        tracing::warn!("Token 刷新已熔断，跳过本次请求 ({})", token.email);
+                        return Err(e);
+                    }
                     Err(e) => {
                         tracing::error!("Token 刷新失败 ({}): {}，尝试下一个账号", token.email, e);
                         if e.contains("\"invalid_grant\"") || e.contains("invalid_grant") {
@@ -816,6 +1136,7 @@ impl TokenManager {
                 }
             }
 
+            self.record_usage(&token.account_id);
             return Ok((token.access_token, project_id, token.email));
         }
 
@@ -841,7 +1162,7 @@ impl TokenManager {
         content["disabled_at"] = serde_json::Value::Number(now.into());
         content["disabled_reason"] = serde_json::Value::String(truncate_reason(reason, 800));
 
-        std::fs::write(&path, serde_json::to_string_pretty(&content).unwrap())
+        crate::modules::accounts_watcher::write(&path, serde_json::to_string_pretty(&content).unwrap())
             .map_err(|e| format!("写入文件失败: {}", e))?;
         
         // 【修复 Issue #3】从内存中移除禁用的账号，防止被60s锁定逻辑继续使用
@@ -864,7 +1185,7 @@ impl TokenManager {
         
         content["token"]["project_id"] = serde_json::Value::String(project_id.to_string());
         
-        std::fs::write(path, serde_json::to_string_pretty(&content).unwrap())
+        crate::modules::accounts_watcher::write(path, serde_json::to_string_pretty(&content).unwrap())
             .map_err(|e| format!("写入文件失败: {}", e))?;
         
         tracing::debug!("已保存 project_id 到账号 {}", account_id);
@@ -888,7 +1209,7 @@ impl TokenManager {
         content["token"]["expires_in"] = serde_json::Value::Number(token_response.expires_in.into());
         content["token"]["expiry_timestamp"] = serde_json::Value::Number((now + token_response.expires_in).into());
         
-        std::fs::write(path, serde_json::to_string_pretty(&content).unwrap())
+        crate::modules::accounts_watcher::write(path, serde_json::to_string_pretty(&content).unwrap())
             .map_err(|e| format!("写入文件失败: {}", e))?;
         
         tracing::debug!("已保存刷新后的 token 到账号 {}", account_id);
@@ -899,6 +1220,168 @@ impl TokenManager {
         self.tokens.len()
     }
 
+    /// 账号总数、可用数与不可用原因 (403 优先于冷却计数，各原因互斥)
+    pub fn availability(&self) -> AccountAvailability {
+        let mut excluded = ExcludedAccounts {
+            proxy_disabled: self.skipped_accounts.load(Ordering::Relaxed),
+            corrupted: self.corrupted_accounts.lock().unwrap_or_else(|e| e.into_inner()).len(),
+            ..Default::default()
+        };
+        let mut usable = 0;
+        let pooled: Vec<(String, bool)> = self
+            .tokens
+            .iter()
+            .map(|e| (e.account_id.clone(), e.forbidden))
+            .collect();
+        for (account_id, forbidden) in &pooled {
+            if *forbidden {
+                excluded.forbidden += 1;
+            } else if self.rate_limit_tracker.is_rate_limited(account_id) {
+                excluded.cooldown += 1;
+            } else {
+                usable += 1;
+            }
+        }
+        AccountAvailability {
+            total_accounts: pooled.len() + excluded.proxy_disabled + excluded.corrupted,
+            usable_accounts: usable,
+            excluded,
+        }
+    }
+
+    /// 按调度顺序排在最前的可用账号 (账号 ID, email)，用于启动预热
+    pub fn warmup_candidates(&self, limit: usize) -> Vec<(String, String)> {
+        let mut tokens: Vec<ProxyToken> = self
+            .tokens
+            .iter()
+            .filter(|entry| !entry.value().forbidden && !self.is_rate_limited_by_account_id(entry.key()))
+            .map(|entry| entry.value().clone())
+            .collect();
+        sort_by_priority(&mut tokens, "");
+        tokens
+            .into_iter()
+            .take(limit)
+            .map(|token| (token.account_id, token.email))
+            .collect()
+    }
+
+    /// 预热单个账号：Token 即将过期时刷新并补齐 project_id，返回是否刷新了 Token；
+    /// 刷新遇到 invalid_grant 时与调度中一样禁用账号
+    pub async fn warm_up_account(&self, account_id: &str) -> Result<bool, String> {
+        let token = self
+            .tokens
+            .get(account_id)
+            .map(|entry| entry.value().clone())
+            .ok_or_else(|| format!("未找到账号: {}", account_id))?;
+
+        let now = chrono::Utc::now().timestamp();
+        let mut access_token = token.access_token.clone();
+        let refreshed = now >= token.timestamp - 300;
+        if refreshed {
+            match Self::refresh_with_timing(&token.refresh_token, &token.email).await {
+                Ok(token_response) => {
+                    access_token = token_response.access_token.clone();
+                    if let Some(mut entry) = self.tokens.get_mut(account_id) {
+                        entry.access_token = token_response.access_token.clone();
+                        entry.expires_in = token_response.expires_in;
+                        entry.timestamp = now + token_response.expires_in;
+                    }
+                    if let Err(e) = self.save_refreshed_token(account_id, &token_response).await {
+                        tracing::debug!("保存刷新后的 token 失败 ({}): {}", token.email, e);
+                    }
+                }
+                Err(e) => {
+                    if e.contains("invalid_grant") {
+                        tracing::error!("预热时 Token 已失效，禁用账号 ({}): {}", token.email, e);
+                        let _ = self.disable_account(account_id, &format!("invalid_grant: {}", e)).await;
+                        self.tokens.remove(account_id);
+                    }
+                    return Err(format!("Token refresh failed: {}", e));
+                }
+            }
+        }
+
+        if token.project_id.is_none() {
+            let pid = crate::proxy::project_resolver::fetch_project_id(&access_token).await?;
+            if let Some(mut entry) = self.tokens.get_mut(account_id) {
+                entry.project_id = Some(pid.clone());
+            }
+            let _ = self.save_project_id(account_id, &pid).await;
+        }
+        Ok(refreshed)
+    }
+
+    /// 解析请求头固定的账号 (email 或账号 ID)，返回可用账号的 email；
+    /// 不在调度池中的账号从账号文件读取禁用原因
+    pub fn resolve_pinned_account(&self, account: &str) -> Result<String, PinnedAccountError> {
+        let account = account.trim();
+        let pooled = self
+            .tokens
+            .iter()
+            .find(|entry| entry.value().email.eq_ignore_ascii_case(account) || entry.value().account_id == account)
+            .map(|entry| (entry.value().account_id.clone(), entry.value().email.clone(), entry.value().forbidden));
+
+        if let Some((account_id, email, forbidden)) = pooled {
+            let mut reasons = Vec::new();
+            if forbidden {
+                reasons.push("forbidden (upstream returned 403)".to_string());
+            }
+            if let Some(secs) = self.rate_limit_tracker.get_reset_seconds(&account_id) {
+                let reason = self
+                    .rate_limit_tracker
+                    .get(&account_id)
+                    .map(|limit| format!(", {:?}", limit.reason))
+                    .unwrap_or_default();
+                reasons.push(format!("cooldown ({}s remaining{})", secs, reason));
+            }
+            return if reasons.is_empty() {
+                Ok(email)
+            } else {
+                Err(PinnedAccountError::Unusable { email, reasons })
+            };
+        }
+
+        let file = self.find_account_file(account).ok_or_else(|| PinnedAccountError::NotFound(account.to_string()))?;
+        let flag = |key: &str| file.get(key).and_then(|v| v.as_bool()).unwrap_or(false);
+        let mut reasons = Vec::new();
+        if flag("disabled") {
+            reasons.push("disabled".to_string());
+        }
+        if flag("proxy_disabled") {
+            match file.get("proxy_disabled_reason").and_then(|v| v.as_str()) {
+                Some(reason) => reasons.push(format!("proxy disabled ({})", reason)),
+                None => reasons.push("proxy disabled".to_string()),
+            }
+        }
+        if flag("region_blocked") {
+            reasons.push("region blocked".to_string());
+        }
+        if reasons.is_empty() {
+            reasons.push("not loaded into the account pool (reload accounts)".to_string());
+        }
+        let email = file.get("email").and_then(|v| v.as_str()).unwrap_or(account).to_string();
+        Err(PinnedAccountError::Unusable { email, reasons })
+    }
+
+    /// 按账号 ID 或 email 查找账号文件
+    fn find_account_file(&self, account: &str) -> Option<serde_json::Value> {
+        let accounts_dir = self.data_dir.join("accounts");
+        let by_id = accounts_dir.join(format!("{}.json", account));
+        if !account.contains(['/', '\\']) && by_id.is_file() {
+            return crate::modules::account::load_account_json(&by_id).ok();
+        }
+        std::fs::read_dir(&accounts_dir)
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("json"))
+            .filter_map(|path| crate::modules::account::load_account_json(&path).ok())
+            .find(|json| {
+                json.get("email")
+                    .and_then(|v| v.as_str())
+                    .is_some_and(|email| email.eq_ignore_ascii_case(account))
+            })
+    }
+
     /// 通过 email 获取指定账号的 Token（用于预热等需要指定账号的场景）
     /// 此方法会自动刷新过期的 token
     pub async fn get_token_by_email(&self, email: &str) -> Result<(String, String, String), String> {
@@ -935,6 +1418,7 @@ impl TokenManager {
             Some(info) => info,
             None => return Err(format!("未找到账号: {}", email)),
         };
+        crate::proxy::upstream::client::set_account_endpoint(self.account_endpoint(email));
 
         let project_id = project_id_opt.unwrap_or_else(|| "bamboo-precept-lgxtn".to_string());
         
@@ -946,7 +1430,7 @@ impl TokenManager {
         tracing::info!("[Warmup] Token for {} is expiring, refreshing...", email);
 
         // 调用 OAuth 刷新 token
-        match crate::modules::oauth::refresh_access_token(&refresh_token).await {
+        match Self::refresh_with_timing(&refresh_token, email).await {
             Ok(token_response) => {
                 tracing::info!("[Warmup] Token refresh successful for {}", email);
                 let new_now = chrono::Utc::now().timestamp();
@@ -980,6 +1464,7 @@ impl TokenManager {
     ) {
         // 【替代方案】转换 email -> account_id
         let key = self.email_to_account_id(email).unwrap_or_else(|| email.to_string());
+        self.notify_if_quota_exhausted(&key, status, error_body);
         self.rate_limit_tracker.parse_from_error(
             &key,
             status,
@@ -988,6 +1473,32 @@ impl TokenManager {
             None,
         );
     }
+
+    /// 上游返回地区不受支持时持久标记账号并移出调度池，返回是否已标记
+    pub fn mark_region_blocked(&self, email: &str, error_body: &str) -> bool {
+        if !ErrorClass::is_region_blocked(error_body) {
+            return false;
+        }
+        let Some(account_id) = self.email_to_account_id(email) else {
+            return false;
+        };
+        if self.tokens.remove(&account_id).is_some() {
+            self.skipped_accounts.fetch_add(1, Ordering::Relaxed);
+        }
+        tracing::warn!("账号 {} 所在地区不受支持，已停止调度", email);
+        if let Err(e) = crate::modules::account::set_region_blocked(&account_id, true) {
+            tracing::warn!("保存账号 {} 的地区限制标记失败: {}", email, e);
+        }
+        true
+    }
+
+    /// 配额耗尽的 429 交给自动切换逻辑 (仅影响桌面端当前账号)
+    fn notify_if_quota_exhausted(&self, account_id: &str, status: u16, error_body: &str) {
+        let body = error_body.to_lowercase();
+        if status == 429 && !body.contains("model_capacity") && (body.contains("exhausted") || body.contains("quota")) {
+            crate::modules::auto_switch::on_quota_error(account_id);
+        }
+    }
     
     /// 检查账号是否在限流中
     /// 参数为 email，内部会自动转换为 account_id
@@ -1042,46 +1553,34 @@ impl TokenManager {
     
     /// 从账号文件获取配额刷新时间
     /// 
-    /// 返回该账号最近的配额刷新时间字符串（ISO 8601 格式）
-    pub fn get_quota_reset_time(&self, email: &str) -> Option<String> {
-        // 尝试从账号文件读取配额信息
+    /// 返回该账号下次配额重置的 Unix 时间戳 (优先使用 quota.resets_at，旧数据按各模型 reset_time 推导)，
+    /// 已过期的时间视为未知
+    pub fn get_quota_resets_at(&self, email: &str) -> Option<i64> {
         let accounts_dir = self.data_dir.join("accounts");
-        
+        let now = chrono::Utc::now().timestamp();
+
         // 遍历账号文件查找对应的 email
-        if let Ok(entries) = std::fs::read_dir(&accounts_dir) {
-            for entry in entries.flatten() {
-                if let Ok(content) = std::fs::read_to_string(entry.path()) {
-                    if let Ok(account) = serde_json::from_str::<serde_json::Value>(&content) {
-                        // 检查 email 是否匹配
-                        if account.get("email").and_then(|e| e.as_str()) == Some(email) {
-                            // 获取 quota.models 中最早的 reset_time
-                            if let Some(models) = account
-                                .get("quota")
-                                .and_then(|q| q.get("models"))
-                                .and_then(|m| m.as_array()) 
-                            {
-                                // 找到最早的 reset_time（最保守的锁定策略）
-                                let mut earliest_reset: Option<&str> = None;
-                                for model in models {
-                                    if let Some(reset_time) = model.get("reset_time").and_then(|r| r.as_str()) {
-                                        if !reset_time.is_empty() {
-                                            if earliest_reset.is_none() || reset_time < earliest_reset.unwrap() {
-                                                earliest_reset = Some(reset_time);
-                                            }
-                                        }
-                                    }
-                                }
-                                if let Some(reset) = earliest_reset {
-                                    return Some(reset.to_string());
-                                }
-                            }
-                        }
-                    }
-                }
+        let entries = std::fs::read_dir(&accounts_dir).ok()?;
+        for entry in entries.flatten() {
+            let Ok(account) = crate::modules::account::load_account_json(&entry.path()) else {
+                continue;
+            };
+            if account.get("email").and_then(|e| e.as_str()) != Some(email) {
+                continue;
             }
+            let mut quota: crate::models::QuotaData =
+                serde_json::from_value(account.get("quota")?.clone()).ok()?;
+            quota.update_countdown(now);
+            return quota.resets_at.filter(|ts| *ts > now);
         }
         None
     }
+
+    /// 按 Unix 时间戳锁定到配额重置时刻
+    fn lock_until_reset(&self, email: &str, resets_at: i64, reason: crate::proxy::rate_limit::RateLimitReason, model: Option<String>) {
+        let until = std::time::UNIX_EPOCH + std::time::Duration::from_secs(resets_at.max(0) as u64);
+        self.rate_limit_tracker.set_lockout_until(email, until, reason, model);
+    }
     
     /// 使用配额刷新时间精确锁定账号
     /// 
@@ -1090,9 +1589,10 @@ impl TokenManager {
     /// # 参数
     /// - `model`: 可选的模型名称,用于模型级别限流
     pub fn set_precise_lockout(&self, email: &str, reason: crate::proxy::rate_limit::RateLimitReason, model: Option<String>) -> bool {
-        if let Some(reset_time_str) = self.get_quota_reset_time(email) {
-            tracing::info!("找到账号 {} 的配额刷新时间: {}", email, reset_time_str);
-            self.rate_limit_tracker.set_lockout_until_iso(email, &reset_time_str, reason, model)
+        if let Some(resets_at) = self.get_quota_resets_at(email) {
+            tracing::info!("找到账号 {} 的配额刷新时间: {}", email, resets_at);
+            self.lock_until_reset(email, resets_at, reason, model);
+            true
         } else {
             tracing::debug!("未找到账号 {} 的配额刷新时间,将使用默认退避策略", email);
             false
@@ -1138,23 +1638,15 @@ impl TokenManager {
         tracing::info!("账号 {} 正在实时刷新配额...", email);
         match crate::modules::quota::fetch_quota(&access_token, email).await {
             Ok((quota_data, _project_id)) => {
-                // 3. 从最新配额中提取 reset_time
-                let earliest_reset = quota_data.models.iter()
-                    .filter_map(|m| {
-                        if !m.reset_time.is_empty() {
-                            Some(m.reset_time.as_str())
-                        } else {
-                            None
-                        }
-                    })
-                    .min();
-                
-                if let Some(reset_time_str) = earliest_reset {
+                // 3. 使用最新配额计算出的重置时间 (已换算时区，必要时按配置的重置小时推算)
+                let now = chrono::Utc::now().timestamp();
+                if let Some(resets_at) = quota_data.resets_at.filter(|ts| *ts > now) {
                     tracing::info!(
-                        "账号 {} 实时配额刷新成功,reset_time: {}",
-                        email, reset_time_str
+                        "账号 {} 实时配额刷新成功,resets_at: {}",
+                        email, resets_at
                     );
-                    self.rate_limit_tracker.set_lockout_until_iso(email, reset_time_str, reason, model)
+                    self.lock_until_reset(email, resets_at, reason, model);
+                    true
                 } else {
                     tracing::warn!("账号 {} 配额刷新成功但未找到 reset_time", email);
                     false
@@ -1185,6 +1677,10 @@ impl TokenManager {
         error_body: &str,
         model: Option<&str>,  // 🆕 新增模型参数
     ) {
+        if let Some(id) = self.email_to_account_id(account_id) {
+            self.notify_if_quota_exhausted(&id, status, error_body);
+        }
+
         // 检查 API 是否返回了精确的重试时间
         let has_explicit_retry_time = retry_after_header.is_some() || 
             error_body.contains("quotaResetDelay");
@@ -1268,6 +1764,107 @@ impl TokenManager {
     pub fn clear_all_sessions(&self) {
         self.session_accounts.clear();
     }
+
+    /// 调度状态快照 (调试用)，token 已脱敏；`account_id` 非空时只返回该账号
+    ///
+    /// 先复制账号池再逐个计算，不在持有 DashMap 分片锁时访问其他结构
+    pub async fn debug_snapshot(&self, account_id: Option<&str>) -> TokenManagerSnapshot {
+        let tokens: Vec<ProxyToken> = match account_id {
+            Some(id) => self.tokens.get(id).map(|t| t.clone()).into_iter().collect(),
+            None => self.tokens.iter().map(|e| e.value().clone()).collect(),
+        };
+        let last_used = self
+            .last_used_account
+            .lock()
+            .await
+            .as_ref()
+            .map(|(id, at)| (id.clone(), at.elapsed().as_secs()));
+        let scheduling = self.sticky_config.read().await.clone();
+        let now = chrono::Utc::now().timestamp();
+
+        let mut accounts: Vec<TokenDebugEntry> = tokens
+            .into_iter()
+            .map(|token| {
+                let limit = self.rate_limit_tracker.get(&token.account_id);
+                let cooldown_secs = self.rate_limit_tracker.get_reset_seconds(&token.account_id);
+                let selections = self.selections.get(&token.account_id).map(|u| *u).unwrap_or_default();
+                let mut protected_models: Vec<String> = token.protected_models.into_iter().collect();
+                protected_models.sort();
+                TokenDebugEntry {
+                    locked_secs_ago: last_used
+                        .as_ref()
+                        .filter(|(id, _)| id == &token.account_id)
+                        .map(|(_, secs)| *secs),
+                    account_id: token.account_id,
+                    email: token.email,
+                    forbidden: token.forbidden,
+                    cooling_down: cooldown_secs.is_some(),
+                    cooldown_secs,
+                    cooldown_reason: limit.as_ref().filter(|_| cooldown_secs.is_some()).map(|l| format!("{:?}", l.reason)),
+                    cooldown_model: limit.filter(|_| cooldown_secs.is_some()).and_then(|l| l.model),
+                    access_token: crate::models::token::mask_secret(&token.access_token),
+                    token_expires_at: token.timestamp,
+                    token_expires_in_secs: token.timestamp - now,
+                    project_id: token.project_id,
+                    subscription_tier: token.subscription_tier,
+                    remaining_quota: token.remaining_quota,
+                    protected_models,
+                    selection_count: selections.requests,
+                    last_selected_at: (selections.last_used_at > 0).then_some(selections.last_used_at),
+                }
+            })
+            .collect();
+        accounts.sort_by(|a, b| a.email.cmp(&b.email));
+
+        TokenManagerSnapshot {
+            accounts,
+            scheduling,
+            session_bindings: self.session_accounts.len(),
+        }
+    }
+}
+
+/// 调度池中单个账号的状态 (调试用)
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub struct TokenDebugEntry {
+    pub account_id: String,
+    pub email: String,
+    /// 上游返回 403
+    pub forbidden: bool,
+    /// 处于限流冷却中 (调度池只包含已启用的账号)
+    pub cooling_down: bool,
+    /// 冷却剩余秒数
+    pub cooldown_secs: Option<u64>,
+    pub cooldown_reason: Option<String>,
+    /// 仅对该模型冷却时的模型名
+    pub cooldown_model: Option<String>,
+    /// 脱敏后的 access token
+    pub access_token: String,
+    /// 缓存 token 的过期时间 (Unix 秒)
+    pub token_expires_at: i64,
+    /// 距离过期的秒数 (负数表示已过期，下次选中时刷新)
+    pub token_expires_in_secs: i64,
+    pub project_id: Option<String>,
+    /// 调度权重：先按订阅等级 (ULTRA > PRO > FREE)，同等级按剩余配额百分比从高到低
+    pub subscription_tier: Option<String>,
+    pub remaining_quota: Option<i32>,
+    /// 触发配额保护的模型
+    pub protected_models: Vec<String>,
+    /// 进程启动以来被选中的次数
+    pub selection_count: u64,
+    /// 最近一次被选中的时间 (Unix 秒)
+    pub last_selected_at: Option<i64>,
+    /// 当前为粘性锁定账号时，距离锁定的秒数
+    pub locked_secs_ago: Option<u64>,
+}
+
+/// TokenManager 调度状态快照
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub struct TokenManagerSnapshot {
+    pub accounts: Vec<TokenDebugEntry>,
+    pub scheduling: StickySessionConfig,
+    /// 会话粘性绑定数
+    pub session_bindings: usize,
 }
 
 fn truncate_reason(reason: &str, max_len: usize) -> String {
@@ -1278,3 +1875,217 @@ fn truncate_reason(reason: &str, max_len: usize) -> String {
     s.push('…');
     s
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(account_id: &str, email: &str) -> ProxyToken {
+        ProxyToken {
+            account_id: account_id.to_string(),
+            access_token: "ya29.a0AfB_byC-access-token".to_string(),
+            refresh_token: "1//0g-refresh-token-value".to_string(),
+            expires_in: 3600,
+            timestamp: chrono::Utc::now().timestamp() + 3600,
+            email: email.to_string(),
+            account_path: PathBuf::new(),
+            project_id: None,
+            subscription_tier: Some("PRO".to_string()),
+            remaining_quota: Some(80),
+            protected_models: HashSet::new(),
+            forbidden: false,
+            quota: None,
+            upstream_endpoint: None,
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_retain_group() {
+        let group = AccountGroup {
+            name: "team-a".to_string(),
+            tags: vec!["team-a".to_string(), "shared".to_string()],
+        };
+        let mut a = token("a", "a@example.com");
+        a.tags = vec!["team-a".to_string()];
+        let mut b = token("b", "b@example.com");
+        b.tags = vec!["shared".to_string(), "team-b".to_string()];
+        let mut c = token("c", "c@example.com");
+        c.tags = vec!["team-b".to_string()];
+
+        let mut pool = vec![a, b, c.clone(), token("d", "d@example.com")];
+        retain_group(&mut pool, &group).unwrap();
+        let ids: Vec<&str> = pool.iter().map(|t| t.account_id.as_str()).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+
+        let mut empty = vec![c];
+        let err = retain_group(&mut empty, &group).unwrap_err();
+        assert!(err.contains("team-a"));
+    }
+
+    #[test]
+    fn test_quota_for_requested_model() {
+        let mut quota = crate::models::QuotaData::new();
+        quota.add_model("gemini-3-pro-high".to_string(), 90, String::new());
+        quota.add_model("gemini-3-pro-image".to_string(), 0, String::new());
+        let mut t = token("a", "a@example.com");
+        t.quota = Some(quota);
+
+        assert_eq!(t.quota_for("gemini-3-pro-image"), 0);
+        assert_eq!(t.quota_for("gemini-3-pro-high"), 90);
+        // 没有对应模型族的数据时退回 remaining_quota
+        assert_eq!(t.quota_for("claude-sonnet-4-5"), 80);
+    }
+
+    #[tokio::test]
+    async fn test_debug_snapshot() {
+        let manager = TokenManager::new(std::env::temp_dir());
+        manager.tokens.insert("a".to_string(), token("a", "a@example.com"));
+        manager.tokens.insert("b".to_string(), token("b", "b@example.com"));
+        manager.record_usage("a");
+        manager.record_usage("a");
+        manager.session_accounts.insert("session".to_string(), "a".to_string());
+
+        let snapshot = manager.debug_snapshot(None).await;
+        assert_eq!(snapshot.accounts.len(), 2);
+        assert_eq!(snapshot.session_bindings, 1);
+        let a = &snapshot.accounts[0];
+        assert_eq!(a.email, "a@example.com");
+        assert_eq!(a.selection_count, 2);
+        assert!(a.last_selected_at.is_some());
+        assert!(!a.cooling_down);
+        assert_eq!(a.access_token, "ya29.a...oken");
+        assert!(!serde_json::to_string(&snapshot).unwrap().contains("refresh"));
+
+        // 落盘后启动以来的选中次数不清零
+        manager.pending_usage.clear();
+        let single = manager.debug_snapshot(Some("a")).await;
+        assert_eq!(single.accounts.len(), 1);
+        assert_eq!(single.accounts[0].selection_count, 2);
+        assert!(manager.debug_snapshot(Some("missing")).await.accounts.is_empty());
+    }
+
+    #[test]
+    fn test_availability() {
+        let manager = TokenManager::new(std::env::temp_dir());
+        manager.tokens.insert("a".to_string(), token("a", "a@example.com"));
+        let mut forbidden = token("b", "b@example.com");
+        forbidden.forbidden = true;
+        manager.tokens.insert("b".to_string(), forbidden);
+        manager.tokens.insert("c".to_string(), token("c", "c@example.com"));
+        manager.rate_limit_tracker.parse_from_error("c", 429, Some("60"), "", None);
+        manager.skipped_accounts.store(2, Ordering::Relaxed);
+        manager.corrupted_accounts.lock().unwrap().push(CorruptedAccount {
+            file: "d.json".to_string(),
+            error: "解析 JSON 失败".to_string(),
+        });
+
+        assert_eq!(
+            manager.availability(),
+            AccountAvailability {
+                total_accounts: 6,
+                usable_accounts: 1,
+                excluded: ExcludedAccounts { proxy_disabled: 2, cooldown: 1, forbidden: 1, corrupted: 1 },
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_accounts_skips_corrupted_files() {
+        let dir = std::env::temp_dir().join(format!("ag-tm-corrupt-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("accounts")).unwrap();
+        let token = crate::models::TokenData::new("at".into(), "rt".into(), 3600, None, None, None);
+        let account = crate::models::Account::new("good".into(), "good@example.com".into(), token);
+        std::fs::write(dir.join("accounts/good.json"), serde_json::to_string(&account).unwrap()).unwrap();
+        std::fs::write(dir.join("accounts/broken.json"), "{\"id\": \"broken\", ").unwrap();
+
+        let manager = TokenManager::new(dir.clone());
+        assert_eq!(manager.load_accounts().await.unwrap(), 1);
+        let corrupted = manager.corrupted_accounts.lock().unwrap().clone();
+        assert_eq!(corrupted.len(), 1);
+        assert_eq!(corrupted[0].file, "broken.json");
+        assert_eq!(manager.availability().excluded.corrupted, 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_warmup_candidates_follow_priority() {
+        let manager = TokenManager::new(std::env::temp_dir());
+        let mut free = token("a", "a@example.com");
+        free.subscription_tier = Some("FREE".to_string());
+        manager.tokens.insert("a".to_string(), free);
+        let mut forbidden = token("b", "b@example.com");
+        forbidden.forbidden = true;
+        manager.tokens.insert("b".to_string(), forbidden);
+        manager.tokens.insert("c".to_string(), token("c", "c@example.com"));
+        manager.rate_limit_tracker.parse_from_error("c", 429, Some("60"), "", None);
+        let mut ultra = token("d", "d@example.com");
+        ultra.subscription_tier = Some("ULTRA".to_string());
+        ultra.project_id = Some("p".to_string());
+        manager.tokens.insert("d".to_string(), ultra);
+        manager.tokens.insert("e".to_string(), token("e", "e@example.com"));
+
+        let ids: Vec<String> = manager.warmup_candidates(10).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, vec!["d", "e", "a"]);
+        assert_eq!(manager.warmup_candidates(1), vec![("d".to_string(), "d@example.com".to_string())]);
+
+        // Token 未过期且已有 project_id 时无需访问上游
+        assert_eq!(manager.warm_up_account("d").await, Ok(false));
+        assert!(manager.warm_up_account("missing").await.is_err());
+    }
+
+    #[test]
+    fn test_resolve_pinned_account() {
+        let dir = std::env::temp_dir().join(format!("pinned-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("accounts")).unwrap();
+        std::fs::write(
+            dir.join("accounts").join("d.json"),
+            r#"{"id": "d", "email": "d@example.com", "disabled": true, "proxy_disabled": true, "proxy_disabled_reason": "manual"}"#,
+        )
+        .unwrap();
+
+        let manager = TokenManager::new(dir.clone());
+        manager.tokens.insert("a".to_string(), token("a", "a@example.com"));
+        let mut forbidden = token("b", "b@example.com");
+        forbidden.forbidden = true;
+        manager.tokens.insert("b".to_string(), forbidden);
+        manager.tokens.insert("c".to_string(), token("c", "c@example.com"));
+        manager.rate_limit_tracker.parse_from_error("c", 429, Some("60"), "", None);
+
+        assert_eq!(manager.resolve_pinned_account("A@example.com").unwrap(), "a@example.com");
+        assert_eq!(manager.resolve_pinned_account("a").unwrap(), "a@example.com");
+
+        let err = manager.resolve_pinned_account("b@example.com").unwrap_err();
+        assert!(err.to_string().contains("forbidden"), "{}", err);
+        let err = manager.resolve_pinned_account("c").unwrap_err();
+        assert!(err.to_string().contains("cooldown"), "{}", err);
+
+        for account in ["d", "d@example.com"] {
+            match manager.resolve_pinned_account(account).unwrap_err() {
+                PinnedAccountError::Unusable { email, reasons } => {
+                    assert_eq!(email, "d@example.com");
+                    assert_eq!(reasons, vec!["disabled", "proxy disabled (manual)"]);
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        assert_eq!(
+            manager.resolve_pinned_account("missing@example.com").unwrap_err(),
+            PinnedAccountError::NotFound("missing@example.com".to_string())
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_usage_flusher_is_supervised() {
+        let tasks = TaskSupervisor::new();
+        let manager = Arc::new(TokenManager::new(std::env::temp_dir()));
+        manager.register_usage_flusher(&tasks);
+        assert!(tasks.list().iter().any(|task| task.name == "usage-flusher" && task.running));
+
+        // 恢复备份等切换数据的操作前随其他任务一起停止
+        assert_eq!(tasks.quiesce().await, vec!["usage-flusher"]);
+        assert!(tasks.list().iter().all(|task| !task.running));
+    }
+}
@@ -0,0 +1,304 @@
+// 反代鉴权失败记录与来源 IP 临时封禁 (仅保存在内存中)
+use dashmap::DashMap;
+use serde::Serialize;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use utoipa::ToSchema;
+
+use crate::proxy::config::UnauthorizedLockout;
+
+/// 最多记录的来源地址数
+const MAX_TRACKED_IPS: usize = 10_000;
+/// 达到上限时一次淘汰的记录数，分摊全表扫描的开销
+const PRUNE_BATCH: usize = MAX_TRACKED_IPS / 10;
+
+#[derive(Debug, Clone)]
+struct AttemptRecord {
+    attempts: u64,
+    /// 上次鉴权成功或解封后的连续失败次数
+    strikes: u32,
+    first_seen: i64,
+    last_seen: i64,
+    last_path: String,
+    banned_until: Option<i64>,
+}
+
+/// 某个来源地址的鉴权失败情况
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UnauthorizedSource {
+    pub ip: String,
+    /// 累计鉴权失败次数
+    pub attempts: u64,
+    /// 连续失败次数 (鉴权成功或解封后清零)
+    pub strikes: u32,
+    /// 首次与最近一次失败时间 (毫秒时间戳)
+    pub first_seen: i64,
+    pub last_seen: i64,
+    /// 最近一次失败请求的路径
+    pub last_path: String,
+    /// 封禁到期时间 (毫秒时间戳)，未封禁时为空
+    pub banned_until: Option<i64>,
+}
+
+/// 鉴权失败统计与封禁列表
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UnauthorizedReport {
+    /// 失败次数最多的来源 (按次数降序)
+    pub top_sources: Vec<UnauthorizedSource>,
+    /// 当前封禁中的来源
+    pub banned: Vec<UnauthorizedSource>,
+    /// 记录的来源地址总数
+    pub tracked: usize,
+}
+
+/// 鉴权失败的来源记录，随反代实例存活
+pub struct UnauthorizedTracker {
+    records: DashMap<IpAddr, AttemptRecord>,
+    max_tracked: usize,
+    prune_batch: usize,
+    /// 新地址的插入串行进行，保证记录数不超过上限
+    insert_lock: Mutex<()>,
+}
+
+impl Default for UnauthorizedTracker {
+    fn default() -> Self {
+        Self::with_limits(MAX_TRACKED_IPS, PRUNE_BATCH)
+    }
+}
+
+/// 本机地址 (含 IPv4 映射的 IPv6) 从不封禁
+fn is_exempt(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(v6.is_loopback(), |v4| v4.is_loopback()),
+        IpAddr::V4(v4) => v4.is_loopback(),
+    }
+}
+
+impl UnauthorizedTracker {
+    fn with_limits(max_tracked: usize, prune_batch: usize) -> Self {
+        Self {
+            records: DashMap::new(),
+            max_tracked: max_tracked.max(1),
+            prune_batch: prune_batch.clamp(1, max_tracked.max(1)),
+            insert_lock: Mutex::new(()),
+        }
+    }
+
+    /// 地址当前是否被封禁，返回封禁到期时间
+    pub fn banned_until(&self, ip: IpAddr, now: i64) -> Option<i64> {
+        self.records
+            .get(&ip)
+            .and_then(|record| record.banned_until)
+            .filter(|until| *until > now)
+    }
+
+    /// 记录一次鉴权失败，达到阈值时封禁；返回是否因本次失败被封禁
+    pub fn record_failure(&self, ip: IpAddr, path: &str, lockout: &UnauthorizedLockout, now: i64) -> bool {
+        let _insert_guard = if self.records.contains_key(&ip) {
+            None
+        } else {
+            let guard = self.insert_lock.lock().unwrap_or_else(|e| e.into_inner());
+            if self.records.len() >= self.max_tracked && !self.records.contains_key(&ip) {
+                self.prune(now);
+            }
+            Some(guard)
+        };
+        let mut record = self.records.entry(ip).or_insert_with(|| AttemptRecord {
+            attempts: 0,
+            strikes: 0,
+            first_seen: now,
+            last_seen: now,
+            last_path: String::new(),
+            banned_until: None,
+        });
+        // 封禁已到期时重新计数
+        if record.banned_until.is_some_and(|until| until <= now) {
+            record.banned_until = None;
+            record.strikes = 0;
+        }
+        record.attempts += 1;
+        record.strikes = record.strikes.saturating_add(1);
+        record.last_seen = now;
+        record.last_path = path.to_string();
+
+        let should_ban = lockout.ban_after > 0
+            && record.strikes >= lockout.ban_after
+            && record.banned_until.is_none()
+            && !is_exempt(ip);
+        if should_ban {
+            record.banned_until = Some(now + lockout.ban_minutes as i64 * 60_000);
+        }
+        should_ban
+    }
+
+    /// 鉴权成功后清零连续失败次数
+    pub fn record_success(&self, ip: IpAddr) {
+        if let Some(mut record) = self.records.get_mut(&ip) {
+            record.strikes = 0;
+        }
+    }
+
+    /// 解除封禁，`ip` 为空时解除全部；返回解除的数量
+    pub fn clear_bans(&self, ip: Option<IpAddr>, now: i64) -> usize {
+        let mut cleared = 0;
+        for mut record in self.records.iter_mut() {
+            if ip.is_some_and(|ip| *record.key() != ip) {
+                continue;
+            }
+            if record.banned_until.take().is_some_and(|until| until > now) {
+                cleared += 1;
+            }
+            record.strikes = 0;
+        }
+        cleared
+    }
+
+    pub fn report(&self, limit: usize, now: i64) -> UnauthorizedReport {
+        let mut sources: Vec<UnauthorizedSource> = self
+            .records
+            .iter()
+            .map(|entry| {
+                let record = entry.value();
+                UnauthorizedSource {
+                    ip: entry.key().to_string(),
+                    attempts: record.attempts,
+                    strikes: record.strikes,
+                    first_seen: record.first_seen,
+                    last_seen: record.last_seen,
+                    last_path: record.last_path.clone(),
+                    banned_until: record.banned_until.filter(|until| *until > now),
+                }
+            })
+            .collect();
+        let tracked = sources.len();
+        let mut banned: Vec<UnauthorizedSource> =
+            sources.iter().filter(|s| s.banned_until.is_some()).cloned().collect();
+        banned.sort_by_key(|s| std::cmp::Reverse(s.banned_until));
+        sources.sort_by(|a, b| b.attempts.cmp(&a.attempts).then(b.last_seen.cmp(&a.last_seen)));
+        sources.truncate(limit);
+        UnauthorizedReport {
+            top_sources: sources,
+            banned,
+            tracked,
+        }
+    }
+
+    /// 一次淘汰一批记录：先淘汰未封禁 (或封禁已到期) 中最久未出现的地址，不够时淘汰最早到期的封禁
+    fn prune(&self, now: i64) {
+        let mut candidates: Vec<((bool, i64), IpAddr)> = self
+            .records
+            .iter()
+            .map(|entry| {
+                let key = match entry.banned_until.filter(|until| *until > now) {
+                    Some(until) => (true, until),
+                    None => (false, entry.last_seen),
+                };
+                (key, *entry.key())
+            })
+            .collect();
+        let excess = (self.records.len() + self.prune_batch).saturating_sub(self.max_tracked);
+        let count = excess.min(candidates.len());
+        if count == 0 {
+            return;
+        }
+        if count < candidates.len() {
+            candidates.select_nth_unstable_by_key(count - 1, |(key, _)| *key);
+        }
+        for (_, ip) in candidates.into_iter().take(count) {
+            self.records.remove(&ip);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn bans_after_threshold_and_expires() {
+        let tracker = UnauthorizedTracker::default();
+        let lockout = UnauthorizedLockout { ban_after: 3, ban_minutes: 1 };
+        let scanner = ip("203.0.113.9");
+
+        assert!(!tracker.record_failure(scanner, "/v1/models", &lockout, 0));
+        assert!(!tracker.record_failure(scanner, "/v1/models", &lockout, 1));
+        assert!(tracker.record_failure(scanner, "/v1/messages", &lockout, 2));
+        assert_eq!(tracker.banned_until(scanner, 3), Some(60_002));
+        assert_eq!(tracker.banned_until(scanner, 60_002), None);
+
+        let report = tracker.report(10, 3);
+        assert_eq!(report.banned.len(), 1);
+        assert_eq!(report.top_sources[0].attempts, 3);
+        assert_eq!(report.top_sources[0].last_path, "/v1/messages");
+
+        assert_eq!(tracker.clear_bans(Some(scanner), 3), 1);
+        assert_eq!(tracker.banned_until(scanner, 3), None);
+    }
+
+    #[test]
+    fn success_resets_strikes_and_loopback_is_exempt() {
+        let tracker = UnauthorizedTracker::default();
+        let lockout = UnauthorizedLockout { ban_after: 2, ban_minutes: 5 };
+        let client = ip("192.168.1.20");
+        tracker.record_failure(client, "/", &lockout, 0);
+        tracker.record_success(client);
+        assert!(!tracker.record_failure(client, "/", &lockout, 1));
+
+        for local in ["127.0.0.1", "::1", "::ffff:127.0.0.1"] {
+            for now in 0..5 {
+                assert!(!tracker.record_failure(ip(local), "/", &lockout, now));
+            }
+            assert_eq!(tracker.banned_until(ip(local), 5), None);
+        }
+
+        // 未配置阈值时只记录
+        let record_only = UnauthorizedLockout::default();
+        for now in 0..10 {
+            assert!(!tracker.record_failure(ip("198.51.100.1"), "/", &record_only, now));
+        }
+    }
+
+    #[test]
+    fn stays_bounded_when_full() {
+        let tracker = UnauthorizedTracker::with_limits(10, 3);
+        let lockout = UnauthorizedLockout { ban_after: 1, ban_minutes: 5 };
+        let record_only = UnauthorizedLockout::default();
+        let addr = |i: u32| IpAddr::from(std::net::Ipv4Addr::from(0xCB00_7100 + i));
+
+        // 前 5 个地址被封禁，其余只记录
+        for i in 0..10 {
+            let lockout = if i < 5 { &lockout } else { &record_only };
+            tracker.record_failure(addr(i), "/", lockout, i as i64);
+        }
+        assert_eq!(tracker.records.len(), 10);
+
+        // 达到上限后一次淘汰一批，优先淘汰最久未出现的未封禁地址
+        tracker.record_failure(addr(100), "/", &record_only, 100);
+        assert_eq!(tracker.records.len(), 8);
+        for i in 5..8 {
+            assert!(!tracker.records.contains_key(&addr(i)));
+        }
+        for i in 0..5 {
+            assert!(tracker.banned_until(addr(i), 100).is_some());
+        }
+
+        // 全部为封禁地址时淘汰最早到期的封禁，记录数仍不超过上限
+        let full = UnauthorizedTracker::with_limits(4, 2);
+        for i in 0..4 {
+            full.record_failure(addr(i), "/", &lockout, i as i64);
+        }
+        full.record_failure(addr(100), "/", &lockout, 10);
+        assert_eq!(full.records.len(), 3);
+        assert!(!full.records.contains_key(&addr(0)));
+        assert!(!full.records.contains_key(&addr(1)));
+        assert!(full.banned_until(addr(100), 10).is_some());
+        for i in 200..300 {
+            full.record_failure(addr(i), "/", &lockout, 20);
+            assert!(full.records.len() <= 4);
+        }
+    }
+}
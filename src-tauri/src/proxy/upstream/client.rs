@@ -4,6 +4,15 @@
 use reqwest::{header, Client, Response, StatusCode};
 use serde_json::Value;
 use tokio::time::Duration;
+use tracing::Instrument;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use crate::proxy::config::{DnsOverride, TimeoutOverrides, TimeoutRoute, UpstreamProxyConfig, MIN_STREAM_TIMEOUT};
+use crate::proxy::monitor::{record_phase, ConnectionCounters};
 
 // Cloud Code v1internal endpoints (fallback order: prod → daily)
 // 优先使用稳定的 prod 端点，避免影响缓存命中率
@@ -14,33 +23,242 @@ const V1_INTERNAL_BASE_URL_FALLBACKS: [&str; 2] = [
     V1_INTERNAL_BASE_URL_DAILY,  // 备用测试环境（新功能）
 ];
 
+tokio::task_local! {
+    /// 当前请求最近一次上游调用实际使用的超时 (秒)，由监控中间件写入日志
+    pub static EFFECTIVE_TIMEOUT: std::cell::Cell<Option<u64>>;
+}
+
+/// 单个反代请求的上游端点选择 (均为规范化后的 v1internal 基础地址)
+#[derive(Debug, Clone, Default)]
+pub struct EndpointSelection {
+    /// 请求头 `X-Antigravity-Endpoint` 指定的端点
+    pub requested: Option<String>,
+    /// 当前选中账号设置的端点
+    pub account: Option<String>,
+    /// 最近一次上游调用实际使用的端点
+    pub used: Option<String>,
+}
+
+tokio::task_local! {
+    /// 当前请求的上游端点选择，由端点中间件建立
+    pub static ENDPOINT_SELECTION: std::cell::RefCell<EndpointSelection>;
+}
+
+/// 记录当前请求选中账号的端点 (不在请求上下文中时忽略)
+pub fn set_account_endpoint(endpoint: Option<String>) {
+    let _ = ENDPOINT_SELECTION.try_with(|cell| cell.borrow_mut().account = endpoint);
+}
+
+/// 当前请求最近一次上游调用实际使用的端点
+pub fn used_endpoint() -> Option<String> {
+    ENDPOINT_SELECTION.try_with(|cell| cell.borrow().used.clone()).ok().flatten()
+}
+
+/// 上游请求超时配置 (可热更新)
+#[derive(Debug, Clone)]
+pub struct UpstreamTimeouts {
+    pub request_timeout: u64,
+    pub overrides: TimeoutOverrides,
+}
+
+impl UpstreamTimeouts {
+    pub fn from_proxy_config(config: &crate::proxy::config::ProxyConfig) -> Self {
+        Self {
+            request_timeout: config.request_timeout,
+            overrides: config.timeouts.clone(),
+        }
+    }
+}
+
+/// 上游连接池设置
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpstreamPoolSettings {
+    pub max_idle_per_host: usize,
+    pub idle_timeout_secs: u64,
+    /// 0 表示关闭 TCP 保活
+    pub tcp_keepalive_secs: u64,
+}
+
+impl UpstreamPoolSettings {
+    pub fn from_proxy_config(config: &crate::proxy::config::ProxyConfig) -> Self {
+        Self {
+            max_idle_per_host: config.upstream_pool_max_idle_per_host,
+            idle_timeout_secs: config.upstream_pool_idle_timeout_secs,
+            tcp_keepalive_secs: config.tcp_keepalive_secs,
+        }
+    }
+}
+
+/// 构建 HTTP 客户端所需的连接设置，变化时重建客户端
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpstreamConnectionSettings {
+    pub proxy: UpstreamProxyConfig,
+    pub pool: UpstreamPoolSettings,
+    pub dns_overrides: Vec<DnsOverride>,
+    /// 默认上游端点 (规范化后的基础地址)，为空时按 prod → daily 回退
+    pub endpoint: Option<String>,
+}
+
+impl UpstreamConnectionSettings {
+    pub fn from_proxy_config(config: &crate::proxy::config::ProxyConfig) -> Self {
+        Self {
+            proxy: config.upstream_proxy.clone(),
+            pool: UpstreamPoolSettings::from_proxy_config(config),
+            dns_overrides: config.dns_overrides.clone(),
+            // 配置已校验；无效值按未设置处理
+            endpoint: config
+                .upstream_endpoint
+                .as_deref()
+                .and_then(|e| crate::proxy::config::normalize_endpoint(e).ok()),
+        }
+    }
+}
+
+/// 统计新建连接数的连接器包装
+#[derive(Clone)]
+struct CountingConnector<S> {
+    inner: S,
+    counters: Arc<ConnectionCounters>,
+}
+
+impl<S, R> tower::Service<R> for CountingConnector<S>
+where
+    S: tower::Service<R>,
+    S::Future: Send + 'static,
+    S::Response: Send + 'static,
+    S::Error: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        let counters = self.counters.clone();
+        let connecting = self.inner.call(req);
+        Box::pin(async move {
+            let conn = connecting.await?;
+            counters.record_new_connection();
+            Ok(conn)
+        })
+    }
+}
+
+#[derive(Clone)]
+struct CountingLayer(Arc<ConnectionCounters>);
+
+impl<S> tower::Layer<S> for CountingLayer {
+    type Service = CountingConnector<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CountingConnector {
+            inner,
+            counters: self.0.clone(),
+        }
+    }
+}
+
+fn build_http_client(
+    settings: &UpstreamConnectionSettings,
+    counters: &Arc<ConnectionCounters>,
+) -> Result<Client, String> {
+    let pool = &settings.pool;
+    let mut builder = Client::builder()
+        // Connection settings (优化连接复用，减少建立开销)
+        .connect_timeout(Duration::from_secs(20))
+        .pool_max_idle_per_host(pool.max_idle_per_host)
+        .pool_idle_timeout(Duration::from_secs(pool.idle_timeout_secs))
+        .tcp_keepalive((pool.tcp_keepalive_secs > 0).then(|| Duration::from_secs(pool.tcp_keepalive_secs)))
+        .connector_layer(CountingLayer(counters.clone()))
+        // 总超时按请求设置，见 timeout_for
+        .user_agent("antigravity/1.11.9 windows/amd64");
+
+    let proxy = &settings.proxy;
+    if proxy.enabled && !proxy.url.is_empty() {
+        match reqwest::Proxy::all(&proxy.url) {
+            Ok(p) => {
+                builder = builder.proxy(p);
+                tracing::info!("UpstreamClient enabled proxy: {}", proxy.url);
+            }
+            Err(e) => tracing::warn!("UpstreamClient ignored invalid proxy {}: {}", proxy.url, e),
+        }
+    }
+
+    builder = crate::utils::http::apply_dns_overrides(builder, &settings.dns_overrides);
+
+    builder.build().map_err(|e| format!("创建 HTTP 客户端失败: {}", e))
+}
+
 pub struct UpstreamClient {
-    http_client: Client,
+    /// 配置变化时整体替换；进行中的请求继续持有旧客户端直到结束
+    http_client: std::sync::RwLock<Arc<Client>>,
+    settings: std::sync::Mutex<UpstreamConnectionSettings>,
+    counters: Arc<ConnectionCounters>,
+    timeouts: std::sync::RwLock<UpstreamTimeouts>,
 }
 
 impl UpstreamClient {
-    pub fn new(proxy_config: Option<crate::proxy::config::UpstreamProxyConfig>) -> Self {
-        let mut builder = Client::builder()
-            // Connection settings (优化连接复用，减少建立开销)
-            .connect_timeout(Duration::from_secs(20))
-            .pool_max_idle_per_host(16)                  // 每主机最多 16 个空闲连接
-            .pool_idle_timeout(Duration::from_secs(90))  // 空闲连接保持 90 秒
-            .tcp_keepalive(Duration::from_secs(60))      // TCP 保活探测 60 秒
-            .timeout(Duration::from_secs(600))
-            .user_agent("antigravity/1.11.9 windows/amd64");
-
-        if let Some(config) = proxy_config {
-            if config.enabled && !config.url.is_empty() {
-                if let Ok(proxy) = reqwest::Proxy::all(&config.url) {
-                    builder = builder.proxy(proxy);
-                    tracing::info!("UpstreamClient enabled proxy: {}", config.url);
+    pub fn new(
+        settings: UpstreamConnectionSettings,
+        timeouts: UpstreamTimeouts,
+        counters: Arc<ConnectionCounters>,
+    ) -> Self {
+        let http_client = build_http_client(&settings, &counters).expect("Failed to create HTTP client");
+
+        Self {
+            http_client: std::sync::RwLock::new(Arc::new(http_client)),
+            settings: std::sync::Mutex::new(settings),
+            counters,
+            timeouts: std::sync::RwLock::new(timeouts),
+        }
+    }
+
+    fn client(&self) -> Arc<Client> {
+        match self.http_client.read() {
+            Ok(client) => client.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// 热更新代理与连接池设置，有变化时重建客户端；构建失败时保留原客户端
+    pub fn reconfigure(&self, settings: UpstreamConnectionSettings) {
+        let Ok(mut current) = self.settings.lock() else {
+            return;
+        };
+        if *current == settings {
+            return;
+        }
+        match build_http_client(&settings, &self.counters) {
+            Ok(client) => {
+                if let Ok(mut slot) = self.http_client.write() {
+                    *slot = Arc::new(client);
                 }
+                *current = settings;
+                tracing::info!("上游 HTTP 客户端已按新配置重建");
             }
+            Err(e) => tracing::error!("{}", e),
         }
+    }
 
-        let http_client = builder.build().expect("Failed to create HTTP client");
+    /// 热更新超时配置，对之后发起的请求生效
+    pub fn set_timeouts(&self, timeouts: UpstreamTimeouts) {
+        if let Ok(mut current) = self.timeouts.write() {
+            *current = timeouts;
+        }
+    }
 
-        Self { http_client }
+    /// 按 v1internal 方法计算本次请求的超时，并记录到当前请求上下文
+    fn timeout_for(&self, method: &str) -> Duration {
+        let (route, stream) = TimeoutRoute::from_upstream_method(method);
+        let secs = match self.timeouts.read() {
+            Ok(t) => t.overrides.effective(route, stream, t.request_timeout),
+            Err(_) => MIN_STREAM_TIMEOUT,
+        };
+        let _ = EFFECTIVE_TIMEOUT.try_with(|cell| cell.set(Some(secs)));
+        Duration::from_secs(secs)
     }
 
     /// 构建 v1internal URL
@@ -54,6 +272,22 @@ impl UpstreamClient {
         }
     }
 
+    /// 本次请求依次尝试的端点：请求头 > 账号设置 > 默认端点，指定时只使用该端点；都未指定时按回退顺序
+    fn endpoints(&self) -> Vec<String> {
+        let selection = ENDPOINT_SELECTION
+            .try_with(|cell| {
+                let selection = cell.borrow();
+                selection.requested.clone().or_else(|| selection.account.clone())
+            })
+            .ok()
+            .flatten();
+        let default = self.settings.lock().ok().and_then(|s| s.endpoint.clone());
+        match selection.or(default) {
+            Some(endpoint) => vec![endpoint],
+            None => V1_INTERNAL_BASE_URL_FALLBACKS.iter().map(|u| u.to_string()).collect(),
+        }
+    }
+
     /// 判断是否应尝试下一个端点
     /// 
     /// 当遇到以下错误时，尝试切换到备用端点：
@@ -70,13 +304,42 @@ impl UpstreamClient {
 
     /// 调用 v1internal API（基础方法）
     /// 
-    /// 发起基础网络请求，支持多端点自动 Fallback
+    /// 发起基础网络请求，支持多端点自动 Fallback；记录 upstream_request span 与首字节耗时
     pub async fn call_v1_internal(
         &self,
         method: &str,
         access_token: &str,
         body: Value,
         query_string: Option<&str>,
+    ) -> Result<Response, String> {
+        let span = tracing::debug_span!(
+            "upstream_request",
+            method,
+            status = tracing::field::Empty,
+            ttfb_ms = tracing::field::Empty
+        );
+        let start = std::time::Instant::now();
+        let result = self
+            .send_with_fallback(method, access_token, body, query_string)
+            .instrument(span.clone())
+            .await;
+        // reqwest 在收到响应头后返回，此时的耗时即首字节耗时
+        let ttfb = start.elapsed().as_millis() as u64;
+        record_phase(|t| t.upstream_ttfb_ms = Some(ttfb));
+        span.record("ttfb_ms", ttfb);
+        if let Ok(resp) = &result {
+            span.record("status", resp.status().as_u16());
+            crate::proxy::middleware::response_headers::record_upstream_headers(resp.headers());
+        }
+        result
+    }
+
+    async fn send_with_fallback(
+        &self,
+        method: &str,
+        access_token: &str,
+        body: Value,
+        query_string: Option<&str>,
     ) -> Result<Response, String> {
         // 构建 Headers (所有端点复用)
         let mut headers = header::HeaderMap::new();
@@ -94,17 +357,23 @@ impl UpstreamClient {
             header::HeaderValue::from_static("antigravity/1.11.9 windows/amd64"),
         );
 
+        let timeout = self.timeout_for(method);
+        let http_client = self.client();
         let mut last_err: Option<String> = None;
 
+        let endpoints = self.endpoints();
+
         // 遍历所有端点，失败时自动切换
-        for (idx, base_url) in V1_INTERNAL_BASE_URL_FALLBACKS.iter().enumerate() {
+        for (idx, base_url) in endpoints.iter().enumerate() {
             let url = Self::build_url(base_url, method, query_string);
-            let has_next = idx + 1 < V1_INTERNAL_BASE_URL_FALLBACKS.len();
+            let has_next = idx + 1 < endpoints.len();
+            let _ = ENDPOINT_SELECTION.try_with(|cell| cell.borrow_mut().used = Some(base_url.clone()));
 
-            let response = self
-                .http_client
+            self.counters.record_request();
+            let response = http_client
                 .post(&url)
                 .headers(headers.clone())
+                .timeout(timeout)
                 .json(&body)
                 .send()
                 .await;
@@ -119,7 +388,7 @@ impl UpstreamClient {
                                 base_url,
                                 status,
                                 idx + 1,
-                                V1_INTERNAL_BASE_URL_FALLBACKS.len()
+                                endpoints.len()
                             );
                         } else {
                             tracing::debug!("✓ Upstream request succeeded | Endpoint: {} | Status: {}", base_url, status);
@@ -198,16 +467,19 @@ impl UpstreamClient {
             header::HeaderValue::from_static("antigravity/1.11.9 windows/amd64"),
         );
 
+        let timeout = self.timeout_for("fetchAvailableModels");
+        let http_client = self.client();
         let mut last_err: Option<String> = None;
 
         // 遍历所有端点，失败时自动切换
         for (idx, base_url) in V1_INTERNAL_BASE_URL_FALLBACKS.iter().enumerate() {
             let url = Self::build_url(base_url, "fetchAvailableModels", None);
 
-            let response = self
-                .http_client
+            self.counters.record_request();
+            let response = http_client
                 .post(&url)
                 .headers(headers.clone())
+                .timeout(timeout)
                 .json(&serde_json::json!({}))
                 .send()
                 .await;
@@ -286,4 +558,74 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_connection_reuse_is_counted() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = axum::Router::new().route("/", axum::routing::get(|| async { "ok" }));
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+
+        let settings = UpstreamConnectionSettings {
+            proxy: UpstreamProxyConfig::default(),
+            pool: UpstreamPoolSettings {
+                max_idle_per_host: 4,
+                idle_timeout_secs: 30,
+                tcp_keepalive_secs: 0,
+            },
+            dns_overrides: Vec::new(),
+            endpoint: None,
+        };
+        let counters = Arc::new(ConnectionCounters::default());
+        let client = build_http_client(&settings, &counters).unwrap();
+        for _ in 0..3 {
+            counters.record_request();
+            let body = client.get(format!("http://{}/", addr)).send().await.unwrap().text().await.unwrap();
+            assert_eq!(body, "ok");
+        }
+
+        let stats = counters.snapshot();
+        assert_eq!(stats.requests, 3);
+        assert_eq!(stats.new_connections, 1);
+        assert_eq!(stats.reused_connections, 2);
+    }
+
+    #[test]
+    fn test_endpoint_precedence() {
+        let settings = UpstreamConnectionSettings {
+            proxy: UpstreamProxyConfig::default(),
+            pool: UpstreamPoolSettings {
+                max_idle_per_host: 4,
+                idle_timeout_secs: 30,
+                tcp_keepalive_secs: 0,
+            },
+            dns_overrides: Vec::new(),
+            endpoint: None,
+        };
+        let timeouts = UpstreamTimeouts {
+            request_timeout: 60,
+            overrides: TimeoutOverrides::default(),
+        };
+        let client = UpstreamClient::new(settings.clone(), timeouts, Arc::new(ConnectionCounters::default()));
+        assert_eq!(client.endpoints(), V1_INTERNAL_BASE_URL_FALLBACKS.to_vec());
+
+        client.reconfigure(UpstreamConnectionSettings {
+            endpoint: Some(V1_INTERNAL_BASE_URL_DAILY.to_string()),
+            ..settings
+        });
+        assert_eq!(client.endpoints(), vec![V1_INTERNAL_BASE_URL_DAILY]);
+
+        let selection = EndpointSelection {
+            account: Some(V1_INTERNAL_BASE_URL_PROD.to_string()),
+            ..Default::default()
+        };
+        ENDPOINT_SELECTION.sync_scope(std::cell::RefCell::new(selection), || {
+            assert_eq!(client.endpoints(), vec![V1_INTERNAL_BASE_URL_PROD]);
+            ENDPOINT_SELECTION.with(|cell| {
+                cell.borrow_mut().requested = Some(V1_INTERNAL_BASE_URL_DAILY.to_string())
+            });
+            assert_eq!(client.endpoints(), vec![V1_INTERNAL_BASE_URL_DAILY]);
+        });
+    }
 }
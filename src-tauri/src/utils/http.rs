@@ -1,20 +1,24 @@
-use reqwest::{Client, Proxy};
+use reqwest::{Client, ClientBuilder, Proxy};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
 use crate::modules::config::load_app_config;
+use crate::proxy::config::DnsOverride;
 
 /// 创建统一配置的 HTTP 客户端
-/// 自动加载全局配置并应用代理
+/// 自动加载全局配置并应用代理与自定义 DNS 解析
 pub fn create_client(timeout_secs: u64) -> Client {
     if let Ok(config) = load_app_config() {
-        create_client_with_proxy(timeout_secs, Some(config.proxy.upstream_proxy))
+        create_client_with_proxy(timeout_secs, Some(config.proxy.upstream_proxy), &config.proxy.dns_overrides)
     } else {
-        create_client_with_proxy(timeout_secs, None)
+        create_client_with_proxy(timeout_secs, None, &[])
     }
 }
 
 /// 创建带指定代理配置的 HTTP 客户端
 pub fn create_client_with_proxy(
     timeout_secs: u64, 
-    proxy_config: Option<crate::proxy::config::UpstreamProxyConfig>
+    proxy_config: Option<crate::proxy::config::UpstreamProxyConfig>,
+    dns_overrides: &[DnsOverride],
 ) -> Client {
     let mut builder = Client::builder()
         .timeout(std::time::Duration::from_secs(timeout_secs));
@@ -33,5 +37,24 @@ pub fn create_client_with_proxy(
         }
     }
 
+    builder = apply_dns_overrides(builder, dns_overrides);
+
     builder.build().unwrap_or_else(|_| Client::new())
 }
+
+/// 应用自定义 DNS 解析，同一主机的多条记录合并为候选地址列表
+pub fn apply_dns_overrides(mut builder: ClientBuilder, overrides: &[DnsOverride]) -> ClientBuilder {
+    let mut by_host: BTreeMap<String, Vec<SocketAddr>> = BTreeMap::new();
+    for entry in overrides {
+        match entry.socket_addr() {
+            Some(addr) => by_host.entry(entry.host_key()).or_default().push(addr),
+            None => tracing::warn!("忽略无效的 DNS 覆盖: {} -> {}", entry.host, entry.ip),
+        }
+    }
+    for (host, addrs) in &by_host {
+        if !host.is_empty() {
+            builder = builder.resolve_to_addrs(host, addrs);
+        }
+    }
+    builder
+}
@@ -1,2 +1,4 @@
 pub mod http;
 pub mod protobuf;
+pub mod panic;
+pub mod tasks;
@@ -0,0 +1,157 @@
+// panic 兜底：记录 panic 信息与调用栈，把 handler 中的 panic 转成 500 响应；后台任务的 panic 重启见 `utils::tasks`
+
+use axum::{
+    extract::Request,
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use futures::FutureExt;
+use std::any::Any;
+use serde::Serialize;
+use std::panic::AssertUnwindSafe;
+use std::sync::Mutex;
+use utoipa::ToSchema;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// 后台任务失败记录
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TaskError {
+    pub task: String,
+    pub message: String,
+    /// 发生时间 (Unix 秒)
+    pub at: i64,
+}
+
+static LAST_TASK_ERROR: Mutex<Option<TaskError>> = Mutex::new(None);
+
+/// 记录后台任务失败，供健康检查展示
+pub fn record_task_error(task: &str, message: &str) {
+    *LAST_TASK_ERROR.lock().unwrap_or_else(|e| e.into_inner()) = Some(TaskError {
+        task: task.to_string(),
+        message: message.to_string(),
+        at: chrono::Utc::now().timestamp(),
+    });
+}
+
+/// 最近一次后台任务失败
+pub fn last_task_error() -> Option<TaskError> {
+    LAST_TASK_ERROR.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// 安装 panic hook，将 panic 信息、位置与调用栈写入日志
+pub fn install_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}", l.file(), l.line()))
+            .unwrap_or_else(|| "unknown".to_string());
+        let thread = std::thread::current();
+        tracing::error!(
+            "线程 {} 发生 panic: {} (位置: {})\n{}",
+            thread.name().unwrap_or("<unnamed>"),
+            payload_message(info.payload()),
+            location,
+            std::backtrace::Backtrace::force_capture()
+        );
+    }));
+}
+
+/// 提取 panic 负载中的文本
+pub fn payload_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
+}
+
+/// 被捕获的 handler panic
+pub struct CaughtPanic {
+    pub request_id: String,
+    pub message: String,
+}
+
+/// 执行后续中间件与 handler，panic 时返回 `Err` 由调用方按协议生成错误响应
+///
+/// 请求 ID 取自 `X-Request-Id` 请求头，缺失时生成，并回写到响应头
+pub async fn run_catching(request: Request, next: Next) -> Result<Response, CaughtPanic> {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= 128)
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+
+    match AssertUnwindSafe(next.run(request)).catch_unwind().await {
+        Ok(mut response) => {
+            if let Ok(value) = HeaderValue::from_str(&request_id) {
+                response.headers_mut().entry(REQUEST_ID_HEADER).or_insert(value);
+            }
+            Ok(response)
+        }
+        Err(payload) => {
+            let message = payload_message(payload.as_ref());
+            tracing::error!("请求 {} {} 处理时 panic (request_id={}): {}", method, path, request_id, message);
+            Err(CaughtPanic { request_id, message })
+        }
+    }
+}
+
+/// 为 panic 生成的错误响应附上请求 ID
+pub fn with_request_id(mut response: Response, request_id: &str) -> Response {
+    if let Ok(value) = HeaderValue::from_str(request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_catching_converts_panic() {
+        use axum::{body::Body, response::IntoResponse, routing::get, Router};
+        use tower::ServiceExt;
+
+        async fn guard(request: Request, next: Next) -> Response {
+            match run_catching(request, next).await {
+                Ok(response) => response,
+                Err(caught) => with_request_id(
+                    (axum::http::StatusCode::INTERNAL_SERVER_ERROR, caught.message).into_response(),
+                    &caught.request_id,
+                ),
+            }
+        }
+
+        async fn exploding_handler() -> &'static str {
+            panic!("handler exploded")
+        }
+
+        let app = Router::new()
+            .route("/boom", get(exploding_handler))
+            .route("/ok", get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn(guard));
+
+        let response = app
+            .clone()
+            .oneshot(Request::get("/boom").header(REQUEST_ID_HEADER, "req-1").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 500);
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "req-1");
+        let body = axum::body::to_bytes(response.into_body(), 1024).await.unwrap();
+        assert_eq!(&body[..], b"handler exploded");
+
+        let response = app.oneshot(Request::get("/ok").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert!(response.headers().contains_key(REQUEST_ID_HEADER));
+    }
+}
@@ -0,0 +1,341 @@
+// 后台任务监管：按名称管理常驻任务，支持查询状态、单独重启，以及在切换数据 (如恢复备份) 前统一停止
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use utoipa::ToSchema;
+
+use crate::utils::panic::{payload_message, record_task_error};
+
+/// 任务 panic 后的重启间隔
+const RESTART_DELAY: Duration = Duration::from_secs(5);
+/// 停止任务时等待其自行退出的时间，超时后强制中止
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+type TaskFactory = Arc<dyn Fn(TaskContext) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+/// 任务最近的运行记录
+#[derive(Debug, Clone, Default)]
+struct TaskRecord {
+    last_run: Option<i64>,
+    last_error: Option<String>,
+    last_error_at: Option<i64>,
+}
+
+/// 传给任务的上下文：停止信号与运行记录
+#[derive(Clone, Default)]
+pub struct TaskContext {
+    cancel: CancellationToken,
+    record: Arc<Mutex<TaskRecord>>,
+}
+
+impl TaskContext {
+    /// 等待指定时间，任务被停止时提前返回 false
+    pub async fn sleep(&self, duration: Duration) -> bool {
+        tokio::select! {
+            _ = self.cancel.cancelled() => false,
+            _ = tokio::time::sleep(duration) => true,
+        }
+    }
+
+    /// 任务被停止时完成
+    pub async fn cancelled(&self) {
+        self.cancel.cancelled().await
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// 记录一次运行及其结果
+    pub fn record_run(&self, result: Result<(), String>) {
+        let now = chrono::Utc::now().timestamp();
+        let mut record = self.record.lock().unwrap_or_else(|e| e.into_inner());
+        record.last_run = Some(now);
+        if let Err(e) = result {
+            record.last_error = Some(e);
+            record.last_error_at = Some(now);
+        }
+    }
+
+    fn record_error(&self, message: String) {
+        let mut record = self.record.lock().unwrap_or_else(|e| e.into_inner());
+        record.last_error = Some(message);
+        record.last_error_at = Some(chrono::Utc::now().timestamp());
+    }
+}
+
+/// 后台任务状态
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TaskStatus {
+    pub name: String,
+    pub running: bool,
+    /// 最近一次运行时间 (Unix 秒)
+    pub last_run: Option<i64>,
+    /// 最近一次失败或 panic 的信息
+    pub last_error: Option<String>,
+    pub last_error_at: Option<i64>,
+    /// 切换数据时是否保持运行 (如 systemd 心跳)
+    pub persistent: bool,
+}
+
+struct TaskEntry {
+    factory: TaskFactory,
+    persistent: bool,
+    record: Arc<Mutex<TaskRecord>>,
+    running: Option<(CancellationToken, JoinHandle<()>)>,
+}
+
+impl TaskEntry {
+    fn start(&mut self, name: &str) {
+        let ctx = TaskContext {
+            cancel: CancellationToken::new(),
+            record: self.record.clone(),
+        };
+        let handle = tokio::spawn(supervise(name.to_string(), self.factory.clone(), ctx.clone()));
+        self.running = Some((ctx.cancel, handle));
+    }
+}
+
+/// 运行任务：panic 时记录并在稍后重启，正常结束或被停止时退出
+async fn supervise(name: String, factory: TaskFactory, ctx: TaskContext) {
+    loop {
+        let mut task = tokio::spawn(factory(ctx.clone()));
+        let result = tokio::select! {
+            result = &mut task => result,
+            _ = ctx.cancel.cancelled() => {
+                // 给任务时间完成当前一轮，超时后中止
+                if tokio::time::timeout(STOP_TIMEOUT, &mut task).await.is_err() {
+                    tracing::warn!("后台任务 {} 未在 {} 秒内退出，已强制中止", name, STOP_TIMEOUT.as_secs());
+                    task.abort();
+                }
+                return;
+            }
+        };
+        match result {
+            Err(e) if e.is_panic() => {
+                let message = payload_message(e.into_panic().as_ref());
+                tracing::error!("后台任务 {} panic: {}，{} 秒后重启", name, message, RESTART_DELAY.as_secs());
+                record_task_error(&name, &message);
+                ctx.record_error(format!("panic: {}", message));
+                if !ctx.sleep(RESTART_DELAY).await {
+                    return;
+                }
+            }
+            _ => return,
+        }
+    }
+}
+
+/// 后台任务监管器
+#[derive(Default)]
+pub struct TaskSupervisor {
+    tasks: Mutex<BTreeMap<String, TaskEntry>>,
+}
+
+impl TaskSupervisor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 注册并启动任务，切换数据前会被停止；同名任务已存在时先停止旧任务
+    pub fn register<F, Fut>(&self, name: &str, make: F)
+    where
+        F: Fn(TaskContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.insert(name, false, make);
+    }
+
+    /// 注册切换数据时保持运行的任务 (不访问数据目录)
+    pub fn register_persistent<F, Fut>(&self, name: &str, make: F)
+    where
+        F: Fn(TaskContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.insert(name, true, make);
+    }
+
+    fn insert<F, Fut>(&self, name: &str, persistent: bool, make: F)
+    where
+        F: Fn(TaskContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let factory: TaskFactory = Arc::new(move |ctx| Box::pin(make(ctx)));
+        let mut entry = TaskEntry {
+            factory,
+            persistent,
+            record: Arc::default(),
+            running: None,
+        };
+        entry.start(name);
+        let mut tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((cancel, _)) = tasks.insert(name.to_string(), entry).and_then(|old| old.running) {
+            cancel.cancel();
+        }
+    }
+
+    /// 所有任务的状态，按名称排序
+    pub fn list(&self) -> Vec<TaskStatus> {
+        let tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+        tasks
+            .iter()
+            .map(|(name, entry)| {
+                let record = entry.record.lock().unwrap_or_else(|e| e.into_inner()).clone();
+                TaskStatus {
+                    name: name.clone(),
+                    running: entry.running.as_ref().is_some_and(|(_, handle)| !handle.is_finished()),
+                    last_run: record.last_run,
+                    last_error: record.last_error,
+                    last_error_at: record.last_error_at,
+                    persistent: entry.persistent,
+                }
+            })
+            .collect()
+    }
+
+    /// 停止指定任务并等待退出
+    async fn stop(&self, names: &[String]) {
+        let stopping: Vec<JoinHandle<()>> = {
+            let mut tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+            names
+                .iter()
+                .filter_map(|name| tasks.get_mut(name)?.running.take())
+                .map(|(cancel, handle)| {
+                    cancel.cancel();
+                    handle
+                })
+                .collect()
+        };
+        for handle in stopping {
+            let _ = handle.await;
+        }
+    }
+
+    fn start(&self, names: &[String]) {
+        let mut tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+        for name in names {
+            if let Some(entry) = tasks.get_mut(name) {
+                if entry.running.is_none() {
+                    entry.start(name);
+                }
+            }
+        }
+    }
+
+    /// 重启指定任务，任务不存在时返回 false
+    pub async fn restart(&self, name: &str) -> bool {
+        if !self.tasks.lock().unwrap_or_else(|e| e.into_inner()).contains_key(name) {
+            return false;
+        }
+        let names = [name.to_string()];
+        self.stop(&names).await;
+        self.start(&names);
+        tracing::info!("后台任务 {} 已重启", name);
+        true
+    }
+
+    /// 停止所有非常驻的运行中任务并等待退出，返回被停止的任务名供 `resume` 恢复
+    pub async fn quiesce(&self) -> Vec<String> {
+        let names: Vec<String> = {
+            let tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+            tasks
+                .iter()
+                .filter(|(_, entry)| !entry.persistent && entry.running.is_some())
+                .map(|(name, _)| name.clone())
+                .collect()
+        };
+        self.stop(&names).await;
+        if !names.is_empty() {
+            tracing::info!("已停止后台任务: {}", names.join(", "));
+        }
+        names
+    }
+
+    /// 重新启动 `quiesce` 停止的任务
+    pub fn resume(&self, names: &[String]) {
+        self.start(names);
+        if !names.is_empty() {
+            tracing::info!("已恢复后台任务: {}", names.join(", "));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn counting_loop(supervisor: &TaskSupervisor, name: &str, persistent: bool, starts: Arc<AtomicUsize>) {
+        let make = move |ctx: TaskContext| {
+            let starts = starts.clone();
+            async move {
+                starts.fetch_add(1, Ordering::SeqCst);
+                while ctx.sleep(Duration::from_millis(5)).await {
+                    ctx.record_run(Err("tick failed".to_string()));
+                }
+            }
+        };
+        if persistent {
+            supervisor.register_persistent(name, make);
+        } else {
+            supervisor.register(name, make);
+        }
+    }
+
+    fn status(supervisor: &TaskSupervisor, name: &str) -> TaskStatus {
+        supervisor.list().into_iter().find(|t| t.name == name).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_quiesce_and_resume() {
+        let supervisor = TaskSupervisor::new();
+        let sync_starts = Arc::new(AtomicUsize::new(0));
+        let watchdog_starts = Arc::new(AtomicUsize::new(0));
+        counting_loop(&supervisor, "db-sync", false, sync_starts.clone());
+        counting_loop(&supervisor, "watchdog", true, watchdog_starts.clone());
+        tokio::time::sleep(Duration::from_millis(30)).await;
+
+        let sync = status(&supervisor, "db-sync");
+        assert!(sync.running);
+        assert!(sync.last_run.is_some());
+        assert_eq!(sync.last_error.as_deref(), Some("tick failed"));
+
+        let stopped = supervisor.quiesce().await;
+        assert_eq!(stopped, vec!["db-sync"]);
+        assert!(!status(&supervisor, "db-sync").running);
+        assert!(status(&supervisor, "watchdog").running);
+
+        supervisor.resume(&stopped);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(status(&supervisor, "db-sync").running);
+        assert_eq!(sync_starts.load(Ordering::SeqCst), 2);
+
+        assert!(supervisor.restart("watchdog").await);
+        assert!(!supervisor.restart("missing").await);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(watchdog_starts.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_panicking_task_records_error() {
+        let supervisor = TaskSupervisor::new();
+        supervisor.register("boom", |_ctx| async { panic!("exploded") });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let boom = status(&supervisor, "boom");
+        assert!(boom.running, "task waits to restart after a panic");
+        assert_eq!(boom.last_error.as_deref(), Some("panic: exploded"));
+        let error = crate::utils::panic::last_task_error().unwrap();
+        assert_eq!(error.task, "boom");
+        assert_eq!(error.message, "exploded");
+
+        // 停止时不必等待重启间隔
+        let stopped = tokio::time::timeout(Duration::from_secs(1), supervisor.quiesce()).await.unwrap();
+        assert_eq!(stopped, vec!["boom"]);
+    }
+}
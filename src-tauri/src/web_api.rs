@@ -3,24 +3,57 @@
 //! 此模块提供独立运行的 Web 服务端 API，复用现有业务逻辑。
 
 use axum::{
-    extract::{Path, Query, State, rejection::JsonRejection, FromRequest, Request},
-    http::StatusCode,
+    extract::{DefaultBodyLimit, MatchedPath, Multipart, Path, Query, State, rejection::JsonRejection, FromRequest, FromRequestParts, Request},
+    http::{header, request::Parts, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
     response::{IntoResponse, Response, Json, Sse},
-    routing::{delete, get, post, put},
+    routing::{delete, get, patch, post, put},
     Router,
 };
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use futures::stream::Stream;
 use std::convert::Infallible;
 use std::time::Duration;
+use utoipa::{IntoParams, OpenApi, ToSchema};
 
 
-use crate::models::{Account, AppConfig, QuotaData};
+use crate::models::{Account, AppConfig, QuotaData, WebApiRole, WebAuthConfig};
 use crate::modules;
-use crate::proxy::{ProxyConfig, TokenManager};
-use crate::proxy::monitor::{ProxyMonitor, ProxyRequestLog, ProxyStats};
+use crate::modules::account::{AccountIdMismatch, CorruptedAccount, DataDirSource, DedupeReport, DuplicateGroup, ImportAction, ImportOutcome, OnDuplicate, ProfileInfo};
+use crate::modules::db_sync::{DbSyncOutcome, DbSyncStatus};
+use crate::modules::seed::{SeedEntryResult, SeedOutcome, SeedStatus};
+use crate::modules::oauth::{BreakerState, RefreshBreakerStatus};
+use crate::modules::migration::{ImportOptions, ImportPlanEntry, ImportReport, PlannedAction, UploadFormat};
+use crate::modules::audit::AuditEntry;
+use crate::modules::backup::RestoreSummary;
+use crate::modules::stats_history::{AccountHeatmap, CostGroupBy, CostReport, CostRow, DailyStats, UsageHeatmap};
+use crate::modules::dashboard::{AccountCounts, QuotaTotals};
+use crate::modules::logger::WarningEvent;
+use crate::modules::system_info::{BuildInfo, DiskUsage, EventSubscribers, MonitorBuffer, SystemInfo};
+use crate::modules::upstream_probe::UpstreamProbe;
+use crate::modules::health::{HealthReport, ProxyHealth};
+use crate::modules::web_rate_limit::{RateLimitState, RouteClass, WebRateLimiter};
+use crate::modules::token_health::{TokenCheck, TokenStatus};
+use crate::modules::account_test::{AccountTestResult, TestErrorKind};
+use crate::modules::account_repair::{RepairReport, RepairedAccount};
+use crate::modules::proxy_warmup::WarmupResult;
+use crate::modules::project::{ProjectCandidate, ProjectDiscovery, ProjectSource};
+use crate::modules::replay::ReplayResult;
+use crate::modules::auto_switch::SwitchReason;
+use crate::modules::rotation::RotationStatus;
+use crate::modules::setup::{SetupStatus, SetupStep};
+use crate::utils::tasks::{TaskStatus, TaskSupervisor};
+use crate::proxy::security::{ApiKeySummary, SecuritySummary};
+use crate::proxy::unauthorized::{UnauthorizedReport, UnauthorizedSource};
+use crate::proxy::{ApiKeyEntry, ConfigIssue, ProxyConfig, TokenManager};
+use crate::proxy::ip_filter::{peer_ip, ClientIp, IpAccessList};
+use crate::proxy::key_limiter::KeyUsage;
+use crate::proxy::monitor::{ClientTraffic, LogCursorPage, LogFilter, MonitorStatus, ProxyMonitor, ProxyRequestLog, ProxyStats, RequestKind, ErrorClass, TokenUsage};
+use crate::proxy::sticky_config::StickySessionConfig;
+use crate::proxy::token_manager::{AccountAvailability, ExcludedAccounts, TokenDebugEntry, TokenManagerSnapshot};
 
 // ============================================================================
 // 共享状态
@@ -34,6 +67,30 @@ pub struct WebApiState {
     pub monitor: Arc<RwLock<Option<Arc<ProxyMonitor>>>>,
     /// SSE 广播通道
     pub sse_tx: tokio::sync::broadcast::Sender<SseEvent>,
+    /// 当前连接的 `/api/events` 订阅者数
+    sse_subscribers: Arc<AtomicUsize>,
+    /// 子路径部署时的挂载前缀 (如 `/antigravity`)，根路径部署时为空
+    pub base_path: String,
+    /// 等待回调的 OAuth 授权请求 (state -> PKCE verifier)
+    pub oauth_pending: Arc<modules::oauth::PendingOAuthStore>,
+    /// 本机 OAuth 回调监听
+    oauth_listener: tokio::sync::Mutex<Option<oauth_listener::ActiveListener>>,
+    /// 设备授权 (id -> 状态)
+    device_flows: RwLock<HashMap<String, DeviceFlow>>,
+    /// 命令行指定的管理 API 白名单，非空时覆盖 `web_auth.allowed_ips`
+    cli_allowed_ips: Vec<String>,
+    /// 命令行指定的可信代理，非空时覆盖 `web_auth.trusted_proxies`
+    cli_trusted_proxies: Vec<String>,
+    /// 独立服务端的监听地址，启动反代前据此检查端口冲突
+    listen_addrs: Vec<std::net::SocketAddr>,
+    /// 单独的管理 API 监听地址 (--admin-host/--admin-port)
+    admin_addr: Option<std::net::SocketAddr>,
+    /// 常驻后台任务，恢复备份等切换数据的操作前统一停止
+    pub tasks: TaskSupervisor,
+    /// 当前生效的管理 API 访问控制，随配置保存与文件监听更新，鉴权时不再读取配置文件
+    web_auth: std::sync::RwLock<WebAuthConfig>,
+    /// 配置的 `public_url`，随配置保存更新
+    public_url: std::sync::RwLock<Option<String>>,
 }
 
 /// 反代服务实例 (复用自 commands/proxy.rs)
@@ -42,6 +99,8 @@ pub struct ProxyServiceInstance {
     pub token_manager: Arc<TokenManager>,
     pub axum_server: crate::proxy::AxumServer,
     pub server_handle: tokio::task::JoinHandle<()>,
+    /// 启动时间 (Unix 秒)
+    pub started_at: i64,
 }
 
 /// SSE 事件类型
@@ -50,29 +109,257 @@ pub struct ProxyServiceInstance {
 pub enum SseEvent {
     ProxyRequest(ProxyRequestLog),
     ConfigUpdated,
-    AccountSwitched,
+    AccountSwitched {
+        account_id: String,
+        email: String,
+        name: Option<String>,
+        /// 切换前的账号 (未知时为空)
+        previous_account_id: Option<String>,
+        /// 切换原因：`manual`、`quota_exhausted` 或 `scheduled_rotation`
+        reason: SwitchReason,
+    },
+    UpdateProgress(modules::self_update::UpdateProgress),
+    OAuthCompleted { email: String },
+    /// 从 IDE 数据库同步到了新账号/新 Token
+    DbSynced { email: String },
+    /// Token 健康检查进度
+    TokenValidation {
+        done: usize,
+        total: usize,
+        result: TokenCheck,
+    },
+    /// 账号批量测试进度
+    AccountTest {
+        done: usize,
+        total: usize,
+        result: AccountTestResult,
+    },
+    /// 反代启动预热进度
+    Warmup {
+        done: usize,
+        total: usize,
+        result: WarmupResult,
+    },
+    /// 定时推送的轻量统计，按 `sse_stats_interval_secs` 间隔
+    StatsTick(StatsTick),
+    /// 运行中反代的安全配置 (鉴权模式、API 密钥、白名单) 已变更
+    SecurityUpdated(SecuritySummary),
+    /// 账号文件被外部修改 (如同步工具) 后已重新加载
+    AccountsReloaded {
+        /// 反代重新加载的账号数，反代未运行时为空
+        proxy_accounts: Option<usize>,
+    },
+}
+
+/// 所有 SSE 订阅者共享的一次统计计算结果
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct StatsTick {
+    /// 最近 60 秒的请求数
+    pub requests_last_minute: u64,
+    /// 最近 60 秒的失败数
+    pub errors_last_minute: u64,
+    /// 可参与调度的账号数，反代未运行时为 0
+    pub active_accounts: usize,
+    /// 正在处理的请求数
+    pub in_flight: usize,
+}
+
+impl SseEvent {
+    fn account_switched(account: &Account, previous_account_id: Option<String>, reason: SwitchReason) -> Self {
+        SseEvent::AccountSwitched {
+            account_id: account.id.clone(),
+            email: account.email.clone(),
+            name: account.name.clone(),
+            previous_account_id,
+            reason,
+        }
+    }
 }
 
 impl WebApiState {
     pub fn new() -> Self {
-        let (sse_tx, _) = tokio::sync::broadcast::channel(256);
+        let (sse_tx, _) = tokio::sync::broadcast::channel(crate::models::config::default_sse_channel_capacity());
+        let config = modules::config::load_app_config().ok();
         Self {
             proxy_instance: Arc::new(RwLock::new(None)),
             monitor: Arc::new(RwLock::new(None)),
             sse_tx,
+            sse_subscribers: Arc::new(AtomicUsize::new(0)),
+            base_path: String::new(),
+            oauth_pending: Arc::new(modules::oauth::PendingOAuthStore::default()),
+            oauth_listener: tokio::sync::Mutex::new(None),
+            device_flows: RwLock::new(HashMap::new()),
+            cli_allowed_ips: Vec::new(),
+            cli_trusted_proxies: Vec::new(),
+            listen_addrs: Vec::new(),
+            admin_addr: None,
+            tasks: TaskSupervisor::new(),
+            public_url: std::sync::RwLock::new(config.as_ref().and_then(|config| config.public_url.clone())),
+            web_auth: std::sync::RwLock::new(config.map(|config| config.web_auth).unwrap_or_default()),
+        }
+    }
+
+    /// 设置挂载前缀
+    pub fn with_base_path(mut self, base_path: String) -> Self {
+        self.base_path = base_path;
+        self
+    }
+
+    /// 设置 SSE 广播通道容量，须在订阅前调用
+    pub fn with_event_capacity(mut self, capacity: usize) -> Self {
+        let (sse_tx, _) = tokio::sync::broadcast::channel(capacity.max(1));
+        self.sse_tx = sse_tx;
+        self
+    }
+
+    /// 记录服务端监听地址；`admin` 为单独的管理 API 监听地址
+    pub fn with_listeners(mut self, listen_addrs: Vec<std::net::SocketAddr>, admin: Option<std::net::SocketAddr>) -> Self {
+        self.listen_addrs = listen_addrs;
+        self.admin_addr = admin;
+        self
+    }
+
+    /// 检查反代监听地址是否与服务端自身的监听地址冲突
+    pub fn check_proxy_bind(&self, config: &ProxyConfig) -> Result<(), String> {
+        let proxy_addr = config.bind_socket_addr();
+        match self
+            .listen_addrs
+            .iter()
+            .find(|addr| crate::proxy::config::listeners_conflict(**addr, proxy_addr))
+        {
+            Some(addr) => Err(format!(
+                "反代监听地址 {} 与管理服务监听地址 {} 冲突，请修改反代端口",
+                proxy_addr, addr
+            )),
+            None => Ok(()),
         }
     }
+
+    /// 设置命令行指定的 IP 白名单与可信代理
+    pub fn with_ip_access(mut self, allowed_ips: Vec<String>, trusted_proxies: Vec<String>) -> Self {
+        self.cli_allowed_ips = allowed_ips;
+        self.cli_trusted_proxies = trusted_proxies;
+        self
+    }
+
+    /// 当前生效的管理 API 鉴权配置
+    fn web_auth(&self) -> WebAuthConfig {
+        self.web_auth.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// 当前生效的管理 API 访问控制：命令行优先，其次为应用配置
+    fn ip_access(&self) -> IpAccessList {
+        let config = self.web_auth();
+        let pick = |cli: &Vec<String>, configured: Vec<String>| {
+            if cli.is_empty() { configured } else { cli.clone() }
+        };
+        IpAccessList::new(
+            &pick(&self.cli_allowed_ips, config.allowed_ips),
+            &pick(&self.cli_trusted_proxies, config.trusted_proxies),
+        )
+    }
+
+    /// 客户端视角的服务地址，见 [`PublicOrigin::resolve`]
+    fn public_origin(&self, peer: Option<std::net::IpAddr>, headers: &HeaderMap) -> Option<PublicOrigin> {
+        let configured = self.public_url.read().unwrap_or_else(|e| e.into_inner()).clone();
+        PublicOrigin::resolve(configured.as_deref(), &self.ip_access(), peer, headers, &self.base_path)
+    }
 }
 
 // ============================================================================
 // API 响应类型
 // ============================================================================
 
+/// 错误码，客户端据此判断错误类型；对应文本见语言包的 `errors.api`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum ErrorCode {
+    /// 操作失败 (未细分的错误，原因见 `detail`)
+    OperationFailed,
+    InvalidRequest,
+    InvalidJson,
+    InvalidConfig,
+    Unauthorized,
+    Forbidden,
+    IpNotAllowed,
+    NotFound,
+    AccountNotFound,
+    NoAvailableAccount,
+    ProxyNotRunning,
+    /// 操作会消耗配额，需显式确认
+    ConfirmRequired,
+    PreconditionRequired,
+    /// 配置已被其他客户端修改
+    ConfigConflict,
+    /// 提交的数据与现有数据不一致
+    Conflict,
+    PayloadTooLarge,
+    RateLimited,
+    InternalError,
+}
+
+impl ErrorCode {
+    #[cfg(test)]
+    const ALL: [ErrorCode; 18] = [
+        ErrorCode::OperationFailed,
+        ErrorCode::InvalidRequest,
+        ErrorCode::InvalidJson,
+        ErrorCode::InvalidConfig,
+        ErrorCode::Unauthorized,
+        ErrorCode::Forbidden,
+        ErrorCode::IpNotAllowed,
+        ErrorCode::NotFound,
+        ErrorCode::AccountNotFound,
+        ErrorCode::NoAvailableAccount,
+        ErrorCode::ProxyNotRunning,
+        ErrorCode::ConfirmRequired,
+        ErrorCode::PreconditionRequired,
+        ErrorCode::ConfigConflict,
+        ErrorCode::Conflict,
+        ErrorCode::PayloadTooLarge,
+        ErrorCode::RateLimited,
+        ErrorCode::InternalError,
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::OperationFailed => "operation_failed",
+            ErrorCode::InvalidRequest => "invalid_request",
+            ErrorCode::InvalidJson => "invalid_json",
+            ErrorCode::InvalidConfig => "invalid_config",
+            ErrorCode::Unauthorized => "unauthorized",
+            ErrorCode::Forbidden => "forbidden",
+            ErrorCode::IpNotAllowed => "ip_not_allowed",
+            ErrorCode::NotFound => "not_found",
+            ErrorCode::AccountNotFound => "account_not_found",
+            ErrorCode::NoAvailableAccount => "no_available_account",
+            ErrorCode::ProxyNotRunning => "proxy_not_running",
+            ErrorCode::ConfirmRequired => "confirm_required",
+            ErrorCode::PreconditionRequired => "precondition_required",
+            ErrorCode::ConfigConflict => "config_conflict",
+            ErrorCode::Conflict => "conflict",
+            ErrorCode::PayloadTooLarge => "payload_too_large",
+            ErrorCode::RateLimited => "rate_limited",
+            ErrorCode::InternalError => "internal_error",
+        }
+    }
+
+}
+
 #[derive(Serialize)]
 struct ApiResponse<T> {
     success: bool,
     data: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<ErrorCode>,
+    /// 按请求语言本地化的错误信息
     error: Option<String>,
+    /// 服务端原始错误详情 (中文)，便于排查
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+    /// 请求成功但有需要提示的问题 (如被跳过的损坏账号文件)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
 }
 
 impl<T: Serialize> ApiResponse<T> {
@@ -80,19 +367,508 @@ impl<T: Serialize> ApiResponse<T> {
         Json(Self {
             success: true,
             data: Some(data),
+            code: None,
+            error: None,
+            detail: None,
+            warnings: Vec::new(),
+        })
+    }
+
+    /// 成功并附带警告
+    fn ok_with_warnings(data: T, warnings: Vec<String>) -> Json<Self> {
+        Json(Self {
+            success: true,
+            data: Some(data),
+            code: None,
             error: None,
+            detail: None,
+            warnings,
         })
     }
 
+    /// 未细分的失败，错误码为 `operation_failed`
     fn err(error: impl ToString) -> Json<Self> {
+        Self::fail(ErrorCode::OperationFailed, error)
+    }
+
+    /// 指定错误码的失败，`error` 按当前请求的语言生成
+    fn fail(code: ErrorCode, detail: impl ToString) -> Json<Self> {
+        Self::build(code, detail.to_string(), None)
+    }
+
+    /// 失败但附带数据 (如配置校验问题列表)
+    fn err_with(code: ErrorCode, detail: impl ToString, data: T) -> Json<Self> {
+        Self::build(code, detail.to_string(), Some(data))
+    }
+
+    fn build(code: ErrorCode, detail: String, data: Option<T>) -> Json<Self> {
+        let lang = modules::i18n::request_lang();
         Json(Self {
             success: false,
-            data: None,
-            error: Some(error.to_string()),
+            data,
+            code: Some(code),
+            error: Some(modules::i18n::localize(code.as_str(), Some(&detail), lang)),
+            detail: Some(detail),
+            warnings: Vec::new(),
         })
     }
 }
 
+/// 单个账号的操作失败：账号不在索引中时为 `account_not_found`，否则为 `operation_failed`
+fn account_failure<T: Serialize>(account_id: &str, error: impl ToString) -> Json<ApiResponse<T>> {
+    let exists = modules::load_account_index()
+        .map(|index| index.accounts.iter().any(|a| a.id == account_id))
+        .unwrap_or(true);
+    let code = if exists { ErrorCode::OperationFailed } else { ErrorCode::AccountNotFound };
+    ApiResponse::fail(code, error)
+}
+
+/// 配置未通过校验：422 + 问题列表
+fn invalid_config(issues: Vec<ConfigIssue>) -> Response {
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        ApiResponse::err_with(ErrorCode::InvalidConfig, ConfigIssue::summarize(&issues), issues),
+    )
+        .into_response()
+}
+
+// ============================================================================
+// OpenAPI 文档
+// ============================================================================
+//
+// 所有管理接口都使用 `ApiResponse` 信封：业务错误同样返回 200，
+// 以 `success = false` + `error` 表示；仅请求体无法解析时由 `AppJson` 返回 400。
+// 下面的结构体只用于生成文档，为每种 `data` 类型展开一份具体的信封 schema。
+
+macro_rules! api_response_schema {
+    ($name:ident, $data:ty, $doc:literal) => {
+        #[doc = $doc]
+        #[derive(ToSchema)]
+        #[allow(dead_code)]
+        struct $name {
+            success: bool,
+            data: Option<$data>,
+            code: Option<ErrorCode>,
+            error: Option<String>,
+            detail: Option<String>,
+        }
+    };
+}
+
+/// 无返回数据的响应；`data` 恒为 null。同时也是错误响应的格式：
+/// `code` 为错误码，`error` 按 `lang` 参数或 `Accept-Language` 本地化，`detail` 为服务端原始错误详情
+#[derive(ToSchema)]
+#[allow(dead_code)]
+struct EmptyResponse {
+    success: bool,
+    #[schema(value_type = Option<Object>)]
+    data: Option<serde_json::Value>,
+    code: Option<ErrorCode>,
+    error: Option<String>,
+    detail: Option<String>,
+}
+
+api_response_schema!(AccountResponse, Account, "单个账号");
+/// 账号列表；存在无法读取或解析的账号文件时，`warnings` 列出被跳过的文件
+#[derive(ToSchema)]
+#[allow(dead_code)]
+struct AccountListResponse {
+    success: bool,
+    data: Option<Vec<Account>>,
+    code: Option<ErrorCode>,
+    error: Option<String>,
+    detail: Option<String>,
+    warnings: Option<Vec<String>>,
+}
+api_response_schema!(RepairReportResponse, RepairReport, "账号文件修复结果");
+api_response_schema!(QuotaResponse, QuotaData, "账号配额");
+api_response_schema!(RefreshStatsResponse, RefreshStats, "批量刷新结果");
+api_response_schema!(TokenCheckResponse, TokenCheck, "Token 检查结果");
+api_response_schema!(AccountTestResponse, AccountTestResult, "账号测试请求结果");
+api_response_schema!(AccountTestReportResponse, AccountTestReport, "账号批量测试报告");
+api_response_schema!(AccountTestResultListResponse, Vec<AccountTestResult>, "缓存的账号测试结果");
+api_response_schema!(RotationStatusResponse, RotationStatus, "账号轮换计划状态");
+api_response_schema!(ProjectDiscoveryResponse, ProjectDiscovery, "账号项目发现结果");
+api_response_schema!(TokenValidationReportResponse, TokenValidationReport, "Token 批量检查报告");
+api_response_schema!(AppConfigResponse, AppConfig, "应用配置");
+api_response_schema!(ProxyStatusResponse, ProxyStatus, "反代服务状态");
+api_response_schema!(ProxyStatsResponse, ProxyStats, "请求统计");
+api_response_schema!(MonitorStatusResponse, MonitorStatus, "请求监控开关状态");
+api_response_schema!(DailyStatsResponse, Vec<DailyStats>, "按天汇总的请求统计 (按日期先后)");
+api_response_schema!(UsageHeatmapResponse, UsageHeatmap, "按账号的小时请求热力图");
+api_response_schema!(CostReportResponse, CostReport, "按账号、API 密钥或模型分组的估算费用");
+api_response_schema!(MirrorStatsResponse, crate::proxy::middleware::mirror::MirrorStats, "请求镜像统计");
+api_response_schema!(PromptRulesDryRunResponse, PromptRulesDryRun, "提示词规则试运行结果");
+api_response_schema!(ClearLogsResultResponse, ClearLogsResult, "日志删除结果");
+api_response_schema!(ClearBansResultResponse, ClearBansResult, "解除封禁结果");
+api_response_schema!(ProxyLogsResponse, Vec<ProxyRequestLog>, "请求日志");
+api_response_schema!(ReplayResultResponse, ReplayResult, "请求重放结果");
+api_response_schema!(LogCursorPageResponse, LogCursorPage, "增量拉取的请求日志");
+api_response_schema!(SchedulingResponse, StickySessionConfig, "调度配置");
+api_response_schema!(TokenManagerSnapshotResponse, TokenManagerSnapshot, "账号调度状态快照");
+api_response_schema!(ExperimentalStateResponse, ExperimentalState, "实验性开关");
+api_response_schema!(SecuritySummaryResponse, SecuritySummary, "反代生效的安全配置");
+api_response_schema!(UnauthorizedReportResponse, UnauthorizedReport, "反代鉴权失败统计与封禁列表");
+api_response_schema!(CountResponse, usize, "数量");
+api_response_schema!(StringResponse, String, "字符串结果");
+api_response_schema!(StringListResponse, Vec<String>, "字符串列表");
+api_response_schema!(UpstreamProbeListResponse, Vec<UpstreamProbe>, "上游连通性检查结果");
+api_response_schema!(ApiKeyListResponse, Vec<ApiKeyInfo>, "具名 API 密钥列表");
+api_response_schema!(ApiKeyEntryResponse, ApiKeyEntry, "具名 API 密钥");
+api_response_schema!(KeyUsageResponse, KeyUsage, "具名 API 密钥的限流状态");
+api_response_schema!(OAuthUrlApiResponse, OAuthUrlResponse, "OAuth 授权链接");
+api_response_schema!(DeviceFlowStartResponse, DeviceFlowStart, "设备授权信息");
+api_response_schema!(DeviceFlowStateResponse, DeviceFlowState, "设备授权状态");
+api_response_schema!(UpdateInfoResponse, UpdateInfo, "版本检查结果");
+api_response_schema!(ConfigIssuesResponse, Vec<ConfigIssue>, "配置校验失败，data 为问题列表");
+api_response_schema!(ConfigValidationResponse, ConfigValidation, "配置校验结果");
+api_response_schema!(EffectiveConfigResponse, EffectiveConfig, "生效配置");
+api_response_schema!(SelfUpdateResponse, SelfUpdateResult, "自更新结果");
+api_response_schema!(AuditLogResponse, Vec<AuditEntry>, "审计记录 (按时间先后)");
+api_response_schema!(DashboardResponse, Dashboard, "首页看板汇总");
+api_response_schema!(AccountIdMismatchResponse, AccountIdMismatch, "账号 ID 列表差异");
+api_response_schema!(SystemInfoResponse, SystemInfo, "进程与运行环境信息");
+api_response_schema!(ImportReportResponse, ImportReport, "导入计划与结果");
+api_response_schema!(DedupeReportResponse, DedupeReport, "重复账号合并结果");
+api_response_schema!(DbSyncStatusResponse, DbSyncStatus, "IDE 数据库同步状态");
+api_response_schema!(SeedStatusResponse, SeedStatus, "种子账号导入结果");
+api_response_schema!(RefreshBreakerStatusResponse, RefreshBreakerStatus, "Token 刷新熔断状态");
+api_response_schema!(RestoreSummaryResponse, RestoreSummary, "备份恢复结果");
+api_response_schema!(TaskStatusListResponse, Vec<TaskStatus>, "后台任务状态");
+api_response_schema!(SetupStatusResponse, SetupStatus, "首次运行引导状态");
+api_response_schema!(QuickstartReportResponse, QuickstartReport, "快速配置结果");
+api_response_schema!(ProfileListResponse, ProfileList, "profile 列表");
+
+/// 管理 API 的 OpenAPI 3 文档
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Antigravity Manager API",
+        description = "Antigravity Manager 独立服务端的管理接口"
+    ),
+    paths(
+        list_accounts,
+        add_account,
+        get_current_account,
+        export_accounts_csv,
+        get_account,
+        get_account_requests,
+        delete_account,
+        delete_accounts,
+        switch_account,
+        rotate_account_now,
+        get_rotation_status,
+        fetch_account_quota,
+        refresh_all_quotas,
+        validate_all_tokens,
+        validate_account_token,
+        test_account,
+        test_all_accounts,
+        get_account_test_results,
+        update_account_token,
+        reorder_accounts,
+        move_account,
+        pin_account,
+        set_account_endpoint,
+        discover_account_project,
+        patch_account,
+        dedupe_accounts,
+        repair_accounts,
+        toggle_proxy_status,
+        clear_account_forbidden,
+        load_config,
+        save_config,
+        patch_config,
+        validate_config,
+        get_effective_config,
+        start_proxy_service,
+        stop_proxy_service,
+        get_proxy_status,
+        get_proxy_stats,
+        get_proxy_daily_stats,
+        get_usage_heatmap,
+        get_usage_cost,
+        get_proxy_logs,
+        clear_proxy_logs,
+        replay_proxy_log,
+        get_proxy_monitor_status,
+        set_proxy_monitor_enabled,
+        reload_proxy_accounts,
+        update_model_mapping,
+        get_proxy_experimental,
+        get_proxy_mirror_stats,
+        dry_run_prompt_rules,
+        get_proxy_security,
+        get_proxy_unauthorized,
+        clear_proxy_bans,
+        update_proxy_experimental,
+        get_proxy_scheduling_config,
+        update_proxy_scheduling_config,
+        debug_token_manager,
+        clear_proxy_session_bindings,
+        fetch_zai_models,
+        test_upstream,
+        generate_api_key,
+        list_api_keys,
+        upsert_api_key,
+        delete_api_key,
+        get_api_key_usage,
+        prepare_oauth_url,
+        process_oauth_callback,
+        start_device_flow,
+        get_device_flow,
+        import_v1_accounts,
+        import_from_db,
+        import_custom_db,
+        import_upload,
+        sync_account_from_db,
+        get_db_sync_status,
+        get_data_dir_path,
+        list_profiles,
+        check_for_updates,
+        self_update,
+        clear_log_cache,
+        get_base_path,
+        get_seed_status,
+        get_oauth_status,
+        get_audit_log,
+        get_system_info,
+        get_diagnostics,
+        get_system_backup,
+        restore_system_backup,
+        list_background_tasks,
+        restart_background_task,
+        get_setup_status,
+        complete_setup_step,
+        setup_quickstart,
+        get_dashboard,
+        sse_handler,
+        health_check,
+        openapi_json,
+    ),
+    components(schemas(
+        EmptyResponse,
+        ErrorCode,
+        AccountResponse,
+        AccountListResponse,
+        QuotaResponse,
+        RefreshStatsResponse,
+        TokenCheckResponse,
+        AccountTestResponse,
+        AccountTestReportResponse,
+        AccountTestResultListResponse,
+        ProjectDiscoveryResponse,
+        TokenValidationReportResponse,
+        AppConfigResponse,
+        ProxyStatusResponse,
+        ProxyStatsResponse,
+        DailyStatsResponse,
+        DailyStats,
+        UsageHeatmapResponse,
+        UsageHeatmap,
+        AccountHeatmap,
+        CostReportResponse,
+        CostReport,
+        CostRow,
+        CostGroupBy,
+        ClearLogsResultResponse,
+        ClearLogsResult,
+        ProxyLogsResponse,
+        ReplayResultResponse,
+        ReplayResult,
+        SchedulingResponse,
+        CountResponse,
+        StringResponse,
+        StringListResponse,
+        ApiKeyListResponse,
+        ApiKeyEntryResponse,
+        KeyUsageResponse,
+        KeyUsage,
+        crate::proxy::key_limiter::KeyAccountUsage,
+        OAuthUrlApiResponse,
+        DeviceFlowStartResponse,
+        DeviceFlowStateResponse,
+        UpdateInfoResponse,
+        SelfUpdateResponse,
+        AuditLogResponse,
+        AuditEntry,
+        SystemInfoResponse,
+        SystemInfo,
+        BuildInfo,
+        DiskUsage,
+        MonitorBuffer,
+        EventSubscribers,
+        DashboardResponse,
+        Dashboard,
+        DashboardProxy,
+        RequestCounts,
+        AccountCounts,
+        QuotaTotals,
+        WarningEvent,
+        ConfigIssuesResponse,
+        ConfigValidationResponse,
+        EffectiveConfigResponse,
+        AddAccountRequest,
+        DeleteAccountsRequest,
+        ReorderRequest,
+        MoveAccountRequest,
+        PinAccountRequest,
+        AccountPatchRequest,
+        ProjectDiscovery,
+        ProjectCandidate,
+        ProjectSource,
+        AccountEndpointRequest,
+        AccountIdMismatch,
+        AccountIdMismatchResponse,
+        DedupeRequest,
+        DedupeReportResponse,
+        DedupeReport,
+        DuplicateGroup,
+        RepairReportResponse,
+        RepairReport,
+        RepairedAccount,
+        CorruptedAccount,
+        ToggleProxyStatusRequest,
+        SetMonitorRequest,
+        FetchZaiModelsRequest,
+        GenerateApiKeyRequest,
+        ApiKeyUpsertRequest,
+        ApiKeyInfo,
+        ApiKeyEntry,
+        crate::proxy::AccountGroup,
+        ProcessCallbackRequest,
+        PrepareOAuthRequest,
+        DeviceFlowStart,
+        DeviceFlowState,
+        DeviceFlowStatus,
+        ImportCustomDbRequest,
+        ImportOptions,
+        ImportUploadForm,
+        DbSyncStatusResponse,
+        SeedStatus,
+        SeedStatusResponse,
+        SeedEntryResult,
+        SeedOutcome,
+        RefreshBreakerStatus,
+        RefreshBreakerStatusResponse,
+        BreakerState,
+        DbSyncStatus,
+        RestoreSummaryResponse,
+        RestoreSummary,
+        TaskStatusListResponse,
+        TaskStatus,
+        SetupStatusResponse,
+        SetupStatus,
+        SetupStep,
+        CompleteStepRequest,
+        QuickstartRequest,
+        QuickstartReportResponse,
+        QuickstartReport,
+        DbSyncOutcome,
+        UploadFormat,
+        ImportReportResponse,
+        ImportReport,
+        ImportPlanEntry,
+        PlannedAction,
+        ImportOutcome,
+        ImportAction,
+        OnDuplicate,
+        RefreshStats,
+        ValidateTokensRequest,
+        UpdateTokenRequest,
+        TokenValidationReport,
+        TokenCheck,
+        TokenStatus,
+        AccountTestRequest,
+        TestAllAccountsRequest,
+        AccountTestReport,
+        AccountTestResult,
+        TestErrorKind,
+        ProxyStatus,
+        WarmupResult,
+        OAuthUrlResponse,
+        UpdateInfo,
+        SelfUpdateRequest,
+        ConfigValidation,
+        EffectiveConfig,
+        ConfigIssue,
+        SelfUpdateResult,
+        modules::self_update::UpdatePlan,
+        Account,
+        AppConfig,
+        QuotaData,
+        ProxyConfig,
+        crate::proxy::TimeoutOverrides,
+        ProxyRequestLog,
+        RequestKind,
+        ErrorClass,
+        TokenUsage,
+        crate::proxy::monitor::UpstreamConnectionStats,
+        LogCursorPageResponse,
+        LogCursorPage,
+        ProxyStats,
+        MonitorStatus,
+        MonitorStatusResponse,
+        ClientTraffic,
+        StickySessionConfig,
+        crate::proxy::DnsOverride,
+        UpstreamProbeListResponse,
+        UpstreamProbe,
+        HealthReport,
+        ProxyHealth,
+        crate::utils::panic::TaskError,
+        TokenManagerSnapshotResponse,
+        TokenManagerSnapshot,
+        TokenDebugEntry,
+        AccountAvailability,
+        ExcludedAccounts,
+        ProfileListResponse,
+        ProfileList,
+        RateLimitState,
+        RouteClass,
+        RateLimited,
+        crate::models::WebRateLimits,
+        crate::models::RotationConfig,
+        RotationStatus,
+        RotationStatusResponse,
+        ExperimentalStateResponse,
+        SecuritySummaryResponse,
+        SecuritySummary,
+        ApiKeySummary,
+        crate::proxy::ProxyAuthMode,
+        crate::proxy::config::UnauthorizedLockout,
+        UnauthorizedReportResponse,
+        UnauthorizedReport,
+        UnauthorizedSource,
+        ClearBansResult,
+        ClearBansResultResponse,
+        ExperimentalState,
+        crate::proxy::config::ExperimentalConfig,
+        crate::proxy::config::ExperimentalFlag,
+        crate::proxy::config::MirrorConfig,
+        MirrorStatsResponse,
+        crate::proxy::middleware::mirror::MirrorStats,
+        crate::proxy::config::PromptRule,
+        crate::proxy::config::PromptRuleMatch,
+        crate::proxy::config::PromptRuleAction,
+        PromptRulesDryRunRequest,
+        PromptRulesDryRunResponse,
+        PromptRulesDryRun,
+        ProfileInfo,
+        DataDirSource,
+    )),
+    tags(
+        (name = "accounts", description = "账号管理"),
+        (name = "config", description = "应用配置"),
+        (name = "proxy", description = "反代服务"),
+        (name = "oauth", description = "OAuth 授权"),
+        (name = "import", description = "账号导入与同步"),
+        (name = "system", description = "系统"),
+        (name = "setup", description = "首次运行引导")
+    )
+)]
+pub struct ApiDoc;
+
 // ============================================================================
 // 自定义 JSON 提取器 (返回 JSON 格式错误而非纯文本)
 // ============================================================================
@@ -113,28 +889,468 @@ where
         match axum::extract::Json::<T>::from_request(req, state).await {
             Ok(Json(value)) => Ok(AppJson(value)),
             Err(rejection) => {
-                let error_message = match &rejection {
-                    JsonRejection::JsonDataError(e) => format!("JSON 解析错误: {}", e),
-                    JsonRejection::JsonSyntaxError(e) => format!("JSON 语法错误: {}", e),
-                    JsonRejection::MissingJsonContentType(e) => format!("缺少 Content-Type: {}", e),
-                    _ => format!("请求体错误: {}", rejection),
+                let (code, error_message) = match &rejection {
+                    JsonRejection::JsonDataError(e) => (ErrorCode::InvalidJson, format!("JSON 解析错误: {}", e)),
+                    JsonRejection::JsonSyntaxError(e) => (ErrorCode::InvalidJson, format!("JSON 语法错误: {}", e)),
+                    JsonRejection::MissingJsonContentType(e) => {
+                        (ErrorCode::InvalidRequest, format!("缺少 Content-Type: {}", e))
+                    }
+                    _ => (ErrorCode::InvalidRequest, format!("请求体错误: {}", rejection)),
                 };
-                
-                let body = serde_json::json!({
-                    "success": false,
-                    "data": null,
-                    "error": error_message
-                });
-                
+
                 Err((
                     StatusCode::BAD_REQUEST,
-                    Json(body),
+                    ApiResponse::<()>::fail(code, error_message),
                 ).into_response())
             }
         }
     }
 }
 
+/// 解析可省略的 JSON 请求体，空 body 时使用默认值；解析失败时由 [`invalid_json`] 转换为响应
+fn parse_optional_body<T: DeserializeOwned + Default>(body: &[u8]) -> Result<T, serde_json::Error> {
+    if body.is_empty() {
+        return Ok(T::default());
+    }
+    serde_json::from_slice(body)
+}
+
+/// 请求体 JSON 解析失败：与 `AppJson` 一致的 400
+fn invalid_json(e: serde_json::Error) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        ApiResponse::<()>::fail(ErrorCode::InvalidJson, format!("JSON 解析错误: {}", e)),
+    )
+        .into_response()
+}
+
+// ============================================================================
+// 管理 API 鉴权
+// ============================================================================
+
+/// 通过鉴权的管理令牌名与角色，由鉴权中间件写入请求扩展
+#[derive(Debug, Clone)]
+pub struct WebActor(pub String, pub WebApiRole);
+
+/// 只读令牌读取账号时凭据一律脱敏；admin 令牌与未启用鉴权时返回原值 (前端导出账号需要 refresh_token)
+fn redact_account(actor: Option<&WebActor>, account: &mut Account) {
+    if actor.is_some_and(|actor| actor.1 != WebApiRole::Admin) {
+        account.token = account.token.masked();
+    }
+}
+
+/// 即使是读取也需要 admin 的接口 (响应包含管理令牌等凭据)
+const ADMIN_ONLY_READS: &[&str] = &[
+    "/api/config",
+    "/api/config/effective",
+    "/api/system/backup",
+    "/api/proxy/debug/token-manager",
+];
+
+/// 接口所需的角色：读取请求只需 readonly，其余请求 (包括今后新增的写接口) 一律需要 admin
+fn required_role(method: &Method, path: &str) -> WebApiRole {
+    let is_read = matches!(*method, Method::GET | Method::HEAD);
+    if is_read && !ADMIN_ONLY_READS.contains(&path) {
+        WebApiRole::Readonly
+    } else {
+        WebApiRole::Admin
+    }
+}
+
+/// 从请求中取出管理令牌：`Authorization: Bearer`；事件流 (EventSource 无法设置请求头) 额外支持 `?access_token=`
+fn request_token(request: &Request) -> Option<String> {
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|s| s.strip_prefix("Bearer "))
+        .map(|s| s.trim().to_string());
+    if bearer.is_some() || request.uri().path() != "/api/events" {
+        return bearer;
+    }
+    url::form_urlencoded::parse(request.uri().query()?.as_bytes())
+        .find(|(key, _)| key == "access_token")
+        .map(|(_, value)| value.into_owned())
+}
+
+/// 管理 API 鉴权中间件：配置了 `web_auth.tokens` 时校验令牌与角色，未配置时不鉴权
+async fn web_auth_middleware(
+    State(state): State<Arc<WebApiState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if request.method() == Method::OPTIONS || request.uri().path() == "/api/health" {
+        return next.run(request).await;
+    }
+
+    let auth = state.web_auth();
+    if !auth.is_enabled() {
+        return next.run(request).await;
+    }
+
+    let token = request_token(&request);
+    let Some(entry) = token.as_deref().and_then(|t| auth.authenticate(t)) else {
+        return (
+            StatusCode::UNAUTHORIZED,
+            ApiResponse::<()>::fail(ErrorCode::Unauthorized, "缺少或无效的管理令牌"),
+        )
+            .into_response();
+    };
+
+    let required = required_role(request.method(), request.uri().path());
+    if !entry.role.allows(required) {
+        return (
+            StatusCode::FORBIDDEN,
+            ApiResponse::<()>::fail(ErrorCode::Forbidden, format!(
+                "该操作需要 {} 角色，当前令牌 {} 为 {} 角色",
+                required.as_str(),
+                entry.name,
+                entry.role.as_str()
+            )),
+        )
+            .into_response();
+    }
+
+    request.extensions_mut().insert(WebActor(entry.name.clone(), entry.role));
+    next.run(request).await
+}
+
+/// 管理 API IP 白名单中间件：解析客户端地址，拒绝白名单外的请求 (`/api/health` 除外)
+async fn ip_filter_middleware(
+    State(state): State<Arc<WebApiState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let access = state.ip_access();
+    let client_ip = peer_ip(request.extensions()).map(|peer| access.client_ip(peer, request.headers()));
+    if let Some(ip) = client_ip {
+        request.extensions_mut().insert(ClientIp(ip));
+    }
+
+    if !access.is_restricted() || request.uri().path() == "/api/health" {
+        return next.run(request).await;
+    }
+    match client_ip {
+        Some(ip) if access.is_allowed(ip) => next.run(request).await,
+        _ => {
+            let client = client_ip.map(|ip| ip.to_string()).unwrap_or_else(|| "unknown".to_string());
+            tracing::warn!("拒绝来自 {} 的管理 API 请求 (不在白名单内)", client);
+            (
+                StatusCode::FORBIDDEN,
+                ApiResponse::<()>::fail(ErrorCode::IpNotAllowed, format!("客户端地址 {} 不在允许的范围内", client)),
+            )
+                .into_response()
+        }
+    }
+}
+
+// ============================================================================
+// 外部访问地址
+// ============================================================================
+
+/// 客户端访问本服务所用的地址 (经反向代理时为代理对外的地址)
+#[derive(Debug, Clone, PartialEq)]
+struct PublicOrigin {
+    scheme: String,
+    /// 主机名与端口，如 `tools.example.com`、`192.168.1.10:8765`
+    host: String,
+    /// 子路径，如 `/antigravity`；根路径部署时为空
+    path: String,
+}
+
+impl PublicOrigin {
+    /// 依次采用：配置的 `public_url`；直连对端为可信代理时的 `X-Forwarded-Proto` / `X-Forwarded-Host`；请求的 Host 头。
+    /// 来自不可信地址的转发头一律忽略，无法确定主机时返回 None
+    fn resolve(
+        configured: Option<&str>,
+        access: &IpAccessList,
+        peer: Option<std::net::IpAddr>,
+        headers: &HeaderMap,
+        base_path: &str,
+    ) -> Option<Self> {
+        if let Some(url) = configured
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .and_then(|url| url::Url::parse(url).ok())
+        {
+            let host = url.host_str()?;
+            return Some(Self {
+                scheme: url.scheme().to_string(),
+                host: match url.port() {
+                    Some(port) => format!("{}:{}", host, port),
+                    None => host.to_string(),
+                },
+                path: url.path().trim_end_matches('/').to_string(),
+            });
+        }
+
+        // 多级代理时取最外层 (第一个) 的值
+        let header_value = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.split(',').next())
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        };
+        let trusted = peer.is_some_and(|ip| access.is_trusted_proxy(ip));
+        let forwarded = |name: &str| if trusted { header_value(name) } else { None };
+
+        let scheme = forwarded("x-forwarded-proto")
+            .map(|proto| proto.to_ascii_lowercase())
+            .filter(|proto| proto == "http" || proto == "https")
+            .unwrap_or_else(|| "http".to_string());
+        let host = forwarded("x-forwarded-host").or_else(|| header_value(header::HOST.as_str()))?;
+        Some(Self {
+            scheme,
+            host,
+            path: base_path.to_string(),
+        })
+    }
+
+    fn url(&self) -> String {
+        format!("{}://{}{}", self.scheme, self.host, self.path)
+    }
+}
+
+/// 提取器：本次请求的 [`PublicOrigin`]
+pub struct RequestOrigin(Option<PublicOrigin>);
+
+#[axum::async_trait]
+impl FromRequestParts<Arc<WebApiState>> for RequestOrigin {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<WebApiState>) -> Result<Self, Self::Rejection> {
+        Ok(Self(state.public_origin(peer_ip(&parts.extensions), &parts.headers)))
+    }
+}
+
+/// 去掉 `host[:port]` 中的端口，IPv6 地址保留方括号
+fn strip_port(host: &str) -> &str {
+    match host.find(']') {
+        Some(end) if host.starts_with('[') => &host[..=end],
+        _ => host.split(':').next().unwrap_or_default(),
+    }
+}
+
+/// 主机 (`host[:port]`) 是否为本机地址
+fn is_loopback_host(host: &str) -> bool {
+    let hostname = strip_port(host).trim_start_matches('[').trim_end_matches(']');
+    hostname.eq_ignore_ascii_case("localhost")
+        || hostname.parse::<std::net::IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// 反代 API 的访问地址：优先使用 `proxy.public_url`；反代监听所有网卡时沿用客户端访问管理页面的主机名，否则为本机地址
+fn proxy_base_url(config: &ProxyConfig, origin: Option<&PublicOrigin>) -> String {
+    if let Some(url) = config.public_url.as_deref().map(str::trim).filter(|url| !url.is_empty()) {
+        return url.trim_end_matches('/').to_string();
+    }
+    match origin.map(|origin| strip_port(&origin.host)) {
+        Some(host) if config.allow_lan_access && !is_loopback_host(host) => format!("http://{}:{}", host, config.port),
+        _ => format!("http://127.0.0.1:{}", config.port),
+    }
+}
+
+// ============================================================================
+// 管理 API 限流
+// ============================================================================
+
+#[derive(Serialize, ToSchema)]
+struct RateLimited {
+    retry_after_seconds: u64,
+}
+
+/// 高开销接口 (配额刷新、Token 检查、OAuth、导入) 按客户端限流，优先按管理令牌区分，未鉴权时按 IP
+async fn rate_limit_middleware(State(state): State<Arc<WebApiState>>, request: Request, next: Next) -> Response {
+    let Some(class) = request
+        .extensions()
+        .get::<MatchedPath>()
+        .and_then(|route| RouteClass::of(request.method().as_str(), route.as_str()))
+    else {
+        return next.run(request).await;
+    };
+    let per_minute = class.per_minute(&state.web_auth.read().unwrap_or_else(|e| e.into_inner()).rate_limits);
+    let client = match (request.extensions().get::<WebActor>(), request.extensions().get::<ClientIp>()) {
+        (Some(actor), _) => format!("token:{}", actor.0),
+        (None, Some(ClientIp(ip))) => format!("ip:{}", ip),
+        (None, None) => "unknown".to_string(),
+    };
+
+    match WebRateLimiter::global().check(class, &client, per_minute) {
+        Ok(()) => next.run(request).await,
+        Err(retry_after_seconds) => {
+            tracing::warn!("管理 API 限流: {} 请求 {} 过于频繁", client, request.uri().path());
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after_seconds.to_string())],
+                ApiResponse::err_with(
+                    ErrorCode::RateLimited,
+                    format!("请求过于频繁，每分钟最多 {} 次，请 {} 秒后重试", per_minute, retry_after_seconds),
+                    RateLimited { retry_after_seconds },
+                ),
+            )
+                .into_response()
+        }
+    }
+}
+
+// ============================================================================
+// 审计日志
+// ============================================================================
+
+/// 写操作审计中间件：记录操作者、脱敏后的请求摘要与结果
+///
+/// 配置保存不记录请求体，只记录变化的顶层字段。
+async fn audit_middleware(request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    if matches!(method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        return next.run(request).await;
+    }
+
+    let path = request.uri().path().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| path.clone());
+    let actor = request
+        .extensions()
+        .get::<WebActor>()
+        .map(|actor| actor.0.clone())
+        .unwrap_or_else(|| modules::audit::ANONYMOUS_ACTOR.to_string());
+    let is_config_save = path == "/api/config";
+
+    let (parts, body) = request.into_parts();
+    let (body, summary) = if is_config_save {
+        (body, None)
+    } else {
+        match summarize_request_body(&parts.headers, body).await {
+            Ok(result) => result,
+            Err(response) => return response,
+        }
+    };
+    let before = if is_config_save { config_snapshot() } else { None };
+
+    let response = next.run(Request::from_parts(parts, body)).await;
+
+    let changed_keys = match (before, is_config_save.then(config_snapshot).flatten()) {
+        (Some(before), Some(after)) => Some(modules::audit::changed_top_level_keys(&before, &after)),
+        _ => None,
+    };
+    let (response, success, error) = response_outcome(response).await;
+
+    let entry = AuditEntry {
+        timestamp: chrono::Utc::now().timestamp(),
+        method: method.to_string(),
+        route,
+        path,
+        actor,
+        request: summary,
+        changed_keys,
+        status: response.status().as_u16(),
+        success,
+        error,
+    };
+    if let Err(e) = modules::audit::append(&entry) {
+        modules::logger::log_warn(&format!("写入审计日志失败: {}", e));
+    }
+    response
+}
+
+fn config_snapshot() -> Option<serde_json::Value> {
+    modules::config::load_app_config()
+        .ok()
+        .and_then(|config| serde_json::to_value(config).ok())
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"))
+}
+
+/// 审计读取请求体/响应体的上限，与 axum 默认的 `DefaultBodyLimit` 一致
+const AUDIT_BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+/// 请求/响应体的已知大小：优先取 Content-Length，否则取 body 的确切大小
+fn known_body_size(headers: &HeaderMap, body: &axum::body::Body) -> Option<u64> {
+    headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+        .or_else(|| axum::body::HttpBody::size_hint(body).exact())
+}
+
+/// 读取 JSON 请求体生成脱敏摘要，并还原请求体供后续处理
+///
+/// 其他类型 (如上传文件)、大小未知或超过 [`AUDIT_BODY_LIMIT`] 的请求体不读取，只记录类型与大小。
+async fn summarize_request_body(
+    headers: &HeaderMap,
+    body: axum::body::Body,
+) -> Result<(axum::body::Body, Option<serde_json::Value>), Response> {
+    let size = known_body_size(headers, &body);
+    if !is_json(headers) || size.is_none_or(|size| size > AUDIT_BODY_LIMIT as u64) {
+        let summary = headers.get(header::CONTENT_TYPE).map(|content_type| {
+            serde_json::json!({
+                "content_type": content_type.to_str().unwrap_or_default(),
+                "bytes": size,
+            })
+        });
+        return Ok((body, summary));
+    }
+
+    let bytes = axum::body::to_bytes(body, AUDIT_BODY_LIMIT).await.map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            ApiResponse::<()>::fail(ErrorCode::InvalidRequest, format!("读取请求体失败: {}", e)),
+        )
+            .into_response()
+    })?;
+    let summary = serde_json::from_slice::<serde_json::Value>(&bytes)
+        .ok()
+        .map(|value| modules::audit::redact(&value));
+    Ok((axum::body::Body::from(bytes), summary))
+}
+
+/// 从 `{success, error}` 响应信封中取出结果；非 JSON 或超过 [`AUDIT_BODY_LIMIT`] 的响应按状态码判断
+async fn response_outcome(response: Response) -> (Response, bool, Option<String>) {
+    let size = known_body_size(response.headers(), response.body());
+    if !is_json(response.headers()) || size.is_none_or(|size| size > AUDIT_BODY_LIMIT as u64) {
+        let success = response.status().is_success();
+        return (response, success, None);
+    }
+
+    let (parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, AUDIT_BODY_LIMIT).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            let response = Response::from_parts(parts, axum::body::Body::empty());
+            return (response, false, Some(format!("读取响应失败: {}", e)));
+        }
+    };
+    let envelope: serde_json::Value = serde_json::from_slice(&bytes).unwrap_or_default();
+    let success = parts.status.is_success()
+        && envelope.get("success").and_then(|v| v.as_bool()).unwrap_or(true);
+    // 审计日志统一使用日志语言，不随请求语言变化
+    let detail = envelope.get("detail").and_then(|v| v.as_str());
+    let error = match envelope.get("code").and_then(|v| v.as_str()) {
+        Some(code) => Some(modules::i18n::log_text(code, detail)),
+        None => envelope.get("error").and_then(|v| v.as_str()).map(str::to_string),
+    };
+    (Response::from_parts(parts, axum::body::Body::from(bytes)), success, error)
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct AuditQuery {
+    /// 最多返回的条数，默认 100，最大 1000
+    limit: Option<usize>,
+    /// 只返回该 Unix 时间戳 (秒) 及之后的记录
+    since: Option<i64>,
+}
+
 // ============================================================================
 // 路由构建
 // ============================================================================
@@ -147,70 +1363,387 @@ pub fn create_api_router(state: Arc<WebApiState>) -> Router {
         .route("/api/accounts", get(list_accounts))
         .route("/api/accounts", post(add_account))
         .route("/api/accounts/current", get(get_current_account))
+        .route("/api/accounts/rotate-now", post(rotate_account_now))
+        .route("/api/accounts/rotation", get(get_rotation_status))
+        .route("/api/accounts/export.csv", get(export_accounts_csv))
+        .route("/api/accounts/:id", get(get_account))
+        .route("/api/accounts/:id/requests", get(get_account_requests))
         .route("/api/accounts/:id", delete(delete_account))
         .route("/api/accounts/batch-delete", post(delete_accounts))
         .route("/api/accounts/:id/switch", post(switch_account))
         .route("/api/accounts/:id/quota", post(fetch_account_quota))
         .route("/api/accounts/refresh-all", post(refresh_all_quotas))
+        .route("/api/accounts/validate-all", post(validate_all_tokens))
+        .route("/api/accounts/:id/validate", post(validate_account_token))
+        .route("/api/accounts/:id/test", post(test_account))
+        .route("/api/accounts/test-all", post(test_all_accounts))
+        .route("/api/accounts/test-results", get(get_account_test_results))
+        .route("/api/accounts/:id/token", put(update_account_token))
         .route("/api/accounts/reorder", post(reorder_accounts))
+        .route("/api/accounts/:id/move", post(move_account))
+        .route("/api/accounts/:id/pin", post(pin_account))
+        .route("/api/accounts/:id/endpoint", put(set_account_endpoint))
+        .route("/api/accounts/:id/discover-project", post(discover_account_project))
+        .route("/api/accounts/:id", patch(patch_account))
+        .route("/api/accounts/dedupe", post(dedupe_accounts))
+        .route("/api/accounts/repair", post(repair_accounts))
         .route("/api/accounts/:id/proxy-status", post(toggle_proxy_status))
+        .route("/api/accounts/:id/clear-forbidden", post(clear_account_forbidden))
         // 配置
         .route("/api/config", get(load_config))
         .route("/api/config", put(save_config))
+        .route("/api/config", patch(patch_config))
+        .route("/api/config/validate", post(validate_config))
+        .route("/api/config/effective", get(get_effective_config))
         // 反代服务
         .route("/api/proxy/start", post(start_proxy_service))
         .route("/api/proxy/stop", post(stop_proxy_service))
         .route("/api/proxy/status", get(get_proxy_status))
         .route("/api/proxy/stats", get(get_proxy_stats))
+        .route("/api/proxy/stats/daily", get(get_proxy_daily_stats))
+        .route("/api/proxy/usage/heatmap", get(get_usage_heatmap))
+        .route("/api/proxy/usage/cost", get(get_usage_cost))
         .route("/api/proxy/logs", get(get_proxy_logs))
         .route("/api/proxy/logs", delete(clear_proxy_logs))
-        .route("/api/proxy/monitor", post(set_proxy_monitor_enabled))
+        .route("/api/proxy/logs/:id/replay", post(replay_proxy_log))
+        .route("/api/proxy/monitor", get(get_proxy_monitor_status).post(set_proxy_monitor_enabled).put(set_proxy_monitor_enabled))
         .route("/api/proxy/reload-accounts", post(reload_proxy_accounts))
         .route("/api/proxy/model-mapping", put(update_model_mapping))
+        .route("/api/proxy/experimental", get(get_proxy_experimental))
+        .route("/api/proxy/mirror/stats", get(get_proxy_mirror_stats))
+        .route("/api/proxy/prompt-rules/dry-run", post(dry_run_prompt_rules))
+        .route("/api/proxy/security", get(get_proxy_security))
+        .route("/api/proxy/security/unauthorized", get(get_proxy_unauthorized))
+        .route("/api/proxy/security/unauthorized", delete(clear_proxy_bans))
+        .route("/api/proxy/experimental", put(update_proxy_experimental))
         .route("/api/proxy/scheduling", get(get_proxy_scheduling_config))
         .route("/api/proxy/scheduling", put(update_proxy_scheduling_config))
+        .route("/api/proxy/debug/token-manager", get(debug_token_manager))
         .route("/api/proxy/sessions", delete(clear_proxy_session_bindings))
         .route("/api/proxy/zai-models", post(fetch_zai_models))
+        .route("/api/proxy/test-upstream", post(test_upstream))
         .route("/api/proxy/generate-api-key", post(generate_api_key))
+        .route("/api/proxy/api-keys", get(list_api_keys))
+        .route("/api/proxy/api-keys", post(upsert_api_key))
+        .route("/api/proxy/api-keys/:name", delete(delete_api_key))
+        .route("/api/proxy/api-keys/:name/usage", get(get_api_key_usage))
         // OAuth (Web 模式简化版)
         .route("/api/oauth/prepare-url", post(prepare_oauth_url))
         .route("/api/oauth/process-callback", post(process_oauth_callback))
+        .route("/api/oauth/device/start", post(start_device_flow))
+        .route("/api/oauth/device/:id", get(get_device_flow))
         // 导入
 
         .route("/api/import/v1", post(import_v1_accounts))
         .route("/api/import/db", post(import_from_db))
         .route("/api/import/custom-db", post(import_custom_db))
+        .route(
+            "/api/import/upload",
+            post(import_upload).layer(DefaultBodyLimit::max(MAX_IMPORT_UPLOAD_BYTES + 1024 * 1024)),
+        )
         .route("/api/sync/db", post(sync_account_from_db))
+        .route("/api/sync/status", get(get_db_sync_status))
         // 系统
         .route("/api/system/data-dir", get(get_data_dir_path))
+        .route("/api/system/profiles", get(list_profiles))
         .route("/api/system/check-updates", get(check_for_updates))
+        .route("/api/system/update", post(self_update))
         .route("/api/system/clear-logs", post(clear_log_cache))
+        .route("/api/system/base-path", get(get_base_path))
+        .route("/api/system/seed-status", get(get_seed_status))
+        .route("/api/system/oauth-status", get(get_oauth_status))
+        .route("/api/system/audit", get(get_audit_log))
+        .route("/api/system/info", get(get_system_info))
+        .route("/api/system/diagnostics", get(get_diagnostics))
+        .route("/api/system/backup", get(get_system_backup))
+        .route(
+            "/api/system/restore",
+            post(restore_system_backup).layer(DefaultBodyLimit::max(MAX_RESTORE_UPLOAD_BYTES)),
+        )
+        .route("/api/system/tasks", get(list_background_tasks))
+        .route("/api/system/tasks/:name/restart", post(restart_background_task))
+        // 首次运行引导
+        .route("/api/setup/status", get(get_setup_status))
+        .route("/api/setup/complete-step", post(complete_setup_step))
+        .route("/api/setup/quickstart", post(setup_quickstart))
+        // 看板
+        .route("/api/dashboard", get(get_dashboard))
         // SSE 事件流
         .route("/api/events", get(sse_handler))
         // 健康检查
         .route("/api/health", get(health_check))
+        // OpenAPI 文档
+        .route("/api/openapi.json", get(openapi_json))
+        // 审计在鉴权之后执行，以便记录操作者
+        .route_layer(axum::middleware::from_fn(audit_middleware))
+        // 限流需要路由模板与操作者，同样在鉴权之后；被限流的请求不进入审计
+        .route_layer(axum::middleware::from_fn_with_state(state.clone(), rate_limit_middleware))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), web_auth_middleware))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), ip_filter_middleware))
+        .layer(axum::middleware::from_fn(catch_panic_middleware))
+        .layer(axum::middleware::from_fn(locale_middleware))
         .with_state(state)
 }
 
+/// 请求指定的语言：`lang` 参数优先，其次 `Accept-Language`；均未指定或不支持时为 None (使用配置的界面语言)
+fn requested_lang(request: &Request) -> Option<modules::i18n::Lang> {
+    let from_query = request.uri().query().and_then(|query| {
+        url::form_urlencoded::parse(query.as_bytes())
+            .find(|(key, _)| key == "lang")
+            .and_then(|(_, value)| modules::i18n::Lang::parse(&value))
+    });
+    from_query.or_else(|| {
+        request
+            .headers()
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(modules::i18n::from_accept_language)
+    })
+}
+
+/// 确定本次请求错误信息的语言；位于最外层，鉴权、限流与 panic 的错误同样本地化
+async fn locale_middleware(request: Request, next: Next) -> Response {
+    match requested_lang(&request) {
+        Some(lang) => modules::i18n::scope(lang, next.run(request)).await,
+        None => next.run(request).await,
+    }
+}
+
+/// handler panic 时返回 500 与 ApiResponse 格式的错误，而不是直接断开连接
+async fn catch_panic_middleware(request: Request, next: Next) -> Response {
+    match crate::utils::panic::run_catching(request, next).await {
+        Ok(response) => response,
+        Err(caught) => crate::utils::panic::with_request_id(
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ApiResponse::<()>::fail(ErrorCode::InternalError, format!("服务内部错误 (request_id: {}): {}", caught.request_id, caught.message)),
+            )
+                .into_response(),
+            &caught.request_id,
+        ),
+    }
+}
+
 // ============================================================================
 // 账号管理 API
 // ============================================================================
 
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ListAccountsQuery {
+    /// 排序方式：`last_used` 按最近被反代使用时间升序 (从未使用的排在最前)；
+    /// `reset` 按配额重置时间升序 (最快重置的在前，未知的排在最后)；缺省保持列表顺序
+    sort_by: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/accounts",
+    tag = "accounts",
+    params(ListAccountsQuery),
+    responses(
+        (status = 200, description = "列出所有账号", body = AccountListResponse),
+    )
+)]
 async fn list_accounts(
-    State(_state): State<Arc<WebApiState>>,
+    State(state): State<Arc<WebApiState>>,
+    actor: Option<axum::Extension<WebActor>>,
+    Query(query): Query<ListAccountsQuery>,
+) -> impl IntoResponse {
+    let (mut accounts, corrupted) = match modules::account::list_accounts_checked() {
+        Ok(listing) => listing,
+        Err(e) => return ApiResponse::<Vec<Account>>::err(e),
+    };
+
+    for account in accounts.iter_mut() {
+        merge_pending_usage(&state, account).await;
+        redact_account(actor.as_deref(), account);
+    }
+
+    if let Err(e) = sort_accounts(&mut accounts, query.sort_by.as_deref()) {
+        return ApiResponse::<Vec<Account>>::err(e);
+    }
+
+    ApiResponse::ok_with_warnings(accounts, corrupted.iter().map(CorruptedAccount::warning).collect())
+}
+
+fn sort_accounts(accounts: &mut [Account], sort_by: Option<&str>) -> Result<(), String> {
+    match sort_by {
+        None => {}
+        Some("last_used") => accounts.sort_by_key(|a| a.last_used_at.unwrap_or(0)),
+        Some("reset") => accounts.sort_by_key(|a| {
+            a.quota.as_ref().and_then(|q| q.resets_at).unwrap_or(i64::MAX)
+        }),
+        Some(other) => return Err(format!("不支持的排序方式: {}", other)),
+    }
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/accounts/export.csv",
+    tag = "accounts",
+    params(ListAccountsQuery),
+    responses(
+        (status = 200, description = "导出账号 CSV (email, name, tags, status, quota_limit, quota_remaining, quota_updated_at, last_used_at, total_requests)，不包含任何 token；筛选参数与账号列表相同", content_type = "text/csv"),
+    )
+)]
+async fn export_accounts_csv(
+    State(state): State<Arc<WebApiState>>,
+    Query(query): Query<ListAccountsQuery>,
+) -> Response {
+    let mut accounts = match modules::list_accounts() {
+        Ok(accounts) => accounts,
+        Err(e) => return ApiResponse::<()>::err(e).into_response(),
+    };
+    for account in accounts.iter_mut() {
+        merge_pending_usage(&state, account).await;
+    }
+    if let Err(e) = sort_accounts(&mut accounts, query.sort_by.as_deref()) {
+        return ApiResponse::<()>::err(e).into_response();
+    }
+
+    // 逐行输出，账号较多时不必一次拼接整个文件
+    let lines = std::iter::once(modules::account_export::header_line())
+        .chain(accounts.into_iter().map(|account| modules::account_export::account_line(&account)))
+        .map(|line| Ok::<_, std::convert::Infallible>(axum::body::Bytes::from(line)));
+    let filename = modules::account_export::export_filename(chrono::Utc::now());
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        axum::body::Body::from_stream(futures::stream::iter(lines)),
+    )
+        .into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/accounts/{id}",
+    tag = "accounts",
+    params(("id" = String, Path, description = "账号 ID")),
+    responses(
+        (status = 200, description = "获取单个账号", body = AccountResponse),
+    )
+)]
+async fn get_account(
+    State(state): State<Arc<WebApiState>>,
+    actor: Option<axum::Extension<WebActor>>,
+    Path(account_id): Path<String>,
 ) -> impl IntoResponse {
-    match modules::list_accounts() {
-        Ok(accounts) => ApiResponse::ok(accounts),
-        Err(e) => ApiResponse::<Vec<Account>>::err(e),
+    match modules::load_account(&account_id) {
+        Ok(mut account) => {
+            merge_pending_usage(&state, &mut account).await;
+            redact_account(actor.as_deref(), &mut account);
+            if let Some(quota) = account.quota.as_mut() {
+                quota.update_countdown(chrono::Utc::now().timestamp());
+            }
+            ApiResponse::ok(account)
+        }
+        Err(e) => account_failure::<Account>(&account_id, e),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct AccountRequestsQuery {
+    /// 返回条数上限 (默认 100，最大 1000)
+    limit: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/accounts/{id}/requests",
+    tag = "accounts",
+    params(("id" = String, Path, description = "账号 ID"), AccountRequestsQuery),
+    responses(
+        (status = 200, description = "该账号最近处理的反代请求 (从新到旧，不含请求/响应体)；反代未运行时从持久化的请求日志读取", body = ProxyLogsResponse),
+        (status = 404, description = "账号不存在"),
+    )
+)]
+async fn get_account_requests(
+    State(state): State<Arc<WebApiState>>,
+    Path(account_id): Path<String>,
+    Query(query): Query<AccountRequestsQuery>,
+) -> Response {
+    let exists = match modules::load_account_index() {
+        Ok(index) => index.accounts.iter().any(|a| a.id == account_id),
+        Err(e) => return ApiResponse::<()>::err(e).into_response(),
+    };
+    if !exists {
+        return (
+            StatusCode::NOT_FOUND,
+            ApiResponse::<()>::fail(ErrorCode::AccountNotFound, format!("账号不存在: {}", account_id)),
+        )
+            .into_response();
+    }
+    let email = match modules::load_account(&account_id) {
+        Ok(account) => account.email,
+        Err(e) => return ApiResponse::<()>::err(e).into_response(),
+    };
+
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    let result = match state.monitor.read().await.as_ref() {
+        Some(monitor) => Ok(monitor.get_logs_filtered(limit, None, Some(&email), None).await),
+        None => modules::proxy_db::get_logs_filtered(limit, 0, None, Some(&email), None),
+    };
+    match result {
+        Ok(logs) => ApiResponse::ok(logs).into_response(),
+        Err(e) => ApiResponse::<Vec<ProxyRequestLog>>::err(e).into_response(),
+    }
+}
+
+/// 当前账号配额耗尽自动切换或定时轮换后推送 `AccountSwitched`
+pub fn watch_auto_switch(state: &WebApiState) {
+    let sse_tx = state.sse_tx.clone();
+    modules::auto_switch::set_listener(move |switch| {
+        let _ = sse_tx.send(SseEvent::account_switched(
+            &switch.account,
+            Some(switch.previous_account_id.clone()),
+            switch.reason,
+        ));
+    });
+}
+
+/// 退出前保存反代中尚未落盘的使用统计
+pub async fn flush_proxy_usage(state: &WebApiState) {
+    if let Some(instance) = state.proxy_instance.read().await.as_ref() {
+        instance.token_manager.flush_usage();
+    }
+    if let Some(monitor) = state.monitor.read().await.as_ref() {
+        monitor.flush_daily_stats();
+    }
+}
+
+/// 合并反代中尚未落盘的使用统计
+async fn merge_pending_usage(state: &WebApiState, account: &mut Account) {
+    let instance = state.proxy_instance.read().await;
+    if let Some(usage) = instance
+        .as_ref()
+        .and_then(|i| i.token_manager.pending_usage(&account.id))
+    {
+        account.total_requests += usage.requests;
+        account.last_used_at = Some(usage.last_used_at);
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct AddAccountRequest {
     email: String,
     refresh_token: String,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/accounts",
+    tag = "accounts",
+    request_body = AddAccountRequest,
+    responses(
+        (status = 200, description = "通过 refresh_token 添加账号", body = AccountResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
 async fn add_account(
     State(state): State<Arc<WebApiState>>,
     AppJson(req): AppJson<AddAccountRequest>,
@@ -255,10 +1788,53 @@ async fn add_account(
     }
 }
 
-async fn get_current_account(
-    State(_state): State<Arc<WebApiState>>,
-) -> impl IntoResponse {
-    let result = || -> Result<Option<Account>, String> {
+#[utoipa::path(
+    post,
+    path = "/api/accounts/rotate-now",
+    tag = "accounts",
+    responses(
+        (status = 200, description = "立即将当前账号轮换到候选列表中的下一个可用账号 (不要求启用定时轮换)，并从此刻重新计时；推送 reason 为 scheduled_rotation 的 AccountSwitched", body = RotationStatusResponse),
+    )
+)]
+async fn rotate_account_now() -> impl IntoResponse {
+    match modules::rotation::rotate_now().await {
+        Ok(Some(_)) => match modules::rotation::status() {
+            Ok(status) => ApiResponse::ok(status),
+            Err(e) => ApiResponse::<RotationStatus>::err(e),
+        },
+        Ok(None) => ApiResponse::<RotationStatus>::fail(ErrorCode::NoAvailableAccount, "没有可轮换的账号"),
+        Err(e) => ApiResponse::<RotationStatus>::err(e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/accounts/rotation",
+    tag = "accounts",
+    responses(
+        (status = 200, description = "账号定时轮换状态：下次轮换时间与账号、上次轮换时间与账号", body = RotationStatusResponse),
+    )
+)]
+async fn get_rotation_status() -> impl IntoResponse {
+    match modules::rotation::status() {
+        Ok(status) => ApiResponse::ok(status),
+        Err(e) => ApiResponse::<RotationStatus>::err(e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/accounts/current",
+    tag = "accounts",
+    responses(
+        (status = 200, description = "获取当前账号 (未设置时 data 为 null)", body = AccountResponse),
+    )
+)]
+async fn get_current_account(
+    State(_state): State<Arc<WebApiState>>,
+    actor: Option<axum::Extension<WebActor>>,
+) -> impl IntoResponse {
+    let result = || -> Result<Option<Account>, String> {
         let account_id = modules::get_current_account_id()?;
         if let Some(id) = account_id {
             modules::load_account(&id).map(Some)
@@ -268,11 +1844,25 @@ async fn get_current_account(
     };
 
     match result() {
-        Ok(account) => ApiResponse::ok(account),
+        Ok(mut account) => {
+            if let Some(account) = account.as_mut() {
+                redact_account(actor.as_deref(), account);
+            }
+            ApiResponse::ok(account)
+        }
         Err(e) => ApiResponse::<Option<Account>>::err(e),
     }
 }
 
+#[utoipa::path(
+    delete,
+    path = "/api/accounts/{id}",
+    tag = "accounts",
+    params(("id" = String, Path, description = "账号 ID")),
+    responses(
+        (status = 200, description = "删除账号", body = EmptyResponse),
+    )
+)]
 async fn delete_account(
     State(state): State<Arc<WebApiState>>,
     Path(account_id): Path<String>,
@@ -282,15 +1872,25 @@ async fn delete_account(
             reload_proxy_accounts_internal(&state).await;
             ApiResponse::ok(())
         }
-        Err(e) => ApiResponse::<()>::err(e),
+        Err(e) => account_failure::<()>(&account_id, e),
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct DeleteAccountsRequest {
     account_ids: Vec<String>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/accounts/batch-delete",
+    tag = "accounts",
+    request_body = DeleteAccountsRequest,
+    responses(
+        (status = 200, description = "批量删除账号", body = EmptyResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
 async fn delete_accounts(
     State(state): State<Arc<WebApiState>>,
     AppJson(req): AppJson<DeleteAccountsRequest>,
@@ -304,20 +1904,46 @@ async fn delete_accounts(
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/accounts/{id}/switch",
+    tag = "accounts",
+    params(("id" = String, Path, description = "账号 ID")),
+    responses(
+        (status = 200, description = "切换当前账号", body = EmptyResponse),
+    )
+)]
 async fn switch_account(
     State(state): State<Arc<WebApiState>>,
     Path(account_id): Path<String>,
 ) -> impl IntoResponse {
+    let previous_account_id = modules::get_current_account_id().ok().flatten();
     match modules::switch_account(&account_id).await {
         Ok(()) => {
             // 广播账号切换事件
-            let _ = state.sse_tx.send(SseEvent::AccountSwitched);
+            match modules::load_account(&account_id) {
+                Ok(account) => {
+                    let _ = state
+                        .sse_tx
+                        .send(SseEvent::account_switched(&account, previous_account_id, SwitchReason::Manual));
+                }
+                Err(e) => tracing::warn!("读取切换后的账号失败: {}", e),
+            }
             ApiResponse::ok(())
         }
-        Err(e) => ApiResponse::<()>::err(e),
+        Err(e) => account_failure::<()>(&account_id, e),
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/accounts/{id}/quota",
+    tag = "accounts",
+    params(("id" = String, Path, description = "账号 ID")),
+    responses(
+        (status = 200, description = "刷新单个账号配额", body = QuotaResponse),
+    )
+)]
 async fn fetch_account_quota(
     State(_state): State<Arc<WebApiState>>,
     Path(account_id): Path<String>,
@@ -334,29 +1960,41 @@ async fn fetch_account_quota(
 
     match result {
         Ok(quota) => ApiResponse::ok(quota),
-        Err(e) => ApiResponse::<QuotaData>::err(e),
+        Err(e) => account_failure::<QuotaData>(&account_id, e),
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 struct RefreshStats {
     total: usize,
     success: usize,
     failed: usize,
+    /// 本次因 403 / 账号停用被标记为 forbidden 的账号数，后续刷新自动跳过
+    forbidden: usize,
+    /// 失败与被标记 forbidden 的账号明细 ("<邮箱>: marked forbidden - <原因>" 或 "<邮箱>: <错误>")
     details: Vec<String>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/accounts/refresh-all",
+    tag = "accounts",
+    responses(
+        (status = 200, description = "刷新所有账号配额", body = RefreshStatsResponse),
+    )
+)]
 async fn refresh_all_quotas(
-    State(_state): State<Arc<WebApiState>>,
+    State(state): State<Arc<WebApiState>>,
 ) -> impl IntoResponse {
     let result = async {
         let accounts = modules::list_accounts()?;
         let mut success = 0;
         let mut failed = 0;
+        let mut forbidden = 0;
         let mut details = Vec::new();
 
         for mut account in accounts {
-            if account.disabled {
+            if account.disabled || account.region_blocked {
                 continue;
             }
             if let Some(ref q) = account.quota {
@@ -367,10 +2005,19 @@ async fn refresh_all_quotas(
 
             match modules::account::fetch_quota_with_retry(&mut account).await {
                 Ok(quota) => {
-                    if modules::update_account_quota(&account.id, quota).is_ok() {
-                        success += 1;
-                    } else {
-                        failed += 1;
+                    let reason = quota.is_forbidden.then(|| {
+                        quota.forbidden_reason.clone().unwrap_or_else(|| "403 Forbidden".to_string())
+                    });
+                    match (modules::update_account_quota(&account.id, quota), reason) {
+                        (Err(e), _) => {
+                            failed += 1;
+                            details.push(format!("{}: {}", account.email, e));
+                        }
+                        (Ok(()), Some(reason)) => {
+                            forbidden += 1;
+                            details.push(format!("{}: marked forbidden - {}", account.email, reason));
+                        }
+                        (Ok(()), None) => success += 1,
                     }
                 }
                 Err(e) => {
@@ -381,13 +2028,17 @@ async fn refresh_all_quotas(
         }
 
         Ok::<_, String>(RefreshStats {
-            total: success + failed,
+            total: success + failed + forbidden,
             success,
             failed,
+            forbidden,
             details,
         })
     }
     .await;
+    if result.as_ref().is_ok_and(|stats| stats.forbidden > 0) {
+        reload_proxy_accounts_internal(&state).await;
+    }
 
     match result {
         Ok(stats) => ApiResponse::ok(stats),
@@ -395,843 +2046,4456 @@ async fn refresh_all_quotas(
     }
 }
 
-#[derive(Deserialize)]
-struct ReorderRequest {
-    account_ids: Vec<String>,
-}
-
-async fn reorder_accounts(
-    State(_state): State<Arc<WebApiState>>,
-    AppJson(req): AppJson<ReorderRequest>,
-) -> impl IntoResponse {
-    match modules::account::reorder_accounts(&req.account_ids) {
-        Ok(()) => ApiResponse::ok(()),
-        Err(e) => ApiResponse::<()>::err(e),
-    }
+#[derive(Deserialize, Default, ToSchema)]
+struct ValidateTokensRequest {
+    /// 是否自动禁用 Token 失效账号的反代
+    #[serde(default)]
+    disable_invalid: bool,
 }
 
-#[derive(Deserialize)]
-struct ToggleProxyStatusRequest {
-    enable: bool,
-    reason: Option<String>,
+/// Token 批量检查报告
+#[derive(Serialize, ToSchema)]
+struct TokenValidationReport {
+    total: usize,
+    valid: usize,
+    invalid: usize,
+    network_error: usize,
+    /// 本次被禁用反代的账号数
+    disabled: usize,
+    results: Vec<TokenCheck>,
 }
 
-async fn toggle_proxy_status(
+#[utoipa::path(
+    post,
+    path = "/api/accounts/validate-all",
+    tag = "accounts",
+    request_body(content = Option<ValidateTokensRequest>, description = "可选"),
+    responses(
+        (status = 200, description = "检查所有账号的 Token，进度通过 SSE TokenValidation 事件推送", body = TokenValidationReportResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn validate_all_tokens(
     State(state): State<Arc<WebApiState>>,
-    Path(account_id): Path<String>,
-    AppJson(req): AppJson<ToggleProxyStatusRequest>,
-) -> impl IntoResponse {
-    // 简化版：直接修改账号文件
-    let result = || -> Result<(), String> {
-        let data_dir = modules::account::get_data_dir()?;
-        let account_path = data_dir.join("accounts").join(format!("{}.json", account_id));
-
-        if !account_path.exists() {
-            return Err(format!("账号文件不存在: {}", account_id));
-        }
+    body: axum::body::Bytes,
+) -> Response {
+    let req: ValidateTokensRequest = match parse_optional_body(&body) {
+        Ok(req) => req,
+        Err(e) => return invalid_json(e),
+    };
 
-        let content = std::fs::read_to_string(&account_path)
-            .map_err(|e| format!("读取账号文件失败: {}", e))?;
+    let sse_tx = state.sse_tx.clone();
+    let results = modules::token_health::check_all_accounts(req.disable_invalid, |check, done, total| {
+        let _ = sse_tx.send(SseEvent::TokenValidation {
+            done,
+            total,
+            result: check.clone(),
+        });
+    })
+    .await;
 
-        let mut account_json: serde_json::Value = serde_json::from_str(&content)
-            .map_err(|e| format!("解析账号文件失败: {}", e))?;
+    match results {
+        Ok(results) => {
+            // 新 Token 已写入账号文件，被禁用的账号也需要移出轮换
+            reload_proxy_accounts_internal(&state).await;
 
-        if req.enable {
-            account_json["proxy_disabled"] = serde_json::Value::Bool(false);
-            account_json["proxy_disabled_reason"] = serde_json::Value::Null;
-            account_json["proxy_disabled_at"] = serde_json::Value::Null;
-        } else {
-            let now = chrono::Utc::now().timestamp();
-            account_json["proxy_disabled"] = serde_json::Value::Bool(true);
-            account_json["proxy_disabled_at"] = serde_json::Value::Number(now.into());
-            account_json["proxy_disabled_reason"] = serde_json::Value::String(
-                req.reason.unwrap_or_else(|| "用户手动禁用".to_string()),
-            );
+            let count = |status| results.iter().filter(|c| c.status == status).count();
+            let report = TokenValidationReport {
+                total: results.len(),
+                valid: count(TokenStatus::Valid),
+                invalid: count(TokenStatus::Invalid),
+                network_error: count(TokenStatus::NetworkError),
+                disabled: results.iter().filter(|c| c.proxy_disabled).count(),
+                results,
+            };
+            ApiResponse::ok(report).into_response()
         }
+        Err(e) => ApiResponse::<TokenValidationReport>::err(e).into_response(),
+    }
+}
 
-        std::fs::write(&account_path, serde_json::to_string_pretty(&account_json).unwrap())
-            .map_err(|e| format!("写入账号文件失败: {}", e))?;
-
-        Ok(())
+#[utoipa::path(
+    post,
+    path = "/api/accounts/{id}/validate",
+    tag = "accounts",
+    params(("id" = String, Path, description = "账号 ID")),
+    request_body(content = Option<ValidateTokensRequest>, description = "可选"),
+    responses(
+        (status = 200, description = "检查单个账号的 Token", body = TokenCheckResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn validate_account_token(
+    State(state): State<Arc<WebApiState>>,
+    Path(account_id): Path<String>,
+    body: axum::body::Bytes,
+) -> Response {
+    let req: ValidateTokensRequest = match parse_optional_body(&body) {
+        Ok(req) => req,
+        Err(e) => return invalid_json(e),
     };
 
-    match result() {
-        Ok(()) => {
-            reload_proxy_accounts_internal(&state).await;
-            ApiResponse::ok(())
-        }
-        Err(e) => ApiResponse::<()>::err(e),
-    }
+    let account = match modules::load_account(&account_id) {
+        Ok(account) => account,
+        Err(e) => return account_failure::<TokenCheck>(&account_id, e).into_response(),
+    };
+    let check = modules::token_health::check_account(&account, req.disable_invalid).await;
+    reload_proxy_accounts_internal(&state).await;
+    ApiResponse::ok(check).into_response()
 }
 
-// ============================================================================
-// 配置 API
-// ============================================================================
-
-async fn load_config(
-    State(_state): State<Arc<WebApiState>>,
-) -> impl IntoResponse {
-    match modules::load_app_config() {
-        Ok(config) => ApiResponse::ok(config),
-        Err(e) => ApiResponse::<AppConfig>::err(e),
-    }
+#[derive(Deserialize, ToSchema)]
+struct AccountTestRequest {
+    /// 确认发送 (测试会消耗该账号的配额)，必须为 true
+    #[serde(default)]
+    confirm: bool,
+    /// 请求的模型，按反代的模型映射解析；默认 `gemini-2.5-flash`
+    model: Option<String>,
+    /// 提示词，默认 `Say OK`
+    prompt: Option<String>,
 }
 
-async fn save_config(
+#[utoipa::path(
+    post,
+    path = "/api/accounts/{id}/test",
+    tag = "accounts",
+    params(("id" = String, Path, description = "账号 ID")),
+    request_body = AccountTestRequest,
+    responses(
+        (status = 200, description = "固定使用该账号经反代链路发送一次最小的生成请求 (最多输出 5 个 token)，返回延迟、实际模型与截断后的响应或错误分类；需要反代服务运行，计入该账号的使用统计", body = AccountTestResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn test_account(
     State(state): State<Arc<WebApiState>>,
-    AppJson(config): AppJson<AppConfig>,
+    Path(account_id): Path<String>,
+    AppJson(req): AppJson<AccountTestRequest>,
 ) -> impl IntoResponse {
-    match modules::save_app_config(&config) {
-        Ok(()) => {
-            // 广播配置更新事件
-            let _ = state.sse_tx.send(SseEvent::ConfigUpdated);
-
-            // 热更新正在运行的反代服务
-            let instance_lock = state.proxy_instance.read().await;
-            if let Some(instance) = instance_lock.as_ref() {
-                instance.axum_server.update_mapping(&config.proxy).await;
-                instance
-                    .axum_server
-                    .update_proxy(config.proxy.upstream_proxy.clone())
-                    .await;
-                instance.axum_server.update_security(&config.proxy).await;
-                instance.axum_server.update_zai(&config.proxy).await;
-            }
-
-            ApiResponse::ok(())
-        }
-        Err(e) => ApiResponse::<()>::err(e),
+    if !req.confirm {
+        return ApiResponse::<AccountTestResult>::fail(ErrorCode::ConfirmRequired, "测试请求会消耗该账号的配额，请设置 confirm 为 true");
     }
+    let account = match modules::load_account(&account_id) {
+        Ok(account) => account,
+        Err(e) => return account_failure::<AccountTestResult>(&account_id, e),
+    };
+    let (upstream, token_manager, custom_mapping) = {
+        let instance_lock = state.proxy_instance.read().await;
+        let Some(instance) = instance_lock.as_ref() else {
+            return ApiResponse::<AccountTestResult>::fail(ErrorCode::ProxyNotRunning, "反代服务未运行");
+        };
+        (
+            instance.axum_server.upstream(),
+            instance.token_manager.clone(),
+            instance.config.custom_mapping.clone(),
+        )
+    };
+
+    let model = req.model.as_deref().map(str::trim).filter(|m| !m.is_empty());
+    let prompt = req.prompt.as_deref().filter(|p| !p.trim().is_empty());
+    let result = modules::account_test::run(
+        &upstream,
+        &token_manager,
+        &custom_mapping,
+        &account,
+        model.unwrap_or(modules::account_test::DEFAULT_MODEL),
+        prompt.unwrap_or(modules::account_test::DEFAULT_PROMPT),
+    )
+    .await;
+    ApiResponse::ok(result)
 }
 
-// ============================================================================
-// 反代服务 API
-// ============================================================================
+#[derive(Deserialize, ToSchema)]
+struct TestAllAccountsRequest {
+    /// 确认发送 (每个账号都会消耗配额)，必须为 true
+    #[serde(default)]
+    confirm: bool,
+    /// 请求的模型，默认 `gemini-2.5-flash`
+    model: Option<String>,
+    /// 提示词，默认 `Say OK`
+    prompt: Option<String>,
+    /// 禁用鉴权失败 (`error_kind = auth`) 账号的反代
+    #[serde(default)]
+    disable_auth_failures: bool,
+}
 
-#[derive(Serialize)]
-struct ProxyStatus {
-    running: bool,
-    port: u16,
-    base_url: String,
-    active_accounts: usize,
+/// 账号批量测试报告
+#[derive(Serialize, ToSchema)]
+struct AccountTestReport {
+    total: usize,
+    ok: usize,
+    failed: usize,
+    /// 本次被禁用反代的账号数
+    disabled: usize,
+    results: Vec<AccountTestResult>,
 }
 
-async fn start_proxy_service(
+#[utoipa::path(
+    post,
+    path = "/api/accounts/test-all",
+    tag = "accounts",
+    request_body = TestAllAccountsRequest,
+    responses(
+        (status = 200, description = "并发测试所有可用账号 (未禁用且未禁用反代)，进度通过 SSE AccountTest 事件推送；结果缓存一小时", body = AccountTestReportResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn test_all_accounts(
     State(state): State<Arc<WebApiState>>,
-    AppJson(config): AppJson<ProxyConfig>,
+    AppJson(req): AppJson<TestAllAccountsRequest>,
 ) -> impl IntoResponse {
-    let mut instance_lock = state.proxy_instance.write().await;
-
-    if instance_lock.is_some() {
-        return ApiResponse::<ProxyStatus>::err("服务已在运行中");
-    }
-
-    // 确保 monitor 存在
-    {
-        let mut monitor_lock = state.monitor.write().await;
-        if monitor_lock.is_none() {
-            // Web 模式下创建不带 app_handle 的 monitor
-            *monitor_lock = Some(Arc::new(ProxyMonitor::new(1000, None)));
-        }
-        if let Some(monitor) = monitor_lock.as_ref() {
-            monitor.set_enabled(config.enable_logging);
-        }
+    if !req.confirm {
+        return ApiResponse::<AccountTestReport>::fail(ErrorCode::ConfirmRequired, "批量测试会消耗每个账号的配额，请设置 confirm 为 true");
     }
-
-    let monitor = state.monitor.read().await.as_ref().unwrap().clone();
-
-    // 初始化 Token 管理器
-    let app_data_dir = match modules::account::get_data_dir() {
-        Ok(dir) => dir,
-        Err(e) => return ApiResponse::<ProxyStatus>::err(e),
+    let (upstream, token_manager, custom_mapping) = {
+        let instance_lock = state.proxy_instance.read().await;
+        let Some(instance) = instance_lock.as_ref() else {
+            return ApiResponse::<AccountTestReport>::fail(ErrorCode::ProxyNotRunning, "反代服务未运行");
+        };
+        (
+            instance.axum_server.upstream(),
+            instance.token_manager.clone(),
+            instance.config.custom_mapping.clone(),
+        )
     };
-    let _ = modules::account::get_accounts_dir();
-
-    let token_manager = Arc::new(TokenManager::new(app_data_dir.clone()));
-    token_manager
-        .update_sticky_config(config.scheduling.clone())
-        .await;
 
-    // 加载账号
-    let active_accounts = match token_manager.load_accounts().await {
-        Ok(count) => count,
-        Err(e) => return ApiResponse::<ProxyStatus>::err(format!("加载账号失败: {}", e)),
-    };
+    let model = req.model.as_deref().map(str::trim).filter(|m| !m.is_empty());
+    let prompt = req.prompt.as_deref().filter(|p| !p.trim().is_empty());
+    let sse_tx = state.sse_tx.clone();
+    let results = modules::account_test::run_all(
+        upstream,
+        token_manager,
+        custom_mapping,
+        model.unwrap_or(modules::account_test::DEFAULT_MODEL),
+        prompt.unwrap_or(modules::account_test::DEFAULT_PROMPT),
+        req.disable_auth_failures,
+        |result, done, total| {
+            let _ = sse_tx.send(SseEvent::AccountTest {
+                done,
+                total,
+                result: result.clone(),
+            });
+        },
+    )
+    .await;
 
-    if active_accounts == 0 {
-        let zai_enabled = config.zai.enabled
-            && !matches!(
-                config.zai.dispatch_mode,
-                crate::proxy::ZaiDispatchMode::Off
-            );
-        if !zai_enabled {
-            return ApiResponse::<ProxyStatus>::err("没有可用账号，请先添加账号");
+    match results {
+        Ok(results) => {
+            let disabled = results.iter().filter(|r| r.proxy_disabled).count();
+            if disabled > 0 {
+                // 被禁用的账号需要移出轮换
+                reload_proxy_accounts_internal(&state).await;
+            }
+            let ok = results.iter().filter(|r| r.success).count();
+            ApiResponse::ok(AccountTestReport {
+                total: results.len(),
+                ok,
+                failed: results.len() - ok,
+                disabled,
+                results,
+            })
         }
+        Err(e) => ApiResponse::<AccountTestReport>::err(e),
     }
+}
 
-    // 启动 Axum 服务器
-    let result = crate::proxy::AxumServer::start(
-        config.get_bind_address().to_string(),
-        config.port,
-        token_manager.clone(),
-        config.custom_mapping.clone(),
-        config.request_timeout,
-        config.upstream_proxy.clone(),
-        crate::proxy::ProxySecurityConfig::from_proxy_config(&config),
-        config.zai.clone(),
-        monitor.clone(),
-        config.experimental.clone(),
+#[utoipa::path(
+    get,
+    path = "/api/accounts/test-results",
+    tag = "accounts",
+    responses(
+        (status = 200, description = "一小时内的账号测试结果 (单个测试与批量测试)，不会发送新的请求", body = AccountTestResultListResponse),
     )
-    .await;
+)]
+async fn get_account_test_results() -> impl IntoResponse {
+    ApiResponse::ok(modules::account_test::cached_results())
+}
 
-    match result {
-        Ok((axum_server, server_handle)) => {
-            let instance = ProxyServiceInstance {
-                config: config.clone(),
-                token_manager,
-                axum_server,
-                server_handle,
-            };
+#[derive(Deserialize, ToSchema)]
+struct UpdateTokenRequest {
+    refresh_token: String,
+    /// 允许 Token 邮箱与账号不一致 (改绑到新邮箱)
+    #[serde(default)]
+    force: bool,
+}
 
-            *instance_lock = Some(instance);
+#[utoipa::path(
+    put,
+    path = "/api/accounts/{id}/token",
+    tag = "accounts",
+    params(("id" = String, Path, description = "账号 ID")),
+    request_body = UpdateTokenRequest,
+    responses(
+        (status = 200, description = "替换账号的 refresh_token，返回脱敏后的账号", body = AccountResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn update_account_token(
+    State(state): State<Arc<WebApiState>>,
+    Path(account_id): Path<String>,
+    AppJson(req): AppJson<UpdateTokenRequest>,
+) -> impl IntoResponse {
+    let refresh_token = req.refresh_token.trim();
+    if refresh_token.is_empty() {
+        return ApiResponse::<Account>::fail(ErrorCode::InvalidRequest, "refresh_token 不能为空");
+    }
 
-            // 保存配置
-            if let Ok(mut app_config) = modules::config::load_app_config() {
-                app_config.proxy = config.clone();
-                let _ = modules::config::save_app_config(&app_config);
+    match modules::account::update_account_token(&account_id, refresh_token, req.force).await {
+        Ok(mut account) => {
+            if let Some(instance) = state.proxy_instance.read().await.as_ref() {
+                if let Err(e) = instance.token_manager.reload_account(&account_id).await {
+                    tracing::warn!("重新加载账号 {} 失败: {}", account_id, e);
+                }
             }
-
-            ApiResponse::ok(ProxyStatus {
-                running: true,
-                port: config.port,
-                base_url: format!("http://127.0.0.1:{}", config.port),
-                active_accounts,
-            })
+            account.token = account.token.masked();
+            ApiResponse::ok(account)
         }
-        Err(e) => ApiResponse::<ProxyStatus>::err(format!("启动服务器失败: {}", e)),
+        Err(e) => account_failure::<Account>(&account_id, e),
     }
 }
 
-async fn stop_proxy_service(
-    State(state): State<Arc<WebApiState>>,
-) -> impl IntoResponse {
-    let mut instance_lock = state.proxy_instance.write().await;
+#[derive(Deserialize, ToSchema)]
+struct ReorderRequest {
+    account_ids: Vec<String>,
+}
 
-    if instance_lock.is_none() {
-        return ApiResponse::<()>::err("服务未运行");
+#[utoipa::path(
+    post,
+    path = "/api/accounts/reorder",
+    tag = "accounts",
+    request_body = ReorderRequest,
+    responses(
+        (status = 200, description = "调整账号顺序 (须提交全部账号 ID)", body = EmptyResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+        (status = 409, description = "提交的列表与现有账号不一致，data 为缺少/未知/重复的 ID", body = AccountIdMismatchResponse),
+    )
+)]
+async fn reorder_accounts(
+    State(_state): State<Arc<WebApiState>>,
+    AppJson(req): AppJson<ReorderRequest>,
+) -> Response {
+    match modules::account::check_reorder_ids(&req.account_ids) {
+        Ok(Some(mismatch)) => {
+            return (StatusCode::CONFLICT, ApiResponse::err_with(ErrorCode::Conflict, mismatch.summarize(), mismatch)).into_response();
+        }
+        Ok(None) => {}
+        Err(e) => return ApiResponse::<()>::err(e).into_response(),
     }
-
-    if let Some(instance) = instance_lock.take() {
-        instance.axum_server.stop();
-        instance.server_handle.await.ok();
+    match modules::account::reorder_accounts(&req.account_ids) {
+        Ok(()) => ApiResponse::ok(()).into_response(),
+        Err(e) => ApiResponse::<()>::err(e).into_response(),
     }
+}
 
-    ApiResponse::ok(())
+/// 二选一：`position` 为目标位置 (从 0 开始)，`before` 为放在其之前的账号
+#[derive(Deserialize, ToSchema)]
+struct MoveAccountRequest {
+    position: Option<usize>,
+    before: Option<String>,
 }
 
-async fn get_proxy_status(
-    State(state): State<Arc<WebApiState>>,
+#[utoipa::path(
+    post,
+    path = "/api/accounts/{id}/move",
+    tag = "accounts",
+    params(("id" = String, Path, description = "账号 ID")),
+    request_body = MoveAccountRequest,
+    responses(
+        (status = 200, description = "移动单个账号；位置按展示顺序计算，置顶账号始终在前", body = EmptyResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn move_account(
+    Path(account_id): Path<String>,
+    AppJson(req): AppJson<MoveAccountRequest>,
 ) -> impl IntoResponse {
-    let instance_lock = state.proxy_instance.read().await;
-
-    match instance_lock.as_ref() {
-        Some(instance) => ApiResponse::ok(ProxyStatus {
-            running: true,
-            port: instance.config.port,
-            base_url: format!("http://127.0.0.1:{}", instance.config.port),
-            active_accounts: instance.token_manager.len(),
-        }),
-        None => ApiResponse::ok(ProxyStatus {
-            running: false,
-            port: 0,
-            base_url: String::new(),
-            active_accounts: 0,
-        }),
+    let target = match (req.position, req.before) {
+        (Some(position), None) => modules::account::MoveTarget::Position(position),
+        (None, Some(before)) => modules::account::MoveTarget::Before(before),
+        _ => return ApiResponse::<()>::fail(ErrorCode::InvalidRequest, "position 与 before 须且只能指定一个"),
+    };
+    match modules::account::move_account(&account_id, target) {
+        Ok(()) => ApiResponse::ok(()),
+        Err(e) => account_failure::<()>(&account_id, e),
     }
 }
 
-async fn get_proxy_stats(
-    State(state): State<Arc<WebApiState>>,
+#[derive(Deserialize, ToSchema)]
+struct PinAccountRequest {
+    pinned: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/accounts/{id}/pin",
+    tag = "accounts",
+    params(("id" = String, Path, description = "账号 ID")),
+    request_body = PinAccountRequest,
+    responses(
+        (status = 200, description = "置顶/取消置顶账号", body = AccountResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn pin_account(
+    Path(account_id): Path<String>,
+    AppJson(req): AppJson<PinAccountRequest>,
 ) -> impl IntoResponse {
-    let monitor_lock = state.monitor.read().await;
-    if let Some(monitor) = monitor_lock.as_ref() {
-        ApiResponse::ok(monitor.get_stats().await)
-    } else {
-        ApiResponse::ok(ProxyStats::default())
+    match modules::account::set_account_pinned(&account_id, req.pinned) {
+        Ok(account) => ApiResponse::ok(account),
+        Err(e) => account_failure::<Account>(&account_id, e),
     }
 }
 
-#[derive(Deserialize)]
-struct LogsQuery {
-    limit: Option<usize>,
+#[derive(Deserialize, ToSchema)]
+struct AccountEndpointRequest {
+    /// 主机名或 `https://主机/v1internal`，须在白名单内；为空时使用反代的默认端点
+    endpoint: Option<String>,
 }
 
-async fn get_proxy_logs(
+#[utoipa::path(
+    put,
+    path = "/api/accounts/{id}/endpoint",
+    tag = "accounts",
+    params(("id" = String, Path, description = "账号 ID")),
+    request_body = AccountEndpointRequest,
+    responses(
+        (status = 200, description = "设置账号的上游端点，立即应用到反代账号池", body = AccountResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn set_account_endpoint(
     State(state): State<Arc<WebApiState>>,
-    Query(query): Query<LogsQuery>,
+    Path(account_id): Path<String>,
+    AppJson(req): AppJson<AccountEndpointRequest>,
 ) -> impl IntoResponse {
-    let monitor_lock = state.monitor.read().await;
-    if let Some(monitor) = monitor_lock.as_ref() {
-        ApiResponse::ok(monitor.get_logs(query.limit.unwrap_or(100)).await)
-    } else {
-        ApiResponse::ok(Vec::<ProxyRequestLog>::new())
-    }
-}
+    match modules::account::set_account_endpoint(&account_id, req.endpoint.as_deref()) {
+        Ok(account) => {
+            reload_proxy_accounts_internal(&state).await;
+            ApiResponse::ok(account)
+        }
+        Err(e) => account_failure::<Account>(&account_id, e),
+    }
+}
 
-async fn clear_proxy_logs(
+#[utoipa::path(
+    post,
+    path = "/api/accounts/{id}/discover-project",
+    tag = "accounts",
+    params(("id" = String, Path, description = "账号 ID")),
+    responses(
+        (status = 200, description = "使用账号的 Access Token 查询 Antigravity 项目 (loadCodeAssist) 与可访问的 Cloud 项目，保存选中的 project_id (当前值仍可用时保留，否则取第一个) 并返回全部候选", body = ProjectDiscoveryResponse),
+    )
+)]
+async fn discover_account_project(
     State(state): State<Arc<WebApiState>>,
+    Path(account_id): Path<String>,
 ) -> impl IntoResponse {
-    let monitor_lock = state.monitor.read().await;
-    if let Some(monitor) = monitor_lock.as_ref() {
-        monitor.clear().await;
+    match modules::project::discover_project(&account_id).await {
+        Ok(discovery) => {
+            reload_proxy_accounts_internal(&state).await;
+            ApiResponse::ok(discovery)
+        }
+        Err(e) => account_failure::<ProjectDiscovery>(&account_id, e),
     }
-    ApiResponse::ok(())
 }
 
-#[derive(Deserialize)]
-struct SetMonitorRequest {
-    enabled: bool,
+/// 区分缺省字段 (None) 与显式的 null (Some(None))
+fn deserialize_present<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
 }
 
-async fn set_proxy_monitor_enabled(
+#[derive(Deserialize, ToSchema)]
+struct AccountPatchRequest {
+    /// 手动指定 project_id；`null` 或空字符串清除，反代将在下次使用时自动获取
+    #[serde(default, deserialize_with = "deserialize_present")]
+    #[schema(value_type = Option<String>)]
+    project_id: Option<Option<String>>,
+    /// 设为 false 清除地区限制标记，账号重新参与反代调度
+    region_blocked: Option<bool>,
+    /// 账号标签 (覆盖原有标签)，用于反代分组调度
+    tags: Option<Vec<String>>,
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/accounts/{id}",
+    tag = "accounts",
+    params(("id" = String, Path, description = "账号 ID")),
+    request_body = AccountPatchRequest,
+    responses(
+        (status = 200, description = "修改账号字段，只更新请求体中出现的字段，立即应用到反代账号池", body = AccountResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn patch_account(
     State(state): State<Arc<WebApiState>>,
-    AppJson(req): AppJson<SetMonitorRequest>,
+    Path(account_id): Path<String>,
+    AppJson(req): AppJson<AccountPatchRequest>,
 ) -> impl IntoResponse {
-    let monitor_lock = state.monitor.read().await;
-    if let Some(monitor) = monitor_lock.as_ref() {
-        monitor.set_enabled(req.enabled);
+    let result = (|| {
+        if let Some(project_id) = &req.project_id {
+            modules::project::set_project_id(&account_id, project_id.as_deref())?;
+        }
+        if let Some(blocked) = req.region_blocked {
+            modules::account::set_region_blocked(&account_id, blocked)?;
+        }
+        if let Some(tags) = &req.tags {
+            modules::account::set_tags(&account_id, tags)?;
+        }
+        modules::load_account(&account_id)
+    })();
+    match result {
+        Ok(account) => {
+            reload_proxy_accounts_internal(&state).await;
+            ApiResponse::ok(account)
+        }
+        Err(e) => account_failure::<Account>(&account_id, e),
     }
-    ApiResponse::ok(())
 }
 
-async fn reload_proxy_accounts(
-    State(state): State<Arc<WebApiState>>,
-) -> impl IntoResponse {
-    let instance_lock = state.proxy_instance.read().await;
+#[derive(Deserialize, Default, ToSchema)]
+struct DedupeRequest {
+    /// 只返回合并计划，不做修改
+    #[serde(default)]
+    dry_run: bool,
+}
 
-    if let Some(instance) = instance_lock.as_ref() {
-        match instance.token_manager.load_accounts().await {
-            Ok(count) => ApiResponse::ok(count),
-            Err(e) => ApiResponse::<usize>::err(format!("重新加载账号失败: {}", e)),
+#[utoipa::path(
+    post,
+    path = "/api/accounts/repair",
+    tag = "accounts",
+    responses(
+        (status = 200, description = "修复损坏的账号文件：补全缺失或无法解析的可选字段后重新保存，缺少 email 或 refresh_token 等无法修复的文件移入 accounts/.corrupt/", body = RepairReportResponse),
+    )
+)]
+async fn repair_accounts(State(state): State<Arc<WebApiState>>) -> impl IntoResponse {
+    match modules::account_repair::repair() {
+        Ok(report) => {
+            if !report.repaired.is_empty() || !report.quarantined.is_empty() {
+                reload_proxy_accounts_internal(&state).await;
+            }
+            ApiResponse::ok(report)
         }
-    } else {
-        ApiResponse::<usize>::err("服务未运行")
+        Err(e) => ApiResponse::<RepairReport>::err(e),
     }
 }
 
-/// 内部辅助函数：重新加载账号池
-async fn reload_proxy_accounts_internal(state: &WebApiState) {
-    let instance_lock = state.proxy_instance.read().await;
-    if let Some(instance) = instance_lock.as_ref() {
-        let _ = instance.token_manager.load_accounts().await;
+#[utoipa::path(
+    post,
+    path = "/api/accounts/dedupe",
+    tag = "accounts",
+    request_body(content = Option<DedupeRequest>, description = "可选"),
+    responses(
+        (status = 200, description = "合并同邮箱的重复账号：保留 Token 最新的一个，其余移入数据目录下的 trash/", body = DedupeReportResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn dedupe_accounts(
+    State(state): State<Arc<WebApiState>>,
+    body: axum::body::Bytes,
+) -> Response {
+    let req: DedupeRequest = match parse_optional_body(&body) {
+        Ok(req) => req,
+        Err(e) => return invalid_json(e),
+    };
+    match modules::account::dedupe_accounts(req.dry_run) {
+        Ok(report) => {
+            if !report.dry_run && report.removed > 0 {
+                reload_proxy_accounts_internal(&state).await;
+            }
+            ApiResponse::ok(report).into_response()
+        }
+        Err(e) => ApiResponse::<DedupeReport>::err(e).into_response(),
     }
 }
 
-async fn update_model_mapping(
+#[derive(Deserialize, ToSchema)]
+struct ToggleProxyStatusRequest {
+    enable: bool,
+    reason: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/accounts/{id}/proxy-status",
+    tag = "accounts",
+    params(("id" = String, Path, description = "账号 ID")),
+    request_body = ToggleProxyStatusRequest,
+    responses(
+        (status = 200, description = "启用/禁用账号的反代功能", body = EmptyResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn toggle_proxy_status(
     State(state): State<Arc<WebApiState>>,
-    AppJson(config): AppJson<ProxyConfig>,
+    Path(account_id): Path<String>,
+    AppJson(req): AppJson<ToggleProxyStatusRequest>,
 ) -> impl IntoResponse {
-    let instance_lock = state.proxy_instance.read().await;
-    if let Some(instance) = instance_lock.as_ref() {
-        instance.axum_server.update_mapping(&config).await;
-    }
+    let result = || -> Result<(), String> {
+        let mut account = modules::account::load_account(&account_id)?;
 
-    // 保存到配置
-    if let Ok(mut app_config) = modules::config::load_app_config() {
-        app_config.proxy.custom_mapping = config.custom_mapping;
-        let _ = modules::config::save_app_config(&app_config);
-    }
+        if req.enable {
+            account.proxy_disabled = false;
+            account.proxy_disabled_reason = None;
+            account.proxy_disabled_at = None;
+        } else {
+            account.proxy_disabled = true;
+            account.proxy_disabled_at = Some(chrono::Utc::now().timestamp());
+            account.proxy_disabled_reason =
+                Some(req.reason.unwrap_or_else(|| "用户手动禁用".to_string()));
+        }
 
-    ApiResponse::ok(())
+        modules::account::save_account(&account)
+    };
+
+    match result() {
+        Ok(()) => {
+            reload_proxy_accounts_internal(&state).await;
+            ApiResponse::ok(())
+        }
+        Err(e) => account_failure::<()>(&account_id, e),
+    }
 }
 
-async fn get_proxy_scheduling_config(
+#[utoipa::path(
+    post,
+    path = "/api/accounts/{id}/clear-forbidden",
+    tag = "accounts",
+    params(("id" = String, Path, description = "账号 ID")),
+    responses(
+        (status = 200, description = "解除账号的 forbidden 标记 (如申诉恢复后)，账号重新参与配额刷新与反代调度", body = AccountResponse),
+    )
+)]
+async fn clear_account_forbidden(
     State(state): State<Arc<WebApiState>>,
+    Path(account_id): Path<String>,
 ) -> impl IntoResponse {
-    let instance_lock = state.proxy_instance.read().await;
-    if let Some(instance) = instance_lock.as_ref() {
-        ApiResponse::ok(instance.token_manager.get_sticky_config().await)
-    } else {
-        ApiResponse::ok(crate::proxy::sticky_config::StickySessionConfig::default())
+    match modules::account::clear_forbidden(&account_id) {
+        Ok(mut account) => {
+            reload_proxy_accounts_internal(&state).await;
+            account.token = account.token.masked();
+            ApiResponse::ok(account)
+        }
+        Err(e) => account_failure::<Account>(&account_id, e),
     }
 }
 
-async fn update_proxy_scheduling_config(
+// ============================================================================
+// 配置 API
+// ============================================================================
+
+/// 将配置版本号格式化为强 ETag
+fn config_etag(revision: &str) -> String {
+    format!("\"{}\"", revision)
+}
+
+/// 判断 `If-Match` / `If-None-Match` 头是否包含指定 ETag (支持 `*`、逗号分隔与弱校验前缀)
+fn etag_matches(header_value: &HeaderValue, etag: &str) -> bool {
+    header_value.to_str().is_ok_and(|value| {
+        value
+            .split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == "*" || tag == etag)
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/config",
+    tag = "config",
+    params(
+        ("If-None-Match" = Option<String>, Header, description = "上次获取的 ETag，未变化时返回 304"),
+    ),
+    responses(
+        (status = 200, description = "读取应用配置，ETag 头为配置版本号", body = AppConfigResponse,
+            headers(("ETag" = String, description = "配置版本号"))),
+        (status = 304, description = "配置未变化"),
+    )
+)]
+async fn load_config(
+    State(_state): State<Arc<WebApiState>>,
+    headers: HeaderMap,
+) -> Response {
+    let (config, revision) = match modules::config::load_app_config_with_revision() {
+        Ok(loaded) => loaded,
+        Err(e) => return ApiResponse::<AppConfig>::err(e).into_response(),
+    };
+    let etag = config_etag(&revision);
+
+    if headers
+        .get(header::IF_NONE_MATCH)
+        .is_some_and(|value| etag_matches(value, &etag))
+    {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+
+    ([(header::ETAG, etag)], ApiResponse::ok(config)).into_response()
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/config",
+    tag = "config",
+    params(
+        ("If-Match" = String, Header, description = "GET /api/config 返回的 ETag"),
+    ),
+    request_body = AppConfig,
+    responses(
+        (status = 200, description = "保存应用配置并热更新反代服务，ETag 头为新的配置版本号", body = EmptyResponse,
+            headers(("ETag" = String, description = "配置版本号"))),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+        (status = 422, description = "配置未通过校验，data 为问题列表", body = ConfigIssuesResponse),
+        (status = 412, description = "配置已被其他客户端修改，需重新获取后合并", body = EmptyResponse),
+        (status = 428, description = "缺少 If-Match 头", body = EmptyResponse),
+    )
+)]
+async fn save_config(
     State(state): State<Arc<WebApiState>>,
-    AppJson(config): AppJson<crate::proxy::sticky_config::StickySessionConfig>,
-) -> impl IntoResponse {
-    let instance_lock = state.proxy_instance.read().await;
-    if let Some(instance) = instance_lock.as_ref() {
-        instance.token_manager.update_sticky_config(config).await;
-        ApiResponse::ok(())
-    } else {
-        ApiResponse::<()>::err("服务未运行")
+    headers: HeaderMap,
+    AppJson(config): AppJson<AppConfig>,
+) -> Response {
+    let Some(if_match) = headers.get(header::IF_MATCH) else {
+        return (
+            StatusCode::PRECONDITION_REQUIRED,
+            ApiResponse::<()>::fail(ErrorCode::PreconditionRequired, "缺少 If-Match 头，请先获取配置"),
+        )
+            .into_response();
+    };
+
+    // `If-Match: *` 表示无条件覆盖
+    let expected = match if_match.to_str() {
+        Ok("*") => match modules::config::config_revision() {
+            Ok(revision) => revision,
+            Err(e) => return ApiResponse::<()>::err(e).into_response(),
+        },
+        Ok(value) => value.trim().trim_start_matches("W/").trim_matches('"').to_string(),
+        Err(_) => String::new(),
+    };
+
+    match modules::config::save_app_config_if_match(&config, &expected) {
+        Ok(modules::config::RevisionedSave::Conflict(current)) => (
+            StatusCode::PRECONDITION_FAILED,
+            [(header::ETAG, config_etag(&current))],
+            ApiResponse::<()>::fail(ErrorCode::ConfigConflict, "配置已被其他客户端修改，请重新获取后再保存"),
+        )
+            .into_response(),
+        Ok(modules::config::RevisionedSave::Invalid(issues)) => invalid_config(issues),
+        Ok(modules::config::RevisionedSave::Saved(revision)) => {
+            apply_saved_config(&state, &config).await;
+            ([(header::ETAG, config_etag(&revision))], ApiResponse::ok(())).into_response()
+        }
+        Err(e) => ApiResponse::<()>::err(e).into_response(),
     }
 }
 
-async fn clear_proxy_session_bindings(
+#[utoipa::path(
+    patch,
+    path = "/api/config",
+    tag = "config",
+    params(
+        ("If-Match" = Option<String>, Header, description = "可选，提供时校验配置版本号"),
+    ),
+    request_body(content = Object, description = "JSON Merge Patch (RFC 7396)，`null` 表示删除字段", content_type = "application/merge-patch+json"),
+    responses(
+        (status = 200, description = "合并并保存配置，返回合并后的配置", body = AppConfigResponse,
+            headers(("ETag" = String, description = "配置版本号"))),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+        (status = 422, description = "配置未通过校验，data 为问题列表", body = ConfigIssuesResponse),
+        (status = 412, description = "配置已被其他客户端修改", body = EmptyResponse),
+    )
+)]
+async fn patch_config(
     State(state): State<Arc<WebApiState>>,
-) -> impl IntoResponse {
-    let instance_lock = state.proxy_instance.read().await;
-    if let Some(instance) = instance_lock.as_ref() {
-        instance.token_manager.clear_all_sessions();
-        ApiResponse::ok(())
-    } else {
-        ApiResponse::<()>::err("服务未运行")
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> Response {
+    // 同时接受 application/json 与 application/merge-patch+json，故不使用 AppJson
+    let patch: serde_json::Value = match serde_json::from_slice(&body) {
+        Ok(patch) => patch,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                ApiResponse::<()>::fail(ErrorCode::InvalidJson, format!("JSON 语法错误: {}", e)),
+            )
+                .into_response()
+        }
+    };
+
+    let expected = headers
+        .get(header::IF_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| *value != "*")
+        .map(|value| value.trim().trim_start_matches("W/").trim_matches('"').to_string());
+
+    match modules::config::patch_app_config(&patch, expected.as_deref()) {
+        Ok(modules::config::RevisionedSave::Conflict(current)) => (
+            StatusCode::PRECONDITION_FAILED,
+            [(header::ETAG, config_etag(&current))],
+            ApiResponse::<()>::fail(ErrorCode::ConfigConflict, "配置已被其他客户端修改，请重新获取后再保存"),
+        )
+            .into_response(),
+        Ok(modules::config::RevisionedSave::Invalid(issues)) => invalid_config(issues),
+        Ok(modules::config::RevisionedSave::Saved(revision)) => {
+            let config = match modules::load_app_config() {
+                Ok(config) => config,
+                Err(e) => return ApiResponse::<AppConfig>::err(e).into_response(),
+            };
+            apply_saved_config(&state, &config).await;
+            ([(header::ETAG, config_etag(&revision))], ApiResponse::ok(config)).into_response()
+        }
+        Err(e) => ApiResponse::<AppConfig>::err(e).into_response(),
     }
 }
 
-#[derive(Deserialize)]
-struct FetchZaiModelsRequest {
-    zai: crate::proxy::ZaiConfig,
-    upstream_proxy: crate::proxy::config::UpstreamProxyConfig,
-    request_timeout: u64,
+#[derive(Serialize, ToSchema)]
+struct EffectiveConfig {
+    /// 合并环境变量覆盖后的配置
+    config: AppConfig,
+    /// 被环境变量覆盖的字段 (JSON Pointer)
+    overridden: Vec<String>,
 }
 
-// Helper functions for fetch_zai_models (inlined from commands/proxy.rs)
-fn join_base_url(base: &str, path: &str) -> String {
-    let base = base.trim_end_matches('/');
-    let path = if path.starts_with('/') {
-        path.to_string()
-    } else {
-        format!("/{}", path)
-    };
-    format!("{}{}", base, path)
+#[utoipa::path(
+    get,
+    path = "/api/config/effective",
+    tag = "config",
+    responses(
+        (status = 200, description = "当前生效的配置及被环境变量覆盖的字段", body = EffectiveConfigResponse),
+    )
+)]
+async fn get_effective_config(
+    State(_state): State<Arc<WebApiState>>,
+) -> impl IntoResponse {
+    match modules::load_app_config() {
+        Ok(config) => ApiResponse::ok(EffectiveConfig {
+            config,
+            overridden: modules::config::overridden_paths(),
+        }),
+        Err(e) => ApiResponse::<EffectiveConfig>::err(e),
+    }
 }
 
-fn extract_model_ids(value: &serde_json::Value) -> Vec<String> {
-    let mut out = Vec::new();
+#[derive(Serialize, ToSchema)]
+struct ConfigValidation {
+    valid: bool,
+    issues: Vec<ConfigIssue>,
+}
 
-    fn push_from_item(out: &mut Vec<String>, item: &serde_json::Value) {
-        match item {
-            serde_json::Value::String(s) => out.push(s.to_string()),
-            serde_json::Value::Object(map) => {
-                if let Some(id) = map.get("id").and_then(|v| v.as_str()) {
-                    out.push(id.to_string());
-                } else if let Some(name) = map.get("name").and_then(|v| v.as_str()) {
-                    out.push(name.to_string());
+#[utoipa::path(
+    post,
+    path = "/api/config/validate",
+    tag = "config",
+    request_body = AppConfig,
+    responses(
+        (status = 200, description = "校验配置但不保存", body = ConfigValidationResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn validate_config(
+    State(_state): State<Arc<WebApiState>>,
+    AppJson(config): AppJson<AppConfig>,
+) -> impl IntoResponse {
+    let issues = config.validate();
+    ApiResponse::ok(ConfigValidation {
+        valid: issues.is_empty(),
+        issues,
+    })
+}
+
+/// 监听配置文件的外部修改并热更新；重启任务时以当前文件内容为基准
+pub fn watch_config(state: Arc<WebApiState>) {
+    let weak = Arc::downgrade(&state);
+    state.tasks.register("config-watcher", move |ctx| {
+        let weak = weak.clone();
+        async move {
+            let watch = modules::config_watcher::watch(move |config| {
+                let weak = weak.clone();
+                async move {
+                    if let Some(state) = weak.upgrade() {
+                        apply_saved_config(&state, &config).await;
+                    }
+                }
+            });
+            match watch {
+                Ok(watch) => {
+                    tokio::select! {
+                        _ = watch => {}
+                        _ = ctx.cancelled() => {}
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!("{}", e);
+                    ctx.record_run(Err(e));
                 }
             }
-            _ => {}
         }
-    }
+    });
+}
 
-    match value {
-        serde_json::Value::Array(arr) => {
-            for item in arr {
-                push_from_item(&mut out, item);
-            }
-        }
-        serde_json::Value::Object(map) => {
-            if let Some(data) = map.get("data") {
-                if let serde_json::Value::Array(arr) = data {
-                    for item in arr {
-                        push_from_item(&mut out, item);
+/// 监听账号目录的外部修改，清空账号缓存并重新加载反代账号池
+pub fn watch_accounts(state: Arc<WebApiState>) {
+    let weak = Arc::downgrade(&state);
+    state.tasks.register("accounts-watcher", move |ctx| {
+        let weak = weak.clone();
+        async move {
+            let watch = modules::accounts_watcher::watch(move || {
+                let weak = weak.clone();
+                async move {
+                    if let Some(state) = weak.upgrade() {
+                        reload_changed_accounts(&state).await;
                     }
                 }
-            }
-            if let Some(models) = map.get("models") {
-                match models {
-                    serde_json::Value::Array(arr) => {
-                        for item in arr {
-                            push_from_item(&mut out, item);
-                        }
+            });
+            match watch {
+                Ok(watch) => {
+                    tokio::select! {
+                        _ = watch => {}
+                        _ = ctx.cancelled() => {}
                     }
-                    other => push_from_item(&mut out, other),
+                }
+                Err(e) => {
+                    tracing::warn!("{}", e);
+                    ctx.record_run(Err(e));
                 }
             }
         }
-        _ => {}
-    }
+    });
+}
 
-    out
+async fn reload_changed_accounts(state: &WebApiState) {
+    modules::account::account_store().invalidate();
+    let proxy_accounts = match state.proxy_instance.read().await.as_ref() {
+        Some(instance) => match instance.token_manager.load_accounts().await {
+            Ok(count) => Some(count),
+            Err(e) => {
+                tracing::warn!("重新加载反代账号失败: {}", e);
+                None
+            }
+        },
+        None => None,
+    };
+    let _ = state.sse_tx.send(SseEvent::AccountsReloaded { proxy_accounts });
 }
 
-async fn fetch_zai_models(
-    State(_state): State<Arc<WebApiState>>,
-    AppJson(req): AppJson<FetchZaiModelsRequest>,
-) -> impl IntoResponse {
-    let result = async {
-        if req.zai.base_url.trim().is_empty() {
-            return Err("z.ai base_url is empty".to_string());
-        }
-        if req.zai.api_key.trim().is_empty() {
-            return Err("z.ai api_key is not set".to_string());
-        }
+/// 配置保存后广播事件并热更新正在运行的反代服务
+async fn apply_saved_config(state: &WebApiState, config: &AppConfig) {
+    let _ = state.sse_tx.send(SseEvent::ConfigUpdated);
 
-        let url = join_base_url(&req.zai.base_url, "/v1/models");
+    *state.web_auth.write().unwrap_or_else(|e| e.into_inner()) = config.web_auth.clone();
+    *state.public_url.write().unwrap_or_else(|e| e.into_inner()) = config.public_url.clone();
 
-        let mut builder = reqwest::Client::builder()
-            .timeout(Duration::from_secs(req.request_timeout.max(5)));
-        if req.upstream_proxy.enabled && !req.upstream_proxy.url.is_empty() {
-            let proxy = reqwest::Proxy::all(&req.upstream_proxy.url)
-                .map_err(|e| format!("Invalid upstream proxy url: {}", e))?;
-            builder = builder.proxy(proxy);
-        }
-        let client = builder
-            .build()
-            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+    crate::proxy::pricing::update(&config.pricing);
+    crate::modules::i18n::update(config);
 
-        let resp = client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", req.zai.api_key))
-            .header("x-api-key", &req.zai.api_key)
-            .header("anthropic-version", "2023-06-01")
-            .header("accept", "application/json")
-            .send()
-            .await
-            .map_err(|e| format!("Upstream request failed: {}", e))?;
+    if let Some(monitor) = state.monitor.read().await.as_ref() {
+        monitor
+            .set_limits(config.proxy.monitor_buffer_size, config.proxy.monitor_max_memory_mb)
+            .await;
+    }
+
+    let instance_lock = state.proxy_instance.read().await;
+    if let Some(instance) = instance_lock.as_ref() {
+        instance.axum_server.update_mapping(&config.proxy).await;
+        instance
+            .axum_server
+            .update_proxy(config.proxy.upstream_proxy.clone())
+            .await;
+        if instance.axum_server.update_security(&config.proxy).await {
+            let summary = instance.axum_server.security().await.summary();
+            let _ = state.sse_tx.send(SseEvent::SecurityUpdated(summary));
+        }
+        instance.axum_server.update_zai(&config.proxy).await;
+        instance.axum_server.update_experimental(&config.proxy).await;
+        instance.axum_server.update_prompt_rules(&config.proxy).await;
+        instance.axum_server.update_response_headers(&config.proxy).await;
+        instance.axum_server.update_upstream(&config.proxy);
+    }
+}
+
+// ============================================================================
+// 反代服务 API
+// ============================================================================
+
+#[derive(Serialize, ToSchema)]
+struct ProxyStatus {
+    running: bool,
+    port: u16,
+    base_url: String,
+    /// 反代监听地址，由 `allow_lan_access` 决定，与管理 API 的监听地址相互独立
+    bind_address: String,
+    /// 单独配置的管理 API 监听地址 (--admin-host/--admin-port)，未配置时管理 API 与页面共用 --host/--port
+    #[serde(skip_serializing_if = "Option::is_none")]
+    admin_address: Option<String>,
+    /// 账号总数、可用数与不可用原因
+    #[serde(flatten)]
+    accounts: AccountAvailability,
+    /// 开启 `warmup` 时，启动响应返回前已完成预热的账号
+    #[serde(skip_serializing_if = "Option::is_none")]
+    warmed_accounts: Option<Vec<WarmupResult>>,
+    /// 预热未在启动响应返回前完成，其余进度通过 SSE `Warmup` 事件推送
+    warmup_pending: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/proxy/start",
+    tag = "proxy",
+    request_body = ProxyConfig,
+    responses(
+        (status = 200, description = "启动反代服务", body = ProxyStatusResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+        (status = 422, description = "配置未通过校验，data 为问题列表", body = ConfigIssuesResponse),
+    )
+)]
+async fn start_proxy_service(
+    State(state): State<Arc<WebApiState>>,
+    RequestOrigin(origin): RequestOrigin,
+    AppJson(config): AppJson<ProxyConfig>,
+) -> Response {
+    let issues = config.validate("");
+    if !issues.is_empty() {
+        return invalid_config(issues);
+    }
+
+    match start_proxy(&state, config.clone()).await {
+        Ok(mut status) => {
+            status.base_url = proxy_base_url(&config, origin.as_ref());
+            ApiResponse::ok(status).into_response()
+        }
+        Err(e) => ApiResponse::<ProxyStatus>::err(e).into_response(),
+    }
+}
+
+/// 按配置启动反代服务，供 API 与独立服务端自动启动共用
+async fn start_proxy(state: &WebApiState, config: ProxyConfig) -> Result<ProxyStatus, String> {
+    let mut instance_lock = state.proxy_instance.write().await;
+
+    if instance_lock.is_some() {
+        return Err("服务已在运行中".to_string());
+    }
+    state.check_proxy_bind(&config)?;
+
+    // 确保 monitor 存在
+    {
+        let mut monitor_lock = state.monitor.write().await;
+        if monitor_lock.is_none() {
+            // Web 模式下创建不带 app_handle 的 monitor
+            let monitor = Arc::new(ProxyMonitor::new(config.monitor_buffer_size, None));
+            monitor.register_daily_flush(&state.tasks);
+            let sse_tx = state.sse_tx.clone();
+            monitor.set_listener(move |log| {
+                let _ = sse_tx.send(SseEvent::ProxyRequest(log.clone()));
+            });
+            *monitor_lock = Some(monitor);
+        }
+        if let Some(monitor) = monitor_lock.as_ref() {
+            monitor.apply_saved(config.enable_logging);
+            monitor.set_limits(config.monitor_buffer_size, config.monitor_max_memory_mb).await;
+        }
+    }
+
+    let monitor = state.monitor.read().await.as_ref().unwrap().clone();
+
+    // 初始化 Token 管理器
+    let app_data_dir = match modules::account::get_data_dir() {
+        Ok(dir) => dir,
+        Err(e) => return Err(e),
+    };
+    let _ = modules::account::get_accounts_dir();
+
+    let token_manager = Arc::new(TokenManager::new(app_data_dir.clone()));
+    token_manager
+        .update_sticky_config(config.scheduling.clone())
+        .await;
+    token_manager.register_usage_flusher(&state.tasks);
+
+    // 加载账号
+    if let Err(e) = token_manager.load_accounts().await {
+        return Err(format!("加载账号失败: {}", e));
+    }
+    let accounts = token_manager.availability();
+
+    if accounts.total_accounts == 0 {
+        let zai_enabled = config.zai.enabled
+            && !matches!(
+                config.zai.dispatch_mode,
+                crate::proxy::ZaiDispatchMode::Off
+            );
+        if !zai_enabled {
+            return Err("没有可用账号，请先添加账号".to_string());
+        }
+    } else if accounts.usable_accounts == 0 {
+        tracing::warn!(
+            "共 {} 个账号但均不可用 (反代禁用 {}，冷却 {}，403 {})，服务仍将启动",
+            accounts.total_accounts,
+            accounts.excluded.proxy_disabled,
+            accounts.excluded.cooldown,
+            accounts.excluded.forbidden
+        );
+    }
+
+    // 启动 Axum 服务器
+    let result = crate::proxy::AxumServer::start(
+        config.get_bind_address().to_string(),
+        config.port,
+        token_manager.clone(),
+        config.custom_mapping.clone(),
+        config.request_timeout,
+        config.timeouts.clone(),
+        config.upstream_proxy.clone(),
+        crate::proxy::upstream::client::UpstreamConnectionSettings::from_proxy_config(&config),
+        crate::proxy::ProxySecurityConfig::from_proxy_config(&config),
+        config.zai.clone(),
+        monitor.clone(),
+        config.experimental.clone(),
+        config.prompt_rules.clone(),
+        config.response_headers.clone(),
+    )
+    .await;
+
+    match result {
+        Ok((axum_server, server_handle)) => {
+            // 监听已绑定，预热在后台进行，失败不影响启动
+            let warmup = config.warmup.then(|| {
+                let results = Arc::new(std::sync::Mutex::new(Vec::new()));
+                let collected = results.clone();
+                let sse_tx = state.sse_tx.clone();
+                let handle = tokio::spawn(modules::proxy_warmup::run(
+                    token_manager.clone(),
+                    axum_server.upstream(),
+                    config.custom_mapping.clone(),
+                    config.warmup_accounts,
+                    config.warmup_probe,
+                    move |result, done, total| {
+                        collected.lock().unwrap_or_else(|e| e.into_inner()).push(result.clone());
+                        let _ = sse_tx.send(SseEvent::Warmup { done, total, result: result.clone() });
+                    },
+                ));
+                (results, handle)
+            });
+
+            let instance = ProxyServiceInstance {
+                config: config.clone(),
+                token_manager,
+                axum_server,
+                server_handle,
+                started_at: chrono::Utc::now().timestamp(),
+            };
+
+            *instance_lock = Some(instance);
+            drop(instance_lock);
+
+            // 保存配置
+            if let Ok(mut app_config) = modules::config::load_app_config() {
+                app_config.proxy = config.clone();
+                let _ = modules::config::save_app_config(&app_config);
+            }
+
+            let (warmed_accounts, warmup_pending) = match warmup {
+                Some((results, handle)) => {
+                    let wait = Duration::from_secs(modules::proxy_warmup::START_WAIT_SECS);
+                    let finished = tokio::time::timeout(wait, handle).await.is_ok();
+                    let results = results.lock().unwrap_or_else(|e| e.into_inner()).clone();
+                    (Some(results), !finished)
+                }
+                None => (None, false),
+            };
+
+            Ok(ProxyStatus {
+                running: true,
+                port: config.port,
+                base_url: proxy_base_url(&config, None),
+                bind_address: config.bind_socket_addr().to_string(),
+                admin_address: state.admin_addr.map(|addr| addr.to_string()),
+                accounts,
+                warmed_accounts,
+                warmup_pending,
+            })
+        }
+        Err(e) => Err(format!("启动服务器失败: {}", e)),
+    }
+}
+
+/// 若配置开启了 `proxy.auto_start`，则按已保存的配置启动反代服务
+///
+/// 返回是否实际启动了服务。
+pub async fn auto_start_proxy(state: &WebApiState) -> Result<bool, String> {
+    let config = modules::config::load_app_config()?;
+    if !config.proxy.auto_start {
+        return Ok(false);
+    }
+    let issues = config.proxy.validate("/proxy");
+    if !issues.is_empty() {
+        return Err(ConfigIssue::summarize(&issues));
+    }
+    start_proxy(state, config.proxy).await.map(|_| true)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/proxy/stop",
+    tag = "proxy",
+    responses(
+        (status = 200, description = "停止反代服务", body = EmptyResponse),
+    )
+)]
+async fn stop_proxy_service(
+    State(state): State<Arc<WebApiState>>,
+) -> impl IntoResponse {
+    if !stop_proxy(&state).await {
+        return ApiResponse::<()>::fail(ErrorCode::ProxyNotRunning, "服务未运行");
+    }
+
+    ApiResponse::ok(())
+}
+
+/// 停止反代服务并落盘用量；返回此前是否在运行
+async fn stop_proxy(state: &WebApiState) -> bool {
+    let Some(instance) = state.proxy_instance.write().await.take() else {
+        return false;
+    };
+    instance.axum_server.stop();
+    instance.server_handle.await.ok();
+    instance.token_manager.flush_usage();
+    if let Some(monitor) = state.monitor.read().await.as_ref() {
+        monitor.flush_daily_stats();
+    }
+    true
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/proxy/status",
+    tag = "proxy",
+    responses(
+        (status = 200, description = "获取反代服务状态", body = ProxyStatusResponse),
+    )
+)]
+async fn get_proxy_status(
+    State(state): State<Arc<WebApiState>>,
+    RequestOrigin(origin): RequestOrigin,
+) -> impl IntoResponse {
+    let instance_lock = state.proxy_instance.read().await;
+
+    match instance_lock.as_ref() {
+        Some(instance) => ApiResponse::ok(ProxyStatus {
+            running: true,
+            port: instance.config.port,
+            base_url: proxy_base_url(&instance.config, origin.as_ref()),
+            bind_address: instance.config.bind_socket_addr().to_string(),
+            admin_address: state.admin_addr.map(|addr| addr.to_string()),
+            accounts: instance.token_manager.availability(),
+            warmed_accounts: None,
+            warmup_pending: false,
+        }),
+        None => ApiResponse::ok(ProxyStatus {
+            running: false,
+            port: 0,
+            base_url: String::new(),
+            bind_address: String::new(),
+            admin_address: state.admin_addr.map(|addr| addr.to_string()),
+            accounts: AccountAvailability::default(),
+            warmed_accounts: None,
+            warmup_pending: false,
+        }),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/proxy/stats",
+    tag = "proxy",
+    responses(
+        (status = 200, description = "获取请求统计", body = ProxyStatsResponse),
+    )
+)]
+async fn get_proxy_stats(
+    State(state): State<Arc<WebApiState>>,
+) -> impl IntoResponse {
+    let monitor_lock = state.monitor.read().await;
+    if let Some(monitor) = monitor_lock.as_ref() {
+        ApiResponse::ok(monitor.get_stats().await)
+    } else {
+        ApiResponse::ok(ProxyStats::default())
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct DailyStatsQuery {
+    /// 返回最近多少天 (默认 30，最大 400)
+    days: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/proxy/stats/daily",
+    tag = "proxy",
+    params(DailyStatsQuery),
+    responses(
+        (status = 200, description = "按天汇总的请求数、失败数、Token 用量与各模型请求数；重启后继续累计，只统计开启监控期间的请求", body = DailyStatsResponse),
+    )
+)]
+async fn get_proxy_daily_stats(
+    State(state): State<Arc<WebApiState>>,
+    Query(query): Query<DailyStatsQuery>,
+) -> impl IntoResponse {
+    let days = query.days.unwrap_or(30).clamp(1, 400);
+    let monitor = state.monitor.read().await.clone();
+    let result = tokio::task::spawn_blocking(move || match monitor {
+        Some(monitor) => monitor.daily_stats(days),
+        None => modules::stats_history::recent(days, None),
+    })
+    .await
+    .map_err(|e| format!("读取统计历史失败: {}", e))
+    .and_then(|result| result);
+    match result {
+        Ok(stats) => ApiResponse::ok(stats),
+        Err(e) => ApiResponse::<Vec<DailyStats>>::err(e),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct HeatmapQuery {
+    /// 统计最近多少天 (含今天，默认 7，最大 14)
+    days: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/proxy/usage/heatmap",
+    tag = "proxy",
+    params(HeatmapQuery),
+    responses(
+        (status = 200, description = "每个账号最近 N 天按本地小时的请求数 (稀疏编码，只列出非零格子)；来自按天汇总，未开启监控的日期列在 missing_dates 中且计为 0", body = UsageHeatmapResponse),
+    )
+)]
+async fn get_usage_heatmap(
+    State(state): State<Arc<WebApiState>>,
+    Query(query): Query<HeatmapQuery>,
+) -> impl IntoResponse {
+    let days = query.days.unwrap_or(7).clamp(1, 14);
+    let monitor = state.monitor.read().await.clone();
+    let result = tokio::task::spawn_blocking(move || match monitor {
+        Some(monitor) => monitor.usage_heatmap(days),
+        None => modules::stats_history::heatmap(days, None),
+    })
+    .await
+    .map_err(|e| format!("读取统计历史失败: {}", e))
+    .and_then(|result| result);
+    match result {
+        Ok(heatmap) => ApiResponse::ok(heatmap),
+        Err(e) => ApiResponse::<UsageHeatmap>::err(e),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct CostQuery {
+    /// 分组方式：account、key 或 model (默认 model)
+    group_by: Option<CostGroupBy>,
+    /// 统计窗口，如 30d (含今天，默认 30d，最大 400d)
+    window: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/proxy/usage/cost",
+    tag = "proxy",
+    params(CostQuery),
+    responses(
+        (status = 200, description = "按配置的单价表估算的费用；模型没有单价时 cost 为空而不是 0，对应请求计入 unpriced_requests；只统计开启监控期间的请求", body = CostReportResponse),
+    )
+)]
+async fn get_usage_cost(
+    State(state): State<Arc<WebApiState>>,
+    Query(query): Query<CostQuery>,
+) -> impl IntoResponse {
+    let group_by = query.group_by.unwrap_or_default();
+    let days = match modules::stats_history::parse_window_days(query.window.as_deref().unwrap_or("30d")) {
+        Ok(days) => days,
+        Err(e) => return ApiResponse::<CostReport>::err(e),
+    };
+    let monitor = state.monitor.read().await.clone();
+    let result = tokio::task::spawn_blocking(move || match monitor {
+        Some(monitor) => monitor.cost_report(group_by, days),
+        None => modules::stats_history::cost_report(group_by, days, None),
+    })
+    .await
+    .map_err(|e| format!("读取统计历史失败: {}", e))
+    .and_then(|result| result);
+    match result {
+        Ok(report) => ApiResponse::ok(report),
+        Err(e) => ApiResponse::<CostReport>::err(e),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct LogsQuery {
+    /// 返回条数上限 (默认 100)
+    limit: Option<usize>,
+    /// 仅返回该客户端地址的请求
+    client_ip: Option<String>,
+    /// `true` 仅返回被安全策略拦截的请求，`false` 仅返回未被拦截的请求
+    blocked: Option<bool>,
+    /// 只返回序号大于该值的日志，响应改为 `LogCursorPage` (含 latest_seq / truncated)
+    after_seq: Option<u64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/proxy/logs",
+    tag = "proxy",
+    params(LogsQuery),
+    responses(
+        (status = 200, description = "获取请求日志 (从新到旧)；带 after_seq 时 data 为 LogCursorPage，按序号先后返回新日志，可用其中的 latest_seq 继续轮询", body = ProxyLogsResponse),
+    )
+)]
+async fn get_proxy_logs(
+    State(state): State<Arc<WebApiState>>,
+    Query(query): Query<LogsQuery>,
+) -> Response {
+    let monitor_lock = state.monitor.read().await;
+    if let Some(after_seq) = query.after_seq {
+        let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+        let page = match monitor_lock.as_ref() {
+            Some(monitor) => monitor.logs_after(after_seq, limit).await,
+            None => LogCursorPage {
+                logs: Vec::new(),
+                latest_seq: after_seq,
+                has_more: false,
+                truncated: false,
+            },
+        };
+        return ApiResponse::ok(page).into_response();
+    }
+    if let Some(monitor) = monitor_lock.as_ref() {
+        let client_ip = query.client_ip.as_deref().map(str::trim).filter(|ip| !ip.is_empty());
+        ApiResponse::ok(monitor.get_logs_filtered(query.limit.unwrap_or(100), client_ip, None, query.blocked).await).into_response()
+    } else {
+        ApiResponse::ok(Vec::<ProxyRequestLog>::new()).into_response()
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ClearLogsQuery {
+    /// 只删除早于该时间 (毫秒时间戳) 的日志
+    before: Option<i64>,
+    /// 只删除该类别的状态码 (`1xx`-`5xx`)
+    status_class: Option<String>,
+    /// 只删除请求模型或路由后模型为该值的日志
+    model: Option<String>,
+    /// 同时清空按天汇总的统计历史 (默认保留)
+    history: Option<bool>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ClearLogsResult {
+    /// 删除的日志条数
+    removed: usize,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/proxy/logs",
+    tag = "proxy",
+    params(ClearLogsQuery),
+    responses(
+        (status = 200, description = "删除请求日志；不带过滤条件时清空全部，带条件时只删除同时满足各条件的日志", body = ClearLogsResultResponse),
+        (status = 400, description = "状态码类别无效", body = EmptyResponse),
+    )
+)]
+async fn clear_proxy_logs(
+    State(state): State<Arc<WebApiState>>,
+    Query(query): Query<ClearLogsQuery>,
+) -> Response {
+    let status_class = match query.status_class.as_deref().map(LogFilter::parse_status_class).transpose() {
+        Ok(class) => class,
+        Err(e) => return (StatusCode::BAD_REQUEST, ApiResponse::<()>::fail(ErrorCode::InvalidRequest, e)).into_response(),
+    };
+    let filter = LogFilter {
+        before: query.before,
+        status_class,
+        model: query.model.map(|m| m.trim().to_string()).filter(|m| !m.is_empty()),
+    };
+    let history = query.history.unwrap_or(false);
+
+    let monitor_lock = state.monitor.read().await;
+    let removed = match monitor_lock.as_ref() {
+        Some(monitor) => {
+            let removed = monitor.clear_matching(&filter).await;
+            if history {
+                monitor.clear_history();
+            }
+            removed
+        }
+        None => {
+            if history {
+                if let Err(e) = modules::stats_history::clear() {
+                    return ApiResponse::<ClearLogsResult>::err(e).into_response();
+                }
+            }
+            0
+        }
+    };
+    ApiResponse::ok(ClearLogsResult { removed }).into_response()
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ReplayQuery {
+    /// 固定使用原请求的账号 (默认按当前调度选择账号)
+    same_account: Option<bool>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/proxy/logs/{id}/replay",
+    tag = "proxy",
+    params(("id" = String, Path, description = "日志 ID"), ReplayQuery),
+    responses(
+        (status = 200, description = "以原请求的密钥与客户端地址，将日志记录的入站请求经当前反代链路 (IP 过滤、鉴权、模型映射、账号调度) 重新发送，新日志标记 replay_of；流式请求改为非流式执行。需要反代服务运行且日志记录了请求体", body = ReplayResultResponse),
+    )
+)]
+async fn replay_proxy_log(
+    State(state): State<Arc<WebApiState>>,
+    Path(log_id): Path<String>,
+    Query(query): Query<ReplayQuery>,
+) -> Response {
+    let Some(monitor) = state.monitor.read().await.clone() else {
+        return ApiResponse::<ReplayResult>::fail(ErrorCode::ProxyNotRunning, "反代服务未运行").into_response();
+    };
+    let instance_lock = state.proxy_instance.read().await;
+    let Some(instance) = instance_lock.as_ref() else {
+        return ApiResponse::<ReplayResult>::fail(ErrorCode::ProxyNotRunning, "反代服务未运行").into_response();
+    };
+    let same_account = query.same_account.unwrap_or(false);
+    match modules::replay::replay(&instance.axum_server, &monitor, &log_id, same_account).await {
+        Ok(result) => ApiResponse::ok(result).into_response(),
+        Err(e) => ApiResponse::<ReplayResult>::err(e).into_response(),
+    }
+}
+
+/// 请求监控的开关状态；反代尚未启动过时按配置返回
+async fn monitor_status(state: &WebApiState) -> Result<MonitorStatus, String> {
+    let config = modules::config::load_app_config()?;
+    let persisted = config.proxy.enable_logging;
+    match state.monitor.read().await.as_ref() {
+        Some(monitor) => Ok(monitor.status(persisted).await),
+        None => Ok(MonitorStatus {
+            enabled: persisted,
+            buffer_size: config.proxy.monitor_buffer_size,
+            entries: 0,
+            persisted,
+        }),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/proxy/monitor",
+    tag = "proxy",
+    responses(
+        (status = 200, description = "请求监控开关状态", body = MonitorStatusResponse),
+    )
+)]
+async fn get_proxy_monitor_status(
+    State(state): State<Arc<WebApiState>>,
+) -> impl IntoResponse {
+    match monitor_status(&state).await {
+        Ok(status) => ApiResponse::ok(status),
+        Err(e) => ApiResponse::<MonitorStatus>::err(e),
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct SetMonitorRequest {
+    enabled: bool,
+    /// 为 true 时只临时切换，不写入配置
+    #[serde(default)]
+    transient: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/proxy/monitor",
+    tag = "proxy",
+    request_body = SetMonitorRequest,
+    responses(
+        (status = 200, description = "开关请求监控，默认写入配置，返回切换后的状态；同时支持 PUT", body = MonitorStatusResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn set_proxy_monitor_enabled(
+    State(state): State<Arc<WebApiState>>,
+    AppJson(req): AppJson<SetMonitorRequest>,
+) -> impl IntoResponse {
+    {
+        let monitor_lock = state.monitor.read().await;
+        if req.transient {
+            match monitor_lock.as_ref() {
+                Some(monitor) => monitor.set_transient(req.enabled),
+                None => return ApiResponse::<MonitorStatus>::fail(ErrorCode::ProxyNotRunning, "反代服务尚未启动，无法临时切换监控"),
+            }
+        } else {
+            if let Err(e) = crate::proxy::monitor::save_enabled(req.enabled) {
+                return ApiResponse::<MonitorStatus>::err(e);
+            }
+            if let Some(monitor) = monitor_lock.as_ref() {
+                monitor.set_enabled(req.enabled);
+            }
+        }
+    }
+    match monitor_status(&state).await {
+        Ok(status) => ApiResponse::ok(status),
+        Err(e) => ApiResponse::<MonitorStatus>::err(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/proxy/reload-accounts",
+    tag = "proxy",
+    responses(
+        (status = 200, description = "重新加载账号池，返回加载数量", body = CountResponse),
+    )
+)]
+async fn reload_proxy_accounts(
+    State(state): State<Arc<WebApiState>>,
+) -> impl IntoResponse {
+    let instance_lock = state.proxy_instance.read().await;
+
+    if let Some(instance) = instance_lock.as_ref() {
+        match instance.token_manager.load_accounts().await {
+            Ok(count) => ApiResponse::ok(count),
+            Err(e) => ApiResponse::<usize>::err(format!("重新加载账号失败: {}", e)),
+        }
+    } else {
+        ApiResponse::<usize>::fail(ErrorCode::ProxyNotRunning, "服务未运行")
+    }
+}
+
+/// 内部辅助函数：重新加载账号池
+async fn reload_proxy_accounts_internal(state: &WebApiState) {
+    let instance_lock = state.proxy_instance.read().await;
+    if let Some(instance) = instance_lock.as_ref() {
+        let _ = instance.token_manager.load_accounts().await;
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/proxy/model-mapping",
+    tag = "proxy",
+    request_body = ProxyConfig,
+    responses(
+        (status = 200, description = "更新模型映射", body = EmptyResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn update_model_mapping(
+    State(state): State<Arc<WebApiState>>,
+    AppJson(config): AppJson<ProxyConfig>,
+) -> impl IntoResponse {
+    let instance_lock = state.proxy_instance.read().await;
+    if let Some(instance) = instance_lock.as_ref() {
+        instance.axum_server.update_mapping(&config).await;
+    }
+
+    // 保存到配置
+    if let Ok(mut app_config) = modules::config::load_app_config() {
+        app_config.proxy.custom_mapping = config.custom_mapping;
+        let _ = modules::config::save_app_config(&app_config);
+    }
+
+    ApiResponse::ok(())
+}
+
+#[derive(Serialize, ToSchema)]
+struct ExperimentalState {
+    config: crate::proxy::config::ExperimentalConfig,
+    /// 各开关的说明、默认值与是否需要重启
+    flags: Vec<crate::proxy::config::ExperimentalFlag>,
+}
+
+impl ExperimentalState {
+    fn of(config: crate::proxy::config::ExperimentalConfig) -> Self {
+        Self { flags: config.flags(), config }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/proxy/experimental",
+    tag = "proxy",
+    responses(
+        (status = 200, description = "当前实验性开关及其说明", body = ExperimentalStateResponse),
+    )
+)]
+async fn get_proxy_experimental() -> impl IntoResponse {
+    match modules::config::load_app_config() {
+        Ok(config) => ApiResponse::ok(ExperimentalState::of(config.proxy.experimental)),
+        Err(e) => ApiResponse::<ExperimentalState>::err(e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/proxy/mirror/stats",
+    tag = "proxy",
+    responses(
+        (status = 200, description = "反代进程启动以来镜像请求的发出数、镜像实例的成功/失败数、平均耗时与最近一次状态；镜像配置见 experimental.mirror", body = MirrorStatsResponse),
+    )
+)]
+async fn get_proxy_mirror_stats() -> impl IntoResponse {
+    ApiResponse::ok(crate::proxy::middleware::mirror::stats())
+}
+
+#[derive(Deserialize, ToSchema)]
+struct PromptRulesDryRunRequest {
+    /// 模拟的请求路径，如 `/v1/messages`、`/v1/chat/completions`、`/v1beta/models/gemini-3-pro:generateContent`
+    path: String,
+    /// 模拟的 API 密钥名
+    #[serde(default)]
+    api_key_name: Option<String>,
+    /// 客户端请求体
+    #[schema(value_type = Object)]
+    body: serde_json::Value,
+    /// 待验证的规则，未提供时使用已保存的 `proxy.prompt_rules`
+    #[serde(default)]
+    rules: Option<Vec<crate::proxy::PromptRule>>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct PromptRulesDryRun {
+    /// 命中的规则名 (按执行顺序)
+    fired: Vec<String>,
+    /// 改写后的请求体
+    #[schema(value_type = Object)]
+    body: serde_json::Value,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/proxy/prompt-rules/dry-run",
+    tag = "proxy",
+    request_body = PromptRulesDryRunRequest,
+    responses(
+        (status = 200, description = "按提示词规则改写示例请求体并返回结果，不发往上游", body = PromptRulesDryRunResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn dry_run_prompt_rules(AppJson(req): AppJson<PromptRulesDryRunRequest>) -> impl IntoResponse {
+    let rules = match req.rules {
+        Some(rules) => rules,
+        None => match modules::config::load_app_config() {
+            Ok(config) => config.proxy.prompt_rules,
+            Err(e) => return ApiResponse::<PromptRulesDryRun>::err(e),
+        },
+    };
+    let path = req.path.split('?').next().unwrap_or_default();
+    if crate::proxy::middleware::prompt_rules::PromptFormat::detect(path, &req.body).is_none() {
+        return ApiResponse::<PromptRulesDryRun>::fail(ErrorCode::InvalidRequest, format!("不支持改写 system 提示词的请求路径: {}", req.path));
+    }
+    let mut body = req.body;
+    let fired = crate::proxy::middleware::prompt_rules::apply_rules(&rules, path, req.api_key_name.as_deref(), &mut body);
+    ApiResponse::ok(PromptRulesDryRun { fired, body })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/proxy/security",
+    tag = "proxy",
+    responses(
+        (status = 200, description = "运行中反代实际执行的安全配置：鉴权模式 (含 auto 解析结果)、是否需要密钥、脱敏后的密钥与客户端白名单；需要反代服务运行", body = SecuritySummaryResponse),
+    )
+)]
+async fn get_proxy_security(State(state): State<Arc<WebApiState>>) -> impl IntoResponse {
+    let instance_lock = state.proxy_instance.read().await;
+    match instance_lock.as_ref() {
+        Some(instance) => ApiResponse::ok(instance.axum_server.security().await.summary()),
+        None => ApiResponse::<SecuritySummary>::fail(ErrorCode::ProxyNotRunning, "反代服务未运行"),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct UnauthorizedQuery {
+    /// 返回失败次数最多的来源条数 (默认 20)
+    limit: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/proxy/security/unauthorized",
+    tag = "proxy",
+    params(UnauthorizedQuery),
+    responses(
+        (status = 200, description = "反代鉴权失败次数最多的来源 IP (次数、连续失败次数、首次/最近时间、最近路径) 与当前封禁列表；仅保存在内存中，重启反代后清空", body = UnauthorizedReportResponse),
+    )
+)]
+async fn get_proxy_unauthorized(
+    State(state): State<Arc<WebApiState>>,
+    Query(query): Query<UnauthorizedQuery>,
+) -> impl IntoResponse {
+    let instance_lock = state.proxy_instance.read().await;
+    let Some(instance) = instance_lock.as_ref() else {
+        return ApiResponse::<UnauthorizedReport>::fail(ErrorCode::ProxyNotRunning, "反代服务未运行");
+    };
+    let limit = query.limit.unwrap_or(20).clamp(1, 1000);
+    let now = chrono::Utc::now().timestamp_millis();
+    ApiResponse::ok(instance.axum_server.unauthorized().report(limit, now))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ClearBansQuery {
+    /// 只解除该地址的封禁，为空时解除全部
+    ip: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct ClearBansResult {
+    /// 解除封禁的地址数
+    cleared: usize,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/proxy/security/unauthorized",
+    tag = "proxy",
+    params(ClearBansQuery),
+    responses(
+        (status = 200, description = "解除来源 IP 的封禁并清零其连续失败次数 (保留累计次数)", body = ClearBansResultResponse),
+        (status = 400, description = "IP 地址无效", body = EmptyResponse),
+    )
+)]
+async fn clear_proxy_bans(
+    State(state): State<Arc<WebApiState>>,
+    Query(query): Query<ClearBansQuery>,
+) -> Response {
+    let ip = match query.ip.as_deref().map(str::trim).filter(|ip| !ip.is_empty()) {
+        Some(ip) => match ip.parse::<std::net::IpAddr>() {
+            Ok(ip) => Some(ip),
+            Err(_) => {
+                return (StatusCode::BAD_REQUEST, ApiResponse::<()>::fail(ErrorCode::InvalidRequest, format!("无效的 IP 地址: {}", ip)))
+                    .into_response()
+            }
+        },
+        None => None,
+    };
+    let instance_lock = state.proxy_instance.read().await;
+    let Some(instance) = instance_lock.as_ref() else {
+        return ApiResponse::<ClearBansResult>::fail(ErrorCode::ProxyNotRunning, "反代服务未运行").into_response();
+    };
+    let now = chrono::Utc::now().timestamp_millis();
+    let cleared = instance.axum_server.unauthorized().clear_bans(ip, now);
+    if cleared > 0 {
+        tracing::info!("已解除 {} 个反代来源地址的封禁", cleared);
+    }
+    ApiResponse::ok(ClearBansResult { cleared }).into_response()
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/proxy/experimental",
+    tag = "proxy",
+    request_body(content = HashMap<String, bool>, description = "需要修改的开关 (名称 -> 是否启用)，未列出的保持不变"),
+    responses(
+        (status = 200, description = "保存并热更新实验性开关，返回更新后的状态", body = ExperimentalStateResponse),
+        (status = 400, description = "请求体解析失败或包含未知开关", body = EmptyResponse),
+    )
+)]
+async fn update_proxy_experimental(
+    State(state): State<Arc<WebApiState>>,
+    AppJson(updates): AppJson<HashMap<String, bool>>,
+) -> Response {
+    // 先校验名称，未知开关返回 400 而不是保存失败
+    if let Err(e) = crate::proxy::config::ExperimentalConfig::default().apply_updates(&updates) {
+        return (StatusCode::BAD_REQUEST, ApiResponse::<()>::fail(ErrorCode::InvalidRequest, e)).into_response();
+    }
+    match modules::config::update_app_config(|config| config.proxy.experimental.apply_updates(&updates)) {
+        Ok(((), config)) => {
+            apply_saved_config(&state, &config).await;
+            ApiResponse::ok(ExperimentalState::of(config.proxy.experimental)).into_response()
+        }
+        Err(e) => ApiResponse::<ExperimentalState>::err(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/proxy/scheduling",
+    tag = "proxy",
+    responses(
+        (status = 200, description = "获取调度配置", body = SchedulingResponse),
+    )
+)]
+async fn get_proxy_scheduling_config(
+    State(state): State<Arc<WebApiState>>,
+) -> impl IntoResponse {
+    let instance_lock = state.proxy_instance.read().await;
+    if let Some(instance) = instance_lock.as_ref() {
+        ApiResponse::ok(instance.token_manager.get_sticky_config().await)
+    } else {
+        ApiResponse::ok(crate::proxy::sticky_config::StickySessionConfig::default())
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/proxy/scheduling",
+    tag = "proxy",
+    request_body = StickySessionConfig,
+    responses(
+        (status = 200, description = "更新调度配置", body = EmptyResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn update_proxy_scheduling_config(
+    State(state): State<Arc<WebApiState>>,
+    AppJson(config): AppJson<crate::proxy::sticky_config::StickySessionConfig>,
+) -> impl IntoResponse {
+    let instance_lock = state.proxy_instance.read().await;
+    if let Some(instance) = instance_lock.as_ref() {
+        instance.token_manager.update_sticky_config(config).await;
+        ApiResponse::ok(())
+    } else {
+        ApiResponse::<()>::fail(ErrorCode::ProxyNotRunning, "服务未运行")
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct TokenManagerDebugQuery {
+    /// 只返回指定账号
+    account_id: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/proxy/debug/token-manager",
+    tag = "proxy",
+    params(TokenManagerDebugQuery),
+    responses(
+        (status = 200, description = "账号调度状态快照 (token 已脱敏)：各账号冷却状态、token 过期时间、调度权重、启动以来的选中次数，以及调度配置与会话绑定数；需要 admin 角色", body = TokenManagerSnapshotResponse),
+    )
+)]
+async fn debug_token_manager(
+    State(state): State<Arc<WebApiState>>,
+    Query(query): Query<TokenManagerDebugQuery>,
+) -> impl IntoResponse {
+    let token_manager = match state.proxy_instance.read().await.as_ref() {
+        Some(instance) => instance.token_manager.clone(),
+        None => return ApiResponse::<TokenManagerSnapshot>::fail(ErrorCode::ProxyNotRunning, "服务未运行"),
+    };
+    let account_id = query.account_id.as_deref().filter(|id| !id.is_empty());
+    let snapshot = token_manager.debug_snapshot(account_id).await;
+    if let Some(id) = account_id {
+        if snapshot.accounts.is_empty() {
+            return ApiResponse::<TokenManagerSnapshot>::fail(ErrorCode::AccountNotFound, format!("账号 {} 不在调度池中 (未加载或已禁用)", id));
+        }
+    }
+    ApiResponse::ok(snapshot)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/proxy/sessions",
+    tag = "proxy",
+    responses(
+        (status = 200, description = "清除会话绑定", body = EmptyResponse),
+    )
+)]
+async fn clear_proxy_session_bindings(
+    State(state): State<Arc<WebApiState>>,
+) -> impl IntoResponse {
+    let instance_lock = state.proxy_instance.read().await;
+    if let Some(instance) = instance_lock.as_ref() {
+        instance.token_manager.clear_all_sessions();
+        ApiResponse::ok(())
+    } else {
+        ApiResponse::<()>::fail(ErrorCode::ProxyNotRunning, "服务未运行")
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct FetchZaiModelsRequest {
+    zai: crate::proxy::ZaiConfig,
+    upstream_proxy: crate::proxy::config::UpstreamProxyConfig,
+    request_timeout: u64,
+}
+
+// Helper functions for fetch_zai_models (inlined from commands/proxy.rs)
+fn join_base_url(base: &str, path: &str) -> String {
+    let base = base.trim_end_matches('/');
+    let path = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("/{}", path)
+    };
+    format!("{}{}", base, path)
+}
+
+fn extract_model_ids(value: &serde_json::Value) -> Vec<String> {
+    let mut out = Vec::new();
+
+    fn push_from_item(out: &mut Vec<String>, item: &serde_json::Value) {
+        match item {
+            serde_json::Value::String(s) => out.push(s.to_string()),
+            serde_json::Value::Object(map) => {
+                if let Some(id) = map.get("id").and_then(|v| v.as_str()) {
+                    out.push(id.to_string());
+                } else if let Some(name) = map.get("name").and_then(|v| v.as_str()) {
+                    out.push(name.to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    match value {
+        serde_json::Value::Array(arr) => {
+            for item in arr {
+                push_from_item(&mut out, item);
+            }
+        }
+        serde_json::Value::Object(map) => {
+            if let Some(data) = map.get("data") {
+                if let serde_json::Value::Array(arr) = data {
+                    for item in arr {
+                        push_from_item(&mut out, item);
+                    }
+                }
+            }
+            if let Some(models) = map.get("models") {
+                match models {
+                    serde_json::Value::Array(arr) => {
+                        for item in arr {
+                            push_from_item(&mut out, item);
+                        }
+                    }
+                    other => push_from_item(&mut out, other),
+                }
+            }
+        }
+        _ => {}
+    }
+
+    out
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/proxy/zai-models",
+    tag = "proxy",
+    request_body = FetchZaiModelsRequest,
+    responses(
+        (status = 200, description = "拉取 z.ai 模型列表", body = StringListResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn fetch_zai_models(
+    State(_state): State<Arc<WebApiState>>,
+    AppJson(req): AppJson<FetchZaiModelsRequest>,
+) -> impl IntoResponse {
+    let result = async {
+        if req.zai.base_url.trim().is_empty() {
+            return Err("z.ai base_url is empty".to_string());
+        }
+        if req.zai.api_key.trim().is_empty() {
+            return Err("z.ai api_key is not set".to_string());
+        }
+
+        let url = join_base_url(&req.zai.base_url, "/v1/models");
+
+        let mut builder = reqwest::Client::builder()
+            .timeout(Duration::from_secs(req.request_timeout.max(5)));
+        if req.upstream_proxy.enabled && !req.upstream_proxy.url.is_empty() {
+            let proxy = reqwest::Proxy::all(&req.upstream_proxy.url)
+                .map_err(|e| format!("Invalid upstream proxy url: {}", e))?;
+            builder = builder.proxy(proxy);
+        }
+        let client = builder
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+
+        let resp = client
+            .get(&url)
+            .header("Authorization", format!("Bearer {}", req.zai.api_key))
+            .header("x-api-key", &req.zai.api_key)
+            .header("anthropic-version", "2023-06-01")
+            .header("accept", "application/json")
+            .send()
+            .await
+            .map_err(|e| format!("Upstream request failed: {}", e))?;
 
         let status = resp.status();
         let text = resp.text().await.map_err(|e| format!("Failed to read response: {}", e))?;
 
-        if !status.is_success() {
-            let preview = if text.len() > 4000 { &text[..4000] } else { &text };
-            return Err(format!("Upstream returned {}: {}", status, preview));
+        if !status.is_success() {
+            let preview = if text.len() > 4000 { &text[..4000] } else { &text };
+            return Err(format!("Upstream returned {}: {}", status, preview));
+        }
+
+        let json: serde_json::Value =
+            serde_json::from_str(&text).map_err(|e| format!("Invalid JSON response: {}", e))?;
+        let mut models = extract_model_ids(&json);
+        models.retain(|s| !s.trim().is_empty());
+        models.sort();
+        models.dedup();
+        Ok(models)
+    }
+    .await;
+
+    match result {
+        Ok(models) => ApiResponse::ok(models),
+        Err(e) => ApiResponse::<Vec<String>>::err(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/proxy/test-upstream",
+    tag = "proxy",
+    responses(
+        (status = 200, description = "按已保存的上游代理与 DNS 覆盖配置连接各上游主机，返回连通性与实际连接的地址，可据此确认 DNS 覆盖是否生效", body = UpstreamProbeListResponse),
+    )
+)]
+async fn test_upstream() -> impl IntoResponse {
+    match modules::config::load_app_config() {
+        Ok(config) => ApiResponse::ok(modules::upstream_probe::probe(&config.proxy).await),
+        Err(e) => ApiResponse::<Vec<UpstreamProbe>>::err(e),
+    }
+}
+
+
+#[derive(Deserialize, Default, ToSchema)]
+struct GenerateApiKeyRequest {
+    /// 提供时以该名称保存为具名密钥 (已存在则轮换其密钥)
+    #[serde(default)]
+    name: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/proxy/generate-api-key",
+    tag = "proxy",
+    request_body(content = Option<GenerateApiKeyRequest>, description = "可选"),
+    responses(
+        (status = 200, description = "生成新的 API Key", body = StringResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn generate_api_key(
+    State(state): State<Arc<WebApiState>>,
+    body: axum::body::Bytes,
+) -> Response {
+    let req: GenerateApiKeyRequest = match parse_optional_body(&body) {
+        Ok(req) => req,
+        Err(e) => return invalid_json(e),
+    };
+    let key = crate::proxy::config::new_api_key();
+
+    let Some(name) = req.name else {
+        return ApiResponse::ok(key).into_response();
+    };
+    let upsert = ApiKeyUpsertRequest {
+        name,
+        key: Some(key),
+        enabled: None,
+        requests_per_minute: None,
+        concurrent_requests: None,
+        admin: None,
+        group: None,
+    };
+    match upsert_api_key_entry(&state, upsert).await {
+        Ok(entry) => ApiResponse::ok(entry.key).into_response(),
+        Err(e) => ApiResponse::<String>::err(e).into_response(),
+    }
+}
+
+/// 具名 API 密钥 (列表中密钥已脱敏)
+#[derive(Serialize, ToSchema)]
+struct ApiKeyInfo {
+    name: String,
+    key: String,
+    enabled: bool,
+    requests_per_minute: Option<u32>,
+    concurrent_requests: Option<u32>,
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ApiKeyUpsertRequest {
+    name: String,
+    /// 新建时缺省自动生成；更新时缺省保留原密钥
+    #[serde(default)]
+    key: Option<String>,
+    /// 新建时缺省为启用；更新时缺省保持不变
+    #[serde(default)]
+    enabled: Option<bool>,
+    /// 每分钟最大请求数；缺省保持不变，0 表示取消限制
+    #[serde(default)]
+    requests_per_minute: Option<u32>,
+    /// 最大并发请求数；缺省保持不变，0 表示取消限制
+    #[serde(default)]
+    concurrent_requests: Option<u32>,
+    /// 绑定的账号分组；缺省保持不变，空字符串表示解除绑定
+    #[serde(default)]
+    group: Option<String>,
+    /// 是否为管理员密钥；新建时缺省为否，更新时缺省保持不变
+    #[serde(default)]
+    admin: Option<bool>,
+}
+
+/// 请求中的分组名：空字符串表示不绑定
+fn group_value(group: String) -> Option<String> {
+    Some(group.trim().to_string()).filter(|g| !g.is_empty())
+}
+
+/// 请求中的限制值：0 表示不限制
+fn limit_value(value: u32) -> Option<u32> {
+    (value > 0).then_some(value)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/proxy/api-keys",
+    tag = "proxy",
+    responses(
+        (status = 200, description = "列出具名 API 密钥 (不含旧版单一 api_key)", body = ApiKeyListResponse),
+    )
+)]
+async fn list_api_keys(
+    State(_state): State<Arc<WebApiState>>,
+) -> impl IntoResponse {
+    match modules::config::load_app_config() {
+        Ok(config) => ApiResponse::ok(
+            config
+                .proxy
+                .api_keys
+                .into_iter()
+                .map(|entry| ApiKeyInfo {
+                    name: entry.name,
+                    key: crate::models::token::mask_secret(&entry.key),
+                    enabled: entry.enabled,
+                    requests_per_minute: entry.requests_per_minute,
+                    concurrent_requests: entry.concurrent_requests,
+                })
+                .collect::<Vec<_>>(),
+        ),
+        Err(e) => ApiResponse::<Vec<ApiKeyInfo>>::err(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/proxy/api-keys",
+    tag = "proxy",
+    request_body = ApiKeyUpsertRequest,
+    responses(
+        (status = 200, description = "新建或更新具名 API 密钥，返回完整密钥；立即对运行中的反代生效", body = ApiKeyEntryResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn upsert_api_key(
+    State(state): State<Arc<WebApiState>>,
+    AppJson(req): AppJson<ApiKeyUpsertRequest>,
+) -> impl IntoResponse {
+    match upsert_api_key_entry(&state, req).await {
+        Ok(entry) => ApiResponse::ok(entry),
+        Err(e) => ApiResponse::<ApiKeyEntry>::err(e),
+    }
+}
+
+async fn upsert_api_key_entry(state: &WebApiState, req: ApiKeyUpsertRequest) -> Result<ApiKeyEntry, String> {
+    let name = req.name.trim().to_string();
+    let (entry, config) = modules::config::update_app_config(|config| {
+        let keys = &mut config.proxy.api_keys;
+        let entry = match keys.iter_mut().find(|entry| entry.name == name) {
+            Some(entry) => {
+                if let Some(key) = req.key {
+                    entry.key = key;
+                }
+                if let Some(enabled) = req.enabled {
+                    entry.enabled = enabled;
+                }
+                if let Some(rpm) = req.requests_per_minute {
+                    entry.requests_per_minute = limit_value(rpm);
+                }
+                if let Some(concurrent) = req.concurrent_requests {
+                    entry.concurrent_requests = limit_value(concurrent);
+                }
+                if let Some(group) = req.group {
+                    entry.group = group_value(group);
+                }
+                if let Some(admin) = req.admin {
+                    entry.admin = admin;
+                }
+                entry.clone()
+            }
+            None => {
+                let entry = ApiKeyEntry {
+                    name: name.clone(),
+                    key: req.key.unwrap_or_else(crate::proxy::config::new_api_key),
+                    enabled: req.enabled.unwrap_or(true),
+                    requests_per_minute: req.requests_per_minute.and_then(limit_value),
+                    concurrent_requests: req.concurrent_requests.and_then(limit_value),
+                    group: req.group.and_then(group_value),
+                    admin: req.admin.unwrap_or(false),
+                };
+                keys.push(entry.clone());
+                entry
+            }
+        };
+        Ok(entry)
+    })?;
+    apply_saved_config(state, &config).await;
+    Ok(entry)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/proxy/api-keys/{name}/usage",
+    tag = "proxy",
+    params(("name" = String, Path, description = "密钥名")),
+    responses(
+        (status = 200, description = "查询具名密钥当前的限流状态 (令牌桶剩余、并发占用、被拒绝次数) 与为其服务的账号", body = KeyUsageResponse),
+    )
+)]
+async fn get_api_key_usage(
+    State(state): State<Arc<WebApiState>>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    let instance_lock = state.proxy_instance.read().await;
+    let Some(instance) = instance_lock.as_ref() else {
+        return ApiResponse::<KeyUsage>::fail(ErrorCode::ProxyNotRunning, "反代服务未运行");
+    };
+    match instance.axum_server.key_usage(&name).await {
+        Some(usage) => ApiResponse::ok(usage),
+        None => ApiResponse::<KeyUsage>::fail(ErrorCode::NotFound, format!("API 密钥不存在: {}", name)),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/proxy/api-keys/{name}",
+    tag = "proxy",
+    params(("name" = String, Path, description = "密钥名")),
+    responses(
+        (status = 200, description = "删除具名 API 密钥", body = EmptyResponse),
+    )
+)]
+async fn delete_api_key(
+    State(state): State<Arc<WebApiState>>,
+    Path(name): Path<String>,
+) -> impl IntoResponse {
+    let exists = modules::config::load_app_config()
+        .is_ok_and(|config| config.proxy.api_keys.iter().any(|entry| entry.name == name));
+    if !exists {
+        return ApiResponse::<()>::fail(ErrorCode::NotFound, format!("API 密钥不存在: {}", name));
+    }
+    let result = modules::config::update_app_config(|config| {
+        config.proxy.api_keys.retain(|entry| entry.name != name);
+        Ok(())
+    });
+
+    match result {
+        Ok(((), config)) => {
+            apply_saved_config(&state, &config).await;
+            ApiResponse::ok(())
+        }
+        Err(e) => ApiResponse::<()>::err(e),
+    }
+}
+
+// ============================================================================
+// OAuth API (简化版)
+// ============================================================================
+
+/// OAuth URL 响应
+#[derive(Serialize, ToSchema)]
+struct OAuthUrlResponse {
+    url: String,
+    redirect_uri: String,
+    /// 实际使用的回调端口
+    redirect_port: u16,
+    /// 是否已在本机启动回调监听 (浏览器跳转后自动完成授权，无需手动粘贴)
+    listening: bool,
+    /// 未能启动监听时的原因 (如端口被占用)，此时仍可手动粘贴回调 URL
+    listener_error: Option<String>,
+}
+
+#[derive(Deserialize, Default, ToSchema)]
+struct PrepareOAuthRequest {
+    /// 是否在本机启动回调监听；缺省时仅当通过 localhost 访问管理页面时启动
+    #[serde(default)]
+    local_listener: Option<bool>,
+    /// 指定回调端口，须为 `oauth.redirect_port` 或 `oauth.fallback_ports` 之一
+    #[serde(default)]
+    redirect_port: Option<u16>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/oauth/prepare-url",
+    tag = "oauth",
+    request_body(content = Option<PrepareOAuthRequest>, description = "可选"),
+    responses(
+        (status = 200, description = "生成 OAuth 授权链接", body = OAuthUrlApiResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn prepare_oauth_url(
+    State(state): State<Arc<WebApiState>>,
+    RequestOrigin(origin): RequestOrigin,
+    body: axum::body::Bytes,
+) -> Response {
+    // 请求体可省略 (旧版前端不发送 body)
+    let req: PrepareOAuthRequest = match parse_optional_body(&body) {
+        Ok(req) => req,
+        Err(e) => return invalid_json(e),
+    };
+
+    let oauth_config = match modules::config::load_app_config() {
+        Ok(config) => config.oauth,
+        Err(e) => return ApiResponse::<OAuthUrlResponse>::err(e).into_response(),
+    };
+    let allowed = oauth_config.allowed_ports();
+
+    // 显式指定端口时只使用该端口，否则按默认端口、备用端口的顺序尝试
+    let candidates = match req.redirect_port {
+        Some(port) if allowed.contains(&port) => vec![port],
+        Some(port) => {
+            return ApiResponse::<OAuthUrlResponse>::fail(ErrorCode::InvalidRequest, format!(
+                "回调端口 {} 不在允许列表中 (允许: {:?})",
+                port, allowed
+            ))
+            .into_response()
+        }
+        None => allowed,
+    };
+
+    // 浏览器与服务端在同一台机器上时，回调可直接打到本机监听 (经反向代理访问时以代理对外的地址判断)
+    let local_listener = req
+        .local_listener
+        .unwrap_or_else(|| origin.is_some_and(|origin| is_loopback_host(&origin.host)));
+    let (redirect_port, listening, listener_error) = if local_listener {
+        match oauth_listener::ensure_started(state.clone(), &candidates).await {
+            Ok(port) => (port, true, None),
+            Err(e) => (candidates[0], false, Some(e)),
+        }
+    } else {
+        (candidates[0], false, None)
+    };
+
+    // Web 模式下返回 OAuth URL，由用户在浏览器中打开
+    // redirect_uri 随 PKCE 一起保存，回调时原样用于换取 token
+    let redirect_uri = oauth_redirect_uri(redirect_port);
+    let pkce = modules::oauth::PkceParams::generate();
+    state.oauth_pending.insert(&pkce, &redirect_uri);
+    let url = modules::oauth::get_auth_url(&redirect_uri, Some(&pkce));
+
+    ApiResponse::ok(OAuthUrlResponse {
+        url,
+        redirect_uri,
+        redirect_port,
+        listening,
+        listener_error,
+    })
+    .into_response()
+}
+
+/// OAuth 回调地址 (需与 Google OAuth 客户端登记的 redirect_uri 一致)
+fn oauth_redirect_uri(port: u16) -> String {
+    format!("http://localhost:{}/callback", port)
+}
+
+/// 处理手动粘贴的 OAuth 回调 URL
+#[derive(Deserialize, ToSchema)]
+struct ProcessCallbackRequest {
+    callback_url: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/oauth/process-callback",
+    tag = "oauth",
+    request_body = ProcessCallbackRequest,
+    responses(
+        (status = 200, description = "处理手动粘贴的 OAuth 回调 URL", body = AccountResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn process_oauth_callback(
+    State(state): State<Arc<WebApiState>>,
+    AppJson(req): AppJson<ProcessCallbackRequest>,
+) -> impl IntoResponse {
+    let result = async {
+        // 1. 解析回调 URL 中的 code 与 state 参数
+        let url = url::Url::parse(&req.callback_url)
+            .map_err(|e| format!("无效的回调 URL: {}", e))?;
+        
+        let code = url.query_pairs()
+            .find(|(k, _)| k == "code")
+            .map(|(_, v)| v.to_string())
+            .ok_or_else(|| "回调 URL 中未找到 code 参数".to_string())?;
+
+        let oauth_state = url.query_pairs()
+            .find(|(k, _)| k == "state")
+            .map(|(_, v)| v.to_string())
+            .ok_or_else(|| "回调 URL 中未找到 state 参数，请使用本页面生成的授权链接".to_string())?;
+
+        complete_oauth(&state, &code, &oauth_state).await
+    }.await;
+    
+    match result {
+        Ok(account) => ApiResponse::ok(account),
+        Err(e) => ApiResponse::<Account>::err(e),
+    }
+}
+
+/// 校验 state 并用 code 换取 Token，保存账号
+///
+/// 手动粘贴与本机回调监听共用。
+async fn complete_oauth(state: &WebApiState, code: &str, oauth_state: &str) -> Result<Account, String> {
+    // 取回生成授权链接时的 PKCE verifier 与 redirect_uri
+    let pending = state.oauth_pending.take(oauth_state)?;
+
+    // 使用 code 交换 token
+    let token_res = modules::oauth::exchange_code(
+        code,
+        &pending.redirect_uri,
+        Some(&pending.code_verifier),
+    )
+    .await?;
+
+    save_oauth_account(state, token_res).await
+}
+
+/// 根据 OAuth Token 创建或更新账号，并通知前端
+async fn save_oauth_account(
+    state: &WebApiState,
+    token_res: modules::oauth::TokenResponse,
+) -> Result<Account, String> {
+    // 检查是否返回了 refresh_token
+    let refresh_token = token_res.refresh_token.ok_or_else(|| {
+        "OAuth 未返回 Refresh Token。可能原因：\n\
+         1. 此 Google 账号之前已授权过此应用\n\
+         2. 请访问 https://myaccount.google.com/permissions 撤销授权后重试"
+            .to_string()
+    })?;
+
+    // 获取用户信息
+    let user_info = modules::oauth::get_user_info(&token_res.access_token).await?;
+
+    let token_data = crate::models::TokenData::new(
+        token_res.access_token,
+        refresh_token,
+        token_res.expires_in,
+        Some(user_info.email.clone()),  // email: Option<String>
+        None,  // project_id
+        None,  // session_id
+    );
+
+    // 同一邮箱重复授权时更新已有账号
+    let account = modules::account::upsert_account(
+        user_info.email.clone(),
+        user_info.get_display_name(),
+        token_data,
+    )?;
+    let _ = modules::account::set_current_account_id(&account.id);
+
+    // 重新加载反代账号
+    reload_proxy_accounts_internal(state).await;
+
+    let _ = state.sse_tx.send(SseEvent::OAuthCompleted {
+        email: account.email.clone(),
+    });
+
+    Ok(account)
+}
+
+/// 本机 OAuth 回调监听：浏览器跳转到 `http://localhost:<port>/callback` 时自动完成授权
+mod oauth_listener {
+    use super::*;
+    use axum::extract::Query;
+    use axum::response::Html;
+    use std::time::Instant;
+
+    /// 无新授权请求时监听的存活时间
+    const LISTENER_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+    /// 正在运行的监听，`deadline` 在每次生成授权链接时顺延
+    pub struct ActiveListener {
+        port: u16,
+        deadline: Arc<std::sync::Mutex<Instant>>,
+        handle: tokio::task::JoinHandle<()>,
+    }
+
+    /// 在 `ports` 中按顺序选择可用端口启动监听，返回实际监听的端口
+    ///
+    /// 已在其中某个端口运行时只顺延超时；在其他端口运行的旧监听会被关闭。
+    pub async fn ensure_started(state: Arc<WebApiState>, ports: &[u16]) -> Result<u16, String> {
+        let mut active = state.oauth_listener.lock().await;
+        if let Some(listener) = active.as_ref().filter(|l| !l.handle.is_finished()) {
+            if ports.contains(&listener.port) {
+                *listener.deadline.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now() + LISTENER_TIMEOUT;
+                return Ok(listener.port);
+            }
+        }
+        if let Some(old) = active.take() {
+            old.handle.abort();
+        }
+
+        let mut last_error = "未配置可用的 OAuth 回调端口".to_string();
+        let mut bound = None;
+        for &port in ports {
+            match tokio::net::TcpListener::bind(("127.0.0.1", port)).await {
+                Ok(listener) => {
+                    bound = Some((port, listener));
+                    break;
+                }
+                Err(e) => {
+                    last_error = match e.kind() {
+                        std::io::ErrorKind::AddrInUse => format!(
+                            "端口 {} 已被其他程序占用，无法自动接收回调，请在授权后手动粘贴回调 URL",
+                            port
+                        ),
+                        _ => format!("启动 OAuth 回调监听失败: {}", e),
+                    };
+                    tracing::debug!("OAuth 回调端口 {} 不可用: {}", port, e);
+                }
+            }
+        }
+        let Some((port, listener)) = bound else {
+            return Err(last_error);
+        };
+
+        let deadline = Arc::new(std::sync::Mutex::new(Instant::now() + LISTENER_TIMEOUT));
+        let done = Arc::new(tokio::sync::Notify::new());
+
+        let app = Router::new()
+            .route("/callback", get(callback))
+            .with_state((state.clone(), done.clone()));
+
+        let shutdown = {
+            let deadline = deadline.clone();
+            async move {
+                loop {
+                    let remaining = deadline
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        tracing::info!("OAuth 回调监听超时关闭");
+                        break;
+                    }
+                    tokio::select! {
+                        _ = tokio::time::sleep(remaining) => {}
+                        _ = done.notified() => break,
+                    }
+                }
+            }
+        };
+
+        tracing::info!("OAuth 回调监听已启动: http://localhost:{}/callback", port);
+        let handle = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).with_graceful_shutdown(shutdown).await {
+                tracing::warn!("OAuth 回调监听异常退出: {}", e);
+            }
+        });
+
+        *active = Some(ActiveListener { port, deadline, handle });
+        Ok(port)
+    }
+
+    async fn callback(
+        State((state, done)): State<(Arc<WebApiState>, Arc<tokio::sync::Notify>)>,
+        Query(params): Query<HashMap<String, String>>,
+    ) -> Html<String> {
+        let result = match (params.get("code"), params.get("state")) {
+            (Some(code), Some(oauth_state)) => complete_oauth(&state, code, oauth_state).await,
+            _ => Err(params
+                .get("error")
+                .map(|e| format!("授权被拒绝: {}", e))
+                .unwrap_or_else(|| "回调缺少 code 或 state 参数".to_string())),
+        };
+
+        match result {
+            Ok(account) => {
+                done.notify_one();
+                Html(callback_page(
+                    "授权成功",
+                    &format!("账号 {} 已添加，可以关闭此页面。", account.email),
+                ))
+            }
+            Err(e) => Html(callback_page("授权失败", &e)),
+        }
+    }
+
+    fn callback_page(title: &str, message: &str) -> String {
+        let escape = |text: &str| {
+            text.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+        };
+        format!(
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{title}</title></head>\
+             <body style=\"font-family:sans-serif;text-align:center;padding-top:15vh\">\
+             <h2>{title}</h2><p>{message}</p></body></html>",
+            title = escape(title),
+            message = escape(message)
+        )
+    }
+}
+
+/// 设备授权状态
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum DeviceFlowStatus {
+    Pending,
+    Approved,
+    Denied,
+    Expired,
+    Failed,
+}
+
+/// 进行中 (或刚结束) 的设备授权
+struct DeviceFlow {
+    status: DeviceFlowStatus,
+    email: Option<String>,
+    error: Option<String>,
+    expires_at: std::time::Instant,
+}
+
+/// 设备授权结束后保留状态供前端查询的时长
+const DEVICE_FLOW_RETENTION: Duration = Duration::from_secs(10 * 60);
+
+/// 清理已过期并超过保留期的设备授权
+fn prune_device_flows(flows: &mut HashMap<String, DeviceFlow>) {
+    flows.retain(|_, flow| flow.expires_at + DEVICE_FLOW_RETENTION > std::time::Instant::now());
+}
+
+#[derive(Serialize, ToSchema)]
+struct DeviceFlowStart {
+    /// 用于查询授权状态
+    id: String,
+    verification_url: String,
+    user_code: String,
+    expires_in: u64,
+    interval: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+struct DeviceFlowState {
+    id: String,
+    status: DeviceFlowStatus,
+    /// 授权成功后添加的账号
+    email: Option<String>,
+    error: Option<String>,
+    /// 剩余有效期 (秒)
+    expires_in: u64,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/oauth/device/start",
+    tag = "oauth",
+    responses(
+        (status = 200, description = "发起设备授权，在任意设备上访问 verification_url 并输入 user_code", body = DeviceFlowStartResponse),
+    )
+)]
+async fn start_device_flow(
+    State(state): State<Arc<WebApiState>>,
+) -> impl IntoResponse {
+    let device = match modules::oauth::start_device_flow().await {
+        Ok(device) => device,
+        Err(e) => return ApiResponse::<DeviceFlowStart>::err(e),
+    };
+
+    let id = uuid::Uuid::new_v4().to_string();
+    {
+        let mut flows = state.device_flows.write().await;
+        prune_device_flows(&mut flows);
+        flows.insert(
+            id.clone(),
+            DeviceFlow {
+                status: DeviceFlowStatus::Pending,
+                email: None,
+                error: None,
+                expires_at: std::time::Instant::now() + Duration::from_secs(device.expires_in),
+            },
+        );
+    }
+
+    tokio::spawn(poll_device_flow(state.clone(), id.clone(), device.clone()));
+
+    ApiResponse::ok(DeviceFlowStart {
+        id,
+        verification_url: device.verification_url,
+        user_code: device.user_code,
+        expires_in: device.expires_in,
+        interval: device.interval,
+    })
+}
+
+/// 后台轮询设备授权，直到批准、拒绝或过期
+async fn poll_device_flow(
+    state: Arc<WebApiState>,
+    id: String,
+    device: modules::oauth::DeviceCodeResponse,
+) {
+    let mut interval = Duration::from_secs(device.interval.max(1));
+    let deadline = std::time::Instant::now() + Duration::from_secs(device.expires_in);
+
+    let (status, email, error) = loop {
+        tokio::time::sleep(interval).await;
+        if std::time::Instant::now() >= deadline {
+            break (DeviceFlowStatus::Expired, None, None);
         }
 
-        let json: serde_json::Value =
-            serde_json::from_str(&text).map_err(|e| format!("Invalid JSON response: {}", e))?;
-        let mut models = extract_model_ids(&json);
-        models.retain(|s| !s.trim().is_empty());
-        models.sort();
-        models.dedup();
-        Ok(models)
+        match modules::oauth::poll_device_token(&device.device_code).await {
+            Ok(modules::oauth::DevicePoll::Pending) => {}
+            Ok(modules::oauth::DevicePoll::SlowDown) => interval += Duration::from_secs(5),
+            Ok(modules::oauth::DevicePoll::Approved(token_res)) => {
+                break match save_oauth_account(&state, token_res).await {
+                    Ok(account) => (DeviceFlowStatus::Approved, Some(account.email), None),
+                    Err(e) => (DeviceFlowStatus::Failed, None, Some(e)),
+                };
+            }
+            Ok(modules::oauth::DevicePoll::Denied) => {
+                break (DeviceFlowStatus::Denied, None, Some("用户拒绝了授权".to_string()))
+            }
+            Ok(modules::oauth::DevicePoll::Expired) => break (DeviceFlowStatus::Expired, None, None),
+            Err(e) => break (DeviceFlowStatus::Failed, None, Some(e)),
+        }
+    };
+
+    if let Some(flow) = state.device_flows.write().await.get_mut(&id) {
+        flow.status = status;
+        flow.email = email;
+        flow.error = error;
     }
-    .await;
+}
 
-    match result {
-        Ok(models) => ApiResponse::ok(models),
-        Err(e) => ApiResponse::<Vec<String>>::err(e),
+#[utoipa::path(
+    get,
+    path = "/api/oauth/device/{id}",
+    tag = "oauth",
+    params(("id" = String, Path, description = "start 返回的授权 ID")),
+    responses(
+        (status = 200, description = "查询设备授权状态", body = DeviceFlowStateResponse),
+    )
+)]
+async fn get_device_flow(
+    State(state): State<Arc<WebApiState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let mut flows = state.device_flows.write().await;
+    prune_device_flows(&mut flows);
+
+    match flows.get(&id) {
+        Some(flow) => ApiResponse::ok(DeviceFlowState {
+            id,
+            status: flow.status,
+            email: flow.email.clone(),
+            error: flow.error.clone(),
+            expires_in: flow
+                .expires_at
+                .saturating_duration_since(std::time::Instant::now())
+                .as_secs(),
+        }),
+        None => ApiResponse::<DeviceFlowState>::fail(ErrorCode::NotFound, "设备授权不存在或已过期"),
     }
 }
 
+// ============================================================================
+// 导入 API
+// ============================================================================
 
-async fn generate_api_key(
-    State(_state): State<Arc<WebApiState>>,
+
+#[utoipa::path(
+    post,
+    path = "/api/import/v1",
+    tag = "import",
+    request_body(content = Option<ImportOptions>, description = "可选"),
+    responses(
+        (status = 200, description = "从 V1 版本导入账号；dry_run 时只返回计划 (create/update/skip/error)", body = ImportReportResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn import_v1_accounts(
+    State(state): State<Arc<WebApiState>>,
+    body: axum::body::Bytes,
+) -> Response {
+    let options: ImportOptions = match parse_optional_body(&body) {
+        Ok(options) => options,
+        Err(e) => return invalid_json(e),
+    };
+    match modules::migration::import_from_v1(options).await {
+        Ok(report) => {
+            if !report.outcomes.is_empty() {
+                reload_proxy_accounts_internal(&state).await;
+            }
+            ApiResponse::ok(report).into_response()
+        }
+        Err(e) => ApiResponse::<ImportReport>::err(e).into_response(),
+    }
+}
+
+/// 单账号导入完成后设为当前账号并重载反代账号池
+async fn finish_single_import(state: &WebApiState, report: &ImportReport) {
+    if let Some(outcome) = report.outcomes.first() {
+        let _ = modules::account::set_current_account_id(&outcome.account.id);
+        reload_proxy_accounts_internal(state).await;
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/import/db",
+    tag = "import",
+    request_body(content = Option<ImportOptions>, description = "可选"),
+    responses(
+        (status = 200, description = "从 Antigravity 数据库导入当前账号；dry_run 时只返回计划", body = ImportReportResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn import_from_db(
+    State(state): State<Arc<WebApiState>>,
+    body: axum::body::Bytes,
+) -> Response {
+    let options: ImportOptions = match parse_optional_body(&body) {
+        Ok(options) => options,
+        Err(e) => return invalid_json(e),
+    };
+    match modules::migration::import_from_db(options).await {
+        Ok(report) => {
+            finish_single_import(&state, &report).await;
+            ApiResponse::ok(report).into_response()
+        }
+        Err(e) => ApiResponse::<ImportReport>::err(e).into_response(),
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ImportCustomDbRequest {
+    path: String,
+    /// 邮箱已存在时的处理方式，缺省为 `update`
+    #[serde(default)]
+    on_duplicate: OnDuplicate,
+    /// 只返回导入计划，不写入任何文件
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/import/custom-db",
+    tag = "import",
+    request_body = ImportCustomDbRequest,
+    responses(
+        (status = 200, description = "从指定数据库文件导入账号；dry_run 时只返回计划", body = ImportReportResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn import_custom_db(
+    State(state): State<Arc<WebApiState>>,
+    AppJson(req): AppJson<ImportCustomDbRequest>,
+) -> impl IntoResponse {
+    let options = ImportOptions { on_duplicate: req.on_duplicate, dry_run: req.dry_run };
+    match modules::migration::import_from_custom_db_path(req.path, options).await {
+        Ok(report) => {
+            finish_single_import(&state, &report).await;
+            ApiResponse::ok(report)
+        }
+        Err(e) => ApiResponse::<ImportReport>::err(e),
+    }
+}
+
+/// 上传导入的文件大小上限 (IDE 的 state.vscdb 可能有数十 MB)
+const MAX_IMPORT_UPLOAD_BYTES: usize = 64 * 1024 * 1024;
+
+/// multipart 表单字段 (仅用于文档)
+#[derive(ToSchema)]
+#[allow(dead_code)]
+struct ImportUploadForm {
+    /// V1 导出的 JSON 或 Antigravity 的 SQLite 数据库
+    #[schema(value_type = String, format = Binary)]
+    file: Vec<u8>,
+    /// 文件格式，缺省时按内容判断
+    format: Option<UploadFormat>,
+    /// 邮箱已存在时的处理方式，缺省为 `update`
+    on_duplicate: Option<OnDuplicate>,
+    /// 只返回导入计划，不写入账号
+    dry_run: Option<bool>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/import/upload",
+    tag = "import",
+    request_body(content = ImportUploadForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "从上传的文件导入账号；dry_run 时只返回计划", body = ImportReportResponse),
+        (status = 400, description = "表单解析失败或缺少文件", body = EmptyResponse),
+        (status = 413, description = "文件超过 64 MB", body = EmptyResponse),
+    )
+)]
+async fn import_upload(
+    State(state): State<Arc<WebApiState>>,
+    mut multipart: Multipart,
+) -> Response {
+    let bad_request = |message: String| (StatusCode::BAD_REQUEST, ApiResponse::<()>::fail(ErrorCode::InvalidRequest, message)).into_response();
+
+    let mut file: Option<Vec<u8>> = None;
+    let mut format: Option<UploadFormat> = None;
+    let mut options = ImportOptions::default();
+    loop {
+        let mut field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return bad_request(format!("解析表单失败: {}", e)),
+        };
+        let name = field.name().unwrap_or_default().to_string();
+        match name.as_str() {
+            "file" => {
+                let mut data = Vec::new();
+                loop {
+                    match field.chunk().await {
+                        Ok(Some(chunk)) => {
+                            if data.len() + chunk.len() > MAX_IMPORT_UPLOAD_BYTES {
+                                return (
+                                    StatusCode::PAYLOAD_TOO_LARGE,
+                                    ApiResponse::<()>::fail(ErrorCode::PayloadTooLarge, format!("文件超过 {} MB", MAX_IMPORT_UPLOAD_BYTES / 1024 / 1024)),
+                                )
+                                    .into_response();
+                            }
+                            data.extend_from_slice(&chunk);
+                        }
+                        Ok(None) => break,
+                        Err(e) => return bad_request(format!("读取文件失败: {}", e)),
+                    }
+                }
+                file = Some(data);
+            }
+            "format" | "on_duplicate" | "dry_run" => {
+                let text = match field.text().await {
+                    Ok(text) => text,
+                    Err(e) => return bad_request(format!("读取字段 {} 失败: {}", name, e)),
+                };
+                let text = text.trim();
+                let value = serde_json::Value::String(text.to_string());
+                let parsed = match name.as_str() {
+                    "format" => serde_json::from_value(value).map(|f| format = Some(f)),
+                    "on_duplicate" => serde_json::from_value(value).map(|d| options.on_duplicate = d),
+                    _ => serde_json::from_str(text).map(|d| options.dry_run = d),
+                };
+                if let Err(e) = parsed {
+                    return bad_request(format!("字段 {} 无效: {}", name, e));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let Some(file) = file.filter(|f| !f.is_empty()) else {
+        return bad_request("缺少上传文件 (字段 file)".to_string());
+    };
+
+    match modules::migration::import_from_upload(&file, format, options).await {
+        Ok(report) => {
+            if !report.outcomes.is_empty() {
+                reload_proxy_accounts_internal(&state).await;
+            }
+            ApiResponse::ok(report).into_response()
+        }
+        Err(e) => ApiResponse::<ImportReport>::err(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/sync/db",
+    tag = "import",
+    responses(
+        (status = 200, description = "与 Antigravity 数据库同步 (无变化时 data 为 null)", body = AccountResponse),
+    )
+)]
+async fn sync_account_from_db(
+    State(state): State<Arc<WebApiState>>,
 ) -> impl IntoResponse {
-    ApiResponse::ok(format!("sk-{}", uuid::Uuid::new_v4().simple()))
+    match modules::db_sync::sync_from_db().await {
+        Ok(account) => {
+            if let Some(account) = &account {
+                on_db_synced(&state, account).await;
+            }
+            ApiResponse::ok(account)
+        }
+        Err(e) => ApiResponse::<Option<Account>>::err(e.to_string()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/sync/status",
+    tag = "import",
+    responses(
+        (status = 200, description = "IDE 数据库同步状态 (最近一次同步时间与结果)", body = DbSyncStatusResponse),
+    )
+)]
+async fn get_db_sync_status() -> impl IntoResponse {
+    ApiResponse::ok(modules::db_sync::status())
+}
+
+async fn on_db_synced(state: &WebApiState, account: &Account) {
+    reload_proxy_accounts_internal(state).await;
+    let _ = state.sse_tx.send(SseEvent::DbSynced { email: account.email.clone() });
+}
+
+/// 按 `db_sync_interval_minutes` 定时与 IDE 数据库同步，间隔修改后下个周期生效
+pub fn spawn_db_sync(state: &Arc<WebApiState>) {
+    let weak = Arc::downgrade(state);
+    state.tasks.register("db-sync", move |ctx| {
+        let weak = weak.clone();
+        async move {
+            loop {
+                let interval = modules::config::load_app_config()
+                    .map(|c| c.db_sync_interval_minutes)
+                    .unwrap_or(0);
+                // 未开启时每分钟检查一次配置
+                let wait = if interval == 0 { 60 } else { u64::from(interval) * 60 };
+                if !ctx.sleep(Duration::from_secs(wait)).await {
+                    break;
+                }
+                if interval == 0 {
+                    continue;
+                }
+                // 失败已记录在同步状态中，下个周期重试
+                match modules::db_sync::sync_from_db().await {
+                    Ok(synced) => {
+                        if let (Some(account), Some(state)) = (synced, weak.upgrade()) {
+                            on_db_synced(&state, &account).await;
+                        }
+                        ctx.record_run(Ok(()));
+                    }
+                    Err(e) => ctx.record_run(Err(e.to_string())),
+                }
+            }
+        }
+    });
 }
 
 // ============================================================================
-// OAuth API (简化版)
+// 系统 API
 // ============================================================================
 
-/// OAuth URL 响应
-#[derive(Serialize)]
-struct OAuthUrlResponse {
-    url: String,
-    redirect_uri: String,
+#[utoipa::path(
+    get,
+    path = "/api/system/data-dir",
+    tag = "system",
+    responses(
+        (status = 200, description = "获取数据目录", body = StringResponse),
+    )
+)]
+async fn get_data_dir_path(
+    State(_state): State<Arc<WebApiState>>,
+) -> impl IntoResponse {
+    match modules::account::get_data_dir() {
+        Ok(path) => ApiResponse::ok(path.to_string_lossy().to_string()),
+        Err(e) => ApiResponse::<String>::err(e),
+    }
 }
 
-async fn prepare_oauth_url(
+#[derive(Serialize, ToSchema)]
+struct ProfileList {
+    /// 当前生效的 profile，未使用 --profile 时为空
+    active: Option<String>,
+    data_dir: String,
+    /// 数据目录来源: data_dir > profile > env > default
+    source: DataDirSource,
+    profiles: Vec<ProfileInfo>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/system/profiles",
+    tag = "system",
+    responses(
+        (status = 200, description = "~/.antigravity_tools/profiles 下已有的 profile，以及当前数据目录及其来源", body = ProfileListResponse),
+    )
+)]
+async fn list_profiles(
     State(_state): State<Arc<WebApiState>>,
 ) -> impl IntoResponse {
-    // Web 模式下返回 OAuth URL，由用户手动在浏览器中打开
-    // 使用固定的 redirect_uri (用户需要手动复制回调 URL)
-    let redirect_uri = "http://localhost:9004/callback".to_string();
-    let url = modules::oauth::get_auth_url(&redirect_uri);
-    
-    ApiResponse::ok(OAuthUrlResponse {
-        url,
-        redirect_uri,
+    let result = modules::account::current_data_dir().and_then(|(data_dir, source)| {
+        Ok(ProfileList {
+            active: modules::account::active_profile(),
+            data_dir: data_dir.to_string_lossy().to_string(),
+            source,
+            profiles: modules::account::list_profiles()?,
+        })
+    });
+    match result {
+        Ok(list) => ApiResponse::ok(list),
+        Err(e) => ApiResponse::<ProfileList>::err(e),
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+struct UpdateInfo {
+    has_update: bool,
+    latest_version: String,
+    current_version: String,
+    download_url: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/system/check-updates",
+    tag = "system",
+    responses(
+        (status = 200, description = "检查新版本", body = UpdateInfoResponse),
+    )
+)]
+async fn check_for_updates(
+    State(_state): State<Arc<WebApiState>>,
+) -> impl IntoResponse {
+    // 复用桌面端的检查逻辑 (更新通道、缓存与上游代理)
+    match modules::update_checker::check_for_updates().await {
+        Ok(info) => ApiResponse::ok(UpdateInfo {
+            has_update: info.has_update,
+            latest_version: format!("v{}", info.latest_version),
+            current_version: format!("v{}", info.current_version),
+            download_url: info.download_url,
+        }),
+        Err(e) => ApiResponse::<UpdateInfo>::err(e),
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct SelfUpdateRequest {
+    /// 仅返回将要下载的资产，不实际更新
+    #[serde(default)]
+    dry_run: bool,
+    /// 更新完成后以相同参数重启进程
+    #[serde(default)]
+    restart: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+struct SelfUpdateResult {
+    /// 已是最新版本时为 null
+    plan: Option<modules::self_update::UpdatePlan>,
+    applied: bool,
+    restarting: bool,
+    message: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/system/update",
+    tag = "system",
+    request_body = SelfUpdateRequest,
+    responses(
+        (status = 200, description = "下载并安装新版本，进度通过 /api/events 的 UpdateProgress 事件推送", body = SelfUpdateResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn self_update(
+    State(state): State<Arc<WebApiState>>,
+    AppJson(req): AppJson<SelfUpdateRequest>,
+) -> impl IntoResponse {
+    let plan = match modules::self_update::plan_update().await {
+        Ok(Some(plan)) => plan,
+        Ok(None) => {
+            return ApiResponse::ok(SelfUpdateResult {
+                plan: None,
+                applied: false,
+                restarting: false,
+                message: "已是最新版本".to_string(),
+            })
+        }
+        Err(e) => return ApiResponse::<SelfUpdateResult>::err(e),
+    };
+
+    if req.dry_run {
+        let message = format!("将下载 {} ({} 字节)", plan.asset_name, plan.size);
+        return ApiResponse::ok(SelfUpdateResult {
+            plan: Some(plan),
+            applied: false,
+            restarting: false,
+            message,
+        });
+    }
+
+    let sse_tx = state.sse_tx.clone();
+    let result = modules::self_update::apply_update(&plan, |progress| {
+        let _ = sse_tx.send(SseEvent::UpdateProgress(progress));
     })
+    .await;
+
+    match result {
+        Ok(install_path) => {
+            if req.restart {
+                // 延迟片刻，确保响应先发送给客户端
+                tokio::spawn(async move {
+                    tokio::time::sleep(Duration::from_millis(500)).await;
+                    if let Err(e) = modules::self_update::restart(&install_path) {
+                        modules::logger::log_error(&e);
+                    }
+                });
+            }
+            let message = if req.restart {
+                format!("已更新至 v{}，正在重启", plan.latest_version)
+            } else {
+                format!("已更新至 v{}，请重启服务以生效", plan.latest_version)
+            };
+            ApiResponse::ok(SelfUpdateResult {
+                plan: Some(plan),
+                applied: true,
+                restarting: req.restart,
+                message,
+            })
+        }
+        Err(e) => ApiResponse::<SelfUpdateResult>::err(e),
+    }
 }
 
-/// 处理手动粘贴的 OAuth 回调 URL
-#[derive(Deserialize)]
-struct ProcessCallbackRequest {
-    callback_url: String,
+#[utoipa::path(
+    post,
+    path = "/api/system/clear-logs",
+    tag = "system",
+    responses(
+        (status = 200, description = "清理日志文件", body = EmptyResponse),
+    )
+)]
+async fn clear_log_cache(
+    State(_state): State<Arc<WebApiState>>,
+) -> impl IntoResponse {
+    match modules::logger::clear_logs() {
+        Ok(()) => ApiResponse::ok(()),
+        Err(e) => ApiResponse::<()>::err(e),
+    }
 }
 
-async fn process_oauth_callback(
+#[utoipa::path(
+    get,
+    path = "/api/system/base-path",
+    tag = "system",
+    responses(
+        (status = 200, description = "挂载前缀，根路径部署时为空字符串", body = StringResponse),
+    )
+)]
+async fn get_base_path(
     State(state): State<Arc<WebApiState>>,
-    AppJson(req): AppJson<ProcessCallbackRequest>,
 ) -> impl IntoResponse {
-    let result = async {
-        // 1. 解析回调 URL 中的 code 参数
-        let url = url::Url::parse(&req.callback_url)
-            .map_err(|e| format!("无效的回调 URL: {}", e))?;
-        
-        let code = url.query_pairs()
-            .find(|(k, _)| k == "code")
-            .map(|(_, v)| v.to_string())
-            .ok_or_else(|| "回调 URL 中未找到 code 参数".to_string())?;
-        
-        // 获取 redirect_uri (从 URL 中提取 scheme://host:port/path)
-        let redirect_uri = format!(
-            "{}://{}{}",
-            url.scheme(),
-            url.host_str().unwrap_or("localhost"),
-            if let Some(port) = url.port() { format!(":{}", port) } else { String::new() }
-        ) + url.path();
-        
-        // 2. 使用 code 交换 token
-        let token_res = modules::oauth::exchange_code(&code, &redirect_uri).await?;
-        
-        // 3. 检查是否返回了 refresh_token
-        let refresh_token = token_res.refresh_token.ok_or_else(|| {
-            "OAuth 未返回 Refresh Token。可能原因：\n\
-             1. 此 Google 账号之前已授权过此应用\n\
-             2. 请访问 https://myaccount.google.com/permissions 撤销授权后重试"
-                .to_string()
-        })?;
-        
-        // 4. 获取用户信息
-        let user_info = modules::oauth::get_user_info(&token_res.access_token).await?;
-        
-        // 5. 构造 TokenData 并保存账号
-        let token_data = crate::models::TokenData::new(
-            token_res.access_token,
-            refresh_token,
-            token_res.expires_in,
-            Some(user_info.email.clone()),  // email: Option<String>
-            None,  // project_id
-            None,  // session_id
-        );
-        
-        // 6. 创建并保存账号
-        let account_id = uuid::Uuid::new_v4().to_string();
-        let mut account = crate::models::Account::new(
-            account_id,
-            user_info.email.clone(),
-            token_data,
-        );
-        account.name = user_info.get_display_name();
+    ApiResponse::ok(state.base_path.clone())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/system/seed-status",
+    tag = "system",
+    responses(
+        (status = 200, description = "启动时种子账号导入的汇总 (--seed-accounts-file)，未配置时 source 为空", body = SeedStatusResponse),
+    )
+)]
+async fn get_seed_status() -> impl IntoResponse {
+    ApiResponse::ok(modules::seed::status())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/system/oauth-status",
+    tag = "system",
+    responses(
+        (status = 200, description = "Token 刷新熔断状态：连续临时失败达到阈值后暂停刷新，冷却结束后放行一次探测", body = RefreshBreakerStatusResponse),
+    )
+)]
+async fn get_oauth_status() -> impl IntoResponse {
+    ApiResponse::ok(modules::oauth::refresh_breaker_status())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/system/audit",
+    tag = "system",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "读取管理操作审计记录 (最近的记录在最后)", body = AuditLogResponse),
+    )
+)]
+async fn get_audit_log(Query(query): Query<AuditQuery>) -> impl IntoResponse {
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    match modules::audit::read(limit, query.since) {
+        Ok(entries) => ApiResponse::ok(entries),
+        Err(e) => ApiResponse::<Vec<AuditEntry>>::err(e),
+    }
+}
+
+/// 采集系统信息 (文件遍历与进程信息读取在阻塞线程中执行)
+async fn collect_system_info(state: &WebApiState) -> Result<SystemInfo, String> {
+    let monitor_buffer = match state.monitor.read().await.as_ref() {
+        Some(monitor) => Some(MonitorBuffer {
+            len: monitor.logs.read().await.len(),
+            capacity: monitor.capacity(),
+        }),
+        None => None,
+    };
+    let mut info = tokio::task::spawn_blocking(move || modules::system_info::collect(monitor_buffer))
+        .await
+        .map_err(|e| format!("采集系统信息失败: {}", e))?;
+    info.event_subscribers = Some(EventSubscribers {
+        active: state.sse_subscribers.load(Ordering::Relaxed),
+        max: sse_max_subscribers(),
+    });
+    Ok(info)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/system/info",
+    tag = "system",
+    responses(
+        (status = 200, description = "进程运行时长、内存、文件描述符、运行时任务数、数据目录占用与构建信息；不支持的平台对应字段为空", body = SystemInfoResponse),
+    )
+)]
+async fn get_system_info(State(state): State<Arc<WebApiState>>) -> impl IntoResponse {
+    match collect_system_info(&state).await {
+        Ok(info) => ApiResponse::ok(info),
+        Err(e) => ApiResponse::<SystemInfo>::err(e),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/system/diagnostics",
+    tag = "system",
+    responses(
+        (status = 200, description = "下载诊断包 (zip)：脱敏配置、最近的应用日志与请求日志、请求统计、账号状态与系统信息，用于提交问题反馈", content_type = "application/zip"),
+    )
+)]
+async fn get_diagnostics(State(state): State<Arc<WebApiState>>) -> Response {
+    let system = match collect_system_info(&state).await {
+        Ok(info) => info,
+        Err(e) => return ApiResponse::<()>::err(e).into_response(),
+    };
+    let (proxy_logs, proxy_stats) = match state.monitor.read().await.as_ref() {
+        Some(monitor) => (
+            monitor.get_logs(modules::diagnostics::PROXY_LOG_ENTRIES).await,
+            monitor.get_stats().await,
+        ),
+        None => (Vec::new(), ProxyStats::default()),
+    };
+
+    let result = tokio::task::spawn_blocking(move || {
+        modules::diagnostics::build_bundle(modules::diagnostics::DiagnosticsInput {
+            config: modules::config::load_app_config()?,
+            app_log: modules::logger::tail_lines(modules::diagnostics::APP_LOG_LINES)
+                .unwrap_or_default(),
+            proxy_logs,
+            proxy_stats,
+            accounts: modules::account::list_accounts()?,
+            system,
+            oauth: modules::oauth::refresh_breaker_status(),
+        })
+    })
+    .await
+    .map_err(|e| format!("生成诊断包失败: {}", e))
+    .and_then(|result| result);
 
-        
-        modules::account::save_account(&account)?;
-        let _ = modules::account::set_current_account_id(&account.id);
-        
-        // 7. 重新加载反代账号
-        reload_proxy_accounts_internal(&state).await;
-        
-        Ok::<_, String>(account)
-    }.await;
-    
     match result {
-        Ok(account) => ApiResponse::ok(account),
-        Err(e) => ApiResponse::<Account>::err(e),
+        Ok(bundle) => {
+            let filename = modules::diagnostics::bundle_filename(chrono::Utc::now());
+            (
+                [
+                    (header::CONTENT_TYPE, "application/zip".to_string()),
+                    (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+                ],
+                bundle,
+            )
+                .into_response()
+        }
+        Err(e) => ApiResponse::<()>::err(e).into_response(),
     }
 }
 
-// ============================================================================
-// 导入 API
-// ============================================================================
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct BackupQuery {
+    /// 是否包含日志 (应用日志、请求日志数据库与审计日志)，默认 true
+    include_logs: Option<bool>,
+}
 
+#[utoipa::path(
+    get,
+    path = "/api/system/backup",
+    tag = "system",
+    params(BackupQuery),
+    responses(
+        (status = 200, description = "下载数据目录的完整备份 (zip)：账号、配置、账号排序等，包含令牌，请妥善保管", content_type = "application/zip"),
+    )
+)]
+async fn get_system_backup(Query(query): Query<BackupQuery>) -> Response {
+    let include_logs = query.include_logs.unwrap_or(true);
+    let archive = match tokio::task::spawn_blocking(move || modules::backup::build_backup(include_logs))
+        .await
+        .map_err(|e| format!("生成备份失败: {}", e))
+        .and_then(|result| result)
+    {
+        Ok(archive) => archive,
+        Err(e) => return ApiResponse::<()>::err(e).into_response(),
+    };
+    let file = match tokio::fs::File::open(&archive.path).await {
+        Ok(file) => file,
+        Err(e) => return ApiResponse::<()>::err(format!("读取备份文件失败: {}", e)).into_response(),
+    };
 
-async fn import_v1_accounts(
-    State(_state): State<Arc<WebApiState>>,
-) -> impl IntoResponse {
-    match modules::migration::import_from_v1().await {
-        Ok(accounts) => ApiResponse::ok(accounts),
-        Err(e) => ApiResponse::<Vec<Account>>::err(e),
+    // 边读边发，传输结束后随 archive 一起删除临时文件 (先关闭文件)
+    let size = archive.size;
+    let stream = futures::stream::unfold((file, archive), |(mut file, archive)| async move {
+        use tokio::io::AsyncReadExt;
+        let mut buf = vec![0u8; 64 * 1024];
+        match file.read(&mut buf).await {
+            Ok(0) => None,
+            Ok(n) => {
+                buf.truncate(n);
+                Some((Ok(axum::body::Bytes::from(buf)), (file, archive)))
+            }
+            Err(e) => Some((Err(e), (file, archive))),
+        }
+    });
+    let filename = modules::backup::backup_filename(chrono::Utc::now());
+    (
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (header::CONTENT_LENGTH, size.to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        axum::body::Body::from_stream(stream),
+    )
+        .into_response()
+}
+
+/// 恢复上传的备份大小上限 (包含日志的备份可能较大)
+const MAX_RESTORE_UPLOAD_BYTES: usize = 512 * 1024 * 1024;
+
+#[utoipa::path(
+    post,
+    path = "/api/system/restore",
+    tag = "system",
+    request_body(content = Vec<u8>, description = "GET /api/system/backup 生成的 zip", content_type = "application/zip"),
+    responses(
+        (status = 200, description = "恢复备份：校验后停止反代、替换数据、重新加载账号与配置，反代此前在运行时自动重启；原有数据保留在数据目录的 pre-restore-* 中", body = RestoreSummaryResponse),
+        (status = 400, description = "不是有效的备份 (结构错误、文件无法解析或包含路径穿越条目)", body = EmptyResponse),
+    )
+)]
+async fn restore_system_backup(State(state): State<Arc<WebApiState>>, body: axum::body::Bytes) -> Response {
+    // 先校验，无效的归档不会打断正在运行的反代
+    let bytes = body.clone();
+    let validation = tokio::task::spawn_blocking(move || modules::backup::validate_backup(&bytes))
+        .await
+        .map_err(|e| format!("校验备份失败: {}", e))
+        .and_then(|result| result);
+    if let Err(e) = validation {
+        return (StatusCode::BAD_REQUEST, ApiResponse::<()>::fail(ErrorCode::InvalidRequest, e)).into_response();
+    }
+
+    // 先停止会读写数据目录的后台任务与反代，替换数据后再恢复
+    let quiesced = state.tasks.quiesce().await;
+    let was_running = stop_proxy(&state).await;
+    let result = tokio::task::spawn_blocking(move || modules::backup::restore_backup(&body))
+        .await
+        .map_err(|e| format!("恢复备份失败: {}", e))
+        .and_then(|result| result);
+
+    // 无论成功与否都按磁盘上的数据重新加载，失败时已回滚到原数据
+    modules::account::account_store().invalidate();
+    let config = modules::config::load_app_config();
+    if let Ok(config) = &config {
+        apply_saved_config(&state, config).await;
+    }
+    let restarted = match (&config, was_running) {
+        (Ok(config), true) => match start_proxy(&state, config.proxy.clone()).await {
+            Ok(_) => true,
+            Err(e) => {
+                modules::logger::log_error(&format!("恢复备份后重启反代服务失败: {}", e));
+                false
+            }
+        },
+        _ => false,
+    };
+    state.tasks.resume(&quiesced);
+
+    match result {
+        Ok(mut summary) => {
+            summary.proxy_restarted = restarted;
+            modules::logger::log_info(&format!(
+                "已恢复备份: {} 个账号, {} 个文件",
+                summary.accounts, summary.files
+            ));
+            ApiResponse::ok(summary).into_response()
+        }
+        Err(e) => ApiResponse::<RestoreSummary>::err(e).into_response(),
     }
 }
 
-async fn import_from_db(
+#[utoipa::path(
+    get,
+    path = "/api/system/tasks",
+    tag = "system",
+    responses(
+        (status = 200, description = "常驻后台任务的运行状态、最近一次运行时间与最近的错误", body = TaskStatusListResponse),
+    )
+)]
+async fn list_background_tasks(State(state): State<Arc<WebApiState>>) -> impl IntoResponse {
+    ApiResponse::ok(state.tasks.list())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/system/tasks/{name}/restart",
+    tag = "system",
+    params(("name" = String, Path, description = "任务名")),
+    responses(
+        (status = 200, description = "停止并重新启动后台任务", body = EmptyResponse),
+        (status = 404, description = "任务不存在", body = EmptyResponse),
+    )
+)]
+async fn restart_background_task(
     State(state): State<Arc<WebApiState>>,
-) -> impl IntoResponse {
-    match modules::migration::import_from_db().await {
-        Ok(mut account) => {
-            // 设为当前账号
-            let _ = modules::account::set_current_account_id(&account.id);
-            reload_proxy_accounts_internal(&state).await;
-            ApiResponse::ok(account)
-        }
-        Err(e) => ApiResponse::<Account>::err(e),
+    Path(name): Path<String>,
+) -> Response {
+    if !state.tasks.restart(&name).await {
+        return (
+            StatusCode::NOT_FOUND,
+            ApiResponse::<()>::fail(ErrorCode::NotFound, format!("后台任务不存在: {}", name)),
+        )
+            .into_response();
     }
+    ApiResponse::ok(()).into_response()
 }
 
-#[derive(Deserialize)]
-struct ImportCustomDbRequest {
-    path: String,
+/// 引导状态，附带反代运行状态
+async fn setup_status(state: &WebApiState) -> Result<SetupStatus, String> {
+    let mut status = modules::setup::status()?;
+    status.proxy_running = state.proxy_instance.read().await.is_some();
+    Ok(status)
 }
 
-async fn import_custom_db(
+#[utoipa::path(
+    get,
+    path = "/api/setup/status",
+    tag = "setup",
+    responses(
+        (status = 200, description = "首次运行引导各步骤是否完成 (数据目录、账号、API 密钥、反代配置)，根据当前数据实时判断", body = SetupStatusResponse),
+    )
+)]
+async fn get_setup_status(State(state): State<Arc<WebApiState>>) -> impl IntoResponse {
+    match setup_status(&state).await {
+        Ok(status) => ApiResponse::ok(status),
+        Err(e) => ApiResponse::<SetupStatus>::err(e),
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+struct CompleteStepRequest {
+    step: SetupStep,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/setup/complete-step",
+    tag = "setup",
+    request_body = CompleteStepRequest,
+    responses(
+        (status = 200, description = "完成指定步骤 (已完成时不做修改)：初始化数据目录、生成 API 密钥或写入默认反代配置；账号步骤只检查是否已添加账号", body = SetupStatusResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn complete_setup_step(
     State(state): State<Arc<WebApiState>>,
-    AppJson(req): AppJson<ImportCustomDbRequest>,
+    AppJson(req): AppJson<CompleteStepRequest>,
 ) -> impl IntoResponse {
-    match modules::migration::import_from_custom_db_path(req.path).await {
-        Ok(mut account) => {
-            let _ = modules::account::set_current_account_id(&account.id);
-            reload_proxy_accounts_internal(&state).await;
-            ApiResponse::ok(account)
+    match modules::setup::complete_step(req.step) {
+        Ok(true) => {
+            if let Ok(config) = modules::config::load_app_config() {
+                apply_saved_config(&state, &config).await;
+            }
         }
-        Err(e) => ApiResponse::<Account>::err(e),
+        Ok(false) => {}
+        Err(e) => return ApiResponse::<SetupStatus>::err(e),
+    }
+    match setup_status(&state).await {
+        Ok(status) => ApiResponse::ok(status),
+        Err(e) => ApiResponse::<SetupStatus>::err(e),
     }
 }
 
-async fn sync_account_from_db(
+#[derive(Deserialize, Default, ToSchema)]
+struct QuickstartRequest {
+    /// 已有账号时是否启动反代，默认 true
+    #[serde(default)]
+    start_proxy: Option<bool>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct QuickstartReport {
+    status: SetupStatus,
+    /// 反代 API 密钥
+    api_key: String,
+    /// 本次是否启动了反代
+    proxy_started: bool,
+    /// 启动反代失败的原因
+    proxy_error: Option<String>,
+    /// 反代运行时客户端使用的地址
+    base_url: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/setup/quickstart",
+    tag = "setup",
+    request_body(content = Option<QuickstartRequest>, description = "可选"),
+    responses(
+        (status = 200, description = "一键完成可自动完成的步骤：初始化数据目录、生成 API 密钥、写入默认反代配置 (允许局域网访问、除健康检查外要求鉴权、自动启动)；已完成的步骤保持不变。已有账号时启动反代", body = QuickstartReportResponse),
+        (status = 400, description = "请求体解析失败", body = EmptyResponse),
+    )
+)]
+async fn setup_quickstart(
     State(state): State<Arc<WebApiState>>,
-) -> impl IntoResponse {
-    let result = async {
-        let db_refresh_token = modules::migration::get_refresh_token_from_db()?;
-        let curr_account = modules::account::get_current_account()?;
+    RequestOrigin(origin): RequestOrigin,
+    body: axum::body::Bytes,
+) -> Response {
+    let req: QuickstartRequest = match parse_optional_body(&body) {
+        Ok(req) => req,
+        Err(e) => return invalid_json(e),
+    };
 
-        if let Some(acc) = curr_account {
-            if acc.token.refresh_token == db_refresh_token {
-                return Ok(None);
-            }
+    let mut changed = false;
+    for step in [SetupStep::DataDir, SetupStep::ApiKey, SetupStep::ProxyConfig] {
+        match modules::setup::complete_step(step) {
+            Ok(modified) => changed |= modified,
+            Err(e) => return ApiResponse::<QuickstartReport>::err(e).into_response(),
         }
-
-        let account = modules::migration::import_from_db().await?;
-        let _ = modules::account::set_current_account_id(&account.id);
-        Ok::<_, String>(Some(account))
     }
-    .await;
+    let config = match modules::config::load_app_config() {
+        Ok(config) => config,
+        Err(e) => return ApiResponse::<QuickstartReport>::err(e).into_response(),
+    };
+    if changed {
+        apply_saved_config(&state, &config).await;
+    }
 
-    match result {
-        Ok(account) => {
-            if account.is_some() {
-                reload_proxy_accounts_internal(&state).await;
-            }
-            ApiResponse::ok(account)
+    let mut proxy_started = false;
+    let mut proxy_error = None;
+    let has_accounts = modules::account::list_accounts().is_ok_and(|accounts| !accounts.is_empty());
+    let running = state.proxy_instance.read().await.is_some();
+    if req.start_proxy.unwrap_or(true) && has_accounts && !running {
+        match start_proxy(&state, config.proxy.clone()).await {
+            Ok(_) => proxy_started = true,
+            Err(e) => proxy_error = Some(e),
         }
-        Err(e) => ApiResponse::<Option<Account>>::err(e),
     }
+
+    let status = match setup_status(&state).await {
+        Ok(status) => status,
+        Err(e) => return ApiResponse::<QuickstartReport>::err(e).into_response(),
+    };
+    let base_url = status.proxy_running.then(|| proxy_base_url(&config.proxy, origin.as_ref()));
+    ApiResponse::ok(QuickstartReport {
+        status,
+        api_key: config.proxy.api_key,
+        proxy_started,
+        proxy_error,
+        base_url,
+    })
+    .into_response()
 }
 
-// ============================================================================
-// 系统 API
-// ============================================================================
+/// 看板中的反代状态
+#[derive(Serialize, ToSchema)]
+struct DashboardProxy {
+    running: bool,
+    port: u16,
+    /// 调度池的账号总数、可用数与不可用原因 (服务未运行时为 0)
+    #[serde(flatten)]
+    accounts: AccountAvailability,
+    /// 运行时长 (秒)，未运行时为空
+    uptime_secs: Option<i64>,
+}
 
-async fn get_data_dir_path(
-    State(_state): State<Arc<WebApiState>>,
-) -> impl IntoResponse {
-    match modules::account::get_data_dir() {
-        Ok(path) => ApiResponse::ok(path.to_string_lossy().to_string()),
-        Err(e) => ApiResponse::<String>::err(e),
-    }
+#[derive(Serialize, ToSchema)]
+struct RequestCounts {
+    requests: u64,
+    errors: u64,
 }
 
-#[derive(Serialize)]
-struct UpdateInfo {
-    has_update: bool,
-    latest_version: String,
-    current_version: String,
-    download_url: String,
+/// 首页看板汇总
+#[derive(Serialize, ToSchema)]
+struct Dashboard {
+    /// 生成时间 (Unix 毫秒)
+    generated_at: i64,
+    accounts: AccountCounts,
+    quota: QuotaTotals,
+    proxy: DashboardProxy,
+    /// 最近一小时的反代请求
+    last_hour: RequestCounts,
+    /// 最近的警告/错误日志，最新的在前
+    warnings: Vec<WarningEvent>,
 }
 
-async fn check_for_updates(
-    State(_state): State<Arc<WebApiState>>,
-) -> impl IntoResponse {
-    const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
-    const GITHUB_API_URL: &str =
-        "https://api.github.com/repos/lbjlaq/Antigravity-Manager/releases/latest";
+/// 看板返回的警告条数
+const DASHBOARD_WARNINGS: usize = 5;
 
-    let result = async {
-        let client = crate::utils::http::create_client(15);
-        let response = client
-            .get(GITHUB_API_URL)
-            .header("User-Agent", "Antigravity-Tools")
-            .send()
-            .await
-            .map_err(|e| format!("请求失败: {}", e))?;
+#[utoipa::path(
+    get,
+    path = "/api/dashboard",
+    tag = "system",
+    responses(
+        (status = 200, description = "账号、配额、反代状态与最近警告的汇总", body = DashboardResponse),
+    )
+)]
+async fn get_dashboard(State(state): State<Arc<WebApiState>>) -> impl IntoResponse {
+    let (accounts, quota) = match tokio::task::spawn_blocking(modules::dashboard::account_summary).await {
+        Ok(Ok(summary)) => summary,
+        Ok(Err(e)) => return ApiResponse::<Dashboard>::err(e),
+        Err(e) => return ApiResponse::<Dashboard>::err(format!("汇总账号失败: {}", e)),
+    };
 
-        if !response.status().is_success() {
-            return Err(format!("GitHub API 返回错误: {}", response.status()));
-        }
+    let proxy = match state.proxy_instance.read().await.as_ref() {
+        Some(instance) => DashboardProxy {
+            running: true,
+            port: instance.config.port,
+            accounts: instance.token_manager.availability(),
+            uptime_secs: Some(chrono::Utc::now().timestamp() - instance.started_at),
+        },
+        None => DashboardProxy {
+            running: false,
+            port: 0,
+            accounts: AccountAvailability::default(),
+            uptime_secs: None,
+        },
+    };
 
-        let json: serde_json::Value = response
-            .json()
-            .await
-            .map_err(|e| format!("解析响应失败: {}", e))?;
+    let (requests, errors) = match state.monitor.read().await.as_ref() {
+        Some(monitor) => monitor.recent_counts(3600).await,
+        None => (0, 0),
+    };
 
-        let latest_version = json["tag_name"]
-            .as_str()
-            .ok_or("无法获取版本号")?
-            .trim_start_matches('v');
+    ApiResponse::ok(Dashboard {
+        generated_at: chrono::Utc::now().timestamp_millis(),
+        accounts,
+        quota,
+        proxy,
+        last_hour: RequestCounts { requests, errors },
+        warnings: modules::logger::recent_warnings(DASHBOARD_WARNINGS),
+    })
+}
+
+// ============================================================================
+// SSE 事件流
+// ============================================================================
 
-        let download_url = json["html_url"]
-            .as_str()
-            .unwrap_or("https://github.com/lbjlaq/Antigravity-Manager/releases")
-            .to_string();
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct EventsQuery {
+    /// 只接收这些类型的事件 (逗号分隔，如 `ProxyRequest,AccountSwitched`)；不含 `StatsTick` 即不接收定时统计
+    types: Option<String>,
+}
 
-        let has_update = compare_versions(latest_version, CURRENT_VERSION);
+/// 计算一次 `StatsTick`
+async fn compute_stats_tick(state: &WebApiState) -> StatsTick {
+    let active_accounts = state
+        .proxy_instance
+        .read()
+        .await
+        .as_ref()
+        .map(|instance| instance.token_manager.availability().usable_accounts)
+        .unwrap_or(0);
+    let (requests_last_minute, errors_last_minute, in_flight) = match state.monitor.read().await.as_ref() {
+        Some(monitor) => {
+            let (requests, errors) = monitor.recent_counts(60).await;
+            (requests, errors, monitor.in_flight())
+        }
+        None => (0, 0, 0),
+    };
+    StatsTick { requests_last_minute, errors_last_minute, active_accounts, in_flight }
+}
 
-        Ok(UpdateInfo {
-            has_update,
-            latest_version: format!("v{}", latest_version),
-            current_version: format!("v{}", CURRENT_VERSION),
-            download_url,
-        })
-    }
-    .await;
+/// 按 `sse_stats_interval_secs` 定时广播 `StatsTick`；每个周期只计算一次，无订阅者时跳过
+pub fn spawn_stats_tick(state: &Arc<WebApiState>) {
+    let weak = Arc::downgrade(state);
+    state.tasks.register("sse-stats-tick", move |ctx| {
+        let weak = weak.clone();
+        async move {
+            loop {
+                let interval = modules::config::load_app_config()
+                    .map(|c| c.sse_stats_interval_secs)
+                    .unwrap_or(0);
+                // 未开启时每分钟检查一次配置
+                if !ctx.sleep(Duration::from_secs(if interval == 0 { 60 } else { interval })).await {
+                    break;
+                }
+                let Some(state) = weak.upgrade() else {
+                    break;
+                };
+                if interval == 0 || state.sse_tx.receiver_count() == 0 {
+                    continue;
+                }
+                let tick = compute_stats_tick(&state).await;
+                let _ = state.sse_tx.send(SseEvent::StatsTick(tick));
+                ctx.record_run(Ok(()));
+            }
+        }
+    });
+}
 
-    match result {
-        Ok(info) => ApiResponse::ok(info),
-        Err(e) => ApiResponse::<UpdateInfo>::err(e),
-    }
+/// 定时轮换当前账号
+pub fn spawn_rotation(state: &WebApiState) {
+    state.tasks.register("account-rotation", modules::rotation::check_loop);
 }
 
-fn compare_versions(latest: &str, current: &str) -> bool {
-    let parse_version =
-        |v: &str| -> Vec<u32> { v.split('.').filter_map(|s| s.parse::<u32>().ok()).collect() };
+/// 订阅者在该时间窗口内落后达到次数上限时断开 (秒)
+const SSE_LAG_WINDOW_SECS: u64 = 60;
+/// 时间窗口内允许的落后次数
+const SSE_MAX_LAGS: u32 = 3;
 
-    let latest_parts = parse_version(latest);
-    let current_parts = parse_version(current);
+fn sse_max_subscribers() -> usize {
+    modules::config::load_app_config()
+        .map(|c| c.sse_max_subscribers)
+        .unwrap_or(0)
+}
+
+/// 事件流订阅计数，连接结束时减一
+struct SseSubscription(Arc<AtomicUsize>);
 
-    for i in 0..3 {
-        let l = latest_parts.get(i).unwrap_or(&0);
-        let c = current_parts.get(i).unwrap_or(&0);
-        if l > c {
-            return true;
-        } else if l < c {
-            return false;
+impl SseSubscription {
+    /// 占用一个订阅名额，已达上限 (max > 0) 时返回 None
+    fn acquire(counter: &Arc<AtomicUsize>, max: usize) -> Option<Self> {
+        let previous = counter.fetch_add(1, Ordering::Relaxed);
+        if max > 0 && previous >= max {
+            counter.fetch_sub(1, Ordering::Relaxed);
+            return None;
         }
+        Some(Self(counter.clone()))
     }
-
-    false
 }
 
-async fn clear_log_cache(
-    State(_state): State<Arc<WebApiState>>,
-) -> impl IntoResponse {
-    match modules::logger::clear_logs() {
-        Ok(()) => ApiResponse::ok(()),
-        Err(e) => ApiResponse::<()>::err(e),
+impl Drop for SseSubscription {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
     }
 }
 
-// ============================================================================
-// SSE 事件流
-// ============================================================================
-
+#[utoipa::path(
+    get,
+    path = "/api/events",
+    tag = "system",
+    params(EventsQuery),
+    responses(
+        (status = 200, description = "SSE 事件流，每条 data 为 {type, data} 格式的 JSON；另按配置间隔推送 StatsTick 统计。订阅者持续落后 (60 秒内 3 次丢弃事件) 时服务端断开连接", content_type = "text/event-stream", body = String),
+        (status = 429, description = "同时连接数已达 `sse_max_subscribers` 上限", body = EmptyResponse),
+    )
+)]
 async fn sse_handler(
     State(state): State<Arc<WebApiState>>,
-) -> Sse<impl Stream<Item = Result<axum::response::sse::Event, Infallible>>> {
+    Query(query): Query<EventsQuery>,
+    client_ip: Option<axum::Extension<ClientIp>>,
+) -> Response {
+    let max = sse_max_subscribers();
+    let Some(subscription) = SseSubscription::acquire(&state.sse_subscribers, max) else {
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            ApiResponse::<()>::fail(ErrorCode::RateLimited, format!(
+                "事件流连接数已达上限 ({})，请关闭多余的 /api/events 连接或调大 sse_max_subscribers",
+                max
+            )),
+        )
+            .into_response();
+    };
+    let client = client_ip
+        .map(|axum::Extension(ClientIp(ip))| ip.to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
     let rx = state.sse_tx.subscribe();
+    let types: Option<HashSet<String>> = query.types.map(|types| {
+        types
+            .split(',')
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect()
+    });
 
     let stream = async_stream::stream! {
+        let _subscription = subscription;
         let mut rx = rx;
+        let mut lag_window = std::time::Instant::now();
+        let mut lags = 0u32;
+        let mut skipped = 0u64;
         loop {
             match rx.recv().await {
                 Ok(event) => {
-                    let data = serde_json::to_string(&event).unwrap_or_default();
-                    yield Ok(axum::response::sse::Event::default().data(data));
+                    let value = serde_json::to_value(&event).unwrap_or_default();
+                    if let Some(types) = &types {
+                        let kind = value.get("type").and_then(|t| t.as_str()).unwrap_or("");
+                        if !types.contains(kind) {
+                            continue;
+                        }
+                    }
+                    yield Ok::<_, Infallible>(axum::response::sse::Event::default().data(value.to_string()));
                 }
                 Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
-                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                    if lag_window.elapsed() > Duration::from_secs(SSE_LAG_WINDOW_SECS) {
+                        lag_window = std::time::Instant::now();
+                        lags = 0;
+                        skipped = 0;
+                    }
+                    lags += 1;
+                    skipped += n;
+                    tracing::debug!("SSE 订阅者 {} 落后，丢弃 {} 条事件", client, n);
+                    if lags >= SSE_MAX_LAGS {
+                        tracing::warn!(
+                            "SSE 订阅者 {} 持续落后 ({} 秒内 {} 次，共丢弃 {} 条事件)，已断开连接",
+                            client, SSE_LAG_WINDOW_SECS, lags, skipped
+                        );
+                        break;
+                    }
+                }
             }
         }
     };
 
-    Sse::new(stream).keep_alive(
-        axum::response::sse::KeepAlive::new()
-            .interval(Duration::from_secs(30))
-            .text("ping"),
-    )
+    Sse::new(stream)
+        .keep_alive(
+            axum::response::sse::KeepAlive::new()
+                .interval(Duration::from_secs(30))
+                .text("ping"),
+        )
+        .into_response()
 }
 
 // ============================================================================
 // 健康检查
 // ============================================================================
 
-async fn health_check() -> impl IntoResponse {
-    Json(serde_json::json!({
-        "status": "ok",
-        "version": env!("CARGO_PKG_VERSION"),
-        "mode": "web"
-    }))
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HealthQuery {
+    /// 为 false 时只返回 status / version / mode (兼容只需要存活探测的负载均衡)
+    verbose: Option<bool>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/health",
+    tag = "system",
+    params(HealthQuery),
+    responses(
+        (status = 200, description = "服务状态：数据目录可写性、账号存储、反代服务状态 (running / stopped / crashed) 与最近的后台任务错误，任一异常时 status 为 degraded；文件系统探测结果缓存 10 秒。verbose=false 时只返回 status、version、mode", body = HealthReport),
+    )
+)]
+pub async fn health_check(
+    State(state): State<Arc<WebApiState>>,
+    RequestOrigin(origin): RequestOrigin,
+    Query(query): Query<HealthQuery>,
+) -> Response {
+    if query.verbose == Some(false) {
+        return Json(serde_json::json!({
+            "status": "ok",
+            "version": env!("CARGO_PKG_VERSION"),
+            "mode": "web"
+        }))
+        .into_response();
+    }
+
+    let proxy = match state.proxy_instance.read().await.as_ref() {
+        None => ProxyHealth::Stopped,
+        Some(instance) if instance.server_handle.is_finished() => ProxyHealth::Crashed,
+        Some(_) => ProxyHealth::Running,
+    };
+    let mut report = modules::health::report(proxy, "web").await;
+    report.public_url = origin.map(|origin| origin.url());
+    Json(report).into_response()
+}
+
+// ============================================================================
+// OpenAPI
+// ============================================================================
+
+#[utoipa::path(
+    get,
+    path = "/api/openapi.json",
+    tag = "system",
+    responses(
+        (status = 200, description = "本 API 的 OpenAPI 3 文档", body = Object),
+    )
+)]
+async fn openapi_json() -> impl IntoResponse {
+    Json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn audit_skips_bodies_over_limit() {
+        let json_headers = |length: Option<usize>| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
+            if let Some(length) = length {
+                headers.insert(header::CONTENT_LENGTH, length.into());
+            }
+            headers
+        };
+
+        let (_, summary) = summarize_request_body(&json_headers(None), axum::body::Body::from(r#"{"api_key":"sk-1"}"#))
+            .await
+            .unwrap();
+        assert_eq!(summary.unwrap()["api_key"], "***");
+
+        // 声明的大小超限：不读取请求体，原样传给处理函数
+        let big = AUDIT_BODY_LIMIT + 1;
+        let (body, summary) = summarize_request_body(&json_headers(Some(big)), axum::body::Body::from("{}"))
+            .await
+            .unwrap();
+        assert_eq!(summary.unwrap(), serde_json::json!({"content_type": "application/json", "bytes": big}));
+        assert_eq!(axum::body::to_bytes(body, usize::MAX).await.unwrap(), "{}");
+
+        // 大小未知 (分块传输) 同样不读取
+        let stream = futures::stream::iter([Ok::<_, std::io::Error>(bytes::Bytes::from("{}"))]);
+        let (_, summary) = summarize_request_body(&json_headers(None), axum::body::Body::from_stream(stream))
+            .await
+            .unwrap();
+        assert_eq!(summary.unwrap()["bytes"], serde_json::Value::Null);
+
+        let failed = ApiResponse::<()>::fail(ErrorCode::NotFound, "x").into_response();
+        let (_, success, error) = response_outcome(failed).await;
+        assert!(!success);
+        assert!(error.is_some());
+
+        let mut huge = Json(serde_json::json!({"success": false, "data": "x".repeat(AUDIT_BODY_LIMIT)})).into_response();
+        huge.headers_mut().insert(header::CONTENT_LENGTH, (AUDIT_BODY_LIMIT + 32).into());
+        let (response, success, error) = response_outcome(huge).await;
+        assert!(success, "超限响应按状态码判断");
+        assert!(error.is_none());
+        assert!(axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap().len() > AUDIT_BODY_LIMIT);
+    }
+
+    #[test]
+    fn sse_subscription_enforces_limit() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let first = SseSubscription::acquire(&counter, 2).unwrap();
+        let _second = SseSubscription::acquire(&counter, 2).unwrap();
+        assert!(SseSubscription::acquire(&counter, 2).is_none());
+        assert_eq!(counter.load(Ordering::Relaxed), 2);
+
+        drop(first);
+        assert!(SseSubscription::acquire(&counter, 2).is_some());
+        // 0 表示不限制
+        let unlimited: Vec<_> = (0..5).filter_map(|_| SseSubscription::acquire(&counter, 0)).collect();
+        assert_eq!(unlimited.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn missing_account_reports_account_not_found() {
+        use modules::i18n::Lang;
+
+        let en = modules::i18n::scope(Lang::En, async {
+            let response = account_failure::<()>("no-such-account", "读取账号失败: No such file or directory");
+            serde_json::to_value(&response.0).unwrap()
+        })
+        .await;
+        assert_eq!(en["code"], "account_not_found");
+        assert_eq!(en["error"], "Account not found");
+    }
+
+    #[tokio::test]
+    async fn error_responses_are_localized_per_request() {
+        use modules::i18n::{api_error_text, Lang};
+
+        // 每个错误码都有中英文文本
+        for code in ErrorCode::ALL {
+            let (zh, en) = (api_error_text(code.as_str(), Lang::Zh), api_error_text(code.as_str(), Lang::En));
+            assert_ne!(zh, en, "{}", code.as_str());
+        }
+
+        let en = modules::i18n::scope(Lang::En, async {
+            serde_json::to_value(&ApiResponse::<()>::fail(ErrorCode::AccountNotFound, "账号不存在: a").0).unwrap()
+        })
+        .await;
+        assert_eq!(en["code"], "account_not_found");
+        assert_eq!(en["error"], "Account not found");
+        assert_eq!(en["detail"], "账号不存在: a");
+        let zh = modules::i18n::scope(Lang::Zh, async {
+            serde_json::to_value(&ApiResponse::<()>::fail(ErrorCode::AccountNotFound, "账号不存在: a").0).unwrap()
+        })
+        .await;
+        assert_eq!(zh["error"], "账号不存在: a");
+
+
+        let request = |uri: &str, accept: &str| {
+            axum::http::Request::builder()
+                .uri(uri)
+                .header(header::ACCEPT_LANGUAGE, accept)
+                .body(axum::body::Body::empty())
+                .unwrap()
+        };
+        assert_eq!(requested_lang(&request("/api/accounts?lang=en", "zh-CN")), Some(Lang::En));
+        assert_eq!(requested_lang(&request("/api/accounts?lang=fr", "zh-CN,en;q=0.8")), Some(Lang::Zh));
+        assert_eq!(requested_lang(&request("/api/accounts", "ja")), None);
+    }
+
+    #[tokio::test]
+    async fn rate_limit_uses_in_memory_config() {
+        use tower::ServiceExt;
+
+        let state = Arc::new(WebApiState::new());
+        {
+            let mut auth = state.web_auth.write().unwrap();
+            auth.rate_limits.enabled = true;
+            auth.rate_limits.quota_refresh_per_minute = 1;
+        }
+        let app = Router::new()
+            .route("/api/accounts/:id/quota", post(|| async { "ok" }))
+            .route_layer(axum::middleware::from_fn_with_state(state.clone(), rate_limit_middleware));
+        let request = || {
+            let mut request = axum::http::Request::builder()
+                .method(Method::POST)
+                .uri("/api/accounts/a/quota")
+                .body(axum::body::Body::empty())
+                .unwrap();
+            request.extensions_mut().insert(WebActor("rate-limit-test".to_string(), WebApiRole::Admin));
+            request
+        };
+
+        let res = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let res = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // 保存配置后立即生效，无需重新读取磁盘
+        state.web_auth.write().unwrap().rate_limits.enabled = false;
+        let res = app.oneshot(request()).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn account_switched_event_carries_account_details() {
+        let state = WebApiState::new();
+        let mut rx = state.sse_tx.subscribe();
+
+        let token = crate::models::TokenData::new(String::new(), String::new(), 0, None, None, None);
+        let mut account = Account::new("acc-2".to_string(), "next@example.com".to_string(), token);
+        account.name = Some("Next".to_string());
+        state
+            .sse_tx
+            .send(SseEvent::account_switched(&account, Some("acc-1".to_string()), SwitchReason::ScheduledRotation))
+            .unwrap();
+
+        let value = serde_json::to_value(rx.recv().await.unwrap()).unwrap();
+        assert_eq!(value["type"], "AccountSwitched");
+        assert_eq!(value["data"]["account_id"], "acc-2");
+        assert_eq!(value["data"]["email"], "next@example.com");
+        assert_eq!(value["data"]["name"], "Next");
+        assert_eq!(value["data"]["previous_account_id"], "acc-1");
+        assert_eq!(value["data"]["reason"], "scheduled_rotation");
+    }
+
+    #[test]
+    fn readonly_role_is_denied_every_mutating_route() {
+        // 以 OpenAPI 文档作为路由表，新增的写接口无需额外登记即默认拒绝只读令牌
+        let doc = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let mut mutating = 0;
+        for (path, item) in doc["paths"].as_object().unwrap() {
+            for operation in item.as_object().unwrap().keys() {
+                let method = match operation.as_str() {
+                    "get" => Method::GET,
+                    "post" => Method::POST,
+                    "put" => Method::PUT,
+                    "patch" => Method::PATCH,
+                    "delete" => Method::DELETE,
+                    _ => continue,
+                };
+                let required = required_role(&method, path);
+                assert!(WebApiRole::Admin.allows(required), "{} {}", method, path);
+                if method != Method::GET {
+                    mutating += 1;
+                    assert!(
+                        !WebApiRole::Readonly.allows(required),
+                        "readonly token must not reach {} {}",
+                        method,
+                        path
+                    );
+                }
+            }
+        }
+        assert!(mutating > 0);
+    }
+
+    #[test]
+    fn readonly_role_can_read_dashboard_routes() {
+        for path in ["/api/proxy/stats", "/api/proxy/logs", "/api/events", "/api/accounts"] {
+            assert!(WebApiRole::Readonly.allows(required_role(&Method::GET, path)), "{}", path);
+        }
+        for path in ADMIN_ONLY_READS {
+            assert!(!WebApiRole::Readonly.allows(required_role(&Method::GET, path)), "{}", path);
+        }
+    }
+
+    #[test]
+    fn replay_requires_admin() {
+        let required = required_role(&Method::POST, "/api/proxy/logs/log-1/replay");
+        assert_eq!(required, WebApiRole::Admin);
+        assert!(!WebApiRole::Readonly.allows(required));
+    }
+
+    #[test]
+    fn readonly_actor_gets_masked_account_tokens() {
+        let token = crate::models::TokenData::new(
+            "ya29.access-token-value".to_string(),
+            "1//refresh-token-value".to_string(),
+            3600,
+            None,
+            None,
+            None,
+        );
+        let account = Account::new("acc-1".to_string(), "a@example.com".to_string(), token);
+
+        let mut readonly = account.clone();
+        redact_account(Some(&WebActor("viewer".to_string(), WebApiRole::Readonly)), &mut readonly);
+        assert_eq!(readonly.token.refresh_token, "1//ref...alue");
+        assert_eq!(readonly.token.access_token, "ya29.a...alue");
+
+        // admin 令牌与未启用鉴权时保留原值
+        let mut admin = account.clone();
+        redact_account(Some(&WebActor("ops".to_string(), WebApiRole::Admin)), &mut admin);
+        assert_eq!(admin.token.refresh_token, "1//refresh-token-value");
+        let mut open = account.clone();
+        redact_account(None, &mut open);
+        assert_eq!(open.token.refresh_token, "1//refresh-token-value");
+    }
+
+    #[test]
+    fn openapi_document_is_valid() {
+        let json = ApiDoc::openapi().to_json().expect("serialize openapi");
+        let doc: openapiv3::OpenAPI =
+            serde_json::from_str(&json).expect("generated document must match the OpenAPI 3 schema");
+
+        assert!(doc.openapi.starts_with("3."));
+        for path in ["/api/accounts", "/api/accounts/{id}/switch", "/api/config", "/api/proxy/status", "/api/openapi.json"] {
+            assert!(doc.paths.paths.contains_key(path), "missing path {}", path);
+        }
+    }
+
+    #[test]
+    fn openapi_document_describes_error_envelope() {
+        let value = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+        let envelope = &value["components"]["schemas"]["EmptyResponse"]["properties"];
+        for field in ["success", "data", "error"] {
+            assert!(envelope.get(field).is_some(), "envelope missing {}", field);
+        }
+
+        // 业务错误走 200 + success=false，请求体解析失败走 400
+        let post = &value["paths"]["/api/accounts"]["post"]["responses"];
+        assert!(post.get("200").is_some());
+        assert_eq!(
+            post["400"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/EmptyResponse"
+        );
+    }
+
+    #[test]
+    fn loopback_host_detection() {
+        assert!(is_loopback_host("localhost:8765"));
+        assert!(is_loopback_host("127.0.0.1:8765"));
+        assert!(is_loopback_host("[::1]:8765"));
+        assert!(!is_loopback_host("192.168.1.10:8765"));
+        assert!(!is_loopback_host("tools.example.com"));
+        assert_eq!(strip_port("[fd00::1]:8765"), "[fd00::1]");
+    }
+
+    #[test]
+    fn public_origin_honors_only_trusted_forwarded_headers() {
+        let access = IpAccessList::new(&[], &["10.0.0.1".to_string()]);
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, HeaderValue::from_static("localhost:8765"));
+        headers.insert("x-forwarded-proto", HeaderValue::from_static("HTTPS"));
+        headers.insert("x-forwarded-host", HeaderValue::from_static("tools.example.com, inner.local"));
+        let resolve = |configured: Option<&str>, peer: &str| {
+            PublicOrigin::resolve(configured, &access, Some(peer.parse().unwrap()), &headers, "/antigravity").map(|o| o.url())
+        };
+
+        assert_eq!(resolve(None, "10.0.0.1").as_deref(), Some("https://tools.example.com/antigravity"));
+        // 不可信的对端只能使用 Host 头
+        assert_eq!(resolve(None, "203.0.113.9").as_deref(), Some("http://localhost:8765/antigravity"));
+        // 配置的地址优先，且自带子路径
+        assert_eq!(
+            resolve(Some("https://ag.example.com:8443/tools/"), "10.0.0.1").as_deref(),
+            Some("https://ag.example.com:8443/tools")
+        );
+        assert_eq!(PublicOrigin::resolve(None, &access, None, &HeaderMap::new(), ""), None);
+    }
+
+    #[test]
+    fn proxy_base_url_prefers_config_then_request_host() {
+        let origin = |host: &str| PublicOrigin {
+            scheme: "https".to_string(),
+            host: host.to_string(),
+            path: String::new(),
+        };
+        let mut config = ProxyConfig {
+            port: 8045,
+            allow_lan_access: true,
+            ..ProxyConfig::default()
+        };
+        assert_eq!(proxy_base_url(&config, Some(&origin("tools.example.com"))), "http://tools.example.com:8045");
+        assert_eq!(proxy_base_url(&config, Some(&origin("localhost:8765"))), "http://127.0.0.1:8045");
+        assert_eq!(proxy_base_url(&config, None), "http://127.0.0.1:8045");
+
+        config.public_url = Some("https://api.example.com/".to_string());
+        assert_eq!(proxy_base_url(&config, Some(&origin("tools.example.com"))), "https://api.example.com");
+
+        // 反代只监听本机时外部无法访问
+        config.public_url = None;
+        config.allow_lan_access = false;
+        assert_eq!(proxy_base_url(&config, Some(&origin("tools.example.com"))), "http://127.0.0.1:8045");
+    }
+
+    #[tokio::test]
+    async fn public_origin_follows_saved_config() {
+        let state = WebApiState::new();
+        let mut config = AppConfig::new();
+        config.public_url = Some("https://tools.example.com/ag".to_string());
+        apply_saved_config(&state, &config).await;
+        let origin = state.public_origin(None, &HeaderMap::new()).unwrap();
+        assert_eq!(origin.url(), "https://tools.example.com/ag");
+
+        config.public_url = None;
+        apply_saved_config(&state, &config).await;
+        assert!(state.public_origin(None, &HeaderMap::new()).is_none());
+    }
+
+    #[test]
+    fn etag_matching_handles_lists_and_wildcards() {
+        let etag = config_etag("0123456789abcdef");
+        assert_eq!(etag, "\"0123456789abcdef\"");
+
+        assert!(etag_matches(&HeaderValue::from_static("\"0123456789abcdef\""), &etag));
+        assert!(etag_matches(&HeaderValue::from_static("W/\"0123456789abcdef\""), &etag));
+        assert!(etag_matches(&HeaderValue::from_static("\"other\", \"0123456789abcdef\""), &etag));
+        assert!(etag_matches(&HeaderValue::from_static("*"), &etag));
+        assert!(!etag_matches(&HeaderValue::from_static("\"other\""), &etag));
+    }
 }
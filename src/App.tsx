@@ -14,6 +14,7 @@ import { useAccountStore } from './stores/useAccountStore';
 import { useTranslation } from 'react-i18next';
 import { listen } from '@tauri-apps/api/event';
 import { invoke } from '@tauri-apps/api/core';
+import { BASE_PATH } from './utils/request';
 
 const router = createBrowserRouter([
   {
@@ -42,7 +43,7 @@ const router = createBrowserRouter([
       },
     ],
   },
-]);
+], { basename: BASE_PATH || undefined });
 
 function App() {
   const { config, loadConfig } = useConfigStore();
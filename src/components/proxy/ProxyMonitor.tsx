@@ -4,11 +4,12 @@ import ModalDialog from '../common/ModalDialog';
 import { useTranslation } from 'react-i18next';
 import { request as invoke } from '../../utils/request';
 import { Trash2, Search, X } from 'lucide-react';
-import { AppConfig } from '../../types/config';
 import { formatCompactNumber } from '../../utils/format';
 import { useVirtualizer } from '@tanstack/react-virtual';
 
 interface ProxyRequestLog {
+    seq?: number;
+    kind?: 'generate' | 'count_tokens' | 'embeddings';
     id: string;
     timestamp: number;
     method: string;
@@ -22,13 +23,67 @@ interface ProxyRequestLog {
     response_body?: string;
     input_tokens?: number;
     output_tokens?: number;
+    vector_count?: number;
     account_email?: string;
+    api_key_name?: string;
+    client_ip?: string;
+    user_agent?: string;
+    client_disconnected?: boolean;
+    bytes_relayed?: number;
+    timeout_secs?: number;
+    phase_timings?: PhaseTimings;
+    upstream_endpoint?: string;
+    error_class?: 'region_blocked' | 'rate_limited' | 'auth' | 'safety_blocked' | 'invalid_request' | 'overloaded' | 'upstream';
+    replay_of?: string;
+    finish_reason?: string;
+    blocked?: boolean;
+    cost?: number | null;
+    currency?: string | null;
+    mirrored?: boolean;
+    prompt_rules?: string[];
+    pinned?: boolean;
+}
+
+interface PhaseTimings {
+    select_ms?: number | null;
+    auth_ms?: number | null;
+    upstream_ttfb_ms?: number | null;
+    stream_ms?: number | null;
+}
+
+interface TokenUsage {
+    input_tokens: number;
+    output_tokens: number;
+}
+
+interface UpstreamConnectionStats {
+    requests: number;
+    new_connections: number;
+    reused_connections: number;
+}
+
+interface MonitorStatus {
+    enabled: boolean;
+    buffer_size: number;
+    entries: number;
+    persisted: boolean;
 }
 
 interface ProxyStats {
     total_requests: number;
     success_count: number;
     error_count: number;
+    cancelled_count?: number;
+    blocked_count?: number;
+    avg_duration_ms?: number | null;
+    avg_ttfb_ms?: number | null;
+    requests_by_key?: Record<string, number>;
+    top_clients?: { ip: string; requests: number }[];
+    tokens_by_account?: Record<string, TokenUsage>;
+    tokens_by_model?: Record<string, TokenUsage>;
+    buffer_entries?: number;
+    buffer_bytes?: number;
+    upstream_connections?: UpstreamConnectionStats;
 }
 
 interface ProxyMonitorProps {
@@ -173,14 +228,13 @@ export const ProxyMonitor: React.FC<ProxyMonitorProps> = ({ className }) => {
                 setTimeout(() => reject(new Error('Request timeout')), 10000)
             );
 
-            const config = await Promise.race([
-                invoke<AppConfig>('load_config'),
+            const status = await Promise.race([
+                invoke<MonitorStatus>('get_proxy_monitor_status'),
                 timeoutPromise
-            ]) as AppConfig;
+            ]) as MonitorStatus;
 
-            if (config && config.proxy) {
-                setIsLoggingEnabled(config.proxy.enable_logging);
-                await invoke('set_proxy_monitor_enabled', { enabled: config.proxy.enable_logging });
+            if (status) {
+                setIsLoggingEnabled(status.enabled);
             }
 
             // Use paginated query
@@ -226,13 +280,9 @@ export const ProxyMonitor: React.FC<ProxyMonitorProps> = ({ className }) => {
     const toggleLogging = async () => {
         const newState = !isLoggingEnabled;
         try {
-            const config = await invoke<AppConfig>('load_config');
-            if (config && config.proxy) {
-                config.proxy.enable_logging = newState;
-                await invoke('save_config', { config });
-                await invoke('set_proxy_monitor_enabled', { enabled: newState });
-                setIsLoggingEnabled(newState);
-            }
+            // 后端写入配置，重启后保持
+            const status = await invoke<MonitorStatus>('set_proxy_monitor_enabled', { enabled: newState });
+            setIsLoggingEnabled(status.enabled);
         } catch (e) {
             console.error("Failed to toggle logging", e);
         }
@@ -291,6 +341,7 @@ export const ProxyMonitor: React.FC<ProxyMonitorProps> = ({ className }) => {
             log.url.toLowerCase().includes(filter.toLowerCase()) ||
             log.method.toLowerCase().includes(filter.toLowerCase()) ||
             (log.model && log.model.toLowerCase().includes(filter.toLowerCase())) ||
+            (log.client_ip && log.client_ip.includes(filter)) ||
             log.status.toString().includes(filter)
         )
         .sort((a, b) => b.timestamp - a.timestamp);
@@ -37,7 +37,28 @@ interface ProxyStatus {
     running: boolean;
     port: number;
     base_url: string;
-    active_accounts: number;
+    bind_address: string;
+    admin_address?: string;
+    total_accounts: number;
+    usable_accounts: number;
+    excluded: {
+        proxy_disabled: number;
+        cooldown: number;
+        forbidden: number;
+        corrupted?: number; // 账号文件无法读取或解析
+    };
+    warmed_accounts?: WarmupResult[]; // 开启预热时，启动响应返回前已完成的账号
+    warmup_pending?: boolean; // 预热仍在进行，进度通过 proxy://warmup 事件推送
+}
+
+interface WarmupResult {
+    account_id: string;
+    email: string;
+    success: boolean;
+    refreshed: boolean;
+    probe_status?: number | null;
+    latency_ms: number;
+    error?: string | null;
 }
 
 
@@ -139,7 +160,10 @@ export default function ApiProxy() {
         running: false,
         port: 0,
         base_url: '',
-        active_accounts: 0,
+        bind_address: '',
+        total_accounts: 0,
+        usable_accounts: 0,
+        excluded: { proxy_disabled: 0, cooldown: 0, forbidden: 0 },
     });
 
     const [appConfig, setAppConfig] = useState<AppConfig | null>(null);
@@ -596,7 +620,7 @@ export default function ApiProxy() {
                                     <div className={`w-2 h-2 rounded-full ${status.running ? 'bg-green-500 animate-pulse' : 'bg-gray-400'}`} />
                                     <span className={`text-xs font-medium ${status.running ? 'text-green-600' : 'text-gray-500'}`}>
                                         {status.running
-                                            ? `${t('proxy.status.running')} (${status.active_accounts} ${t('common.accounts') || 'Accounts'})`
+                                            ? `${t('proxy.status.running')} (${status.usable_accounts}/${status.total_accounts} ${t('common.accounts') || 'Accounts'})`
                                             : t('proxy.status.stopped')}
                                     </span>
                                 </div>
@@ -1,6 +1,6 @@
 import i18n from '../i18n';
 import { request as invoke } from '../utils/request';
-import { Account, QuotaData, DeviceProfile, DeviceProfileVersion } from '../types/account';
+import { Account, QuotaData, DeviceProfile, DeviceProfileVersion, ImportReport, OnDuplicate, DedupeReport, RepairReport, ProjectDiscovery, RotationStatus } from '../types/account';
 
 // 检查 Tauri 环境
 function ensureTauriEnvironment() {
@@ -35,6 +35,54 @@ export async function switchAccount(accountId: string): Promise<void> {
     return await invoke('switch_account', { accountId });
 }
 
+export async function rotateAccountNow(): Promise<RotationStatus> {
+    return await invoke('rotate_account_now');
+}
+
+export async function getRotationStatus(): Promise<RotationStatus> {
+    return await invoke('get_rotation_status');
+}
+
+export async function moveAccount(accountId: string, target: { position: number } | { before: string }): Promise<void> {
+    return await invoke('move_account', { accountId, ...target });
+}
+
+export async function setAccountPinned(accountId: string, pinned: boolean): Promise<Account> {
+    return await invoke('set_account_pinned', { accountId, pinned });
+}
+
+export async function setAccountEndpoint(accountId: string, endpoint: string | null): Promise<Account> {
+    return await invoke('set_account_endpoint', { accountId, endpoint });
+}
+
+export async function discoverAccountProject(accountId: string): Promise<ProjectDiscovery> {
+    return await invoke('discover_account_project', { accountId });
+}
+
+export async function setAccountProjectId(accountId: string, projectId: string | null): Promise<Account> {
+    return await invoke('set_account_project_id', { accountId, projectId });
+}
+
+export async function setAccountRegionBlocked(accountId: string, regionBlocked: boolean): Promise<Account> {
+    return await invoke('set_account_region_blocked', { accountId, regionBlocked });
+}
+
+export async function setAccountTags(accountId: string, tags: string[]): Promise<Account> {
+    return await invoke('set_account_tags', { accountId, tags });
+}
+
+export async function clearAccountForbidden(accountId: string): Promise<Account> {
+    return await invoke('clear_account_forbidden', { accountId });
+}
+
+export async function dedupeAccounts(dryRun: boolean): Promise<DedupeReport> {
+    return await invoke('dedupe_accounts', { dryRun });
+}
+
+export async function repairAccounts(): Promise<RepairReport> {
+    return await invoke('repair_accounts');
+}
+
 export async function fetchAccountQuota(accountId: string): Promise<QuotaData> {
     return await invoke('fetch_account_quota', { accountId });
 }
@@ -43,6 +91,7 @@ export interface RefreshStats {
     total: number;
     success: number;
     failed: number;
+    forbidden?: number;  // 本次被标记为 forbidden 的账号数
     details: string[];
 }
 
@@ -91,16 +140,21 @@ export async function cancelOAuthLogin(): Promise<void> {
 }
 
 // 导入
-export async function importV1Accounts(): Promise<Account[]> {
-    return await invoke('import_v1_accounts');
+export interface ImportOptions {
+    onDuplicate?: OnDuplicate;
+    dryRun?: boolean;
+}
+
+export async function importV1Accounts(options: ImportOptions = {}): Promise<ImportReport> {
+    return await invoke('import_v1_accounts', { ...options });
 }
 
-export async function importFromDb(): Promise<Account> {
-    return await invoke('import_from_db');
+export async function importFromDb(options: ImportOptions = {}): Promise<ImportReport> {
+    return await invoke('import_from_db', { ...options });
 }
 
-export async function importFromCustomDb(path: string): Promise<Account> {
-    return await invoke('import_custom_db', { path });
+export async function importFromCustomDb(path: string, options: ImportOptions = {}): Promise<ImportReport> {
+    return await invoke('import_custom_db', { path, ...options });
 }
 
 export async function syncAccountFromDb(): Promise<Account | null> {
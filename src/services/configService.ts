@@ -1,5 +1,5 @@
 import { request as invoke } from '../utils/request';
-import { AppConfig } from '../types/config';
+import { AppConfig, QuickstartReport, SetupStatus, SetupStep } from '../types/config';
 
 export async function loadConfig(): Promise<AppConfig> {
     return await invoke('load_config');
@@ -8,3 +8,15 @@ export async function loadConfig(): Promise<AppConfig> {
 export async function saveConfig(config: AppConfig): Promise<void> {
     return await invoke('save_config', { config });
 }
+
+export async function getSetupStatus(): Promise<SetupStatus> {
+    return await invoke('get_setup_status');
+}
+
+export async function completeSetupStep(step: SetupStep): Promise<SetupStatus> {
+    return await invoke('complete_setup_step', { step });
+}
+
+export async function setupQuickstart(startProxy = true): Promise<QuickstartReport> {
+    return await invoke('setup_quickstart', { startProxy });
+}
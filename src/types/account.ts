@@ -14,6 +14,83 @@ export interface Account {
     proxy_disabled_at?: number;
     created_at: number;
     last_used: number;
+    last_used_at?: number; // 最近一次被反代使用的时间
+    total_requests?: number; // 反代累计使用次数
+    pinned?: boolean; // 置顶账号始终排在前面
+    upstream_endpoint?: string; // 该账号使用的上游端点，覆盖默认端点
+    region_blocked?: boolean; // 上游返回地区不受支持，反代不再调度
+    region_blocked_at?: number;
+    tags?: string[]; // 账号标签，用于反代分组调度
+}
+
+/** 导入时遇到同邮箱账号的处理方式 */
+export type OnDuplicate = 'update' | 'skip';
+
+export interface ImportOutcome {
+    action: 'created' | 'updated' | 'skipped';
+    account: Account;
+}
+
+export interface ImportPlanEntry {
+    source: string;
+    email?: string;
+    action: 'create' | 'update' | 'skip' | 'error';
+    existing_account_id?: string;
+    error?: string;
+}
+
+export interface ImportReport {
+    dry_run: boolean;
+    plan: ImportPlanEntry[];
+    outcomes: ImportOutcome[]; // dry_run 时为空
+}
+
+export interface DuplicateGroup {
+    email: string;
+    kept: string;
+    removed: string[];
+}
+
+export interface DedupeReport {
+    dry_run: boolean;
+    groups: DuplicateGroup[];
+    removed: number;
+}
+
+export interface CorruptedAccount {
+    file: string;
+    error: string;
+}
+
+export interface RepairReport {
+    healthy: number;
+    repaired: { file: string; defaulted_fields: string[] }[];
+    quarantined: CorruptedAccount[]; // 已移入 accounts/.corrupt/
+    failed: CorruptedAccount[];
+}
+
+export interface ProjectCandidate {
+    project_id: string;
+    name?: string;
+    source: 'antigravity' | 'cloud';
+}
+
+export interface ProjectDiscovery {
+    account_id: string;
+    email: string;
+    project_id: string;  // 保存到账号的 project_id
+    previous?: string;
+    candidates: ProjectCandidate[];
+    errors: string[];  // 获取失败的来源及原因
+}
+
+export interface RotationStatus {
+    enabled: boolean;
+    interval_hours: number;
+    next_rotation_at?: number;  // Unix 秒
+    next_account_id?: string;
+    last_rotated_at?: number;
+    last_account_id?: string;
 }
 
 export interface TokenData {
@@ -23,13 +100,18 @@ export interface TokenData {
     expiry_timestamp: number;
     token_type: string;
     email?: string;
+    project_id?: string;
 }
 
 export interface QuotaData {
     models: ModelQuota[];
     last_updated: number;
     is_forbidden?: boolean;
+    forbidden_reason?: string;  // 被标记为 forbidden 的原因 (上游错误信息)
     subscription_tier?: string;  // 订阅类型: FREE/PRO/ULTRA
+    families?: Record<string, number>;  // 按模型族 (image/claude/flash/pro/other) 汇总的剩余百分比
+    resets_at?: number;  // 下次配额重置的 Unix 时间戳 (秒)
+    resets_in_seconds?: number;  // 距下次重置的秒数
 }
 
 export interface ModelQuota {
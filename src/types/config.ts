@@ -3,20 +3,89 @@ export interface UpstreamProxyConfig {
     url: string;
 }
 
+export interface UnauthorizedLockout {
+    ban_after: number; // 连续鉴权失败多少次后封禁，0 表示只记录
+    ban_minutes: number; // 封禁时长（分钟）
+}
+
 export interface ProxyConfig {
     enabled: boolean;
     allow_lan_access?: boolean;
     auth_mode?: 'off' | 'strict' | 'all_except_health' | 'auto';
     port: number;
     api_key: string;
+    api_keys?: ApiKeyEntry[]; // 具名密钥，按密钥统计用量
+    account_groups?: AccountGroup[]; // 账号分组，具名密钥绑定后只调度分组内的账号
+    allowed_ips?: string[]; // 允许访问反代的网段 (CIDR)，为空时不限制
+    trusted_proxies?: string[]; // 可信反向代理 (CIDR)
+    public_url?: string | null; // 反代 API 的外部访问地址，为空时根据管理页面的访问地址推断
+    unauthorized_lockout?: UnauthorizedLockout; // 鉴权失败封禁
     auto_start: boolean;
+    warmup?: boolean; // 启动后按调度顺序预先刷新账号 Token
+    warmup_accounts?: number; // 预热账号数，默认 3
+    warmup_probe?: boolean; // 预热时额外发送一次最小的生成请求 (消耗少量配额)
     custom_mapping?: Record<string, string>;
     request_timeout: number;
+    timeouts?: TimeoutOverrides; // 按路由覆盖的上游超时 (秒)
     enable_logging: boolean;
+    monitor_buffer_size?: number; // 内存中保留的最近请求条数，默认 1000
+    monitor_max_memory_mb?: number; // 请求记录的内存上限 (MB)，0 表示不限制
     upstream_proxy: UpstreamProxyConfig;
+    dns_overrides?: DnsOverride[]; // 自定义 DNS 解析 (同 curl --resolve)
+    upstream_endpoint?: string; // 默认上游端点 (主机名，须在白名单内)，为空时按 prod → daily 回退
+    upstream_pool_max_idle_per_host?: number; // 每主机最大空闲连接数，默认 16
+    upstream_pool_idle_timeout_secs?: number; // 空闲连接保留时间 (秒)，默认 90
+    tcp_keepalive_secs?: number; // TCP 保活间隔 (秒)，0 表示关闭，默认 60
     zai?: ZaiConfig;
     scheduling?: StickySessionConfig;
     experimental?: ExperimentalConfig;
+    prompt_rules?: PromptRule[]; // 系统提示词规则，按顺序执行
+    response_headers?: ResponseHeadersConfig; // 上游响应头透传与固定响应头注入
+}
+
+export interface ResponseHeadersConfig {
+    passthrough?: string[]; // 原样透传的上游响应头名
+    inject?: Record<string, string>; // 注入到每个反代响应的固定响应头，同名时优先
+}
+
+export interface PromptRule {
+    name?: string; // 规则名，记录在请求日志中
+    enabled?: boolean;
+    match?: {
+        model?: string; // 客户端请求的模型名，支持一个 * 通配符
+        api_key?: string; // 具名 API 密钥名
+    };
+    action: 'prepend_system' | 'append_system' | 'replace_system';
+    text: string;
+}
+
+export interface DnsOverride {
+    host: string;
+    ip: string;
+    port?: number;
+}
+
+export interface TimeoutOverrides {
+    chat?: number;
+    chat_stream?: number;
+    embeddings?: number;
+    count_tokens?: number;
+    models?: number;
+}
+
+export interface ApiKeyEntry {
+    name: string;
+    key: string;
+    enabled: boolean;
+    requests_per_minute?: number; // 未设置时不限制
+    concurrent_requests?: number; // 未设置时不限制
+    group?: string; // 绑定的账号分组，未设置时使用全部账号
+    admin?: boolean; // 管理员密钥，可使用调试类请求头
+}
+
+export interface AccountGroup {
+    name: string;
+    tags: string[]; // 带有任一标签的账号属于该分组
 }
 
 export type SchedulingMode = 'CacheFirst' | 'Balance' | 'PerformanceFirst';
@@ -64,6 +133,17 @@ export interface QuotaProtectionConfig {
 
 export interface ExperimentalConfig {
     enable_usage_scaling: boolean;
+    enable_endpoint_header?: boolean; // 允许通过 X-Antigravity-Endpoint 请求头选择上游端点
+    enable_safety_block_errors?: boolean; // 安全拦截时返回协议错误而非带标记的空回复
+    mirror?: MirrorConfig; // 按比例将请求镜像到另一个实例
+    allow_account_pinning?: boolean; // 允许管理员密钥通过 X-Antigravity-Account 请求头固定账号
+}
+
+export interface MirrorConfig {
+    url: string; // 镜像实例根地址，为空时关闭
+    sample_percent: number; // 抽样比例 (0-100)
+    include_streaming?: boolean; // 是否镜像流式请求
+    api_key?: string | null; // 发往镜像实例的 API 密钥
 }
 
 export interface AppConfig {
@@ -82,6 +162,88 @@ export interface AppConfig {
     accounts_page_size?: number; // 账号列表每页显示数量,默认 0 表示自动计算
     scheduled_warmup: ScheduledWarmupConfig;
     quota_protection: QuotaProtectionConfig; // [NEW] 配额保护配置
+    update_channel?: 'stable' | 'beta'; // [NEW] 更新通道
+    oauth?: OAuthConfig; // [NEW] Web 模式 OAuth 回调端口
+    auto_switch_on_exhaustion?: boolean; // [NEW] 当前账号配额耗尽时自动切换
+    web_auth?: WebAuthConfig; // [NEW] 管理 API 访问令牌
+    public_url?: string | null; // 管理页面的外部访问地址，经反向代理部署时用于生成绝对链接
+    db_sync_interval_minutes?: number; // 服务端定时与 IDE 数据库同步的间隔（分钟），0 表示关闭
+    sse_stats_interval_secs?: number; // SSE 推送 StatsTick 的间隔（秒），0 表示关闭
+    sse_max_subscribers?: number; // /api/events 同时连接数上限，0 表示不限制
+    sse_channel_capacity?: number; // SSE 广播通道容量，重启后生效
+    quota_reset_hour_utc?: number | null; // 上游未返回重置时间时假定的每日重置小时 (UTC, 0-23)
+    rotation?: RotationConfig; // 定时轮换当前账号
+    pricing?: Record<string, ModelPrice>; // 模型单价表，键为模型名或通配模式
+    quota_retry?: QuotaRetryPolicy; // 配额查询的重试与退避策略
+    log_language?: 'zh' | 'en'; // 日志与审计日志中错误信息的语言
     proxy: ProxyConfig;
 }
 
+export interface QuotaRetryPolicy {
+    max_attempts: number; // 最多尝试次数 (含首次)
+    initial_backoff_ms: number;
+    max_backoff_ms: number;
+    retry_on: Array<'timeout' | '5xx' | '429'>;
+}
+
+export interface ModelPrice {
+    input_per_1k: number; // 每 1000 输入 Token 的价格
+    output_per_1k: number; // 每 1000 输出 Token 的价格
+    currency?: string; // 货币代码，默认 USD
+}
+
+export interface RotationConfig {
+    enabled: boolean;
+    interval_hours: number; // 轮换间隔（小时）
+    candidates: string[]; // 按顺序轮换的账号 ID，为空时使用全部可用账号
+}
+
+export type WebApiRole = 'admin' | 'readonly';
+
+export interface WebApiToken {
+    name: string;
+    token: string;
+    role: WebApiRole;
+}
+
+export interface WebAuthConfig {
+    tokens: WebApiToken[]; // 为空时管理 API 不鉴权
+    allowed_ips?: string[]; // CIDR 白名单，为空时不限制
+    trusted_proxies?: string[]; // 可信反向代理 (CIDR)
+    rate_limits?: WebRateLimits; // 高开销接口限流
+}
+
+export interface WebRateLimits {
+    enabled: boolean;
+    quota_refresh_per_minute: number; // 每分钟请求数，0 表示不限制
+    token_validation_per_minute: number;
+    oauth_per_minute: number;
+    import_per_minute: number;
+}
+
+export interface OAuthConfig {
+    redirect_port: number; // 默认 9004
+    fallback_ports: number[]; // 默认端口被占用时依次尝试
+}
+
+
+export type SetupStep = 'data_dir' | 'account' | 'api_key' | 'proxy_config';
+
+export interface SetupStatus {
+    data_dir: boolean;
+    account: boolean;
+    api_key: boolean;
+    proxy_config: boolean;
+    complete: boolean;
+    next_step: SetupStep | null; // 按建议顺序排在最前的未完成步骤
+    account_count: number;
+    proxy_running: boolean;
+}
+
+export interface QuickstartReport {
+    status: SetupStatus;
+    api_key: string;
+    proxy_started: boolean;
+    proxy_error: string | null;
+    base_url: string | null; // 反代运行时客户端使用的地址
+}
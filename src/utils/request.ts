@@ -5,11 +5,40 @@
  * - Web 模式：使用 HTTP API
  */
 
+import i18n from '../i18n';
+
 // 运行时环境检测
 export const isTauri = typeof window !== 'undefined' && '__TAURI__' in window;
 
+// 子路径部署时由服务端注入 index.html 的挂载前缀 (如 /antigravity)
+export const BASE_PATH: string =
+  (typeof window !== 'undefined' && (window as any).__ANTIGRAVITY_BASE_PATH__) || '';
+
 // Web 模式下的 API 基础路径
-const API_BASE = import.meta.env.VITE_API_BASE || '';
+export const API_BASE = import.meta.env.VITE_API_BASE || BASE_PATH;
+
+// 管理 API 令牌：可通过页面地址 ?token= 传入 (如只读看板)，之后保存在 localStorage
+const WEB_API_TOKEN_KEY = 'web_api_token';
+
+export function getWebApiToken(): string | null {
+  if (typeof window === 'undefined') return null;
+  const fromUrl = new URLSearchParams(window.location.search).get('token');
+  if (fromUrl) {
+    localStorage.setItem(WEB_API_TOKEN_KEY, fromUrl);
+    return fromUrl;
+  }
+  return localStorage.getItem(WEB_API_TOKEN_KEY);
+}
+
+function authHeaders(): Record<string, string> {
+  const token = getWebApiToken();
+  return token ? { Authorization: `Bearer ${token}` } : {};
+}
+
+// 服务端错误信息按界面语言返回 (目前支持中文与英文，其他语言使用配置的默认语言)
+function languageHeaders(): Record<string, string> {
+  return i18n.language ? { 'Accept-Language': i18n.language } : {};
+}
 
 // 命令名称到 HTTP 端点的映射
 // unwrapKey: 可选，指定从 args 中提取哪个键作为请求体（解包 Tauri 调用参数）
@@ -26,31 +55,93 @@ const COMMAND_ENDPOINTS: Record<string, EndpointConfig> = {
   get_current_account: { method: 'GET', path: '/api/accounts/current' },
   delete_account: { method: 'DELETE', path: (args) => `/api/accounts/${args.account_id || args.id}` },
   delete_accounts: { method: 'POST', path: '/api/accounts/batch-delete' },
+  rotate_account_now: { method: 'POST', path: '/api/accounts/rotate-now' },
+  get_rotation_status: { method: 'GET', path: '/api/accounts/rotation' },
   switch_account: { method: 'POST', path: (args) => `/api/accounts/${args.account_id || args.id}/switch` },
   fetch_account_quota: { method: 'POST', path: (args) => `/api/accounts/${args.account_id || args.id}/quota` },
   refresh_all_quotas: { method: 'POST', path: '/api/accounts/refresh-all' },
+  validate_all_tokens: { method: 'POST', path: '/api/accounts/validate-all' },
+  validate_account_token: { method: 'POST', path: (args) => `/api/accounts/${args.account_id || args.id}/validate` },
+  test_account: { method: 'POST', path: (args) => `/api/accounts/${args.account_id || args.id}/test` },
+  test_all_accounts: { method: 'POST', path: '/api/accounts/test-all' },
+  get_account_test_results: { method: 'GET', path: '/api/accounts/test-results' },
+  update_account_token: { method: 'PUT', path: (args) => `/api/accounts/${args.account_id || args.id}/token` },
   reorder_accounts: { method: 'POST', path: '/api/accounts/reorder' },
+  move_account: { method: 'POST', path: (args) => `/api/accounts/${args.accountId || args.account_id}/move` },
+  set_account_pinned: { method: 'POST', path: (args) => `/api/accounts/${args.accountId || args.account_id}/pin` },
+  set_account_endpoint: { method: 'PUT', path: (args) => `/api/accounts/${args.accountId || args.account_id}/endpoint` },
+  discover_account_project: { method: 'POST', path: (args) => `/api/accounts/${args.accountId || args.account_id}/discover-project` },
+  set_account_project_id: { method: 'PATCH', path: (args) => `/api/accounts/${args.accountId || args.account_id}` },
+  set_account_region_blocked: { method: 'PATCH', path: (args) => `/api/accounts/${args.accountId || args.account_id}` },
+  set_account_tags: { method: 'PATCH', path: (args) => `/api/accounts/${args.accountId || args.account_id}` },
+  clear_account_forbidden: { method: 'POST', path: (args) => `/api/accounts/${args.accountId || args.account_id}/clear-forbidden` },
+  dedupe_accounts: { method: 'POST', path: '/api/accounts/dedupe' },
+  repair_accounts: { method: 'POST', path: '/api/accounts/repair' },
   toggle_proxy_status: { method: 'POST', path: (args) => `/api/accounts/${args.account_id || args.id}/proxy-status` },
+  get_account_requests: { method: 'GET', path: (args) => `/api/accounts/${args.accountId || args.account_id}/requests?limit=${args?.limit ?? 100}` },
 
   // 配置
   load_config: { method: 'GET', path: '/api/config' },
   save_config: { method: 'PUT', path: '/api/config', unwrapKey: 'config' },
+  validate_config: { method: 'POST', path: '/api/config/validate', unwrapKey: 'config' },
 
   // 反代服务
   start_proxy_service: { method: 'POST', path: '/api/proxy/start', unwrapKey: 'config' },
   stop_proxy_service: { method: 'POST', path: '/api/proxy/stop' },
   get_proxy_status: { method: 'GET', path: '/api/proxy/status' },
   get_proxy_stats: { method: 'GET', path: '/api/proxy/stats' },
-  get_proxy_logs: { method: 'GET', path: '/api/proxy/logs' },
-  clear_proxy_logs: { method: 'DELETE', path: '/api/proxy/logs' },
+  get_proxy_daily_stats: { method: 'GET', path: (args) => `/api/proxy/stats/daily?days=${args?.days ?? 30}` },
+  get_usage_heatmap: { method: 'GET', path: (args) => `/api/proxy/usage/heatmap?days=${args?.days ?? 7}` },
+  get_usage_cost: { method: 'GET', path: (args) => `/api/proxy/usage/cost?group_by=${args?.groupBy ?? 'model'}&window=${encodeURIComponent(args?.window ?? '30d')}` },
+  get_proxy_logs: {
+    method: 'GET',
+    path: (args) => {
+      const params = new URLSearchParams();
+      if (args?.limit) params.set('limit', String(args.limit));
+      if (args?.clientIp) params.set('client_ip', args.clientIp);
+      if (args?.blocked !== undefined) params.set('blocked', String(args.blocked));
+      const query = params.toString();
+      return `/api/proxy/logs${query ? `?${query}` : ''}`;
+    },
+  },
+  get_proxy_logs_after: { method: 'GET', path: (args) => `/api/proxy/logs?after_seq=${args?.afterSeq ?? 0}${args?.limit ? `&limit=${args.limit}` : ''}` },
+  clear_proxy_logs: {
+    method: 'DELETE',
+    path: (args) => {
+      const params = new URLSearchParams();
+      if (args?.before !== undefined) params.set('before', String(args.before));
+      if (args?.statusClass) params.set('status_class', args.statusClass);
+      if (args?.model) params.set('model', args.model);
+      if (args?.history) params.set('history', 'true');
+      const query = params.toString();
+      return `/api/proxy/logs${query ? `?${query}` : ''}`;
+    },
+  },
+  replay_proxy_log: {
+    method: 'POST',
+    path: (args) => `/api/proxy/logs/${encodeURIComponent(args?.logId)}/replay${args?.sameAccount ? '?same_account=true' : ''}`,
+  },
+  get_proxy_monitor_status: { method: 'GET', path: '/api/proxy/monitor' },
   set_proxy_monitor_enabled: { method: 'POST', path: '/api/proxy/monitor' },
   reload_proxy_accounts: { method: 'POST', path: '/api/proxy/reload-accounts' },
   update_model_mapping: { method: 'PUT', path: '/api/proxy/model-mapping', unwrapKey: 'config' },
+  get_proxy_security: { method: 'GET', path: '/api/proxy/security' },
+  get_proxy_unauthorized: { method: 'GET', path: (args) => `/api/proxy/security/unauthorized${args?.limit ? `?limit=${args.limit}` : ''}` },
+  clear_proxy_bans: { method: 'DELETE', path: (args) => `/api/proxy/security/unauthorized${args?.ip ? `?ip=${encodeURIComponent(args.ip)}` : ''}` },
+  get_proxy_experimental: { method: 'GET', path: '/api/proxy/experimental' },
+  update_proxy_experimental: { method: 'PUT', path: '/api/proxy/experimental', unwrapKey: 'flags' },
   get_proxy_scheduling_config: { method: 'GET', path: '/api/proxy/scheduling' },
   update_proxy_scheduling_config: { method: 'PUT', path: '/api/proxy/scheduling', unwrapKey: 'config' },
   clear_proxy_session_bindings: { method: 'DELETE', path: '/api/proxy/sessions' },
+  debug_token_manager: { method: 'GET', path: '/api/proxy/debug/token-manager' },
   fetch_zai_models: { method: 'POST', path: '/api/proxy/zai-models' },
+  test_upstream: { method: 'POST', path: '/api/proxy/test-upstream' },
   generate_api_key: { method: 'POST', path: '/api/proxy/generate-api-key' },
+  list_api_keys: { method: 'GET', path: '/api/proxy/api-keys' },
+  upsert_api_key: { method: 'POST', path: '/api/proxy/api-keys' },
+  delete_api_key: { method: 'DELETE', path: (args) => `/api/proxy/api-keys/${encodeURIComponent(args.name)}` },
+  get_audit_log: { method: 'GET', path: (args) => `/api/system/audit?limit=${args?.limit ?? 100}${args?.since ? `&since=${args.since}` : ''}` },
+  get_api_key_usage: { method: 'GET', path: (args) => `/api/proxy/api-keys/${encodeURIComponent(args.name)}/usage` },
 
   // OAuth
   prepare_oauth_url: { method: 'POST', path: '/api/oauth/prepare-url' },
@@ -65,11 +156,21 @@ const COMMAND_ENDPOINTS: Record<string, EndpointConfig> = {
   import_from_db: { method: 'POST', path: '/api/import/db' },
   import_custom_db: { method: 'POST', path: '/api/import/custom-db' },
   sync_account_from_db: { method: 'POST', path: '/api/sync/db' },
+  get_db_sync_status: { method: 'GET', path: '/api/sync/status' },
 
   // 系统
   get_data_dir_path: { method: 'GET', path: '/api/system/data-dir' },
+  list_profiles: { method: 'GET', path: '/api/system/profiles' },
   check_for_updates: { method: 'GET', path: '/api/system/check-updates' },
+  self_update: { method: 'POST', path: '/api/system/update' },
   clear_log_cache: { method: 'POST', path: '/api/system/clear-logs' },
+  get_dashboard: { method: 'GET', path: '/api/dashboard' },
+  get_system_info: { method: 'GET', path: '/api/system/info' },
+
+  // 首次运行引导 (仅 Web 模式)
+  get_setup_status: { method: 'GET', path: '/api/setup/status' },
+  complete_setup_step: { method: 'POST', path: '/api/setup/complete-step' },
+  setup_quickstart: { method: 'POST', path: '/api/setup/quickstart' },
 };
 
 // camelCase 转 snake_case
@@ -91,6 +192,9 @@ function convertKeysToSnakeCase(obj: any): any {
   return converted;
 }
 
+// 最近一次读取到的配置版本号 (ETag)，保存配置时通过 If-Match 提交以防止多个标签页互相覆盖
+let configEtag: string | null = null;
+
 // Web 模式下的 HTTP 请求实现
 async function httpRequest<T>(cmd: string, args?: any): Promise<T> {
   const endpoint = COMMAND_ENDPOINTS[cmd];
@@ -99,7 +203,7 @@ async function httpRequest<T>(cmd: string, args?: any): Promise<T> {
     console.warn(`[Web API] Unknown command: ${cmd}, trying generic POST`);
     const response = await fetch(`${API_BASE}/api/${cmd.replace(/_/g, '-')}`, {
       method: 'POST',
-      headers: { 'Content-Type': 'application/json' },
+      headers: { 'Content-Type': 'application/json', ...languageHeaders(), ...authHeaders() },
       body: args ? JSON.stringify(convertKeysToSnakeCase(args)) : undefined,
     });
     const data = await response.json();
@@ -112,9 +216,14 @@ async function httpRequest<T>(cmd: string, args?: any): Promise<T> {
     : endpoint.path;
 
   const url = `${API_BASE}${path}`;
+  const headers: Record<string, string> = { 'Content-Type': 'application/json', ...languageHeaders(), ...authHeaders() };
+  const isConfig = path === '/api/config';
+  if (isConfig && endpoint.method === 'PUT') {
+    headers['If-Match'] = configEtag ?? '*';
+  }
   const options: RequestInit = {
     method: endpoint.method,
-    headers: { 'Content-Type': 'application/json' },
+    headers,
   };
 
   // GET/DELETE 请求不发送 body（路径参数已在 path 中）
@@ -136,11 +245,21 @@ async function httpRequest<T>(cmd: string, args?: any): Promise<T> {
   }
 
   const response = await fetch(url, options);
+  if (isConfig) {
+    configEtag = response.headers.get('ETag') ?? configEtag;
+  }
   const data = await response.json();
 
+  if (response.status === 412) {
+    throw new Error(data.error || '配置已被其他页面修改，请刷新后重试');
+  }
+
   if (!data.success) {
     throw new Error(data.error || `HTTP ${response.status}`);
   }
+  if (Array.isArray(data.warnings)) {
+    data.warnings.forEach((warning: string) => console.warn(`[Web API] ${path}: ${warning}`));
+  }
 
   return data.data;
 }
@@ -4,7 +4,7 @@
  * 提供 Web 模式下 Tauri 特有 API 的替代实现
  */
 
-import { isTauri } from './request';
+import { isTauri, API_BASE, getWebApiToken } from './request';
 
 // ============================================================================
 // 事件系统兼容 - 使用 SSE 替代 Tauri emit/listen
@@ -20,8 +20,10 @@ const eventListeners: Map<string, Set<(payload: any) => void>> = new Map();
 function initSSE() {
   if (eventSource || isTauri) return;
 
-  const API_BASE = import.meta.env.VITE_API_BASE || '';
-  eventSource = new EventSource(`${API_BASE}/api/events`);
+  // EventSource 无法设置请求头，令牌通过查询参数传递
+  const token = getWebApiToken();
+  const query = token ? `?access_token=${encodeURIComponent(token)}` : '';
+  eventSource = new EventSource(`${API_BASE}/api/events${query}`);
 
   eventSource.onmessage = (event) => {
     try {
@@ -39,7 +41,24 @@ function initSSE() {
       } else if (eventType === 'ConfigUpdated') {
         eventListeners.get('config://updated')?.forEach(h => h(null));
       } else if (eventType === 'AccountSwitched') {
-        eventListeners.get('tray://account-switched')?.forEach(h => h(null));
+        // 与桌面端一致，payload 为新账号 ID
+        eventListeners.get('tray://account-switched')?.forEach(h => h(payload?.account_id ?? null));
+      } else if (eventType === 'OAuthCompleted') {
+        eventListeners.get('oauth://completed')?.forEach(h => h(payload));
+      } else if (eventType === 'TokenValidation') {
+        eventListeners.get('accounts://token-validation')?.forEach(h => h(payload));
+      } else if (eventType === 'AccountTest') {
+        eventListeners.get('accounts://test-progress')?.forEach(h => h(payload));
+      } else if (eventType === 'StatsTick') {
+        eventListeners.get('proxy://stats-tick')?.forEach(h => h(payload));
+      } else if (eventType === 'SecurityUpdated') {
+        eventListeners.get('proxy://security-updated')?.forEach(h => h(payload));
+      } else if (eventType === 'Warmup') {
+        eventListeners.get('proxy://warmup')?.forEach(h => h(payload));
+      } else if (eventType === 'DbSynced') {
+        eventListeners.get('accounts://db-synced')?.forEach(h => h(payload));
+      } else if (eventType === 'AccountsReloaded') {
+        eventListeners.get('accounts://reloaded')?.forEach(h => h(payload));
       }
     } catch (e) {
       console.error('[SSE] Parse error:', e);